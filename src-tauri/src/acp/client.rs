@@ -0,0 +1,212 @@
+//! Request/response layer over [`AsyncCodec`] that enforces per-method
+//! timeouts, so an unresponsive agent produces an error instead of a hang.
+
+use super::codec::{AsyncCodec, CodecError, Incoming};
+use super::methods::{self, MethodCall};
+use super::notifications::AGENT_NOTIFICATIONS;
+use super::protocol::{JsonRpcMessage, JsonRpcNotification, JsonRpcResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Settings key for the policy of methods without their own entry
+pub const DEFAULT_POLICY_KEY: &str = "*";
+/// Timeout of methods without a built-in or configured policy
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// How long to wait for a method's response and how often to resend it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestPolicy {
+    /// None waits forever
+    pub timeout_secs: Option<u64>,
+    /// Times a timed-out request is sent again
+    #[serde(default)]
+    pub retries: u32,
+}
+
+impl RequestPolicy {
+    pub const fn timeout(secs: u64) -> Self {
+        Self {
+            timeout_secs: Some(secs),
+            retries: 0,
+        }
+    }
+}
+
+/// Request policies by method, with built-in defaults for the methods that
+/// have them
+#[derive(Debug, Clone, Default)]
+pub struct RequestPolicies {
+    overrides: HashMap<String, RequestPolicy>,
+}
+
+impl RequestPolicies {
+    /// Policies with `overrides` (method or `*` -> policy) applied on top of
+    /// the defaults
+    pub fn new(overrides: HashMap<String, RequestPolicy>) -> Self {
+        Self { overrides }
+    }
+
+    pub fn for_method(&self, method: &str) -> RequestPolicy {
+        if let Some(policy) = self.overrides.get(method) {
+            return *policy;
+        }
+        match method {
+            methods::INITIALIZE => RequestPolicy::timeout(60),
+            methods::SESSION_NEW | methods::SESSION_LOAD => RequestPolicy::timeout(120),
+            // Prompts run as long as the agent works, including while it
+            // waits for permission answers
+            methods::SESSION_PROMPT => RequestPolicy {
+                timeout_secs: None,
+                retries: 0,
+            },
+            _ => self
+                .overrides
+                .get(DEFAULT_POLICY_KEY)
+                .copied()
+                .unwrap_or(RequestPolicy::timeout(DEFAULT_TIMEOUT_SECS)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("{method} timed out after {secs}s")]
+    Timeout { method: String, secs: u64 },
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+}
+
+/// A request that was sent and is waiting for its response
+#[derive(Debug, Clone)]
+pub struct PendingCall {
+    pub id: i64,
+    pub method: String,
+    deadline: Option<Instant>,
+    timeout_secs: u64,
+}
+
+pub struct ProtocolClient {
+    codec: AsyncCodec,
+    next_id: i64,
+    policies: RequestPolicies,
+}
+
+impl ProtocolClient {
+    pub fn new(codec: AsyncCodec) -> Self {
+        Self {
+            codec,
+            next_id: 1,
+            policies: RequestPolicies::default(),
+        }
+    }
+
+    pub fn set_policies(&mut self, policies: RequestPolicies) {
+        self.policies = policies;
+    }
+
+    /// Send a request; read its response with [`next_for`](Self::next_for)
+    pub async fn send_request(&mut self, call: MethodCall) -> Result<PendingCall, ProtocolError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let method = call.method;
+        let policy = self.policies.for_method(method);
+        self.codec.send_request(&call.into_request(id)).await?;
+        Ok(PendingCall {
+            id,
+            method: method.to_string(),
+            deadline: policy
+                .timeout_secs
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+            timeout_secs: policy.timeout_secs.unwrap_or_default(),
+        })
+    }
+
+    /// Next message for a pending call: its response, or an agent message
+    /// read while waiting. Fails with [`ProtocolError::Timeout`] once the
+    /// call's deadline has passed.
+    pub async fn next_for(&mut self, call: &PendingCall) -> Result<Incoming, ProtocolError> {
+        let Some(deadline) = call.deadline else {
+            return Ok(self.codec.next_for(call.id).await?);
+        };
+
+        match tokio::time::timeout_at(deadline, self.codec.next_for(call.id)).await {
+            Ok(incoming) => Ok(incoming?),
+            Err(_) => {
+                // A late answer would otherwise be kept forever
+                self.codec.forget_request(call.id);
+                Err(ProtocolError::Timeout {
+                    method: call.method.clone(),
+                    secs: call.timeout_secs,
+                })
+            }
+        }
+    }
+
+    /// Send a request and wait for its response, resending it after a
+    /// timeout as often as the method's policy allows. Notifications read
+    /// meanwhile are dispatched; other messages are skipped.
+    pub async fn request(&mut self, call: MethodCall) -> Result<JsonRpcResponse, ProtocolError> {
+        let method = call.method;
+        let retries = self.policies.for_method(method).retries;
+        let mut attempt = 0;
+        loop {
+            let pending = self.send_request(call.clone()).await?;
+            let result = loop {
+                match self.next_for(&pending).await {
+                    Ok(Incoming::Response(resp)) => break Ok(resp),
+                    Ok(Incoming::Message(JsonRpcMessage::Notification(notification))) => {
+                        AGENT_NOTIFICATIONS.dispatch(self.codec.agent_id(), &notification);
+                    }
+                    Ok(Incoming::Message(msg)) => {
+                        tracing::debug!("Ignoring message while waiting for {}: {:?}", method, msg)
+                    }
+                    Err(e) => break Err(e),
+                }
+            };
+
+            match result {
+                Err(ProtocolError::Timeout { .. }) if attempt < retries => {
+                    attempt += 1;
+                    tracing::warn!("{} timed out, retrying ({}/{})", method, attempt, retries);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub async fn notify(&mut self, notification: &JsonRpcNotification) -> Result<(), ProtocolError> {
+        let json = serde_json::to_string(notification).map_err(CodecError::from)?;
+        self.send_raw(&json).await
+    }
+
+    /// Send a notification or a response to an agent request
+    pub async fn send_raw(&mut self, message: &str) -> Result<(), ProtocolError> {
+        Ok(self.codec.write_message(message).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_defaults_and_overrides() {
+        let defaults = RequestPolicies::default();
+        assert_eq!(defaults.for_method(methods::INITIALIZE).timeout_secs, Some(60));
+        assert_eq!(defaults.for_method(methods::SESSION_NEW).timeout_secs, Some(120));
+        assert_eq!(defaults.for_method(methods::SESSION_PROMPT).timeout_secs, None);
+        assert_eq!(defaults.for_method(methods::AUTHENTICATE).timeout_secs, Some(DEFAULT_TIMEOUT_SECS));
+
+        let policies = RequestPolicies::new(HashMap::from([
+            (methods::INITIALIZE.to_string(), RequestPolicy { timeout_secs: Some(10), retries: 2 }),
+            (DEFAULT_POLICY_KEY.to_string(), RequestPolicy::timeout(30)),
+        ]));
+        assert_eq!(policies.for_method(methods::INITIALIZE).retries, 2);
+        assert_eq!(policies.for_method(methods::AUTHENTICATE).timeout_secs, Some(30));
+        // Built-in defaults win over the catch-all
+        assert_eq!(policies.for_method(methods::SESSION_NEW).timeout_secs, Some(120));
+    }
+}
@@ -1,45 +1,217 @@
-use super::protocol::JsonRpcMessage;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as TokioBufReader};
-use tokio::process::{ChildStdin, ChildStdout};
+use super::protocol::{JsonRpcMessage, JsonRpcRequest, JsonRpcResponse};
+use super::violations::PROTOCOL_VIOLATIONS;
+use crate::diagnostics::{TraceDirection, PROTOCOL_TRACE};
+use std::collections::HashMap;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader as TokioBufReader};
+use uuid::Uuid;
+
+/// Requests sent to the agent that have not been answered yet, so responses
+/// can be matched by id whatever order they arrive in
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    /// id -> method
+    methods: HashMap<i64, String>,
+    /// Responses that arrived while another request was being waited on
+    arrived: HashMap<i64, JsonRpcResponse>,
+}
+
+impl PendingRequests {
+    pub fn insert(&mut self, id: i64, method: &str) {
+        self.methods.insert(id, method.to_string());
+    }
+
+    /// A response to `id` that already arrived
+    pub fn take(&mut self, id: i64) -> Option<JsonRpcResponse> {
+        let response = self.arrived.remove(&id)?;
+        self.methods.remove(&id);
+        Some(response)
+    }
+
+    /// Route a response read while waiting for `waiting_for`. Returns it if
+    /// it answers that request; otherwise keeps it for its own waiter, or
+    /// drops it if it answers nothing pending.
+    pub fn route(&mut self, waiting_for: i64, response: JsonRpcResponse) -> Option<JsonRpcResponse> {
+        // Errors for requests the agent could not parse may carry a null id;
+        // attribute them to the only outstanding request
+        let id = match response.id {
+            Some(id) => id,
+            None if self.methods.len() == 1 && self.methods.contains_key(&waiting_for) => waiting_for,
+            None => {
+                tracing::warn!("Dropping response without id: {:?}", response.error);
+                return None;
+            }
+        };
+
+        if id == waiting_for {
+            self.methods.remove(&id);
+            return Some(response);
+        }
+        match self.methods.get(&id) {
+            Some(method) => {
+                tracing::debug!("Response to {} ({}) arrived out of order", method, id);
+                self.arrived.insert(id, response);
+            }
+            None => tracing::warn!("Dropping response to unknown request {}", id),
+        }
+        None
+    }
+
+    /// Stop waiting for a request; a late response to it will be dropped
+    pub fn forget(&mut self, id: i64) {
+        self.methods.remove(&id);
+        self.arrived.remove(&id);
+    }
+}
+
+/// What was read while waiting for a response
+#[derive(Debug)]
+pub enum Incoming {
+    /// The awaited response
+    Response(JsonRpcResponse),
+    /// A notification or request from the agent, for the caller to handle
+    Message(JsonRpcMessage),
+}
+
+/// Where agent messages are read from: a child's stdout or an in-process pipe
+type AgentReader = Box<dyn AsyncRead + Send + Unpin>;
+/// Where messages to the agent are written: a child's stdin or an in-process pipe
+type AgentWriter = Box<dyn AsyncWrite + Send + Unpin>;
 
 pub struct AsyncCodec {
-    reader: TokioBufReader<ChildStdout>,
-    writer: ChildStdin,
+    reader: TokioBufReader<AgentReader>,
+    writer: AgentWriter,
+    agent_id: Option<Uuid>,
+    /// Names the agent in protocol traces
+    trace_label: String,
+    /// Bytes of the line being read
+    line: Vec<u8>,
+    pending: PendingRequests,
+    closed: bool,
+    /// Skip lines that are not JSON-RPC without reporting them until the
+    /// agent's first message, see [`with_startup_grace`](Self::with_startup_grace)
+    startup_grace: bool,
 }
 
 impl AsyncCodec {
-    pub fn new(stdout: ChildStdout, stdin: ChildStdin) -> Self {
+    pub fn new(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
         Self {
-            reader: TokioBufReader::new(stdout),
-            writer: stdin,
+            reader: TokioBufReader::new(Box::new(reader) as AgentReader),
+            writer: Box::new(writer),
+            agent_id: None,
+            trace_label: String::new(),
+            line: Vec::new(),
+            pending: PendingRequests::default(),
+            closed: false,
+            startup_grace: false,
         }
     }
 
+    /// Codec over one bidirectional stream, such as an in-process duplex pipe
+    pub fn from_stream(stream: impl AsyncRead + AsyncWrite + Send + 'static) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self::new(reader, writer)
+    }
+
+    /// Attribute traces and protocol violations to an agent
+    pub fn for_agent(mut self, id: Uuid, name: &str) -> Self {
+        self.agent_id = Some(id);
+        self.trace_label = format!("{} ({})", name, id);
+        self
+    }
+
+    /// Tolerate output before the first message, such as the install
+    /// progress npx prints before the agent it runs starts
+    pub fn with_startup_grace(mut self) -> Self {
+        self.startup_grace = true;
+        self
+    }
+
+    pub fn agent_id(&self) -> Option<Uuid> {
+        self.agent_id
+    }
+
+    /// Read the next line. Cancel safe: a partially read line is kept and
+    /// completed by the next call.
     pub async fn read_message(&mut self) -> Result<Option<JsonRpcMessage>, CodecError> {
-        let mut line = String::new();
         let bytes_read = self
             .reader
-            .read_line(&mut line)
+            .read_until(b'\n', &mut self.line)
             .await
             .map_err(CodecError::Io)?;
 
-        if bytes_read == 0 {
+        if bytes_read == 0 && self.line.is_empty() {
+            self.closed = true;
             return Ok(None);
         }
 
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
         let trimmed = line.trim();
         if trimmed.is_empty() {
             return Ok(None);
         }
 
-        // Debug: log raw message
-        println!("[CODEC] RAW message: {}", trimmed);
+        tracing::trace!("RAW message: {}", trimmed);
+        PROTOCOL_TRACE.record(&self.trace_label, TraceDirection::Incoming, trimmed);
 
-        let message = serde_json::from_str(trimmed).map_err(CodecError::Json)?;
-        Ok(Some(message))
+        // A malformed line is reported and skipped like an empty one rather
+        // than failing whatever request is waiting on this agent
+        match serde_json::from_str(trimmed) {
+            Ok(message) => {
+                self.startup_grace = false;
+                Ok(Some(message))
+            }
+            Err(_) if self.startup_grace => {
+                tracing::info!("Startup output of {}: {}", self.trace_label, trimmed);
+                Ok(None)
+            }
+            Err(e) => {
+                tracing::warn!("Skipping malformed message from {}: {}", self.trace_label, e);
+                PROTOCOL_VIOLATIONS.record(self.agent_id, trimmed, &e.to_string());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Write a request and track it until its response is read with
+    /// [`next_for`](Self::next_for)
+    pub async fn send_request(&mut self, request: &JsonRpcRequest) -> Result<(), CodecError> {
+        let json = serde_json::to_string(request)?;
+        self.pending.insert(request.id, &request.method);
+        self.write_message(&json).await
+    }
+
+    /// Read until the response to request `id` arrives, handing back agent
+    /// notifications and requests read along the way. Responses to other
+    /// pending requests are kept until they are waited for.
+    pub async fn next_for(&mut self, id: i64) -> Result<Incoming, CodecError> {
+        if let Some(response) = self.pending.take(id) {
+            return Ok(Incoming::Response(response));
+        }
+
+        loop {
+            match self.read_message().await? {
+                Some(JsonRpcMessage::Response(response)) => {
+                    if let Some(response) = self.pending.route(id, response) {
+                        return Ok(Incoming::Response(response));
+                    }
+                }
+                Some(message) => return Ok(Incoming::Message(message)),
+                None if self.closed => return Err(CodecError::Closed),
+                None => {}
+            }
+        }
+    }
+
+    /// Stop tracking a request that is no longer waited for
+    pub fn forget_request(&mut self, id: i64) {
+        self.pending.forget(id);
     }
 
     pub async fn write_message(&mut self, message: &str) -> Result<(), CodecError> {
+        PROTOCOL_TRACE.record(&self.trace_label, TraceDirection::Outgoing, message);
         self.writer
             .write_all(message.as_bytes())
             .await
@@ -59,4 +231,72 @@ pub enum CodecError {
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Agent closed the connection")]
+    Closed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acp::methods;
+    use serde_json::json;
+
+    fn response(id: Option<i64>) -> JsonRpcResponse {
+        JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(json!({ "id": id })),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_out_of_order_responses_are_matched_by_id() {
+        let mut pending = PendingRequests::default();
+        pending.insert(1, methods::SESSION_NEW);
+        pending.insert(2, methods::SESSION_PROMPT);
+
+        // The answer to 2 arrives while waiting for 1
+        assert!(pending.route(1, response(Some(2))).is_none());
+        assert_eq!(pending.route(1, response(Some(1))).unwrap().id, Some(1));
+        assert_eq!(pending.take(2).unwrap().id, Some(2));
+        assert!(pending.take(2).is_none());
+    }
+
+    #[test]
+    fn test_unknown_and_forgotten_responses_are_dropped() {
+        let mut pending = PendingRequests::default();
+        pending.insert(1, methods::INITIALIZE);
+        pending.insert(3, methods::SESSION_PROMPT);
+        pending.forget(3);
+
+        assert!(pending.route(1, response(Some(3))).is_none());
+        assert!(pending.route(1, response(Some(99))).is_none());
+        assert!(pending.take(3).is_none());
+        // With a single request outstanding, a null id belongs to it
+        assert!(pending.route(1, response(None)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_startup_output_is_skipped_until_first_message() {
+        let (agent_side, app_side) = tokio::io::duplex(1024);
+        let id = Uuid::new_v4();
+        let mut codec = AsyncCodec::from_stream(app_side).for_agent(id, "npx").with_startup_grace();
+        let (_, mut agent_writer) = tokio::io::split(agent_side);
+        agent_writer
+            .write_all(
+                b"npm WARN exec The following package was not found\n\
+                  {\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{}}\n\
+                  not json\n",
+            )
+            .await
+            .unwrap();
+
+        assert!(codec.read_message().await.unwrap().is_none());
+        assert!(!PROTOCOL_VIOLATIONS.counts().contains_key(&id));
+        assert!(codec.read_message().await.unwrap().is_some());
+        // Once the agent spoke, junk is a violation again
+        assert!(codec.read_message().await.unwrap().is_none());
+        assert_eq!(PROTOCOL_VIOLATIONS.counts().get(&id), Some(&1));
+    }
 }
@@ -66,7 +66,7 @@ impl Default for InitializeParams {
 // Authentication
 // ============================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AuthMethod {
     pub id: String,
     pub name: String,
@@ -108,10 +108,104 @@ pub struct SessionNewParams {
 pub struct SessionNewResult {
     #[serde(rename = "sessionId")]
     pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient")]
+    pub models: Option<SessionModelState>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient")]
+    pub modes: Option<SessionModeState>,
+}
+
+/// Response to session/load; the session id is the one requested
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionLoadResult {
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient")]
+    pub models: Option<SessionModelState>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient")]
+    pub modes: Option<SessionModeState>,
+}
+
+/// Modes a session can run in, like "ask" or "code"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionModeState {
+    #[serde(rename = "currentModeId")]
+    pub current_mode_id: String,
+    #[serde(rename = "availableModes")]
+    pub available_modes: Vec<SessionMode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMode {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Models a session can use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionModelState {
+    #[serde(rename = "currentModelId")]
+    pub current_model_id: String,
+    #[serde(rename = "availableModels")]
+    pub available_models: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    #[serde(rename = "modelId")]
+    pub model_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// None instead of an error for a value of an unexpected shape, so an
+/// optional field an agent gets wrong does not fail the whole message
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = Option::<Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|v| serde_json::from_value(v).ok()))
+}
+
+/// Resume a previous session; the agent replays it as session/update notifications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionLoadParams {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub cwd: String,
+    #[serde(rename = "mcpServers")]
+    pub mcp_servers: Vec<Value>,
+}
+
+/// List the sessions an agent has stored, optionally only those for `cwd`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionListParams {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub models: Option<Value>,
+    pub cwd: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub modes: Option<Value>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionListEntry {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionListResult {
+    #[serde(default)]
+    pub sessions: Vec<SessionListEntry>,
+    #[serde(rename = "nextCursor", default)]
+    pub next_cursor: Option<String>,
 }
 
 // ============================================================================
@@ -162,6 +256,37 @@ pub struct SessionPromptParams {
 pub struct SessionPromptResult {
     #[serde(rename = "stopReason")]
     pub stop_reason: StopReason,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+/// Tokens a prompt turn used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+}
+
+/// Context size and cost of a session, sent as a `usage_update`
+/// session update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageUpdate {
+    /// Tokens in the context
+    pub used: u64,
+    /// Context window size
+    pub size: u64,
+    /// Cost of the session so far
+    #[serde(default)]
+    pub cost: Option<SessionCost>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionCost {
+    pub amount: f64,
+    pub currency: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -236,6 +361,15 @@ impl SessionUpdate {
         }
     }
 
+    /// File locations of a tool call or tool call update
+    pub fn locations(&self) -> &[FileLocation] {
+        match self {
+            SessionUpdate::ToolCall(tc) => tc.locations.as_deref().unwrap_or_default(),
+            SessionUpdate::ToolCallUpdate(tcu) => tcu.locations.as_deref().unwrap_or_default(),
+            _ => &[],
+        }
+    }
+
     /// Get tool call info if this is a tool-related update
     pub fn get_tool_info(&self) -> Option<(&str, &str)> {
         match self {
@@ -349,6 +483,20 @@ pub struct FileLocation {
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub range: Option<FileRange>,
+    /// Single line, sent instead of a range by newer agents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+}
+
+impl FileLocation {
+    /// First and last line the location covers, if it names any
+    pub fn lines(&self) -> Option<(u32, u32)> {
+        match (&self.range, self.line) {
+            (Some(range), _) => Some((range.start.line, range.end.line.max(range.start.line))),
+            (None, Some(line)) => Some((line, line)),
+            (None, None) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -578,6 +726,26 @@ mod tests {
         assert!(json.contains("\"text\":\"Hello\""));
     }
 
+    #[test]
+    fn test_session_list_deserialization() {
+        let json = r#"{
+            "sessions": [
+                {"sessionId": "s1", "cwd": "/work/app", "title": "Fix watcher", "updatedAt": "2025-01-01T00:00:00Z"},
+                {"sessionId": "s2"}
+            ],
+            "nextCursor": "page-2"
+        }"#;
+        let result: SessionListResult = serde_json::from_str(json).unwrap();
+
+        assert_eq!(result.sessions.len(), 2);
+        assert_eq!(result.sessions[0].title.as_deref(), Some("Fix watcher"));
+        assert!(result.sessions[1].cwd.is_none());
+        assert_eq!(result.next_cursor.as_deref(), Some("page-2"));
+
+        let params = serde_json::to_string(&SessionListParams::default()).unwrap();
+        assert_eq!(params, "{}");
+    }
+
     #[test]
     fn test_agent_message_chunk_deserialization() {
         let json = r#"{
@@ -773,6 +941,10 @@ mod tests {
         let json = r#"{"stopReason": "some_unknown_value"}"#;
         let result: SessionPromptResult = serde_json::from_str(json).unwrap();
         assert!(matches!(result.stop_reason, StopReason::Unknown));
+
+        let json = r#"{"stopReason": "end_turn", "usage": {"inputTokens": 10, "outputTokens": 4}}"#;
+        let result: SessionPromptResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.usage.unwrap().input_tokens + result.usage.unwrap().output_tokens, 14);
     }
 
     #[test]
@@ -835,6 +1007,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_session_new_result_modes_and_models() {
+        let json = r#"{
+            "sessionId": "s1",
+            "modes": {
+                "currentModeId": "code",
+                "availableModes": [
+                    { "id": "ask", "name": "Ask" },
+                    { "id": "code", "name": "Code", "description": "Edit files" }
+                ]
+            },
+            "models": { "current": "unexpected shape" }
+        }"#;
+
+        let result: SessionNewResult = serde_json::from_str(json).unwrap();
+        let modes = result.modes.unwrap();
+        assert_eq!(modes.current_mode_id, "code");
+        assert_eq!(modes.available_modes[1].description.as_deref(), Some("Edit files"));
+        assert!(result.models.is_none());
+    }
+
     #[test]
     fn test_available_commands_update_deserialization() {
         let json = r#"{
@@ -0,0 +1,125 @@
+//! ACP method names and builders for the requests this client sends, so
+//! method strings are not spelled out at call sites.
+
+use super::messages::{
+    InitializeParams, SessionListParams, SessionLoadParams, SessionNewParams, SessionPromptParams,
+};
+use super::protocol::{JsonRpcNotification, JsonRpcRequest};
+use serde::Serialize;
+use serde_json::Value;
+
+pub const INITIALIZE: &str = "initialize";
+pub const INITIALIZED: &str = "notifications/initialized";
+pub const AUTHENTICATE: &str = "authenticate";
+pub const SESSION_NEW: &str = "session/new";
+pub const SESSION_LOAD: &str = "session/load";
+pub const SESSION_LIST: &str = "session/list";
+pub const SESSION_PROMPT: &str = "session/prompt";
+pub const SESSION_SET_MODE: &str = "session/set_mode";
+pub const SESSION_SET_MODEL: &str = "session/set_model";
+/// Notification asking the agent to end the running prompt turn
+pub const SESSION_CANCEL: &str = "session/cancel";
+/// Notification from the agent with session progress
+pub const SESSION_UPDATE: &str = "session/update";
+/// Request from the agent asking the user to allow a tool call
+pub const SESSION_REQUEST_PERMISSION: &str = "session/request_permission";
+
+/// A request to send, before it is given an id
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodCall {
+    pub method: &'static str,
+    pub params: Option<Value>,
+}
+
+impl MethodCall {
+    fn new(method: &'static str, params: &impl Serialize) -> Self {
+        Self {
+            method,
+            params: Some(serde_json::to_value(params).expect("ACP params serialize to JSON")),
+        }
+    }
+
+    pub fn into_request(self, id: i64) -> JsonRpcRequest {
+        JsonRpcRequest::new(id, self.method, self.params)
+    }
+}
+
+pub fn initialize(params: &InitializeParams) -> MethodCall {
+    MethodCall::new(INITIALIZE, params)
+}
+
+/// Sent once the initialize response has been handled
+pub fn initialized() -> JsonRpcNotification {
+    JsonRpcNotification {
+        jsonrpc: "2.0".to_string(),
+        method: INITIALIZED.to_string(),
+        params: None,
+    }
+}
+
+/// The agent answers the running prompt with stopReason "cancelled"
+pub fn session_cancel(session_id: &str) -> JsonRpcNotification {
+    JsonRpcNotification {
+        jsonrpc: "2.0".to_string(),
+        method: SESSION_CANCEL.to_string(),
+        params: Some(serde_json::json!({ "sessionId": session_id })),
+    }
+}
+
+pub fn authenticate(method_id: &str) -> MethodCall {
+    // Built as raw JSON - Codex CLI expects "methodId"
+    MethodCall::new(AUTHENTICATE, &serde_json::json!({ "methodId": method_id }))
+}
+
+pub fn session_new(params: &SessionNewParams) -> MethodCall {
+    MethodCall::new(SESSION_NEW, params)
+}
+
+pub fn session_load(params: &SessionLoadParams) -> MethodCall {
+    MethodCall::new(SESSION_LOAD, params)
+}
+
+pub fn session_list(params: &SessionListParams) -> MethodCall {
+    MethodCall::new(SESSION_LIST, params)
+}
+
+pub fn session_prompt(params: &SessionPromptParams) -> MethodCall {
+    MethodCall::new(SESSION_PROMPT, params)
+}
+
+pub fn session_set_mode(session_id: &str, mode_id: &str) -> MethodCall {
+    MethodCall::new(
+        SESSION_SET_MODE,
+        &serde_json::json!({ "sessionId": session_id, "modeId": mode_id }),
+    )
+}
+
+pub fn session_set_model(session_id: &str, model_id: &str) -> MethodCall {
+    MethodCall::new(
+        SESSION_SET_MODEL,
+        &serde_json::json!({ "sessionId": session_id, "modelId": model_id }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builders_use_method_constants() {
+        let request = session_new(&SessionNewParams {
+            cwd: "/work".to_string(),
+            mcp_servers: vec![],
+        })
+        .into_request(7);
+        assert_eq!(request.id, 7);
+        assert_eq!(request.method, "session/new");
+        assert_eq!(request.params.unwrap()["cwd"], "/work");
+
+        assert_eq!(authenticate("oauth").params.unwrap()["methodId"], "oauth");
+        assert_eq!(
+            serde_json::to_value(initialized()).unwrap(),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })
+        );
+    }
+}
@@ -1,7 +1,18 @@
+pub mod client;
 pub mod codec;
 pub mod messages;
+pub mod methods;
+pub mod notifications;
 pub mod protocol;
+pub mod scripted;
+pub mod transport;
+pub mod violations;
+pub mod websocket;
 
+pub use client::*;
 pub use codec::*;
 pub use messages::*;
+pub use notifications::*;
 pub use protocol::*;
+pub use transport::*;
+pub use violations::*;
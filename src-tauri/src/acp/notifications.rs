@@ -0,0 +1,89 @@
+//! Fan-out of agent notifications the session handling does not consume
+//! (log messages, progress, auth events, ...), so they are not dropped.
+
+use super::methods::SESSION_UPDATE;
+use super::protocol::JsonRpcNotification;
+use crate::clock::now_ms;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+pub static AGENT_NOTIFICATIONS: Lazy<NotificationDispatcher> = Lazy::new(NotificationDispatcher::new);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentNotification {
+    pub agent_id: Option<Uuid>,
+    pub method: String,
+    pub params: Option<Value>,
+    pub timestamp: u64,
+}
+
+pub struct NotificationDispatcher {
+    tx: broadcast::Sender<AgentNotification>,
+}
+
+impl NotificationDispatcher {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(256);
+        Self { tx }
+    }
+
+    /// Forward a notification to subscribers unless it is a session update.
+    /// Returns whether it was forwarded.
+    pub fn dispatch(&self, agent_id: Option<Uuid>, notification: &JsonRpcNotification) -> bool {
+        if notification.method == SESSION_UPDATE {
+            return false;
+        }
+
+        tracing::debug!("Agent notification {}: {:?}", notification.method, notification.params);
+        // No subscribers is fine
+        let _ = self.tx.send(AgentNotification {
+            agent_id,
+            method: notification.method.clone(),
+            params: notification.params.clone(),
+            timestamp: now_ms(),
+        });
+        true
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AgentNotification> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for NotificationDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn notification(method: &str) -> JsonRpcNotification {
+        JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params: Some(json!({ "level": "info", "message": "indexing" })),
+        }
+    }
+
+    #[test]
+    fn test_dispatch_skips_session_updates() {
+        let dispatcher = NotificationDispatcher::new();
+        let mut rx = dispatcher.subscribe();
+        let agent_id = Uuid::new_v4();
+
+        assert!(!dispatcher.dispatch(Some(agent_id), &notification(SESSION_UPDATE)));
+        assert!(dispatcher.dispatch(Some(agent_id), &notification("_zed/log")));
+
+        let received = rx.try_recv().unwrap();
+        assert_eq!(received.method, "_zed/log");
+        assert_eq!(received.agent_id, Some(agent_id));
+        assert!(rx.try_recv().is_err());
+    }
+}
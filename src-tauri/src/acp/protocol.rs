@@ -39,6 +39,18 @@ pub struct JsonRpcError {
     pub data: Option<Value>,
 }
 
+/// Error code of an agent that needs the client to authenticate first
+pub const AUTH_REQUIRED: i32 = -32000;
+
+impl JsonRpcError {
+    /// Whether the agent asks to authenticate first. Besides the ACP error
+    /// code, older agents only say so in the message.
+    pub fn is_auth_required(&self) -> bool {
+        let message = self.message.to_lowercase();
+        self.code == AUTH_REQUIRED || ["auth", "login", "credential"].iter().any(|w| message.contains(w))
+    }
+}
+
 impl JsonRpcResponse {
     /// Create a successful response
     pub fn success(id: i64, result: Value) -> Self {
@@ -149,10 +161,11 @@ impl JsonRpcMessage {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::acp::methods;
 
     #[test]
     fn test_request_serialization() {
-        let req = JsonRpcRequest::new(1, "initialize", Some(serde_json::json!({"foo": "bar"})));
+        let req = JsonRpcRequest::new(1, methods::INITIALIZE, Some(serde_json::json!({"foo": "bar"})));
         let json = serde_json::to_string(&req).unwrap();
 
         assert!(json.contains("\"jsonrpc\":\"2.0\""));
@@ -199,7 +212,7 @@ mod tests {
 
         match msg {
             JsonRpcMessage::Notification(notif) => {
-                assert_eq!(notif.method, "session/update");
+                assert_eq!(notif.method, methods::SESSION_UPDATE);
                 assert!(notif.params.is_some());
             }
             _ => panic!("Expected Notification"),
@@ -229,7 +242,7 @@ mod tests {
 
         match msg {
             JsonRpcMessage::Notification(notif) => {
-                assert_eq!(notif.method, "session/update");
+                assert_eq!(notif.method, methods::SESSION_UPDATE);
                 assert!(notif.params.is_some());
             }
             _ => panic!("Expected Notification, got {:?}", msg),
@@ -245,7 +258,7 @@ mod tests {
         match msg {
             JsonRpcMessage::Request(req) => {
                 assert_eq!(req.id, 42);
-                assert_eq!(req.method, "session/request_permission");
+                assert_eq!(req.method, methods::SESSION_REQUEST_PERMISSION);
                 assert!(req.params.is_some());
             }
             _ => panic!("Expected Request, got {:?}", msg),
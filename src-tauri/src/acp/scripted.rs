@@ -0,0 +1,407 @@
+//! In-process agent that answers ACP requests from a script, connected
+//! through an in-memory duplex pipe instead of a child process. Lets
+//! `AgentProcess` and the pool be exercised without npx or network access.
+
+use super::methods;
+use super::protocol::AUTH_REQUIRED;
+use serde_json::{json, Value};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadHalf, WriteHalf};
+use tokio::task::JoinHandle;
+
+/// Buffer size of each direction of the pipe
+const PIPE_CAPACITY: usize = 64 * 1024;
+/// Ids of requests the agent sends, kept clear of the client's ids
+const FIRST_AGENT_REQUEST_ID: i64 = 10_000;
+
+/// One thing the agent does while handling a prompt
+#[derive(Debug, Clone)]
+pub enum ScriptStep {
+    /// Send session/update with this `update` object
+    Update(Value),
+    /// Send any other notification
+    Notify { method: String, params: Value },
+    /// Send session/request_permission with these params (the session id is
+    /// filled in) and wait for the answer
+    RequestPermission(Value),
+    /// Write this line as-is, e.g. to send malformed JSON
+    Raw(String),
+    /// Send the prompt's text back as an agent_message_chunk
+    EchoPrompt,
+    /// Wait before the next step
+    Pause(Duration),
+    /// Wait for session/cancel, then end the turn as cancelled
+    WaitForCancel,
+}
+
+impl ScriptStep {
+    /// An agent_message_chunk with text
+    pub fn message(text: &str) -> Self {
+        Self::Update(message_chunk(text))
+    }
+}
+
+fn message_chunk(text: &str) -> Value {
+    json!({
+        "sessionUpdate": "agent_message_chunk",
+        "content": { "type": "text", "text": text },
+    })
+}
+
+/// Builder for an in-process agent
+#[derive(Debug, Clone)]
+pub struct ScriptedAgent {
+    capabilities: Value,
+    auth_methods: Value,
+    session_id: String,
+    sessions: Vec<Value>,
+    /// Steps for each prompt, in order; prompts beyond the script just end
+    turns: VecDeque<Vec<ScriptStep>>,
+    stop_reason: String,
+    /// Requests that are never answered
+    ignored: HashSet<String>,
+    /// Start the script over once every turn ran
+    repeat: bool,
+    /// Fail session/new with an auth-required error until authenticated
+    requires_auth: bool,
+}
+
+impl Default for ScriptedAgent {
+    fn default() -> Self {
+        Self {
+            capabilities: json!({}),
+            auth_methods: json!([]),
+            session_id: "scripted-session".to_string(),
+            sessions: Vec::new(),
+            turns: VecDeque::new(),
+            stop_reason: "end_turn".to_string(),
+            ignored: HashSet::new(),
+            repeat: false,
+            requires_auth: false,
+        }
+    }
+}
+
+impl ScriptedAgent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `agentCapabilities` returned from initialize
+    pub fn capabilities(mut self, capabilities: Value) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn auth_methods(mut self, auth_methods: Value) -> Self {
+        self.auth_methods = auth_methods;
+        self
+    }
+
+    /// Id returned from session/new
+    pub fn session_id(mut self, session_id: &str) -> Self {
+        self.session_id = session_id.to_string();
+        self
+    }
+
+    /// Entries returned from session/list, in a single page
+    pub fn sessions(mut self, sessions: Vec<Value>) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    /// Steps run for the next unscripted prompt
+    pub fn turn(mut self, steps: Vec<ScriptStep>) -> Self {
+        self.turns.push_back(steps);
+        self
+    }
+
+    pub fn stop_reason(mut self, stop_reason: &str) -> Self {
+        self.stop_reason = stop_reason.to_string();
+        self
+    }
+
+    /// Cycle through the turns instead of ending prompts once they ran out
+    pub fn repeat(mut self) -> Self {
+        self.repeat = true;
+        self
+    }
+
+    /// Fail session/new until the client calls authenticate
+    pub fn require_auth(mut self) -> Self {
+        self.requires_auth = true;
+        self
+    }
+
+    /// Never answer requests for `method`, e.g. to test timeouts
+    pub fn ignore(mut self, method: &str) -> Self {
+        self.ignored.insert(method.to_string());
+        self
+    }
+
+    /// Start the agent on a background task. The returned stream is the
+    /// client's end of the pipe; the agent stops once it is dropped.
+    pub fn connect(self) -> (DuplexStream, ScriptedAgentHandle) {
+        let (client, agent) = tokio::io::duplex(PIPE_CAPACITY);
+        let handle = ScriptedAgentHandle::default();
+        let received = handle.received.clone();
+        let task = tokio::spawn(async move {
+            let (reader, writer) = tokio::io::split(agent);
+            let mut conn = Connection {
+                lines: BufReader::new(reader).lines(),
+                writer,
+                received,
+                next_request_id: FIRST_AGENT_REQUEST_ID,
+            };
+            if let Err(e) = self.serve(&mut conn).await {
+                tracing::debug!("Scripted agent stopped: {}", e);
+            }
+        });
+        (client, handle.with_task(task))
+    }
+
+    async fn serve(mut self, conn: &mut Connection) -> std::io::Result<()> {
+        while let Some(message) = conn.read().await? {
+            // Notifications and stray responses need no answer
+            let (Some(id), Some(method)) = (
+                message.get("id").cloned(),
+                message.get("method").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            if self.ignored.contains(method) {
+                continue;
+            }
+
+            let result = match method {
+                methods::INITIALIZE => json!({
+                    "protocolVersion": 1,
+                    "agentCapabilities": self.capabilities,
+                    "authMethods": self.auth_methods,
+                }),
+                methods::AUTHENTICATE => {
+                    self.requires_auth = false;
+                    json!({ "completed": true })
+                }
+                methods::SESSION_NEW if self.requires_auth => {
+                    conn.write(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": AUTH_REQUIRED, "message": "Authentication required" },
+                    }))
+                    .await?;
+                    continue;
+                }
+                methods::SESSION_NEW => json!({ "sessionId": self.session_id }),
+                methods::SESSION_LOAD => {
+                    if let Some(session_id) = message.pointer("/params/sessionId").and_then(Value::as_str) {
+                        self.session_id = session_id.to_string();
+                    }
+                    json!({})
+                }
+                methods::SESSION_LIST => json!({ "sessions": self.sessions }),
+                methods::SESSION_PROMPT => {
+                    let steps = self.turns.pop_front().unwrap_or_default();
+                    if self.repeat && !steps.is_empty() {
+                        self.turns.push_back(steps.clone());
+                    }
+                    let prompt = prompt_text(&message);
+                    let mut stop_reason = self.stop_reason.clone();
+                    for step in steps {
+                        let cancelled = matches!(step, ScriptStep::WaitForCancel);
+                        self.run_step(conn, step, &prompt).await?;
+                        if cancelled {
+                            stop_reason = "cancelled".to_string();
+                            break;
+                        }
+                    }
+                    json!({ "stopReason": stop_reason })
+                }
+                _ => {
+                    conn.write(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+                    }))
+                    .await?;
+                    continue;
+                }
+            };
+            conn.write(&json!({ "jsonrpc": "2.0", "id": id, "result": result })).await?;
+        }
+        Ok(())
+    }
+
+    async fn run_step(&self, conn: &mut Connection, step: ScriptStep, prompt: &str) -> std::io::Result<()> {
+        match step {
+            ScriptStep::Update(update) => self.send_update(conn, update).await,
+            ScriptStep::EchoPrompt => self.send_update(conn, message_chunk(prompt)).await,
+            ScriptStep::Pause(duration) => {
+                tokio::time::sleep(duration).await;
+                Ok(())
+            }
+            ScriptStep::Notify { method, params } => {
+                conn.write(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+                    .await
+            }
+            ScriptStep::RequestPermission(mut params) => {
+                params["sessionId"] = json!(self.session_id);
+                let id = conn.next_request_id;
+                conn.next_request_id += 1;
+                conn.write(&json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": methods::SESSION_REQUEST_PERMISSION,
+                    "params": params,
+                }))
+                .await?;
+                // The answer is recorded in `received` like any other message
+                while let Some(message) = conn.read().await? {
+                    if message.get("id").and_then(Value::as_i64) == Some(id)
+                        && message.get("method").is_none()
+                    {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            ScriptStep::Raw(line) => conn.write_line(&line).await,
+            ScriptStep::WaitForCancel => {
+                while let Some(message) = conn.read().await? {
+                    let method = message.get("method").and_then(Value::as_str);
+                    if method == Some(methods::SESSION_CANCEL) {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn send_update(&self, conn: &mut Connection, update: Value) -> std::io::Result<()> {
+        conn.write(&json!({
+            "jsonrpc": "2.0",
+            "method": methods::SESSION_UPDATE,
+            "params": { "sessionId": self.session_id, "update": update },
+        }))
+        .await
+    }
+}
+
+/// Text blocks of a session/prompt request, joined
+fn prompt_text(request: &Value) -> String {
+    request
+        .pointer("/params/prompt")
+        .and_then(Value::as_array)
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter_map(|b| b.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+struct Connection {
+    lines: Lines<BufReader<ReadHalf<DuplexStream>>>,
+    writer: WriteHalf<DuplexStream>,
+    received: Arc<Mutex<Vec<Value>>>,
+    next_request_id: i64,
+}
+
+impl Connection {
+    /// Next message from the client, skipping lines that are not JSON
+    async fn read(&mut self) -> std::io::Result<Option<Value>> {
+        while let Some(line) = self.lines.next_line().await? {
+            if let Ok(message) = serde_json::from_str::<Value>(&line) {
+                self.received.lock().unwrap().push(message.clone());
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn write(&mut self, message: &Value) -> std::io::Result<()> {
+        self.write_line(&message.to_string()).await
+    }
+
+    async fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        self.writer.flush().await
+    }
+}
+
+/// What a running scripted agent has received
+#[derive(Debug, Default)]
+pub struct ScriptedAgentHandle {
+    received: Arc<Mutex<Vec<Value>>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl ScriptedAgentHandle {
+    fn with_task(mut self, task: JoinHandle<()>) -> Self {
+        self.task = Some(task);
+        self
+    }
+
+    /// Every message read from the client, in order
+    pub fn received(&self) -> Vec<Value> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Methods of the requests and notifications received, in order
+    pub fn methods(&self) -> Vec<String> {
+        self.received()
+            .iter()
+            .filter_map(|m| m.get("method").and_then(Value::as_str).map(String::from))
+            .collect()
+    }
+
+    /// Wait until the client closed its end of the pipe
+    pub async fn finished(mut self) {
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acp::{AsyncCodec, Incoming, JsonRpcMessage, ProtocolClient};
+
+    #[tokio::test]
+    async fn test_prompt_streams_updates_before_response() {
+        let (stream, handle) = ScriptedAgent::new()
+            .turn(vec![ScriptStep::message("hello")])
+            .connect();
+        let mut client = ProtocolClient::new(AsyncCodec::from_stream(stream));
+
+        let call = client
+            .send_request(methods::MethodCall {
+                method: methods::SESSION_PROMPT,
+                params: Some(json!({ "sessionId": "scripted-session", "prompt": [] })),
+            })
+            .await
+            .unwrap();
+        match client.next_for(&call).await.unwrap() {
+            Incoming::Message(JsonRpcMessage::Notification(n)) => {
+                assert_eq!(n.method, methods::SESSION_UPDATE);
+                assert_eq!(n.params.unwrap()["update"]["content"]["text"], "hello");
+            }
+            other => panic!("expected an update, got {:?}", other),
+        }
+        match client.next_for(&call).await.unwrap() {
+            Incoming::Response(resp) => assert_eq!(resp.result.unwrap()["stopReason"], "end_turn"),
+            other => panic!("expected the response, got {:?}", other),
+        }
+
+        drop(client);
+        assert_eq!(handle.methods(), vec![methods::SESSION_PROMPT]);
+        handle.finished().await;
+    }
+}
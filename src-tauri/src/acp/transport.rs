@@ -0,0 +1,191 @@
+//! How the client reaches an agent: the stdio of a spawned command (the
+//! default) or a connection to an agent that already runs elsewhere, e.g.
+//! in a container or on another machine.
+
+use super::codec::AsyncCodec;
+use super::websocket;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use tokio::net::TcpStream;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Transport {
+    /// Spawn the command and speak ACP over its stdin/stdout
+    #[default]
+    Stdio,
+    /// Newline-delimited JSON-RPC over a TCP connection to `host:port`
+    Tcp { address: String },
+    /// One JSON-RPC message per frame over a `ws://` or `wss://` URL
+    #[serde(rename = "websocket")]
+    WebSocket { url: String },
+    /// Run the command on another host through the local `ssh` client and
+    /// speak ACP over the remote stdio
+    Ssh(SshTarget),
+}
+
+/// Remote host to launch an agent on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshTarget {
+    pub host: String,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Private key passed to `ssh -i`
+    #[serde(default)]
+    pub identity_file: Option<String>,
+    /// Directory to run the agent in; defaults to the agent's working directory
+    #[serde(default)]
+    pub remote_directory: Option<String>,
+}
+
+impl SshTarget {
+    /// The local `ssh` invocation that runs `command` on the host
+    pub fn command_line(
+        &self,
+        command: &str,
+        args: &[String],
+        working_directory: &str,
+    ) -> Result<(String, Vec<String>), String> {
+        check_destination("host", &self.host)?;
+        if let Some(user) = &self.user {
+            check_destination("user", user)?;
+        }
+        // No tty so the agent's stdio stays raw; never prompt for a password
+        // nobody can type
+        let mut ssh_args: Vec<String> = ["-T", "-o", "BatchMode=yes"].iter().map(|s| s.to_string()).collect();
+        if let Some(port) = self.port {
+            ssh_args.extend(["-p".to_string(), port.to_string()]);
+        }
+        if let Some(identity_file) = &self.identity_file {
+            ssh_args.extend(["-i".to_string(), identity_file.clone()]);
+        }
+        ssh_args.push(match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        });
+
+        let directory = self.remote_directory.as_deref().unwrap_or(working_directory);
+        let mut remote = format!("cd {} && exec {}", shell_quote(directory), shell_quote(command));
+        for arg in args {
+            remote.push(' ');
+            remote.push_str(&shell_quote(arg));
+        }
+        ssh_args.extend(["--".to_string(), remote]);
+        Ok(("ssh".to_string(), ssh_args))
+    }
+}
+
+/// Refuse a host or user `ssh` would parse as an option or split into
+/// several words
+fn check_destination(field: &str, value: &str) -> Result<(), String> {
+    let unsafe_char = |c: char| c.is_whitespace() || c.is_control();
+    if value.is_empty() || value.starts_with('-') || value.chars().any(unsafe_char) {
+        return Err(format!("Invalid SSH {}: {:?}", field, value));
+    }
+    Ok(())
+}
+
+/// Quote for a POSIX shell
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+impl Transport {
+    /// Whether the agent is a local stdio process
+    pub fn is_stdio(&self) -> bool {
+        matches!(self, Transport::Stdio)
+    }
+
+    /// Whether a process is spawned for the agent, locally or over SSH,
+    /// rather than connecting to one that is already running
+    pub fn spawns_process(&self) -> bool {
+        matches!(self, Transport::Stdio | Transport::Ssh(_))
+    }
+
+    /// Connect to an agent that is already running. Agents with a process
+    /// have to be spawned instead.
+    pub async fn connect(&self) -> io::Result<AsyncCodec> {
+        match self {
+            Transport::Stdio | Transport::Ssh(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "agents with a process are spawned, not connected to",
+            )),
+            Transport::Tcp { address } => {
+                let stream = TcpStream::connect(address.as_str()).await?;
+                stream.set_nodelay(true)?;
+                Ok(AsyncCodec::from_stream(stream))
+            }
+            Transport::WebSocket { url } => Ok(AsyncCodec::from_stream(websocket::connect(url).await?)),
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Stdio => write!(f, "stdio"),
+            Transport::Tcp { address } => write!(f, "tcp://{}", address),
+            Transport::WebSocket { url } => write!(f, "{}", url),
+            Transport::Ssh(target) => match &target.user {
+                Some(user) => write!(f, "ssh://{}@{}", user, target.host),
+                None => write!(f, "ssh://{}", target.host),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_serialization() {
+        assert_eq!(serde_json::to_value(Transport::default()).unwrap(), serde_json::json!({ "type": "stdio" }));
+        let ws: Transport =
+            serde_json::from_value(serde_json::json!({ "type": "websocket", "url": "ws://box:9000/acp" })).unwrap();
+        assert_eq!(ws, Transport::WebSocket { url: "ws://box:9000/acp".to_string() });
+        assert_eq!(Transport::Tcp { address: "box:9000".to_string() }.to_string(), "tcp://box:9000");
+    }
+
+    #[test]
+    fn test_ssh_command_line_quotes_remote_command() {
+        let target = SshTarget {
+            host: "build-box".to_string(),
+            user: Some("dev".to_string()),
+            port: Some(2222),
+            identity_file: Some("/home/me/.ssh/id_ed25519".to_string()),
+            remote_directory: None,
+        };
+        let (command, args) = target
+            .command_line("npx", &["pkg@1.0".to_string(), "it's".to_string()], "/src/app")
+            .unwrap();
+        assert_eq!(command, "ssh");
+        assert_eq!(
+            args,
+            vec![
+                "-T", "-o", "BatchMode=yes", "-p", "2222", "-i", "/home/me/.ssh/id_ed25519", "dev@build-box", "--",
+                "cd '/src/app' && exec 'npx' 'pkg@1.0' 'it'\\''s'",
+            ]
+        );
+    }
+    #[test]
+    fn test_ssh_command_line_rejects_option_like_destinations() {
+        let target = |host: &str, user: Option<&str>| SshTarget {
+            host: host.to_string(),
+            user: user.map(str::to_string),
+            port: None,
+            identity_file: None,
+            remote_directory: None,
+        };
+        let run = |target: SshTarget| target.command_line("agent", &[], "/src/app");
+        assert!(run(target("-oProxyCommand=sh -c 'touch /tmp/pwned'", None)).is_err());
+        assert!(run(target("build-box", Some("-oProxyCommand=id"))).is_err());
+        assert!(run(target("build box", None)).is_err());
+        assert!(run(target("build-box\n", None)).is_err());
+        assert!(run(target("", None)).is_err());
+        assert!(run(target("build-box", Some("dev"))).is_ok());
+    }
+}
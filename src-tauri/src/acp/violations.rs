@@ -0,0 +1,114 @@
+//! Tracking of malformed messages received from agents. A bad line is
+//! skipped instead of failing the request it arrived during, and is
+//! reported so flaky agents can be spotted.
+
+use crate::clock::now_ms;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Longest part of the raw payload kept in a violation
+const EXCERPT_CHARS: usize = 500;
+
+pub static PROTOCOL_VIOLATIONS: Lazy<ViolationLog> = Lazy::new(ViolationLog::new);
+
+/// A message from an agent that was not valid JSON-RPC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolViolation {
+    pub agent_id: Option<Uuid>,
+    pub excerpt: String,
+    pub error: String,
+    /// Violations seen from this agent so far, including this one
+    pub count: u64,
+    pub timestamp: u64,
+}
+
+pub struct ViolationLog {
+    counts: Mutex<HashMap<Uuid, u64>>,
+    tx: broadcast::Sender<ProtocolViolation>,
+}
+
+impl ViolationLog {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(64);
+        Self {
+            counts: Mutex::new(HashMap::new()),
+            tx,
+        }
+    }
+
+    pub fn record(&self, agent_id: Option<Uuid>, raw: &str, error: &str) -> ProtocolViolation {
+        let count = match agent_id {
+            Some(id) => {
+                let mut counts = self.counts.lock().unwrap();
+                let count = counts.entry(id).or_insert(0);
+                *count += 1;
+                *count
+            }
+            None => 1,
+        };
+
+        let mut excerpt: String = raw.chars().take(EXCERPT_CHARS).collect();
+        if excerpt.len() < raw.len() {
+            excerpt.push_str("...");
+        }
+        let violation = ProtocolViolation {
+            agent_id,
+            excerpt,
+            error: error.to_string(),
+            count,
+            timestamp: now_ms(),
+        };
+
+        // No subscribers is fine
+        let _ = self.tx.send(violation.clone());
+        violation
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProtocolViolation> {
+        self.tx.subscribe()
+    }
+
+    /// Violation count per agent
+    pub fn counts(&self) -> HashMap<Uuid, u64> {
+        self.counts.lock().unwrap().clone()
+    }
+
+    /// Drop the counter of an agent that was stopped
+    pub fn forget(&self, agent_id: &Uuid) {
+        self.counts.lock().unwrap().remove(agent_id);
+    }
+}
+
+impl Default for ViolationLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_counts_per_agent_and_truncates() {
+        let log = ViolationLog::new();
+        let mut rx = log.subscribe();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        log.record(Some(a), "{oops", "EOF while parsing");
+        let second = log.record(Some(a), &"x".repeat(EXCERPT_CHARS + 1), "expected value");
+        log.record(Some(b), "nope", "expected value");
+
+        assert_eq!(second.count, 2);
+        assert!(second.excerpt.ends_with("..."));
+        assert_eq!(log.counts()[&b], 1);
+        assert_eq!(rx.try_recv().unwrap().excerpt, "{oops");
+
+        log.forget(&a);
+        assert!(!log.counts().contains_key(&a));
+    }
+}
@@ -0,0 +1,303 @@
+//! Minimal WebSocket client (RFC 6455) for agents reached over `ws://` or
+//! `wss://`. Each text frame carries one JSON-RPC message; frames are bridged
+//! onto an in-process pipe as newline-delimited lines so [`AsyncCodec`]
+//! reads them like stdio.
+//!
+//! [`AsyncCodec`]: super::codec::AsyncCodec
+
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::io;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream,
+};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use url::Url;
+use uuid::Uuid;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Appended to the client key to compute the accept header
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Larger messages are treated as a broken connection
+const MAX_MESSAGE_BYTES: u64 = 16 * 1024 * 1024;
+/// Buffer size of each direction of the bridge pipe
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+trait WsStream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> WsStream for T {}
+
+/// Open a WebSocket and return a byte stream of newline-delimited messages
+pub async fn connect(url: &str) -> io::Result<DuplexStream> {
+    let url = Url::parse(url).map_err(|e| invalid(format!("invalid URL {}: {}", url, e)))?;
+    let secure = match url.scheme() {
+        "ws" => false,
+        "wss" => true,
+        scheme => return Err(invalid(format!("unsupported scheme {}", scheme))),
+    };
+    let host = url.host_str().ok_or_else(|| invalid("URL has no host".to_string()))?;
+    let port = url.port_or_known_default().unwrap_or(if secure { 443 } else { 80 });
+
+    let tcp = TcpStream::connect((host, port)).await?;
+    let stream: Box<dyn WsStream> = if secure {
+        let connector = tokio_native_tls::native_tls::TlsConnector::new().map_err(io::Error::other)?;
+        let tls = tokio_native_tls::TlsConnector::from(connector)
+            .connect(host, tcp)
+            .await
+            .map_err(io::Error::other)?;
+        Box::new(tls)
+    } else {
+        Box::new(tcp)
+    };
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    handshake(&mut reader, &mut writer, &url).await?;
+
+    let (client, bridge) = tokio::io::duplex(PIPE_CAPACITY);
+    let (pipe_reader, pipe_writer) = tokio::io::split(bridge);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        if let Err(e) = pump_incoming(reader, pipe_writer, control_tx).await {
+            tracing::debug!("WebSocket read ended: {}", e);
+        }
+    });
+    tokio::spawn(async move {
+        if let Err(e) = pump_outgoing(pipe_reader, control_rx, writer).await {
+            tracing::debug!("WebSocket write ended: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Value the server must return in Sec-WebSocket-Accept for `key`
+fn accept_key(key: &str) -> String {
+    let mut sha = Sha1::new();
+    sha.update(key.as_bytes());
+    sha.update(HANDSHAKE_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(sha.finalize())
+}
+
+async fn handshake(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+    url: &Url,
+) -> io::Result<()> {
+    let key = base64::engine::general_purpose::STANDARD.encode(Uuid::new_v4().as_bytes());
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target = format!("{}?{}", target, query);
+    }
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        target, host, key
+    );
+    writer.write_all(request.as_bytes()).await?;
+    writer.flush().await?;
+
+    let mut status = String::new();
+    reader.read_line(&mut status).await?;
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("WebSocket upgrade refused: {}", status.trim()),
+        ));
+    }
+
+    let mut accepted = false;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-accept") {
+                accepted = value.trim() == accept_key(&key);
+            }
+        }
+    }
+    if !accepted {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket server sent a wrong Sec-WebSocket-Accept",
+        ));
+    }
+    Ok(())
+}
+
+/// A masked client frame
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Frame> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await?;
+    let len = match head[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "WebSocket frame too large"));
+    }
+    let mask = if head[1] & 0x80 != 0 {
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask).await?;
+        Some(mask)
+    } else {
+        None
+    };
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if let Some(mask) = mask {
+        payload.iter_mut().enumerate().for_each(|(i, b)| *b ^= mask[i % 4]);
+    }
+    Ok(Frame {
+        fin: head[0] & 0x80 != 0,
+        opcode: head[0] & 0x0F,
+        payload,
+    })
+}
+
+/// Frames from the server -> lines on the pipe
+async fn pump_incoming(
+    mut reader: impl AsyncRead + Unpin,
+    mut pipe: impl AsyncWrite + Unpin,
+    control: mpsc::Sender<(u8, Vec<u8>)>,
+) -> io::Result<()> {
+    let mut message = Vec::new();
+    let result = loop {
+        let frame = match read_frame(&mut reader).await {
+            Ok(frame) => frame,
+            Err(e) => break Err(e),
+        };
+        match frame.opcode {
+            OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                message.extend_from_slice(&frame.payload);
+                if message.len() as u64 > MAX_MESSAGE_BYTES {
+                    break Err(io::Error::new(io::ErrorKind::InvalidData, "WebSocket message too large"));
+                }
+                if frame.fin {
+                    message.push(b'\n');
+                    pipe.write_all(&message).await?;
+                    pipe.flush().await?;
+                    message.clear();
+                }
+            }
+            OP_PING => {
+                let _ = control.send((OP_PONG, frame.payload)).await;
+            }
+            OP_PONG => {}
+            OP_CLOSE => break Ok(()),
+            opcode => {
+                break Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown WebSocket opcode {}", opcode),
+                ))
+            }
+        }
+    };
+    // Lets the codec see the end of the connection
+    let _ = pipe.shutdown().await;
+    result
+}
+
+/// Lines from the pipe and control frames -> frames to the server
+async fn pump_outgoing(
+    pipe: impl AsyncRead + Unpin,
+    mut control: mpsc::Receiver<(u8, Vec<u8>)>,
+    mut writer: impl AsyncWrite + Unpin,
+) -> io::Result<()> {
+    let mut lines = BufReader::new(pipe).lines();
+    loop {
+        let (opcode, payload) = tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) => (OP_TEXT, line.into_bytes()),
+                // The codec was dropped
+                None => (OP_CLOSE, Vec::new()),
+            },
+            Some(frame) = control.recv() => frame,
+        };
+        let mut mask = [0u8; 4];
+        mask.copy_from_slice(&Uuid::new_v4().as_bytes()[..4]);
+        writer.write_all(&encode_frame(opcode, &payload, mask)).await?;
+        writer.flush().await?;
+        if opcode == OP_CLOSE {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key_matches_rfc_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[tokio::test]
+    async fn test_frames_round_trip_with_extended_lengths() {
+        for len in [5, 300, 70_000] {
+            let payload: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let encoded = encode_frame(OP_TEXT, &payload, [1, 2, 3, 4]);
+            let frame = read_frame(&mut encoded.as_slice()).await.unwrap();
+            assert!(frame.fin);
+            assert_eq!(frame.opcode, OP_TEXT);
+            assert_eq!(frame.payload, payload);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fragmented_messages_become_one_line() {
+        // Unmasked server frames: "ab" split over a text and a continuation frame
+        let input: Vec<u8> = vec![0x01, 1, b'a', 0x80, 1, b'b', 0x89, 0, 0x88, 0];
+        let (control_tx, mut control_rx) = mpsc::channel(8);
+        let mut pipe = Vec::new();
+        pump_incoming(input.as_slice(), &mut pipe, control_tx).await.unwrap();
+        assert_eq!(pipe, b"ab\n");
+        assert_eq!(control_rx.recv().await, Some((OP_PONG, Vec::new())));
+    }
+}
@@ -0,0 +1,574 @@
+//! Retained tool call content (diffs, text output) so a single tool call can
+//! be copied or saved after the live updates have scrolled by. Large output
+//! is spooled to disk and only a preview is kept in memory.
+
+use crate::acp::FileLocation;
+use crate::clock::now_ms;
+use crate::state::app_data_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Tool calls kept per agent before the oldest are dropped
+const MAX_TOOL_CALLS: usize = 200;
+/// Output of a tool call larger than this goes to disk
+const SPOOL_THRESHOLD_BYTES: usize = 64 * 1024;
+/// Characters of spooled text and raw output kept in memory
+const PREVIEW_CHARS: usize = 4 * 1024;
+const TOOL_OUTPUT_DIR: &str = "tool-outputs";
+
+/// One piece of content produced by a tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArtifactBlock {
+    Text {
+        text: String,
+    },
+    Diff {
+        path: String,
+        old_text: Option<String>,
+        new_text: String,
+    },
+    Terminal {
+        terminal_id: String,
+    },
+    /// Content this version does not understand, kept verbatim
+    Other {
+        value: Value,
+    },
+}
+
+impl ArtifactBlock {
+    fn text_len(&self) -> usize {
+        match self {
+            ArtifactBlock::Text { text } => text.len(),
+            ArtifactBlock::Diff { old_text, new_text, .. } => {
+                old_text.as_ref().map_or(0, String::len) + new_text.len()
+            }
+            ArtifactBlock::Terminal { .. } => 0,
+            ArtifactBlock::Other { value } => value.to_string().len(),
+        }
+    }
+
+    /// Parse an ACP tool call content item
+    fn from_value(value: &Value) -> Self {
+        let str_field = |v: &Value, key: &str| v.get(key).and_then(|s| s.as_str()).map(String::from);
+
+        match value.get("type").and_then(|t| t.as_str()) {
+            // Wrapped content block: {"type": "content", "content": {"type": "text", ...}}
+            Some("content") => match value.get("content") {
+                Some(inner) if inner.get("type").and_then(|t| t.as_str()) == Some("text") => {
+                    ArtifactBlock::Text {
+                        text: str_field(inner, "text").unwrap_or_default(),
+                    }
+                }
+                _ => ArtifactBlock::Other {
+                    value: value.clone(),
+                },
+            },
+            Some("text") => ArtifactBlock::Text {
+                text: str_field(value, "text").unwrap_or_default(),
+            },
+            Some("diff") => ArtifactBlock::Diff {
+                path: str_field(value, "path").unwrap_or_default(),
+                old_text: str_field(value, "oldText"),
+                new_text: str_field(value, "newText").unwrap_or_default(),
+            },
+            Some("terminal") => ArtifactBlock::Terminal {
+                terminal_id: str_field(value, "terminalId").unwrap_or_default(),
+            },
+            _ => ArtifactBlock::Other {
+                value: value.clone(),
+            },
+        }
+    }
+}
+
+/// Lines of a file a tool call reads or edits, as numbered by the agent.
+/// Without lines the whole file is meant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TouchedRange {
+    pub path: String,
+    pub start_line: Option<u32>,
+    pub end_line: Option<u32>,
+}
+
+impl TouchedRange {
+    pub fn from_location(location: &FileLocation) -> Self {
+        let lines = location.lines();
+        Self {
+            path: location.path.clone(),
+            start_line: lines.map(|(start, _)| start),
+            end_line: lines.map(|(_, end)| end),
+        }
+    }
+
+    /// Parse the `locations` array of a raw tool call payload
+    pub fn from_locations(locations: &Value) -> Vec<Self> {
+        locations
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|l| serde_json::from_value::<FileLocation>(l.clone()).ok())
+            .map(|l| Self::from_location(&l))
+            .collect()
+    }
+}
+
+/// Everything known about a tool call, merged from its updates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallArtifact {
+    pub tool_call_id: String,
+    pub title: Option<String>,
+    pub kind: Option<String>,
+    pub status: Option<String>,
+    pub content: Vec<ArtifactBlock>,
+    pub locations: Vec<String>,
+    /// Locations with their line ranges
+    #[serde(default)]
+    pub ranges: Vec<TouchedRange>,
+    pub raw_input: Option<Value>,
+    pub raw_output: Option<Value>,
+    /// Set when `content` and `raw_output` are previews of output spooled to
+    /// disk, see [`ToolCallHistory::output`]
+    #[serde(default)]
+    pub spooled: Option<SpooledOutput>,
+    /// Unix ms of the first update
+    #[serde(default)]
+    pub started_at: u64,
+    /// Unix ms of the update that completed or failed it, or of the end of
+    /// the turn that left it unfinished
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+}
+
+impl ToolCallArtifact {
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+}
+
+/// A tool call not finished yet, with how long it has been going
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningToolCall {
+    pub agent_id: Uuid,
+    pub tool_call_id: String,
+    pub title: Option<String>,
+    pub kind: Option<String>,
+    pub status: Option<String>,
+    pub started_at: u64,
+    pub running_ms: u64,
+}
+
+/// Where the full output of a tool call went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpooledOutput {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// Full output of a tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolOutput {
+    pub tool_call_id: String,
+    pub content: Vec<ArtifactBlock>,
+    pub raw_output: Option<Value>,
+}
+
+/// Directory large tool outputs of all agents are spooled to
+pub fn tool_output_dir() -> PathBuf {
+    app_data_dir().join(TOOL_OUTPUT_DIR)
+}
+
+/// Remove outputs spooled by an earlier run; agent ids don't survive restarts
+pub fn clear_tool_outputs() {
+    let dir = tool_output_dir();
+    if dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            tracing::warn!("Failed to remove {}: {}", dir.display(), e);
+        }
+    }
+}
+
+fn preview(text: &str) -> String {
+    let mut preview: String = text.chars().take(PREVIEW_CHARS).collect();
+    if preview.len() < text.len() {
+        preview.push_str("...");
+    }
+    preview
+}
+
+/// Recent tool calls of one agent, readable while the agent is busy
+pub struct ToolCallHistory {
+    calls: Mutex<VecDeque<ToolCallArtifact>>,
+    /// Where large outputs go; kept in memory in full without one
+    spool_dir: Option<PathBuf>,
+}
+
+impl ToolCallHistory {
+    pub fn new() -> Self {
+        Self {
+            calls: Mutex::new(VecDeque::new()),
+            spool_dir: None,
+        }
+    }
+
+    /// Spool large outputs to files in `dir`, removed with the history
+    pub fn spooled_to(dir: PathBuf) -> Self {
+        Self {
+            calls: Mutex::new(VecDeque::new()),
+            spool_dir: Some(dir),
+        }
+    }
+
+    /// Merge a raw `session/update` payload if it is a tool call or tool call update
+    pub fn record(&self, update: &Value) {
+        self.record_at(update, now_ms());
+    }
+
+    fn record_at(&self, update: &Value, now_ms: u64) {
+        let kind = update.get("sessionUpdate").and_then(|s| s.as_str());
+        if !matches!(kind, Some("tool_call") | Some("tool_call_update")) {
+            return;
+        }
+        let Some(id) = update.get("toolCallId").and_then(|s| s.as_str()) else {
+            return;
+        };
+
+        let mut calls = self.calls.lock().unwrap();
+        let index = match calls.iter().position(|c| c.tool_call_id == id) {
+            Some(index) => index,
+            None => {
+                if calls.len() >= MAX_TOOL_CALLS {
+                    let evicted = calls.pop_front().and_then(|c| c.spooled);
+                    if let Some(SpooledOutput { path, .. }) = evicted {
+                        fs::remove_file(path).ok();
+                    }
+                }
+                calls.push_back(ToolCallArtifact {
+                    tool_call_id: id.to_string(),
+                    title: None,
+                    kind: None,
+                    status: None,
+                    content: Vec::new(),
+                    locations: Vec::new(),
+                    ranges: Vec::new(),
+                    raw_input: None,
+                    raw_output: None,
+                    spooled: None,
+                    started_at: now_ms,
+                    finished_at: None,
+                });
+                calls.len() - 1
+            }
+        };
+        let call = &mut calls[index];
+
+        let str_field = |key: &str| update.get(key).and_then(|s| s.as_str()).map(String::from);
+        if let Some(title) = str_field("title") {
+            call.title = Some(title);
+        }
+        if let Some(kind) = str_field("kind") {
+            call.kind = Some(kind);
+        }
+        if let Some(status) = str_field("status") {
+            if matches!(status.as_str(), "completed" | "failed") {
+                call.finished_at.get_or_insert(now_ms);
+            }
+            call.status = Some(status);
+        }
+        // Updates replace the content collection rather than appending to it
+        let content = update.get("content").and_then(|c| c.as_array());
+        if let Some(content) = content {
+            call.content = content.iter().map(ArtifactBlock::from_value).collect();
+        }
+        if let Some(locations) = update.get("locations").and_then(|l| l.as_array()) {
+            call.locations = locations
+                .iter()
+                .filter_map(|l| l.get("path").and_then(|p| p.as_str()).map(String::from))
+                .collect();
+            call.ranges = TouchedRange::from_locations(&update["locations"]);
+        }
+        if let Some(raw_input) = update.get("rawInput") {
+            call.raw_input = Some(raw_input.clone());
+        }
+        if let Some(raw_output) = update.get("rawOutput") {
+            call.raw_output = Some(raw_output.clone());
+        }
+        let has_output = content.is_some() || update.get("rawOutput").is_some();
+        if let Some(dir) = self.spool_dir.as_ref().filter(|_| has_output) {
+            Self::spool(dir, call);
+        }
+    }
+
+    /// Move the output of a call to disk if it is large, keeping previews.
+    /// Output spooled by an earlier update is merged back first.
+    fn spool(dir: &Path, call: &mut ToolCallArtifact) {
+        let mut output = ToolOutput {
+            tool_call_id: call.tool_call_id.clone(),
+            content: call.content.clone(),
+            raw_output: call.raw_output.clone(),
+        };
+        if let Some(spooled) = &call.spooled {
+            if let Some(earlier) = read_output(Path::new(&spooled.path)) {
+                if output.content == preview_content(&earlier.content) {
+                    output.content = earlier.content;
+                }
+                if output.raw_output == earlier.raw_output.as_ref().map(preview_value) {
+                    output.raw_output = earlier.raw_output;
+                }
+            }
+        }
+
+        let size = output.content.iter().map(ArtifactBlock::text_len).sum::<usize>()
+            + output.raw_output.as_ref().map_or(0, |v| v.to_string().len());
+        if size <= SPOOL_THRESHOLD_BYTES {
+            return;
+        }
+
+        let file_name: String = call
+            .tool_call_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}.json", file_name));
+        let written = fs::create_dir_all(dir)
+            .and_then(|_| serde_json::to_vec(&output).map_err(std::io::Error::other))
+            .and_then(|json| fs::write(&path, &json).map(|_| json.len()));
+        match written {
+            Ok(bytes) => {
+                call.content = preview_content(&output.content);
+                call.raw_output = output.raw_output.as_ref().map(preview_value);
+                call.spooled = Some(SpooledOutput {
+                    path: path.display().to_string(),
+                    bytes: bytes as u64,
+                });
+            }
+            Err(e) => tracing::warn!("Failed to spool output of {}: {}", call.tool_call_id, e),
+        }
+    }
+
+    /// Full output of a call, read from disk if it was spooled
+    pub fn output(&self, tool_call_id: &str) -> Result<Option<ToolOutput>, String> {
+        let Some(call) = self.get(tool_call_id) else {
+            return Ok(None);
+        };
+        match call.spooled {
+            Some(spooled) => read_output(Path::new(&spooled.path))
+                .map(Some)
+                .ok_or_else(|| format!("Failed to read {}", spooled.path)),
+            None => Ok(Some(ToolOutput {
+                tool_call_id: call.tool_call_id,
+                content: call.content,
+                raw_output: call.raw_output,
+            })),
+        }
+    }
+
+    /// Mark calls still open as finished; the agent reports nothing more
+    /// about them once their turn ended or the agent stopped
+    pub fn finish_running(&self) {
+        let now_ms = now_ms();
+        for call in self.calls.lock().unwrap().iter_mut() {
+            call.finished_at.get_or_insert(now_ms);
+        }
+    }
+
+    /// Calls not finished yet, longest running first
+    pub fn running(&self, agent_id: Uuid) -> Vec<RunningToolCall> {
+        self.running_at(agent_id, now_ms())
+    }
+
+    fn running_at(&self, agent_id: Uuid, now_ms: u64) -> Vec<RunningToolCall> {
+        let mut running: Vec<RunningToolCall> = self
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| !c.is_finished())
+            .map(|c| RunningToolCall {
+                agent_id,
+                tool_call_id: c.tool_call_id.clone(),
+                title: c.title.clone(),
+                kind: c.kind.clone(),
+                status: c.status.clone(),
+                started_at: c.started_at,
+                running_ms: now_ms.saturating_sub(c.started_at),
+            })
+            .collect();
+        running.sort_by_key(|c| Reverse(c.running_ms));
+        running
+    }
+
+    pub fn get(&self, tool_call_id: &str) -> Option<ToolCallArtifact> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|c| c.tool_call_id == tool_call_id)
+            .cloned()
+    }
+}
+
+impl Default for ToolCallHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ToolCallHistory {
+    fn drop(&mut self) {
+        if let Some(dir) = self.spool_dir.as_ref().filter(|dir| dir.exists()) {
+            fs::remove_dir_all(dir).ok();
+        }
+    }
+}
+
+fn read_output(path: &Path) -> Option<ToolOutput> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// Text blocks cut down to a preview; diffs and other blocks are kept
+fn preview_content(content: &[ArtifactBlock]) -> Vec<ArtifactBlock> {
+    content
+        .iter()
+        .map(|block| match block {
+            ArtifactBlock::Text { text } => ArtifactBlock::Text { text: preview(text) },
+            block => block.clone(),
+        })
+        .collect()
+}
+
+/// Raw output as a preview of its JSON text, unless it is small
+fn preview_value(value: &Value) -> Value {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    if text.chars().count() <= PREVIEW_CHARS {
+        return value.clone();
+    }
+    Value::String(preview(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_merges_tool_call_updates() {
+        let history = ToolCallHistory::new();
+        history.record(&json!({
+            "sessionUpdate": "tool_call",
+            "toolCallId": "call_1",
+            "title": "Edit main.rs",
+            "kind": "edit",
+            "status": "pending",
+            "locations": [{"path": "/src/main.rs"}, {"path": "/src/lib.rs", "line": 7}]
+        }));
+        history.record(&json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": "call_1",
+            "status": "completed",
+            "content": [
+                {"type": "diff", "path": "/src/main.rs", "oldText": "a", "newText": "b"},
+                {"type": "content", "content": {"type": "text", "text": "done"}}
+            ]
+        }));
+        history.record(&json!({"sessionUpdate": "agent_message_chunk", "toolCallId": "x"}));
+
+        let call = history.get("call_1").unwrap();
+        assert_eq!(call.title.as_deref(), Some("Edit main.rs"));
+        assert_eq!(call.status.as_deref(), Some("completed"));
+        assert_eq!(call.locations, vec!["/src/main.rs".to_string(), "/src/lib.rs".to_string()]);
+        assert_eq!(call.ranges[0].start_line, None);
+        assert_eq!((call.ranges[1].start_line, call.ranges[1].end_line), (Some(7), Some(7)));
+        assert_eq!(
+            call.content,
+            vec![
+                ArtifactBlock::Diff {
+                    path: "/src/main.rs".to_string(),
+                    old_text: Some("a".to_string()),
+                    new_text: "b".to_string(),
+                },
+                ArtifactBlock::Text {
+                    text: "done".to_string()
+                },
+            ]
+        );
+        assert!(history.get("x").is_none());
+    }
+
+    #[test]
+    fn test_running_calls_are_timed_until_finished() {
+        let history = ToolCallHistory::new();
+        let agent = Uuid::new_v4();
+        let call = |id: &str, status: &str| {
+            json!({"sessionUpdate": "tool_call_update", "toolCallId": id, "status": status})
+        };
+        history.record_at(&call("build", "in_progress"), 1_000);
+        history.record_at(&call("read", "pending"), 200_000);
+        history.record_at(&call("read", "completed"), 201_000);
+        history.record_at(&call("test", "in_progress"), 230_000);
+
+        let running = history.running_at(agent, 241_000);
+        let ids: Vec<&str> = running.iter().map(|c| c.tool_call_id.as_str()).collect();
+        assert_eq!(ids, vec!["build", "test"]);
+        assert_eq!(running[0].running_ms, 240_000);
+        assert_eq!(history.get("read").unwrap().finished_at, Some(201_000));
+
+        history.record_at(&call("build", "failed"), 250_000);
+        assert_eq!(history.running_at(agent, 250_000).len(), 1);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let history = ToolCallHistory::new();
+        for i in 0..MAX_TOOL_CALLS + 1 {
+            history.record(&json!({"sessionUpdate": "tool_call", "toolCallId": format!("call_{}", i)}));
+        }
+        assert!(history.get("call_0").is_none());
+        assert!(history.get(&format!("call_{}", MAX_TOOL_CALLS)).is_some());
+    }
+
+    #[test]
+    fn test_large_output_is_spooled_with_preview() {
+        let dir = std::env::temp_dir().join(format!("acptorio-spool-{}", uuid::Uuid::new_v4()));
+        let history = ToolCallHistory::spooled_to(dir.clone());
+        let log = "test passed\n".repeat(SPOOL_THRESHOLD_BYTES / 10);
+        history.record(&json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": "call/1",
+            "status": "completed",
+            "content": [{"type": "content", "content": {"type": "text", "text": log}}],
+            "rawOutput": {"stdout": log}
+        }));
+
+        let call = history.get("call/1").unwrap();
+        assert!(call.spooled.is_some());
+        match &call.content[0] {
+            ArtifactBlock::Text { text } => assert!(text.len() < PREVIEW_CHARS + 4),
+            block => panic!("unexpected block {:?}", block),
+        }
+        assert!(call.raw_output.unwrap().is_string());
+
+        // A later status-only update keeps the spooled output
+        history.record(&json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": "call/1",
+            "title": "cargo test"
+        }));
+        let output = history.output("call/1").unwrap().unwrap();
+        assert_eq!(output.content, vec![ArtifactBlock::Text { text: log.clone() }]);
+        assert_eq!(output.raw_output, Some(json!({"stdout": log})));
+
+        drop(history);
+        assert!(!dir.exists());
+    }
+}
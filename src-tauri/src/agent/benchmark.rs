@@ -0,0 +1,195 @@
+//! Latency benchmark of an agent provider: one freshly spawned agent runs a
+//! tiny standard prompt a few times, timing each phase so providers can be
+//! compared. Permission requests are always rejected, so a benchmark never
+//! changes files.
+
+use super::pool::PendingPermissions;
+use super::process::{AgentProcess, AgentProcessError, PermissionUserResponse};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Small enough that latency, not generation, dominates
+pub const BENCHMARK_PROMPT: &str = "Reply with the single word \"ok\". Do not use any tools.";
+
+/// A prompt taking longer than this fails the benchmark
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Timings of one prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkRun {
+    /// Until the first reply text arrived; none if the agent sent no text
+    pub first_token_ms: Option<u64>,
+    pub completion_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub min_ms: u64,
+    pub mean_ms: u64,
+    pub median_ms: u64,
+    pub max_ms: u64,
+}
+
+impl LatencyStats {
+    /// None for no samples
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        let median_ms = if sorted.len().is_multiple_of(2) {
+            (sorted[mid - 1] + sorted[mid]) / 2
+        } else {
+            sorted[mid]
+        };
+        Some(Self {
+            min_ms: sorted[0],
+            mean_ms: sorted.iter().sum::<u64>() / sorted.len() as u64,
+            median_ms,
+            max_ms: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub provider_id: Option<String>,
+    pub provider_name: Option<String>,
+    /// Starting the process, or connecting to a remote agent
+    pub spawn_ms: u64,
+    pub initialize_ms: u64,
+    pub session_ms: u64,
+    pub runs: Vec<BenchmarkRun>,
+    /// Over the runs where the agent sent text
+    pub first_token: Option<LatencyStats>,
+    pub completion: Option<LatencyStats>,
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// Benchmark `agent`, spawned `spawn` ago and not initialized yet. The
+/// agent is stopped afterwards, also when a phase fails.
+pub async fn run_benchmark(
+    mut agent: AgentProcess,
+    spawn: Duration,
+    iterations: usize,
+) -> Result<BenchmarkResult, AgentProcessError> {
+    let result = measure(&mut agent, spawn, iterations).await;
+    if let Err(e) = agent.stop().await {
+        tracing::warn!("Failed to stop benchmark agent: {}", e);
+    }
+    result
+}
+
+async fn measure(
+    agent: &mut AgentProcess,
+    spawn: Duration,
+    iterations: usize,
+) -> Result<BenchmarkResult, AgentProcessError> {
+    let started = Instant::now();
+    agent.initialize().await?;
+    let initialize = started.elapsed();
+
+    let started = Instant::now();
+    agent.create_session().await?;
+    let session = started.elapsed();
+
+    let mut runs = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        runs.push(measure_prompt(agent).await?);
+    }
+
+    let first_tokens: Vec<u64> = runs.iter().filter_map(|r| r.first_token_ms).collect();
+    let completions: Vec<u64> = runs.iter().map(|r| r.completion_ms).collect();
+    Ok(BenchmarkResult {
+        provider_id: agent.provider_id.clone(),
+        provider_name: agent.provider_name.clone(),
+        spawn_ms: millis(spawn),
+        initialize_ms: millis(initialize),
+        session_ms: millis(session),
+        first_token: LatencyStats::from_samples(&first_tokens),
+        completion: LatencyStats::from_samples(&completions),
+        runs,
+    })
+}
+
+async fn measure_prompt(agent: &mut AgentProcess) -> Result<BenchmarkRun, AgentProcessError> {
+    let agent_id = agent.id;
+    let pending = Arc::new(PendingPermissions::new());
+    let (update_tx, mut update_rx) = mpsc::channel(64);
+    let started = Instant::now();
+
+    let prompt = tokio::time::timeout(
+        PROMPT_TIMEOUT,
+        agent.send_prompt(BENCHMARK_PROMPT, update_tx, pending.clone()),
+    );
+    // The sender is dropped with the prompt, which ends this loop
+    let updates = async {
+        let mut first_token = None;
+        while let Some(update) = update_rx.recv().await {
+            match update.update_type.as_str() {
+                "agent_message_chunk" if first_token.is_none() => first_token = Some(started.elapsed()),
+                "permission_request" => {
+                    for request in pending.list().into_iter().filter(|p| p.agent_id == agent_id) {
+                        let _ = pending.respond(
+                            agent_id,
+                            &request.input_id,
+                            PermissionUserResponse {
+                                approved: false,
+                                option_id: None,
+                            },
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        first_token
+    };
+
+    let (response, first_token) = tokio::join!(prompt, updates);
+    response.map_err(|_| {
+        AgentProcessError::PromptFailed(format!("No response within {}s", PROMPT_TIMEOUT.as_secs()))
+    })??;
+    Ok(BenchmarkRun {
+        first_token_ms: first_token.map(millis),
+        completion_ms: millis(started.elapsed()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats() {
+        assert_eq!(LatencyStats::from_samples(&[]), None);
+        assert_eq!(
+            LatencyStats::from_samples(&[40, 10, 30, 20]),
+            Some(LatencyStats {
+                min_ms: 10,
+                mean_ms: 25,
+                median_ms: 25,
+                max_ms: 40,
+            })
+        );
+        assert_eq!(LatencyStats::from_samples(&[7]).unwrap().median_ms, 7);
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_rejects_permissions() {
+        // The demo agent asks to edit on every second turn
+        let agent = crate::agent::demo::connect_with_delay("demo".into(), "/tmp/project".into(), Duration::ZERO);
+        let result = run_benchmark(agent, Duration::from_millis(3), 2).await.unwrap();
+        assert_eq!(result.spawn_ms, 3);
+        assert_eq!(result.runs.len(), 2);
+        assert!(result.runs.iter().all(|r| r.first_token_ms.is_some()));
+        assert!(result.completion.is_some());
+    }
+}
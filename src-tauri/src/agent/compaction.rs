@@ -0,0 +1,99 @@
+//! Session compaction: shrinking an agent's conversation when it grows too
+//! large for the agent's context window.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Asks the agent to summarize its own session before it is replaced
+pub const SUMMARY_PROMPT: &str = "Summarize our conversation so far for a fresh session that will \
+continue this work. Include the goal, decisions made, files changed, and open tasks. \
+Reply with the summary only.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionMethod {
+    /// The agent compacted its own session via a `/compact`-style command
+    AgentCommand,
+    /// A new session was created and seeded with a summary of the old one
+    NewSession,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionRecord {
+    pub agent_id: Uuid,
+    pub method: CompactionMethod,
+    pub previous_session_id: Option<String>,
+    pub session_id: Option<String>,
+    pub summary: Option<String>,
+    pub created_at: u64,
+}
+
+/// Find the agent's compaction command among the ones it advertised
+pub fn find_compact_command(available_commands: &[String]) -> Option<String> {
+    available_commands
+        .iter()
+        .find(|c| c.trim_start_matches('/') == "compact")
+        .map(|c| format!("/{}", c.trim_start_matches('/')))
+}
+
+/// Build the first prompt of a replacement session
+pub fn seed_prompt(summary: &str) -> String {
+    format!(
+        "This session continues an earlier conversation that was compacted. \
+Summary of the earlier conversation:\n\n{}\n\nAcknowledge briefly and wait for the next instruction.",
+        summary
+    )
+}
+
+/// Build the first prompt of a cloned agent's session
+pub fn clone_seed_prompt(summary: &str) -> String {
+    format!(
+        "You are taking over from another agent working on the same task. \
+Summary of its conversation:\n\n{}\n\nAcknowledge briefly and wait for the next instruction.",
+        summary
+    )
+}
+
+/// Old-to-new session mappings produced by compaction, per agent
+pub struct SessionHistory {
+    records: DashMap<Uuid, Vec<CompactionRecord>>,
+}
+
+impl SessionHistory {
+    pub fn new() -> Self {
+        Self {
+            records: DashMap::new(),
+        }
+    }
+
+    pub fn record(&self, record: CompactionRecord) {
+        self.records.entry(record.agent_id).or_default().push(record);
+    }
+
+    pub fn for_agent(&self, agent_id: &Uuid) -> Vec<CompactionRecord> {
+        self.records
+            .get(agent_id)
+            .map(|r| r.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for SessionHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_compact_command() {
+        let commands = vec!["help".to_string(), "/compact".to_string()];
+        assert_eq!(find_compact_command(&commands), Some("/compact".to_string()));
+        assert_eq!(find_compact_command(&["compact".to_string()]), Some("/compact".to_string()));
+        assert_eq!(find_compact_command(&["compaction".to_string()]), None);
+    }
+}
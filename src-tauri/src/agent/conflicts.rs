@@ -0,0 +1,143 @@
+//! Post-hoc conflict report: tasks of different agents that ran at the same
+//! time and touched the same files. Unlike [`FileLocks`] this needs nothing
+//! from the agents while they work, so it also catches reads racing edits.
+//!
+//! [`FileLocks`]: super::locks::FileLocks
+
+use super::tasks::{TaskInfo, TaskStatus};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Two concurrent tasks of different agents and the files both touched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskConflict {
+    /// The earlier task by submission order
+    pub first_task_id: String,
+    pub first_agent_id: Uuid,
+    pub second_task_id: String,
+    pub second_agent_id: Uuid,
+    pub paths: Vec<String>,
+}
+
+impl TaskConflict {
+    /// The task `task_id` conflicts with, if it is one of the two
+    pub fn other(&self, task_id: &str) -> Option<&str> {
+        if self.first_task_id == task_id {
+            Some(&self.second_task_id)
+        } else if self.second_task_id == task_id {
+            Some(&self.first_task_id)
+        } else {
+            None
+        }
+    }
+}
+
+/// Seconds a task ran, still open while it runs
+fn window(task: &TaskInfo) -> Option<(u64, u64)> {
+    let start = task.started_at?;
+    let end = match task.status {
+        TaskStatus::Running => u64::MAX,
+        _ => task.finished_at?,
+    };
+    Some((start, end))
+}
+
+/// Timestamps have second precision, so tasks meeting within the same
+/// second count as concurrent
+fn overlaps(a: (u64, u64), b: (u64, u64)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// Conflicts between all pairs of `tasks`, in submission order
+pub fn find_conflicts(tasks: &[TaskInfo]) -> Vec<TaskConflict> {
+    let mut ran: Vec<(&TaskInfo, (u64, u64))> = tasks
+        .iter()
+        .filter(|t| !t.touched_files.is_empty())
+        .filter_map(|t| window(t).map(|w| (t, w)))
+        .collect();
+    ran.sort_by_key(|(t, _)| t.seq);
+
+    let mut conflicts = Vec::new();
+    for (i, (first, first_window)) in ran.iter().enumerate() {
+        for (second, second_window) in &ran[i + 1..] {
+            if first.agent_id == second.agent_id || !overlaps(*first_window, *second_window) {
+                continue;
+            }
+            let mut paths: Vec<String> = first
+                .touched_files
+                .iter()
+                .filter(|p| second.touched_files.contains(p))
+                .cloned()
+                .collect();
+            if paths.is_empty() {
+                continue;
+            }
+            paths.sort();
+            conflicts.push(TaskConflict {
+                first_task_id: first.id.clone(),
+                first_agent_id: first.agent_id,
+                second_task_id: second.id.clone(),
+                second_agent_id: second.agent_id,
+                paths,
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{TaskGraph, TaskSpec};
+
+    fn run(graph: &TaskGraph, agent_id: Uuid, files: &[&str]) -> String {
+        let (task, _) = graph
+            .add(TaskSpec {
+                agent_id,
+                prompt: "work".to_string(),
+                depends_on: vec![],
+                inject_results: false,
+                context: None,
+                priority: Default::default(),
+                preamble: None,
+            })
+            .unwrap();
+        graph.start(&task.id).unwrap();
+        for file in files {
+            graph.record_touch(&agent_id, file);
+        }
+        task.id
+    }
+
+    #[test]
+    fn test_overlapping_tasks_of_different_agents_conflict() {
+        let graph = TaskGraph::new();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let first = run(&graph, a, &["/p/lib.rs", "/p/main.rs"]);
+        let second = run(&graph, b, &["/p/main.rs", "/p/lib.rs", "/p/ui.ts"]);
+        run(&graph, c, &["/p/other.rs"]);
+
+        let conflicts = find_conflicts(&graph.snapshot().tasks);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].first_task_id, first);
+        assert_eq!(conflicts[0].second_task_id, second);
+        assert_eq!(conflicts[0].paths, vec!["/p/lib.rs", "/p/main.rs"]);
+        assert_eq!(conflicts[0].other(&second), Some(first.as_str()));
+    }
+
+    #[test]
+    fn test_tasks_apart_in_time_do_not_conflict() {
+        let graph = TaskGraph::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        run(&graph, a, &["/p/main.rs"]);
+        run(&graph, b, &["/p/main.rs"]);
+
+        let mut tasks = graph.snapshot().tasks;
+        for (i, task) in tasks.iter_mut().enumerate() {
+            task.status = TaskStatus::Completed;
+            task.started_at = Some(100 * i as u64);
+            task.finished_at = Some(100 * i as u64 + 10);
+        }
+        assert!(find_conflicts(&tasks).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! Packing project files into a prompt preamble.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Largest single file that will be attached
+pub const MAX_FILE_BYTES: usize = 100 * 1024;
+/// Total size of all attached files in one prompt
+pub const MAX_TOTAL_BYTES: usize = 400 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackedContext {
+    /// Text to prepend to the prompt
+    pub preamble: String,
+    /// Paths (as given) whose contents were included
+    pub included: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Read `paths` (relative ones resolved against `base`) and format them as a
+/// preamble, skipping files outside `base` and unreadable, binary, or
+/// over-budget files.
+pub async fn pack_files(base: &Path, paths: &[String]) -> PackedContext {
+    let mut packed = PackedContext::default();
+    let mut total = 0usize;
+    let mut sections = String::new();
+    let base = tokio::fs::canonicalize(base).await.ok();
+
+    for path in paths {
+        let skip = |reason: &str| SkippedFile {
+            path: path.clone(),
+            reason: reason.to_string(),
+        };
+        let Some(base) = &base else {
+            packed.skipped.push(skip("project directory not found"));
+            continue;
+        };
+
+        // Resolves `..` and symlinks so only files inside the project are read
+        let full_path = match tokio::fs::canonicalize(base.join(path)).await {
+            Ok(full_path) if full_path.starts_with(base) => full_path,
+            Ok(_) => {
+                packed.skipped.push(skip("outside the project"));
+                continue;
+            }
+            Err(e) => {
+                packed.skipped.push(skip(&e.to_string()));
+                continue;
+            }
+        };
+        let size = match tokio::fs::metadata(&full_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                packed.skipped.push(skip(&e.to_string()));
+                continue;
+            }
+        };
+        if size > MAX_FILE_BYTES as u64 {
+            packed.skipped.push(skip("file too large"));
+            continue;
+        }
+        if total as u64 + size > MAX_TOTAL_BYTES as u64 {
+            packed.skipped.push(skip("context budget exceeded"));
+            continue;
+        }
+
+        let bytes = match tokio::fs::read(&full_path).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                packed.skipped.push(skip(&e.to_string()));
+                continue;
+            }
+        };
+        if bytes.len() > MAX_FILE_BYTES || total + bytes.len() > MAX_TOTAL_BYTES {
+            packed.skipped.push(skip("file too large"));
+            continue;
+        }
+        let Ok(content) = String::from_utf8(bytes) else {
+            packed.skipped.push(skip("not a text file"));
+            continue;
+        };
+
+        total += content.len();
+        let lang = full_path
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_default();
+        sections.push_str(&format!("### {}\n```{}\n{}", path, lang, content));
+        if !content.ends_with('\n') {
+            sections.push('\n');
+        }
+        sections.push_str("```\n\n");
+        packed.included.push(path.clone());
+    }
+
+    if !packed.included.is_empty() {
+        packed.preamble = format!("Attached project files for context:\n\n{}", sections);
+    }
+    packed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pack_files_respects_budget() {
+        let dir = std::env::temp_dir().join(format!("acptorio-context-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.join("big.txt"), "x".repeat(MAX_FILE_BYTES + 1)).unwrap();
+        std::fs::write(dir.join("logo.png"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let paths = vec![
+            "main.rs".to_string(),
+            "big.txt".to_string(),
+            "logo.png".to_string(),
+            "missing.rs".to_string(),
+        ];
+        let packed = pack_files(&dir, &paths).await;
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(packed.included, vec!["main.rs".to_string()]);
+        assert_eq!(packed.skipped.len(), 3);
+        assert!(packed.preamble.contains("### main.rs\n```rs\nfn main() {}\n```"));
+    }
+
+    #[tokio::test]
+    async fn test_pack_files_stays_inside_base() {
+        let dir = std::env::temp_dir().join(format!("acptorio-context-{}", uuid::Uuid::new_v4()));
+        let project = dir.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.join("secret.txt"), "token").unwrap();
+        let secret = dir.join("secret.txt").to_string_lossy().to_string();
+
+        let paths = vec!["../secret.txt".to_string(), secret, "./main.rs".to_string()];
+        let packed = pack_files(&project, &paths).await;
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(packed.included, vec!["./main.rs".to_string()]);
+        assert!(packed.skipped.iter().all(|s| s.reason == "outside the project"));
+        assert!(!packed.preamble.contains("token"));
+    }
+
+    #[tokio::test]
+    async fn test_pack_nothing_has_empty_preamble() {
+        let packed = pack_files(Path::new("."), &[]).await;
+        assert!(packed.preamble.is_empty());
+    }
+}
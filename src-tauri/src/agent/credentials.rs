@@ -0,0 +1,54 @@
+//! API keys kept in the OS keychain and passed to locally spawned agents as
+//! environment variables.
+
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+const KEYCHAIN_SERVICE: &str = "acptorio";
+
+/// Variable the CLI of a provider reads its API key from, by provider id prefix
+const API_KEY_VARIABLES: &[(&str, &str)] = &[
+    ("claude", "ANTHROPIC_API_KEY"),
+    ("codex", "OPENAI_API_KEY"),
+    ("gemini", "GEMINI_API_KEY"),
+];
+
+static KEYCHAIN_VARIABLES: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Environment variable holding the API key of a provider, if known
+pub fn api_key_variable(provider_id: &str) -> Option<&'static str> {
+    API_KEY_VARIABLES
+        .iter()
+        .find(|(prefix, _)| provider_id.starts_with(prefix))
+        .map(|(_, variable)| *variable)
+}
+
+pub fn store_api_key(variable: &str, key: &str) -> Result<(), String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, variable)
+        .and_then(|entry| entry.set_password(key))
+        .map_err(|e| format!("Failed to store {} in the keychain: {}", variable, e))
+}
+
+pub fn load_api_key(variable: &str) -> Option<String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, variable)
+        .ok()?
+        .get_password()
+        .ok()
+}
+
+/// Set these variables from the keychain for agents spawned from now on
+pub fn set_keychain_variables(variables: &[String]) {
+    *KEYCHAIN_VARIABLES.write().unwrap() = variables.to_vec();
+}
+
+/// Keychain values of the configured variables; the app's own environment
+/// wins over the keychain
+pub fn keychain_env() -> Vec<(String, String)> {
+    KEYCHAIN_VARIABLES
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|variable| std::env::var_os(variable.as_str()).is_none())
+        .filter_map(|variable| Some((variable.clone(), load_api_key(variable)?)))
+        .collect()
+}
@@ -0,0 +1,110 @@
+//! Built-in demo agent: runs in-process on a canned script so the factory
+//! can be tried without Node, network access or API keys. It never touches
+//! the working directory.
+
+use super::process::AgentProcess;
+use crate::acp::scripted::{ScriptStep, ScriptedAgent};
+use crate::registry::{DEMO_AGENT_ID, DEMO_AGENT_NAME};
+use serde_json::json;
+use std::time::Duration;
+
+/// Gap between steps so updates arrive at a readable pace
+const STEP_DELAY: Duration = Duration::from_millis(600);
+
+/// Connect a new demo agent; initialize it like any other agent
+pub fn connect_demo_agent(name: String, working_directory: String) -> AgentProcess {
+    connect_with_delay(name, working_directory, STEP_DELAY)
+}
+
+pub(crate) fn connect_with_delay(name: String, working_directory: String, step_delay: Duration) -> AgentProcess {
+    let (stream, _handle) = demo_script(&working_directory, step_delay).connect();
+    let mut agent = AgentProcess::connect(name, working_directory, stream);
+    agent.provider_id = Some(DEMO_AGENT_ID.to_string());
+    agent.provider_name = Some(DEMO_AGENT_NAME.to_string());
+    agent
+}
+
+/// Script alternating between a turn that reads a file and one that asks to
+/// edit it, echoing the prompt each time
+fn demo_script(working_directory: &str, step_delay: Duration) -> ScriptedAgent {
+    let readme = format!("{}/README.md", working_directory.trim_end_matches('/'));
+    let pause = || ScriptStep::Pause(step_delay);
+
+    let read_turn = vec![
+        ScriptStep::message("Demo agent received: "),
+        ScriptStep::EchoPrompt,
+        pause(),
+        tool_call("demo-read", "Read README.md", "read", "in_progress", &readme),
+        pause(),
+        tool_call_update("demo-read", "completed"),
+        pause(),
+        ScriptStep::message("\n\nThis is a simulated reply. Spawn a real provider to have an agent work on your code."),
+    ];
+
+    let edit_turn = vec![
+        ScriptStep::message("Demo agent received: "),
+        ScriptStep::EchoPrompt,
+        pause(),
+        tool_call("demo-edit", "Edit README.md", "edit", "pending", &readme),
+        ScriptStep::RequestPermission(json!({
+            "toolCall": { "toolCallId": "demo-edit", "title": "Edit README.md", "kind": "edit" },
+            "options": [
+                { "optionId": "allow", "name": "Allow", "kind": "allow_once" },
+                { "optionId": "reject", "name": "Reject", "kind": "reject_once" },
+            ],
+        })),
+        pause(),
+        tool_call_update("demo-edit", "completed"),
+        ScriptStep::message("\n\nThat was a simulated permission request; no files were changed."),
+    ];
+
+    ScriptedAgent::new()
+        .session_id("demo-session")
+        .turn(read_turn)
+        .turn(edit_turn)
+        .repeat()
+}
+
+fn tool_call(id: &str, title: &str, kind: &str, status: &str, path: &str) -> ScriptStep {
+    ScriptStep::Update(json!({
+        "sessionUpdate": "tool_call",
+        "toolCallId": id,
+        "title": title,
+        "kind": kind,
+        "status": status,
+        "locations": [{ "path": path }],
+        "rawInput": { "file_path": path },
+    }))
+}
+
+fn tool_call_update(id: &str, status: &str) -> ScriptStep {
+    ScriptStep::Update(json!({
+        "sessionUpdate": "tool_call_update",
+        "toolCallId": id,
+        "status": status,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::PendingPermissions;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_demo_agent_echoes_prompt() {
+        let mut agent = connect_with_delay("demo".into(), "/tmp/project".into(), Duration::ZERO);
+        agent.initialize().await.unwrap();
+        assert_eq!(agent.create_session().await.unwrap(), "demo-session");
+
+        let (tx, _rx) = mpsc::channel(64);
+        let text = agent
+            .send_prompt("build the belt", tx, Arc::new(PendingPermissions::new()))
+            .await
+            .unwrap();
+        assert!(text.starts_with("Demo agent received: build the belt"));
+        assert!(agent.tool_calls.get("demo-read").is_some());
+        assert_eq!(agent.provider_id.as_deref(), Some(DEMO_AGENT_ID));
+    }
+}
@@ -0,0 +1,102 @@
+//! What an agent process was started with and what it negotiated, for
+//! telling why an agent works in a terminal but not here.
+
+use crate::diagnostics::redact_value;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// Stands in for the values of keychain variables
+const FROM_KEYCHAIN: &str = "[from keychain]";
+
+/// How an agent process was started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchInfo {
+    /// Command and arguments run, after wrapping for SSH or a container
+    pub command: String,
+    pub args: Vec<String>,
+    /// Directory the process started in; none over SSH, where it is a path
+    /// on the remote host given in the command line
+    pub working_directory: Option<String>,
+    pub pid: Option<u32>,
+    /// Variables set from the keychain on top of the app's environment
+    pub keychain_variables: Vec<String>,
+}
+
+/// What the agent answered to `initialize`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NegotiatedProtocol {
+    pub protocol_version: Option<Value>,
+    /// Name and version the agent reported about itself
+    pub agent_info: Option<Value>,
+    pub agent_capabilities: Option<Value>,
+}
+
+impl NegotiatedProtocol {
+    pub fn from_initialize_result(result: &Value) -> Self {
+        Self {
+            protocol_version: result.get("protocolVersion").cloned(),
+            agent_info: result.get("agentInfo").cloned(),
+            agent_capabilities: result.get("agentCapabilities").cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentEnvironment {
+    pub agent_id: Uuid,
+    pub name: String,
+    pub provider_id: Option<String>,
+    pub transport: Option<String>,
+    /// None for agents the app didn't start, e.g. reached over TCP
+    pub launch: Option<LaunchInfo>,
+    pub protocol: Option<NegotiatedProtocol>,
+    /// Environment a local process inherited, with secret-looking values
+    /// redacted; empty for agents on other machines
+    pub environment: BTreeMap<String, String>,
+}
+
+/// The app's environment as a process started now inherits it, plus the
+/// provider's variables and the keychain variables, with secret-looking
+/// values redacted
+pub fn sanitized_environment(
+    provider_env: &HashMap<String, String>,
+    keychain_variables: &[String],
+) -> BTreeMap<String, String> {
+    let mut vars: serde_json::Map<String, Value> = std::env::vars()
+        .chain(provider_env.iter().map(|(name, value)| (name.clone(), value.clone())))
+        .map(|(name, value)| (name, Value::String(value)))
+        .collect();
+    for name in keychain_variables {
+        vars.insert(name.clone(), Value::String(FROM_KEYCHAIN.to_string()));
+    }
+    let mut value = Value::Object(vars);
+    redact_value(&mut value);
+    match value {
+        Value::Object(vars) => vars
+            .into_iter()
+            .map(|(name, value)| (name, value.as_str().unwrap_or_default().to_string()))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitized_environment_hides_secrets() {
+        std::env::set_var("ACPTORIO_TEST_API_TOKEN", "sk-secret");
+        std::env::set_var("ACPTORIO_TEST_HOME", "/home/dev");
+
+        let provider_env = HashMap::from([("ACPTORIO_TEST_MODE".to_string(), "acp".to_string())]);
+        let vars = sanitized_environment(&provider_env, &["ANTHROPIC_API_KEY".to_string()]);
+        assert_eq!(vars["ACPTORIO_TEST_HOME"], "/home/dev");
+        assert_eq!(vars["ACPTORIO_TEST_MODE"], "acp");
+        assert_ne!(vars["ACPTORIO_TEST_API_TOKEN"], "sk-secret");
+        assert!(vars.contains_key("ANTHROPIC_API_KEY"));
+        assert_ne!(vars["ANTHROPIC_API_KEY"], FROM_KEYCHAIN);
+    }
+}
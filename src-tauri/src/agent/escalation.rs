@@ -0,0 +1,75 @@
+//! Aging of unanswered permission requests: reminders while they wait, and
+//! a configured default answer once they waited too long.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Reminders and default answers of all agents, forwarded to the frontend
+/// as agent events
+pub static PERMISSION_ESCALATIONS: Lazy<broadcast::Sender<PermissionEscalation>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+/// How often the pool looks for requests to escalate
+pub const ESCALATION_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Answer given to a request nobody answered in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultAction {
+    /// Keep waiting and reminding
+    #[default]
+    Wait,
+    Deny,
+    /// Never applied to requests reaching outside the project
+    Approve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscalationPolicy {
+    /// Minutes before the first reminder and between reminders; none for
+    /// no reminders
+    #[serde(default)]
+    pub remind_after_mins: Option<u64>,
+    /// Minutes after which `default_action` answers the request
+    #[serde(default)]
+    pub default_action_after_mins: Option<u64>,
+    #[serde(default)]
+    pub default_action: DefaultAction,
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self {
+            remind_after_mins: Some(5),
+            default_action_after_mins: None,
+            default_action: DefaultAction::Wait,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EscalationKind {
+    Reminder,
+    /// The default action answered the request
+    DefaultApplied,
+}
+
+/// A request that waited long enough to be escalated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionEscalation {
+    pub agent_id: Uuid,
+    pub input_id: String,
+    pub message: String,
+    pub kind: EscalationKind,
+    /// Seconds the request has been waiting
+    pub age_secs: u64,
+    /// Reminders sent for it so far
+    pub reminders: u32,
+    /// The answer given, for `DefaultApplied`
+    #[serde(default)]
+    pub approved: Option<bool>,
+}
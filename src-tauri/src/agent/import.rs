@@ -0,0 +1,348 @@
+//! Importing conversations recorded by other CLI agents (currently the
+//! `claude` CLI) so their work can be continued inside the factory.
+
+use crate::clock::now_secs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use uuid::Uuid;
+
+/// Budget for the transcript included in a seed prompt; older messages are
+/// dropped first
+const MAX_SEED_CHARS: usize = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    ClaudeCode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageRole {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportedMessage {
+    pub role: MessageRole,
+    pub text: String,
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedConversation {
+    pub id: String,
+    pub source: ImportSource,
+    pub source_path: String,
+    /// Session id in the source tool, usable with `session/load` by agents
+    /// that share its session store
+    pub session_id: Option<String>,
+    pub cwd: Option<String>,
+    pub title: String,
+    pub messages: Vec<ImportedMessage>,
+    pub imported_at: u64,
+}
+
+/// How an agent picked up an imported conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContinuationMethod {
+    /// The agent resumed the original session via `session/load`
+    SessionLoad,
+    /// The agent's session was seeded with the conversation transcript
+    SeedPrompt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationContinuation {
+    pub conversation_id: String,
+    pub agent_id: Uuid,
+    pub method: ContinuationMethod,
+    /// The agent's reply to the seed prompt
+    pub response: Option<String>,
+}
+
+/// A session file found on disk that can be imported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CliSessionFile {
+    pub path: String,
+    pub session_id: String,
+    /// Directory under the CLI's projects folder the session belongs to
+    pub project: String,
+    pub modified_at: u64,
+    pub size: u64,
+}
+
+/// Parse a `claude` CLI session JSONL file.
+///
+/// Only the readable conversation is kept: tool calls are reduced to a short
+/// marker, tool results and bookkeeping entries are dropped.
+pub fn parse_claude_session(content: &str, source_path: &str) -> Result<ImportedConversation, String> {
+    let mut conversation = ImportedConversation {
+        id: Uuid::new_v4().to_string(),
+        source: ImportSource::ClaudeCode,
+        source_path: source_path.to_string(),
+        session_id: None,
+        cwd: None,
+        title: String::new(),
+        messages: Vec::new(),
+        imported_at: now_secs(),
+    };
+    let mut summary = None;
+
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let str_field = |key: &str| entry.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        if conversation.session_id.is_none() {
+            conversation.session_id = str_field("sessionId");
+        }
+        if conversation.cwd.is_none() {
+            conversation.cwd = str_field("cwd");
+        }
+
+        let role = match entry.get("type").and_then(|t| t.as_str()) {
+            Some("user") => MessageRole::User,
+            Some("assistant") => MessageRole::Assistant,
+            Some("summary") => {
+                summary = str_field("summary");
+                continue;
+            }
+            _ => continue,
+        };
+        // Sidechain entries belong to sub-agents, not the main conversation
+        if entry.get("isSidechain").and_then(|v| v.as_bool()) == Some(true) {
+            continue;
+        }
+
+        let text = entry
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .map(content_text)
+            .unwrap_or_default();
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        // Assistant replies are streamed as several entries; merge them
+        match conversation.messages.last_mut() {
+            Some(last) if last.role == role && role == MessageRole::Assistant => {
+                last.text.push_str("\n\n");
+                last.text.push_str(&text);
+            }
+            _ => conversation.messages.push(ImportedMessage {
+                role,
+                text,
+                timestamp: str_field("timestamp"),
+            }),
+        }
+    }
+
+    if conversation.messages.is_empty() {
+        return Err(format!("No conversation messages found in {}", source_path));
+    }
+
+    conversation.title = summary.unwrap_or_else(|| {
+        let first = conversation
+            .messages
+            .iter()
+            .find(|m| m.role == MessageRole::User)
+            .map(|m| m.text.as_str())
+            .unwrap_or_default();
+        first.lines().next().unwrap_or_default().chars().take(80).collect()
+    });
+    Ok(conversation)
+}
+
+/// Text of a message `content`, which is either a string or a list of blocks
+fn content_text(content: &Value) -> String {
+    if let Some(text) = content.as_str() {
+        return text.to_string();
+    }
+    let Some(blocks) = content.as_array() else {
+        return String::new();
+    };
+
+    blocks
+        .iter()
+        .filter_map(|block| match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => block.get("text").and_then(|t| t.as_str()).map(String::from),
+            Some("tool_use") => Some(format!(
+                "[tool: {}]",
+                block.get("name").and_then(|n| n.as_str()).unwrap_or("unknown")
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Directory where the `claude` CLI keeps session files
+pub fn claude_projects_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".claude").join("projects"))
+}
+
+/// The CLI names a project's folder after its path with separators replaced
+fn claude_project_folder(project_path: &Path) -> String {
+    project_path
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect()
+}
+
+/// Session files under `projects_dir`, newest first, optionally limited to
+/// the sessions of one project
+pub fn find_claude_sessions(projects_dir: &Path, project_path: Option<&Path>) -> Vec<CliSessionFile> {
+    let folder = project_path.map(claude_project_folder);
+    let Ok(projects) = fs::read_dir(projects_dir) else {
+        return Vec::new();
+    };
+
+    let mut sessions = Vec::new();
+    for project in projects.flatten() {
+        let project_name = project.file_name().to_string_lossy().to_string();
+        if folder.as_ref().is_some_and(|f| *f != project_name) {
+            continue;
+        }
+        let Ok(files) = fs::read_dir(project.path()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Ok(metadata) = file.metadata() else {
+                continue;
+            };
+            sessions.push(CliSessionFile {
+                path: path.to_string_lossy().to_string(),
+                session_id: path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                project: project_name.clone(),
+                modified_at: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                size: metadata.len(),
+            });
+        }
+    }
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.modified_at));
+    sessions
+}
+
+/// First prompt of a session that continues an imported conversation
+pub fn import_seed_prompt(conversation: &ImportedConversation) -> String {
+    let mut transcript = Vec::new();
+    let mut used = 0;
+    for message in conversation.messages.iter().rev() {
+        let speaker = match message.role {
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+        };
+        let entry = format!("{}: {}", speaker, message.text);
+        if used + entry.len() > MAX_SEED_CHARS && !transcript.is_empty() {
+            break;
+        }
+        used += entry.len();
+        transcript.push(entry);
+    }
+    transcript.reverse();
+
+    let omitted = conversation.messages.len() - transcript.len();
+    let note = if omitted > 0 {
+        format!("({} earlier messages omitted)\n\n", omitted)
+    } else {
+        String::new()
+    };
+
+    format!(
+        "This session continues a conversation started in another tool ({}). \
+Transcript of that conversation:\n\n{}{}\n\nAcknowledge briefly and wait for the next instruction.",
+        conversation.title,
+        note,
+        transcript.join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &str = r#"{"type":"summary","summary":"Fix flaky watcher test","leafUuid":"x"}
+{"type":"user","sessionId":"abc-123","cwd":"/work/app","timestamp":"2025-01-01T00:00:00Z","message":{"role":"user","content":"The watcher test is flaky"}}
+{"type":"assistant","sessionId":"abc-123","message":{"role":"assistant","content":[{"type":"text","text":"Let me look."},{"type":"tool_use","id":"t1","name":"Read","input":{}}]}}
+{"type":"user","sessionId":"abc-123","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"..."}]}}
+{"type":"assistant","sessionId":"abc-123","message":{"role":"assistant","content":[{"type":"text","text":"Fixed the race."}]}}
+{"type":"user","isSidechain":true,"message":{"role":"user","content":"sub-agent prompt"}}
+not json
+"#;
+
+    #[test]
+    fn test_parse_claude_session() {
+        let conversation = parse_claude_session(SESSION, "/tmp/abc-123.jsonl").unwrap();
+
+        assert_eq!(conversation.session_id.as_deref(), Some("abc-123"));
+        assert_eq!(conversation.cwd.as_deref(), Some("/work/app"));
+        assert_eq!(conversation.title, "Fix flaky watcher test");
+        assert_eq!(conversation.messages.len(), 2);
+        assert_eq!(conversation.messages[0].text, "The watcher test is flaky");
+        assert_eq!(
+            conversation.messages[1].text,
+            "Let me look.\n[tool: Read]\n\nFixed the race."
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_empty_session() {
+        assert!(parse_claude_session("{\"type\":\"summary\"}\n", "empty.jsonl").is_err());
+    }
+
+    #[test]
+    fn test_seed_prompt_keeps_latest_messages() {
+        let mut conversation = parse_claude_session(SESSION, "s.jsonl").unwrap();
+        conversation.messages.insert(
+            0,
+            ImportedMessage {
+                role: MessageRole::User,
+                text: "x".repeat(MAX_SEED_CHARS),
+                timestamp: None,
+            },
+        );
+
+        let prompt = import_seed_prompt(&conversation);
+        assert!(prompt.contains("(1 earlier messages omitted)"));
+        assert!(prompt.contains("Assistant: Let me look."));
+    }
+
+    #[test]
+    fn test_find_claude_sessions_filters_by_project() {
+        let dir = std::env::temp_dir().join(format!("acptorio-import-{}", Uuid::new_v4()));
+        let project = dir.join("-work-app");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(dir.join("-other")).unwrap();
+        fs::write(project.join("abc-123.jsonl"), SESSION).unwrap();
+        fs::write(project.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("-other").join("def.jsonl"), SESSION).unwrap();
+
+        let sessions = find_claude_sessions(&dir, Some(Path::new("/work/app")));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "abc-123");
+        assert_eq!(find_claude_sessions(&dir, None).len(), 2);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
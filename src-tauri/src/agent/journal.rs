@@ -0,0 +1,162 @@
+//! Journal of prompts in flight, so prompts cut off by a crash of the app
+//! can be found and sent again after a restart.
+
+use crate::clock::now_secs;
+use crate::state::app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const JOURNAL_FILE: &str = "prompt_journal.json";
+
+/// A prompt an agent was working on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    /// Id of the agent in the run that wrote the entry
+    pub agent_id: Uuid,
+    /// Agent ids don't survive restarts, names do
+    pub agent_name: String,
+    pub provider_id: Option<String>,
+    pub working_directory: String,
+    pub prompt: String,
+    pub started_at: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    /// Left over from an earlier run of the app
+    interrupted: Vec<JournalEntry>,
+    running: HashMap<String, JournalEntry>,
+}
+
+pub struct PromptJournal {
+    entries: Mutex<Entries>,
+    storage_path: PathBuf,
+}
+
+impl PromptJournal {
+    /// Open the journal; entries still in it were interrupted
+    pub fn new() -> Self {
+        let app_dir = app_data_dir();
+        fs::create_dir_all(&app_dir).ok();
+        Self::at(app_dir.join(JOURNAL_FILE))
+    }
+
+    pub fn at(storage_path: PathBuf) -> Self {
+        let interrupted = Self::load_from_file(&storage_path).unwrap_or_default();
+        Self {
+            entries: Mutex::new(Entries {
+                interrupted,
+                running: HashMap::new(),
+            }),
+            storage_path,
+        }
+    }
+
+    fn load_from_file(path: &Path) -> Option<Vec<JournalEntry>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Interrupted and running entries go to disk, so a second crash before
+    /// the interrupted ones were handled loses nothing
+    fn save(&self, entries: &Entries) {
+        let all: Vec<&JournalEntry> =
+            entries.interrupted.iter().chain(entries.running.values()).collect();
+        let written = serde_json::to_string(&all)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.storage_path, content).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write prompt journal: {}", e);
+        }
+    }
+
+    /// Record a prompt that starts now; returns the entry's id
+    pub fn start(
+        &self,
+        agent_id: Uuid,
+        agent_name: &str,
+        provider_id: Option<String>,
+        working_directory: &str,
+        prompt: &str,
+    ) -> String {
+        let entry = JournalEntry {
+            id: Uuid::new_v4().to_string(),
+            agent_id,
+            agent_name: agent_name.to_string(),
+            provider_id,
+            working_directory: working_directory.to_string(),
+            prompt: prompt.to_string(),
+            started_at: now_secs(),
+        };
+        let id = entry.id.clone();
+        let mut entries = self.entries.lock().unwrap();
+        entries.running.insert(id.clone(), entry);
+        self.save(&entries);
+        id
+    }
+
+    /// The prompt finished, successfully or not
+    pub fn finish(&self, id: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.running.remove(id).is_some() {
+            self.save(&entries);
+        }
+    }
+
+    /// Prompts a crash of an earlier run cut off, oldest first
+    pub fn interrupted(&self) -> Vec<JournalEntry> {
+        let mut interrupted = self.entries.lock().unwrap().interrupted.clone();
+        interrupted.sort_by_key(|e| e.started_at);
+        interrupted
+    }
+
+    /// Forget an interrupted prompt, e.g. once it was sent again
+    pub fn dismiss(&self, id: &str) -> Option<JournalEntry> {
+        let mut entries = self.entries.lock().unwrap();
+        let index = entries.interrupted.iter().position(|e| e.id == id)?;
+        let entry = entries.interrupted.remove(index);
+        self.save(&entries);
+        Some(entry)
+    }
+}
+
+impl Default for PromptJournal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfinished_prompts_are_interrupted_after_reopening() {
+        let path = std::env::temp_dir().join(format!("acptorio-journal-{}.json", Uuid::new_v4()));
+        let agent = Uuid::new_v4();
+
+        let journal = PromptJournal::at(path.clone());
+        let done = journal.start(agent, "Builder", None, "/work/app", "add tests");
+        journal.start(agent, "Builder", Some("claude".to_string()), "/work/app", "fix the build");
+        journal.finish(&done);
+        assert!(journal.interrupted().is_empty());
+
+        let reopened = PromptJournal::at(path.clone());
+        let interrupted = reopened.interrupted();
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].prompt, "fix the build");
+        assert_eq!(interrupted[0].agent_name, "Builder");
+
+        // Still there if the app crashes again before it was handled
+        assert_eq!(PromptJournal::at(path.clone()).interrupted().len(), 1);
+        assert!(reopened.dismiss(&interrupted[0].id).is_some());
+        assert!(PromptJournal::at(path.clone()).interrupted().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,298 @@
+//! Comparison of providers and factory placements by the tasks their
+//! agents finished: how fast, how often successfully, how often the
+//! verification passed and how many tokens a completed task took.
+
+use super::tasks::{TaskInfo, TaskStatus};
+use super::verification::VerificationStatus;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+/// Stop reasons of a turn the agent could not finish properly
+const ERROR_STOP_REASONS: &[&str] = &["max_tokens", "max_turn_requests", "refusal"];
+
+/// Provider label of agents spawned without an explicit provider
+const DEFAULT_PROVIDER: &str = "default";
+
+/// How far back finished tasks count
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardRange {
+    Hour,
+    Day,
+    Week,
+    #[default]
+    All,
+}
+
+impl LeaderboardRange {
+    fn secs(self) -> Option<u64> {
+        match self {
+            LeaderboardRange::Hour => Some(3600),
+            LeaderboardRange::Day => Some(24 * 3600),
+            LeaderboardRange::Week => Some(7 * 24 * 3600),
+            LeaderboardRange::All => None,
+        }
+    }
+
+    /// Start of the range ending at `now`, in seconds
+    pub fn since(self, now: u64) -> u64 {
+        self.secs().map_or(0, |secs| now.saturating_sub(secs))
+    }
+}
+
+/// Placement an agent stands on
+#[derive(Debug, Clone)]
+pub struct PlacementRef {
+    pub agent_id: String,
+    pub name: Option<String>,
+}
+
+/// Where an agent's tasks are counted
+#[derive(Debug, Clone, Default)]
+pub struct AgentOwner {
+    pub provider_id: Option<String>,
+    pub placement: Option<PlacementRef>,
+    /// Tokens the agent used over its whole life
+    pub tokens_used: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    /// Provider id, or the agent id of the placement
+    pub key: String,
+    pub label: String,
+    /// Tasks finished within the range
+    pub tasks: usize,
+    pub avg_prompt_secs: Option<f64>,
+    /// Share of tasks ending with a non-error stop reason
+    pub success_rate: Option<f64>,
+    /// Share of verified tasks whose verification passed
+    pub verification_pass_rate: Option<f64>,
+    /// Tokens of the agents over all their tasks, per completed task in
+    /// the range; tokens are what the budget is counted in
+    pub tokens_per_completed_task: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub range: LeaderboardRange,
+    /// Best success rate first
+    pub providers: Vec<LeaderboardEntry>,
+    pub placements: Vec<LeaderboardEntry>,
+}
+
+#[derive(Default)]
+struct Tally {
+    label: String,
+    tasks: usize,
+    durations: Vec<u64>,
+    succeeded: usize,
+    verified: usize,
+    verification_passed: usize,
+    agents: HashSet<Uuid>,
+}
+
+impl Tally {
+    fn add(&mut self, task: &TaskInfo) {
+        self.tasks += 1;
+        self.agents.insert(task.agent_id);
+        if let (Some(start), Some(end)) = (task.started_at, task.finished_at) {
+            self.durations.push(end.saturating_sub(start));
+        }
+        if is_success(task) {
+            self.succeeded += 1;
+        }
+        match task.verification.as_ref().map(|v| v.status) {
+            Some(VerificationStatus::Passed) => {
+                self.verified += 1;
+                self.verification_passed += 1;
+            }
+            Some(VerificationStatus::Failed) => self.verified += 1,
+            _ => {}
+        }
+    }
+
+    fn into_entry(self, key: String, owners: &HashMap<Uuid, AgentOwner>) -> LeaderboardEntry {
+        let rate = |part: usize, whole: usize| (whole > 0).then(|| part as f64 / whole as f64);
+        let tokens: u64 = self
+            .agents
+            .iter()
+            .filter_map(|id| owners.get(id))
+            .map(|owner| owner.tokens_used)
+            .sum();
+        LeaderboardEntry {
+            key,
+            label: self.label,
+            tasks: self.tasks,
+            avg_prompt_secs: (!self.durations.is_empty())
+                .then(|| self.durations.iter().sum::<u64>() as f64 / self.durations.len() as f64),
+            success_rate: rate(self.succeeded, self.tasks),
+            verification_pass_rate: rate(self.verification_passed, self.verified),
+            tokens_per_completed_task: (self.succeeded > 0)
+                .then(|| tokens as f64 / self.succeeded as f64),
+        }
+    }
+}
+
+fn is_success(task: &TaskInfo) -> bool {
+    task.status == TaskStatus::Completed
+        && !task
+            .stop_reason
+            .as_deref()
+            .is_some_and(|reason| ERROR_STOP_REASONS.contains(&reason))
+}
+
+fn ranked(
+    tallies: BTreeMap<String, Tally>,
+    owners: &HashMap<Uuid, AgentOwner>,
+) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = tallies
+        .into_iter()
+        .map(|(key, tally)| tally.into_entry(key, owners))
+        .collect();
+    entries.sort_by(|a, b| {
+        b.success_rate
+            .unwrap_or(0.0)
+            .total_cmp(&a.success_rate.unwrap_or(0.0))
+            .then(b.tasks.cmp(&a.tasks))
+    });
+    entries
+}
+
+/// Rank providers and placements by the tasks finished after `now` minus
+/// the range. Tasks that never ran and tasks of unknown agents are skipped.
+pub fn build_leaderboard(
+    tasks: &[TaskInfo],
+    owners: &HashMap<Uuid, AgentOwner>,
+    range: LeaderboardRange,
+    now: u64,
+) -> Leaderboard {
+    let since = range.secs().map(|secs| now.saturating_sub(secs));
+    let mut providers: BTreeMap<String, Tally> = BTreeMap::new();
+    let mut placements: BTreeMap<String, Tally> = BTreeMap::new();
+
+    for task in tasks {
+        if !matches!(task.status, TaskStatus::Completed | TaskStatus::Failed) {
+            continue;
+        }
+        let Some(finished_at) = task.finished_at else {
+            continue;
+        };
+        if since.is_some_and(|since| finished_at < since) {
+            continue;
+        }
+        let Some(owner) = owners.get(&task.agent_id) else {
+            continue;
+        };
+
+        let provider = owner.provider_id.clone().unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+        let tally = providers.entry(provider.clone()).or_default();
+        tally.label = provider;
+        tally.add(task);
+
+        if let Some(placement) = &owner.placement {
+            let tally = placements.entry(placement.agent_id.clone()).or_default();
+            tally.label = placement.name.clone().unwrap_or_else(|| placement.agent_id.clone());
+            tally.add(task);
+        }
+    }
+
+    Leaderboard {
+        range,
+        providers: ranked(providers, owners),
+        placements: ranked(placements, owners),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{TaskGraph, TaskSpec, TaskVerification};
+
+    fn run(
+        graph: &TaskGraph,
+        agent_id: Uuid,
+        outcome: Result<&str, &str>,
+        stop_reason: Option<&str>,
+    ) -> String {
+        let (task, _) = graph
+            .add(TaskSpec {
+                agent_id,
+                prompt: "work".to_string(),
+                depends_on: vec![],
+                inject_results: false,
+                context: None,
+                priority: Default::default(),
+                preamble: None,
+            })
+            .unwrap();
+        graph.start(&task.id).unwrap();
+        graph.set_stop_reason(&task.id, stop_reason.map(String::from));
+        graph.finish(&task.id, outcome.map(String::from).map_err(String::from));
+        task.id
+    }
+
+    #[test]
+    fn test_build_leaderboard_groups_and_ranks() {
+        let graph = TaskGraph::new();
+        let (fast, slow) = (Uuid::new_v4(), Uuid::new_v4());
+        let owners = HashMap::from([
+            (
+                fast,
+                AgentOwner {
+                    provider_id: Some("claude".to_string()),
+                    placement: Some(PlacementRef {
+                        agent_id: fast.to_string(),
+                        name: Some("Builder".to_string()),
+                    }),
+                    tokens_used: 900,
+                },
+            ),
+            (slow, AgentOwner { tokens_used: 100, ..Default::default() }),
+        ]);
+
+        let verified = run(&graph, fast, Ok("done"), Some("end_turn"));
+        graph.set_verification(
+            &verified,
+            TaskVerification {
+                status: VerificationStatus::Passed,
+                command: "cargo test".to_string(),
+                run: None,
+                follow_up_task_id: None,
+            },
+        );
+        run(&graph, fast, Ok("done"), Some("end_turn"));
+        run(&graph, fast, Ok("done"), Some("end_turn"));
+        run(&graph, slow, Ok("no"), Some("refusal"));
+        run(&graph, slow, Err("crashed"), None);
+        let old = run(&graph, slow, Ok("done"), Some("end_turn"));
+
+        let mut tasks = graph.snapshot().tasks;
+        let now = tasks[0].finished_at.unwrap();
+        // Too old for the hour range
+        tasks.iter_mut().find(|t| t.id == old).unwrap().finished_at = Some(now - 7200);
+
+        let board = build_leaderboard(&tasks, &owners, LeaderboardRange::Hour, now);
+        assert_eq!(board.providers.len(), 2);
+        let claude = &board.providers[0];
+        assert_eq!(claude.key, "claude");
+        assert_eq!(claude.tasks, 3);
+        assert_eq!(claude.success_rate, Some(1.0));
+        assert_eq!(claude.verification_pass_rate, Some(1.0));
+        assert_eq!(claude.tokens_per_completed_task, Some(300.0));
+
+        let default = &board.providers[1];
+        assert_eq!(default.key, DEFAULT_PROVIDER);
+        assert_eq!(default.tasks, 2);
+        assert_eq!(default.success_rate, Some(0.0));
+        assert_eq!(default.verification_pass_rate, None);
+        assert_eq!(default.tokens_per_completed_task, None);
+
+        assert_eq!(board.placements.len(), 1);
+        assert_eq!(board.placements[0].label, "Builder");
+
+        let all = build_leaderboard(&tasks, &owners, LeaderboardRange::All, now);
+        assert_eq!(all.providers[1].tasks, 3);
+    }
+}
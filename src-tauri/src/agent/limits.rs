@@ -0,0 +1,214 @@
+//! Per-agent CPU and memory limits. Sandboxed agents have them enforced by
+//! the container runtime; other agents are watched by sampling their
+//! process tree with `ps`, and are killed once they exceed the memory limit.
+
+use crate::clock::now_ms;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Limit breaches of all agents, forwarded to the frontend as agent events
+pub static LIMIT_BREACHES: Lazy<broadcast::Sender<LimitBreach>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// Consecutive samples over the CPU limit before it is reported
+const CPU_BREACH_SAMPLES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Resident memory of the agent and its child processes
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// CPU cores the agent may keep busy
+    #[serde(default)]
+    pub cpus: Option<f64>,
+}
+
+impl ResourceLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.memory_mb.is_none() && self.cpus.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitedResource {
+    Memory,
+    Cpu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitBreach {
+    pub agent_id: Uuid,
+    pub resource: LimitedResource,
+    /// MB for memory, cores for CPU
+    pub limit: f64,
+    pub observed: f64,
+    /// Whether the agent was killed for it
+    pub killed: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessSample {
+    pid: u32,
+    ppid: u32,
+    rss_kb: u64,
+    cpu_secs: f64,
+}
+
+/// Parse `ps -A -o pid= -o ppid= -o rss= -o time=`
+fn parse_ps(output: &str) -> Vec<ProcessSample> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(ProcessSample {
+                pid: fields.next()?.parse().ok()?,
+                ppid: fields.next()?.parse().ok()?,
+                rss_kb: fields.next()?.parse().ok()?,
+                cpu_secs: parse_cpu_time(fields.next()?)?,
+            })
+        })
+        .collect()
+}
+
+/// `[dd-][hh:]mm:ss[.ff]` as printed by procps and BSD `ps`
+fn parse_cpu_time(value: &str) -> Option<f64> {
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (days.parse::<f64>().ok()?, clock),
+        None => (0.0, value),
+    };
+    let mut secs = 0.0;
+    for part in clock.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(days * 86_400.0 + secs)
+}
+
+/// Pids of `root` and all its descendants
+fn process_tree(samples: &[ProcessSample], root: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for sample in samples {
+        children.entry(sample.ppid).or_default().push(sample.pid);
+    }
+    let mut tree = vec![root];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(kids) = children.get(&tree[i]) {
+            tree.extend(kids.iter().filter(|&&pid| pid != root));
+        }
+        i += 1;
+    }
+    tree
+}
+
+async fn sample_processes() -> Option<Vec<ProcessSample>> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=", "-o", "ppid=", "-o", "rss=", "-o", "time="])
+        .output()
+        .await
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| parse_ps(&String::from_utf8_lossy(&output.stdout)))
+}
+
+async fn kill_tree(pids: &[u32]) {
+    let pids: Vec<String> = pids.iter().map(|p| p.to_string()).collect();
+    if let Err(e) = Command::new("kill").arg("-9").args(&pids).output().await {
+        tracing::warn!("Failed to kill agent processes {:?}: {}", pids, e);
+    }
+}
+
+fn report(agent_id: Uuid, resource: LimitedResource, limit: f64, observed: f64, killed: bool) {
+    tracing::warn!(
+        "Agent {} exceeded its {:?} limit: {:.1} > {:.1}",
+        agent_id,
+        resource,
+        observed,
+        limit
+    );
+    // No subscribers is fine
+    let _ = LIMIT_BREACHES.send(LimitBreach {
+        agent_id,
+        resource,
+        limit,
+        observed,
+        killed,
+        timestamp: now_ms(),
+    });
+}
+
+/// Watch the process tree rooted at `pid` until it exits. Exceeding the
+/// memory limit kills the tree; exceeding the CPU limit for a while is only
+/// reported, as throttling needs a container.
+pub fn watch(agent_id: Uuid, pid: u32, limits: ResourceLimits) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_cpu: Option<(f64, Instant)> = None;
+        let mut cpu_over = 0;
+        loop {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+            let Some(samples) = sample_processes().await else {
+                tracing::warn!("Cannot sample processes; resource limits of agent {} are not enforced", agent_id);
+                return;
+            };
+            if !samples.iter().any(|s| s.pid == pid) {
+                return;
+            }
+            let tree = process_tree(&samples, pid);
+            let in_tree = || samples.iter().filter(|s| tree.contains(&s.pid));
+            let rss_mb = in_tree().map(|s| s.rss_kb).sum::<u64>() as f64 / 1024.0;
+            let cpu_secs: f64 = in_tree().map(|s| s.cpu_secs).sum();
+
+            if let Some(limit) = limits.memory_mb {
+                if rss_mb > limit as f64 {
+                    kill_tree(&tree).await;
+                    report(agent_id, LimitedResource::Memory, limit as f64, rss_mb, true);
+                    return;
+                }
+            }
+
+            let now = Instant::now();
+            if let (Some(limit), Some((last_secs, last_at))) = (limits.cpus, last_cpu) {
+                let cores = (cpu_secs - last_secs).max(0.0) / now.duration_since(last_at).as_secs_f64();
+                if cores > limit {
+                    cpu_over += 1;
+                    // Once per episode of sustained load
+                    if cpu_over == CPU_BREACH_SAMPLES {
+                        report(agent_id, LimitedResource::Cpu, limit, cores, false);
+                    }
+                } else {
+                    cpu_over = 0;
+                }
+            }
+            last_cpu = Some((cpu_secs, now));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ps_and_process_tree() {
+        let output = "    1     0  1000 00:00:01\n  100     1  2048 01:02:03\n  101   100  1024 1-00:00:00\n  102     1   512 0:01.50\n";
+        let samples = parse_ps(output);
+        assert_eq!(samples.len(), 4);
+        assert_eq!(samples[1].cpu_secs, 3723.0);
+        assert_eq!(samples[2].cpu_secs, 86_400.0);
+        assert_eq!(samples[3].cpu_secs, 1.5);
+
+        let mut tree = process_tree(&samples, 100);
+        tree.sort();
+        assert_eq!(tree, vec![100, 101]);
+    }
+}
@@ -0,0 +1,308 @@
+//! Advisory locks on files agents are editing, so two agents do not
+//! silently overwrite each other. A tool call that edits a file locks it
+//! until the call finishes; another agent touching the file is reported as
+//! a conflict and its permission request can be held until the lock is gone.
+
+use crate::clock::now_secs;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Conflicts of all agents, forwarded to the frontend
+pub static FILE_CONFLICTS: Lazy<broadcast::Sender<FileConflict>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+/// Tool call kinds that change the files they point at
+const EDIT_KINDS: &[&str] = &["edit", "delete", "move"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileLock {
+    pub path: String,
+    pub agent_id: Uuid,
+    pub tool_call_id: String,
+    pub acquired_at: u64,
+}
+
+/// An agent touching a file another agent is editing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileConflict {
+    pub path: String,
+    pub agent_id: Uuid,
+    pub tool_call_id: String,
+    pub holder: FileLock,
+    /// The agent's permission request waits for the holder to finish
+    pub held: bool,
+    pub timestamp: u64,
+}
+
+/// Whether a tool call of this kind changes the files it points at
+pub fn is_edit_kind(kind: &str) -> bool {
+    EDIT_KINDS.contains(&kind)
+}
+
+/// Key for a path: symlinks resolved when the file or its directory exists,
+/// `.` and `..` removed either way
+pub(crate) fn canonical(path: &str) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    if let Ok(canonical) = normalized.canonicalize() {
+        return canonical;
+    }
+    match (normalized.parent().and_then(|p| p.canonicalize().ok()), normalized.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => normalized,
+    }
+}
+
+fn location_paths(update: &Value) -> Vec<String> {
+    update
+        .get("locations")
+        .and_then(|l| l.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|l| l.get("path").and_then(|p| p.as_str()).map(String::from))
+        .collect()
+}
+
+pub struct FileLocks {
+    locks: Mutex<HashMap<PathBuf, FileLock>>,
+    released: Notify,
+    hold_permissions: AtomicBool,
+}
+
+impl FileLocks {
+    pub fn new() -> Self {
+        Self {
+            locks: Mutex::new(HashMap::new()),
+            released: Notify::new(),
+            hold_permissions: AtomicBool::new(false),
+        }
+    }
+
+    /// Hold permission requests that conflict with another agent's edits
+    pub fn set_hold_permissions(&self, enabled: bool) {
+        self.hold_permissions.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn holds_permissions(&self) -> bool {
+        self.hold_permissions.load(Ordering::Relaxed)
+    }
+
+    /// Lock `paths` for a tool call. Paths another agent holds stay with
+    /// that agent; their locks are returned.
+    pub fn acquire(&self, agent_id: Uuid, tool_call_id: &str, paths: &[String]) -> Vec<FileLock> {
+        let mut locks = self.locks.lock().unwrap();
+        let mut conflicts = Vec::new();
+        for path in paths {
+            let key = canonical(path);
+            match locks.get(&key) {
+                Some(lock) if lock.agent_id != agent_id => conflicts.push(lock.clone()),
+                _ => {
+                    locks.insert(
+                        key,
+                        FileLock {
+                            path: path.clone(),
+                            agent_id,
+                            tool_call_id: tool_call_id.to_string(),
+                            acquired_at: now_secs(),
+                        },
+                    );
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn release_where(&self, matches: impl Fn(&FileLock) -> bool) {
+        let mut locks = self.locks.lock().unwrap();
+        let before = locks.len();
+        locks.retain(|_, lock| !matches(lock));
+        if locks.len() != before {
+            self.released.notify_waiters();
+        }
+    }
+
+    pub fn release(&self, agent_id: Uuid, tool_call_id: &str) {
+        self.release_where(|l| l.agent_id == agent_id && l.tool_call_id == tool_call_id);
+    }
+
+    /// Drop every lock of an agent, when its prompt ends or it stops
+    pub fn release_agent(&self, agent_id: Uuid) {
+        self.release_where(|l| l.agent_id == agent_id);
+    }
+
+    fn holds(&self, agent_id: Uuid, tool_call_id: &str) -> bool {
+        self.locks
+            .lock()
+            .unwrap()
+            .values()
+            .any(|l| l.agent_id == agent_id && l.tool_call_id == tool_call_id)
+    }
+
+    /// All locks, oldest first
+    pub fn list(&self) -> Vec<FileLock> {
+        let mut locks: Vec<FileLock> = self.locks.lock().unwrap().values().cloned().collect();
+        locks.sort_by(|a, b| a.acquired_at.cmp(&b.acquired_at).then(a.path.cmp(&b.path)));
+        locks
+    }
+
+    /// Track a raw `session/update` payload: edit tool calls lock their
+    /// files and release them when they finish
+    pub fn observe(&self, agent_id: Uuid, update: &Value) {
+        let session_update = update.get("sessionUpdate").and_then(|s| s.as_str());
+        if !matches!(session_update, Some("tool_call") | Some("tool_call_update")) {
+            return;
+        }
+        let Some(tool_call_id) = update.get("toolCallId").and_then(|s| s.as_str()) else {
+            return;
+        };
+        if matches!(
+            update.get("status").and_then(|s| s.as_str()),
+            Some("completed") | Some("failed")
+        ) {
+            self.release(agent_id, tool_call_id);
+            return;
+        }
+
+        let is_edit = match session_update {
+            Some("tool_call") => update
+                .get("kind")
+                .and_then(|k| k.as_str())
+                .is_some_and(is_edit_kind),
+            // Updates only move the locks of calls known to edit
+            _ => self.holds(agent_id, tool_call_id),
+        };
+        let paths = location_paths(update);
+        if !is_edit || paths.is_empty() {
+            return;
+        }
+        let conflicts = self.acquire(agent_id, tool_call_id, &paths);
+        report(agent_id, tool_call_id, conflicts, false);
+    }
+
+    /// Wait until no other agent holds any of `paths`, then lock them.
+    /// Returns false if `timeout` passed first.
+    pub async fn wait_for(
+        &self,
+        agent_id: Uuid,
+        tool_call_id: &str,
+        paths: &[String],
+        timeout: Duration,
+    ) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Register before checking so a release in between is not missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            if self.acquire(agent_id, tool_call_id, paths).is_empty() {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+impl Default for FileLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Publish conflicts of a tool call on [`FILE_CONFLICTS`]
+pub fn report(agent_id: Uuid, tool_call_id: &str, conflicts: Vec<FileLock>, held: bool) {
+    for holder in conflicts {
+        tracing::warn!(
+            "Agent {} touches {} while agent {} is editing it",
+            agent_id,
+            holder.path,
+            holder.agent_id
+        );
+        // No subscribers is fine
+        let _ = FILE_CONFLICTS.send(FileConflict {
+            path: holder.path.clone(),
+            agent_id,
+            tool_call_id: tool_call_id.to_string(),
+            holder,
+            held,
+            timestamp: now_secs(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_edits_lock_until_the_tool_call_finishes() {
+        let locks = FileLocks::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        locks.observe(a, &json!({
+            "sessionUpdate": "tool_call",
+            "toolCallId": "a1",
+            "kind": "edit",
+            "status": "pending",
+            "locations": [{"path": "/p/../p/main.rs"}]
+        }));
+        locks.observe(b, &json!({
+            "sessionUpdate": "tool_call",
+            "toolCallId": "b1",
+            "kind": "read",
+            "status": "pending",
+            "locations": [{"path": "/p/main.rs"}]
+        }));
+        assert_eq!(locks.list().len(), 1);
+
+        let conflicts = locks.acquire(b, "b2", &["/p/main.rs".to_string()]);
+        assert_eq!(conflicts[0].agent_id, a);
+
+        locks.observe(a, &json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": "a1",
+            "status": "completed"
+        }));
+        assert!(locks.list().is_empty());
+        assert!(locks.acquire(b, "b2", &["/p/main.rs".to_string()]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_resumes_when_the_holder_releases() {
+        let locks = Arc::new(FileLocks::new());
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let paths = vec!["/p/lib.rs".to_string()];
+        locks.acquire(a, "a1", &paths);
+
+        let waiter = {
+            let locks = locks.clone();
+            let paths = paths.clone();
+            tokio::spawn(async move { locks.wait_for(b, "b1", &paths, Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        locks.release_agent(a);
+        assert!(waiter.await.unwrap());
+        assert_eq!(locks.list()[0].agent_id, b);
+
+        assert!(!locks.wait_for(a, "a2", &paths, Duration::from_millis(10)).await);
+    }
+}
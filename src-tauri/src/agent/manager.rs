@@ -1,7 +1,8 @@
 use super::pool::AgentPool;
 use super::process::{AgentInfo, AgentProcessError, AgentUpdate};
+use crate::events::TrackedEmitter;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use tauri::AppHandle;
 use tokio::sync::mpsc;
 
 pub struct AgentManager {
@@ -25,17 +26,17 @@ impl AgentManager {
         let info = self.pool.spawn_agent(name, working_directory).await?;
 
         // Emit event to frontend
-        let _ = self.app_handle.emit("agent-spawned", &info);
+        let _ = self.app_handle.emit_tracked("agent-spawned", &info);
 
         Ok(info)
     }
 
     pub async fn get_agent(&self, id: &uuid::Uuid) -> Option<AgentInfo> {
-        self.pool.get_agent_info(id).await
+        self.pool.get_agent_info(id)
     }
 
     pub async fn list_agents(&self) -> Vec<AgentInfo> {
-        self.pool.list_agents().await
+        self.pool.list_agents()
     }
 
     pub async fn send_prompt(
@@ -49,15 +50,15 @@ impl AgentManager {
         // Spawn task to forward updates to frontend
         tokio::spawn(async move {
             while let Some(update) = rx.recv().await {
-                let _ = app_handle.emit("agent-update", &update);
+                let _ = app_handle.emit_agent_update("agent-update", update.agent_id, &update);
             }
         });
 
         let result = self.pool.send_prompt(agent_id, &prompt, tx).await?;
 
         // Emit completion
-        if let Some(info) = self.pool.get_agent_info(&agent_id).await {
-            let _ = self.app_handle.emit("agent-status-changed", &info);
+        if let Some(info) = self.pool.get_agent_info(&agent_id) {
+            let _ = self.app_handle.emit_tracked("agent-status-changed", &info);
         }
 
         Ok(result)
@@ -65,7 +66,9 @@ impl AgentManager {
 
     pub async fn stop_agent(&self, agent_id: &uuid::Uuid) -> Result<(), AgentProcessError> {
         self.pool.stop_agent(agent_id).await?;
-        let _ = self.app_handle.emit("agent-stopped", agent_id);
+        let _ = self
+            .app_handle
+            .emit_tracked("agent-stopped", serde_json::json!({ "agent_id": agent_id }));
         Ok(())
     }
 
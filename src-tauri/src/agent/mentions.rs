@@ -0,0 +1,175 @@
+//! `@file:` and `@agent:` mentions in prompts. File mentions attach the
+//! file's contents, agent mentions an excerpt of that agent's recent tasks.
+
+use super::context::{pack_files, PackedContext};
+use super::process::AgentInfo;
+use super::tasks::{TaskInfo, TaskStatus};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const FILE_PREFIX: &str = "@file:";
+const AGENT_PREFIX: &str = "@agent:";
+/// Finished tasks quoted per mentioned agent
+const EXCERPT_TASKS: usize = 3;
+/// Longest prompt or result quoted in an excerpt
+const EXCERPT_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "target", rename_all = "snake_case")]
+pub enum Mention {
+    File(String),
+    Agent(String),
+}
+
+/// Mentions in `prompt`, in order, without duplicates
+pub fn parse_mentions(prompt: &str) -> Vec<Mention> {
+    let mut mentions = Vec::new();
+    for word in prompt.split_whitespace() {
+        let word = word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+        let mention = if let Some(path) = word.strip_prefix(FILE_PREFIX) {
+            Mention::File(path.to_string())
+        } else if let Some(name) = word.strip_prefix(AGENT_PREFIX) {
+            Mention::Agent(name.to_string())
+        } else {
+            continue;
+        };
+        let empty = matches!(&mention, Mention::File(s) | Mention::Agent(s) if s.is_empty());
+        if !empty && !mentions.contains(&mention) {
+            mentions.push(mention);
+        }
+    }
+    mentions
+}
+
+/// A prompt with its mentions expanded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResolvedPrompt {
+    /// The prompt with mention prefixes dropped
+    pub prompt: String,
+    /// Mentioned files plus transcript excerpts of mentioned agents
+    pub context: PackedContext,
+    /// Names of the agents whose transcripts were quoted
+    pub agents: Vec<String>,
+    /// Agent mentions that matched no agent
+    pub unknown_agents: Vec<String>,
+}
+
+/// Whether `mention` names `agent`: by id, or by name ignoring case with
+/// spaces written as dashes
+fn names_agent(mention: &str, agent: &AgentInfo) -> bool {
+    agent.id.to_string() == mention
+        || agent.name.eq_ignore_ascii_case(mention)
+        || agent.name.replace(' ', "-").eq_ignore_ascii_case(mention)
+}
+
+fn clip(text: &str) -> &str {
+    match text.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// Excerpt of the last finished tasks of an agent, oldest first
+pub fn transcript_excerpt(tasks: &[TaskInfo]) -> String {
+    let finished: Vec<&TaskInfo> = tasks.iter().filter(|t| t.status.is_finished()).collect();
+    let mut excerpt = String::new();
+    for task in &finished[finished.len().saturating_sub(EXCERPT_TASKS)..] {
+        excerpt.push_str(&format!("> {}\n", clip(&task.prompt)));
+        match (&task.status, &task.result, &task.error) {
+            (TaskStatus::Completed, Some(result), _) => excerpt.push_str(clip(result)),
+            (_, _, Some(error)) => excerpt.push_str(&format!("(failed: {})", clip(error))),
+            _ => excerpt.push_str("(no result)"),
+        }
+        excerpt.push_str("\n\n");
+    }
+    excerpt
+}
+
+/// Expand the mentions in `prompt`: files are read relative to `base`, agents
+/// are looked up in `agents` and quoted from `tasks_for_agent`
+pub async fn resolve_mentions(
+    prompt: &str,
+    base: &Path,
+    agents: &[AgentInfo],
+    tasks_for_agent: impl Fn(&AgentInfo) -> Vec<TaskInfo>,
+) -> ResolvedPrompt {
+    let mentions = parse_mentions(prompt);
+    let files: Vec<String> = mentions
+        .iter()
+        .filter_map(|m| match m {
+            Mention::File(path) => Some(path.clone()),
+            Mention::Agent(_) => None,
+        })
+        .collect();
+
+    let mut resolved = ResolvedPrompt {
+        prompt: prompt.replace(FILE_PREFIX, "").replace(AGENT_PREFIX, "@"),
+        context: pack_files(base, &files).await,
+        ..Default::default()
+    };
+
+    let mut excerpts = String::new();
+    for mention in &mentions {
+        let Mention::Agent(name) = mention else {
+            continue;
+        };
+        let Some(agent) = agents.iter().find(|a| names_agent(name, a)) else {
+            resolved.unknown_agents.push(name.clone());
+            continue;
+        };
+        let excerpt = transcript_excerpt(&tasks_for_agent(agent));
+        let excerpt = if excerpt.is_empty() { "(no finished tasks)\n\n".to_string() } else { excerpt };
+        excerpts.push_str(&format!("### {}\n{}", agent.name, excerpt));
+        resolved.agents.push(agent.name.clone());
+    }
+    if !excerpts.is_empty() {
+        resolved.context.preamble.push_str(&format!(
+            "Recent work of the mentioned agents:\n\n{}",
+            excerpts
+        ));
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mentions() {
+        let mentions = parse_mentions("Ask @agent:reviewer about @file:src/main.rs, then @file:src/main.rs again. @file:");
+        assert_eq!(
+            mentions,
+            vec![
+                Mention::Agent("reviewer".to_string()),
+                Mention::File("src/main.rs".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_mentions() {
+        let dir = std::env::temp_dir().join(format!("acptorio-mentions-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        let reviewer = AgentInfo {
+            name: "Code Reviewer".to_string(),
+            ..Default::default()
+        };
+
+        let resolved = resolve_mentions(
+            "Fix @file:main.rs as @agent:code-reviewer said; ask @agent:nobody",
+            &dir,
+            std::slice::from_ref(&reviewer),
+            |_| Vec::new(),
+        )
+        .await;
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(resolved.prompt, "Fix main.rs as @code-reviewer said; ask @nobody");
+        assert_eq!(resolved.context.included, vec!["main.rs".to_string()]);
+        assert_eq!(resolved.agents, vec!["Code Reviewer".to_string()]);
+        assert_eq!(resolved.unknown_agents, vec!["nobody".to_string()]);
+        assert!(resolved.context.preamble.contains("### Code Reviewer\n(no finished tasks)"));
+    }
+}
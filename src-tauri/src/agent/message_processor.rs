@@ -9,8 +9,13 @@ use crate::acp::{
     RequestPermissionRequest, RequestPermissionResponse, SessionUpdate, SessionUpdateNotification,
     ToolCallStatus,
 };
+use super::artifacts::TouchedRange;
+use super::project_scope::absolute_paths_outside_projects;
+use super::risk::{score_permission, RiskInput};
+use crate::clock::now_secs;
+use crate::messages::{LocalizedMessage, MessageKey};
+use crate::plugins::{AutoApprovalReason, PLUGINS};
 use serde_json::Value;
-use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 // Re-use types from process module to avoid duplication
@@ -27,6 +32,8 @@ pub struct ProcessingResult {
     pub accumulated_text: String,
     /// Current file being worked on (if detected)
     pub current_file: Option<String>,
+    /// Tool calls that finished; their pending inputs are cleared
+    pub finished_tool_calls: Vec<String>,
 }
 
 /// Result of processing a permission request
@@ -36,8 +43,8 @@ pub struct PermissionProcessingResult {
     pub update: AgentUpdate,
     /// Pending input to track
     pub pending_input: PendingInput,
-    /// Response to send back to agent
-    pub response: JsonRpcResponse,
+    /// Response to send back to agent; `None` when the user has to answer
+    pub response: Option<JsonRpcResponse>,
 }
 
 /// Process a session/update notification and return the results
@@ -90,6 +97,10 @@ pub fn process_typed_session_update(
         }
     }
 
+    if let Some(tool_call_id) = finished_tool_call(update) {
+        result.finished_tool_calls.push(tool_call_id.to_string());
+    }
+
     // Extract text content from message chunks
     if let Some(text) = update.get_text() {
         result.accumulated_text = text.to_string();
@@ -174,6 +185,7 @@ pub fn process_typed_session_update(
         current_file: result.current_file.clone(),
         status: None,
         pending_inputs: None,
+        touched_files: update.locations().iter().map(TouchedRange::from_location).collect(),
     };
     result.updates.push(agent_update);
 
@@ -198,17 +210,21 @@ fn create_pending_tool_call(
         _ => return None,
     };
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    let timestamp = now_secs();
 
+    let localized = LocalizedMessage::new(MessageKey::AgentWantsTo).with("tool", &title);
     let pending_input = PendingInput {
-        id: tool_call_id,
+        id: tool_call_id.clone(),
         input_type: PendingInputType::ToolPermission,
         tool_name: Some(title.clone()),
-        message: format!("Agent wants to: {}", title),
+        message: localized.render(),
         timestamp,
+        high_risk: false,
+        outside_paths: Vec::new(),
+        risk: None,
+        similar_scope: None,
+        tool_call_id: Some(tool_call_id),
+        localized: Some(localized),
     };
 
     let agent_update = AgentUpdate {
@@ -223,6 +239,7 @@ fn create_pending_tool_call(
         current_file,
         status: None,
         pending_inputs: None,
+        touched_files: Vec::new(),
     };
 
     Some((pending_input, agent_update))
@@ -247,10 +264,7 @@ pub fn process_legacy_session_update(
         || update_type == "waiting_for_user";
 
     if is_input_request {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let timestamp = now_secs();
 
         let input_type = if update_type.contains("permission") {
             PendingInputType::ToolPermission
@@ -260,16 +274,15 @@ pub fn process_legacy_session_update(
             PendingInputType::UserQuestion
         };
 
-        let message = update
-            .content
-            .as_ref()
-            .and_then(|c| c.text.clone())
-            .unwrap_or_else(|| {
-                format!(
-                    "Agent needs permission to use: {}",
-                    update.name.as_deref().unwrap_or("unknown tool")
-                )
-            });
+        let text = update.content.as_ref().and_then(|c| c.text.clone());
+        let localized = text.is_none().then(|| {
+            LocalizedMessage::new(MessageKey::AgentNeedsPermission)
+                .with("tool", update.name.as_deref().unwrap_or("unknown tool"))
+        });
+        let message = match &localized {
+            Some(localized) => localized.render(),
+            None => text.unwrap_or_default(),
+        };
 
         let pending_input = PendingInput {
             id: update
@@ -280,6 +293,12 @@ pub fn process_legacy_session_update(
             tool_name: update.name.clone(),
             message: message.clone(),
             timestamp,
+            high_risk: false,
+            outside_paths: Vec::new(),
+            risk: None,
+            similar_scope: None,
+            tool_call_id: update.tool_use_id.clone(),
+            localized,
         };
 
         result.pending_inputs.push(pending_input);
@@ -296,6 +315,7 @@ pub fn process_legacy_session_update(
             current_file: current_file.clone(),
             status: None,
             pending_inputs: None,
+            touched_files: Vec::new(),
         };
         result.updates.push(pending_update);
     }
@@ -327,12 +347,49 @@ pub fn process_legacy_session_update(
         current_file: result.current_file.clone(),
         status: None,
         pending_inputs: None,
+        touched_files: Vec::new(),
     };
     result.updates.push(agent_update);
 
     result
 }
 
+/// Tool call an update reports as completed or failed
+pub fn finished_tool_call(update: &SessionUpdate) -> Option<&str> {
+    match update {
+        SessionUpdate::ToolCall(tc) if matches!(tc.status, ToolCallStatus::Completed | ToolCallStatus::Failed) => {
+            Some(&tc.tool_call_id)
+        }
+        SessionUpdate::ToolCallUpdate(tcu)
+            if matches!(tcu.status, Some(ToolCallStatus::Completed | ToolCallStatus::Failed)) =>
+        {
+            Some(&tcu.tool_call_id)
+        }
+        _ => None,
+    }
+}
+
+/// Add `input` to `inputs`, keeping one entry per input id and tool call in
+/// arrival order. A permission request takes the place of the pending tool
+/// call it is about; a pending tool call never replaces anything.
+pub fn merge_pending_input(inputs: &mut Vec<PendingInput>, input: PendingInput) {
+    let existing = inputs.iter().position(|i| {
+        i.id == input.id || (input.tool_call_id.is_some() && i.tool_call_id == input.tool_call_id)
+    });
+    match existing {
+        Some(index) if input.is_permission_request() || inputs[index].id == input.id => inputs[index] = input,
+        Some(_) => {}
+        None => inputs.push(input),
+    }
+}
+
+/// Remove the inputs of a finished tool call; returns whether any were there
+pub fn clear_tool_call_inputs(inputs: &mut Vec<PendingInput>, tool_call_id: &str) -> bool {
+    let before = inputs.len();
+    inputs.retain(|i| i.tool_call_id.as_deref() != Some(tool_call_id));
+    inputs.len() != before
+}
+
 /// Extract file path from tool input JSON
 pub fn extract_file_path(input: &Value) -> Option<String> {
     input
@@ -353,20 +410,35 @@ pub fn process_permission_request(
     let request: RequestPermissionRequest = serde_json::from_value(params.clone())
         .map_err(|e| format!("Invalid permission request: {}", e))?;
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-
+    let timestamp = now_secs();
+
+    // Tool calls reaching outside every project are never auto-approved
+    let raw_tool_call = params.get("toolCall");
+    let raw_input = raw_tool_call.and_then(|c| c.get("rawInput"));
+    let mut paths: Vec<String> = request.tool_call.locations.iter().flatten().map(|l| l.path.clone()).collect();
+    paths.extend(raw_input.and_then(extract_file_path));
+    let outside_paths = absolute_paths_outside_projects(&paths);
+    let risk = score_permission(RiskInput {
+        kind: raw_tool_call.and_then(|c| c.get("kind")).and_then(|k| k.as_str()),
+        paths: &paths,
+        raw_input,
+        outside_project: !outside_paths.is_empty(),
+    });
+
+    let localized = LocalizedMessage::new(MessageKey::PermissionRequested)
+        .with("tool", request.tool_call.title.as_deref().unwrap_or("unknown tool"));
     let pending_input = PendingInput {
         id: format!("perm_req_{}", request_id),
         input_type: PendingInputType::ToolPermission,
         tool_name: request.tool_call.title.clone(),
-        message: format!(
-            "Permission requested: {}",
-            request.tool_call.title.as_deref().unwrap_or("unknown tool")
-        ),
+        message: localized.render(),
         timestamp,
+        high_risk: !outside_paths.is_empty(),
+        outside_paths,
+        risk: Some(risk),
+        similar_scope: None,
+        tool_call_id: Some(request.tool_call.tool_call_id.clone()),
+        localized: Some(localized),
     };
 
     let update = AgentUpdate {
@@ -381,6 +453,7 @@ pub fn process_permission_request(
         current_file,
         status: None,
         pending_inputs: None,
+        touched_files: Vec::new(),
     };
 
     // Create response (auto-approve or wait for user)
@@ -393,8 +466,10 @@ pub fn process_permission_request(
 
     let response = RequestPermissionResponse::selected(option_id);
 
-    let rpc_response =
-        JsonRpcResponse::success(request_id, serde_json::to_value(&response).unwrap());
+    let rpc_response = (auto_approve
+        && !pending_input.high_risk
+        && PLUGINS.allows_auto_approval(agent_id, &request, AutoApprovalReason::AutoApprove))
+        .then(|| JsonRpcResponse::success(request_id, serde_json::to_value(&response).unwrap()));
 
     Ok(PermissionProcessingResult {
         update,
@@ -529,6 +604,52 @@ mod tests {
         assert_eq!(result.updates.len(), 1);
         assert_eq!(result.updates[0].update_type, "tool_call");
         assert!(result.pending_inputs.is_empty());
+        assert_eq!(result.finished_tool_calls, vec!["tc-789".to_string()]);
+    }
+
+    #[test]
+    fn test_pending_inputs_deduplicated_by_tool_call() {
+        let tool_call = serde_json::json!({
+            "sessionId": "test-session",
+            "update": {
+                "type": "tool_call",
+                "toolCallId": "tc-dup",
+                "title": "Edit main.rs",
+                "status": "pending"
+            }
+        });
+        let permission = serde_json::json!({
+            "sessionId": "test-session",
+            "toolCall": {"toolCallId": "tc-dup", "title": "Edit main.rs", "status": "pending"},
+            "options": [{"optionId": "opt-allow", "name": "Allow", "kind": "allow_once"}]
+        });
+        let other = serde_json::json!({
+            "sessionId": "test-session",
+            "update": {
+                "type": "tool_call",
+                "toolCallId": "tc-other",
+                "title": "Edit lib.rs",
+                "status": "pending"
+            }
+        });
+
+        let mut inputs = Vec::new();
+        let pending = process_session_update(test_agent_id(), &tool_call, None).pending_inputs;
+        merge_pending_input(&mut inputs, pending[0].clone());
+        let other = process_session_update(test_agent_id(), &other, None).pending_inputs;
+        merge_pending_input(&mut inputs, other[0].clone());
+        let request = process_permission_request(test_agent_id(), 3, &permission, None, false).unwrap();
+        merge_pending_input(&mut inputs, request.pending_input);
+        // The same pending tool call again does not hide the permission request
+        merge_pending_input(&mut inputs, pending[0].clone());
+
+        let ids: Vec<&str> = inputs.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["perm_req_3", "tc-other"]);
+
+        assert!(clear_tool_call_inputs(&mut inputs, "tc-dup"));
+        assert!(!clear_tool_call_inputs(&mut inputs, "tc-dup"));
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].id, "tc-other");
     }
 
     #[test]
@@ -556,6 +677,14 @@ mod tests {
             result.current_file,
             Some("/project/src/main.rs".to_string())
         );
+        assert_eq!(
+            result.updates[0].touched_files,
+            vec![TouchedRange {
+                path: "/project/src/main.rs".to_string(),
+                start_line: Some(10),
+                end_line: Some(20),
+            }]
+        );
     }
 
     #[test]
@@ -753,6 +882,30 @@ mod tests {
         assert!(response_json.contains("opt-allow"));
     }
 
+    #[test]
+    fn test_process_permission_request_outside_project_is_not_auto_approved() {
+        let params = serde_json::json!({
+            "sessionId": "test-session",
+            "toolCall": {
+                "toolCallId": "tc-perm-hosts",
+                "title": "Edit hosts",
+                "status": "pending",
+                "locations": [{"path": "/etc/hosts"}]
+            },
+            "options": [
+                {"optionId": "opt-allow", "name": "Allow", "kind": "allow_once"}
+            ]
+        });
+
+        let result =
+            process_permission_request(test_agent_id(), 7, &params, None, true).unwrap();
+
+        assert!(result.pending_input.high_risk);
+        assert_eq!(result.pending_input.outside_paths, vec!["/etc/hosts".to_string()]);
+        assert!(result.pending_input.risk.unwrap().tier >= crate::agent::RiskTier::High);
+        assert!(result.response.is_none());
+    }
+
     #[test]
     fn test_process_permission_request_with_options() {
         let params = serde_json::json!({
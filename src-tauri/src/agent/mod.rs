@@ -1,11 +1,67 @@
+pub mod artifacts;
+pub mod benchmark;
+pub mod compaction;
+pub mod conflicts;
+pub mod context;
+pub mod credentials;
+pub mod demo;
+pub mod environment;
+pub mod escalation;
+pub mod import;
+pub mod journal;
+pub mod leaderboard;
+pub mod limits;
+pub mod locks;
 pub mod manager;
+pub mod mentions;
 pub mod message_processor;
+pub mod policy_script;
 pub mod pool;
+pub mod preamble;
 pub mod process;
+pub mod project_scope;
+pub mod review;
+pub mod risk;
+pub mod sandbox;
+pub mod saved_sessions;
+pub mod scheduler;
+pub mod self_test;
+pub mod state_events;
+pub mod tasks;
+pub mod transcript;
+pub mod verification;
 
+pub use artifacts::*;
+pub use benchmark::*;
+pub use compaction::*;
+pub use conflicts::*;
+pub use context::*;
+pub use credentials::*;
+pub use demo::*;
+pub use environment::*;
+pub use escalation::*;
+pub use import::*;
+pub use journal::*;
+pub use leaderboard::*;
+pub use limits::*;
+pub use locks::*;
 pub use manager::*;
+pub use mentions::*;
+pub use policy_script::*;
 pub use pool::*;
+pub use preamble::*;
 pub use process::*;
+pub use project_scope::*;
+pub use review::*;
+pub use risk::*;
+pub use sandbox::*;
+pub use saved_sessions::*;
+pub use scheduler::*;
+pub use self_test::*;
+pub use state_events::*;
+pub use tasks::*;
+pub use transcript::*;
+pub use verification::*;
 
 // Re-export only the processing functions, not the duplicate types
 pub use message_processor::{
@@ -14,6 +70,9 @@ pub use message_processor::{
     process_legacy_session_update,
     process_permission_request,
     extract_file_path,
+    finished_tool_call,
+    merge_pending_input,
+    clear_tool_call_inputs,
     ProcessingResult,
     PermissionProcessingResult,
 };
@@ -0,0 +1,322 @@
+//! Rhai scripts over permission requests and tasks, for rules a static
+//! allow or deny list can't express. The permission script answers
+//! `"approve"`, `"deny"` or `"ask"` for a `request`; the routing script
+//! answers the id of one of the `agents` to run a `task`. Both run with
+//! limits on operations and time, and an error or a timeout leaves the
+//! decision to the user.
+
+use super::process::{AgentInfo, AgentStatus};
+use once_cell::sync::Lazy;
+use rhai::{Dynamic, Engine, Scope};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+static POLICY_SCRIPTS: Lazy<RwLock<PolicyScripts>> = Lazy::new(Default::default);
+
+const MAX_STRING_BYTES: usize = 64 * 1024;
+const MAX_COLLECTION_LEN: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyScripts {
+    /// Script over `request` answering "approve", "deny" or "ask"
+    #[serde(default)]
+    pub permission: Option<String>,
+    /// Script over `task` and `agents` answering the id of the agent to run
+    /// the task
+    #[serde(default)]
+    pub routing: Option<String>,
+    /// Operations a script may run before it is stopped
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_max_operations() -> u64 {
+    100_000
+}
+
+fn default_timeout_ms() -> u64 {
+    50
+}
+
+impl Default for PolicyScripts {
+    fn default() -> Self {
+        Self {
+            permission: None,
+            routing: None,
+            max_operations: default_max_operations(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptDecision {
+    Approve,
+    Deny,
+    Ask,
+}
+
+/// What the permission script sees as `request`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PermissionFacts {
+    pub agent_id: String,
+    pub agent_name: String,
+    pub provider_id: Option<String>,
+    /// Working directory of the agent
+    pub project: String,
+    /// Title of the tool call
+    pub tool: Option<String>,
+    /// ACP tool kind: read, edit, delete, move, search, execute, think, fetch
+    pub kind: Option<String>,
+    pub paths: Vec<String>,
+    /// Command line of an execute call
+    pub command: Option<String>,
+    /// Risk score, 0-100
+    pub risk: u32,
+    pub outside_project: bool,
+}
+
+/// What the routing script sees as `task`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaskFacts {
+    pub prompt: String,
+    pub project: Option<String>,
+}
+
+/// An agent the routing script can pick, one of `agents`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteCandidate {
+    pub id: String,
+    pub name: String,
+    pub provider_id: Option<String>,
+    pub project: String,
+    pub status: AgentStatus,
+    pub tokens_used: u64,
+}
+
+impl RouteCandidate {
+    pub fn from_info(info: &AgentInfo) -> Self {
+        Self {
+            id: info.id.to_string(),
+            name: info.name.clone(),
+            provider_id: info.provider_id.clone(),
+            project: info.working_directory.clone(),
+            status: info.status,
+            tokens_used: info.tokens_used,
+        }
+    }
+}
+
+/// Command line of a tool call's raw input, given as a string or a list
+pub fn command_text(raw_input: Option<&Value>) -> Option<String> {
+    match raw_input?.get("command")? {
+        Value::String(command) => Some(command.clone()),
+        Value::Array(parts) => Some(
+            parts
+                .iter()
+                .map(|p| {
+                    p.as_str()
+                        .map(String::from)
+                        .unwrap_or_else(|| p.to_string())
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    }
+}
+
+impl PolicyScripts {
+    /// Compile both scripts, for rejecting settings with a broken one
+    pub fn validate(&self) -> Result<(), String> {
+        let engine = self.engine();
+        for (name, source) in [("permission", &self.permission), ("routing", &self.routing)] {
+            if let Some(source) = source {
+                engine
+                    .compile(source)
+                    .map_err(|e| format!("Invalid {} script: {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn engine(&self) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(self.max_operations);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(MAX_STRING_BYTES);
+        engine.set_max_array_size(MAX_COLLECTION_LEN);
+        engine.set_max_map_size(MAX_COLLECTION_LEN);
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms);
+        engine.on_progress(move |_| (Instant::now() > deadline).then_some(Dynamic::UNIT));
+        engine
+    }
+
+    fn run(&self, source: &str, vars: Vec<(&str, Dynamic)>) -> Result<Dynamic, String> {
+        let engine = self.engine();
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        let mut scope = Scope::new();
+        for (name, value) in vars {
+            scope.push_constant_dynamic(name, value);
+        }
+        engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &ast)
+            .map_err(|e| e.to_string())
+    }
+
+    /// `Ask` without a permission script or when it answers nothing
+    pub fn decide_permission(&self, facts: &PermissionFacts) -> Result<ScriptDecision, String> {
+        let Some(source) = &self.permission else {
+            return Ok(ScriptDecision::Ask);
+        };
+        let request = rhai::serde::to_dynamic(facts).map_err(|e| e.to_string())?;
+        let answer = self.run(source, vec![("request", request)])?;
+        if answer.is_unit() {
+            return Ok(ScriptDecision::Ask);
+        }
+        let answer = answer
+            .into_string()
+            .map_err(|t| format!("The permission script answered a {}, not a string", t))?;
+        match answer.as_str() {
+            "approve" => Ok(ScriptDecision::Approve),
+            "deny" => Ok(ScriptDecision::Deny),
+            "ask" => Ok(ScriptDecision::Ask),
+            other => Err(format!("The permission script answered {:?}", other)),
+        }
+    }
+
+    /// Agent the routing script picks among `agents`, None if it picks none
+    pub fn route(
+        &self,
+        task: &TaskFacts,
+        agents: &[RouteCandidate],
+    ) -> Result<Option<Uuid>, String> {
+        let source = self
+            .routing
+            .as_ref()
+            .ok_or_else(|| "No routing script is configured".to_string())?;
+        let task = rhai::serde::to_dynamic(task).map_err(|e| e.to_string())?;
+        let candidates = rhai::serde::to_dynamic(agents).map_err(|e| e.to_string())?;
+        let answer = self.run(source, vec![("task", task), ("agents", candidates)])?;
+        if answer.is_unit() {
+            return Ok(None);
+        }
+        let answer = answer
+            .into_string()
+            .map_err(|t| format!("The routing script answered a {}, not an agent id", t))?;
+        if !agents.iter().any(|a| a.id == answer) {
+            return Err(format!(
+                "The routing script answered an unknown agent: {}",
+                answer
+            ));
+        }
+        Uuid::parse_str(&answer)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Use these scripts from now on
+pub fn set_policy_scripts(scripts: &PolicyScripts) {
+    *POLICY_SCRIPTS.write().unwrap() = scripts.clone();
+}
+
+/// What the configured permission script decides about a request
+pub fn permission_decision(facts: &PermissionFacts) -> Result<ScriptDecision, String> {
+    POLICY_SCRIPTS
+        .read()
+        .unwrap()
+        .clone()
+        .decide_permission(facts)
+}
+
+/// Agent the configured routing script picks for a task
+pub fn scripted_route(task: &TaskFacts, agents: &[RouteCandidate]) -> Result<Option<Uuid>, String> {
+    POLICY_SCRIPTS.read().unwrap().clone().route(task, agents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(kind: &str, command: Option<&str>) -> PermissionFacts {
+        PermissionFacts {
+            kind: Some(kind.to_string()),
+            command: command.map(String::from),
+            paths: vec!["src/main.rs".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_scripts_decide_route_and_stop() {
+        let scripts = PolicyScripts {
+            permission: Some(
+                r#"
+                if request.kind == "read" { "approve" }
+                else if type_of(request.command) == "string" && request.command.contains("rm -rf") {
+                    "deny"
+                }
+                "#
+                .to_string(),
+            ),
+            routing: Some(
+                r#"
+                for agent in agents {
+                    if agent.status == "idle" && agent.provider_id == "gemini" { return agent.id; }
+                }
+                "#
+                .to_string(),
+            ),
+            ..Default::default()
+        };
+        scripts.validate().unwrap();
+
+        let decide = |f: PermissionFacts| scripts.decide_permission(&f).unwrap();
+        assert_eq!(decide(facts("read", None)), ScriptDecision::Approve);
+        assert_eq!(
+            decide(facts("execute", Some("rm -rf /"))),
+            ScriptDecision::Deny
+        );
+        assert_eq!(decide(facts("edit", None)), ScriptDecision::Ask);
+
+        let idle = Uuid::new_v4();
+        let candidate = |id: Uuid, status: AgentStatus| RouteCandidate {
+            id: id.to_string(),
+            name: "worker".to_string(),
+            provider_id: Some("gemini".to_string()),
+            project: "/work/app".to_string(),
+            status,
+            tokens_used: 0,
+        };
+        let agents = [
+            candidate(Uuid::new_v4(), AgentStatus::Working),
+            candidate(idle, AgentStatus::Idle),
+        ];
+        assert_eq!(
+            scripts.route(&TaskFacts::default(), &agents),
+            Ok(Some(idle))
+        );
+        assert_eq!(scripts.route(&TaskFacts::default(), &agents[..1]), Ok(None));
+
+        let endless = PolicyScripts {
+            permission: Some("loop {}".to_string()),
+            ..Default::default()
+        };
+        assert!(endless.decide_permission(&facts("read", None)).is_err());
+        assert!(PolicyScripts {
+            permission: Some("if {".to_string()),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -1,33 +1,162 @@
-use super::process::{AgentInfo, AgentProcess, AgentProcessError, AgentUpdate, PermissionUserResponse, SpawnConfig};
+use super::process::{
+    AgentInfo, AgentProcess, AgentProcessError, AgentStatus, AgentUpdate, PendingInput, PendingInputType, PermissionUserResponse,
+    PromptCancel, SpawnConfig, default_package_runner,
+};
+use super::artifacts::{RunningToolCall, ToolCallArtifact, ToolCallHistory, ToolOutput};
+use super::environment::{sanitized_environment, AgentEnvironment, LaunchInfo, NegotiatedProtocol};
+use super::compaction::{
+    find_compact_command, seed_prompt, CompactionMethod, CompactionRecord, SessionHistory,
+    SUMMARY_PROMPT,
+};
+use super::review::{build_review_prompt, parse_verdict, ReviewStatus, TaskReview};
+use super::limits::ResourceLimits;
+use super::locks::{FileLock, FileLocks};
+use super::journal::PromptJournal;
+use super::sandbox::ContainerSandbox;
+use super::escalation::{
+    DefaultAction, EscalationKind, EscalationPolicy, PermissionEscalation, ESCALATION_SWEEP_INTERVAL,
+    PERMISSION_ESCALATIONS,
+};
+use super::risk::PermissionRisk;
+use super::scheduler::{PoolQueue, PromptPriority, PromptScheduler, SchedulingPolicy};
+use super::state_events::forward_state_changes;
+use super::tasks::{TaskGraph, TaskInfo, TaskSpec};
+use crate::acp::{RequestPermissionRequest, RequestPolicies, SessionListEntry, Transport};
+use crate::clock::now_secs;
+use crate::diagnostics::redact_url;
+use crate::plugins::{AutoApprovalReason, PLUGINS};
+use crate::git::{diff_patch, ChangeSummary, CheckpointStore, TreeSnapshot};
 use dashmap::DashMap;
-use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot, Mutex};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 use uuid::Uuid;
 
 /// Key for pending permissions: "agent_id:input_id"
 type PermissionKey = String;
 
+/// Permission requests dropped unanswered because their agent stopped
+pub static CANCELLED_PERMISSIONS: Lazy<broadcast::Sender<PendingPermissionInfo>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+/// Option added to repeated permission requests; answering with it also
+/// approves the similar requests that follow in the same prompt
+pub const ALLOW_SIMILAR_OPTION_ID: &str = "acptorio_allow_similar";
+
+/// Tool kind and directory of a request, shared by near-identical ones
+/// such as reading file after file of one directory
+pub fn similarity_scope(kind: Option<&str>, paths: &[String]) -> Option<String> {
+    let path = Path::new(paths.first()?);
+    let dir = path.parent().unwrap_or(path);
+    Some(format!("{}:{}", kind.unwrap_or("other"), dir.display()))
+}
+
+/// Similar requests of an agent's current prompt
+#[derive(Default)]
+struct SimilarPermissions {
+    seen: HashMap<String, u32>,
+    allowed: HashSet<String>,
+}
+
+/// A permission request waiting for the user's answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingPermissionInfo {
+    pub agent_id: Uuid,
+    pub input_id: String,
+    pub message: String,
+    /// Order in which requests arrived
+    pub seq: u64,
+    /// Reaches outside every registered project; approving it needs
+    /// explicit confirmation
+    #[serde(default)]
+    pub high_risk: bool,
+    #[serde(default)]
+    pub outside_paths: Vec<String>,
+    #[serde(default)]
+    pub risk: Option<PermissionRisk>,
+    #[serde(default)]
+    pub similar_scope: Option<String>,
+    /// When the request arrived, in seconds
+    #[serde(default)]
+    pub created_at: u64,
+    /// Reminders sent while it waited
+    #[serde(default)]
+    pub reminders: u32,
+    #[serde(default)]
+    pub last_reminded_at: Option<u64>,
+}
+
+/// A waiting permission request with the options the agent offered, so
+/// it can still be answered after the frontend missed its event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingPermission {
+    #[serde(flatten)]
+    pub info: PendingPermissionInfo,
+    pub request: RequestPermissionRequest,
+}
+
+struct PendingChannel {
+    info: PendingPermissionInfo,
+    request: RequestPermissionRequest,
+    tx: oneshot::Sender<PermissionUserResponse>,
+}
+
 /// Global storage for pending permission response channels (avoids deadlock)
 pub struct PendingPermissions {
-    channels: DashMap<PermissionKey, oneshot::Sender<PermissionUserResponse>>,
+    channels: DashMap<PermissionKey, PendingChannel>,
+    next_seq: AtomicU64,
+    similar: DashMap<Uuid, SimilarPermissions>,
 }
 
 impl PendingPermissions {
     pub fn new() -> Self {
         Self {
             channels: DashMap::new(),
+            next_seq: AtomicU64::new(0),
+            similar: DashMap::new(),
         }
     }
 
-    pub fn store(&self, agent_id: Uuid, input_id: &str, tx: oneshot::Sender<PermissionUserResponse>) {
-        let key = format!("{}:{}", agent_id, input_id);
-        self.channels.insert(key, tx);
+    pub fn store(
+        &self,
+        agent_id: Uuid,
+        input: &PendingInput,
+        request: RequestPermissionRequest,
+        tx: oneshot::Sender<PermissionUserResponse>,
+    ) {
+        let key = format!("{}:{}", agent_id, input.id);
+        let info = PendingPermissionInfo {
+            agent_id,
+            input_id: input.id.clone(),
+            message: input.message.clone(),
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+            high_risk: input.high_risk,
+            outside_paths: input.outside_paths.clone(),
+            risk: input.risk.clone(),
+            similar_scope: input.similar_scope.clone(),
+            created_at: input.timestamp,
+            reminders: 0,
+            last_reminded_at: None,
+        };
+        self.channels.insert(key, PendingChannel { info, request, tx });
     }
 
-    pub fn respond(&self, agent_id: Uuid, input_id: &str, response: PermissionUserResponse) -> Result<(), AgentProcessError> {
+    pub fn respond(&self, agent_id: Uuid, input_id: &str, mut response: PermissionUserResponse) -> Result<(), AgentProcessError> {
         let key = format!("{}:{}", agent_id, input_id);
-        if let Some((_, tx)) = self.channels.remove(&key) {
-            tx.send(response).map_err(|_| {
+        if let Some((_, pending)) = self.channels.remove(&key) {
+            if response.option_id.as_deref() == Some(ALLOW_SIMILAR_OPTION_ID) {
+                // The agent never offered it; answer with its own allow option
+                response.option_id = None;
+                if let (true, Some(scope)) = (response.approved, &pending.info.similar_scope) {
+                    self.similar.entry(agent_id).or_default().allowed.insert(scope.clone());
+                }
+            }
+            pending.tx.send(response).map_err(|_| {
                 AgentProcessError::CommunicationError("Failed to send permission response".to_string())
             })?;
             Ok(())
@@ -35,32 +164,260 @@ impl PendingPermissions {
             Err(AgentProcessError::CommunicationError(format!("No pending permission with id: {}", input_id)))
         }
     }
+
+    /// Remind about requests that waited past the policy's interval and
+    /// answer those past its cap with the default action; `now` in seconds
+    pub fn sweep(&self, policy: &EscalationPolicy, now: u64) -> Vec<PermissionEscalation> {
+        let mut escalations = Vec::new();
+        let mut answers = Vec::new();
+        for mut entry in self.channels.iter_mut() {
+            let pending = &mut *entry;
+            let info = &mut pending.info;
+            let age_secs = now.saturating_sub(info.created_at);
+            let answer = match (policy.default_action, policy.default_action_after_mins) {
+                (DefaultAction::Wait, _) | (_, None) => None,
+                (DefaultAction::Approve, _) if info.high_risk => None,
+                (action, Some(mins)) if age_secs >= mins * 60 => Some(action == DefaultAction::Approve),
+                _ => None,
+            };
+            // A plugin can leave the request to the user
+            let reason = AutoApprovalReason::DefaultAction;
+            let answer = answer
+                .filter(|&approve| !approve || PLUGINS.allows_auto_approval(info.agent_id, &pending.request, reason));
+            let kind = if answer.is_some() {
+                answers.push((info.agent_id, info.input_id.clone(), answer));
+                EscalationKind::DefaultApplied
+            } else {
+                let Some(mins) = policy.remind_after_mins else {
+                    continue;
+                };
+                if now.saturating_sub(info.last_reminded_at.unwrap_or(info.created_at)) < mins * 60 {
+                    continue;
+                }
+                info.reminders += 1;
+                info.last_reminded_at = Some(now);
+                EscalationKind::Reminder
+            };
+            escalations.push(PermissionEscalation {
+                agent_id: info.agent_id,
+                input_id: info.input_id.clone(),
+                message: info.message.clone(),
+                kind,
+                age_secs,
+                reminders: info.reminders,
+                approved: answer,
+            });
+        }
+        for (agent_id, input_id, approved) in answers {
+            let response = PermissionUserResponse {
+                approved: approved.unwrap_or(false),
+                option_id: None,
+            };
+            // The prompt may have ended meanwhile
+            let _ = self.respond(agent_id, &input_id, response);
+        }
+        escalations
+    }
+
+    /// Count a request of `scope` in the agent's current prompt; returns
+    /// how many there were so far
+    pub fn note_similar(&self, agent_id: Uuid, scope: &str) -> u32 {
+        let mut similar = self.similar.entry(agent_id).or_default();
+        let seen = similar.seen.entry(scope.to_string()).or_default();
+        *seen += 1;
+        *seen
+    }
+
+    /// Whether the user allowed all requests of `scope` for the current prompt
+    pub fn allows_similar(&self, agent_id: Uuid, scope: &str) -> bool {
+        self.similar.get(&agent_id).is_some_and(|s| s.allowed.contains(scope))
+    }
+
+    /// Forget the similar requests seen and allowed during a prompt
+    pub fn end_prompt(&self, agent_id: Uuid) {
+        self.similar.remove(&agent_id);
+    }
+
+    /// A request still waiting for an answer
+    pub fn get(&self, agent_id: Uuid, input_id: &str) -> Option<PendingPermissionInfo> {
+        self.channels.get(&format!("{}:{}", agent_id, input_id)).map(|c| c.info.clone())
+    }
+
+    /// Input id of the request waiting for permission to run a tool call
+    pub fn for_tool_call(&self, agent_id: Uuid, tool_call_id: &str) -> Option<String> {
+        self.channels
+            .iter()
+            .filter(|c| c.info.agent_id == agent_id)
+            .find(|c| c.request.tool_call.tool_call_id == tool_call_id)
+            .map(|c| c.info.input_id.clone())
+    }
+
+    /// Forget a request without answering it
+    pub fn remove(&self, agent_id: Uuid, input_id: &str) {
+        self.channels.remove(&format!("{}:{}", agent_id, input_id));
+    }
+
+    /// Drop the requests of an agent unanswered, oldest first. The prompt
+    /// waiting on each tells the agent the request was cancelled.
+    pub fn cancel_agent(&self, agent_id: Uuid) -> Vec<PendingPermissionInfo> {
+        let keys: Vec<PermissionKey> = self
+            .channels
+            .iter()
+            .filter(|c| c.info.agent_id == agent_id)
+            .map(|c| c.key().clone())
+            .collect();
+        let mut cancelled: Vec<PendingPermissionInfo> = keys
+            .iter()
+            .filter_map(|key| self.channels.remove(key))
+            .map(|(_, pending)| pending.info)
+            .collect();
+        cancelled.sort_by_key(|p| p.seq);
+        for info in &cancelled {
+            // No subscribers is fine
+            let _ = CANCELLED_PERMISSIONS.send(info.clone());
+        }
+        cancelled
+    }
+
+    /// All requests still waiting for an answer, oldest first.
+    ///
+    /// Unlike agent info this never waits on an agent that is mid-prompt.
+    pub fn list(&self) -> Vec<PendingPermissionInfo> {
+        let mut list: Vec<PendingPermissionInfo> =
+            self.channels.iter().map(|c| c.info.clone()).collect();
+        list.sort_by_key(|p| p.seq);
+        list
+    }
+
+    /// Like [`list`](Self::list), with the full request of each
+    pub fn list_requests(&self) -> Vec<PendingPermission> {
+        let mut list: Vec<PendingPermission> = self
+            .channels
+            .iter()
+            .map(|c| PendingPermission {
+                info: c.info.clone(),
+                request: c.request.clone(),
+            })
+            .collect();
+        list.sort_by_key(|p| p.info.seq);
+        list
+    }
+
+    /// The most recent request still waiting for an answer
+    pub fn latest(&self) -> Option<PendingPermissionInfo> {
+        self.channels
+            .iter()
+            .max_by_key(|c| c.info.seq)
+            .map(|c| c.info.clone())
+    }
+}
+
+/// Name and status of an agent, available even while it is mid-prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStatusSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub working_directory: String,
+    pub status: AgentStatus,
 }
 
 /// Wrapper around AgentProcess to allow async locking
 pub struct AgentHandle {
-    inner: Arc<Mutex<AgentProcess>>,
+    id: Uuid,
+    name: String,
+    working_directory: String,
+    tool_calls: Arc<ToolCallHistory>,
+    cancel: Arc<PromptCancel>,
+    spawn_config: Option<SpawnConfig>,
+    launch: Option<LaunchInfo>,
+    protocol: Option<NegotiatedProtocol>,
+    agent: AgentRef,
+}
+
+/// An agent's lock and its latest published info, cloned out of the map so
+/// no map shard stays locked across an await
+#[derive(Clone)]
+struct AgentRef {
+    process: Arc<Mutex<AgentProcess>>,
+    info: watch::Receiver<AgentInfo>,
 }
 
 impl AgentHandle {
     fn new(agent: AgentProcess) -> Self {
         Self {
-            inner: Arc::new(Mutex::new(agent)),
+            id: agent.id,
+            name: agent.name.clone(),
+            working_directory: agent.working_directory.clone(),
+            tool_calls: agent.tool_calls.clone(),
+            cancel: agent.cancel.clone(),
+            spawn_config: agent.spawn_config.clone(),
+            launch: agent.launch.clone(),
+            protocol: agent.protocol.clone(),
+            agent: AgentRef {
+                info: agent.subscribe_state(),
+                process: Arc::new(Mutex::new(agent)),
+            },
         }
     }
 
-    pub async fn info(&self) -> AgentInfo {
-        self.inner.lock().await.info()
+    /// Status without waiting on an agent busy with a prompt
+    pub fn status_summary(&self) -> AgentStatusSummary {
+        AgentStatusSummary {
+            id: self.id,
+            name: self.name.clone(),
+            working_directory: self.working_directory.clone(),
+            status: self.agent.info.borrow().status,
+        }
     }
 
-    pub async fn stop(&self) -> Result<(), AgentProcessError> {
-        self.inner.lock().await.stop().await
+    /// Latest info, never waiting on the agent
+    pub fn info(&self) -> AgentInfo {
+        self.agent.info.borrow().clone()
+    }
+
+    /// What the agent was started with and negotiated. The environment is
+    /// only filled in for processes running on this machine outside a
+    /// container.
+    pub fn environment(&self) -> AgentEnvironment {
+        let config = self.spawn_config.as_ref();
+        let transport = config.map(|config| match &config.transport {
+            Transport::WebSocket { url } => redact_url(url),
+            transport => transport.to_string(),
+        });
+        let local = config.is_some_and(|c| c.transport.is_stdio() && c.sandbox.is_none());
+        let environment = match (&self.launch, config) {
+            (Some(launch), Some(config)) if local => {
+                sanitized_environment(&config.env, &launch.keychain_variables)
+            }
+            _ => Default::default(),
+        };
+        AgentEnvironment {
+            agent_id: self.id,
+            name: self.name.clone(),
+            provider_id: self.info().provider_id,
+            transport,
+            launch: self.launch.clone(),
+            protocol: self.protocol.clone(),
+            environment,
+        }
     }
 }
 
 pub struct AgentPool {
     agents: DashMap<Uuid, AgentHandle>,
     pending_permissions: Arc<PendingPermissions>,
+    file_locks: Arc<FileLocks>,
+    tasks: Arc<TaskGraph>,
+    checkpoints: Arc<CheckpointStore>,
+    git_checkpoints: AtomicBool,
+    session_history: SessionHistory,
+    request_policies: RwLock<RequestPolicies>,
+    sandbox: RwLock<Option<ContainerSandbox>>,
+    resource_limits: RwLock<ResourceLimits>,
+    package_runner: RwLock<String>,
+    journal: RwLock<Option<Arc<PromptJournal>>>,
+    scheduler: PromptScheduler,
+    escalation_policy: RwLock<EscalationPolicy>,
 }
 
 impl AgentPool {
@@ -68,6 +425,18 @@ impl AgentPool {
         Self {
             agents: DashMap::new(),
             pending_permissions: Arc::new(PendingPermissions::new()),
+            file_locks: Arc::new(FileLocks::new()),
+            tasks: Arc::new(TaskGraph::new()),
+            checkpoints: Arc::new(CheckpointStore::new()),
+            git_checkpoints: AtomicBool::new(false),
+            session_history: SessionHistory::new(),
+            request_policies: RwLock::new(RequestPolicies::default()),
+            sandbox: RwLock::new(None),
+            resource_limits: RwLock::new(ResourceLimits::default()),
+            package_runner: RwLock::new(default_package_runner().to_string()),
+            journal: RwLock::new(None),
+            scheduler: PromptScheduler::new(),
+            escalation_policy: RwLock::new(EscalationPolicy::default()),
         }
     }
 
@@ -75,28 +444,96 @@ impl AgentPool {
         self.pending_permissions.clone()
     }
 
+    pub fn task_graph(&self) -> Arc<TaskGraph> {
+        self.tasks.clone()
+    }
+
+    pub fn checkpoints(&self) -> Arc<CheckpointStore> {
+        self.checkpoints.clone()
+    }
+
+    /// When waiting permission requests get reminders and a default answer
+    pub fn set_escalation_policy(&self, policy: EscalationPolicy) {
+        *self.escalation_policy.write().unwrap() = policy;
+    }
+
+    /// Escalate waiting permission requests every
+    /// [`ESCALATION_SWEEP_INTERVAL`], publishing each escalation on
+    /// [`PERMISSION_ESCALATIONS`]
+    pub async fn run_escalation_sweeper(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(ESCALATION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let policy = *self.escalation_policy.read().unwrap();
+            let now = now_secs();
+            for escalation in self.pending_permissions.sweep(&policy, now) {
+                // No subscribers is fine
+                let _ = PERMISSION_ESCALATIONS.send(escalation);
+            }
+        }
+    }
+
+    /// Request timeouts for agents spawned from now on
+    pub fn set_request_policies(&self, policies: RequestPolicies) {
+        *self.request_policies.write().unwrap() = policies;
+    }
+
+    /// Container for local agents spawned from now on, or None to run them
+    /// directly on the host
+    pub fn set_sandbox(&self, sandbox: Option<ContainerSandbox>) {
+        *self.sandbox.write().unwrap() = sandbox;
+    }
+
+    /// CPU and memory limits for local agents spawned from now on
+    pub fn set_resource_limits(&self, limits: ResourceLimits) {
+        *self.resource_limits.write().unwrap() = limits;
+    }
+
+    /// Command running the npm package of the default provider, or None for
+    /// the platform's npx
+    pub fn set_package_runner(&self, package_runner: Option<String>) {
+        *self.package_runner.write().unwrap() =
+            package_runner.unwrap_or_else(|| default_package_runner().to_string());
+    }
+
+    /// Journal prompts while they run, to find them again after a crash
+    pub fn set_journal(&self, journal: Arc<PromptJournal>) {
+        *self.journal.write().unwrap() = Some(journal);
+    }
+
+    /// Hold permission requests of edits to files another agent is editing
+    pub fn set_hold_conflicting_edits(&self, enabled: bool) {
+        self.file_locks.set_hold_permissions(enabled);
+    }
+
+    /// Files agents are editing right now
+    pub fn file_locks(&self) -> Vec<FileLock> {
+        self.file_locks.list()
+    }
+
+    /// Order and concurrency of prompts across agents
+    pub fn set_scheduling_policy(&self, policy: SchedulingPolicy) {
+        self.scheduler.set_policy(policy);
+    }
+
+    /// Prompts running and waiting for their turn
+    pub fn queue(&self) -> PoolQueue {
+        self.scheduler.snapshot()
+    }
+
+    /// Enable or disable git checkpoints before each task
+    pub fn set_git_checkpoints(&self, enabled: bool) {
+        self.git_checkpoints.store(enabled, Ordering::Relaxed);
+    }
+
     pub async fn spawn_agent(
         &self,
         name: String,
         working_directory: String,
     ) -> Result<AgentInfo, AgentProcessError> {
-        let mut agent = AgentProcess::spawn(name, working_directory).await?;
-        agent.initialize().await?;
-
-        // Try to create session - if auth required, still add agent to pool
-        match agent.create_session().await {
-            Ok(_) => {}
-            Err(AgentProcessError::AuthRequired) => {
-                // Agent needs auth - add to pool anyway so user can authenticate
-                tracing::info!("Agent requires authentication");
-            }
-            Err(e) => return Err(e),
-        }
-
-        let info = agent.info();
-        let handle = AgentHandle::new(agent);
-        self.agents.insert(info.id, handle);
-        Ok(info)
+        let package_runner = self.package_runner.read().unwrap().clone();
+        self.spawn_agent_with_config(SpawnConfig::claude(name, working_directory, &package_runner))
+            .await
     }
 
     /// Spawn an agent with a custom configuration
@@ -104,39 +541,97 @@ impl AgentPool {
         &self,
         config: SpawnConfig,
     ) -> Result<AgentInfo, AgentProcessError> {
+        let agent = self.spawn_detached(config).await?;
+        self.add_agent(agent).await
+    }
+
+    /// Spawn an agent with the pool's sandbox, limits and request policies
+    /// but leave it out of the pool, uninitialized
+    pub async fn spawn_detached(&self, mut config: SpawnConfig) -> Result<AgentProcess, AgentProcessError> {
+        if config.transport.is_stdio() {
+            if config.sandbox.is_none() {
+                config.sandbox = self.sandbox.read().unwrap().clone();
+            }
+            if config.limits.is_none() {
+                config.limits = Some(*self.resource_limits.read().unwrap());
+            }
+        }
         let mut agent = AgentProcess::spawn_with_config(config).await?;
-        agent.initialize().await?;
+        agent.set_request_policies(self.request_policies.read().unwrap().clone());
+        Ok(agent)
+    }
 
-        // Try to create session - if auth required, still add agent to pool
-        match agent.create_session().await {
-            Ok(_) => {}
-            Err(AgentProcessError::AuthRequired) => {
-                // Agent needs auth - add to pool anyway so user can authenticate
-                tracing::info!("Agent requires authentication");
+    /// Initialize an agent, open its first session and add it to the pool
+    pub async fn add_agent(&self, mut agent: AgentProcess) -> Result<AgentInfo, AgentProcessError> {
+        agent.set_request_policies(self.request_policies.read().unwrap().clone());
+        agent.set_file_locks(self.file_locks.clone());
+        if let Err(e) = Self::open(&mut agent).await {
+            // Dropping the agent would leave its process running
+            if let Err(stop_error) = agent.stop().await {
+                tracing::warn!("Failed to stop agent that failed to start: {}", stop_error);
             }
-            Err(e) => return Err(e),
+            return Err(e);
         }
 
         let info = agent.info();
+        forward_state_changes(agent.subscribe_state());
         let handle = AgentHandle::new(agent);
         self.agents.insert(info.id, handle);
         Ok(info)
     }
 
-    pub async fn get_agent_info(&self, id: &Uuid) -> Option<AgentInfo> {
-        if let Some(handle) = self.agents.get(id) {
-            Some(handle.info().await)
-        } else {
-            None
+    /// Initialize an agent and create its first session. An agent that
+    /// wants authentication first stays in NeedsAuth, so the user can log
+    /// in and retry the session.
+    async fn open(agent: &mut AgentProcess) -> Result<(), AgentProcessError> {
+        agent.initialize().await?;
+        match agent.create_session().await {
+            Ok(_) => {
+                if let Err(e) = agent.apply_default_model().await {
+                    tracing::warn!("Failed to select default model of agent {}: {}", agent.id, e);
+                }
+                Ok(())
+            }
+            Err(AgentProcessError::AuthRequired) => {
+                tracing::info!("Agent {} requires authentication", agent.id);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
-    pub async fn list_agents(&self) -> Vec<AgentInfo> {
-        let mut infos = Vec::new();
-        for entry in self.agents.iter() {
-            infos.push(entry.value().info().await);
+    /// Last known info of an agent, without waiting on it while it works
+    pub fn get_agent_info(&self, id: &Uuid) -> Option<AgentInfo> {
+        self.agents.get(id).map(|handle| handle.info())
+    }
+
+    pub fn list_agents(&self) -> Vec<AgentInfo> {
+        self.agents.iter().map(|entry| entry.value().info()).collect()
+    }
+
+    fn agent_ref(&self, id: &Uuid) -> Result<AgentRef, AgentProcessError> {
+        self.agents
+            .get(id)
+            .map(|handle| handle.agent.clone())
+            .ok_or(AgentProcessError::NoSession)
+    }
+
+    /// Names and statuses of all agents without blocking on busy ones
+    pub fn agent_statuses(&self) -> Vec<AgentStatusSummary> {
+        let mut statuses: Vec<AgentStatusSummary> =
+            self.agents.iter().map(|h| h.value().status_summary()).collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        statuses
+    }
+
+    /// Refuse prompts to agents that crashed, need authentication or are busy
+    /// before they reach the protocol
+    pub fn check_can_prompt(&self, agent_id: &Uuid) -> Result<(), AgentProcessError> {
+        let info = self.get_agent_info(agent_id).ok_or(AgentProcessError::NoSession)?;
+        match info.prompt_rejection() {
+            Some(rejection) => Err(AgentProcessError::PromptRejected(rejection)),
+            None => Ok(()),
         }
-        infos
     }
 
     pub async fn send_prompt(
@@ -145,22 +640,489 @@ impl AgentPool {
         prompt: &str,
         update_tx: mpsc::Sender<AgentUpdate>,
     ) -> Result<String, AgentProcessError> {
-        let handle = self
+        self.check_can_prompt(&agent_id)?;
+        let project = self
             .agents
             .get(&agent_id)
+            .map(|h| h.working_directory.clone())
             .ok_or(AgentProcessError::NoSession)?;
-        // Clone the Arc to release the DashMap lock, then use the async lock
-        let handle = handle.value().inner.clone();
+        let _permit = self
+            .scheduler
+            .acquire(agent_id, &project, PromptPriority::Normal, None)
+            .await;
+        let (text, _) = self
+            .send_prompt_with_stop_reason(agent_id, None, prompt, update_tx)
+            .await?;
+        Ok(text)
+    }
+
+    /// Send a prompt and also return the stop reason the agent reported;
+    /// the caller holds the prompt's turn from the scheduler
+    async fn send_prompt_with_stop_reason(
+        &self,
+        agent_id: Uuid,
+        preamble: Option<&str>,
+        prompt: &str,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<(String, Option<String>), AgentProcessError> {
+        // Checked again now that the prompt has its turn; the agent may have
+        // crashed while it waited
+        self.check_can_prompt(&agent_id)?;
+        let agent_ref = self.agent_ref(&agent_id)?;
         let pending_perms = self.pending_permissions.clone();
-        let mut agent = handle.lock().await;
-        agent.send_prompt(prompt, update_tx, pending_perms).await
+        let journaled = self.journal_start(agent_id, prompt);
+        let mut agent = agent_ref.process.lock().await;
+        let result = agent
+            .send_prompt_with_preamble(preamble, prompt, update_tx, pending_perms)
+            .await;
+        self.finish_prompt(agent_id, journaled);
+        Ok((result?, agent.last_stop_reason.clone()))
+    }
+
+    /// Send a user follow-up to the agent's session. It runs right after the
+    /// agent's current turn, and may answer the questions the agent paused on.
+    pub async fn send_followup(
+        &self,
+        agent_id: Uuid,
+        text: &str,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<String, AgentProcessError> {
+        self.check_can_follow_up(&agent_id)?;
+        let project = self
+            .agents
+            .get(&agent_id)
+            .map(|h| h.working_directory.clone())
+            .ok_or(AgentProcessError::NoSession)?;
+        let _permit = self
+            .scheduler
+            .acquire(agent_id, &project, PromptPriority::High, None)
+            .await;
+        self.check_can_follow_up(&agent_id)?;
+        let agent_ref = self.agent_ref(&agent_id)?;
+        let pending_perms = self.pending_permissions.clone();
+        let journaled = self.journal_start(agent_id, text);
+        let mut agent = agent_ref.process.lock().await;
+        let result = agent.send_followup(text, update_tx, pending_perms).await;
+        self.finish_prompt(agent_id, journaled);
+        result
+    }
+
+    /// Like [`Self::check_can_prompt`], but an agent paused only on questions
+    /// to the user takes the follow-up as their answer
+    fn check_can_follow_up(&self, agent_id: &Uuid) -> Result<(), AgentProcessError> {
+        let info = self.get_agent_info(agent_id).ok_or(AgentProcessError::NoSession)?;
+        let answers_questions = info.status == AgentStatus::Paused
+            && !info.pending_inputs.is_empty()
+            && info
+                .pending_inputs
+                .iter()
+                .all(|input| input.input_type == PendingInputType::UserQuestion);
+        match info.prompt_rejection() {
+            Some(_) if answers_questions => Ok(()),
+            Some(rejection) => Err(AgentProcessError::PromptRejected(rejection)),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a prompt in the journal, if there is one, until it finishes
+    fn journal_start(&self, agent_id: Uuid, prompt: &str) -> Option<(Arc<PromptJournal>, String)> {
+        let journal = self.journal.read().unwrap().clone()?;
+        let info = self.get_agent_info(&agent_id)?;
+        let entry = journal.start(
+            agent_id,
+            &info.name,
+            info.provider_id,
+            &info.working_directory,
+            prompt,
+        );
+        Some((journal, entry))
+    }
+
+    fn finish_prompt(&self, agent_id: Uuid, journaled: Option<(Arc<PromptJournal>, String)>) {
+        // Tool calls and "allow all similar" answers cannot outlive the prompt
+        self.file_locks.release_agent(agent_id);
+        if let Some(handle) = self.agents.get(&agent_id) {
+            handle.tool_calls.finish_running();
+        }
+        self.pending_permissions.end_prompt(agent_id);
+        if let Some((journal, entry)) = journaled {
+            journal.finish(&entry);
+        }
+    }
+
+    /// Submit a task to the graph; it starts as soon as its dependencies completed
+    pub fn submit_task(
+        self: &Arc<Self>,
+        spec: TaskSpec,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<TaskInfo, AgentProcessError> {
+        if !self.agents.contains_key(&spec.agent_id) {
+            return Err(AgentProcessError::NoSession);
+        }
+
+        let (task, ready) = self.tasks.add(spec).map_err(AgentProcessError::TaskError)?;
+        if ready {
+            self.clone().run_task(task.id.clone(), update_tx);
+        }
+        Ok(task)
+    }
+
+    /// Send a prompt as a standalone task and wait for its result
+    pub async fn send_prompt_as_task(
+        self: &Arc<Self>,
+        agent_id: Uuid,
+        prompt: &str,
+        priority: PromptPriority,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<String, AgentProcessError> {
+        let spec = TaskSpec {
+            agent_id,
+            prompt: prompt.to_string(),
+            depends_on: Vec::new(),
+            inject_results: false,
+            context: None,
+            priority,
+            preamble: None,
+        };
+        self.run_task_now(spec, update_tx).await
+    }
+
+    /// Add a task without dependencies and wait for its result
+    pub async fn run_task_now(
+        self: &Arc<Self>,
+        spec: TaskSpec,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<String, AgentProcessError> {
+        if !spec.depends_on.is_empty() {
+            return Err(AgentProcessError::TaskError(
+                "Tasks with dependencies must be submitted".to_string(),
+            ));
+        }
+        self.check_can_prompt(&spec.agent_id)?;
+        let (task, _) = self.tasks.add(spec).map_err(AgentProcessError::TaskError)?;
+        self.execute_task(&task.id, update_tx).await
+    }
+
+    /// Run a task in the background
+    /// Have `reviewer` review a completed task in the background; the review
+    /// runs as its own task and its verdict is attached to the reviewed task
+    pub async fn review_task(
+        self: &Arc<Self>,
+        task: TaskInfo,
+        reviewer: Uuid,
+        prompt_template: Option<String>,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<TaskInfo, AgentProcessError> {
+        if !self.agents.contains_key(&reviewer) {
+            return Err(AgentProcessError::NoSession);
+        }
+        if task.agent_id == reviewer {
+            return Err(AgentProcessError::TaskError(
+                "An agent cannot review its own task".to_string(),
+            ));
+        }
+
+        let diff = match &task.change_summary {
+            Some(ChangeSummary {
+                repo_root: Some(root),
+                from_tree: Some(from),
+                to_tree: Some(to),
+                ..
+            }) => diff_patch(Path::new(root), from, to).await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to get diff of task {}: {}", task.id, e);
+                String::new()
+            }),
+            _ => String::new(),
+        };
+        let spec = TaskSpec {
+            agent_id: reviewer,
+            prompt: build_review_prompt(prompt_template.as_deref(), &task, &diff),
+            depends_on: Vec::new(),
+            inject_results: false,
+            context: None,
+            priority: task.priority,
+            preamble: None,
+        };
+        let (review_task, _) = self.tasks.add(spec).map_err(AgentProcessError::TaskError)?;
+        self.tasks.set_review_of(&review_task.id, &task.id);
+
+        let mut review = TaskReview {
+            reviewer_agent_id: reviewer,
+            review_task_id: review_task.id.clone(),
+            status: ReviewStatus::InProgress,
+            verdict: None,
+            comments: None,
+            error: None,
+        };
+        self.tasks.set_review(&task.id, review.clone());
+
+        let pool = self.clone();
+        tokio::spawn(async move {
+            match pool.execute_task(&review.review_task_id, update_tx).await {
+                Ok(reply) => {
+                    review.status = ReviewStatus::Completed;
+                    review.verdict = Some(parse_verdict(&reply));
+                    review.comments = Some(reply);
+                }
+                Err(e) => {
+                    review.status = ReviewStatus::Failed;
+                    review.error = Some(e.to_string());
+                }
+            }
+            pool.tasks.set_review(&task.id, review);
+        });
+
+        Ok(review_task)
+    }
+
+    fn run_task(self: Arc<Self>, task_id: String, update_tx: mpsc::Sender<AgentUpdate>) {
+        tokio::spawn(async move {
+            let _ = self.execute_task(&task_id, update_tx).await;
+        });
+    }
+
+    /// Run a task to completion, then start any dependents it unblocked
+    async fn execute_task(
+        self: &Arc<Self>,
+        task_id: &str,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<String, AgentProcessError> {
+        // Tasks stay pending while they wait for their turn
+        let target = self.tasks.get(task_id).and_then(|task| {
+            let project = self.agents.get(&task.agent_id)?.working_directory.clone();
+            Some((task, project))
+        });
+        let _permit = match &target {
+            Some((task, project)) => Some(
+                self.scheduler
+                    .acquire(task.agent_id, project, task.priority, Some(task_id))
+                    .await,
+            ),
+            None => None,
+        };
+
+        let task = self.tasks.start(task_id).ok_or_else(|| {
+            AgentProcessError::TaskError(format!("Task {} is not waiting to run", task_id))
+        })?;
+
+        let working_directory = self
+            .get_agent_info(&task.agent_id)
+            .map(|info| info.working_directory);
+
+        if self.git_checkpoints.load(Ordering::Relaxed) {
+            if let Some(ref dir) = working_directory {
+                self.create_checkpoint(task_id, Path::new(dir)).await;
+            }
+        }
+        let before = match working_directory {
+            Some(ref dir) => TreeSnapshot::capture(Path::new(dir)).await.ok(),
+            None => None,
+        };
+
+        let outcome = match self
+            .send_prompt_with_stop_reason(
+                task.agent_id,
+                task.preamble.as_deref(),
+                &task.prompt,
+                update_tx.clone(),
+            )
+            .await
+        {
+            Ok((text, stop_reason)) => {
+                self.tasks.set_stop_reason(task_id, stop_reason);
+                Ok(text)
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Some(before) = before {
+            match before.changes_since().await {
+                Ok(summary) => self.tasks.set_change_summary(task_id, summary),
+                Err(e) => tracing::warn!("Failed to summarize changes of task {}: {}", task_id, e),
+            }
+        }
+
+        let ready = self
+            .tasks
+            .finish(task_id, outcome.as_ref().map(|s| s.clone()).map_err(|e| e.to_string()));
+        for next in ready {
+            self.clone().run_task(next, update_tx.clone());
+        }
+
+        outcome
+    }
+
+    /// Checkpoint the agent's repo before a task; failures only log since
+    /// not every working directory is a git repository
+    async fn create_checkpoint(&self, task_id: &str, working_directory: &Path) {
+        if let Err(e) = self.checkpoints.create(task_id, working_directory).await {
+            tracing::warn!("Skipping checkpoint for task {}: {}", task_id, e);
+        }
+    }
+
+    /// Compact an agent's session, using the agent's own compaction command
+    /// when it has one, or else replacing the session with a fresh one seeded
+    /// with a summary of the old one
+    pub async fn compact_session(
+        &self,
+        agent_id: Uuid,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<CompactionRecord, AgentProcessError> {
+        let info = self
+            .get_agent_info(&agent_id)
+            .ok_or(AgentProcessError::NoSession)?;
+        let previous_session_id = info.session_id.clone();
+
+        let (method, summary) = if let Some(command) = find_compact_command(&info.available_commands) {
+            self.send_prompt(agent_id, &command, update_tx).await?;
+            (CompactionMethod::AgentCommand, None)
+        } else {
+            let summary = self
+                .send_prompt(agent_id, SUMMARY_PROMPT, update_tx.clone())
+                .await?;
+            self.create_session(&agent_id).await?;
+            self.send_prompt(agent_id, &seed_prompt(&summary), update_tx)
+                .await?;
+            (CompactionMethod::NewSession, Some(summary))
+        };
+
+        let session_id = self
+            .get_agent_info(&agent_id)
+            .and_then(|info| info.session_id);
+        let record = CompactionRecord {
+            agent_id,
+            method,
+            previous_session_id,
+            session_id,
+            summary,
+            created_at: now_secs(),
+        };
+        self.session_history.record(record.clone());
+        Ok(record)
+    }
+
+    /// Ask an agent to summarize its conversation, e.g. to seed another
+    /// session with it
+    pub async fn summarize(
+        &self,
+        agent_id: Uuid,
+        update_tx: mpsc::Sender<AgentUpdate>,
+    ) -> Result<String, AgentProcessError> {
+        self.send_prompt(agent_id, SUMMARY_PROMPT, update_tx).await
+    }
+
+    pub fn agent_environment(&self, agent_id: &Uuid) -> Option<AgentEnvironment> {
+        self.agents.get(agent_id).map(|handle| handle.environment())
+    }
+
+    /// How an agent was spawned; none for agents connected to a stream
+    pub fn spawn_config(&self, agent_id: &Uuid) -> Option<SpawnConfig> {
+        self.agents.get(agent_id).and_then(|handle| handle.spawn_config.clone())
+    }
+
+    pub async fn set_mode(&self, agent_id: &Uuid, mode_id: &str) -> Result<(), AgentProcessError> {
+        let agent_ref = self.agent_ref(agent_id)?;
+        let mut agent = agent_ref.process.lock().await;
+        agent.set_mode(mode_id).await
+    }
+
+    pub async fn set_model(&self, agent_id: &Uuid, model_id: &str) -> Result<(), AgentProcessError> {
+        let agent_ref = self.agent_ref(agent_id)?;
+        let mut agent = agent_ref.process.lock().await;
+        agent.set_model(model_id).await
+    }
+
+    /// Compactions performed on an agent's sessions, oldest first
+    pub fn session_history(&self, agent_id: &Uuid) -> Vec<CompactionRecord> {
+        self.session_history.for_agent(agent_id)
+    }
+
+    /// Full content of one of an agent's recent tool calls
+    pub fn tool_call_artifact(
+        &self,
+        agent_id: &Uuid,
+        tool_call_id: &str,
+    ) -> Option<ToolCallArtifact> {
+        self.agents
+            .get(agent_id)
+            .and_then(|handle| handle.tool_calls.get(tool_call_id))
+    }
+
+    /// Output of one of an agent's recent tool calls, in full even if it was
+    /// spooled to disk
+    pub fn tool_output(
+        &self,
+        agent_id: &Uuid,
+        tool_call_id: &str,
+    ) -> Result<Option<ToolOutput>, String> {
+        let Some(tool_calls) = self.agents.get(agent_id).map(|h| h.tool_calls.clone()) else {
+            return Ok(None);
+        };
+        tool_calls.output(tool_call_id)
+    }
+
+    /// Tool calls not finished yet, of one agent or of all
+    pub fn running_tool_calls(&self, agent_id: Option<&Uuid>) -> Vec<RunningToolCall> {
+        let mut running: Vec<RunningToolCall> = self
+            .agents
+            .iter()
+            .filter(|h| agent_id.is_none_or(|id| h.key() == id))
+            .flat_map(|h| h.tool_calls.running(h.id))
+            .collect();
+        running.sort_by_key(|c| Reverse(c.running_ms));
+        running
+    }
+
+    /// Ask the agent to end its running prompt; the prompt returns once the
+    /// agent answers it as cancelled
+    pub fn cancel_prompt(&self, agent_id: &Uuid) -> Result<(), String> {
+        let handle = self
+            .agents
+            .get(agent_id)
+            .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+        if handle.agent.info.borrow().status != AgentStatus::Working {
+            return Err(format!("Agent {} is not running a prompt", handle.name));
+        }
+        handle.cancel.request();
+        Ok(())
+    }
+
+    /// Stop a single tool call. ACP has no cancel for one tool call, so only
+    /// a call still waiting for permission can be stopped, by rejecting it;
+    /// the agent goes on with the rest of the prompt.
+    pub fn cancel_tool_call(&self, agent_id: &Uuid, tool_call_id: &str) -> Result<(), String> {
+        let call = self
+            .tool_call_artifact(agent_id, tool_call_id)
+            .ok_or_else(|| format!("Unknown tool call: {}", tool_call_id))?;
+        if call.is_finished() {
+            return Err(format!("Tool call {} already finished", tool_call_id));
+        }
+        let Some(input_id) = self.pending_permissions.for_tool_call(*agent_id, tool_call_id) else {
+            return Err(format!(
+                "Tool call {} is already running and the agent cannot stop it alone; \
+cancel the whole prompt instead",
+                tool_call_id
+            ));
+        };
+        let response = PermissionUserResponse {
+            approved: false,
+            option_id: None,
+        };
+        self.pending_permissions
+            .respond(*agent_id, &input_id, response)
+            .map_err(|e| e.to_string())
     }
 
     pub async fn stop_agent(&self, agent_id: &Uuid) -> Result<(), AgentProcessError> {
-        if let Some(handle) = self.agents.get(agent_id) {
-            handle.stop().await?;
+        // A prompt waiting for permission holds the agent's lock until answered
+        self.pending_permissions.cancel_agent(*agent_id);
+        if let Ok(agent_ref) = self.agent_ref(agent_id) {
+            agent_ref.process.lock().await.stop().await?;
+        }
+        if let Some((_, handle)) = self.agents.remove(agent_id) {
+            handle.tool_calls.finish_running();
         }
-        self.agents.remove(agent_id);
+        self.file_locks.release_agent(*agent_id);
         Ok(())
     }
 
@@ -176,43 +1138,81 @@ impl AgentPool {
         self.agents.len()
     }
 
+    /// Answer a permission request. Approving a high risk request needs
+    /// `confirm_high_risk`.
     pub fn respond_to_permission(
         &self,
         agent_id: &Uuid,
         input_id: &str,
         approved: bool,
         option_id: Option<String>,
+        confirm_high_risk: bool,
     ) -> Result<(), AgentProcessError> {
+        let high_risk = self
+            .pending_permissions
+            .get(*agent_id, input_id)
+            .is_some_and(|p| p.high_risk);
+        if approved && high_risk && !confirm_high_risk {
+            return Err(AgentProcessError::ConfirmationRequired(input_id.to_string()));
+        }
         // Use the shared pending_permissions directly - no agent lock needed!
         let response = PermissionUserResponse { approved, option_id };
         self.pending_permissions.respond(*agent_id, input_id, response)
     }
 
+    /// Answer the most recently requested permission across all agents.
+    /// High risk requests cannot be approved this way.
+    pub fn respond_to_latest_permission(
+        &self,
+        approved: bool,
+    ) -> Result<PendingPermissionInfo, AgentProcessError> {
+        let latest = self.pending_permissions.latest().ok_or_else(|| {
+            AgentProcessError::CommunicationError("No pending permission requests".to_string())
+        })?;
+        if approved && latest.high_risk {
+            return Err(AgentProcessError::ConfirmationRequired(latest.input_id));
+        }
+        let response = PermissionUserResponse {
+            approved,
+            option_id: None,
+        };
+        self.pending_permissions
+            .respond(latest.agent_id, &latest.input_id, response)?;
+        Ok(latest)
+    }
+
     /// Start authentication for an agent
     pub async fn start_auth(
         &self,
         agent_id: &Uuid,
         auth_method_id: &str,
     ) -> Result<crate::acp::AuthStartResult, AgentProcessError> {
-        let handle = self
-            .agents
-            .get(agent_id)
-            .ok_or(AgentProcessError::NoSession)?;
-        let handle = handle.value().inner.clone();
-        let mut agent = handle.lock().await;
+        let agent_ref = self.agent_ref(agent_id)?;
+        let mut agent = agent_ref.process.lock().await;
         agent.start_auth(auth_method_id).await
     }
 
     /// Create a session for an agent (used after auth completes)
     pub async fn create_session(&self, agent_id: &Uuid) -> Result<String, AgentProcessError> {
-        let handle = self
-            .agents
-            .get(agent_id)
-            .ok_or(AgentProcessError::NoSession)?;
-        let handle = handle.value().inner.clone();
-        let mut agent = handle.lock().await;
+        let agent_ref = self.agent_ref(agent_id)?;
+        let mut agent = agent_ref.process.lock().await;
         agent.create_session().await
     }
+
+    /// Sessions an agent that supports session/list has stored for its
+    /// working directory
+    pub async fn list_sessions(&self, agent_id: &Uuid) -> Result<Vec<SessionListEntry>, AgentProcessError> {
+        let agent_ref = self.agent_ref(agent_id)?;
+        let mut agent = agent_ref.process.lock().await;
+        agent.list_sessions().await
+    }
+
+    /// Resume an earlier session on an agent that supports session/load
+    pub async fn load_session(&self, agent_id: &Uuid, session_id: &str) -> Result<(), AgentProcessError> {
+        let agent_ref = self.agent_ref(agent_id)?;
+        let mut agent = agent_ref.process.lock().await;
+        agent.load_session(session_id).await
+    }
 }
 
 impl Default for AgentPool {
@@ -220,3 +1220,341 @@ impl Default for AgentPool {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acp::methods;
+    use crate::acp::scripted::{ScriptStep, ScriptedAgent};
+    use crate::agent::demo::connect_with_delay;
+    use crate::agent::{PendingInputType, PromptRejection, AGENT_USAGE};
+    use std::time::Duration;
+
+    fn permission_request(title: &str) -> RequestPermissionRequest {
+        serde_json::from_value(serde_json::json!({
+            "sessionId": "s1",
+            "toolCall": { "toolCallId": "call_1", "title": title },
+            "options": [
+                { "optionId": "allow", "name": "Allow", "kind": "allow_once" },
+                { "optionId": "reject", "name": "Reject", "kind": "reject_once" }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn input(id: &str, message: &str, outside_paths: &[&str]) -> PendingInput {
+        PendingInput {
+            id: id.to_string(),
+            input_type: PendingInputType::ToolPermission,
+            tool_name: None,
+            message: message.to_string(),
+            timestamp: 0,
+            high_risk: !outside_paths.is_empty(),
+            outside_paths: outside_paths.iter().map(|p| p.to_string()).collect(),
+            risk: None,
+            similar_scope: None,
+            tool_call_id: None,
+            localized: None,
+        }
+    }
+
+    #[test]
+    fn test_latest_pending_permission() {
+        let pending = PendingPermissions::new();
+        let agent = Uuid::new_v4();
+        let (tx1, _rx1) = oneshot::channel();
+        let (tx2, mut rx2) = oneshot::channel();
+        pending.store(agent, &input("perm_req_1", "Edit file", &[]), permission_request("Edit file"), tx1);
+        pending.store(agent, &input("perm_req_2", "Run command", &[]), permission_request("Run command"), tx2);
+
+        assert_eq!(pending.list().len(), 2);
+        let requests = pending.list_requests();
+        assert_eq!(requests[0].info.input_id, "perm_req_1");
+        assert_eq!(requests[1].request.options[1].option_id, "reject");
+        assert_eq!(pending.latest().unwrap().input_id, "perm_req_2");
+
+        let response = PermissionUserResponse {
+            approved: true,
+            option_id: None,
+        };
+        pending.respond(agent, "perm_req_2", response).unwrap();
+        assert!(rx2.try_recv().unwrap().approved);
+        assert_eq!(pending.latest().unwrap().input_id, "perm_req_1");
+    }
+
+    #[test]
+    fn test_high_risk_permission_needs_explicit_confirmation() {
+        let pool = AgentPool::new();
+        let agent = Uuid::new_v4();
+        let (tx, mut rx) = oneshot::channel();
+        pool.get_pending_permissions()
+            .store(agent, &input("perm_req_1", "Edit hosts", &["/etc/hosts"]), permission_request("Edit hosts"), tx);
+
+        assert!(matches!(
+            pool.respond_to_latest_permission(true),
+            Err(AgentProcessError::ConfirmationRequired(_))
+        ));
+        assert!(matches!(
+            pool.respond_to_permission(&agent, "perm_req_1", true, None, false),
+            Err(AgentProcessError::ConfirmationRequired(_))
+        ));
+        assert!(pool.get_pending_permissions().latest().unwrap().high_risk);
+
+        pool.respond_to_permission(&agent, "perm_req_1", true, None, true).unwrap();
+        assert!(rx.try_recv().unwrap().approved);
+    }
+
+    #[test]
+    fn test_allow_similar_permissions_for_the_prompt() {
+        let pending = PendingPermissions::new();
+        let agent = Uuid::new_v4();
+        let scope = similarity_scope(Some("read"), &["src/a.rs".to_string()]).unwrap();
+        assert_eq!(similarity_scope(Some("read"), &["src/b.rs".to_string()]).unwrap(), scope);
+        assert_eq!(pending.note_similar(agent, &scope), 1);
+        assert_eq!(pending.note_similar(agent, &scope), 2);
+
+        let mut repeated = input("perm_req_2", "Read b.rs", &[]);
+        repeated.similar_scope = Some(scope.clone());
+        let (tx, mut rx) = oneshot::channel();
+        pending.store(agent, &repeated, permission_request("Read b.rs"), tx);
+        assert!(pending.get(agent, "perm_req_2").unwrap().similar_scope.is_some());
+        let response = PermissionUserResponse {
+            approved: true,
+            option_id: Some(ALLOW_SIMILAR_OPTION_ID.to_string()),
+        };
+        pending.respond(agent, "perm_req_2", response).unwrap();
+
+        let answer = rx.try_recv().unwrap();
+        assert!(answer.approved);
+        assert_eq!(answer.option_id, None);
+        assert!(pending.allows_similar(agent, &scope));
+        assert!(!pending.allows_similar(Uuid::new_v4(), &scope));
+
+        pending.end_prompt(agent);
+        assert!(!pending.allows_similar(agent, &scope));
+        assert_eq!(pending.note_similar(agent, &scope), 1);
+    }
+
+    #[test]
+    fn test_sweep_reminds_and_applies_default_action() {
+        let pending = PendingPermissions::new();
+        let agent = Uuid::new_v4();
+        let (tx, mut rx) = oneshot::channel();
+        let (risky_tx, mut risky_rx) = oneshot::channel();
+        pending.store(agent, &input("perm_req_1", "Edit file", &[]), permission_request("Edit file"), tx);
+        pending.store(agent, &input("perm_req_2", "Edit hosts", &["/etc/hosts"]), permission_request("Edit hosts"), risky_tx);
+        let policy = EscalationPolicy {
+            remind_after_mins: Some(5),
+            default_action_after_mins: Some(30),
+            default_action: DefaultAction::Approve,
+        };
+
+        assert!(pending.sweep(&policy, 60).is_empty());
+        let reminders = pending.sweep(&policy, 300);
+        assert_eq!(reminders.len(), 2);
+        assert!(reminders.iter().all(|e| e.kind == EscalationKind::Reminder && e.reminders == 1));
+        assert!(pending.sweep(&policy, 400).is_empty());
+        assert_eq!(pending.sweep(&policy, 600)[0].reminders, 2);
+
+        let escalations = pending.sweep(&policy, 1800);
+        let applied: Vec<_> = escalations.iter().filter(|e| e.kind == EscalationKind::DefaultApplied).collect();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].input_id, "perm_req_1");
+        assert!(rx.try_recv().unwrap().approved);
+        // Requests outside the project are never approved by default
+        assert!(risky_rx.try_recv().is_err());
+        assert_eq!(pending.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_agent_info_is_readable_while_a_prompt_runs() {
+        let pool = Arc::new(AgentPool::new());
+        let agent = connect_with_delay("demo".into(), "/tmp/project".into(), Duration::ZERO);
+        let id = pool.add_agent(agent).await.unwrap().id;
+        let (tx, _rx) = mpsc::channel(100);
+        pool.send_prompt(id, "read", tx.clone()).await.unwrap();
+
+        // The second demo turn holds the agent's lock until its permission is answered
+        let prompt = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.send_prompt(id, "edit", tx).await })
+        };
+        let info = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let info = pool.list_agents().remove(0);
+                if !info.pending_inputs.is_empty() {
+                    break info;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(info.status, AgentStatus::Paused);
+
+        let pending = pool.get_pending_permissions().latest().unwrap();
+        pool.respond_to_permission(&id, &pending.input_id, false, None, false).unwrap();
+        prompt.await.unwrap().unwrap();
+        let info = pool.get_agent_info(&id).unwrap();
+        assert_eq!(info.status, AgentStatus::Idle);
+        assert!(info.pending_inputs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prompt_rejection_follows_agent_status() {
+        let pool = Arc::new(AgentPool::new());
+        let agent = connect_with_delay("demo".into(), "/tmp/project".into(), Duration::ZERO);
+        let id = pool.add_agent(agent).await.unwrap().id;
+        assert!(pool.check_can_prompt(&id).is_ok());
+
+        let mut info = pool.get_agent_info(&id).unwrap();
+        info.status = AgentStatus::NeedsAuth;
+        info.auth_methods = vec![crate::acp::AuthMethod {
+            id: "login".to_string(),
+            name: "Log in".to_string(),
+            description: None,
+        }];
+        let rejection = info.prompt_rejection().unwrap();
+        let json = serde_json::to_value(&rejection).unwrap();
+        assert_eq!(json["kind"], "needs_auth");
+        assert_eq!(json["auth_methods"][0]["id"], "login");
+
+        info.status = AgentStatus::Error;
+        assert!(matches!(info.prompt_rejection(), Some(PromptRejection::AgentCrashed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_stopping_an_agent_cancels_its_permission_requests() {
+        let pool = Arc::new(AgentPool::new());
+        let agent = connect_with_delay("demo".into(), "/tmp/project".into(), Duration::ZERO);
+        let id = pool.add_agent(agent).await.unwrap().id;
+        let (tx, _rx) = mpsc::channel(100);
+        pool.send_prompt(id, "read", tx.clone()).await.unwrap();
+        let prompt = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.send_prompt(id, "edit", tx).await })
+        };
+        while pool.get_pending_permissions().list().is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let mut cancelled = CANCELLED_PERMISSIONS.subscribe();
+        tokio::time::timeout(Duration::from_secs(5), pool.stop_agent(&id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(prompt.await.unwrap().is_err());
+        assert!(pool.get_pending_permissions().list().is_empty());
+        assert_eq!(cancelled.recv().await.unwrap().agent_id, id);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_prompt_ends_a_turn_with_a_running_tool_call() {
+        let pool = Arc::new(AgentPool::new());
+        let (stream, handle) = ScriptedAgent::new()
+            .turn(vec![
+                ScriptStep::Update(serde_json::json!({
+                    "sessionUpdate": "tool_call",
+                    "toolCallId": "build",
+                    "title": "cargo build",
+                    "kind": "execute",
+                    "status": "in_progress",
+                })),
+                ScriptStep::WaitForCancel,
+            ])
+            .connect();
+        let agent = AgentProcess::connect("scripted".into(), "/tmp/project".into(), stream);
+        let id = pool.add_agent(agent).await.unwrap().id;
+        assert!(pool.cancel_prompt(&id).is_err());
+
+        let (tx, _rx) = mpsc::channel(100);
+        let prompt = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.send_prompt(id, "build it", tx).await })
+        };
+        while pool.running_tool_calls(Some(&id)).is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(pool.running_tool_calls(None)[0].title.as_deref(), Some("cargo build"));
+        // A running call has no permission request left to reject
+        assert!(pool.cancel_tool_call(&id, "build").is_err());
+        assert!(pool.cancel_tool_call(&id, "unknown").is_err());
+
+        pool.cancel_prompt(&id).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), prompt)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(pool.get_agent_info(&id).unwrap().status, AgentStatus::Idle);
+        let agent = pool.agent_ref(&id).unwrap().process;
+        assert_eq!(agent.lock().await.last_stop_reason.as_deref(), Some("cancelled"));
+        // The agent never reported the call as done; the turn ended it
+        assert!(pool.running_tool_calls(Some(&id)).is_empty());
+        assert!(pool.tool_call_artifact(&id, "build").unwrap().is_finished());
+        let cancels = handle
+            .received()
+            .iter()
+            .filter(|m| m["method"] == methods::SESSION_CANCEL)
+            .count();
+        assert_eq!(cancels, 1);
+    }
+
+    #[tokio::test]
+    async fn test_followup_answers_a_question_and_continues_the_transcript() {
+        let pool = Arc::new(AgentPool::new());
+        let (stream, _handle) = ScriptedAgent::new()
+            .turn(vec![ScriptStep::EchoPrompt])
+            .turn(vec![ScriptStep::EchoPrompt])
+            .connect();
+        let agent = AgentProcess::connect("scripted".into(), "/tmp/project".into(), stream);
+        let id = pool.add_agent(agent).await.unwrap().id;
+        let (tx, _rx) = mpsc::channel(100);
+        pool.send_prompt(id, "Pick a branch", tx.clone()).await.unwrap();
+        let transcript = pool.get_agent_info(&id).unwrap().last_transcript.unwrap();
+
+        let mut question = input("q1", "Which branch?", &[]);
+        question.input_type = PendingInputType::UserQuestion;
+        pool.agent_ref(&id).unwrap().process.lock().await.add_pending_input(question);
+        assert!(pool.check_can_prompt(&id).is_err());
+
+        assert_eq!(pool.send_followup(id, "main", tx).await.unwrap(), "main");
+        let info = pool.get_agent_info(&id).unwrap();
+        assert_eq!(info.status, AgentStatus::Idle);
+        assert!(info.pending_inputs.is_empty());
+        let resumed = info.last_transcript.unwrap();
+        assert_eq!(resumed.id, transcript.id);
+        assert!(resumed.length > transcript.length + "main".len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_usage_updates_are_recorded() {
+        let usage = |used: u64, cost: f64| {
+            ScriptStep::Update(serde_json::json!({
+                "sessionUpdate": "usage_update",
+                "used": used,
+                "size": 200000,
+                "cost": { "amount": cost, "currency": "USD" },
+            }))
+        };
+        let pool = Arc::new(AgentPool::new());
+        let (stream, _handle) = ScriptedAgent::new()
+            .turn(vec![usage(1200, 0.05), usage(3000, 0.12)])
+            .connect();
+        let agent = AgentProcess::connect("scripted".into(), "/tmp/project".into(), stream);
+        let id = pool.add_agent(agent).await.unwrap().id;
+        let mut reports = AGENT_USAGE.subscribe();
+        let (tx, _rx) = mpsc::channel(100);
+        pool.send_prompt(id, "go", tx).await.unwrap();
+
+        let mut reported = Vec::new();
+        while let Ok(report) = reports.try_recv() {
+            if report.agent_id == id {
+                reported.push((report.input_tokens, report.cost_cents));
+            }
+        }
+        // Costs as they grow, then the context the turn added
+        assert_eq!(reported, vec![(0, 5), (0, 7), (3000, 0)]);
+        assert_eq!(pool.get_agent_info(&id).unwrap().tokens_used, 3000);
+    }
+}
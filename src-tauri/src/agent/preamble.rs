@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Who a preamble applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PreambleTarget {
+    /// Agents with this name; names survive restarts, ids don't
+    Agent { name: String },
+    /// Agents working in this directory or below it
+    Project { path: String },
+}
+
+/// Standing instructions sent ahead of every prompt, e.g. "always write tests"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptPreamble {
+    pub target: PreambleTarget,
+    pub text: String,
+}
+
+/// The preamble for an agent: project preambles from the outermost project
+/// in, then the agent's own, separated by blank lines
+pub fn resolve_preamble(
+    preambles: &[PromptPreamble],
+    agent_name: &str,
+    working_directory: &str,
+) -> Option<String> {
+    let mut projects: Vec<(&str, &str)> = preambles
+        .iter()
+        .filter_map(|p| match &p.target {
+            PreambleTarget::Project { path } if Path::new(working_directory).starts_with(path) => {
+                Some((path.as_str(), p.text.as_str()))
+            }
+            _ => None,
+        })
+        .collect();
+    projects.sort_by_key(|(path, _)| path.len());
+
+    let own = preambles.iter().filter_map(|p| match &p.target {
+        PreambleTarget::Agent { name } if name == agent_name => Some(p.text.as_str()),
+        _ => None,
+    });
+
+    let parts: Vec<&str> = projects
+        .into_iter()
+        .map(|(_, text)| text)
+        .chain(own)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preamble(target: PreambleTarget, text: &str) -> PromptPreamble {
+        PromptPreamble {
+            target,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_preamble_orders_projects_then_agent() {
+        let preambles = vec![
+            preamble(PreambleTarget::Agent { name: "Builder".to_string() }, "Always write tests."),
+            preamble(PreambleTarget::Project { path: "/work/app/web".to_string() }, "Use pnpm."),
+            preamble(PreambleTarget::Project { path: "/work/app".to_string() }, "Follow STYLE.md."),
+            preamble(PreambleTarget::Project { path: "/work/other".to_string() }, "Unrelated."),
+            preamble(PreambleTarget::Agent { name: "Reviewer".to_string() }, "  "),
+        ];
+
+        assert_eq!(
+            resolve_preamble(&preambles, "Builder", "/work/app/web/src").as_deref(),
+            Some("Follow STYLE.md.\n\nUse pnpm.\n\nAlways write tests.")
+        );
+        assert_eq!(
+            resolve_preamble(&preambles, "Reviewer", "/work/app").as_deref(),
+            Some("Follow STYLE.md.")
+        );
+        // A sibling directory sharing the prefix is not inside the project
+        assert_eq!(resolve_preamble(&preambles, "Reviewer", "/work/application"), None);
+    }
+}
@@ -1,21 +1,49 @@
 use crate::acp::{
-    AsyncCodec, InitializeParams, JsonRpcMessage, JsonRpcRequest, JsonRpcResponse,
-    PromptContent, RequestPermissionRequest, RequestPermissionResponse,
-    SessionNewParams, SessionNewResult, SessionPromptParams, SessionUpdate, SessionUpdateNotification,
-    LegacySessionUpdateNotification, ToolCallStatus, AuthMethod, AuthStartParams, AuthStartResult,
+    methods, AsyncCodec, Incoming, AGENT_NOTIFICATIONS, InitializeParams, JsonRpcMessage, JsonRpcResponse, ProtocolClient,
+    ProtocolError, RequestPolicies,
+    PermissionOption, PermissionOptionKind, PromptContent, RequestPermissionRequest, RequestPermissionResponse,
+    SessionListEntry, SessionListParams, SessionListResult, SessionLoadParams, SessionLoadResult, SessionModeState, SessionModelState, SessionNewParams, SessionNewResult, SessionPromptParams, SessionPromptResult, SessionUpdate, SessionUpdateNotification,
+    LegacySessionUpdateNotification, ToolCallStatus, ToolCallUpdate, UsageUpdate, AuthMethod, AuthStartParams, AuthStartResult,
+    Transport,
 };
-use super::pool::PendingPermissions;
+use super::artifacts::{tool_output_dir, ToolCallHistory, TouchedRange};
+use super::transcript::{TranscriptInfo, TranscriptWriter};
+use super::credentials::keychain_env;
+use super::environment::{LaunchInfo, NegotiatedProtocol};
+use super::policy_script::{command_text, permission_decision, PermissionFacts, ScriptDecision};
+use super::pool::{similarity_scope, PendingPermissions, ALLOW_SIMILAR_OPTION_ID};
+use super::limits::{self, ResourceLimits};
+use super::locks::{self, FileLocks};
+use super::message_processor::{clear_tool_call_inputs, extract_file_path, finished_tool_call, merge_pending_input};
+use super::project_scope::paths_outside_projects;
+use super::risk::{score_permission, PermissionRisk, RiskInput};
+use super::sandbox::{AgentContainer, ContainerSandbox};
+use super::state_events::{AgentUsage, AGENT_USAGE};
+use crate::clock::now_secs;
+use crate::messages::{LocalizedMessage, MessageKey};
+use crate::plugins::{AutoApprovalReason, PLUGINS};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::process::{Child, Command};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch, Notify};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Upper bound on session/list pages fetched, in case an agent keeps
+/// returning cursors
+const MAX_SESSION_LIST_PAGES: usize = 20;
+
+/// Longest a permission request waits for another agent's edit of the same file
+const PERMISSION_HOLD_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct AgentInfo {
     pub id: Uuid,
     pub name: String,
@@ -35,6 +63,33 @@ pub struct AgentInfo {
     pub auth_methods: Vec<AuthMethod>,
     #[serde(default)]
     pub needs_auth: bool,
+    /// Slash commands the agent advertised for its session
+    #[serde(default)]
+    pub available_commands: Vec<String>,
+    /// Whether the agent can resume earlier sessions via session/load
+    #[serde(default)]
+    pub supports_load_session: bool,
+    /// Whether the agent can list its stored sessions via session/list
+    #[serde(default)]
+    pub supports_list_sessions: bool,
+    /// Modes the session offers, if the agent has any
+    #[serde(default)]
+    pub modes: Option<SessionModeState>,
+    /// Models the session offers, if the agent lets the client pick
+    #[serde(default)]
+    pub models: Option<SessionModelState>,
+    /// Unix seconds the agent was started at
+    #[serde(default)]
+    pub created_at: u64,
+    /// Unix seconds the latest prompt was sent at
+    #[serde(default)]
+    pub last_prompt_at: Option<u64>,
+    /// Unix seconds the agent last sent anything during a prompt
+    #[serde(default)]
+    pub last_update_at: Option<u64>,
+    /// Where the answer to the latest finished prompt was written
+    #[serde(default)]
+    pub last_transcript: Option<TranscriptInfo>,
 }
 
 /// Represents a pending input request from the agent (permission, question, etc.)
@@ -45,6 +100,34 @@ pub struct PendingInput {
     pub tool_name: Option<String>,
     pub message: String,
     pub timestamp: u64,
+    /// The tool call reaches outside every registered project; approving
+    /// it needs explicit confirmation
+    #[serde(default)]
+    pub high_risk: bool,
+    /// Paths of the tool call outside every registered project
+    #[serde(default)]
+    pub outside_paths: Vec<String>,
+    /// Heuristic risk of a permission request
+    #[serde(default)]
+    pub risk: Option<PermissionRisk>,
+    /// Set once similar requests repeat: the tool kind and directory an
+    /// "allow all similar" answer covers for the rest of the prompt
+    #[serde(default)]
+    pub similar_scope: Option<String>,
+    /// Tool call the input is about; one entry per tool call is kept
+    #[serde(default)]
+    pub tool_call_id: Option<String>,
+    /// `message` as a key for translation; none for text from the agent
+    #[serde(default)]
+    pub localized: Option<LocalizedMessage>,
+}
+
+impl PendingInput {
+    /// Whether the agent waits on an answer to a session/request_permission,
+    /// rather than having only announced a pending tool call
+    pub fn is_permission_request(&self) -> bool {
+        self.id.starts_with("perm_req_")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -55,6 +138,28 @@ pub enum PendingInputType {
     Confirmation,
 }
 
+struct PendingGuard<'a> {
+    pending_permissions: &'a PendingPermissions,
+    agent_id: Uuid,
+    input_id: &'a str,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending_permissions.remove(self.agent_id, self.input_id);
+    }
+}
+
+/// What a permission request would do, judged before asking the user
+struct PermissionAssessment {
+    risk: PermissionRisk,
+    outside_paths: Vec<String>,
+    /// Tool kind and directory shared by similar requests
+    scope: Option<String>,
+    /// What the permission policy script sees
+    facts: PermissionFacts,
+}
+
 /// User's response to a permission request
 #[derive(Debug, Clone)]
 pub struct PermissionUserResponse {
@@ -62,13 +167,51 @@ pub struct PermissionUserResponse {
     pub option_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Asks the running prompt turn to end, from outside the agent's lock
+#[derive(Default)]
+pub struct PromptCancel {
+    requested: AtomicBool,
+    notify: Notify,
+}
+
+impl PromptCancel {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    fn reset(&self) {
+        self.requested.store(false, Ordering::SeqCst);
+    }
+
+    /// Resolves once a cancel is requested
+    async fn requested(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AgentStatus {
+    #[default]
     Initializing,
     Idle,
     Working,
     Paused,
+    /// Running, but the agent wants the user to authenticate before it
+    /// opens a session
+    #[serde(rename = "needs_auth")]
+    NeedsAuth,
     Error,
     Stopped,
 }
@@ -76,9 +219,13 @@ pub enum AgentStatus {
 pub struct AgentProcess {
     pub id: Uuid,
     pub name: String,
-    child: Child,
-    codec: AsyncCodec,
-    request_id: AtomicI64,
+    /// None for agents that run in-process
+    child: Option<Child>,
+    /// Container the agent runs in, if sandboxed
+    container: Option<AgentContainer>,
+    /// Enforces resource limits of an agent outside a container
+    watchdog: Option<JoinHandle<()>>,
+    client: ProtocolClient,
     pub session_id: Option<String>,
     pub working_directory: String,
     pub status: AgentStatus,
@@ -90,6 +237,48 @@ pub struct AgentProcess {
     pub provider_name: Option<String>,
     pub auth_methods: Vec<AuthMethod>,
     pub needs_auth: bool,
+    pub available_commands: Vec<String>,
+    pub modes: Option<SessionModeState>,
+    pub models: Option<SessionModelState>,
+    /// Content of recent tool calls, shared with the pool so it can be read mid-prompt
+    pub tool_calls: Arc<ToolCallHistory>,
+    /// Shared with the pool to cancel a prompt mid-turn
+    pub cancel: Arc<PromptCancel>,
+    /// Files being edited by agents of the pool
+    file_locks: Arc<FileLocks>,
+    /// Latest info, published whenever it changes
+    state: watch::Sender<AgentInfo>,
+    /// Stop reason reported for the most recent prompt
+    pub last_stop_reason: Option<String>,
+    /// Tokens in the context and USD cents spent on the session, from the
+    /// latest `usage_update`
+    context_tokens: u64,
+    session_cost_cents: u64,
+    /// `context_tokens` when the current prompt was sent
+    turn_start_context: u64,
+    pub last_transcript: Option<TranscriptInfo>,
+    pub supports_load_session: bool,
+    pub supports_list_sessions: bool,
+    /// How the agent was spawned, to spawn another like it; none for
+    /// agents connected to a stream
+    pub spawn_config: Option<SpawnConfig>,
+    /// How the process was started; none for agents the app didn't start
+    pub launch: Option<LaunchInfo>,
+    /// What the agent answered to `initialize`
+    pub protocol: Option<NegotiatedProtocol>,
+    /// Unix seconds, see [`AgentInfo`]
+    pub created_at: u64,
+    pub last_prompt_at: Option<u64>,
+    pub last_update_at: Option<u64>,
+}
+
+/// Command that runs npm packages when none is configured
+pub fn default_package_runner() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "npx.cmd"
+    } else {
+        "npx"
+    }
 }
 
 /// Configuration for spawning an agent
@@ -101,28 +290,113 @@ pub struct SpawnConfig {
     pub provider_name: Option<String>,
     pub command: String,
     pub args: Vec<String>,
+    /// How to reach the agent; `command` is run locally for stdio and on the
+    /// remote host for SSH
+    pub transport: Transport,
+    /// Run a stdio agent inside this container
+    pub sandbox: Option<ContainerSandbox>,
+    /// CPU and memory limits of a stdio agent
+    pub limits: Option<ResourceLimits>,
+    /// Set on a local process on top of the app's environment
+    pub env: HashMap<String, String>,
+    /// Model to switch the first session to, if the agent offers it
+    pub default_model: Option<String>,
+    /// Auth method moved to the front of the agent's list
+    pub auth_method: Option<String>,
+}
+
+impl SpawnConfig {
+    /// The default Claude provider, run with `package_runner` (npx, bunx, ...)
+    pub fn claude(name: String, working_directory: String, package_runner: &str) -> Self {
+        Self {
+            name,
+            working_directory,
+            provider_id: Some("claude".to_string()),
+            provider_name: Some("Claude".to_string()),
+            command: package_runner.to_string(),
+            args: vec!["@zed-industries/claude-code-acp@latest".to_string()],
+            transport: Transport::Stdio,
+            sandbox: None,
+            limits: None,
+            env: HashMap::new(),
+            default_model: None,
+            auth_method: None,
+        }
+    }
 }
 
 impl AgentProcess {
     /// Spawn an agent with the given configuration
     pub async fn spawn_with_config(config: SpawnConfig) -> Result<Self, AgentProcessError> {
         let id = Uuid::new_v4();
+        let spawn_config = Some(config.clone());
 
-        info!(
-            "Spawning agent {} with command: {} {:?}",
-            config.name, config.command, config.args
-        );
+        if !config.transport.spawns_process() {
+            info!("Connecting to agent {} at {}", config.name, config.transport);
+            let codec = config
+                .transport
+                .connect()
+                .await
+                .map_err(|e| AgentProcessError::SpawnFailed(format!("{}: {}", config.transport, e)))?;
+            let client = ProtocolClient::new(codec.for_agent(id, &config.name));
+            let mut agent = Self::with_client(id, config.name, config.working_directory, None, client);
+            agent.provider_id = config.provider_id;
+            agent.provider_name = config.provider_name;
+            agent.spawn_config = spawn_config;
+            return Ok(agent);
+        }
 
-        let mut cmd = Command::new(&config.command);
-        cmd.args(&config.args)
+        let container = match (&config.transport, &config.sandbox) {
+            (Transport::Stdio, Some(sandbox)) => Some(AgentContainer::for_agent(sandbox.runtime, id)),
+            _ => None,
+        };
+        let (command, args) = match (&config.transport, &config.sandbox, &container) {
+            (Transport::Ssh(target), _, _) => target
+                .command_line(&config.command, &config.args, &config.working_directory)
+                .map_err(AgentProcessError::SpawnFailed)?,
+            (_, Some(sandbox), Some(container)) => {
+                sandbox.command_line(
+                    container,
+                    config.limits.as_ref(),
+                    &config.command,
+                    &config.args,
+                    &config.working_directory,
+                )
+            }
+            _ => (config.command.clone(), config.args.clone()),
+        };
+        info!("Spawning agent {} with command: {} {:?}", config.name, command, args);
+
+        let mut cmd = Command::new(&command);
+        cmd.args(&args)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .current_dir(&config.working_directory);
+            .stderr(std::process::Stdio::piped());
+        // Over SSH the working directory is a path on the remote host
+        let mut keychain_variables = Vec::new();
+        if config.transport.is_stdio() {
+            cmd.current_dir(&config.working_directory);
+            if container.is_none() {
+                cmd.envs(&config.env);
+                let keychain = keychain_env();
+                keychain_variables = keychain.iter().map(|(name, _)| name.clone()).collect();
+                cmd.envs(keychain);
+            }
+        }
 
         let mut child = cmd
             .spawn()
-            .map_err(|e| AgentProcessError::SpawnFailed(format!("{}: {}", config.command, e)))?;
+            .map_err(|e| AgentProcessError::SpawnFailed(format!("{}: {}", command, e)))?;
+        let launch = LaunchInfo {
+            command: command.clone(),
+            args: args.clone(),
+            working_directory: config
+                .transport
+                .is_stdio()
+                .then(|| config.working_directory.clone()),
+            pid: child.id(),
+            keychain_variables,
+        };
 
         let stdin = child
             .stdin
@@ -133,159 +407,173 @@ impl AgentProcess {
             .take()
             .ok_or_else(|| AgentProcessError::StdoutUnavailable)?;
 
-        let codec = AsyncCodec::new(stdout, stdin);
+        let codec = AsyncCodec::new(stdout, stdin).with_startup_grace();
+        let client = ProtocolClient::new(codec.for_agent(id, &config.name));
+        // Containers enforce limits themselves
+        let watchdog = match (config.limits, &container, child.id()) {
+            (Some(limits), None, Some(pid)) if !limits.is_unlimited() => Some(limits::watch(id, pid, limits)),
+            _ => None,
+        };
+        let mut agent = Self::with_client(id, config.name, config.working_directory, Some(child), client);
+        agent.container = container;
+        agent.watchdog = watchdog;
+        agent.launch = Some(launch);
+        agent.provider_id = config.provider_id;
+        agent.provider_name = config.provider_name;
+        agent.spawn_config = spawn_config;
+        Ok(agent)
+    }
 
-        Ok(Self {
+    /// An agent that speaks ACP over `stream` instead of a child process's
+    /// stdio, e.g. one end of a [`tokio::io::duplex`] pipe
+    pub fn connect(
+        name: String,
+        working_directory: String,
+        stream: impl AsyncRead + AsyncWrite + Send + 'static,
+    ) -> Self {
+        let id = Uuid::new_v4();
+        let client = ProtocolClient::new(AsyncCodec::from_stream(stream).for_agent(id, &name));
+        Self::with_client(id, name, working_directory, None, client)
+    }
+
+    fn with_client(
+        id: Uuid,
+        name: String,
+        working_directory: String,
+        child: Option<Child>,
+        client: ProtocolClient,
+    ) -> Self {
+        Self {
             id,
-            name: config.name,
+            name,
             child,
-            codec,
-            request_id: AtomicI64::new(1),
+            container: None,
+            watchdog: None,
+            client,
             session_id: None,
-            working_directory: config.working_directory,
+            working_directory,
             status: AgentStatus::Initializing,
             current_file: None,
             progress: 0.0,
             tokens_used: 0,
             pending_inputs: Vec::new(),
-            provider_id: config.provider_id,
-            provider_name: config.provider_name,
+            provider_id: None,
+            provider_name: None,
             auth_methods: Vec::new(),
             needs_auth: false,
-        })
+            available_commands: Vec::new(),
+            modes: None,
+            models: None,
+            tool_calls: Arc::new(ToolCallHistory::spooled_to(
+                tool_output_dir().join(id.to_string()),
+            )),
+            cancel: Arc::new(PromptCancel::default()),
+            file_locks: Arc::new(FileLocks::new()),
+            state: watch::Sender::new(AgentInfo::default()),
+            last_stop_reason: None,
+            context_tokens: 0,
+            session_cost_cents: 0,
+            turn_start_context: 0,
+            last_transcript: None,
+            supports_load_session: false,
+            supports_list_sessions: false,
+            launch: None,
+            protocol: None,
+            spawn_config: None,
+            created_at: now_secs(),
+            last_prompt_at: None,
+            last_update_at: None,
+        }
     }
 
     /// Spawn an agent with default Claude provider (backward compatible)
     pub async fn spawn(
         name: String,
         working_directory: String,
+        package_runner: &str,
     ) -> Result<Self, AgentProcessError> {
-        Self::spawn_with_config(SpawnConfig {
-            name,
-            working_directory,
-            provider_id: Some("claude".to_string()),
-            provider_name: Some("Claude".to_string()),
-            command: "npx".to_string(),
-            args: vec!["@zed-industries/claude-code-acp@latest".to_string()],
-        })
-        .await
+        Self::spawn_with_config(SpawnConfig::claude(name, working_directory, package_runner)).await
     }
 
-    fn next_request_id(&self) -> i64 {
-        self.request_id.fetch_add(1, Ordering::SeqCst)
+    /// Timeouts and retries for requests sent from now on
+    pub fn set_request_policies(&mut self, policies: RequestPolicies) {
+        self.client.set_policies(policies);
+    }
+
+    /// Share the pool's file locks, so edits conflict across agents
+    pub fn set_file_locks(&mut self, file_locks: Arc<FileLocks>) {
+        self.file_locks = file_locks;
     }
 
     pub async fn initialize(&mut self) -> Result<(), AgentProcessError> {
         let params = InitializeParams::new();
-        let request = JsonRpcRequest::new(
-            self.next_request_id(),
-            "initialize",
-            Some(serde_json::to_value(params).unwrap()),
-        );
-
-        let json = serde_json::to_string(&request).unwrap();
-        self.codec
-            .write_message(&json)
-            .await
-            .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
-
-        // Wait for initialize response
-        loop {
-            if let Some(msg) = self
-                .codec
-                .read_message()
-                .await
-                .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?
-            {
-                if let JsonRpcMessage::Response(resp) = msg {
-                    if resp.error.is_some() {
-                        return Err(AgentProcessError::InitializeFailed(
-                            resp.error.unwrap().message,
-                        ));
-                    }
-                    // Parse authMethods from the result if present
-                    if let Some(result) = &resp.result {
-                        if let Some(auth_methods) = result.get("authMethods") {
-                            if let Ok(methods) = serde_json::from_value::<Vec<AuthMethod>>(auth_methods.clone()) {
-                                info!("Agent has {} auth methods available", methods.len());
-                                self.auth_methods = methods;
-                            }
-                        }
+        let resp = self
+            .client
+            .request(methods::initialize(&params))
+            .await?;
+        if let Some(err) = resp.error {
+            return Err(AgentProcessError::InitializeFailed(err.message));
+        }
+        // Parse authMethods from the result if present
+        if let Some(result) = &resp.result {
+            self.protocol = Some(NegotiatedProtocol::from_initialize_result(result));
+            let capabilities = result.get("agentCapabilities");
+            self.supports_load_session = capabilities
+                .and_then(|c| c.get("loadSession"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            // Advertised as an (empty) object under sessionCapabilities
+            self.supports_list_sessions = capabilities
+                .and_then(|c| c.get("sessionCapabilities"))
+                .and_then(|c| c.get("list"))
+                .is_some_and(|v| !v.is_null() && v.as_bool() != Some(false));
+            if let Some(auth_methods) = result.get("authMethods") {
+                if let Ok(methods) = serde_json::from_value::<Vec<AuthMethod>>(auth_methods.clone()) {
+                    info!("Agent has {} auth methods available", methods.len());
+                    self.auth_methods = methods;
+                    let preferred = self.spawn_config.as_ref().and_then(|c| c.auth_method.as_ref());
+                    if let Some(index) = preferred
+                        .and_then(|id| self.auth_methods.iter().position(|m| &m.id == id))
+                    {
+                        let method = self.auth_methods.remove(index);
+                        self.auth_methods.insert(0, method);
                     }
-                    break;
                 }
             }
         }
 
         // Send initialized notification
-        let notification = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": "notifications/initialized"
-        });
-        self.codec
-            .write_message(&notification.to_string())
-            .await
-            .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+        self.client.notify(&methods::initialized()).await?;
 
         self.status = AgentStatus::Idle;
+        self.publish_state();
         Ok(())
     }
 
     /// Start authentication with a specific auth method
     pub async fn start_auth(&mut self, auth_method_id: &str) -> Result<AuthStartResult, AgentProcessError> {
-        // Build params as raw JSON - Codex CLI expects "methodId"
-        let params = serde_json::json!({
-            "methodId": auth_method_id
-        });
-
-        let request = JsonRpcRequest::new(
-            self.next_request_id(),
-            "authenticate",
-            Some(params),
-        );
-
-        let json = serde_json::to_string(&request).unwrap();
-        info!("Starting auth with method: {} - request: {}", auth_method_id, json);
-        println!("[AUTH] Sending auth request: {}", json);
-        self.codec
-            .write_message(&json)
-            .await
-            .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+        info!("Starting auth with method: {}", auth_method_id);
+        let resp = self.client.request(methods::authenticate(auth_method_id)).await?;
+        if let Some(err) = resp.error {
+            debug!("Authenticate failed: {}", err.message);
+            return Err(AgentProcessError::AuthFailed(err.message));
+        }
 
-        // Wait for authenticate response
-        loop {
-            if let Some(msg) = self
-                .codec
-                .read_message()
-                .await
-                .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?
-            {
-                println!("[AUTH] Received message: {:?}", msg);
-                if let JsonRpcMessage::Response(resp) = msg {
-                    if let Some(err) = resp.error {
-                        println!("[AUTH] Error response: {:?}", err);
-                        return Err(AgentProcessError::AuthFailed(err.message));
-                    }
-                    if let Some(result) = resp.result {
-                        println!("[AUTH] Success result: {:?}", result);
-                        let auth_result: AuthStartResult = serde_json::from_value(result.clone())
-                            .map_err(|e| {
-                                println!("[AUTH] Failed to parse result: {} - raw: {:?}", e, result);
-                                AgentProcessError::CommunicationError(e.to_string())
-                            })?;
-
-                        if auth_result.completed {
-                            self.needs_auth = false;
-                            info!("Auth completed immediately");
-                        } else if auth_result.url.is_some() {
-                            info!("Auth requires browser: {:?}", auth_result.url);
-                        }
+        let result = resp.result.unwrap_or_default();
+        let auth_result: AuthStartResult = serde_json::from_value(result).map_err(|e| {
+            debug!("Failed to parse the authenticate result: {}", e);
+            AgentProcessError::CommunicationError(e.to_string())
+        })?;
 
-                        return Ok(auth_result);
-                    }
-                }
-            }
+        if auth_result.completed {
+            self.needs_auth = false;
+            self.publish_state();
+            info!("Auth completed immediately");
+        } else if auth_result.url.is_some() {
+            info!("Auth requires browser: {:?}", auth_result.url);
         }
+
+        Ok(auth_result)
     }
 
     pub async fn create_session(&mut self) -> Result<String, AgentProcessError> {
@@ -294,48 +582,160 @@ impl AgentProcess {
             mcp_servers: vec![],
         };
 
-        let request = JsonRpcRequest::new(
-            self.next_request_id(),
-            "session/new",
-            Some(serde_json::to_value(params).unwrap()),
-        );
+        let resp = self
+            .client
+            .request(methods::session_new(&params))
+            .await?;
+        if let Some(err) = resp.error {
+            if err.is_auth_required() {
+                // Some agents name the methods they accept only here
+                let methods = err
+                    .data
+                    .as_ref()
+                    .and_then(|data| data.get("authMethods"))
+                    .and_then(|m| serde_json::from_value::<Vec<AuthMethod>>(m.clone()).ok());
+                if let Some(methods) = methods.filter(|m| !m.is_empty()) {
+                    self.auth_methods = methods;
+                }
+                self.needs_auth = true;
+                self.status = AgentStatus::NeedsAuth;
+                self.publish_state();
+                return Err(AgentProcessError::AuthRequired);
+            }
+            return Err(AgentProcessError::SessionCreateFailed(err.message));
+        }
 
-        let json = serde_json::to_string(&request).unwrap();
-        self.codec
-            .write_message(&json)
-            .await
+        let session_result: SessionNewResult = serde_json::from_value(resp.result.unwrap_or_default())
             .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+        self.session_id = Some(session_result.session_id.clone());
+        self.reset_usage();
+        self.needs_auth = false;
+        if self.status == AgentStatus::NeedsAuth {
+            self.status = AgentStatus::Idle;
+        }
+        self.available_commands.clear();
+        self.modes = session_result.modes;
+        self.models = session_result.models;
+        self.publish_state();
+        Ok(session_result.session_id)
+    }
 
-        // Wait for session/new response
-        loop {
-            if let Some(msg) = self
-                .codec
-                .read_message()
-                .await
-                .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?
-            {
-                if let JsonRpcMessage::Response(resp) = msg {
-                    if let Some(err) = resp.error {
-                        // Check if it's an auth-required error
-                        let msg_lower = err.message.to_lowercase();
-                        if msg_lower.contains("auth") || msg_lower.contains("login") || msg_lower.contains("credential") {
-                            self.needs_auth = true;
-                            return Err(AgentProcessError::AuthRequired);
-                        }
-                        return Err(AgentProcessError::SessionCreateFailed(err.message));
-                    }
-                    if let Some(result) = resp.result {
-                        let session_result: SessionNewResult =
-                            serde_json::from_value(result).map_err(|e| {
-                                AgentProcessError::CommunicationError(e.to_string())
-                            })?;
-                        self.session_id = Some(session_result.session_id.clone());
-                        self.needs_auth = false;
-                        return Ok(session_result.session_id);
-                    }
-                }
+    /// Sessions the agent has stored for this agent's working directory,
+    /// following pagination cursors
+    pub async fn list_sessions(&mut self) -> Result<Vec<SessionListEntry>, AgentProcessError> {
+        if !self.supports_list_sessions {
+            return Err(AgentProcessError::CommunicationError(
+                "Agent does not support session/list".to_string(),
+            ));
+        }
+
+        let mut sessions = Vec::new();
+        let mut cursor = None;
+        for _ in 0..MAX_SESSION_LIST_PAGES {
+            let params = SessionListParams {
+                cwd: Some(self.working_directory.clone()),
+                cursor,
+            };
+            let resp = self
+                .client
+                .request(methods::session_list(&params))
+                .await?;
+            if let Some(err) = resp.error {
+                return Err(AgentProcessError::CommunicationError(err.message));
+            }
+
+            let page: SessionListResult = serde_json::from_value(resp.result.unwrap_or_default())
+                .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+            sessions.extend(page.sessions);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
             }
         }
+        Ok(sessions)
+    }
+
+    /// Resume an existing session by id instead of creating a new one
+    pub async fn load_session(&mut self, session_id: &str) -> Result<(), AgentProcessError> {
+        if !self.supports_load_session {
+            return Err(AgentProcessError::SessionCreateFailed(
+                "Agent does not support session/load".to_string(),
+            ));
+        }
+
+        let params = SessionLoadParams {
+            session_id: session_id.to_string(),
+            cwd: self.working_directory.clone(),
+            mcp_servers: vec![],
+        };
+        // The replayed history arrives as notifications before the response
+        let resp = self
+            .client
+            .request(methods::session_load(&params))
+            .await?;
+        if let Some(err) = resp.error {
+            return Err(AgentProcessError::SessionCreateFailed(err.message));
+        }
+        let result: SessionLoadResult = resp
+            .result
+            .and_then(|r| serde_json::from_value(r).ok())
+            .unwrap_or_default();
+        self.session_id = Some(session_id.to_string());
+        self.reset_usage();
+        self.available_commands.clear();
+        self.modes = result.modes;
+        self.models = result.models;
+        self.publish_state();
+        Ok(())
+    }
+
+    /// Switch the session to one of the modes it offers
+    pub async fn set_mode(&mut self, mode_id: &str) -> Result<(), AgentProcessError> {
+        let session_id = self.session_id.clone().ok_or(AgentProcessError::NoSession)?;
+        let resp = self
+            .client
+            .request(methods::session_set_mode(&session_id, mode_id))
+            .await?;
+        if let Some(err) = resp.error {
+            return Err(AgentProcessError::CommunicationError(err.message));
+        }
+        if let Some(modes) = &mut self.modes {
+            modes.current_mode_id = mode_id.to_string();
+        }
+        self.publish_state();
+        Ok(())
+    }
+
+    /// Switch the session to one of the models it offers
+    pub async fn set_model(&mut self, model_id: &str) -> Result<(), AgentProcessError> {
+        let session_id = self.session_id.clone().ok_or(AgentProcessError::NoSession)?;
+        let resp = self
+            .client
+            .request(methods::session_set_model(&session_id, model_id))
+            .await?;
+        if let Some(err) = resp.error {
+            return Err(AgentProcessError::CommunicationError(err.message));
+        }
+        if let Some(models) = &mut self.models {
+            models.current_model_id = model_id.to_string();
+        }
+        self.publish_state();
+        Ok(())
+    }
+
+    /// Switch to the spawn configuration's default model, if the session
+    /// offers it and uses another one
+    pub async fn apply_default_model(&mut self) -> Result<(), AgentProcessError> {
+        let Some(model) = self.spawn_config.as_ref().and_then(|c| c.default_model.clone()) else {
+            return Ok(());
+        };
+        let offered = self.models.as_ref().is_some_and(|m| {
+            m.current_model_id != model && m.available_models.iter().any(|a| a.model_id == model)
+        });
+        if offered {
+            self.set_model(&model).await?;
+        }
+        Ok(())
     }
 
     pub async fn send_prompt(
@@ -343,6 +743,52 @@ impl AgentProcess {
         prompt: &str,
         update_tx: mpsc::Sender<AgentUpdate>,
         pending_permissions: Arc<PendingPermissions>,
+    ) -> Result<String, AgentProcessError> {
+        self.send_prompt_with_preamble(None, prompt, update_tx, pending_permissions).await
+    }
+
+    /// Send a prompt, with standing instructions as a separate text block
+    /// ahead of it
+    pub async fn send_prompt_with_preamble(
+        &mut self,
+        preamble: Option<&str>,
+        prompt: &str,
+        update_tx: mpsc::Sender<AgentUpdate>,
+        pending_permissions: Arc<PendingPermissions>,
+    ) -> Result<String, AgentProcessError> {
+        let mut content: Vec<PromptContent> =
+            preamble.map(PromptContent::text).into_iter().collect();
+        content.push(PromptContent::text(prompt));
+        self.run_turn(content, TranscriptWriter::new(), update_tx, pending_permissions).await
+    }
+
+    /// Send a user follow-up on the same session. It answers the questions
+    /// the agent paused on, and its answer continues the last transcript.
+    pub async fn send_followup(
+        &mut self,
+        text: &str,
+        update_tx: mpsc::Sender<AgentUpdate>,
+        pending_permissions: Arc<PendingPermissions>,
+    ) -> Result<String, AgentProcessError> {
+        self.pending_inputs
+            .retain(|input| input.input_type != PendingInputType::UserQuestion);
+        let mut transcript = match self.last_transcript.clone() {
+            Some(info) => TranscriptWriter::resume(info),
+            None => TranscriptWriter::new(),
+        };
+        transcript.note(&format!("\n\n> {}\n\n", text.replace('\n', "\n> ")));
+        let content = vec![PromptContent::text(text)];
+        self.run_turn(content, transcript, update_tx, pending_permissions).await
+    }
+
+    /// Run one prompt turn on the session, collecting the answer in
+    /// `transcript`
+    async fn run_turn(
+        &mut self,
+        content: Vec<PromptContent>,
+        mut transcript: TranscriptWriter,
+        update_tx: mpsc::Sender<AgentUpdate>,
+        pending_permissions: Arc<PendingPermissions>,
     ) -> Result<String, AgentProcessError> {
         let session_id = self
             .session_id
@@ -350,78 +796,105 @@ impl AgentProcess {
             .ok_or(AgentProcessError::NoSession)?
             .clone();
 
-        println!("[DEBUG] Agent {} sending prompt to session {}", self.id, session_id);
         info!("Agent {} sending prompt to session {}", self.id, session_id);
         self.status = AgentStatus::Working;
         self.progress = 0.0;
+        self.last_stop_reason = None;
+        self.turn_start_context = self.context_tokens;
+        self.last_prompt_at = Some(now_secs());
+        self.publish_state();
+        self.cancel.reset();
+        let cancel = self.cancel.clone();
+        let mut cancel_sent = false;
 
         let params = SessionPromptParams {
             session_id: session_id.clone(),
-            prompt: vec![PromptContent::text(prompt)],
+            prompt: content,
         };
 
-        let request = JsonRpcRequest::new(
-            self.next_request_id(),
-            "session/prompt",
-            Some(serde_json::to_value(&params).unwrap()),
-        );
-
-        let json = serde_json::to_string(&request).unwrap();
-        println!("[DEBUG] Sending request: {}", json);
-        debug!("Sending request: {}", json);
-        self.codec
-            .write_message(&json)
-            .await
-            .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+        debug!("Sending prompt request: {:?}", params);
+        let call = self
+            .client
+            .send_request(methods::session_prompt(&params))
+            .await?;
 
-        println!("[DEBUG] Request sent, waiting for response...");
         info!("Request sent, waiting for response...");
 
         // Stream updates until we get the final response
         // Text content comes through notifications, not the final response
-        let mut accumulated_text = String::new();
-
         loop {
-            if let Some(msg) = self
-                .codec
-                .read_message()
-                .await
-                .map_err(|e| {
-                    error!("Read error: {}", e);
-                    AgentProcessError::CommunicationError(e.to_string())
-                })?
-            {
-                match &msg {
-                    JsonRpcMessage::Notification(notif) => {
-                        println!("[DEBUG] Received notification: {} params={:?}", notif.method, notif.params);
-                        debug!("Received notification: {}", notif.method);
-                        if notif.method == "session/update" {
-                            if let Some(params) = &notif.params {
-                                self.handle_session_update(params, &update_tx, &mut accumulated_text).await;
-                            }
+            let incoming = tokio::select! {
+                incoming = self.client.next_for(&call) => incoming,
+                _ = cancel.requested(), if !cancel_sent => {
+                    // The agent answers the prompt with stopReason "cancelled"
+                    info!("Agent {} cancelling prompt on session {}", self.id, session_id);
+                    cancel_sent = true;
+                    self.client.notify(&methods::session_cancel(&session_id)).await?;
+                    continue;
+                }
+            };
+            let incoming = incoming.map_err(|e| {
+                error!("Read error: {}", e);
+                AgentProcessError::from(e)
+            })?;
+            self.last_update_at = Some(now_secs());
+            match incoming {
+                Incoming::Message(JsonRpcMessage::Notification(notif)) => {
+                    debug!("Received notification: {}", notif.method);
+                    if notif.method == methods::SESSION_UPDATE {
+                        if let Some(params) = &notif.params {
+                            self.handle_session_update(params, &update_tx, &mut transcript).await;
+                            self.publish_state();
                         }
+                    } else {
+                        AGENT_NOTIFICATIONS.dispatch(Some(self.id), &notif);
                     }
-                    JsonRpcMessage::Response(resp) => {
-                        debug!("Received response: {:?}", resp);
-                        if let Some(err) = &resp.error {
-                            error!("Response error: {}", err.message);
-                            self.status = AgentStatus::Error;
-                            return Err(AgentProcessError::PromptFailed(err.message.clone()));
-                        }
-                        // Response received - the stopReason indicates completion
-                        // The actual text content comes from accumulated notifications
-                        if resp.result.is_some() {
-                            info!("Prompt completed, accumulated text length: {}", accumulated_text.len());
-                            self.status = AgentStatus::Idle;
-                            self.progress = 100.0;
-                            return Ok(accumulated_text);
-                        }
+                }
+                Incoming::Message(JsonRpcMessage::Request(req)) => {
+                    info!("Received request from agent: {}", req.method);
+                    let handled = self.handle_incoming_request(req.id, &req.method, req.params.as_ref(), &update_tx, &pending_permissions).await;
+                    match handled {
+                        // Keep reading until the agent ends the turn
+                        Err(AgentProcessError::PromptCancelled) => {}
+                        handled => handled?,
+                    }
+                }
+                // Responses are routed by the client
+                Incoming::Message(JsonRpcMessage::Response(_)) => {}
+                Incoming::Response(resp) => {
+                    debug!("Received response: {:?}", resp);
+                    if let Some(err) = &resp.error {
+                        error!("Response error: {}", err.message);
+                        self.status = AgentStatus::Error;
+                        self.publish_state();
+                        return Err(AgentProcessError::PromptFailed(err.message.clone()));
                     }
-                    JsonRpcMessage::Request(req) => {
-                        println!("[DEBUG] Received REQUEST from agent: {} id={} params={:?}", req.method, req.id, req.params);
-                        info!("Received request from agent: {}", req.method);
-                        self.handle_incoming_request(req.id, &req.method, req.params.as_ref(), &update_tx, &pending_permissions).await?;
+                    // Response received - the stopReason indicates completion
+                    // The actual text content comes from accumulated notifications
+                    info!("Prompt completed, answer length: {}", transcript.info().length);
+                    self.last_stop_reason = resp
+                        .result
+                        .as_ref()
+                        .and_then(|result| result.get("stopReason"))
+                        .and_then(|r| r.as_str())
+                        .map(String::from);
+                    let usage = resp
+                        .result
+                        .and_then(|result| serde_json::from_value::<SessionPromptResult>(result).ok())
+                        .and_then(|result| result.usage);
+                    match usage {
+                        Some(usage) => self.record_usage(usage.input_tokens, usage.output_tokens, 0),
+                        // Without a count, what the turn added to the context
+                        None => {
+                            let added = self.context_tokens.saturating_sub(self.turn_start_context);
+                            self.record_usage(added, 0, 0);
+                        }
                     }
+                    self.status = AgentStatus::Idle;
+                    self.progress = 100.0;
+                    self.last_transcript = Some(transcript.info());
+                    self.publish_state();
+                    return Ok(transcript.into_text());
                 }
             }
         }
@@ -432,34 +905,42 @@ impl AgentProcess {
         &mut self,
         params: &Value,
         update_tx: &mpsc::Sender<AgentUpdate>,
-        accumulated_text: &mut String,
+        transcript: &mut TranscriptWriter,
     ) {
+        if let Some(update) = params.get("update") {
+            self.tool_calls.record(update);
+            self.file_locks.observe(self.id, update);
+            if update.get("sessionUpdate").and_then(|u| u.as_str()) == Some("usage_update") {
+                if let Ok(usage) = serde_json::from_value::<UsageUpdate>(update.clone()) {
+                    self.apply_usage_update(usage);
+                }
+                return;
+            }
+        }
+
         // Try parsing as new typed SessionUpdate format first
         match serde_json::from_value::<SessionUpdateNotification>(params.clone()) {
             Ok(notification) => {
-                println!("[DEBUG] Parsed typed SessionUpdate: {:?}", notification.update);
-                self.process_typed_update(&notification.update, update_tx, accumulated_text).await;
+                self.process_typed_update(&notification.update, update_tx, transcript).await;
                 return;
             }
             Err(e) => {
-                println!("[DEBUG] Failed to parse as typed SessionUpdate: {}", e);
+                debug!("Failed to parse as typed SessionUpdate: {}", e);
             }
         }
 
         // Fall back to legacy string-based format
         match serde_json::from_value::<LegacySessionUpdateNotification>(params.clone()) {
             Ok(legacy) => {
-                println!("[DEBUG] Parsed legacy SessionUpdate: {:?}", legacy.update.session_update);
-                self.process_legacy_update(&legacy, update_tx, accumulated_text).await;
+                self.process_legacy_update(&legacy, update_tx, transcript).await;
                 return;
             }
             Err(e) => {
-                println!("[DEBUG] Failed to parse as legacy SessionUpdate: {}", e);
+                debug!("Failed to parse as legacy SessionUpdate: {}", e);
             }
         }
 
         warn!("Failed to parse session update notification: {}", params);
-        println!("[DEBUG] Raw params that failed to parse: {}", params);
 
         // Even if parsing failed, try to extract useful info from raw params
         if let Some(update) = params.get("update") {
@@ -494,6 +975,10 @@ impl AgentProcess {
                 current_file: self.current_file.clone(),
                 status: None,
                 pending_inputs: None,
+                touched_files: update
+                    .get("locations")
+                    .map(TouchedRange::from_locations)
+                    .unwrap_or_default(),
             };
             let _ = update_tx.send(agent_update).await;
         }
@@ -504,7 +989,7 @@ impl AgentProcess {
         &mut self,
         update: &SessionUpdate,
         update_tx: &mpsc::Sender<AgentUpdate>,
-        accumulated_text: &mut String,
+        transcript: &mut TranscriptWriter,
     ) {
         let update_type = match update {
             SessionUpdate::AgentMessageChunk(_) => "agent_message_chunk",
@@ -522,14 +1007,25 @@ impl AgentProcess {
         if update.needs_user_input() {
             self.handle_pending_tool_call(update, update_tx).await;
         }
+        if let Some(tool_call_id) = finished_tool_call(update) {
+            self.clear_tool_call_inputs(tool_call_id);
+        }
 
         // Extract text content from message chunks
         if let Some(text) = update.get_text() {
-            accumulated_text.push_str(text);
+            transcript.push(text);
         }
 
         // Track current file from tool calls
         match update {
+            SessionUpdate::AvailableCommandsUpdate(cmds) => {
+                self.available_commands = cmds.commands.iter().map(|c| c.name.clone()).collect();
+            }
+            SessionUpdate::CurrentModeUpdate(mode) => {
+                if let Some(modes) = &mut self.modes {
+                    modes.current_mode_id = mode.mode.clone();
+                }
+            }
             SessionUpdate::ToolCall(tc) => {
                 // Extract file path from locations or rawInput
                 if let Some(locations) = &tc.locations {
@@ -582,6 +1078,7 @@ impl AgentProcess {
             current_file: self.current_file.clone(),
             status: None,
             pending_inputs: None,
+            touched_files: update.locations().iter().map(TouchedRange::from_location).collect(),
         };
         let _ = update_tx.send(agent_update).await;
     }
@@ -602,17 +1099,21 @@ impl AgentProcess {
             _ => return,
         };
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let timestamp = now_secs();
 
+        let localized = LocalizedMessage::new(MessageKey::AgentWantsTo).with("tool", &title);
         let pending_input = PendingInput {
-            id: tool_call_id,
+            id: tool_call_id.clone(),
             input_type: PendingInputType::ToolPermission,
             tool_name: Some(title.clone()),
-            message: format!("Agent wants to: {}", title),
+            message: localized.render(),
             timestamp,
+            high_risk: false,
+            outside_paths: Vec::new(),
+            risk: None,
+            similar_scope: None,
+            tool_call_id: Some(tool_call_id),
+            localized: Some(localized),
         };
 
         info!("Agent needs permission: {:?}", pending_input);
@@ -630,6 +1131,7 @@ impl AgentProcess {
             current_file: self.current_file.clone(),
             status: Some(self.status),
             pending_inputs: Some(self.pending_inputs.clone()),
+            touched_files: Vec::new(),
         };
         let _ = update_tx.send(agent_update).await;
     }
@@ -639,7 +1141,7 @@ impl AgentProcess {
         &mut self,
         notification: &LegacySessionUpdateNotification,
         update_tx: &mpsc::Sender<AgentUpdate>,
-        accumulated_text: &mut String,
+        transcript: &mut TranscriptWriter,
     ) {
         let update = &notification.update;
         let update_type = &update.session_update;
@@ -652,10 +1154,7 @@ impl AgentProcess {
             || update_type == "waiting_for_user";
 
         if is_input_request {
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs();
+            let timestamp = now_secs();
 
             let input_type = if update_type.contains("permission") {
                 PendingInputType::ToolPermission
@@ -665,16 +1164,15 @@ impl AgentProcess {
                 PendingInputType::UserQuestion
             };
 
-            let message = update
-                .content
-                .as_ref()
-                .and_then(|c| c.text.clone())
-                .unwrap_or_else(|| {
-                    format!(
-                        "Agent needs permission to use: {}",
-                        update.name.as_deref().unwrap_or("unknown tool")
-                    )
-                });
+            let text = update.content.as_ref().and_then(|c| c.text.clone());
+            let localized = text.is_none().then(|| {
+                LocalizedMessage::new(MessageKey::AgentNeedsPermission)
+                    .with("tool", update.name.as_deref().unwrap_or("unknown tool"))
+            });
+            let message = match &localized {
+                Some(localized) => localized.render(),
+                None => text.unwrap_or_default(),
+            };
 
             let pending_input = PendingInput {
                 id: update.tool_use_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string()),
@@ -682,6 +1180,12 @@ impl AgentProcess {
                 tool_name: update.name.clone(),
                 message: message.clone(),
                 timestamp,
+                high_risk: false,
+                outside_paths: Vec::new(),
+                risk: None,
+                similar_scope: None,
+                tool_call_id: update.tool_use_id.clone(),
+                localized,
             };
 
             info!("Agent needs input (legacy): {:?}", pending_input);
@@ -699,6 +1203,7 @@ impl AgentProcess {
                 current_file: self.current_file.clone(),
                 status: Some(self.status),
                 pending_inputs: Some(self.pending_inputs.clone()),
+                touched_files: Vec::new(),
             };
             let _ = update_tx.send(agent_update).await;
         }
@@ -713,7 +1218,7 @@ impl AgentProcess {
 
         // Accumulate text for the result
         if let Some(ref text) = message {
-            accumulated_text.push_str(text);
+            transcript.push(text);
         }
 
         let agent_update = AgentUpdate {
@@ -728,6 +1233,7 @@ impl AgentProcess {
             current_file: self.current_file.clone(),
             status: None,
             pending_inputs: None,
+            touched_files: Vec::new(),
         };
         let _ = update_tx.send(agent_update).await;
     }
@@ -751,7 +1257,7 @@ impl AgentProcess {
         pending_permissions: &Arc<PendingPermissions>,
     ) -> Result<(), AgentProcessError> {
         match method {
-            "session/request_permission" => {
+            methods::SESSION_REQUEST_PERMISSION => {
                 if let Some(params) = params {
                     self.handle_permission_request(request_id, params, update_tx, pending_permissions).await?;
                 }
@@ -765,54 +1271,114 @@ impl AgentProcess {
                     format!("Method not found: {}", method),
                 );
                 let json = serde_json::to_string(&response).unwrap();
-                self.codec
-                    .write_message(&json)
-                    .await
-                    .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+                self.client
+                    .send_raw(&json)
+                    .await?;
             }
         }
         Ok(())
     }
 
-    /// Handle session/request_permission request from agent
-    async fn handle_permission_request(
-        &mut self,
-        request_id: i64,
-        params: &Value,
-        update_tx: &mpsc::Sender<AgentUpdate>,
-        pending_permissions: &Arc<PendingPermissions>,
-    ) -> Result<(), AgentProcessError> {
-        let request: RequestPermissionRequest = serde_json::from_value(params.clone())
-            .map_err(|e| AgentProcessError::CommunicationError(format!("Invalid permission request: {}", e)))?;
-
-        info!("Agent requesting permission for: {}", request.tool_call.title.as_deref().unwrap_or("unknown"));
-
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+    /// Hold an edit until other agents finished editing the same files
+    async fn wait_for_conflicting_edits(&mut self, tool_call: &ToolCallUpdate) {
+        let recorded = self.tool_calls.get(&tool_call.tool_call_id);
+        let is_edit = recorded
+            .as_ref()
+            .and_then(|c| c.kind.as_deref())
+            .is_some_and(locks::is_edit_kind);
+        let paths: Vec<String> = match &tool_call.locations {
+            Some(locations) => locations.iter().map(|l| l.path.clone()).collect(),
+            None => recorded.map(|c| c.locations).unwrap_or_default(),
+        };
+        if !is_edit || paths.is_empty() {
+            return;
+        }
 
-        let input_id = format!("perm_req_{}", request_id);
+        let id = &tool_call.tool_call_id;
+        let conflicts = self.file_locks.acquire(self.id, id, &paths);
+        if conflicts.is_empty() {
+            return;
+        }
+        locks::report(self.id, id, conflicts, true);
+        if !self.file_locks.wait_for(self.id, id, &paths, PERMISSION_HOLD_TIMEOUT).await {
+            warn!("Agent {} still conflicts after waiting; asking for permission anyway", self.id);
+        }
+    }
 
-        // Store the request_id so we can respond later
-        let pending_input = PendingInput {
-            id: input_id.clone(),
-            input_type: PendingInputType::ToolPermission,
-            tool_name: request.tool_call.title.clone(),
-            message: format!(
-                "Permission requested: {}",
-                request.tool_call.title.as_deref().unwrap_or("unknown tool")
-            ),
-            timestamp,
+    /// Risk, paths outside every project and similarity scope of a
+    /// permission request. Paths come from its locations, the path in its raw input, and what
+    /// was recorded for the tool call before; so do kind and raw input.
+    fn assess_permission(&self, tool_call: &ToolCallUpdate, params: &Value) -> PermissionAssessment {
+        let recorded = self.tool_calls.get(&tool_call.tool_call_id);
+        let raw_tool_call = params.get("toolCall");
+        let kind = raw_tool_call
+            .and_then(|c| c.get("kind"))
+            .and_then(|k| k.as_str())
+            .map(String::from)
+            .or_else(|| recorded.as_ref().and_then(|c| c.kind.clone()));
+        let raw_input = raw_tool_call
+            .and_then(|c| c.get("rawInput"))
+            .or_else(|| recorded.as_ref().and_then(|c| c.raw_input.as_ref()));
+
+        let mut paths: Vec<String> = tool_call.locations.iter().flatten().map(|l| l.path.clone()).collect();
+        paths.extend(recorded.as_ref().map(|c| c.locations.clone()).unwrap_or_default());
+        paths.extend(raw_input.and_then(extract_file_path));
+        paths.sort_unstable();
+        paths.dedup();
+
+        let outside_paths = paths_outside_projects(&paths, Path::new(&self.working_directory));
+        let risk = score_permission(RiskInput {
+            kind: kind.as_deref(),
+            paths: &paths,
+            raw_input,
+            outside_project: !outside_paths.is_empty(),
+        });
+        let facts = PermissionFacts {
+            agent_id: self.id.to_string(),
+            agent_name: self.name.clone(),
+            provider_id: self.provider_id.clone(),
+            project: self.working_directory.clone(),
+            tool: tool_call.title.clone(),
+            kind: kind.clone(),
+            paths: paths.clone(),
+            command: command_text(raw_input),
+            risk: risk.score,
+            outside_project: !outside_paths.is_empty(),
         };
+        PermissionAssessment {
+            risk,
+            scope: similarity_scope(kind.as_deref(), &paths),
+            outside_paths,
+            facts,
+        }
+    }
 
+    /// Show a permission request to the user and wait for the answer
+    async fn ask_permission(
+        &mut self,
+        request_id: i64,
+        pending_input: PendingInput,
+        request: &RequestPermissionRequest,
+        update_tx: &mpsc::Sender<AgentUpdate>,
+        pending_permissions: &Arc<PendingPermissions>,
+    ) -> Result<PermissionUserResponse, AgentProcessError> {
+        let input_id = pending_input.id.clone();
         self.add_pending_input(pending_input.clone());
 
         // Create a channel to wait for user response
         let (response_tx, response_rx) = oneshot::channel::<PermissionUserResponse>();
 
         // Store the pending permission in shared storage (avoids deadlock by not requiring agent lock)
-        pending_permissions.store(self.id, &input_id, response_tx);
+        let mut offered = request.clone();
+        if pending_input.similar_scope.is_some() {
+            offered.options.push(PermissionOption {
+                option_id: ALLOW_SIMILAR_OPTION_ID.to_string(),
+                name: "Allow all similar for this task".to_string(),
+                kind: PermissionOptionKind::AllowOnce,
+                description: pending_input.similar_scope.clone(),
+            });
+        }
+        pending_permissions.store(self.id, &pending_input, offered, response_tx);
 
         // Notify frontend about the permission request with available options
         let agent_update = AgentUpdate {
@@ -827,15 +1393,129 @@ impl AgentProcess {
             current_file: self.current_file.clone(),
             status: Some(self.status),
             pending_inputs: Some(self.pending_inputs.clone()),
+            touched_files: Vec::new(),
         };
         let _ = update_tx.send(agent_update).await;
 
         info!("Waiting for user response for permission request {}", input_id);
 
+        // Forgets the request if the prompt is dropped before it is answered
+        let _pending = PendingGuard {
+            pending_permissions,
+            agent_id: self.id,
+            input_id: &input_id,
+        };
+
         // Wait for user response via the channel
-        let user_response = response_rx.await.map_err(|_| {
-            AgentProcessError::CommunicationError("Permission request channel closed".to_string())
-        })?;
+        let cancel = self.cancel.clone();
+        let answered = tokio::select! {
+            answered = response_rx => answered,
+            _ = cancel.requested() => {
+                // Requests of a cancelled prompt must be answered as cancelled
+                self.clear_pending_input(&input_id);
+                self.answer_cancelled(request_id).await?;
+                return Err(AgentProcessError::PromptCancelled);
+            }
+        };
+        let Ok(user_response) = answered else {
+            // Cancelled, e.g. because the agent is stopping
+            info!("Permission request {} cancelled", input_id);
+            self.clear_pending_input(&input_id);
+            if let Err(e) = self.answer_cancelled(request_id).await {
+                debug!("Agent {} gone before the cancellation was sent: {}", self.id, e);
+            }
+            return Err(AgentProcessError::PromptFailed("Permission request cancelled".to_string()));
+        };
+        Ok(user_response)
+    }
+
+    async fn answer_cancelled(&mut self, request_id: i64) -> Result<(), ProtocolError> {
+        let response = serde_json::to_value(RequestPermissionResponse::cancelled()).unwrap();
+        let json = serde_json::to_string(&JsonRpcResponse::success(request_id, response)).unwrap();
+        self.client.send_raw(&json).await
+    }
+
+    /// Handle session/request_permission request from agent
+    async fn handle_permission_request(
+        &mut self,
+        request_id: i64,
+        params: &Value,
+        update_tx: &mpsc::Sender<AgentUpdate>,
+        pending_permissions: &Arc<PendingPermissions>,
+    ) -> Result<(), AgentProcessError> {
+        let request: RequestPermissionRequest = serde_json::from_value(params.clone())
+            .map_err(|e| AgentProcessError::CommunicationError(format!("Invalid permission request: {}", e)))?;
+
+        info!("Agent requesting permission for: {}", request.tool_call.title.as_deref().unwrap_or("unknown"));
+        if self.file_locks.holds_permissions() {
+            self.wait_for_conflicting_edits(&request.tool_call).await;
+        }
+
+        let timestamp = now_secs();
+
+        let input_id = format!("perm_req_{}", request_id);
+        let assessment = self.assess_permission(&request.tool_call, params);
+        let high_risk = !assessment.outside_paths.is_empty();
+        if high_risk {
+            warn!("Agent {} asks for a tool call outside the project: {:?}", self.id, assessment.outside_paths);
+        }
+
+        // The policy script answers first; it can't approve reaching outside the project
+        let scripted = permission_decision(&assessment.facts).unwrap_or_else(|e| {
+            warn!("Permission script failed, asking the user: {}", e);
+            ScriptDecision::Ask
+        });
+        let user_response = match assessment.scope {
+            _ if scripted == ScriptDecision::Deny => {
+                info!("Permission request {} denied by the policy script", input_id);
+                PermissionUserResponse {
+                    approved: false,
+                    option_id: None,
+                }
+            }
+            _ if scripted == ScriptDecision::Approve
+                && !high_risk
+                && PLUGINS.allows_auto_approval(self.id, &request, AutoApprovalReason::Script) =>
+            {
+                info!("Permission request {} approved by the policy script", input_id);
+                PermissionUserResponse {
+                    approved: true,
+                    option_id: None,
+                }
+            }
+            Some(scope)
+                if !high_risk
+                    && pending_permissions.allows_similar(self.id, &scope)
+                    && PLUGINS.allows_auto_approval(self.id, &request, AutoApprovalReason::AllowedSimilar) =>
+            {
+                info!("Permission request {} approved like earlier ones for {}", input_id, scope);
+                PermissionUserResponse {
+                    approved: true,
+                    option_id: None,
+                }
+            }
+            scope => {
+                // Offer allowing the rest once the same kind of call repeats
+                let similar_scope = scope.filter(|s| pending_permissions.note_similar(self.id, s) > 1 && !high_risk);
+                let localized = LocalizedMessage::new(MessageKey::PermissionRequested)
+                    .with("tool", request.tool_call.title.as_deref().unwrap_or("unknown tool"));
+                let pending_input = PendingInput {
+                    id: input_id.clone(),
+                    input_type: PendingInputType::ToolPermission,
+                    tool_name: request.tool_call.title.clone(),
+                    message: localized.render(),
+                    timestamp,
+                    high_risk,
+                    outside_paths: assessment.outside_paths,
+                    risk: Some(assessment.risk),
+                    similar_scope,
+                    tool_call_id: Some(request.tool_call.tool_call_id.clone()),
+                    localized: Some(localized),
+                };
+                self.ask_permission(request_id, pending_input, &request, update_tx, pending_permissions)
+                    .await?
+            }
+        };
 
         info!("Received user response: approved={}, option_id={:?}", user_response.approved, user_response.option_id);
 
@@ -849,7 +1529,7 @@ impl AgentProcess {
                     .map(|o| o.option_id.clone())
                     .unwrap_or_else(|| request.options.first().map(|o| o.option_id.clone()).unwrap_or_default())
             });
-            println!("[DEBUG] Sending permission APPROVED with optionId: {}", option_id);
+            debug!("Approving permission request with option {}", option_id);
             RequestPermissionResponse::selected(option_id)
         } else {
             // User denied - find the first "reject" option or use "cancelled"
@@ -858,10 +1538,10 @@ impl AgentProcess {
                 .find(|o| matches!(o.kind, crate::acp::PermissionOptionKind::RejectOnce | crate::acp::PermissionOptionKind::RejectAlways));
 
             if let Some(reject) = reject_option {
-                println!("[DEBUG] Sending permission REJECTED with optionId: {}", reject.option_id);
+                debug!("Rejecting permission request with option {}", reject.option_id);
                 RequestPermissionResponse::selected(reject.option_id.clone())
             } else {
-                println!("[DEBUG] Sending permission CANCELLED");
+                debug!("Cancelling permission request");
                 RequestPermissionResponse::cancelled()
             }
         };
@@ -873,10 +1553,9 @@ impl AgentProcess {
 
         let json = serde_json::to_string(&rpc_response).unwrap();
         info!("Sending permission response: {}", json);
-        self.codec
-            .write_message(&json)
-            .await
-            .map_err(|e| AgentProcessError::CommunicationError(e.to_string()))?;
+        self.client
+            .send_raw(&json)
+            .await?;
 
         // Clear the pending input since we responded
         self.clear_pending_input(&input_id);
@@ -886,10 +1565,20 @@ impl AgentProcess {
 
     pub async fn stop(&mut self) -> Result<(), AgentProcessError> {
         self.status = AgentStatus::Stopped;
-        self.child
-            .kill()
-            .await
-            .map_err(|e| AgentProcessError::StopFailed(e.to_string()))?;
+        self.pending_inputs.clear();
+        self.publish_state();
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        if let Some(child) = &mut self.child {
+            child
+                .kill()
+                .await
+                .map_err(|e| AgentProcessError::StopFailed(e.to_string()))?;
+        }
+        if let Some(container) = self.container.take() {
+            container.remove().await.map_err(AgentProcessError::StopFailed)?;
+        }
         Ok(())
     }
 
@@ -909,13 +1598,74 @@ impl AgentProcess {
             provider_name: self.provider_name.clone(),
             auth_methods: self.auth_methods.clone(),
             needs_auth: self.needs_auth,
+            available_commands: self.available_commands.clone(),
+            supports_load_session: self.supports_load_session,
+            supports_list_sessions: self.supports_list_sessions,
+            modes: self.modes.clone(),
+            models: self.models.clone(),
+            created_at: self.created_at,
+            last_prompt_at: self.last_prompt_at,
+            last_update_at: self.last_update_at,
+            last_transcript: self.last_transcript.clone(),
+        }
+    }
+
+    /// Receiver of the agent's info, updated as it changes
+    pub fn subscribe_state(&self) -> watch::Receiver<AgentInfo> {
+        // Fields set without a change being published, like the provider
+        self.publish_state();
+        self.state.subscribe()
+    }
+
+    /// Publish the current info if any of it changed
+    /// Add tokens and cost to the agent's total and the usage metrics
+    fn record_usage(&mut self, input_tokens: u64, output_tokens: u64, cost_cents: u64) {
+        if input_tokens + output_tokens + cost_cents == 0 {
+            return;
         }
+        self.tokens_used += input_tokens + output_tokens;
+        let _ = AGENT_USAGE.send(AgentUsage {
+            agent_id: self.id,
+            input_tokens,
+            output_tokens,
+            cost_cents,
+        });
+    }
+
+    /// Keep the context size and record the cost added since the last update
+    fn apply_usage_update(&mut self, usage: UsageUpdate) {
+        self.context_tokens = usage.used;
+        let Some(cost) = usage.cost.filter(|cost| cost.currency == "USD") else {
+            return;
+        };
+        let cents = (cost.amount * 100.0).round() as u64;
+        let added = cents.saturating_sub(self.session_cost_cents);
+        self.session_cost_cents = cents;
+        self.record_usage(0, 0, added);
+    }
+
+    fn reset_usage(&mut self) {
+        self.context_tokens = 0;
+        self.session_cost_cents = 0;
+        self.turn_start_context = 0;
+    }
+
+    fn publish_state(&self) {
+        let info = self.info();
+        self.state.send_if_modified(|current| {
+            let modified = *current != info;
+            if modified {
+                *current = info;
+            }
+            modified
+        });
     }
 
     /// Add a pending input request
     pub fn add_pending_input(&mut self, input: PendingInput) {
-        self.pending_inputs.push(input);
+        merge_pending_input(&mut self.pending_inputs, input);
         self.status = AgentStatus::Paused; // Agent is waiting for input
+        self.publish_state();
     }
 
     /// Clear a pending input by ID
@@ -924,6 +1674,19 @@ impl AgentProcess {
         if self.pending_inputs.is_empty() {
             self.status = AgentStatus::Idle;
         }
+        self.publish_state();
+    }
+
+    /// Drop the pending inputs of a tool call that finished; the agent
+    /// carries on if nothing else waits for an answer
+    fn clear_tool_call_inputs(&mut self, tool_call_id: &str) {
+        if !clear_tool_call_inputs(&mut self.pending_inputs, tool_call_id) {
+            return;
+        }
+        if self.pending_inputs.is_empty() && self.status == AgentStatus::Paused {
+            self.status = AgentStatus::Working;
+        }
+        self.publish_state();
     }
 
     /// Check if agent has pending inputs
@@ -942,6 +1705,9 @@ pub struct AgentUpdate {
     pub current_file: Option<String>,
     pub status: Option<AgentStatus>,
     pub pending_inputs: Option<Vec<PendingInput>>,
+    /// Files and line ranges the tool call of this update points at
+    #[serde(default)]
+    pub touched_files: Vec<TouchedRange>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -974,4 +1740,60 @@ pub enum AgentProcessError {
     AuthFailed(String),
     #[error("Authentication required")]
     AuthRequired,
+    #[error("Task error: {0}")]
+    TaskError(String),
+    #[error("{method} timed out after {secs}s")]
+    Timeout { method: String, secs: u64 },
+    #[error("Permission request {0} touches paths outside the project and needs explicit confirmation")]
+    ConfirmationRequired(String),
+    #[error("{0}")]
+    PromptRejected(PromptRejection),
+    #[error("Prompt cancelled")]
+    PromptCancelled,
+}
+
+/// Why an agent cannot take a prompt in its current state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PromptRejection {
+    /// The agent's process failed or was stopped; it needs a restart
+    #[error("Agent {agent_id} is not running; restart it to send prompts")]
+    AgentCrashed { agent_id: Uuid, status: AgentStatus },
+    /// The agent wants the user to authenticate with one of its methods first
+    #[error("Agent {agent_id} needs authentication")]
+    NeedsAuth { agent_id: Uuid, auth_methods: Vec<AuthMethod> },
+    /// The agent is still starting or waits for answers to its requests
+    #[error("Agent {agent_id} is busy")]
+    Busy { agent_id: Uuid, status: AgentStatus, pending_inputs: usize },
+}
+
+impl AgentInfo {
+    /// Why the agent cannot be prompted now, if it cannot
+    pub fn prompt_rejection(&self) -> Option<PromptRejection> {
+        let agent_id = self.id;
+        match self.status {
+            AgentStatus::Error | AgentStatus::Stopped => {
+                Some(PromptRejection::AgentCrashed { agent_id, status: self.status })
+            }
+            AgentStatus::NeedsAuth => Some(PromptRejection::NeedsAuth {
+                agent_id,
+                auth_methods: self.auth_methods.clone(),
+            }),
+            AgentStatus::Initializing | AgentStatus::Paused => Some(PromptRejection::Busy {
+                agent_id,
+                status: self.status,
+                pending_inputs: self.pending_inputs.len(),
+            }),
+            AgentStatus::Idle | AgentStatus::Working => None,
+        }
+    }
+}
+
+impl From<ProtocolError> for AgentProcessError {
+    fn from(error: ProtocolError) -> Self {
+        match error {
+            ProtocolError::Timeout { method, secs } => Self::Timeout { method, secs },
+            ProtocolError::Codec(e) => Self::CommunicationError(e.to_string()),
+        }
+    }
 }
@@ -0,0 +1,78 @@
+//! Which paths belong to a registered project: the open project, the
+//! projects on the factory floor and each agent's working directory.
+//! Permission requests for tool calls reaching outside all of them are
+//! flagged high risk.
+
+use super::locks::canonical;
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Roots of the registered projects
+static PROJECT_ROOTS: Lazy<RwLock<Vec<PathBuf>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Replace the registered project roots
+pub fn set_project_roots(roots: &[PathBuf]) {
+    *PROJECT_ROOTS.write().unwrap() = roots.iter().map(|r| canonical(&r.to_string_lossy())).collect();
+}
+
+/// Those of `paths` outside `roots` and `working_directory`; relative
+/// paths are resolved against `working_directory`
+pub fn paths_outside(paths: &[String], working_directory: &Path, roots: &[PathBuf]) -> Vec<String> {
+    let working_directory = canonical(&working_directory.to_string_lossy());
+    paths
+        .iter()
+        .filter(|path| {
+            let resolved = canonical(&working_directory.join(path).to_string_lossy());
+            !resolved.starts_with(&working_directory) && !roots.iter().any(|root| resolved.starts_with(root))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Those of `paths` outside every registered project and `working_directory`
+pub fn paths_outside_projects(paths: &[String], working_directory: &Path) -> Vec<String> {
+    paths_outside(paths, working_directory, &PROJECT_ROOTS.read().unwrap())
+}
+
+/// Absolute paths among `paths` outside every registered project, for
+/// callers that do not know the agent's working directory
+pub fn absolute_paths_outside_projects(paths: &[String]) -> Vec<String> {
+    let roots = PROJECT_ROOTS.read().unwrap();
+    paths
+        .iter()
+        .filter(|path| Path::new(path).is_absolute())
+        .filter(|path| {
+            let resolved = canonical(path);
+            !roots.iter().any(|root| resolved.starts_with(root))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_outside_projects() {
+        let work = std::env::temp_dir().join(format!("acptorio-scope-{}", uuid::Uuid::new_v4()));
+        let other = std::env::temp_dir().join(format!("acptorio-scope-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(work.join("src")).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        let roots = vec![canonical(&other.to_string_lossy())];
+        let paths: Vec<String> = vec![
+            "src/main.rs".to_string(),
+            work.join("src/lib.rs").to_string_lossy().to_string(),
+            other.join("notes.md").to_string_lossy().to_string(),
+            "../../../../../../etc/hosts".to_string(),
+            "/etc/hosts".to_string(),
+        ];
+
+        let outside = paths_outside(&paths, &work, &roots);
+        std::fs::remove_dir_all(&work).ok();
+        std::fs::remove_dir_all(&other).ok();
+
+        assert_eq!(outside, vec!["../../../../../../etc/hosts".to_string(), "/etc/hosts".to_string()]);
+    }
+}
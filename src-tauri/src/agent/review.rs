@@ -0,0 +1,159 @@
+//! Agent-to-agent code review: when a task in a project completes, its
+//! changes are sent to a designated reviewer agent and the verdict is
+//! attached to the task.
+
+use super::tasks::TaskInfo;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Diffs larger than this are cut off in the review prompt
+const MAX_REVIEW_DIFF_BYTES: usize = 200 * 1024;
+
+/// Default review prompt; `{prompt}`, `{summary}` and `{diff}` are substituted
+pub const DEFAULT_REVIEW_TEMPLATE: &str = "Review the following change made by another agent.\n\n\
+Task it was given:\n{prompt}\n\nFiles changed:\n{summary}\n\nDiff:\n```diff\n{diff}\n```\n\n\
+Point out bugs, risky changes and missing tests. End your reply with a final line that is exactly \
+`VERDICT: APPROVE` or `VERDICT: REQUEST_CHANGES`.";
+
+/// Review settings for one project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewWorkflow {
+    /// Tasks of agents working in this directory (or below it) are reviewed
+    pub project_path: String,
+    /// Id or name of the reviewer agent
+    pub reviewer: String,
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewVerdict {
+    Approved,
+    ChangesRequested,
+    /// The reviewer did not end with a recognizable verdict
+    Unclear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    InProgress,
+    Completed,
+    Failed,
+}
+
+/// A review attached to the reviewed task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskReview {
+    pub reviewer_agent_id: Uuid,
+    pub review_task_id: String,
+    pub status: ReviewStatus,
+    pub verdict: Option<ReviewVerdict>,
+    /// The reviewer's full reply
+    pub comments: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The enabled workflow covering `working_directory`, preferring the most
+/// specific project path
+pub fn find_workflow<'a>(
+    workflows: &'a [ReviewWorkflow],
+    working_directory: &str,
+) -> Option<&'a ReviewWorkflow> {
+    workflows
+        .iter()
+        .filter(|w| w.enabled && Path::new(working_directory).starts_with(&w.project_path))
+        .max_by_key(|w| w.project_path.len())
+}
+
+/// Build the review prompt for a completed task
+pub fn build_review_prompt(template: Option<&str>, task: &TaskInfo, diff: &str) -> String {
+    let summary = match &task.change_summary {
+        Some(summary) if !summary.is_empty() => summary
+            .files
+            .iter()
+            .map(|f| format!("{:?} {} (+{} -{})", f.kind, f.path, f.additions, f.deletions))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => "(no file changes detected)".to_string(),
+    };
+
+    let diff = if diff.len() > MAX_REVIEW_DIFF_BYTES {
+        let mut end = MAX_REVIEW_DIFF_BYTES;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}\n... (diff truncated)", &diff[..end])
+    } else {
+        diff.to_string()
+    };
+
+    template
+        .unwrap_or(DEFAULT_REVIEW_TEMPLATE)
+        .replace("{prompt}", &task.prompt)
+        .replace("{summary}", &summary)
+        .replace("{diff}", &diff)
+}
+
+/// Read the verdict from the reviewer's reply; the last verdict line wins
+pub fn parse_verdict(reply: &str) -> ReviewVerdict {
+    reply
+        .lines()
+        .rev()
+        .find_map(|line| {
+            let line = line.trim().trim_matches('`').to_ascii_uppercase();
+            let verdict = line.strip_prefix("VERDICT:")?.trim().to_string();
+            match verdict.as_str() {
+                "APPROVE" | "APPROVED" => Some(ReviewVerdict::Approved),
+                "REQUEST_CHANGES" | "CHANGES_REQUESTED" => Some(ReviewVerdict::ChangesRequested),
+                _ => None,
+            }
+        })
+        .unwrap_or(ReviewVerdict::Unclear)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflow(project_path: &str, enabled: bool) -> ReviewWorkflow {
+        ReviewWorkflow {
+            project_path: project_path.to_string(),
+            reviewer: "Reviewer".to_string(),
+            prompt_template: None,
+            enabled,
+        }
+    }
+
+    #[test]
+    fn test_find_workflow_prefers_most_specific_project() {
+        let workflows = vec![
+            workflow("/work", true),
+            workflow("/work/app", true),
+            workflow("/work/app/sub", false),
+        ];
+        let found = find_workflow(&workflows, "/work/app/sub").unwrap();
+        assert_eq!(found.project_path, "/work/app");
+        assert!(find_workflow(&workflows, "/other").is_none());
+        // Path components must match, not just string prefixes
+        assert_eq!(find_workflow(&workflows, "/work/application").unwrap().project_path, "/work");
+    }
+
+    #[test]
+    fn test_parse_verdict() {
+        assert_eq!(parse_verdict("Looks good.\nVERDICT: APPROVE"), ReviewVerdict::Approved);
+        assert_eq!(
+            parse_verdict("Bug on line 3.\n`verdict: request_changes`\n"),
+            ReviewVerdict::ChangesRequested
+        );
+        assert_eq!(parse_verdict("I am not sure."), ReviewVerdict::Unclear);
+    }
+}
@@ -0,0 +1,194 @@
+//! Heuristic risk score of a permission request, from the tool kind, the
+//! paths it touches, the shell command it runs and the size of its edit.
+//! The tier lets the UI color-code requests and policies match on risk
+//! instead of exact tool names.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Path fragments of credentials, keys and system configuration
+const SENSITIVE_PATHS: &[&str] = &[
+    ".env", ".ssh", "id_rsa", "id_ed25519", ".pem", ".key", "credentials", "secret", ".aws",
+    ".npmrc", ".git/", "/etc/",
+];
+
+/// Shell fragments that destroy data, escalate privileges or run
+/// downloaded code
+const DANGEROUS_COMMANDS: &[&str] = &[
+    "rm -rf", "rm -fr", "sudo ", "mkfs", "dd if=", "chmod 777", "chmod -r", "chown -r",
+    "push --force", "push -f", "reset --hard", "| sh", "| bash", "> /dev/", ":(){",
+];
+
+/// Shell fragments that reach the network
+const NETWORK_COMMANDS: &[&str] = &["curl ", "wget ", "ssh ", "scp ", "nc "];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskTier {
+    #[default]
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl RiskTier {
+    fn from_score(score: u32) -> Self {
+        match score {
+            0..=19 => RiskTier::Low,
+            20..=49 => RiskTier::Medium,
+            50..=79 => RiskTier::High,
+            _ => RiskTier::Critical,
+        }
+    }
+}
+
+/// Score (0-100) and tier of a permission request with what raised it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct PermissionRisk {
+    pub score: u32,
+    pub tier: RiskTier,
+    pub reasons: Vec<String>,
+}
+
+/// What a permission request is known to do
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RiskInput<'a> {
+    /// ACP tool kind: read, edit, delete, move, search, execute, think, fetch
+    pub kind: Option<&'a str>,
+    pub paths: &'a [String],
+    pub raw_input: Option<&'a Value>,
+    /// Some path lies outside every registered project
+    pub outside_project: bool,
+}
+
+/// Score a permission request
+pub fn score_permission(input: RiskInput) -> PermissionRisk {
+    let mut score = 0;
+    let mut reasons = Vec::new();
+
+    let kind_score = match input.kind {
+        Some("read") | Some("search") | Some("think") => 0,
+        Some("fetch") => 10,
+        Some("edit") | Some("move") => 20,
+        Some("execute") => 30,
+        Some("delete") => 40,
+        _ => 10,
+    };
+    if kind_score > 0 {
+        score += kind_score;
+        reasons.push(format!("{} tool", input.kind.unwrap_or("unknown")));
+    }
+
+    if let Some(path) = input.paths.iter().find(|p| is_sensitive(p)) {
+        score += 30;
+        reasons.push(format!("sensitive path {}", path));
+    }
+    if input.outside_project {
+        score += 40;
+        reasons.push("outside the project".to_string());
+    }
+
+    if let Some(command) = input.raw_input.and_then(command) {
+        let command = command.to_lowercase();
+        let dangerous: Vec<_> = DANGEROUS_COMMANDS
+            .iter()
+            .filter(|p| command.contains(*p))
+            .map(|p| format!("`{}`", p.trim()))
+            .collect();
+        if !dangerous.is_empty() {
+            score += 40;
+            reasons.push(format!("command uses {}", dangerous.join(", ")));
+        } else if NETWORK_COMMANDS.iter().any(|p| command.contains(p)) {
+            score += 10;
+            reasons.push("command reaches the network".to_string());
+        }
+    }
+
+    let lines = input.raw_input.map(diff_lines).unwrap_or(0);
+    if lines > 200 {
+        score += 20;
+        reasons.push(format!("{} changed lines", lines));
+    } else if lines > 50 {
+        score += 10;
+        reasons.push(format!("{} changed lines", lines));
+    }
+
+    let score = score.min(100);
+    PermissionRisk {
+        score,
+        tier: RiskTier::from_score(score),
+        reasons,
+    }
+}
+
+fn is_sensitive(path: &str) -> bool {
+    let path = path.to_lowercase();
+    SENSITIVE_PATHS.iter().any(|p| path.contains(p))
+}
+
+/// Shell command of an execute tool's input
+fn command(raw_input: &Value) -> Option<String> {
+    match raw_input.get("command")? {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    }
+}
+
+/// Lines an edit tool's input writes or replaces
+fn diff_lines(raw_input: &Value) -> usize {
+    ["content", "new_string", "old_string", "newText", "oldText"]
+        .iter()
+        .filter_map(|key| raw_input.get(key).and_then(|v| v.as_str()))
+        .map(|text| text.lines().count())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_score_permission_tiers() {
+        let read = score_permission(RiskInput {
+            kind: Some("read"),
+            paths: &["src/main.rs".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(read.tier, RiskTier::Low);
+        assert!(read.reasons.is_empty());
+
+        let edit = score_permission(RiskInput {
+            kind: Some("edit"),
+            paths: &["src/main.rs".to_string()],
+            raw_input: Some(&json!({"new_string": "a\n".repeat(60)})),
+            ..Default::default()
+        });
+        assert_eq!(edit.score, 30);
+        assert_eq!(edit.tier, RiskTier::Medium);
+
+        let env = score_permission(RiskInput {
+            kind: Some("edit"),
+            paths: &[".env".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(env.tier, RiskTier::High);
+
+        let wipe = score_permission(RiskInput {
+            kind: Some("execute"),
+            raw_input: Some(&json!({"command": "sudo rm -rf /"})),
+            outside_project: true,
+            ..Default::default()
+        });
+        assert_eq!(wipe.tier, RiskTier::Critical);
+        assert!(wipe.reasons.iter().any(|r| r.contains("sudo")));
+    }
+}
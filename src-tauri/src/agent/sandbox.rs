@@ -0,0 +1,215 @@
+//! Running agents inside a docker/podman container: only the project
+//! directory is mounted read-write, the rest of the host filesystem stays
+//! out of reach.
+
+use super::limits::ResourceLimits;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// Image with Node, so npx agents work out of the box
+pub const DEFAULT_SANDBOX_IMAGE: &str = "node:22-bookworm";
+/// Where a downloaded agent binary is mounted, clear of the image's own
+/// directories
+const CONTAINER_BIN_DIR: &str = "/opt/acptorio/bin";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub fn program(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+fn default_image() -> String {
+    DEFAULT_SANDBOX_IMAGE.to_string()
+}
+
+/// How to containerize locally spawned agents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerSandbox {
+    #[serde(default)]
+    pub runtime: ContainerRuntime,
+    #[serde(default = "default_image")]
+    pub image: String,
+    /// Host environment variables passed into the container, e.g. API keys
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+impl Default for ContainerSandbox {
+    fn default() -> Self {
+        Self {
+            runtime: ContainerRuntime::default(),
+            image: default_image(),
+            env: Vec::new(),
+        }
+    }
+}
+
+/// A container started for an agent, removed when the agent stops
+#[derive(Debug, Clone)]
+pub struct AgentContainer {
+    pub runtime: ContainerRuntime,
+    pub name: String,
+}
+
+impl AgentContainer {
+    pub fn for_agent(runtime: ContainerRuntime, agent_id: Uuid) -> Self {
+        Self {
+            runtime,
+            name: format!("acptorio-agent-{}", agent_id),
+        }
+    }
+
+    /// Force-remove the container. Killing the runtime client alone leaves
+    /// it running.
+    pub async fn remove(&self) -> Result<(), String> {
+        let output = Command::new(self.runtime.program())
+            .args(["rm", "-f", &self.name])
+            .output()
+            .await
+            .map_err(|e| format!("{}: {}", self.runtime.program(), e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ContainerSandbox {
+    /// The runtime invocation that runs `command` in `container`, with the
+    /// working directory mounted at the same path, an absolute `command`
+    /// mounted in [`CONTAINER_BIN_DIR`] and `limits` enforced by the runtime
+    pub fn command_line(
+        &self,
+        container: &AgentContainer,
+        limits: Option<&ResourceLimits>,
+        command: &str,
+        args: &[String],
+        working_directory: &str,
+    ) -> (String, Vec<String>) {
+        let mut run_args: Vec<String> = vec![
+            "run".into(),
+            "--rm".into(),
+            // Keep stdin open for ACP; no tty so stdio stays raw
+            "-i".into(),
+            "--init".into(),
+            "--name".into(),
+            container.name.clone(),
+            "--mount".into(),
+            bind_mount(working_directory, working_directory, false),
+            "-w".into(),
+            working_directory.to_string(),
+        ];
+        // Downloaded agent binaries live outside the project
+        let mut program = command.to_string();
+        let command_path = Path::new(command);
+        if let (true, Some(name)) = (command_path.is_absolute(), command_path.file_name()) {
+            program = format!("{}/{}", CONTAINER_BIN_DIR, name.to_string_lossy());
+            run_args.extend(["--mount".into(), bind_mount(command, &program, true)]);
+        }
+        if let Some(memory_mb) = limits.and_then(|l| l.memory_mb) {
+            run_args.extend(["--memory".into(), format!("{}m", memory_mb)]);
+        }
+        if let Some(cpus) = limits.and_then(|l| l.cpus) {
+            run_args.extend(["--cpus".into(), cpus.to_string()]);
+        }
+        for name in &self.env {
+            run_args.extend(["-e".into(), name.clone()]);
+        }
+        run_args.push(self.image.clone());
+        run_args.push(program);
+        run_args.extend(args.iter().cloned());
+        (self.runtime.program().to_string(), run_args)
+    }
+}
+
+/// `--mount` value binding `source` at `target` in the container. Unlike
+/// `-v` it has no `:` separators, so Windows paths work; fields are quoted
+/// as CSV when they contain a comma.
+fn bind_mount(source: &str, target: &str, read_only: bool) -> String {
+    let field = |key: &str, path: &str| {
+        let field = format!("{}={}", key, path);
+        if field.contains(',') || field.contains('"') {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    };
+    let mut mount = format!("type=bind,{},{}", field("source", source), field("target", target));
+    if read_only {
+        mount.push_str(",readonly");
+    }
+    mount
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_mounts_project_and_binary() {
+        let sandbox = ContainerSandbox {
+            runtime: ContainerRuntime::Podman,
+            image: "node:22".to_string(),
+            env: vec!["ANTHROPIC_API_KEY".to_string()],
+        };
+        let container = AgentContainer {
+            runtime: sandbox.runtime,
+            name: "acptorio-agent-1".to_string(),
+        };
+        let limits = ResourceLimits {
+            memory_mb: Some(2048),
+            cpus: Some(1.5),
+        };
+        let (program, args) = sandbox.command_line(
+            &container,
+            Some(&limits),
+            "/opt/agents/codex",
+            &["--acp".to_string()],
+            "/work/app",
+        );
+        assert_eq!(program, "podman");
+        assert_eq!(
+            args,
+            vec![
+                "run", "--rm", "-i", "--init", "--name", "acptorio-agent-1", "--mount",
+                "type=bind,source=/work/app,target=/work/app", "-w", "/work/app", "--mount",
+                "type=bind,source=/opt/agents/codex,target=/opt/acptorio/bin/codex,readonly",
+                "--memory", "2048m", "--cpus", "1.5", "-e", "ANTHROPIC_API_KEY", "node:22",
+                "/opt/acptorio/bin/codex", "--acp",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_line_mounts_paths_with_colons_and_commas() {
+        let sandbox = ContainerSandbox {
+            runtime: ContainerRuntime::Docker,
+            image: "node:22".to_string(),
+            env: Vec::new(),
+        };
+        let container = AgentContainer {
+            runtime: sandbox.runtime,
+            name: "acptorio-agent-2".to_string(),
+        };
+        let (_, args) = sandbox.command_line(&container, None, "codex", &[], r"C:\Users\me\app");
+        let mount = r"type=bind,source=C:\Users\me\app,target=C:\Users\me\app";
+        assert_eq!(args[6..10], ["--mount", mount, "-w", r"C:\Users\me\app"]);
+        assert_eq!(
+            bind_mount("/work/a,b", "/work/a,b", true),
+            r#"type=bind,"source=/work/a,b","target=/work/a,b",readonly"#
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Agents saved when the app quits, so the next launch can offer to bring
+//! them back: through session/load where the agent supports it, or else a
+//! fresh session seeded with a summary of what the agent did.
+
+use super::process::AgentInfo;
+use super::tasks::TaskInfo;
+use crate::clock::now_secs;
+use crate::state::{app_data_dir, storage_writable};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const SAVED_SESSIONS_FILE: &str = "saved_sessions.json";
+/// Latest prompts of an agent the summary covers
+const SUMMARY_TASKS: usize = 5;
+/// Prompts and results are cut to this many characters in the summary
+const SUMMARY_TEXT_CHARS: usize = 400;
+
+/// An agent as it was when the app quit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub id: String,
+    /// Agent ids don't survive restarts, names do
+    pub agent_name: String,
+    pub provider_id: Option<String>,
+    pub working_directory: String,
+    pub session_id: Option<String>,
+    /// What the agent was asked and answered lately
+    pub summary: String,
+    pub saved_at: u64,
+}
+
+/// How a saved agent was brought back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResumeMethod {
+    /// The agent loaded its saved session via `session/load`
+    SessionLoad,
+    /// A fresh session was seeded with the saved summary
+    SeedPrompt,
+    /// A fresh session, as there was nothing to seed it with
+    FreshSession,
+}
+
+impl SavedSession {
+    /// Save an agent with its prompt history, oldest first
+    pub fn new(info: &AgentInfo, tasks: &[TaskInfo]) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            agent_name: info.name.clone(),
+            provider_id: info.provider_id.clone(),
+            working_directory: info.working_directory.clone(),
+            session_id: info.session_id.clone(),
+            summary: session_summary(tasks),
+            saved_at: now_secs(),
+        }
+    }
+}
+
+fn cut(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() > SUMMARY_TEXT_CHARS {
+        let start: String = text.chars().take(SUMMARY_TEXT_CHARS).collect();
+        format!("{}…", start)
+    } else {
+        text.to_string()
+    }
+}
+
+/// The latest prompts and the start of their results, for seeding a fresh
+/// session; empty without any
+pub fn session_summary(tasks: &[TaskInfo]) -> String {
+    let start = tasks.len().saturating_sub(SUMMARY_TASKS);
+    tasks[start..]
+        .iter()
+        .map(|task| match task.result.as_deref().or(task.error.as_deref()) {
+            Some(result) => format!("User: {}\nAgent: {}", cut(&task.prompt), cut(result)),
+            None => format!("User: {}", cut(&task.prompt)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// First prompt of a fresh session continuing a saved one
+pub fn resume_seed_prompt(summary: &str) -> String {
+    format!(
+        "This session continues one from an earlier run of the app. \
+The latest exchanges of that session:\n\n{}\n\nAcknowledge briefly and wait for the next instruction.",
+        summary
+    )
+}
+
+pub struct SavedSessions {
+    /// Saved by the last run and not resumed or dismissed yet
+    resumable: Mutex<Vec<SavedSession>>,
+    storage_path: PathBuf,
+}
+
+impl SavedSessions {
+    pub fn new() -> Self {
+        Self::at(app_data_dir().join(SAVED_SESSIONS_FILE))
+    }
+
+    pub fn at(storage_path: PathBuf) -> Self {
+        let resumable = Self::load_from_file(&storage_path).unwrap_or_default();
+        Self {
+            resumable: Mutex::new(resumable),
+            storage_path,
+        }
+    }
+
+    fn load_from_file(path: &Path) -> Option<Vec<SavedSession>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Write the agents running now, keeping those of the last run that
+    /// were neither resumed nor dismissed
+    pub fn save(&self, sessions: Vec<SavedSession>) {
+        let all: Vec<SavedSession> = sessions
+            .into_iter()
+            .chain(self.resumable.lock().unwrap().iter().cloned())
+            .collect();
+        if !storage_writable() {
+            return;
+        }
+        let written = serde_json::to_string_pretty(&all)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.storage_path, content).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write saved sessions: {}", e);
+        }
+    }
+
+    /// Agents of the last run, newest first
+    pub fn resumable(&self) -> Vec<SavedSession> {
+        let mut resumable = self.resumable.lock().unwrap().clone();
+        resumable.sort_by_key(|session| Reverse(session.saved_at));
+        resumable
+    }
+
+    /// Forget a saved agent, e.g. once it was resumed
+    pub fn dismiss(&self, id: &str) -> Option<SavedSession> {
+        let mut resumable = self.resumable.lock().unwrap();
+        let index = resumable.iter().position(|s| s.id == id)?;
+        Some(resumable.remove(index))
+    }
+}
+
+impl Default for SavedSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_sessions_are_resumable_after_reopening() {
+        let path = std::env::temp_dir().join(format!("acptorio-saved-{}.json", Uuid::new_v4()));
+        let info = AgentInfo {
+            name: "Builder".to_string(),
+            working_directory: "/work/app".to_string(),
+            session_id: Some("s1".to_string()),
+            ..Default::default()
+        };
+        let saved = SavedSession {
+            summary: "User: fix the build\nAgent: Fixed.".to_string(),
+            ..SavedSession::new(&info, &[])
+        };
+        assert!(SavedSession::new(&info, &[]).summary.is_empty());
+
+        let store = SavedSessions::at(path.clone());
+        assert!(store.resumable().is_empty());
+        store.save(vec![saved.clone()]);
+
+        let reopened = SavedSessions::at(path.clone());
+        let resumable = reopened.resumable();
+        assert_eq!(resumable.len(), 1);
+        assert_eq!(resumable[0].session_id.as_deref(), Some("s1"));
+        assert!(resume_seed_prompt(&resumable[0].summary).contains("fix the build"));
+
+        // Kept for the run after if neither resumed nor dismissed
+        reopened.save(Vec::new());
+        let again = SavedSessions::at(path.clone());
+        assert_eq!(again.resumable().len(), 1);
+        assert!(again.dismiss(&saved.id).is_some());
+        again.save(Vec::new());
+        assert!(SavedSessions::at(path.clone()).resumable().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! Dispatch order of prompts across the pool. Each agent runs one prompt at
+//! a time; with a concurrency limit, waiting prompts are picked by their
+//! priority and then the configured policy, so one busy project cannot
+//! starve the others.
+
+use crate::clock::now_secs;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Priority of a single prompt. A higher priority prompt goes ahead of all
+/// waiting prompts of lower priority but never interrupts a running one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingMode {
+    /// Oldest prompt first
+    #[default]
+    Fifo,
+    /// Take turns between working directories
+    RoundRobinProjects,
+    /// Take turns between agents
+    RoundRobinAgents,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchedulingPolicy {
+    #[serde(default)]
+    pub mode: SchedulingMode,
+    /// Most prompts running at once across all agents; none for no limit
+    #[serde(default)]
+    pub max_concurrent: Option<usize>,
+    /// Priority of placed agents; higher goes first, the default is 0
+    #[serde(default)]
+    pub priorities: HashMap<Uuid, i32>,
+}
+
+/// A prompt waiting for or holding its turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedPrompt {
+    pub seq: u64,
+    pub agent_id: Uuid,
+    /// Working directory of the agent
+    pub project: String,
+    pub priority: PromptPriority,
+    /// Task the prompt belongs to, if it runs as one
+    pub task_id: Option<String>,
+    pub queued_at: u64,
+}
+
+/// Prompts of the pool, each list in dispatch order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolQueue {
+    pub running: Vec<QueuedPrompt>,
+    pub waiting: Vec<QueuedPrompt>,
+    pub max_concurrent: Option<usize>,
+}
+
+struct Waiter {
+    prompt: QueuedPrompt,
+    ready: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct Queue {
+    waiting: Vec<Waiter>,
+    /// Prompts running, by agent
+    running: HashMap<Uuid, QueuedPrompt>,
+    next_seq: u64,
+    dispatches: u64,
+    /// Dispatch count when a project or agent was last served
+    last_served: HashMap<String, u64>,
+}
+
+pub struct PromptScheduler {
+    queue: Mutex<Queue>,
+    policy: RwLock<SchedulingPolicy>,
+}
+
+/// A prompt's turn to run; the next prompt is dispatched when it drops
+pub struct PromptPermit<'a> {
+    scheduler: &'a PromptScheduler,
+    seq: u64,
+    agent_id: Uuid,
+}
+
+impl Drop for PromptPermit<'_> {
+    fn drop(&mut self) {
+        self.scheduler.release(self.seq, self.agent_id);
+    }
+}
+
+impl PromptScheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: Mutex::new(Queue::default()),
+            policy: RwLock::new(SchedulingPolicy::default()),
+        }
+    }
+
+    pub fn set_policy(&self, policy: SchedulingPolicy) {
+        *self.policy.write().unwrap() = policy;
+        // A higher limit may let waiting prompts run now
+        self.dispatch(&mut self.queue.lock().unwrap());
+    }
+
+    /// Wait until a prompt of `agent_id`, working in `project`, may run
+    pub async fn acquire(
+        &self,
+        agent_id: Uuid,
+        project: &str,
+        priority: PromptPriority,
+        task_id: Option<&str>,
+    ) -> PromptPermit<'_> {
+        let (ready, turn) = oneshot::channel();
+        let permit = {
+            let mut queue = self.queue.lock().unwrap();
+            let seq = queue.next_seq;
+            queue.next_seq += 1;
+            let queued_at = now_secs();
+            queue.waiting.push(Waiter {
+                prompt: QueuedPrompt {
+                    seq,
+                    agent_id,
+                    project: project.to_string(),
+                    priority,
+                    task_id: task_id.map(String::from),
+                    queued_at,
+                },
+                ready,
+            });
+            self.dispatch(&mut queue);
+            // Created before waiting so a cancelled caller leaves the queue
+            PromptPermit {
+                scheduler: self,
+                seq,
+                agent_id,
+            }
+        };
+        let _ = turn.await;
+        permit
+    }
+
+    /// Snapshot of running and waiting prompts
+    pub fn snapshot(&self) -> PoolQueue {
+        let queue = self.queue.lock().unwrap();
+        let policy = self.policy.read().unwrap();
+        let mut running: Vec<QueuedPrompt> = queue.running.values().cloned().collect();
+        running.sort_by_key(|p| p.seq);
+        let mut waiting: Vec<&QueuedPrompt> = queue.waiting.iter().map(|w| &w.prompt).collect();
+        waiting.sort_by_key(|p| rank(&queue, &policy, p));
+        PoolQueue {
+            running,
+            waiting: waiting.into_iter().cloned().collect(),
+            max_concurrent: policy.max_concurrent,
+        }
+    }
+
+    fn release(&self, seq: u64, agent_id: Uuid) {
+        let mut queue = self.queue.lock().unwrap();
+        match queue.waiting.iter().position(|w| w.prompt.seq == seq) {
+            Some(index) => {
+                queue.waiting.remove(index);
+            }
+            None => {
+                queue.running.remove(&agent_id);
+                self.dispatch(&mut queue);
+            }
+        }
+    }
+
+    fn dispatch(&self, queue: &mut Queue) {
+        let policy = self.policy.read().unwrap();
+        let limit = policy.max_concurrent.unwrap_or(usize::MAX).max(1);
+        while queue.running.len() < limit {
+            let Some(index) = pick(queue, &policy) else {
+                break;
+            };
+            let waiter = queue.waiting.remove(index);
+            queue.dispatches += 1;
+            let dispatches = queue.dispatches;
+            queue.last_served.insert(served_key(&policy, &waiter.prompt), dispatches);
+            queue.running.insert(waiter.prompt.agent_id, waiter.prompt);
+            let _ = waiter.ready.send(());
+        }
+    }
+}
+
+impl Default for PromptScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn served_key(policy: &SchedulingPolicy, prompt: &QueuedPrompt) -> String {
+    match policy.mode {
+        SchedulingMode::RoundRobinAgents => prompt.agent_id.to_string(),
+        _ => prompt.project.clone(),
+    }
+}
+
+/// Sort key of a waiting prompt: prompt priority, then agent priority, then
+/// the project or agent served longest ago, then the oldest
+fn rank(
+    queue: &Queue,
+    policy: &SchedulingPolicy,
+    prompt: &QueuedPrompt,
+) -> (Reverse<PromptPriority>, Reverse<i32>, u64, u64) {
+    let agent_priority = policy.priorities.get(&prompt.agent_id).copied().unwrap_or(0);
+    let served = match policy.mode {
+        SchedulingMode::Fifo => 0,
+        _ => queue.last_served.get(&served_key(policy, prompt)).copied().unwrap_or(0),
+    };
+    (Reverse(prompt.priority), Reverse(agent_priority), served, prompt.seq)
+}
+
+/// The waiter to run next among those whose agent is not busy
+fn pick(queue: &Queue, policy: &SchedulingPolicy) -> Option<usize> {
+    queue
+        .waiting
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| !queue.running.contains_key(&w.prompt.agent_id))
+        .min_by_key(|(_, w)| rank(queue, policy, &w.prompt))
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Queue prompts of (agent, project) behind a running one and return
+    /// the order they ran in
+    async fn run_order(
+        policy: SchedulingPolicy,
+        prompts: Vec<(Uuid, &'static str, PromptPriority)>,
+    ) -> Vec<usize> {
+        let scheduler = Arc::new(PromptScheduler::new());
+        scheduler.set_policy(policy);
+        let blocker = scheduler
+            .acquire(Uuid::new_v4(), "/blocker", PromptPriority::Normal, None)
+            .await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (index, (agent_id, project, priority)) in prompts.into_iter().enumerate() {
+            let (scheduler, order) = (scheduler.clone(), order.clone());
+            handles.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire(agent_id, project, priority, None).await;
+                order.lock().unwrap().push(index);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }));
+            // Queue in a known order
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        drop(blocker);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let order = order.lock().unwrap().clone();
+        order
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_across_projects() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let normal = PromptPriority::Normal;
+        let prompts = vec![(a, "/busy", normal), (b, "/busy", normal), (a, "/busy", normal), (c, "/quiet", normal)];
+        let policy = SchedulingPolicy {
+            max_concurrent: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(run_order(policy.clone(), prompts.clone()).await, vec![0, 1, 2, 3]);
+
+        let round_robin = SchedulingPolicy {
+            mode: SchedulingMode::RoundRobinProjects,
+            ..policy
+        };
+        assert_eq!(run_order(round_robin, prompts).await, vec![0, 3, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_priorities_go_first() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let policy = SchedulingPolicy {
+            max_concurrent: Some(1),
+            priorities: HashMap::from([(b, 5)]),
+            ..Default::default()
+        };
+        let prompts = vec![
+            (a, "/p", PromptPriority::Normal),
+            (b, "/p", PromptPriority::Normal),
+            (c, "/p", PromptPriority::High),
+            (b, "/p", PromptPriority::Low),
+        ];
+        assert_eq!(run_order(policy, prompts).await, vec![2, 1, 0, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_lists_waiting_prompts_in_dispatch_order() {
+        let scheduler = Arc::new(PromptScheduler::new());
+        scheduler.set_policy(SchedulingPolicy {
+            max_concurrent: Some(1),
+            ..Default::default()
+        });
+        let running = scheduler
+            .acquire(Uuid::new_v4(), "/p", PromptPriority::Normal, Some("t1"))
+            .await;
+        for (task_id, priority) in [("t2", PromptPriority::Low), ("t3", PromptPriority::High)] {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move {
+                let _permit = scheduler.acquire(Uuid::new_v4(), "/p", priority, Some(task_id)).await;
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let queue = scheduler.snapshot();
+        assert_eq!(queue.running[0].task_id.as_deref(), Some("t1"));
+        let waiting: Vec<_> = queue.waiting.iter().filter_map(|p| p.task_id.as_deref()).collect();
+        assert_eq!(waiting, vec!["t3", "t2"]);
+        drop(running);
+    }
+}
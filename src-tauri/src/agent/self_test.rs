@@ -0,0 +1,308 @@
+//! End-to-end check of the agent pipeline against an in-process scripted
+//! agent: spawn, initialize, session, prompt, tool call, permission, cancel
+//! and stop, each reported on its own. Needs no network, Node or API keys,
+//! so it serves both as a diagnostic for users and as a smoke test.
+
+use super::pool::PendingPermissions;
+use super::process::{AgentProcess, AgentStatus, AgentUpdate, PermissionUserResponse};
+use crate::acp::scripted::{ScriptStep, ScriptedAgent, ScriptedAgentHandle};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const STAGE_TIMEOUT: Duration = Duration::from_secs(10);
+const PROMPT: &str = "self-test: read the readme";
+const READ_CALL_ID: &str = "self-test-read";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageOutcome {
+    Passed,
+    Failed,
+    /// An earlier stage failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestStage {
+    pub name: String,
+    pub outcome: StageOutcome,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub stages: Vec<SelfTestStage>,
+    pub duration_ms: u64,
+}
+
+#[derive(Default)]
+struct Run {
+    stages: Vec<SelfTestStage>,
+    failed: bool,
+}
+
+impl Run {
+    /// Run a stage unless an earlier one failed
+    async fn stage<T>(
+        &mut self,
+        name: &str,
+        stage: impl Future<Output = Result<T, String>>,
+    ) -> Option<T> {
+        if self.failed {
+            self.skip(name);
+            return None;
+        }
+        let started = Instant::now();
+        let result = tokio::time::timeout(STAGE_TIMEOUT, stage)
+            .await
+            .unwrap_or_else(|_| Err(format!("Timed out after {}s", STAGE_TIMEOUT.as_secs())));
+        self.failed = result.is_err();
+        self.stages.push(SelfTestStage {
+            name: name.to_string(),
+            outcome: match result {
+                Ok(_) => StageOutcome::Passed,
+                Err(_) => StageOutcome::Failed,
+            },
+            duration_ms: started.elapsed().as_millis() as u64,
+            error: result.as_ref().err().cloned(),
+        });
+        result.ok()
+    }
+
+    fn skip(&mut self, name: &str) {
+        self.stages.push(SelfTestStage {
+            name: name.to_string(),
+            outcome: StageOutcome::Skipped,
+            duration_ms: 0,
+            error: None,
+        });
+    }
+
+    fn finish(self, started: Instant) -> SelfTestReport {
+        SelfTestReport {
+            passed: !self.failed,
+            stages: self.stages,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// Run every stage in order against a fresh scripted agent; the agent never
+/// touches its working directory
+pub async fn self_test() -> SelfTestReport {
+    let started = Instant::now();
+    let mut run = Run::default();
+    let working_directory = std::env::temp_dir()
+        .join("acptorio-self-test")
+        .display()
+        .to_string();
+
+    let spawned = run
+        .stage("spawn", async {
+            let (stream, handle) = self_test_script(&working_directory).connect();
+            let agent =
+                AgentProcess::connect("self-test".to_string(), working_directory.clone(), stream);
+            Ok((agent, handle))
+        })
+        .await;
+    let Some((mut agent, handle)) = spawned else {
+        for name in [
+            "initialize",
+            "session",
+            "prompt",
+            "tool_call",
+            "permission",
+            "cancel",
+            "stop",
+        ] {
+            run.skip(name);
+        }
+        return run.finish(started);
+    };
+    let pending = Arc::new(PendingPermissions::new());
+    let (tx, mut rx) = mpsc::channel::<AgentUpdate>(256);
+
+    run.stage("initialize", async {
+        agent.initialize().await.map_err(|e| e.to_string())
+    })
+    .await;
+    run.stage("session", async {
+        let session_id = agent.create_session().await.map_err(|e| e.to_string())?;
+        if session_id.is_empty() {
+            return Err("The agent returned an empty session id".to_string());
+        }
+        Ok(())
+    })
+    .await;
+    run.stage("prompt", async {
+        let text = agent
+            .send_prompt(PROMPT, tx.clone(), pending.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        if !text.contains(PROMPT) {
+            return Err(format!("Unexpected answer: {:?}", text));
+        }
+        Ok(())
+    })
+    .await;
+    run.stage("tool_call", async {
+        let mut forwarded = false;
+        while let Ok(update) = rx.try_recv() {
+            forwarded |= update.update_type.starts_with("tool_call");
+        }
+        if !forwarded {
+            return Err("No tool call update was forwarded".to_string());
+        }
+        match agent.tool_calls.get(READ_CALL_ID) {
+            Some(call) if call.status.as_deref() == Some("completed") => Ok(()),
+            Some(call) => Err(format!("The tool call ended as {:?}", call.status)),
+            None => Err("The tool call was not recorded".to_string()),
+        }
+    })
+    .await;
+    run.stage("permission", async {
+        let id = agent.id;
+        let (answer, approved) = tokio::join!(
+            agent.send_prompt("self-test: edit the readme", tx.clone(), pending.clone()),
+            async {
+                let request = wait_for_permission(&pending).await;
+                let response = PermissionUserResponse {
+                    approved: true,
+                    option_id: None,
+                };
+                pending
+                    .respond(id, &request, response)
+                    .map_err(|e| e.to_string())
+            }
+        );
+        approved?;
+        answer.map_err(|e| e.to_string())?;
+        match last_permission_outcome(&handle) {
+            Some(outcome) if outcome["outcome"] == "selected" && outcome["optionId"] == "allow" => {
+                Ok(())
+            }
+            outcome => Err(format!("The agent received {:?}", outcome)),
+        }
+    })
+    .await;
+    run.stage("cancel", async {
+        let id = agent.id;
+        let (answer, _) = tokio::join!(
+            agent.send_prompt("self-test: edit it again", tx.clone(), pending.clone()),
+            async {
+                let request = wait_for_permission(&pending).await;
+                // Dropping the answer channel cancels the request
+                pending.remove(id, &request);
+            }
+        );
+        if answer.is_ok() {
+            return Err(
+                "The prompt went on after its permission request was cancelled".to_string(),
+            );
+        }
+        // The cancellation reaches the agent after the prompt gave up
+        while last_permission_outcome(&handle).is_none_or(|o| o["outcome"] != "cancelled") {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        Ok(())
+    })
+    .await;
+    run.stage("stop", async {
+        agent.stop().await.map_err(|e| e.to_string())?;
+        match agent.status {
+            AgentStatus::Stopped => Ok(()),
+            status => Err(format!("The agent is {:?} after stopping", status)),
+        }
+    })
+    .await;
+    run.finish(started)
+}
+
+/// Input id of the first request waiting for an answer
+async fn wait_for_permission(pending: &PendingPermissions) -> String {
+    loop {
+        if let Some(request) = pending.list().into_iter().next() {
+            return request.input_id;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// Outcome of the last permission answer the agent received
+fn last_permission_outcome(handle: &ScriptedAgentHandle) -> Option<Value> {
+    handle
+        .received()
+        .into_iter()
+        .rev()
+        .find_map(|m| m.pointer("/result/outcome").cloned())
+}
+
+/// A turn with a tool call, then two asking for permission to edit
+fn self_test_script(working_directory: &str) -> ScriptedAgent {
+    let readme = format!("{}/README.md", working_directory.trim_end_matches('/'));
+    let tool_call = |id: &str, kind: &str, status: &str| {
+        ScriptStep::Update(json!({
+            "sessionUpdate": "tool_call",
+            "toolCallId": id,
+            "title": format!("{} README.md", kind),
+            "kind": kind,
+            "status": status,
+            "locations": [{ "path": readme }],
+            "rawInput": { "file_path": readme },
+        }))
+    };
+    let edit_turn = || {
+        vec![
+            tool_call("self-test-edit", "edit", "pending"),
+            ScriptStep::RequestPermission(json!({
+                "toolCall": {
+                    "toolCallId": "self-test-edit",
+                    "title": "edit README.md",
+                    "kind": "edit",
+                },
+                "options": [
+                    { "optionId": "allow", "name": "Allow", "kind": "allow_once" },
+                    { "optionId": "reject", "name": "Reject", "kind": "reject_once" },
+                ],
+            })),
+            ScriptStep::message("Edited."),
+        ]
+    };
+
+    ScriptedAgent::new()
+        .session_id("self-test-session")
+        .turn(vec![
+            ScriptStep::EchoPrompt,
+            tool_call(READ_CALL_ID, "read", "in_progress"),
+            ScriptStep::Update(json!({
+                "sessionUpdate": "tool_call_update",
+                "toolCallId": READ_CALL_ID,
+                "status": "completed",
+            })),
+        ])
+        .turn(edit_turn())
+        .turn(edit_turn())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_self_test_passes() {
+        let report = self_test().await;
+        let failed: Vec<&SelfTestStage> = report
+            .stages
+            .iter()
+            .filter(|s| s.outcome != StageOutcome::Passed)
+            .collect();
+        assert!(report.passed, "{:?}", failed);
+        assert_eq!(report.stages.len(), 8);
+    }
+}
@@ -0,0 +1,131 @@
+//! Incremental agent state changes. Every agent publishes its info on a
+//! watch channel; the pool reads the latest value without locking the agent
+//! and turns each change into an [`AgentStateChange`] for the frontend.
+
+use super::process::{AgentInfo, AgentStatus, PendingInput};
+use crate::acp::{SessionModeState, SessionModelState};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+use uuid::Uuid;
+
+/// State changes of all agents, forwarded to the frontend
+pub static AGENT_STATE_CHANGES: Lazy<broadcast::Sender<AgentStateChange>> =
+    Lazy::new(|| broadcast::channel(256).0);
+
+/// Tokens and cost agents reported, recorded in the usage metrics
+pub static AGENT_USAGE: Lazy<broadcast::Sender<AgentUsage>> =
+    Lazy::new(|| broadcast::channel(256).0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AgentUsage {
+    pub agent_id: Uuid,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_cents: u64,
+}
+
+/// Fields of an agent that changed; absent fields did not change
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentStateChange {
+    pub agent_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<AgentStatus>,
+    /// `null` when the agent moved off its file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_file: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_used: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_inputs: Option<Vec<PendingInput>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub needs_auth: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available_commands: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modes: Option<Option<SessionModeState>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<Option<SessionModelState>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_prompt_at: Option<Option<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_update_at: Option<Option<u64>>,
+}
+
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+    (old != new).then(|| new.clone())
+}
+
+impl AgentStateChange {
+    /// What changed from `old` to `new`, or None if nothing tracked did
+    pub fn between(old: &AgentInfo, new: &AgentInfo) -> Option<Self> {
+        let change = Self {
+            agent_id: new.id,
+            status: changed(&old.status, &new.status),
+            current_file: changed(&old.current_file, &new.current_file),
+            progress: changed(&old.progress, &new.progress),
+            tokens_used: changed(&old.tokens_used, &new.tokens_used),
+            pending_inputs: changed(&old.pending_inputs, &new.pending_inputs),
+            session_id: changed(&old.session_id, &new.session_id),
+            needs_auth: changed(&old.needs_auth, &new.needs_auth),
+            available_commands: changed(&old.available_commands, &new.available_commands),
+            modes: changed(&old.modes, &new.modes),
+            models: changed(&old.models, &new.models),
+            last_prompt_at: changed(&old.last_prompt_at, &new.last_prompt_at),
+            last_update_at: changed(&old.last_update_at, &new.last_update_at),
+        };
+        let unchanged = Self {
+            agent_id: new.id,
+            ..Default::default()
+        };
+        (change != unchanged).then_some(change)
+    }
+}
+
+/// Publish the changes of an agent on [`AGENT_STATE_CHANGES`] until the
+/// agent is dropped
+pub fn forward_state_changes(mut state: watch::Receiver<AgentInfo>) {
+    tokio::spawn(async move {
+        let mut last = state.borrow_and_update().clone();
+        while state.changed().await.is_ok() {
+            let info = state.borrow_and_update().clone();
+            if let Some(change) = AgentStateChange::between(&last, &info) {
+                // No subscribers is fine
+                let _ = AGENT_STATE_CHANGES.send(change);
+            }
+            last = info;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_change_holds_only_changed_fields() {
+        let old = AgentInfo {
+            id: Uuid::new_v4(),
+            status: AgentStatus::Working,
+            current_file: Some("/p/main.rs".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(AgentStateChange::between(&old, &old.clone()), None);
+
+        let new = AgentInfo {
+            status: AgentStatus::Idle,
+            current_file: None,
+            ..old.clone()
+        };
+        let change = AgentStateChange::between(&old, &new).unwrap();
+        assert_eq!(
+            serde_json::to_value(&change).unwrap(),
+            json!({ "agent_id": old.id, "status": "idle", "current_file": null })
+        );
+    }
+}
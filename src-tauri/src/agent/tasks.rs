@@ -0,0 +1,648 @@
+//! Task graph for prompts dispatched to agents.
+//!
+//! A task is a single prompt sent to a single agent. Tasks may depend on
+//! other tasks: a dependent task only starts once all of its dependencies
+//! completed successfully, and is cancelled if any of them fails.
+
+use super::context::PackedContext;
+use super::review::TaskReview;
+use super::scheduler::PromptPriority;
+use super::verification::TaskVerification;
+use crate::clock::now_secs;
+use crate::git::ChangeSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Finished tasks kept in the graph; older ones live on in the task history
+const MAX_FINISHED_TASKS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// Waiting for dependencies to complete
+    Waiting,
+    Running,
+    Completed,
+    Failed,
+    /// Never ran because a dependency failed or was cancelled
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled
+        )
+    }
+}
+
+/// What the caller asks for when dispatching a task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSpec {
+    pub agent_id: Uuid,
+    pub prompt: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Prepend the results of dependencies to the prompt
+    #[serde(default)]
+    pub inject_results: bool,
+    /// Project files attached to the prompt
+    #[serde(default)]
+    pub context: Option<PackedContext>,
+    #[serde(default)]
+    pub priority: PromptPriority,
+    /// Standing instructions sent as a separate block ahead of the prompt
+    #[serde(default)]
+    pub preamble: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub id: String,
+    /// Position in submission order
+    pub seq: u64,
+    pub agent_id: Uuid,
+    pub prompt: String,
+    pub depends_on: Vec<String>,
+    pub inject_results: bool,
+    /// Files whose contents were attached to the prompt
+    pub context_files: Vec<String>,
+    #[serde(skip)]
+    context_preamble: Option<String>,
+    pub status: TaskStatus,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// Why the agent ended its turn (end_turn, max_tokens, cancelled, ...)
+    pub stop_reason: Option<String>,
+    /// Files the task changed in the project repo, if it is one
+    pub change_summary: Option<ChangeSummary>,
+    /// Id of the task this task reviews
+    pub review_of: Option<String>,
+    /// Review of this task by a reviewer agent
+    pub review: Option<TaskReview>,
+    /// Id of the task whose failed verification this task follows up
+    pub follow_up_of: Option<String>,
+    /// Result of the project's verification command after this task
+    pub verification: Option<TaskVerification>,
+    /// Files the agent's tool calls pointed at while the task ran
+    #[serde(default)]
+    pub touched_files: Vec<String>,
+    /// Place in the pool's queue while the task waits for its turn
+    #[serde(default)]
+    pub priority: PromptPriority,
+    /// Standing instructions the prompt was sent with, kept apart from it
+    #[serde(default)]
+    pub preamble: Option<String>,
+}
+
+/// Emitted when a finished task's changes have been summarized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskChangeSummary {
+    pub task_id: String,
+    pub agent_id: Uuid,
+    pub summary: ChangeSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/// Serializable snapshot of the whole task graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskGraphState {
+    pub tasks: Vec<TaskInfo>,
+    pub edges: Vec<TaskEdge>,
+}
+
+pub struct TaskGraph {
+    tasks: Mutex<HashMap<String, TaskInfo>>,
+    next_seq: AtomicU64,
+    events: broadcast::Sender<TaskInfo>,
+    change_summaries: broadcast::Sender<TaskChangeSummary>,
+    reviews: broadcast::Sender<TaskInfo>,
+}
+
+impl TaskGraph {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(256);
+        let (change_summaries, _) = broadcast::channel(64);
+        let (reviews, _) = broadcast::channel(64);
+        Self {
+            tasks: Mutex::new(HashMap::new()),
+            next_seq: AtomicU64::new(0),
+            events,
+            change_summaries,
+            reviews,
+        }
+    }
+
+    /// Subscribe to task status changes
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskInfo> {
+        self.events.subscribe()
+    }
+
+    /// Subscribe to change summaries of finished tasks
+    pub fn subscribe_change_summaries(&self) -> broadcast::Receiver<TaskChangeSummary> {
+        self.change_summaries.subscribe()
+    }
+
+    /// Subscribe to reviews attached to tasks
+    pub fn subscribe_reviews(&self) -> broadcast::Receiver<TaskInfo> {
+        self.reviews.subscribe()
+    }
+
+    fn notify(&self, task: &TaskInfo) {
+        let _ = self.events.send(task.clone());
+    }
+
+    /// Add a task to the graph. Returns the task and whether it can start now.
+    ///
+    /// Dependencies must already exist, which also rules out cycles.
+    pub fn add(&self, spec: TaskSpec) -> Result<(TaskInfo, bool), String> {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        for dep in &spec.depends_on {
+            if !tasks.contains_key(dep) {
+                return Err(format!("Unknown dependency: {}", dep));
+            }
+        }
+
+        let dep_statuses: Vec<TaskStatus> = spec
+            .depends_on
+            .iter()
+            .map(|d| tasks[d].status)
+            .collect();
+        let blocked = dep_statuses
+            .iter()
+            .any(|s| matches!(s, TaskStatus::Failed | TaskStatus::Cancelled));
+        let ready = dep_statuses.iter().all(|s| *s == TaskStatus::Completed);
+
+        let (context_files, context_preamble) = match spec.context {
+            Some(context) => (context.included, Some(context.preamble)),
+            None => (Vec::new(), None),
+        };
+
+        let mut task = TaskInfo {
+            id: Uuid::new_v4().to_string(),
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+            agent_id: spec.agent_id,
+            prompt: spec.prompt,
+            depends_on: spec.depends_on,
+            inject_results: spec.inject_results,
+            context_files,
+            context_preamble,
+            status: TaskStatus::Waiting,
+            result: None,
+            error: None,
+            created_at: now_secs(),
+            started_at: None,
+            finished_at: None,
+            stop_reason: None,
+            change_summary: None,
+            review_of: None,
+            review: None,
+            follow_up_of: None,
+            verification: None,
+            touched_files: Vec::new(),
+            priority: spec.priority,
+            preamble: spec.preamble,
+        };
+
+        if blocked {
+            task.status = TaskStatus::Cancelled;
+            task.error = Some("A dependency did not complete successfully".to_string());
+            task.finished_at = Some(task.created_at);
+        }
+
+        tasks.insert(task.id.clone(), task.clone());
+        if blocked {
+            Self::prune(&mut tasks);
+        }
+        drop(tasks);
+
+        self.notify(&task);
+        Ok((task, ready && !blocked))
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<TaskInfo> {
+        self.tasks.lock().unwrap().get(task_id).cloned()
+    }
+
+    /// Mark a task as running and build the prompt to send, including
+    /// attached files and dependency results when requested.
+    pub fn start(&self, task_id: &str) -> Option<TaskInfo> {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        let prompt = {
+            let task = tasks.get(task_id)?;
+            if task.status != TaskStatus::Waiting {
+                return None;
+            }
+            let mut prompt = task.context_preamble.clone().unwrap_or_default();
+            if task.inject_results && !task.depends_on.is_empty() {
+                prompt.push_str("Results from prerequisite tasks:\n\n");
+                for dep in &task.depends_on {
+                    if let Some(dep_task) = tasks.get(dep) {
+                        prompt.push_str(&format!(
+                            "### {}\n{}\n\n",
+                            dep_task.prompt,
+                            dep_task.result.as_deref().unwrap_or("")
+                        ));
+                    }
+                }
+            }
+            prompt.push_str(&task.prompt);
+            prompt
+        };
+
+        let task = tasks.get_mut(task_id)?;
+        task.status = TaskStatus::Running;
+        task.started_at = Some(now_secs());
+        let mut started = task.clone();
+        drop(tasks);
+
+        self.notify(&started);
+        started.prompt = prompt;
+        Some(started)
+    }
+
+    pub fn set_stop_reason(&self, task_id: &str, stop_reason: Option<String>) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(task_id) {
+            task.stop_reason = stop_reason;
+        }
+    }
+
+    /// Attach the summary of what a task changed in the project
+    pub fn set_change_summary(&self, task_id: &str, summary: ChangeSummary) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let Some(task) = tasks.get_mut(task_id) else {
+            return;
+        };
+        task.change_summary = Some(summary.clone());
+        let event = TaskChangeSummary {
+            task_id: task.id.clone(),
+            agent_id: task.agent_id,
+            summary,
+        };
+        drop(tasks);
+
+        let _ = self.change_summaries.send(event);
+    }
+
+    /// Mark a task as the review of another task
+    pub fn set_review_of(&self, task_id: &str, reviewed_task_id: &str) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(task_id) {
+            task.review_of = Some(reviewed_task_id.to_string());
+        }
+    }
+
+    /// Attach the state of a review to the reviewed task
+    pub fn set_review(&self, task_id: &str, review: TaskReview) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let Some(task) = tasks.get_mut(task_id) else {
+            return;
+        };
+        task.review = Some(review);
+        let task = task.clone();
+        drop(tasks);
+
+        let _ = self.reviews.send(task);
+    }
+
+    /// Mark a task as the follow-up of a task that failed verification
+    pub fn set_follow_up_of(&self, task_id: &str, verified_task_id: &str) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(task_id) {
+            task.follow_up_of = Some(verified_task_id.to_string());
+        }
+    }
+
+    /// Attach the state of a verification to the verified task
+    pub fn set_verification(&self, task_id: &str, verification: TaskVerification) -> Option<TaskInfo> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let task = tasks.get_mut(task_id)?;
+        task.verification = Some(verification);
+        Some(task.clone())
+    }
+
+    /// Number of follow-ups between `task_id` and the task that started
+    /// the chain
+    pub fn follow_up_depth(&self, task_id: &str) -> usize {
+        let tasks = self.tasks.lock().unwrap();
+        let mut depth = 0;
+        let mut current = tasks.get(task_id).and_then(|t| t.follow_up_of.clone());
+        while let Some(id) = current {
+            depth += 1;
+            current = tasks.get(&id).and_then(|t| t.follow_up_of.clone());
+        }
+        depth
+    }
+
+    /// Record the outcome of a task. Returns the ids of dependents that became
+    /// ready to run. Dependents of a failed task are cancelled transitively.
+    pub fn finish(&self, task_id: &str, outcome: Result<String, String>) -> Vec<String> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let mut changed = Vec::new();
+
+        let succeeded = outcome.is_ok();
+        if let Some(task) = tasks.get_mut(task_id) {
+            match outcome {
+                Ok(result) => {
+                    task.status = TaskStatus::Completed;
+                    task.result = Some(result);
+                }
+                Err(e) => {
+                    task.status = TaskStatus::Failed;
+                    task.error = Some(e);
+                }
+            }
+            task.finished_at = Some(now_secs());
+            changed.push(task.clone());
+        } else {
+            return Vec::new();
+        }
+
+        let mut ready = Vec::new();
+        if succeeded {
+            for task in tasks.values() {
+                if task.status == TaskStatus::Waiting
+                    && task.depends_on.iter().any(|d| d == task_id)
+                    && task
+                        .depends_on
+                        .iter()
+                        .all(|d| tasks.get(d).map(|t| t.status) == Some(TaskStatus::Completed))
+                {
+                    ready.push(task.id.clone());
+                }
+            }
+        } else {
+            changed.extend(Self::cancel_dependents(&mut tasks, task_id));
+        }
+        Self::prune(&mut tasks);
+        drop(tasks);
+
+        for task in &changed {
+            self.notify(task);
+        }
+        ready
+    }
+
+    /// Drop the oldest finished tasks beyond [`MAX_FINISHED_TASKS`], except
+    /// those an unfinished task depends on
+    fn prune(tasks: &mut HashMap<String, TaskInfo>) {
+        let mut finished: Vec<(u64, String)> = tasks
+            .values()
+            .filter(|t| t.status.is_finished())
+            .map(|t| (t.seq, t.id.clone()))
+            .collect();
+        if finished.len() <= MAX_FINISHED_TASKS {
+            return;
+        }
+        let needed: HashSet<String> = tasks
+            .values()
+            .filter(|t| !t.status.is_finished())
+            .flat_map(|t| t.depends_on.iter().cloned())
+            .collect();
+        finished.sort();
+        let excess = finished.len() - MAX_FINISHED_TASKS;
+        for (_, id) in finished.into_iter().filter(|(_, id)| !needed.contains(id)).take(excess) {
+            tasks.remove(&id);
+        }
+    }
+
+    fn cancel_dependents(tasks: &mut HashMap<String, TaskInfo>, root: &str) -> Vec<TaskInfo> {
+        let mut cancelled = Vec::new();
+        let mut stack = vec![root.to_string()];
+
+        while let Some(current) = stack.pop() {
+            let dependents: Vec<String> = tasks
+                .values()
+                .filter(|t| t.status == TaskStatus::Waiting && t.depends_on.contains(&current))
+                .map(|t| t.id.clone())
+                .collect();
+
+            for id in dependents {
+                if let Some(task) = tasks.get_mut(&id) {
+                    task.status = TaskStatus::Cancelled;
+                    task.error = Some(format!("Dependency {} did not complete", current));
+                    task.finished_at = Some(now_secs());
+                    cancelled.push(task.clone());
+                }
+                stack.push(id);
+            }
+        }
+
+        cancelled
+    }
+
+    /// Add a file to the touched files of the agent's running task
+    pub fn record_touch(&self, agent_id: &Uuid, path: &str) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let running = tasks
+            .values_mut()
+            .find(|t| t.agent_id == *agent_id && t.status == TaskStatus::Running);
+        if let Some(task) = running {
+            if !task.touched_files.iter().any(|p| p == path) {
+                task.touched_files.push(path.to_string());
+            }
+        }
+    }
+
+    /// Tasks sent to an agent, oldest first
+    pub fn tasks_for_agent(&self, agent_id: &Uuid) -> Vec<TaskInfo> {
+        let tasks = self.tasks.lock().unwrap();
+        let mut list: Vec<TaskInfo> = tasks
+            .values()
+            .filter(|t| t.agent_id == *agent_id)
+            .cloned()
+            .collect();
+        list.sort_by_key(|t| t.seq);
+        list
+    }
+
+    pub fn snapshot(&self) -> TaskGraphState {
+        let tasks = self.tasks.lock().unwrap();
+        let mut list: Vec<TaskInfo> = tasks.values().cloned().collect();
+        list.sort_by_key(|t| t.seq);
+
+        let edges = list
+            .iter()
+            .flat_map(|t| {
+                t.depends_on.iter().map(move |d| TaskEdge {
+                    from: d.clone(),
+                    to: t.id.clone(),
+                })
+            })
+            .collect();
+
+        TaskGraphState { tasks: list, edges }
+    }
+}
+
+impl Default for TaskGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(prompt: &str, depends_on: Vec<String>) -> TaskSpec {
+        TaskSpec {
+            agent_id: Uuid::nil(),
+            prompt: prompt.to_string(),
+            depends_on,
+            inject_results: true,
+            context: None,
+            priority: PromptPriority::Normal,
+            preamble: None,
+        }
+    }
+
+    #[test]
+    fn test_task_without_dependencies_is_ready() {
+        let graph = TaskGraph::new();
+        let (task, ready) = graph.add(spec("a", vec![])).unwrap();
+        assert!(ready);
+        assert_eq!(task.status, TaskStatus::Waiting);
+    }
+
+    #[test]
+    fn test_unknown_dependency_rejected() {
+        let graph = TaskGraph::new();
+        assert!(graph.add(spec("b", vec!["missing".to_string()])).is_err());
+    }
+
+    #[test]
+    fn test_dependent_ready_after_completion_with_injected_result() {
+        let graph = TaskGraph::new();
+        let (a, _) = graph.add(spec("design the API", vec![])).unwrap();
+        let (b, ready) = graph.add(spec("implement it", vec![a.id.clone()])).unwrap();
+        assert!(!ready);
+
+        graph.start(&a.id).unwrap();
+        let ready = graph.finish(&a.id, Ok("use REST".to_string()));
+        assert_eq!(ready, vec![b.id.clone()]);
+
+        let started = graph.start(&b.id).unwrap();
+        assert!(started.prompt.contains("use REST"));
+        assert!(started.prompt.ends_with("implement it"));
+        // The stored prompt stays as submitted
+        assert_eq!(graph.get(&b.id).unwrap().prompt, "implement it");
+    }
+
+    #[test]
+    fn test_failure_cancels_transitive_dependents() {
+        let graph = TaskGraph::new();
+        let (a, _) = graph.add(spec("a", vec![])).unwrap();
+        let (b, _) = graph.add(spec("b", vec![a.id.clone()])).unwrap();
+        let (c, _) = graph.add(spec("c", vec![b.id.clone()])).unwrap();
+
+        graph.start(&a.id).unwrap();
+        let ready = graph.finish(&a.id, Err("boom".to_string()));
+        assert!(ready.is_empty());
+
+        assert_eq!(graph.get(&a.id).unwrap().status, TaskStatus::Failed);
+        assert_eq!(graph.get(&b.id).unwrap().status, TaskStatus::Cancelled);
+        assert_eq!(graph.get(&c.id).unwrap().status, TaskStatus::Cancelled);
+
+        // New tasks depending on a failed task are cancelled immediately
+        let (d, ready) = graph.add(spec("d", vec![a.id.clone()])).unwrap();
+        assert!(!ready);
+        assert_eq!(d.status, TaskStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_waits_for_all_dependencies() {
+        let graph = TaskGraph::new();
+        let (a, _) = graph.add(spec("a", vec![])).unwrap();
+        let (b, _) = graph.add(spec("b", vec![])).unwrap();
+        let (c, _) = graph.add(spec("c", vec![a.id.clone(), b.id.clone()])).unwrap();
+
+        graph.start(&a.id).unwrap();
+        assert!(graph.finish(&a.id, Ok(String::new())).is_empty());
+        graph.start(&b.id).unwrap();
+        assert_eq!(graph.finish(&b.id, Ok(String::new())), vec![c.id.clone()]);
+
+        assert_eq!(graph.snapshot().edges.len(), 2);
+    }
+
+    #[test]
+    fn test_old_finished_tasks_are_pruned_unless_depended_on() {
+        let graph = TaskGraph::new();
+        let (kept, _) = graph.add(spec("kept", vec![])).unwrap();
+        graph.start(&kept.id).unwrap();
+        graph.finish(&kept.id, Ok(String::new()));
+        let (waiting, _) = graph.add(spec("waiting", vec![kept.id.clone()])).unwrap();
+        let (dropped, _) = graph.add(spec("dropped", vec![])).unwrap();
+        graph.start(&dropped.id).unwrap();
+        graph.finish(&dropped.id, Ok(String::new()));
+
+        for i in 0..MAX_FINISHED_TASKS {
+            let (task, _) = graph.add(spec(&i.to_string(), vec![])).unwrap();
+            graph.start(&task.id).unwrap();
+            graph.finish(&task.id, Ok(String::new()));
+        }
+
+        assert!(graph.get(&dropped.id).is_none());
+        assert!(graph.get(&kept.id).is_some());
+        assert_eq!(graph.get(&waiting.id).unwrap().status, TaskStatus::Waiting);
+        assert_eq!(graph.tasks_for_agent(&Uuid::nil()).len(), MAX_FINISHED_TASKS + 1);
+    }
+
+    #[test]
+    fn test_tasks_for_agent() {
+        let graph = TaskGraph::new();
+        graph.add(spec("first", vec![])).unwrap();
+        graph.add(spec("second", vec![])).unwrap();
+        let mut other = spec("other agent", vec![]);
+        other.agent_id = Uuid::new_v4();
+        graph.add(other).unwrap();
+
+        let history = graph.tasks_for_agent(&Uuid::nil());
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].prompt, "first");
+        assert!(history.iter().all(|t| t.agent_id.is_nil()));
+    }
+
+    #[test]
+    fn test_context_preamble_prepended_but_not_stored() {
+        let graph = TaskGraph::new();
+        let mut with_context = spec("explain main", vec![]);
+        with_context.context = Some(PackedContext {
+            preamble: "### main.rs\nfn main() {}\n\n".to_string(),
+            included: vec!["main.rs".to_string()],
+            skipped: Vec::new(),
+        });
+        let (task, _) = graph.add(with_context).unwrap();
+        assert_eq!(task.context_files, vec!["main.rs".to_string()]);
+
+        let started = graph.start(&task.id).unwrap();
+        assert!(started.prompt.starts_with("### main.rs"));
+        assert!(started.prompt.ends_with("explain main"));
+        assert_eq!(graph.get(&task.id).unwrap().prompt, "explain main");
+    }
+
+    #[test]
+    fn test_follow_up_depth_walks_the_chain() {
+        let graph = TaskGraph::new();
+        let (a, _) = graph.add(spec("a", vec![])).unwrap();
+        let (b, _) = graph.add(spec("fix a", vec![])).unwrap();
+        let (c, _) = graph.add(spec("fix b", vec![])).unwrap();
+        graph.set_follow_up_of(&b.id, &a.id);
+        graph.set_follow_up_of(&c.id, &b.id);
+        assert_eq!(graph.follow_up_depth(&a.id), 0);
+        assert_eq!(graph.follow_up_depth(&c.id), 2);
+    }
+}
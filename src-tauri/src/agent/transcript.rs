@@ -0,0 +1,309 @@
+//! Answers of agents streamed to a file per prompt, so a long answer neither
+//! piles up in memory nor reaches the frontend as one giant payload.
+
+use crate::state::app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const TRANSCRIPT_DIR: &str = "transcripts";
+/// Answer text kept in memory; past it only the end of the answer is kept
+const MEMORY_LIMIT_BYTES: usize = 1024 * 1024;
+/// End of the answer the prompt commands return along with the transcript
+const TAIL_BYTES: usize = 16 * 1024;
+/// Largest chunk [`read_transcript_chunk`] returns
+pub const MAX_CHUNK_BYTES: usize = 256 * 1024;
+
+/// Transcript of a prompt's answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptInfo {
+    pub id: String,
+    /// Bytes of answer text
+    pub length: u64,
+}
+
+/// What the prompt commands return: the end of the answer and where to page
+/// through the rest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptReply {
+    /// None if the agent answered nothing
+    pub transcript_id: Option<String>,
+    pub length: u64,
+    pub tail: String,
+    /// Whether `tail` is only the end of the answer
+    pub truncated: bool,
+}
+
+impl PromptReply {
+    pub fn new(answer: &str, transcript: Option<TranscriptInfo>) -> Self {
+        let tail = tail(answer, TAIL_BYTES);
+        let length = transcript.as_ref().map_or(answer.len() as u64, |t| t.length);
+        Self {
+            transcript_id: transcript.filter(|t| t.length > 0).map(|t| t.id),
+            length,
+            tail: tail.to_string(),
+            truncated: (tail.len() as u64) < length,
+        }
+    }
+}
+
+/// A page of a transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptChunk {
+    pub transcript_id: String,
+    pub offset: u64,
+    pub text: String,
+    /// Offset of the next chunk
+    pub next_offset: u64,
+    pub eof: bool,
+}
+
+pub fn transcript_dir() -> PathBuf {
+    app_data_dir().join(TRANSCRIPT_DIR)
+}
+
+/// Remove transcripts of an earlier run
+pub fn clear_transcripts() {
+    let dir = transcript_dir();
+    if dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            tracing::warn!("Failed to remove {}: {}", dir.display(), e);
+        }
+    }
+}
+
+/// The last `max_bytes` of `text` or a little less, at a char boundary
+fn tail(text: &str, max_bytes: usize) -> &str {
+    let mut start = text.len().saturating_sub(max_bytes);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+/// Collects the answer of one prompt, writing it to the transcript as it
+/// arrives
+pub struct TranscriptWriter {
+    info: TranscriptInfo,
+    dir: PathBuf,
+    /// Opened on the first text; None after writing failed
+    file: Option<File>,
+    failed: bool,
+    text: String,
+    /// Whether the start of the answer was dropped from `text`
+    cut: bool,
+}
+
+impl TranscriptWriter {
+    pub fn new() -> Self {
+        Self::in_dir(transcript_dir())
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self {
+            info: TranscriptInfo {
+                id: Uuid::new_v4().to_string(),
+                length: 0,
+            },
+            dir,
+            file: None,
+            failed: false,
+            text: String::new(),
+            cut: false,
+        }
+    }
+
+    /// Continue an earlier transcript, for a follow-up on the same session;
+    /// only the new answer is kept in memory
+    pub fn resume(info: TranscriptInfo) -> Self {
+        Self::resume_in(transcript_dir(), info)
+    }
+
+    fn resume_in(dir: PathBuf, info: TranscriptInfo) -> Self {
+        Self {
+            info,
+            ..Self::in_dir(dir)
+        }
+    }
+
+    pub fn push(&mut self, text: &str) {
+        self.write(text);
+        self.info.length += text.len() as u64;
+        self.text.push_str(text);
+        if self.text.len() > MEMORY_LIMIT_BYTES {
+            self.text = tail(&self.text, MEMORY_LIMIT_BYTES / 2).to_string();
+            self.cut = true;
+        }
+    }
+
+    /// Write text to the transcript that is not part of the answer
+    pub fn note(&mut self, text: &str) {
+        self.write(text);
+        self.info.length += text.len() as u64;
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.failed {
+            return;
+        }
+        if self.file.is_none() {
+            let path = self.dir.join(format!("{}.txt", self.info.id));
+            let opened = fs::create_dir_all(&self.dir)
+                .and_then(|_| OpenOptions::new().create(true).append(true).open(&path));
+            match opened {
+                Ok(file) => self.file = Some(file),
+                Err(e) => {
+                    tracing::warn!("Failed to create transcript {}: {}", path.display(), e);
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+        if let Some(Err(e)) = self.file.as_mut().map(|f| f.write_all(text.as_bytes())) {
+            tracing::warn!("Failed to write transcript {}: {}", self.info.id, e);
+            self.file = None;
+            self.failed = true;
+        }
+    }
+
+    pub fn info(&self) -> TranscriptInfo {
+        self.info.clone()
+    }
+
+    /// The answer, or its end if it outgrew memory
+    pub fn into_text(self) -> String {
+        if self.cut {
+            format!(
+                "[... {} bytes more in transcript {}]\n{}",
+                self.info.length - self.text.len() as u64,
+                self.info.id,
+                self.text
+            )
+        } else {
+            self.text
+        }
+    }
+}
+
+impl Default for TranscriptWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Up to `limit` bytes of a transcript from `offset`, ending at a char
+/// boundary
+pub fn read_transcript_chunk(
+    id: &str,
+    offset: u64,
+    limit: usize,
+) -> Result<TranscriptChunk, String> {
+    read_chunk_in(&transcript_dir(), id, offset, limit)
+}
+
+fn read_chunk_in(
+    dir: &Path,
+    id: &str,
+    offset: u64,
+    limit: usize,
+) -> Result<TranscriptChunk, String> {
+    // Ids are uuids; anything else could point outside the directory
+    let id = Uuid::parse_str(id).map_err(|_| format!("Invalid transcript id: {}", id))?;
+    let path = dir.join(format!("{}.txt", id));
+    let mut file = File::open(&path).map_err(|e| format!("Transcript {} not found: {}", id, e))?;
+    let length = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let offset = offset.min(length);
+    file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::new();
+    // A chunk holds at least one char of up to four bytes
+    file.take(limit.clamp(4, MAX_CHUNK_BYTES) as u64)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    let (text, consumed) = match String::from_utf8(bytes) {
+        Ok(text) => {
+            let consumed = text.len();
+            (text, consumed)
+        }
+        // A char split by the limit is left for the next chunk
+        Err(e) if e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            (String::from_utf8(bytes).unwrap_or_default(), valid)
+        }
+        Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), e.as_bytes().len()),
+    };
+
+    let next_offset = offset + consumed as u64;
+    Ok(TranscriptChunk {
+        transcript_id: id.to_string(),
+        offset,
+        text,
+        next_offset,
+        eof: next_offset >= length,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_pages_at_char_boundaries() {
+        let dir = std::env::temp_dir().join(format!("acptorio-transcripts-{}", Uuid::new_v4()));
+        let mut writer = TranscriptWriter::in_dir(dir.clone());
+        writer.push("héllo ");
+        writer.push("wörld");
+        let info = writer.info();
+        assert_eq!(writer.into_text(), "héllo wörld");
+
+        let mut text = String::new();
+        let mut offset = 0;
+        loop {
+            let chunk = read_chunk_in(&dir, &info.id, offset, 4).unwrap();
+            text.push_str(&chunk.text);
+            offset = chunk.next_offset;
+            if chunk.eof {
+                break;
+            }
+        }
+        assert_eq!(text, "héllo wörld");
+        assert_eq!(offset, info.length);
+
+        // The "ö" split by the limit is left for the next chunk
+        let chunk = read_chunk_in(&dir, &info.id, 5, 4).unwrap();
+        assert_eq!((chunk.text.as_str(), chunk.next_offset), ("o w", 8));
+        assert!(read_chunk_in(&dir, "../secrets", 0, 10).is_err());
+
+        let reply = PromptReply::new(&"x".repeat(TAIL_BYTES + 1), None);
+        assert!(reply.truncated);
+        assert_eq!(reply.tail.len(), TAIL_BYTES);
+        assert!(!PromptReply::new("héllo wörld", Some(info)).truncated);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resumed_transcript_appends() {
+        let dir = std::env::temp_dir().join(format!("acptorio-transcripts-{}", Uuid::new_v4()));
+        let mut writer = TranscriptWriter::in_dir(dir.clone());
+        writer.push("first");
+        let info = writer.info();
+
+        let mut writer = TranscriptWriter::resume_in(dir.clone(), info.clone());
+        writer.note("\n> more\n");
+        writer.push("second");
+        let resumed = writer.info();
+        assert_eq!(resumed.id, info.id);
+        assert_eq!(writer.into_text(), "second");
+
+        let chunk = read_chunk_in(&dir, &resumed.id, 0, MAX_CHUNK_BYTES).unwrap();
+        assert_eq!(chunk.text, "first\n> more\nsecond");
+        assert_eq!(chunk.next_offset, resumed.length);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
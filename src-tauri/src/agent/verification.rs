@@ -0,0 +1,86 @@
+//! Post-task verification: when a task changes files in a project that has
+//! a verification command, the command runs and its result is attached to
+//! the task. On failure the agent can be sent the output to fix it.
+
+use crate::runner::ProjectCommandRun;
+use serde::{Deserialize, Serialize};
+
+/// Follow-up prompts sent in a row for one original task
+pub const MAX_FOLLOW_UPS: usize = 2;
+
+/// Failure output longer than this is cut from the front of the prompt
+const MAX_FOLLOW_UP_OUTPUT_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Running,
+    Passed,
+    Failed,
+}
+
+/// Verification attached to the verified task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskVerification {
+    pub status: VerificationStatus,
+    pub command: String,
+    pub run: Option<ProjectCommandRun>,
+    /// Task sent to the agent to fix the failure
+    pub follow_up_task_id: Option<String>,
+}
+
+/// Prompt asking the agent to fix what made verification fail
+pub fn build_follow_up_prompt(run: &ProjectCommandRun) -> String {
+    format!(
+        "Verification of your last change failed: {}\n\nFix the cause of the failure.",
+        describe_failed_run(run)
+    )
+}
+
+/// The command, its exit code and the end of its output
+pub fn describe_failed_run(run: &ProjectCommandRun) -> String {
+    let output = &run.output_tail;
+    let output = if output.len() > MAX_FOLLOW_UP_OUTPUT_BYTES {
+        // Keep the end, where failures are usually summarized
+        let mut start = output.len() - MAX_FOLLOW_UP_OUTPUT_BYTES;
+        while !output.is_char_boundary(start) {
+            start += 1;
+        }
+        format!("...\n{}", &output[start..])
+    } else {
+        output.clone()
+    };
+    let exit = match run.exit_code {
+        Some(code) => format!("exit code {}", code),
+        None => "no exit code".to_string(),
+    };
+    format!("`{}` finished with {}.\n\nOutput:\n```\n{}\n```", run.command, exit, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ProjectCommandKind;
+
+    #[test]
+    fn test_follow_up_prompt_keeps_end_of_long_output() {
+        let run = ProjectCommandRun {
+            run_id: "r".into(),
+            project_id: "p".into(),
+            kind: ProjectCommandKind::Test,
+            command: "cargo test".into(),
+            success: false,
+            exit_code: Some(101),
+            output_tail: format!("{}test result: FAILED", "x".repeat(MAX_FOLLOW_UP_OUTPUT_BYTES)),
+            started_at: 0,
+            finished_at: 0,
+            after_task: None,
+            agent_id: None,
+        };
+        let prompt = build_follow_up_prompt(&run);
+        assert!(prompt.contains("`cargo test` finished with exit code 101"));
+        assert!(prompt.contains("...\nxxx"));
+        assert!(prompt.contains("test result: FAILED\n```"));
+        assert!(prompt.len() < MAX_FOLLOW_UP_OUTPUT_BYTES + 200);
+    }
+}
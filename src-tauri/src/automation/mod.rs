@@ -0,0 +1,11 @@
+//! Prompts dispatched without the user: standing orders of placed agents and
+//! per-project rules triggered by file changes. Also suggests where the
+//! factory could use another agent.
+
+pub mod standing_orders;
+pub mod suggestions;
+pub mod triggers;
+
+pub use standing_orders::*;
+pub use suggestions::*;
+pub use triggers::*;
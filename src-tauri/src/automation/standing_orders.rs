@@ -0,0 +1,245 @@
+//! Standing orders: prompts sent to placed agents whenever a command of their
+//! project fails or someone else changes files in their working directory.
+
+use crate::agent::{describe_failed_run, PackedContext, PromptPriority, TaskSpec};
+use crate::clock::now_secs;
+use crate::commands::spawn_update_forwarder;
+use crate::events::TrackedEmitter;
+use crate::filesystem::{current_filter, FileEvent, FileEventKind, FILE_EVENTS};
+use crate::runner::{ProjectCommandRun, FINISHED_RUNS};
+use crate::state::{AgentPlacement, AppState, StandingOrder, StandingOrderTrigger};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// What standing orders fire on
+#[derive(Debug, Clone)]
+pub enum AutomationEvent {
+    CommandFinished(ProjectCommandRun),
+    FilesChanged(FileEvent),
+}
+
+impl AutomationEvent {
+    /// Context sent along with the standing prompt
+    fn describe(&self, placement: &AgentPlacement) -> String {
+        match self {
+            AutomationEvent::CommandFinished(run) => {
+                format!("The project's {} command failed: {}", run.kind, describe_failed_run(run))
+            }
+            AutomationEvent::FilesChanged(event) => {
+                let paths = changed_paths(placement, event);
+                format!(
+                    "Files in your working directory changed ({:?}):\n{}",
+                    event.kind,
+                    paths.iter().map(|p| format!("- {}", p)).collect::<Vec<_>>().join("\n")
+                )
+            }
+        }
+    }
+}
+
+/// Paths of `event` in the agent's working directory, outside ignored
+/// directories
+fn changed_paths<'a>(placement: &AgentPlacement, event: &'a FileEvent) -> Vec<&'a str> {
+    let Some(working_directory) = &placement.working_directory else {
+        return Vec::new();
+    };
+    let filter = current_filter();
+    event
+        .paths
+        .iter()
+        .filter(|path| {
+            Path::new(path)
+                .strip_prefix(working_directory)
+                .is_ok_and(|relative| !filter.ignores_path(relative))
+        })
+        .map(|p| p.as_str())
+        .collect()
+}
+
+/// Whether `order` of the agent at `placement` fires on `event`. Agents do
+/// not fire on their own changes or on verification of their own tasks,
+/// which has its own follow-up.
+pub fn order_fires(placement: &AgentPlacement, order: &StandingOrder, event: &AutomationEvent) -> bool {
+    let agent_id = Uuid::parse_str(&placement.agent_id).ok();
+    match (&order.trigger, event) {
+        (StandingOrderTrigger::CommandFailed { command }, AutomationEvent::CommandFinished(run)) => {
+            !run.success
+                && placement.connected_project_id.as_deref() == Some(run.project_id.as_str())
+                && command.is_none_or(|kind| kind == run.kind)
+                && (run.agent_id.is_none() || run.agent_id != agent_id)
+        }
+        (StandingOrderTrigger::FilesChanged, AutomationEvent::FilesChanged(event)) => {
+            !matches!(event.kind, FileEventKind::Other)
+                && (event.agent_id.is_none() || event.agent_id != agent_id)
+                && !changed_paths(placement, event).is_empty()
+        }
+        _ => false,
+    }
+}
+
+/// Lets a key through at most once per interval
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    last: HashMap<String, u64>,
+}
+
+impl RateLimiter {
+    pub fn allow(&mut self, key: &str, min_interval_secs: u64, now: u64) -> bool {
+        if let Some(last) = self.last.get(key) {
+            if now < last + min_interval_secs {
+                return false;
+            }
+        }
+        self.last.insert(key.to_string(), now);
+        true
+    }
+}
+
+/// Dispatch the standing prompt of every placed agent whose order fires on
+/// `event`
+async fn dispatch_standing_orders(
+    app_handle: &AppHandle,
+    state: &Arc<AppState>,
+    limiter: &mut RateLimiter,
+    event: &AutomationEvent,
+) {
+    let layout = state.factory.get_layout().await;
+    for placement in &layout.agent_placements {
+        let Some(order) = &placement.standing_order else {
+            continue;
+        };
+        let Ok(agent_id) = Uuid::parse_str(&placement.agent_id) else {
+            continue;
+        };
+        if state.agent_pool.get_agent_info(&agent_id).is_none()
+            || !order_fires(placement, order, event)
+            || !limiter.allow(&placement.agent_id, order.min_interval_secs, now_secs())
+        {
+            continue;
+        }
+
+        let spec = TaskSpec {
+            agent_id,
+            prompt: order.prompt.clone(),
+            depends_on: Vec::new(),
+            inject_results: false,
+            context: Some(PackedContext {
+                preamble: format!("{}\n\n", event.describe(placement)),
+                ..Default::default()
+            }),
+            priority: PromptPriority::Normal,
+            preamble: state.prompt_preamble(&agent_id).await,
+        };
+        let tx = spawn_update_forwarder(app_handle.clone(), state.clone());
+        match state.agent_pool.submit_task(spec, tx) {
+            Ok(task) => {
+                let _ = app_handle.emit_agent_event("standing-order-dispatched", agent_id, &task);
+            }
+            Err(e) => tracing::warn!("Failed to dispatch standing order of {}: {}", agent_id, e),
+        }
+    }
+}
+
+/// Listen for failed project commands and file changes and dispatch the
+/// standing orders they fire
+pub fn start_standing_orders(app_handle: AppHandle) {
+    let mut runs = FINISHED_RUNS.subscribe();
+    let mut files = FILE_EVENTS.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<Arc<AppState>>().inner().clone();
+        let mut limiter = RateLimiter::default();
+        loop {
+            let event = tokio::select! {
+                run = runs.recv() => run.map(AutomationEvent::CommandFinished),
+                file_event = files.recv() => file_event.map(AutomationEvent::FilesChanged),
+            };
+            match event {
+                Ok(event) => dispatch_standing_orders(&app_handle, &state, &mut limiter, &event).await,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ProjectCommandKind;
+
+    fn placement(order: StandingOrderTrigger) -> (AgentPlacement, StandingOrder) {
+        let order = StandingOrder {
+            prompt: "Investigate".to_string(),
+            trigger: order,
+            min_interval_secs: 60,
+        };
+        let placement = AgentPlacement {
+            agent_id: Uuid::new_v4().to_string(),
+            grid_x: 0,
+            grid_y: 0,
+            connected_project_id: Some("p1".to_string()),
+            name: None,
+            working_directory: Some("/repo".to_string()),
+            provider_id: None,
+            standing_order: Some(order.clone()),
+            stats: Default::default(),
+        };
+        (placement, order)
+    }
+
+    fn run(success: bool, kind: ProjectCommandKind) -> AutomationEvent {
+        AutomationEvent::CommandFinished(ProjectCommandRun {
+            run_id: "r".into(),
+            project_id: "p1".into(),
+            kind,
+            command: "cargo test".into(),
+            success,
+            exit_code: Some(101),
+            output_tail: String::new(),
+            started_at: 0,
+            finished_at: 0,
+            after_task: None,
+            agent_id: None,
+        })
+    }
+
+    fn changed(path: &str, agent_id: Option<Uuid>) -> AutomationEvent {
+        AutomationEvent::FilesChanged(FileEvent {
+            kind: FileEventKind::Modify,
+            paths: vec![path.to_string()],
+            agent_id,
+            attributions: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_order_fires_on_matching_events_only() {
+        let (tests, order) = placement(StandingOrderTrigger::CommandFailed {
+            command: Some(ProjectCommandKind::Test),
+        });
+        assert!(order_fires(&tests, &order, &run(false, ProjectCommandKind::Test)));
+        assert!(!order_fires(&tests, &order, &run(true, ProjectCommandKind::Test)));
+        assert!(!order_fires(&tests, &order, &run(false, ProjectCommandKind::Lint)));
+        assert!(!order_fires(&tests, &order, &changed("/repo/src/main.rs", None)));
+
+        let (files, order) = placement(StandingOrderTrigger::FilesChanged);
+        let own_id = Uuid::parse_str(&files.agent_id).ok();
+        assert!(order_fires(&files, &order, &changed("/repo/src/main.rs", None)));
+        assert!(!order_fires(&files, &order, &changed("/repo/src/main.rs", own_id)));
+        assert!(!order_fires(&files, &order, &changed("/repo/target/debug/app", None)));
+        assert!(!order_fires(&files, &order, &changed("/elsewhere/main.rs", None)));
+    }
+
+    #[test]
+    fn test_rate_limiter_waits_out_the_interval() {
+        let mut limiter = RateLimiter::default();
+        assert!(limiter.allow("a", 60, 1000));
+        assert!(!limiter.allow("a", 60, 1059));
+        assert!(limiter.allow("b", 60, 1059));
+        assert!(limiter.allow("a", 60, 1060));
+    }
+}
@@ -0,0 +1,273 @@
+//! Placement suggestions: factory projects someone keeps editing by hand
+//! while no agent works on them are offered as places for a new agent.
+
+use crate::clock::now_secs;
+use crate::filesystem::{FileEventKind, FILE_EVENTS};
+use crate::messages::{LocalizedMessage, MessageKey};
+use crate::state::{AlertKind, AppState, FactoryLayout};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::RecvError;
+
+/// Only activity this recent counts
+const SUGGESTION_WINDOW_SECS: u64 = 2 * 3600;
+/// Manual edits within the window that make a project worth an agent
+const MIN_MANUAL_EDITS: usize = 10;
+/// Manual edits remembered per project
+const MAX_EDITS_KEPT: usize = 200;
+/// How often suggestions are refreshed and raised as alerts
+const SUGGESTION_SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default)]
+struct ActivityLog {
+    manual_edits: VecDeque<u64>,
+    last_agent_edit: Option<u64>,
+}
+
+/// Who changed files of each factory project lately, as seen by the watcher
+pub struct ProjectActivity {
+    projects: Mutex<HashMap<String, ActivityLog>>,
+}
+
+impl ProjectActivity {
+    pub fn new() -> Self {
+        Self {
+            projects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a change of a project's files, by an agent or by hand
+    pub fn record(&self, project_id: &str, by_agent: bool, at: u64) {
+        let mut projects = self.projects.lock().unwrap();
+        let log = projects.entry(project_id.to_string()).or_default();
+        if by_agent {
+            log.last_agent_edit = Some(at);
+        } else {
+            if log.manual_edits.len() >= MAX_EDITS_KEPT {
+                log.manual_edits.pop_front();
+            }
+            log.manual_edits.push_back(at);
+        }
+    }
+
+    pub fn forget(&self, project_id: &str) {
+        self.projects.lock().unwrap().remove(project_id);
+    }
+
+    /// Manual edits since `since` and the last agent edit of a project
+    fn recent(&self, project_id: &str, since: u64) -> (Vec<u64>, Option<u64>) {
+        let projects = self.projects.lock().unwrap();
+        match projects.get(project_id) {
+            Some(log) => (
+                log.manual_edits.iter().copied().filter(|at| *at >= since).collect(),
+                log.last_agent_edit,
+            ),
+            None => (Vec::new(), None),
+        }
+    }
+}
+
+impl Default for ProjectActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A project that could use an agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementSuggestion {
+    pub project_id: String,
+    pub project_name: String,
+    /// Manual edits within the suggestion window
+    pub manual_edits: usize,
+    pub last_manual_edit: u64,
+    /// Share of the project's files agents explored, if its size is known
+    pub explored_percent: Option<f64>,
+    pub message: String,
+    pub localized: LocalizedMessage,
+}
+
+/// Projects with many recent manual edits, no recent agent edits and no
+/// agent connected. `explored` counts explored files per project id.
+pub fn suggest_placements(
+    layout: &FactoryLayout,
+    activity: &ProjectActivity,
+    explored: &HashMap<String, usize>,
+    now: u64,
+) -> Vec<PlacementSuggestion> {
+    let since = now.saturating_sub(SUGGESTION_WINDOW_SECS);
+    let mut suggestions: Vec<PlacementSuggestion> = layout
+        .projects
+        .iter()
+        .filter(|project| {
+            !layout
+                .agent_placements
+                .iter()
+                .any(|p| p.connected_project_id.as_deref() == Some(project.id.as_str()))
+        })
+        .filter_map(|project| {
+            let (edits, last_agent_edit) = activity.recent(&project.id, since);
+            if edits.len() < MIN_MANUAL_EDITS || last_agent_edit.is_some_and(|at| at >= since) {
+                return None;
+            }
+            let explored_percent = project.file_count.filter(|count| *count > 0).map(|count| {
+                let explored = explored.get(&project.id).copied().unwrap_or(0);
+                (explored as f64 / count as f64 * 100.0).min(100.0)
+            });
+            let localized = match explored_percent {
+                Some(percent) => LocalizedMessage::new(MessageKey::PlacementSuggestedExplored)
+                    .with("percent", format!("{:.0}", percent)),
+                None => LocalizedMessage::new(MessageKey::PlacementSuggested),
+            }
+            .with("project", &project.name)
+            .with("edits", edits.len());
+            Some(PlacementSuggestion {
+                project_id: project.id.clone(),
+                project_name: project.name.clone(),
+                manual_edits: edits.len(),
+                last_manual_edit: edits.iter().copied().max().unwrap_or(now),
+                explored_percent,
+                message: localized.render(),
+                localized,
+            })
+        })
+        .collect();
+    suggestions.sort_by_key(|s| Reverse(s.manual_edits));
+    suggestions
+}
+
+/// Current suggestions from the recorded activity and the fog of war
+pub async fn placement_suggestions(state: &AppState) -> Vec<PlacementSuggestion> {
+    let layout = state.factory.get_layout().await;
+    let mut explored: HashMap<String, usize> = HashMap::new();
+    for path in state.fog.explored_paths() {
+        if let Some(project) = layout.project_containing(&path) {
+            *explored.entry(project.id.clone()).or_default() += 1;
+        }
+    }
+    suggest_placements(&layout, &state.project_activity, &explored, now_secs())
+}
+
+/// Record who changes the factory projects' files and raise an info alert
+/// for each project that could use an agent
+pub fn start_placement_suggestions(app_handle: AppHandle) {
+    let mut files = FILE_EVENTS.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<Arc<AppState>>().inner().clone();
+        let mut interval = tokio::time::interval(SUGGESTION_SWEEP_INTERVAL);
+        let mut suggested: HashSet<String> = HashSet::new();
+        loop {
+            tokio::select! {
+                event = files.recv() => match event {
+                    Ok(event) => {
+                        if matches!(event.kind, FileEventKind::Other) {
+                            continue;
+                        }
+                        let layout = state.factory.get_layout().await;
+                        let projects: HashSet<String> = event
+                            .paths
+                            .iter()
+                            .filter_map(|p| layout.project_containing(p))
+                            .map(|p| p.id.clone())
+                            .collect();
+                        for project_id in projects {
+                            let by_agent = event.agent_id.is_some();
+                            state.project_activity.record(&project_id, by_agent, now_secs());
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = interval.tick() => {
+                    let suggestions = placement_suggestions(&state).await;
+                    let current: HashSet<String> =
+                        suggestions.iter().map(|s| s.project_id.clone()).collect();
+                    for suggestion in suggestions {
+                        // Raised once per stretch of manual work, not every sweep
+                        if suggested.insert(suggestion.project_id.clone()) {
+                            state.alerts.raise(
+                                AlertKind::PlacementSuggestion,
+                                None,
+                                Some(suggestion.project_id),
+                                suggestion.localized,
+                            );
+                        }
+                    }
+                    for project_id in suggested.difference(&current) {
+                        state.alerts.resolve_entity(AlertKind::PlacementSuggestion, project_id);
+                    }
+                    suggested = current;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AgentPlacement, ProjectNode};
+
+    fn project(id: &str, file_count: Option<u32>) -> ProjectNode {
+        ProjectNode {
+            id: id.to_string(),
+            path: format!("/work/{}", id),
+            name: id.to_string(),
+            grid_x: 0,
+            grid_y: 0,
+            file_count,
+            color_index: None,
+            summary: None,
+        }
+    }
+
+    #[test]
+    fn test_suggests_busy_projects_without_agents() {
+        let now = 100_000;
+        let layout = FactoryLayout {
+            projects: vec![
+                project("manual", Some(40)),
+                project("agent", None),
+                project("connected", None),
+                project("quiet", None),
+            ],
+            agent_placements: vec![AgentPlacement {
+                agent_id: "a".to_string(),
+                grid_x: 0,
+                grid_y: 0,
+                connected_project_id: Some("connected".to_string()),
+                name: None,
+                working_directory: None,
+                provider_id: None,
+                standing_order: None,
+                stats: Default::default(),
+            }],
+            ..Default::default()
+        };
+        let activity = ProjectActivity::new();
+        for i in 0..MIN_MANUAL_EDITS as u64 {
+            for id in ["manual", "agent", "connected"] {
+                activity.record(id, false, now - i);
+            }
+            // Too old to count
+            activity.record("quiet", false, now - SUGGESTION_WINDOW_SECS - 1);
+        }
+        activity.record("agent", true, now - 60);
+        let explored = HashMap::from([("manual".to_string(), 10)]);
+
+        let suggestions = suggest_placements(&layout, &activity, &explored, now);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].project_id, "manual");
+        assert_eq!(suggestions[0].manual_edits, MIN_MANUAL_EDITS);
+        assert_eq!(suggestions[0].last_manual_edit, now);
+        assert_eq!(suggestions[0].explored_percent, Some(25.0));
+
+        let containing = layout.project_containing("/work/manual/src/lib.rs");
+        assert_eq!(containing.map(|p| p.id.as_str()), Some("manual"));
+        assert!(layout.project_containing("/elsewhere/lib.rs").is_none());
+    }
+}
@@ -0,0 +1,277 @@
+//! Rules that prompt an agent when files of a project change, e.g. "when
+//! anything under migrations/ changes, have the reviewer look at it".
+//! Watcher events are collected until the project has been quiet for a
+//! moment, then every enabled rule is matched against the whole batch.
+
+use crate::agent::{pack_files, PromptPriority, TaskSpec};
+use crate::clock::now_secs;
+use crate::commands::{resolve_agent, spawn_update_forwarder};
+use crate::events::TrackedEmitter;
+use crate::filesystem::{current_filter, FileEvent, FileEventKind, FILE_EVENTS};
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Quiet time after the last change before a batch is evaluated
+const DEBOUNCE: Duration = Duration::from_secs(2);
+/// Longest a batch waits while changes keep coming
+const MAX_BATCH_WAIT: Duration = Duration::from_secs(10);
+/// Executions kept in the history
+const HISTORY_CAPACITY: usize = 200;
+
+/// Prompt an agent when files under a path of a project change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriggerRule {
+    pub id: String,
+    /// Project the rule watches
+    pub project_path: String,
+    /// Path relative to the project the changed files must be under; the
+    /// whole project when empty
+    #[serde(default)]
+    pub path_prefix: String,
+    /// Id or name of the agent to prompt
+    pub agent: String,
+    pub prompt: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl TriggerRule {
+    /// Changed files the rule fires on, relative to its project. Changes the
+    /// rule's own agent made do not count.
+    pub fn matching_paths(&self, changes: &ChangeBatch, agent_id: Option<Uuid>) -> Vec<String> {
+        let filter = current_filter();
+        changes
+            .paths
+            .iter()
+            .filter(|(_, changed_by)| changed_by.is_none() || **changed_by != agent_id)
+            .filter_map(|(path, _)| {
+                let relative = Path::new(path).strip_prefix(&self.project_path).ok()?;
+                (relative.starts_with(&self.path_prefix) && !filter.ignores_path(relative))
+                    .then(|| relative.to_string_lossy().to_string())
+            })
+            .collect()
+    }
+}
+
+/// Changed paths of a debounced batch, with the agent each change is
+/// attributed to
+#[derive(Debug, Clone, Default)]
+pub struct ChangeBatch {
+    paths: BTreeMap<String, Option<Uuid>>,
+}
+
+impl ChangeBatch {
+    pub fn add(&mut self, event: &FileEvent) {
+        if matches!(event.kind, FileEventKind::Other) {
+            return;
+        }
+        for path in &event.paths {
+            let changed_by = event
+                .attributions
+                .iter()
+                .find(|a| &a.path == path)
+                .map(|a| a.agent_id)
+                .or(event.agent_id);
+            self.paths.insert(path.clone(), changed_by);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+/// One firing of a rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerExecution {
+    pub rule_id: String,
+    pub fired_at: u64,
+    /// Changed files, relative to the rule's project
+    pub paths: Vec<String>,
+    pub agent_id: Option<Uuid>,
+    pub task_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The most recent rule executions, oldest first
+pub struct TriggerHistory {
+    executions: Mutex<VecDeque<TriggerExecution>>,
+}
+
+impl TriggerHistory {
+    pub fn new() -> Self {
+        Self {
+            executions: Mutex::new(VecDeque::with_capacity(HISTORY_CAPACITY)),
+        }
+    }
+
+    pub fn record(&self, execution: TriggerExecution) {
+        let mut executions = self.executions.lock().unwrap();
+        if executions.len() == HISTORY_CAPACITY {
+            executions.pop_front();
+        }
+        executions.push_back(execution);
+    }
+
+    /// Executions of one rule, or of all rules
+    pub fn list(&self, rule_id: Option<&str>) -> Vec<TriggerExecution> {
+        self.executions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| rule_id.is_none_or(|id| e.rule_id == id))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for TriggerHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Prompt the agent of every enabled rule that matches the batch
+async fn fire_rules(app_handle: &AppHandle, state: &Arc<AppState>, changes: &ChangeBatch) {
+    for rule in state.settings.get().trigger_rules.iter().filter(|r| r.enabled) {
+        let agent_id = resolve_agent(state, &rule.agent);
+        let paths = rule.matching_paths(changes, agent_id);
+        if paths.is_empty() {
+            continue;
+        }
+
+        let mut execution = TriggerExecution {
+            rule_id: rule.id.clone(),
+            fired_at: now_secs(),
+            paths,
+            agent_id,
+            task_id: None,
+            error: None,
+        };
+        match agent_id {
+            Some(agent_id) => {
+                let mut context = pack_files(Path::new(&rule.project_path), &execution.paths).await;
+                context.preamble = format!(
+                    "Changed files in {}:\n{}\n\n{}",
+                    rule.project_path,
+                    execution.paths.iter().map(|p| format!("- {}", p)).collect::<Vec<_>>().join("\n"),
+                    context.preamble
+                );
+                let spec = TaskSpec {
+                    agent_id,
+                    prompt: rule.prompt.clone(),
+                    depends_on: Vec::new(),
+                    inject_results: false,
+                    context: Some(context),
+                    priority: PromptPriority::Normal,
+                    preamble: state.prompt_preamble(&agent_id).await,
+                };
+                let tx = spawn_update_forwarder(app_handle.clone(), state.clone());
+                match state.agent_pool.submit_task(spec, tx) {
+                    Ok(task) => execution.task_id = Some(task.id),
+                    Err(e) => execution.error = Some(e.to_string()),
+                }
+            }
+            None => execution.error = Some(format!("Agent {} is not running", rule.agent)),
+        }
+
+        let _ = app_handle.emit_tracked("trigger-fired", &execution);
+        state.trigger_history.record(execution);
+    }
+}
+
+/// Collect file changes into debounced batches and fire the matching rules
+pub fn start_trigger_rules(app_handle: AppHandle) {
+    let mut files = FILE_EVENTS.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<Arc<AppState>>().inner().clone();
+        loop {
+            let mut changes = ChangeBatch::default();
+            match files.recv().await {
+                Ok(event) => changes.add(&event),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+            let started = Instant::now();
+            loop {
+                let quiet_until = (Instant::now() + DEBOUNCE).min(started + MAX_BATCH_WAIT);
+                match tokio::time::timeout_at(quiet_until, files.recv()).await {
+                    Ok(Ok(event)) => changes.add(&event),
+                    Ok(Err(RecvError::Lagged(_))) => {}
+                    Ok(Err(RecvError::Closed)) | Err(_) => break,
+                }
+            }
+            if !changes.is_empty() {
+                fire_rules(&app_handle, &state, &changes).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(path: &str, agent_id: Option<Uuid>) -> FileEvent {
+        FileEvent {
+            kind: FileEventKind::Modify,
+            paths: vec![path.to_string()],
+            agent_id,
+            attributions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_rule_matches_paths_under_its_prefix() {
+        let reviewer = Uuid::new_v4();
+        let rule = TriggerRule {
+            id: "migrations".to_string(),
+            project_path: "/repo".to_string(),
+            path_prefix: "migrations".to_string(),
+            agent: "reviewer".to_string(),
+            prompt: "Review the migration".to_string(),
+            enabled: true,
+        };
+        let mut changes = ChangeBatch::default();
+        changes.add(&event("/repo/migrations/001_init.sql", None));
+        changes.add(&event("/repo/migrations/002_users.sql", Some(reviewer)));
+        changes.add(&event("/repo/migrations_old/x.sql", None));
+        changes.add(&event("/repo/src/main.rs", None));
+        changes.add(&event("/other/migrations/001_init.sql", None));
+
+        assert_eq!(
+            rule.matching_paths(&changes, Some(reviewer)),
+            vec!["migrations/001_init.sql".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_filtered_by_rule() {
+        let history = TriggerHistory::new();
+        for i in 0..HISTORY_CAPACITY + 1 {
+            history.record(TriggerExecution {
+                rule_id: if i % 2 == 0 { "a" } else { "b" }.to_string(),
+                fired_at: i as u64,
+                paths: Vec::new(),
+                agent_id: None,
+                task_id: None,
+                error: None,
+            });
+        }
+        assert_eq!(history.list(None).len(), HISTORY_CAPACITY);
+        assert_eq!(history.list(None)[0].fired_at, 1);
+        assert!(history.list(Some("a")).iter().all(|e| e.rule_id == "a"));
+    }
+}
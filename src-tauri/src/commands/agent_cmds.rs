@@ -1,67 +1,386 @@
-use crate::agent::{AgentInfo, AgentUpdate, SpawnConfig};
-use crate::registry::{Distribution, BinaryManager, get_platform};
-use crate::state::AppState;
+use crate::acp::{SessionListEntry, Transport, PROTOCOL_VIOLATIONS};
+use crate::agent::{
+    clone_seed_prompt, connect_demo_agent, find_conflicts, find_workflow, pack_files, resolve_mentions,
+    scripted_route, RouteCandidate, TaskFacts,
+    resume_seed_prompt, ResumeMethod, SavedSession,
+    build_leaderboard, run_benchmark, transcript, AgentEnvironment, AgentInfo, AgentOwner, AgentProcessError, AgentUpdate, BenchmarkResult,
+    CompactionRecord, FileLock, Leaderboard, LeaderboardRange, PendingPermission, PlacementRef,
+    JournalEntry, PendingPermissionInfo, PoolQueue, PromptPriority, PromptRejection, PromptReply, RunningToolCall, SpawnConfig, TaskConflict, TaskGraphState, TaskInfo, TaskSpec, TaskStatus, ToolCallArtifact, ToolOutput,
+    TranscriptChunk, MAX_CHUNK_BYTES,
+};
+use crate::clock::now_secs;
+use crate::events::TrackedEmitter;
+use crate::hooks::{self, HookEvent, HookPayload};
+use crate::filesystem::{suggest_files, ContextSuggestion};
+use crate::plugins::PLUGINS;
+use crate::registry::{
+    get_claude_agent, get_platform, pin_npx_package, BinaryManager, Distribution, DEMO_AGENT_ID,
+};
+use crate::state::{AgentPlacement, AppState, AuditKind};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter, State};
+use std::time::Instant;
+use tauri::{AppHandle, State};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+/// Spawn an agent. With a `preset`, its working directory comes from the
+/// preset's template resolved against `project_id`, and the preset's
+/// provider is used unless `provider_id` is given. Agents of a project in
+/// a group fall back to the group's preset and provider.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn spawn_agent(
     name: String,
     working_directory: String,
     provider_id: Option<String>,
+    transport: Option<Transport>,
+    preset: Option<String>,
+    project_id: Option<String>,
     state: State<'_, Arc<AppState>>,
     app_handle: AppHandle,
 ) -> Result<AgentInfo, String> {
-    // If provider_id is specified, look up the distribution from registry
-    let info = if let Some(ref pid) = provider_id {
-        let agent = state
-            .registry
-            .get_agent(pid)
+    let group_defaults = match &project_id {
+        Some(id) => state.factory.get_layout().await.group_of(id).map(|g| g.agent_defaults.clone()),
+        None => None,
+    }
+    .unwrap_or_default();
+    let (working_directory, provider_id) = match preset.or(group_defaults.preset) {
+        Some(preset) => {
+            let (directory, preset_provider) = resolve_preset(&state, &preset, project_id.as_deref()).await?;
+            (directory, provider_id.or(preset_provider))
+        }
+        None => (working_directory, provider_id),
+    };
+    let provider_id = provider_id
+        .or(group_defaults.provider_id)
+        .or_else(|| state.settings.get().default_provider_id);
+
+    let info = spawn_agent_process(
+        &state,
+        name,
+        working_directory,
+        provider_id,
+        transport.unwrap_or_default(),
+    )
+    .await?;
+
+    let _ = app_handle.emit_tracked("agent-spawned", &info);
+    Ok(info)
+}
+
+/// Working directory and provider of a spawn preset for an agent of
+/// `project_id`
+async fn resolve_preset(
+    state: &AppState,
+    preset: &str,
+    project_id: Option<&str>,
+) -> Result<(String, Option<String>), String> {
+    let preset = state
+        .settings
+        .get()
+        .spawn_presets
+        .into_iter()
+        .find(|p| p.name == preset)
+        .ok_or_else(|| format!("Unknown spawn preset: {}", preset))?;
+    let layout = state.factory.get_layout().await;
+    let project = match project_id {
+        Some(id) => Some(
+            layout
+                .projects
+                .iter()
+                .find(|p| p.id == id)
+                .ok_or_else(|| format!("Unknown project: {}", id))?,
+        ),
+        None => None,
+    };
+    let directory = preset.resolve_working_directory(project)?;
+    Ok((directory, preset.provider_id))
+}
+
+/// Spawn an agent, resolving its command from the registry when a provider is
+/// given, or connect to it when it runs elsewhere
+pub(crate) async fn spawn_agent_process(
+    state: &AppState,
+    name: String,
+    working_directory: String,
+    provider_id: Option<String>,
+    transport: Transport,
+) -> Result<AgentInfo, String> {
+    let provider = provider_id.clone().unwrap_or_else(|| get_claude_agent().id);
+    let spawned = start_agent_process(state, name, working_directory, provider_id, transport).await;
+    match &spawned {
+        Ok(info) => {
+            state.usage.record_spawn(&provider);
+            let message = format!("Spawned {} with {}", info.name, provider);
+            state.audit(AuditKind::AgentSpawned, Some(info.id), &message);
+        }
+        Err(_) => state.usage.record_error("spawn_failed"),
+    }
+    spawned
+}
+
+async fn start_agent_process(
+    state: &AppState,
+    name: String,
+    working_directory: String,
+    provider_id: Option<String>,
+    transport: Transport,
+) -> Result<AgentInfo, String> {
+    // The demo agent runs in-process, there is nothing to spawn
+    if provider_id.as_deref() == Some(DEMO_AGENT_ID) {
+        return state
+            .agent_pool
+            .add_agent(connect_demo_agent(name, working_directory))
             .await
-            .ok_or_else(|| format!("Unknown provider: {}", pid))?;
+            .map_err(|e| e.to_string());
+    }
 
-        let (command, args) = build_spawn_command(&agent.distribution, &agent.id, &agent.version).await?;
+    let config = resolve_spawn_config(state, name, working_directory, provider_id, transport).await?;
+    state
+        .agent_pool
+        .spawn_agent_with_config(config)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-        let config = SpawnConfig {
+/// How to start an agent of `provider_id`, the default Claude provider when
+/// none is given
+async fn resolve_spawn_config(
+    state: &AppState,
+    name: String,
+    working_directory: String,
+    provider_id: Option<String>,
+    transport: Transport,
+) -> Result<SpawnConfig, String> {
+    // A remote agent is already running; the provider only labels it
+    if !transport.spawns_process() {
+        let provider = match &provider_id {
+            Some(pid) => Some(
+                state
+                    .registry
+                    .get_agent(pid)
+                    .await
+                    .ok_or_else(|| format!("Unknown provider: {}", pid))?,
+            ),
+            None => None,
+        };
+        return Ok(SpawnConfig {
             name,
             working_directory,
-            provider_id: Some(agent.id.clone()),
-            provider_name: Some(agent.name.clone()),
-            command,
-            args,
-        };
+            provider_id: provider.as_ref().map(|p| p.id.clone()),
+            provider_name: provider.as_ref().map(|p| p.name.clone()),
+            command: String::new(),
+            args: Vec::new(),
+            transport,
+            sandbox: None,
+            limits: None,
+            env: HashMap::new(),
+            default_model: provider.as_ref().and_then(|p| p.default_model.clone()),
+            auth_method: provider.as_ref().and_then(|p| p.auth_method.clone()),
+        });
+    }
 
-        state
+    let package_runner = state.settings.get().package_runner(transport.is_stdio());
+    // Over SSH the default agent needs its npx package spelled out
+    let provider_id = match provider_id {
+        None if !transport.is_stdio() => Some(get_claude_agent().id),
+        provider_id => provider_id,
+    };
+
+    // If provider_id is specified, look up the distribution from registry
+    let Some(pid) = provider_id else {
+        // Default to the backward-compatible spawn
+        return Ok(SpawnConfig::claude(name, working_directory, &package_runner));
+    };
+    let agent = state
+        .registry
+        .get_agent(&pid)
+        .await
+        .ok_or_else(|| format!("Unknown provider: {}", pid))?;
+    // Binaries are downloaded for this machine, not the remote one
+    if !transport.is_stdio() && agent.distribution.npx.is_none() {
+        return Err(format!("{} can only run locally: it has no npx distribution", agent.name));
+    }
+
+    let pinned = state.registry.pinned_version(&agent.id);
+    let (command, args, env) = build_spawn_command(
+        &agent.distribution,
+        &agent.id,
+        &agent.version,
+        pinned.as_deref(),
+        &package_runner,
+    )
+    .await?;
+
+    Ok(SpawnConfig {
+        name,
+        working_directory,
+        provider_id: Some(agent.id.clone()),
+        provider_name: Some(agent.name.clone()),
+        command,
+        args,
+        transport,
+        sandbox: None,
+        limits: None,
+        env,
+        default_model: agent.default_model,
+        auth_method: agent.auth_method,
+    })
+}
+
+/// Most prompts a benchmark runs
+const MAX_BENCHMARK_ITERATIONS: usize = 20;
+
+/// Spawn a copy of an agent with the same spawn configuration, working
+/// directory, mode and model, placed next to it on the grid. With
+/// `seed_summary`, the source summarizes its conversation and the copy
+/// starts from that summary.
+#[tauri::command]
+pub async fn clone_agent(
+    agent_id: String,
+    seed_summary: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<AgentInfo, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let source = state
+        .agent_pool
+        .get_agent_info(&id)
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+    let name = format!("{} (copy)", source.name);
+    let info = match state.agent_pool.spawn_config(&id) {
+        Some(config) => state
             .agent_pool
-            .spawn_agent_with_config(config)
+            .spawn_agent_with_config(SpawnConfig { name, ..config })
             .await
-            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?,
+        None => {
+            spawn_agent_process(
+                &state,
+                name,
+                source.working_directory.clone(),
+                source.provider_id.clone(),
+                Transport::default(),
+            )
+            .await?
+        }
+    };
+    copy_session_settings(&state, &source, &info).await;
+    let info = state.agent_pool.get_agent_info(&info.id).unwrap_or(info);
+    let _ = app_handle.emit_tracked("agent-spawned", &info);
+
+    let layout = state.factory.get_layout().await;
+    if let Some(placement) = layout.agent_placements.iter().find(|p| p.agent_id == agent_id) {
+        let (grid_x, grid_y) = layout.free_cell_near(placement.grid_x, placement.grid_y);
+        state
+            .factory
+            .set_agent_placement(AgentPlacement {
+                agent_id: info.id.to_string(),
+                grid_x,
+                grid_y,
+                connected_project_id: placement.connected_project_id.clone(),
+                name: Some(info.name.clone()),
+                working_directory: Some(info.working_directory.clone()),
+                provider_id: info.provider_id.clone(),
+                standing_order: None,
+                stats: Default::default(),
+            })
+            .await?;
+        let _ = app_handle.emit_tracked("factory-layout-changed", ());
+    }
+
+    if seed_summary.unwrap_or(false) {
+        let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+        let seeded = match state.agent_pool.summarize(id, tx.clone()).await {
+            Ok(summary) => state
+                .agent_pool
+                .send_prompt(info.id, &clone_seed_prompt(&summary), tx)
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        seeded.map_err(|e| format!("Cloned as {} but seeding it failed: {}", info.id, e))?;
+    }
+
+    Ok(info)
+}
+
+/// Put a clone's session in the source's mode and model, if it offers them
+async fn copy_session_settings(state: &AppState, source: &AgentInfo, clone: &AgentInfo) {
+    if let (Some(wanted), Some(offered)) = (&source.modes, &clone.modes) {
+        let mode = &wanted.current_mode_id;
+        if &offered.current_mode_id != mode && offered.available_modes.iter().any(|m| &m.id == mode) {
+            if let Err(e) = state.agent_pool.set_mode(&clone.id, mode).await {
+                tracing::warn!("Failed to set mode {} on clone {}: {}", mode, clone.id, e);
+            }
+        }
+    }
+    if let (Some(wanted), Some(offered)) = (&source.models, &clone.models) {
+        let model = &wanted.current_model_id;
+        if &offered.current_model_id != model && offered.available_models.iter().any(|m| &m.model_id == model) {
+            if let Err(e) = state.agent_pool.set_model(&clone.id, model).await {
+                tracing::warn!("Failed to set model {} on clone {}: {}", model, clone.id, e);
+            }
+        }
+    }
+}
+
+/// Measure spawn, initialize, first-token and completion latency of a
+/// provider on a fresh agent that is not added to the pool
+#[tauri::command]
+pub async fn benchmark_agent(
+    provider_id: Option<String>,
+    iterations: Option<usize>,
+    working_directory: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<BenchmarkResult, String> {
+    let iterations = iterations.unwrap_or(3).clamp(1, MAX_BENCHMARK_ITERATIONS);
+    let working_directory = match working_directory {
+        Some(dir) => dir,
+        None => match state.project_path.read().await.as_ref() {
+            Some(path) => path.display().to_string(),
+            None => std::env::temp_dir().display().to_string(),
+        },
+    };
+    let name = "Benchmark".to_string();
+
+    let started = Instant::now();
+    let agent = if provider_id.as_deref() == Some(DEMO_AGENT_ID) {
+        connect_demo_agent(name, working_directory)
     } else {
-        // Default to the backward-compatible spawn
+        let config = resolve_spawn_config(&state, name, working_directory, provider_id, Transport::Stdio).await?;
         state
             .agent_pool
-            .spawn_agent(name, working_directory)
+            .spawn_detached(config)
             .await
             .map_err(|e| e.to_string())?
     };
-
-    let _ = app_handle.emit("agent-spawned", &info);
-    Ok(info)
+    run_benchmark(agent, started.elapsed(), iterations)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// Build command and args from a Distribution
+/// Build command and args from a Distribution, honouring a pinned version.
+/// npx distributions run with `package_runner`.
 async fn build_spawn_command(
     distribution: &Distribution,
     agent_id: &str,
     version: &str,
-) -> Result<(String, Vec<String>), String> {
+    pinned_version: Option<&str>,
+    package_runner: &str,
+) -> Result<(String, Vec<String>, HashMap<String, String>), String> {
     // Check for npx distribution first
     if let Some(ref npx) = distribution.npx {
-        let mut args = vec![npx.package.clone()];
+        let package = match pinned_version {
+            Some(pinned) => pin_npx_package(&npx.package, pinned),
+            None => npx.package.clone(),
+        };
+        let mut args = vec![package];
         args.extend(npx.args.clone());
-        return Ok(("npx".to_string(), args));
+        return Ok((package_runner.to_string(), args, npx.env.clone()));
     }
 
     // Check for binary distribution
@@ -70,8 +389,17 @@ async fn build_spawn_command(
             .ok_or_else(|| "Unsupported platform".to_string())?;
 
         if let Some(binary_info) = binaries.get(platform) {
-            // Download and cache the binary
+            // The registry only has the archive of its latest version, so an
+            // older pinned version must already be installed
             let binary_manager = BinaryManager::new();
+            if let Some(pinned) = pinned_version {
+                if pinned != version
+                    && !binary_manager.installed_versions(agent_id).iter().any(|v| v == pinned)
+                {
+                    return Err(format!("Pinned version {} of {} is not installed", pinned, agent_id));
+                }
+            }
+            let version = pinned_version.unwrap_or(version);
             let binary_path = binary_manager
                 .get_binary(agent_id, version, &binary_info.archive, &binary_info.cmd)
                 .await
@@ -82,7 +410,7 @@ async fn build_spawn_command(
                 .ok_or_else(|| "Invalid binary path".to_string())?
                 .to_string();
 
-            return Ok((cmd, binary_info.args.clone()));
+            return Ok((cmd, binary_info.args.clone(), binary_info.env.clone()));
         } else {
             return Err(format!("Binary not available for platform: {}", platform));
         }
@@ -98,19 +426,31 @@ pub async fn stop_agent(
     app_handle: AppHandle,
 ) -> Result<(), String> {
     let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    stop_agent_process(&state, &id).await?;
+    let _ = app_handle.emit_tracked("agent-stopped", serde_json::json!({ "agent_id": agent_id }));
+    Ok(())
+}
+
+/// Stop an agent and drop what was tracked about it
+pub(crate) async fn stop_agent_process(state: &AppState, id: &Uuid) -> Result<(), String> {
+    // Logged first, while the agent's project is still known
+    if let Some(info) = state.agent_pool.get_agent_info(id) {
+        state.audit(AuditKind::AgentStopped, Some(*id), &format!("Stopped {}", info.name));
+    }
     state
         .agent_pool
-        .stop_agent(&id)
+        .stop_agent(id)
         .await
         .map_err(|e| e.to_string())?;
-
-    let _ = app_handle.emit("agent-stopped", &agent_id);
+    state.file_activity.forget(id);
+    state.file_correlation.forget(id);
+    PROTOCOL_VIOLATIONS.forget(id);
     Ok(())
 }
 
 #[tauri::command]
 pub async fn list_agents(state: State<'_, Arc<AppState>>) -> Result<Vec<AgentInfo>, String> {
-    Ok(state.agent_pool.list_agents().await)
+    Ok(state.agent_pool.list_agents())
 }
 
 #[tauri::command]
@@ -119,46 +459,843 @@ pub async fn get_agent(
     state: State<'_, Arc<AppState>>,
 ) -> Result<Option<AgentInfo>, String> {
     let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
-    Ok(state.agent_pool.get_agent_info(&id).await)
+    Ok(state.agent_pool.get_agent_info(&id))
+}
+
+/// Command line, working directory, PID, redacted environment and
+/// negotiated capabilities of a running agent
+#[tauri::command]
+pub fn get_agent_environment(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AgentEnvironment, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state
+        .agent_pool
+        .agent_environment(&id)
+        .ok_or_else(|| format!("Agent not found: {}", agent_id))
+}
+
+/// Error of the prompt commands: why the agent refused the prompt, for the
+/// frontend to act on, or a message
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum PromptError {
+    Rejected(PromptRejection),
+    Message(String),
+}
+
+impl From<String> for PromptError {
+    fn from(message: String) -> Self {
+        PromptError::Message(message)
+    }
+}
+
+impl From<AgentProcessError> for PromptError {
+    fn from(error: AgentProcessError) -> Self {
+        match error {
+            AgentProcessError::PromptRejected(rejection) => PromptError::Rejected(rejection),
+            other => PromptError::Message(other.to_string()),
+        }
+    }
 }
 
 #[tauri::command]
 pub async fn send_prompt(
     agent_id: String,
     prompt: String,
+    priority: Option<PromptPriority>,
     state: State<'_, Arc<AppState>>,
     app_handle: AppHandle,
-) -> Result<String, String> {
+) -> Result<PromptReply, PromptError> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let info = state
+        .agent_pool
+        .get_agent_info(&id)
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+
+    // Expand @file: and @agent: mentions into attached context
+    let base = Path::new(&info.working_directory);
+    let tasks = state.agent_pool.task_graph();
+    let resolved = resolve_mentions(&prompt, base, &state.agent_pool.list_agents(), |agent| {
+        tasks.tasks_for_agent(&agent.id)
+    })
+    .await;
+    for path in &resolved.context.included {
+        let full_path = base.join(path).to_string_lossy().to_string();
+        state.fog.touch(&full_path);
+        state.fog_reveals.push(&full_path);
+    }
+    if resolved.prompt != prompt {
+        let _ = app_handle.emit_tracked("prompt-mentions-resolved", serde_json::json!({
+            "agent_id": agent_id,
+            "included": resolved.context.included,
+            "skipped": resolved.context.skipped,
+            "agents": resolved.agents,
+            "unknown_agents": resolved.unknown_agents,
+        }));
+    }
+
+    let spec = TaskSpec {
+        agent_id: id,
+        prompt: resolved.prompt,
+        depends_on: Vec::new(),
+        inject_results: false,
+        context: (!resolved.context.preamble.is_empty()).then_some(resolved.context),
+        priority: priority.unwrap_or_default(),
+        preamble: state.prompt_preamble(&id).await,
+    };
+    let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+    let result = state.agent_pool.run_task_now(spec, tx).await?;
+
+    // Emit completion
+    let info = state.agent_pool.get_agent_info(&id);
+    if let Some(info) = &info {
+        let _ = app_handle.emit_tracked("agent-status-changed", info);
+    }
+
+    Ok(PromptReply::new(&result, info.and_then(|info| info.last_transcript)))
+}
+
+/// Send a user message into the agent's session mid-task; it runs right
+/// after the current turn and answers questions the agent paused on
+#[tauri::command]
+pub async fn send_followup(
+    agent_id: String,
+    text: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<PromptReply, PromptError> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+    let result = state.agent_pool.send_followup(id, &text, tx).await?;
+
+    let info = state.agent_pool.get_agent_info(&id);
+    if let Some(info) = &info {
+        let _ = app_handle.emit_tracked("agent-status-changed", info);
+    }
+
+    Ok(PromptReply::new(&result, info.and_then(|info| info.last_transcript)))
+}
+
+/// Send a prompt with the contents of project files attached
+#[tauri::command]
+pub async fn send_prompt_with_context(
+    agent_id: String,
+    prompt: String,
+    paths: Vec<String>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<PromptReply, PromptError> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let info = state
+        .agent_pool
+        .get_agent_info(&id)
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+
+    let base = Path::new(&info.working_directory);
+    let context = pack_files(base, &paths).await;
+    for path in &context.included {
+        let full_path = base.join(path).to_string_lossy().to_string();
+        state.fog.touch(&full_path);
+        state.fog_reveals.push(&full_path);
+    }
+    let _ = app_handle.emit_tracked("prompt-context-packed", serde_json::json!({
+        "agent_id": agent_id,
+        "included": context.included,
+        "skipped": context.skipped,
+    }));
+
+    let spec = TaskSpec {
+        agent_id: id,
+        prompt,
+        depends_on: Vec::new(),
+        inject_results: false,
+        context: Some(context),
+        priority: PromptPriority::Normal,
+        preamble: state.prompt_preamble(&id).await,
+    };
+
+    let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+    let result = state.agent_pool.run_task_now(spec, tx).await?;
+
+    let info = state.agent_pool.get_agent_info(&id);
+    if let Some(info) = &info {
+        let _ = app_handle.emit_tracked("agent-status-changed", info);
+    }
+
+    Ok(PromptReply::new(&result, info.and_then(|info| info.last_transcript)))
+}
+
+/// Compact an agent's session so long conversations fit its context window
+#[tauri::command]
+pub async fn compact_session(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<CompactionRecord, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+
+    let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+    let record = state
+        .agent_pool
+        .compact_session(id, tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = state.conversations.save_compaction(&record) {
+        tracing::warn!("{}", e);
+    }
+
+    let _ = app_handle.emit_tracked("session-compacted", &record);
+    if let Some(info) = state.agent_pool.get_agent_info(&id) {
+        let _ = app_handle.emit_tracked("agent-status-changed", &info);
+    }
+
+    Ok(record)
+}
+
+/// Get the compactions performed on an agent's sessions, including those of
+/// earlier runs that led to its current session
+#[tauri::command]
+pub fn get_session_history(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<CompactionRecord>, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let mut history = state
+        .agent_pool
+        .get_agent_info(&id)
+        .and_then(|info| info.session_id)
+        .map(|session_id| state.conversations.compactions_leading_to(&session_id))
+        .unwrap_or_default();
+    // Compactions of this run the database missed
+    for record in state.agent_pool.session_history(&id) {
+        let saved = history.iter().any(|r| {
+            r.created_at == record.created_at && r.session_id == record.session_id
+        });
+        if !saved {
+            history.push(record);
+        }
+    }
+    history.sort_by_key(|r| r.created_at);
+    Ok(history)
+}
+
+/// Get the full content (diffs, text output) of one of an agent's recent tool calls
+#[tauri::command]
+pub fn get_tool_call_artifact(
+    agent_id: String,
+    tool_call_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ToolCallArtifact, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state
+        .agent_pool
+        .tool_call_artifact(&id, &tool_call_id)
+        .ok_or_else(|| format!("Tool call not found: {}", tool_call_id))
+}
+
+/// Full output of a tool call, which the artifact only previews when it is large
+#[tauri::command]
+pub fn get_tool_output(
+    agent_id: String,
+    tool_call_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ToolOutput, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state
+        .agent_pool
+        .tool_output(&id, &tool_call_id)?
+        .ok_or_else(|| format!("Tool call not found: {}", tool_call_id))
+}
+
+/// Tool calls not finished yet with how long each has run, of one agent or all
+#[tauri::command]
+pub fn get_running_tool_calls(
+    agent_id: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<RunningToolCall>, String> {
+    let id = agent_id
+        .map(|id| Uuid::parse_str(&id))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    Ok(state.agent_pool.running_tool_calls(id.as_ref()))
+}
+
+/// Stop one tool call; only calls still waiting for permission can be
+/// stopped alone, others need the whole prompt cancelled
+#[tauri::command]
+pub fn cancel_tool_call(
+    agent_id: String,
+    tool_call_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state.agent_pool.cancel_tool_call(&id, &tool_call_id)?;
+    let message = format!("Cancelled tool call {}", tool_call_id);
+    state.audit(AuditKind::ToolCallCancelled, Some(id), &message);
+    Ok(())
+}
+
+/// Ask an agent to end its running prompt via session/cancel
+#[tauri::command]
+pub fn cancel_prompt(agent_id: String, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state.agent_pool.cancel_prompt(&id)?;
+    state.audit(AuditKind::PromptCancelled, Some(id), "Cancelled the running prompt");
+    Ok(())
+}
+
+/// Page through the answer to a prompt from `offset`, `limit` bytes at a time
+#[tauri::command]
+pub fn read_transcript_chunk(
+    transcript_id: String,
+    offset: Option<u64>,
+    limit: Option<usize>,
+) -> Result<TranscriptChunk, String> {
+    transcript::read_transcript_chunk(
+        &transcript_id,
+        offset.unwrap_or(0),
+        limit.unwrap_or(MAX_CHUNK_BYTES),
+    )
+}
+
+/// Malformed messages received so far, per agent
+#[tauri::command]
+pub fn get_protocol_violations() -> HashMap<Uuid, u64> {
+    PROTOCOL_VIOLATIONS.counts()
+}
+
+/// Get every prompt sent to an agent, oldest first, with its outcome
+#[tauri::command]
+pub fn get_prompt_history(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<TaskInfo>, String> {
     let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    Ok(state.prompt_history(&id))
+}
 
+/// Send an earlier prompt from the agent's history again, re-reading any
+/// files that were attached to it
+#[tauri::command]
+pub async fn resend_prompt(
+    agent_id: String,
+    history_index: usize,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<PromptReply, PromptError> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let previous = state
+        .prompt_history(&id)
+        .into_iter()
+        .nth(history_index)
+        .ok_or_else(|| format!("No prompt at history index {}", history_index))?;
+
+    let context = if previous.context_files.is_empty() {
+        None
+    } else {
+        let info = state
+            .agent_pool
+            .get_agent_info(&id)
+            .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+        Some(pack_files(Path::new(&info.working_directory), &previous.context_files).await)
+    };
+
+    let spec = TaskSpec {
+        agent_id: id,
+        prompt: previous.prompt,
+        depends_on: Vec::new(),
+        inject_results: false,
+        context,
+        priority: previous.priority,
+        preamble: previous.preamble,
+    };
+
+    let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+    let result = state.agent_pool.run_task_now(spec, tx).await?;
+
+    let info = state.agent_pool.get_agent_info(&id);
+    if let Some(info) = &info {
+        let _ = app_handle.emit_tracked("agent-status-changed", info);
+    }
+
+    Ok(PromptReply::new(&result, info.and_then(|info| info.last_transcript)))
+}
+
+/// Forward agent updates to the frontend, revealing touched files in fog
+pub(crate) fn spawn_update_forwarder(app_handle: AppHandle, state: Arc<AppState>) -> mpsc::Sender<AgentUpdate> {
     let (tx, mut rx) = mpsc::channel::<AgentUpdate>(100);
-    let app_handle_clone = app_handle.clone();
-    let fog = state.fog.clone();
 
-    // Forward updates to frontend
     tokio::spawn(async move {
         while let Some(update) = rx.recv().await {
+            let Some(update) = PLUGINS.transform_update(update) else {
+                continue;
+            };
             // Reveal files in fog when agent accesses them
             if let Some(ref file) = update.current_file {
-                fog.reveal(file);
-                let _ = app_handle_clone.emit("fog-revealed", file);
+                state.file_activity.record(update.agent_id, file);
+                // current_file sticks across updates; only tool calls are fresh touches
+                if update.update_type.starts_with("tool_call") {
+                    state.fog.touch(file);
+                    state.file_correlation.record(update.agent_id, file);
+                    state.agent_pool.task_graph().record_touch(&update.agent_id, file);
+                }
+                // Revealed when the batch is sent
+                state.fog_reveals.push(file);
+            }
+            let project = state
+                .agent_pool
+                .get_agent_info(&update.agent_id)
+                .map(|info| info.working_directory);
+            if let Err(e) = state.database.record_activity(&update, project.as_deref()) {
+                tracing::warn!("{}", e);
             }
-            let _ = app_handle_clone.emit("agent-update", &update);
+            if update.update_type == "permission_request" {
+                let message = update.message.clone().unwrap_or_default();
+                hooks::dispatch(
+                    &state,
+                    HookPayload::new(HookEvent::PermissionRequested, Some(update.agent_id), message),
+                );
+            }
+            let _ = app_handle.emit_agent_update("agent-update", update.agent_id, &update);
         }
     });
 
-    let result = state
+    tx
+}
+
+/// Rank project files the user may want to attach to a prompt
+#[tauri::command]
+pub async fn suggest_context(
+    agent_id: String,
+    prompt: String,
+    limit: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ContextSuggestion>, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let info = state
+        .agent_pool
+        .get_agent_info(&id)
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+
+    let touched = state.file_activity.touched_by(&id);
+    let explored: HashSet<String> = state.fog.explored_paths().into_iter().collect();
+
+    tokio::task::spawn_blocking(move || {
+        suggest_files(
+            Path::new(&info.working_directory),
+            &prompt,
+            &touched,
+            &explored,
+            limit.unwrap_or(10),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Dispatch a prompt as a task, optionally waiting for other tasks to complete first
+#[tauri::command]
+pub async fn dispatch_task(
+    agent_id: String,
+    prompt: String,
+    depends_on: Option<Vec<String>>,
+    inject_results: Option<bool>,
+    priority: Option<PromptPriority>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<TaskInfo, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+
+    let spec = TaskSpec {
+        agent_id: id,
+        prompt,
+        depends_on: depends_on.unwrap_or_default(),
+        inject_results: inject_results.unwrap_or(false),
+        context: None,
+        priority: priority.unwrap_or_default(),
+        preamble: state.prompt_preamble(&id).await,
+    };
+
+    let tx = spawn_update_forwarder(app_handle, state.inner().clone());
+    state
         .agent_pool
-        .send_prompt(id, &prompt, tx)
+        .submit_task(spec, tx)
+        .map_err(|e| e.to_string())
+}
+
+/// Dispatch a prompt to the agent the routing policy script picks among the
+/// running agents, optionally only those working in `project`
+#[tauri::command]
+pub async fn dispatch_routed_task(
+    prompt: String,
+    project: Option<String>,
+    depends_on: Option<Vec<String>>,
+    inject_results: Option<bool>,
+    priority: Option<PromptPriority>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<TaskInfo, String> {
+    let agents: Vec<RouteCandidate> = state
+        .agent_pool
+        .list_agents()
+        .iter()
+        .filter(|a| project.as_ref().is_none_or(|p| a.working_directory == *p))
+        .map(RouteCandidate::from_info)
+        .collect();
+    let task = TaskFacts {
+        prompt: prompt.clone(),
+        project,
+    };
+    let agent_id = tokio::task::spawn_blocking(move || scripted_route(&task, &agents))
         .await
+        .map_err(|e| e.to_string())??
+        .ok_or_else(|| "The routing script picked no agent".to_string())?;
+
+    dispatch_task(
+        agent_id.to_string(),
+        prompt,
+        depends_on,
+        inject_results,
+        priority,
+        state,
+        app_handle,
+    )
+    .await
+}
+
+/// A prompt a crash of the app cut off
+#[derive(Debug, Clone, Serialize)]
+pub struct InterruptedTask {
+    #[serde(flatten)]
+    pub entry: JournalEntry,
+    /// Running agent with the same name and working directory, to send it to
+    pub matching_agent_id: Option<Uuid>,
+}
+
+fn matching_agent(state: &AppState, entry: &JournalEntry) -> Option<Uuid> {
+    state
+        .agent_pool
+        .agent_statuses()
+        .into_iter()
+        .find(|a| a.name == entry.agent_name && a.working_directory == entry.working_directory)
+        .map(|a| a.id)
+}
+
+/// Prompts that were running when the app last crashed
+#[tauri::command]
+pub fn get_interrupted_tasks(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<InterruptedTask>, String> {
+    Ok(state
+        .journal
+        .interrupted()
+        .into_iter()
+        .map(|entry| InterruptedTask {
+            matching_agent_id: matching_agent(&state, &entry),
+            entry,
+        })
+        .collect())
+}
+
+/// Dispatch an interrupted prompt again, to `agent_id`, the matching agent,
+/// or else a new agent spawned like the one it was sent to
+#[tauri::command]
+pub async fn redispatch_interrupted_task(
+    entry_id: String,
+    agent_id: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<TaskInfo, String> {
+    let entry = state
+        .journal
+        .interrupted()
+        .into_iter()
+        .find(|e| e.id == entry_id)
+        .ok_or_else(|| format!("No interrupted task: {}", entry_id))?;
+
+    let id = match agent_id {
+        Some(agent_id) => Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?,
+        None => match matching_agent(&state, &entry) {
+            Some(id) => id,
+            None => {
+                let info = spawn_agent_process(
+                    &state,
+                    entry.agent_name.clone(),
+                    entry.working_directory.clone(),
+                    entry.provider_id.clone(),
+                    Transport::Stdio,
+                )
+                .await?;
+                let _ = app_handle.emit_tracked("agent-spawned", &info);
+                info.id
+            }
+        },
+    };
+
+    let spec = TaskSpec {
+        agent_id: id,
+        prompt: entry.prompt,
+        depends_on: Vec::new(),
+        inject_results: false,
+        context: None,
+        priority: PromptPriority::default(),
+        preamble: state.prompt_preamble(&id).await,
+    };
+    let tx = spawn_update_forwarder(app_handle, state.inner().clone());
+    let task = state
+        .agent_pool
+        .submit_task(spec, tx)
         .map_err(|e| e.to_string())?;
+    state.journal.dismiss(&entry_id);
+    Ok(task)
+}
 
-    // Emit completion
-    if let Some(info) = state.agent_pool.get_agent_info(&id).await {
-        let _ = app_handle.emit("agent-status-changed", &info);
+/// Forget an interrupted prompt without sending it again
+#[tauri::command]
+pub fn dismiss_interrupted_task(
+    entry_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state
+        .journal
+        .dismiss(&entry_id)
+        .map(|_| ())
+        .ok_or_else(|| format!("No interrupted task: {}", entry_id))
+}
+
+/// A saved agent of the last run, running again
+#[derive(Debug, Clone, Serialize)]
+pub struct ResumedSession {
+    pub agent: AgentInfo,
+    pub method: ResumeMethod,
+    /// Task of the prompt seeding a fresh session with the saved summary
+    pub seed_task_id: Option<String>,
+}
+
+/// Agents that were running when the app last quit, for offering to resume
+/// them
+#[tauri::command]
+pub fn get_resumable_sessions(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<SavedSession>, String> {
+    Ok(state.saved_sessions.resumable())
+}
+
+/// Spawn a saved agent again and pick up its session via session/load, or
+/// else seed a fresh session with the saved summary
+#[tauri::command]
+pub async fn resume_saved_session(
+    saved_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<ResumedSession, String> {
+    let saved = state
+        .saved_sessions
+        .resumable()
+        .into_iter()
+        .find(|s| s.id == saved_id)
+        .ok_or_else(|| format!("No saved session: {}", saved_id))?;
+
+    let info = spawn_agent_process(
+        &state,
+        saved.agent_name.clone(),
+        saved.working_directory.clone(),
+        saved.provider_id.clone(),
+        Transport::Stdio,
+    )
+    .await?;
+    let _ = app_handle.emit_tracked("agent-spawned", &info);
+    state.saved_sessions.dismiss(&saved_id);
+
+    let loaded = match &saved.session_id {
+        Some(session_id) if info.supports_load_session => {
+            match state.agent_pool.load_session(&info.id, session_id).await {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("session/load failed, seeding with the summary instead: {}", e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    let (method, seed_task_id) = if loaded {
+        (ResumeMethod::SessionLoad, None)
+    } else if saved.summary.is_empty() {
+        (ResumeMethod::FreshSession, None)
+    } else {
+        let spec = TaskSpec {
+            agent_id: info.id,
+            prompt: resume_seed_prompt(&saved.summary),
+            depends_on: Vec::new(),
+            inject_results: false,
+            context: None,
+            priority: PromptPriority::default(),
+            preamble: state.prompt_preamble(&info.id).await,
+        };
+        let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+        let task = state
+            .agent_pool
+            .submit_task(spec, tx)
+            .map_err(|e| e.to_string())?;
+        (ResumeMethod::SeedPrompt, Some(task.id))
+    };
+
+    let agent = state.agent_pool.get_agent_info(&info.id).unwrap_or(info);
+    let _ = app_handle.emit_tracked("agent-status-changed", &agent);
+    Ok(ResumedSession {
+        agent,
+        method,
+        seed_task_id,
+    })
+}
+
+/// Forget a saved agent without resuming it
+#[tauri::command]
+pub fn dismiss_saved_session(
+    saved_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state
+        .saved_sessions
+        .dismiss(&saved_id)
+        .map(|_| ())
+        .ok_or_else(|| format!("No saved session: {}", saved_id))
+}
+
+/// Prompts running and waiting for their turn, in dispatch order
+#[tauri::command]
+pub fn get_pool_queue(state: State<'_, Arc<AppState>>) -> Result<PoolQueue, String> {
+    Ok(state.agent_pool.queue())
+}
+
+/// Find an agent by id or, failing that, by name
+pub(crate) fn resolve_agent(state: &AppState, id_or_name: &str) -> Option<Uuid> {
+    let agents = state.agent_pool.agent_statuses();
+    agents
+        .iter()
+        .find(|a| a.id.to_string() == id_or_name)
+        .or_else(|| agents.iter().find(|a| a.name == id_or_name))
+        .map(|a| a.id)
+}
+
+/// Start the review of a completed task if its project has a review workflow
+pub(crate) async fn start_configured_review(
+    app_handle: AppHandle,
+    state: Arc<AppState>,
+    task: TaskInfo,
+) {
+    if task.review_of.is_some() || task.review.is_some() {
+        return;
     }
+    let Some(working_directory) = state
+        .agent_pool
+        .agent_statuses()
+        .into_iter()
+        .find(|a| a.id == task.agent_id)
+        .map(|a| a.working_directory)
+    else {
+        return;
+    };
 
-    Ok(result)
+    let workflows = state.settings.get().reviews;
+    let Some(workflow) = find_workflow(&workflows, &working_directory) else {
+        return;
+    };
+    let Some(reviewer) = resolve_agent(&state, &workflow.reviewer) else {
+        tracing::warn!("Reviewer agent {} is not running", workflow.reviewer);
+        return;
+    };
+    if reviewer == task.agent_id {
+        return;
+    }
+
+    let tx = spawn_update_forwarder(app_handle, state.clone());
+    if let Err(e) = state
+        .agent_pool
+        .review_task(task, reviewer, workflow.prompt_template.clone(), tx)
+        .await
+    {
+        tracing::warn!("Failed to start review: {}", e);
+    }
+}
+
+/// Have an agent review a completed task now, regardless of project settings
+#[tauri::command]
+pub async fn request_task_review(
+    task_id: String,
+    reviewer: String,
+    prompt_template: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<TaskInfo, String> {
+    let task = state
+        .agent_pool
+        .task_graph()
+        .get(&task_id)
+        .ok_or_else(|| format!("Unknown task: {}", task_id))?;
+    if task.status != TaskStatus::Completed {
+        return Err("Only completed tasks can be reviewed".to_string());
+    }
+    let reviewer_id = resolve_agent(&state, &reviewer)
+        .ok_or_else(|| format!("Unknown agent: {}", reviewer))?;
+
+    let tx = spawn_update_forwarder(app_handle, state.inner().clone());
+    state
+        .agent_pool
+        .review_task(task, reviewer_id, prompt_template, tx)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Files touched by tasks of different agents that ran at the same time
+#[tauri::command]
+pub fn get_conflicts(state: State<'_, Arc<AppState>>) -> Result<Vec<TaskConflict>, String> {
+    Ok(find_conflicts(&state.agent_pool.task_graph().snapshot().tasks))
+}
+
+/// Compare providers and placements by the tasks their agents finished
+/// within `range`, all time by default
+#[tauri::command]
+pub async fn get_agent_leaderboard(
+    range: Option<LeaderboardRange>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Leaderboard, String> {
+    let layout = state.factory.get_layout().await;
+    let mut owners: HashMap<Uuid, AgentOwner> = HashMap::new();
+    for placement in &layout.agent_placements {
+        let Ok(id) = Uuid::parse_str(&placement.agent_id) else {
+            continue;
+        };
+        owners.insert(
+            id,
+            AgentOwner {
+                provider_id: placement.provider_id.clone(),
+                placement: Some(PlacementRef {
+                    agent_id: placement.agent_id.clone(),
+                    name: placement.name.clone(),
+                }),
+                tokens_used: 0,
+            },
+        );
+    }
+    for info in state.agent_pool.list_agents() {
+        let owner = owners.entry(info.id).or_default();
+        owner.provider_id = info.provider_id.or(owner.provider_id.take());
+        owner.tokens_used = info.tokens_used;
+    }
+
+    let now = now_secs();
+    let tasks = state.agent_pool.task_graph().snapshot().tasks;
+    Ok(build_leaderboard(&tasks, &owners, range.unwrap_or_default(), now))
+}
+
+/// Get all tasks and their dependency edges
+#[tauri::command]
+pub fn get_task_graph(state: State<'_, Arc<AppState>>) -> Result<TaskGraphState, String> {
+    Ok(state.agent_pool.task_graph().snapshot())
 }
 
 #[tauri::command]
@@ -172,45 +1309,97 @@ pub async fn stop_all_agents(
         .await
         .map_err(|e| e.to_string())?;
 
-    let _ = app_handle.emit("all-agents-stopped", ());
+    let _ = app_handle.emit_tracked("all-agents-stopped", ());
     Ok(())
 }
 
+/// Answer a permission request; approving one that reaches outside every
+/// registered project needs `confirm_high_risk`
 #[tauri::command]
 pub async fn respond_to_permission(
     agent_id: String,
     input_id: String,
     approved: bool,
     option_id: Option<String>,
+    confirm_high_risk: Option<bool>,
     state: State<'_, Arc<AppState>>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
     let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
 
     println!("[DEBUG] respond_to_permission called: agent_id={}, input_id={}, approved={}", agent_id, input_id, approved);
+    let asked = state.agent_pool.get_pending_permissions().get(id, &input_id);
 
     state
         .agent_pool
-        .respond_to_permission(&id, &input_id, approved, option_id)
+        .respond_to_permission(&id, &input_id, approved, option_id, confirm_high_risk.unwrap_or(false))
         .map_err(|e| e.to_string())?;
 
     println!("[DEBUG] respond_to_permission succeeded");
+    let request = asked.map_or(input_id.clone(), |asked| asked.message);
+    let answer = if approved { "Approved" } else { "Rejected" };
+    state.audit(AuditKind::PermissionAnswered, Some(id), &format!("{}: {}", answer, request));
+    state.resolve_stale_permissions(id);
 
     // Emit an event to notify about the permission response
-    let _ = app_handle.emit("permission-responded", serde_json::json!({
+    let _ = app_handle.emit_tracked("permission-responded", serde_json::json!({
         "agent_id": agent_id,
         "input_id": input_id,
         "approved": approved,
     }));
 
     // Refresh agent info (still async)
-    if let Some(info) = state.agent_pool.get_agent_info(&id).await {
-        let _ = app_handle.emit("agent-status-changed", &info);
+    if let Some(info) = state.agent_pool.get_agent_info(&id) {
+        let _ = app_handle.emit_tracked("agent-status-changed", &info);
     }
 
     Ok(())
 }
 
+/// Approve or reject the most recent pending permission across all agents
+#[tauri::command]
+pub fn respond_to_latest_permission(
+    approved: bool,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<PendingPermissionInfo, String> {
+    let permission = state
+        .agent_pool
+        .respond_to_latest_permission(approved)
+        .map_err(|e| e.to_string())?;
+    state.resolve_stale_permissions(permission.agent_id);
+
+    let _ = app_handle.emit_tracked("permission-responded", serde_json::json!({
+        "agent_id": permission.agent_id.to_string(),
+        "input_id": permission.input_id,
+        "approved": approved,
+    }));
+    Ok(permission)
+}
+
+/// List permission requests waiting for an answer, oldest first
+#[tauri::command]
+pub fn get_pending_permissions(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<PendingPermissionInfo>, String> {
+    Ok(state.agent_pool.get_pending_permissions().list())
+}
+
+/// List permission requests waiting for an answer with the options the
+/// agent offered, so a reloaded frontend can still answer them
+#[tauri::command]
+pub fn list_pending_permissions(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<PendingPermission>, String> {
+    Ok(state.agent_pool.get_pending_permissions().list_requests())
+}
+
+/// Files agents are editing right now, oldest lock first
+#[tauri::command]
+pub fn get_file_locks(state: State<'_, Arc<AppState>>) -> Result<Vec<FileLock>, String> {
+    Ok(state.agent_pool.file_locks())
+}
+
 /// Start authentication for an agent
 #[tauri::command]
 pub async fn start_agent_auth(
@@ -235,7 +1424,7 @@ pub async fn start_agent_auth(
     }
 
     // Emit auth status
-    let _ = app_handle.emit("agent-auth-started", serde_json::json!({
+    let _ = app_handle.emit_tracked("agent-auth-started", serde_json::json!({
         "agent_id": agent_id,
         "auth_method_id": auth_method_id,
         "url": result.url,
@@ -247,16 +1436,25 @@ pub async fn start_agent_auth(
     if result.completed {
         // Try to create session now
         if let Ok(session_id) = state.agent_pool.create_session(&id).await {
-            let _ = app_handle.emit("agent-session-created", serde_json::json!({
-                "agent_id": agent_id,
-                "session_id": session_id,
-            }));
+            let _ = app_handle.emit_tracked("agent-session-created", session_created_payload(&state, &id, &session_id));
         }
     }
 
     Ok(result)
 }
 
+/// Payload of agent-session-created, with the modes and models the new
+/// session offers
+fn session_created_payload(state: &AppState, id: &Uuid, session_id: &str) -> serde_json::Value {
+    let info = state.agent_pool.get_agent_info(id);
+    serde_json::json!({
+        "agent_id": id.to_string(),
+        "session_id": session_id,
+        "modes": info.as_ref().and_then(|i| i.modes.clone()),
+        "models": info.and_then(|i| i.models),
+    })
+}
+
 /// Retry creating a session after auth (called after browser auth completes)
 #[tauri::command]
 pub async fn retry_create_session(
@@ -272,15 +1470,50 @@ pub async fn retry_create_session(
         .await
         .map_err(|e| e.to_string())?;
 
-    let _ = app_handle.emit("agent-session-created", serde_json::json!({
-        "agent_id": agent_id,
-        "session_id": session_id,
-    }));
+    let _ = app_handle.emit_tracked("agent-session-created", session_created_payload(&state, &id, &session_id));
 
     // Refresh agent info
-    if let Some(info) = state.agent_pool.get_agent_info(&id).await {
-        let _ = app_handle.emit("agent-status-changed", &info);
+    if let Some(info) = state.agent_pool.get_agent_info(&id) {
+        let _ = app_handle.emit_tracked("agent-status-changed", &info);
     }
 
     Ok(session_id)
 }
+
+/// Sessions the agent has stored for its working directory, for offering to
+/// resume one after respawning it
+#[tauri::command]
+pub async fn list_agent_sessions(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<SessionListEntry>, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state
+        .agent_pool
+        .list_sessions(&id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Switch an agent to one of its earlier sessions via session/load
+#[tauri::command]
+pub async fn resume_agent_session(
+    agent_id: String,
+    session_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<AgentInfo, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    state
+        .agent_pool
+        .load_session(&id, &session_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let info = state
+        .agent_pool
+        .get_agent_info(&id)
+        .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+    let _ = app_handle.emit_tracked("agent-status-changed", &info);
+    Ok(info)
+}
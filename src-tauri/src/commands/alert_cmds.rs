@@ -0,0 +1,20 @@
+use crate::state::{Alert, AppState};
+use std::sync::Arc;
+use tauri::State;
+
+/// Open alerts of the factory, oldest first
+#[tauri::command]
+pub fn get_alerts(state: State<'_, Arc<AppState>>) -> Result<Vec<Alert>, String> {
+    Ok(state.alerts.list())
+}
+
+/// Mark an alert as seen; its badge stays until it is dismissed
+#[tauri::command]
+pub fn acknowledge_alert(id: u64, state: State<'_, Arc<AppState>>) -> Result<Alert, String> {
+    state.alerts.acknowledge(id)
+}
+
+#[tauri::command]
+pub fn dismiss_alert(id: u64, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    state.alerts.dismiss(id)
+}
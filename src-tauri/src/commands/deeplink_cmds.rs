@@ -0,0 +1,25 @@
+use crate::deeplink::{self, DeepLinkOutcome, PendingDeepLink};
+use tauri::AppHandle;
+
+/// Route an `acptorio://` URL to the action it describes; spawning and
+/// prompting wait for `confirm_deep_link`
+#[tauri::command]
+pub async fn handle_deep_link(url: String, app_handle: AppHandle) -> Result<DeepLinkOutcome, String> {
+    deeplink::handle(&app_handle, &url).await
+}
+
+#[tauri::command]
+pub fn list_pending_deep_links() -> Vec<PendingDeepLink> {
+    deeplink::pending()
+}
+
+/// Run a link the user accepted
+#[tauri::command]
+pub async fn confirm_deep_link(id: String, app_handle: AppHandle) -> Result<DeepLinkOutcome, String> {
+    deeplink::confirm(&app_handle, &id).await
+}
+
+#[tauri::command]
+pub fn reject_deep_link(id: String) -> Result<(), String> {
+    deeplink::reject(&id)
+}
@@ -0,0 +1,118 @@
+use crate::agent::{self_test, SelfTestReport};
+use crate::diagnostics::{self, default_bundle_path, LogLevelState};
+use crate::state::{
+    default_usage_export_path, move_data_dir, storage_status, AppState, DataDirMove, StorageStatus,
+    UsageStats,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+/// Time for the reply to reach the frontend before the app restarts
+const RESTART_DELAY: Duration = Duration::from_millis(500);
+
+/// Write a zip with recent logs, redacted protocol traces, environment
+/// checks, settings and registry state for attaching to bug reports.
+/// Returns the bundle's path.
+#[tauri::command]
+pub async fn generate_diagnostics_bundle(
+    output_path: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let path = output_path.map(PathBuf::from).unwrap_or_else(default_bundle_path);
+    let environment = diagnostics::check_environment().await;
+    let registry = state.registry.cache_state().await;
+
+    diagnostics::write_bundle(&path, &environment, &state.settings.get(), &registry)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Run spawn, initialize, session, prompt, tool call, permission, cancel and
+/// stop against a built-in scripted agent, reporting each stage
+#[tauri::command]
+pub async fn run_self_test() -> SelfTestReport {
+    self_test().await
+}
+
+/// Change the log level at runtime, for the whole app or one module
+/// (e.g. `acp::codec`). Passing `default` as a module's level removes its
+/// override.
+#[tauri::command]
+pub fn set_log_level(level: String, module: Option<String>) -> Result<LogLevelState, String> {
+    diagnostics::set_log_level(&level, module.as_deref())
+}
+
+#[tauri::command]
+pub fn get_log_levels() -> LogLevelState {
+    diagnostics::log_levels()
+}
+
+/// Counts of features used, agents spawned per provider and errors, kept on
+/// this machine only
+#[tauri::command]
+pub fn get_usage_stats(state: State<'_, Arc<AppState>>) -> UsageStats {
+    state.usage.get()
+}
+
+/// Write the usage stats to a file the user can attach to a report.
+/// Returns the file's path.
+#[tauri::command]
+pub fn export_usage_stats(
+    output_path: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let path = output_path.map(PathBuf::from).unwrap_or_else(default_usage_export_path);
+    state.usage.export(&path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn reset_usage_stats(state: State<'_, Arc<AppState>>) -> UsageStats {
+    state.usage.reset()
+}
+
+/// Whether the app data directory is writable or data only lives in memory
+/// for this session
+#[tauri::command]
+pub fn get_storage_status() -> StorageStatus {
+    storage_status()
+}
+
+/// Move layouts, caches, binaries and transcripts to `new_path`, e.g. a synced
+/// folder, and restart the app there. Worktrees stay where they are.
+#[tauri::command]
+pub async fn migrate_data_dir(
+    new_path: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<DataDirMove, String> {
+    if !state.agent_pool.list_agents().is_empty() {
+        return Err("Stop all agents before moving the data directory".to_string());
+    }
+    // Written now so they move too; the saves at exit no longer write
+    state.usage.save();
+    state.save_sessions();
+    // Closed so the copy is consistent; the app restarts on the moved file
+    let database = state.database.clone();
+    let moved = tokio::task::spawn_blocking(move || {
+        if database.is_persistent() {
+            database.close()?;
+        }
+        move_data_dir(&PathBuf::from(new_path)).inspect_err(|_| {
+            if database.is_persistent() {
+                if let Err(e) = database.reopen() {
+                    tracing::warn!("{}", e);
+                }
+            }
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(RESTART_DELAY).await;
+        app_handle.restart();
+    });
+    Ok(moved)
+}
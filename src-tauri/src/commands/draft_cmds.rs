@@ -0,0 +1,31 @@
+use crate::state::{AppState, PromptDraft};
+use std::sync::Arc;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_prompt_draft(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<PromptDraft>, String> {
+    Ok(state.drafts.get(&agent_id).await)
+}
+
+/// Save the unsent prompt of an agent; an empty text deletes the draft
+#[tauri::command]
+pub async fn save_prompt_draft(
+    agent_id: String,
+    text: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state.drafts.save(&agent_id, text).await
+}
+
+/// Keep a draft when an agent is respawned under a new id
+#[tauri::command]
+pub async fn move_prompt_draft(
+    from_agent_id: String,
+    to_agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state.drafts.move_draft(&from_agent_id, &to_agent_id).await
+}
@@ -0,0 +1,283 @@
+use crate::agent::{AgentInfo, PendingPermission};
+use crate::events::{EventReplay, TrackedEmitter, SEQ_FIELD};
+use crate::filesystem::FogState;
+use crate::recording::{default_recording_path, load_recording, RecordingStatus};
+use crate::registry::RegistryAgent;
+use crate::state::{
+    finished_tasks, tally_file_changes, Alert, AppState, AwaySummary, BudgetUsage, FactoryLayout,
+    Metrics,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Fastest and slowest replay speeds accepted
+const REPLAY_SPEED_RANGE: (f64, f64) = (0.1, 100.0);
+
+/// Get buffered events newer than `since_seq` that were sent to the calling
+/// window, for windows catching up after a reload
+#[tauri::command]
+pub fn get_recent_events(
+    since_seq: Option<u64>,
+    window: WebviewWindow,
+    state: State<'_, Arc<AppState>>,
+) -> Result<EventReplay, String> {
+    let scopes = &state.window_scopes;
+    Ok(state
+        .events
+        .replay(since_seq.unwrap_or(0), |e| scopes.received(window.label(), e)))
+}
+
+/// Sequence number of the latest emitted event
+#[tauri::command]
+pub fn get_last_event_seq(state: State<'_, Arc<AppState>>) -> Result<u64, String> {
+    Ok(state.events.last_seq())
+}
+
+/// Everything a window shows, for restoring it after a reload
+#[derive(Debug, Clone, Serialize)]
+pub struct FullState {
+    /// Last event the snapshot reflects; events newer than it are replayed
+    /// with `get_recent_events`
+    pub seq: u64,
+    pub agents: Vec<AgentInfo>,
+    pub layout: FactoryLayout,
+    pub fog: FogState,
+    pub metrics: Metrics,
+    pub pending_permissions: Vec<PendingPermission>,
+    pub registry: Vec<RegistryAgent>,
+    pub alerts: Vec<Alert>,
+}
+
+/// Snapshot of the whole app state in one call. The sequence number is read
+/// first, so replaying the events after it never misses a change; changes
+/// made while the snapshot is assembled may be applied twice.
+#[tauri::command]
+pub async fn get_full_state(state: State<'_, Arc<AppState>>) -> Result<FullState, String> {
+    let seq = state.events.last_seq();
+    Ok(FullState {
+        seq,
+        agents: state.agent_pool.list_agents(),
+        layout: state.factory.get_layout().await,
+        fog: FogState::from(state.fog.as_ref()),
+        metrics: state.metrics.get_metrics(),
+        pending_permissions: state.agent_pool.get_pending_permissions().list_requests(),
+        registry: state.registry.get_agents().await,
+        alerts: state.alerts.list(),
+    })
+}
+
+/// What happened since `since` (in seconds): finished tasks, permissions still
+/// waiting, usage against the budget, and the files changed the most
+#[tauri::command]
+pub fn get_away_summary(
+    since: u64,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AwaySummary, String> {
+    let tasks = state.agent_pool.task_graph().snapshot().tasks;
+    let (completed_tasks, failed_tasks) = finished_tasks(&tasks, since);
+    let (file_changes, file_changes_complete) = tally_file_changes(&state.events.since(0), since);
+    let budget = BudgetUsage::new(
+        state.metrics.usage_since(since),
+        state.metrics.get_metrics().total_tokens,
+        state.settings.get().token_budget,
+    );
+    Ok(AwaySummary {
+        since,
+        completed_tasks,
+        failed_tasks,
+        waiting_permissions: state.agent_pool.get_pending_permissions().list(),
+        budget,
+        file_changes,
+        file_changes_complete,
+    })
+}
+
+/// Limit agent-scoped events sent to the calling window to these agents
+#[tauri::command]
+pub fn register_window_interest(
+    agent_ids: Vec<String>,
+    window: WebviewWindow,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let ids = agent_ids
+        .iter()
+        .map(|id| Uuid::parse_str(id).map_err(|e| e.to_string()))
+        .collect::<Result<HashSet<_>, _>>()?;
+    state.window_scopes.set_interest(window.label(), ids);
+    Ok(())
+}
+
+/// Agents the calling window is scoped to, or None if it receives everything
+#[tauri::command]
+pub fn get_window_interest(
+    window: WebviewWindow,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<Vec<String>>, String> {
+    Ok(state
+        .window_scopes
+        .interest(window.label())
+        .map(|ids| ids.iter().map(|id| id.to_string()).collect()))
+}
+
+/// Go back to receiving events for every agent in the calling window
+#[tauri::command]
+pub fn clear_window_interest(
+    window: WebviewWindow,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state.window_scopes.clear(window.label());
+    Ok(())
+}
+
+/// Get updates of these agents on their `agent-update:{id}` channels in the
+/// calling window, instead of every agent's on `agent-update`; returns the
+/// agents subscribed to
+#[tauri::command]
+pub fn subscribe_agent_updates(
+    agent_ids: Vec<String>,
+    window: WebviewWindow,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, String> {
+    let ids = parse_agent_ids(&agent_ids)?;
+    let subscribed = state.window_scopes.subscribe(window.label(), &ids);
+    Ok(subscribed.iter().map(|id| id.to_string()).collect())
+}
+
+/// Stop the updates of these agents in the calling window; without
+/// subscriptions left it gets every agent's updates on `agent-update` again
+#[tauri::command]
+pub fn unsubscribe_agent_updates(
+    agent_ids: Vec<String>,
+    window: WebviewWindow,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, String> {
+    let ids = parse_agent_ids(&agent_ids)?;
+    let subscribed = state.window_scopes.unsubscribe(window.label(), &ids);
+    Ok(subscribed.iter().map(|id| id.to_string()).collect())
+}
+
+fn parse_agent_ids(agent_ids: &[String]) -> Result<Vec<Uuid>, String> {
+    agent_ids
+        .iter()
+        .map(|id| Uuid::parse_str(id).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Open (or focus) a detached chat window scoped to a single agent
+#[tauri::command]
+pub fn open_agent_window(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let label = format!("agent-{}", id.simple());
+
+    if let Some(window) = app_handle.get_webview_window(&label) {
+        window.set_focus().map_err(|e| e.to_string())?;
+        return Ok(label);
+    }
+
+    let url = WebviewUrl::App(format!("index.html?agentId={}", id).into());
+    WebviewWindowBuilder::new(&app_handle, &label, url)
+        .title("Agent chat")
+        .inner_size(480.0, 720.0)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    state.window_scopes.set_interest(&label, HashSet::from([id]));
+    Ok(label)
+}
+
+/// Start recording every emitted event to `path`, or to a new file in the
+/// app data dir
+#[tauri::command]
+pub fn start_recording(
+    path: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RecordingStatus, String> {
+    let path = path.map(PathBuf::from).unwrap_or_else(default_recording_path);
+    state.recorder.start(&path)
+}
+
+#[tauri::command]
+pub fn stop_recording(state: State<'_, Arc<AppState>>) -> Result<RecordingStatus, String> {
+    state.recorder.stop()
+}
+
+/// The recording in progress, if any
+#[tauri::command]
+pub fn get_recording_status(state: State<'_, Arc<AppState>>) -> Result<Option<RecordingStatus>, String> {
+    Ok(state.recorder.status())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayInfo {
+    pub path: String,
+    pub events: usize,
+    pub speed: f64,
+    /// Wall-clock length of the replay at this speed
+    pub duration_ms: u64,
+}
+
+/// Re-emit the events of a recording with their original timing divided by
+/// `speed`, replacing any replay in progress. Replayed events are neither
+/// sequenced nor recorded again.
+#[tauri::command]
+pub fn replay_session(
+    path: String,
+    speed: Option<f64>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<ReplayInfo, String> {
+    let speed = speed.unwrap_or(1.0);
+    if !(REPLAY_SPEED_RANGE.0..=REPLAY_SPEED_RANGE.1).contains(&speed) {
+        return Err(format!(
+            "Replay speed must be between {} and {}",
+            REPLAY_SPEED_RANGE.0, REPLAY_SPEED_RANGE.1
+        ));
+    }
+    let frames = load_recording(PathBuf::from(&path).as_path())?;
+    let info = ReplayInfo {
+        path,
+        events: frames.len(),
+        speed,
+        duration_ms: (frames.last().map_or(0, |f| f.offset_ms) as f64 / speed) as u64,
+    };
+
+    let recorder = state.recorder.clone();
+    let generation = recorder.begin_replay();
+    let _ = app_handle.emit_tracked("replay-started", &info);
+    let finished = info.clone();
+    tauri::async_runtime::spawn(async move {
+        let start = Instant::now();
+        for frame in frames {
+            let due = Duration::from_secs_f64(frame.offset_ms as f64 / 1000.0 / speed);
+            tokio::time::sleep_until(start + due).await;
+            if !recorder.is_current_replay(generation) {
+                return;
+            }
+            // Their old numbers would look like gaps to the frontend
+            let mut payload = frame.payload;
+            if let Some(map) = payload.as_object_mut() {
+                map.remove(SEQ_FIELD);
+            }
+            let _ = app_handle.emit(&frame.event, payload);
+        }
+        let _ = app_handle.emit_tracked("replay-finished", &finished);
+    });
+    Ok(info)
+}
+
+/// Stop the replay in progress
+#[tauri::command]
+pub fn stop_replay(state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    state.recorder.cancel_replay();
+    Ok(())
+}
@@ -1,6 +1,19 @@
-use crate::state::{AgentPlacement, AppState, FactoryLayout, FactoryViewport, ProjectNode};
+use crate::automation::{placement_suggestions, PlacementSuggestion};
+use crate::clock::now_secs;
+use crate::filesystem::{
+    analyze_tree, detect_workspace_members, instruction_paths, read_instructions, FogStatistics,
+    InstructionFile, ProjectScanner, ProjectSummary, SubprojectCandidate,
+};
+use crate::report::{self, ReportFormat, ReportInput};
+use crate::state::{
+    AgentPlacement, AppState, FactoryLayout, FactoryViewport, GroupAgentDefaults, PlacementStats,
+    ProjectGroup, ProjectNode, StandingOrder,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tauri::State;
+use uuid::Uuid;
 
 #[tauri::command]
 pub async fn get_factory_layout(state: State<'_, Arc<AppState>>) -> Result<FactoryLayout, String> {
@@ -12,7 +25,9 @@ pub async fn save_factory_layout(
     state: State<'_, Arc<AppState>>,
     layout: FactoryLayout,
 ) -> Result<(), String> {
-    state.factory.save_layout(layout).await
+    state.factory.save_layout(layout).await?;
+    state.sync_project_roots().await;
+    Ok(())
 }
 
 #[tauri::command]
@@ -33,8 +48,11 @@ pub async fn add_factory_project(
         grid_y,
         file_count: None,
         color_index,
+        summary: None,
     };
-    state.factory.add_project(project).await
+    let layout = state.factory.add_project(project).await?;
+    state.sync_project_roots().await;
+    Ok(layout)
 }
 
 #[tauri::command]
@@ -47,12 +65,98 @@ pub async fn update_factory_project(
     state.factory.update_project(&project_id, file_count, color_index).await
 }
 
+/// Compute language and size statistics of a factory project and cache
+/// them on its node
+#[tauri::command]
+pub async fn analyze_project(
+    state: State<'_, Arc<AppState>>,
+    project_id: String,
+) -> Result<ProjectSummary, String> {
+    let layout = state.factory.get_layout().await;
+    let project = layout
+        .projects
+        .iter()
+        .find(|p| p.id == project_id)
+        .ok_or_else(|| format!("Unknown project: {}", project_id))?;
+    let path = PathBuf::from(&project.path);
+
+    let summary = tokio::task::spawn_blocking(move || {
+        ProjectScanner::new()
+            .scan(&path)
+            .map(|tree| analyze_tree(&tree))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    state.factory.set_project_summary(&project_id, summary.clone()).await?;
+    Ok(summary)
+}
+
+/// AGENTS.md, CLAUDE.md and .cursorrules files of a factory project, as
+/// found by its last scan
+/// Workspace members of the repo at `path` (Cargo, npm, pnpm, go.work)
+/// that are not factory projects yet, for adding as sub-project nodes
+#[tauri::command]
+pub async fn detect_subprojects(
+    state: State<'_, Arc<AppState>>,
+    path: String,
+) -> Result<Vec<SubprojectCandidate>, String> {
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", path));
+    }
+    let candidates = tokio::task::spawn_blocking(move || detect_workspace_members(&root))
+        .await
+        .map_err(|e| e.to_string())?;
+    let layout = state.factory.get_layout().await;
+    Ok(candidates
+        .into_iter()
+        .filter(|c| !layout.projects.iter().any(|p| Path::new(&p.path) == Path::new(&c.path)))
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_project_instructions(
+    state: State<'_, Arc<AppState>>,
+    project_id: String,
+) -> Result<Vec<InstructionFile>, String> {
+    let layout = state.factory.get_layout().await;
+    let project = layout
+        .projects
+        .iter()
+        .find(|p| p.id == project_id)
+        .ok_or_else(|| format!("Unknown project: {}", project_id))?;
+    let root = PathBuf::from(&project.path);
+    let scanned = state.instruction_files.read().await.get(&project.path).cloned();
+
+    let (paths, files) = tokio::task::spawn_blocking(move || {
+        let paths = match scanned {
+            Some(paths) => paths,
+            None => {
+                let tree = ProjectScanner::new().scan(&root).map_err(|e| e.to_string())?;
+                instruction_paths(&tree)
+            }
+        };
+        let files = read_instructions(&root, &paths);
+        Ok::<_, String>((paths, files))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    state.instruction_files.write().await.insert(project.path.clone(), paths);
+    Ok(files)
+}
+
 #[tauri::command]
 pub async fn remove_factory_project(
     state: State<'_, Arc<AppState>>,
     project_id: String,
 ) -> Result<FactoryLayout, String> {
-    state.factory.remove_project(&project_id).await
+    let layout = state.factory.remove_project(&project_id).await?;
+    state.project_activity.forget(&project_id);
+    state.sync_project_roots().await;
+    Ok(layout)
 }
 
 #[tauri::command]
@@ -84,10 +188,33 @@ pub async fn set_agent_placement(
         name,
         working_directory,
         provider_id,
+        standing_order: None,
+        stats: PlacementStats::default(),
     };
     state.factory.set_agent_placement(placement).await
 }
 
+/// Attach a standing order to a placed agent, or remove it with None
+#[tauri::command]
+pub async fn set_standing_order(
+    state: State<'_, Arc<AppState>>,
+    agent_id: String,
+    order: Option<StandingOrder>,
+) -> Result<FactoryLayout, String> {
+    state.factory.set_standing_order(&agent_id, order).await
+}
+
+/// Give a placed agent the stats of the placement it replaces, so a
+/// restored agent keeps counting where its predecessor stopped
+#[tauri::command]
+pub async fn set_placement_stats(
+    state: State<'_, Arc<AppState>>,
+    agent_id: String,
+    stats: PlacementStats,
+) -> Result<FactoryLayout, String> {
+    state.factory.set_placement_stats(&agent_id, stats).await
+}
+
 #[tauri::command]
 pub async fn remove_agent_placement(
     state: State<'_, Arc<AppState>>,
@@ -96,6 +223,56 @@ pub async fn remove_agent_placement(
     state.factory.remove_agent_placement(&agent_id).await
 }
 
+/// Projects edited a lot by hand but not by agents lately, where placing an
+/// agent could help
+#[tauri::command]
+pub async fn get_placement_suggestions(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<PlacementSuggestion>, String> {
+    Ok(placement_suggestions(&state).await)
+}
+
+/// Write a Markdown or HTML overview of the factory for sharing its status.
+/// The format follows the file extension unless given.
+#[tauri::command]
+pub async fn export_factory_report(
+    path: String,
+    format: Option<ReportFormat>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let path = PathBuf::from(path);
+    let format = format.unwrap_or_else(|| ReportFormat::from_path(&path));
+    let layout = state.factory.get_layout().await;
+    let mut explored: HashMap<String, usize> = HashMap::new();
+    for explored_path in state.fog.explored_paths() {
+        if let Some(project) = layout.project_containing(&explored_path) {
+            *explored.entry(project.id.clone()).or_default() += 1;
+        }
+    }
+    let agents = state.agent_pool.list_agents();
+    let tasks = state.agent_pool.task_graph().snapshot().tasks;
+    let metrics = state.metrics.get_metrics();
+    let time = state.metrics.time_tracking(0);
+    let generated_at = now_secs();
+
+    let content = report::render(
+        &ReportInput {
+            layout: &layout,
+            agents: &agents,
+            tasks: &tasks,
+            metrics: &metrics,
+            time: &time,
+            explored: &explored,
+            generated_at,
+        },
+        format,
+    );
+    tokio::fs::write(&path, content)
+        .await
+        .map_err(|e| format!("Failed to write report: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 pub async fn set_factory_viewport(
     state: State<'_, Arc<AppState>>,
@@ -110,3 +287,75 @@ pub async fn set_factory_viewport(
     };
     state.factory.set_viewport(viewport).await
 }
+
+#[tauri::command]
+pub async fn create_project_group(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    project_ids: Vec<String>,
+) -> Result<FactoryLayout, String> {
+    let group = ProjectGroup {
+        id: Uuid::new_v4().to_string(),
+        name,
+        project_ids,
+        agent_defaults: GroupAgentDefaults::default(),
+    };
+    state.factory.create_group(group).await
+}
+
+/// Delete a group; its projects stay in the factory
+#[tauri::command]
+pub async fn delete_project_group(
+    state: State<'_, Arc<AppState>>,
+    group_id: String,
+) -> Result<FactoryLayout, String> {
+    state.factory.delete_group(&group_id).await
+}
+
+/// Move a project into a group, out of any other it was in
+#[tauri::command]
+pub async fn add_project_to_group(
+    state: State<'_, Arc<AppState>>,
+    group_id: String,
+    project_id: String,
+) -> Result<FactoryLayout, String> {
+    state.factory.add_project_to_group(&group_id, &project_id).await
+}
+
+#[tauri::command]
+pub async fn remove_project_from_group(
+    state: State<'_, Arc<AppState>>,
+    project_id: String,
+) -> Result<FactoryLayout, String> {
+    state.factory.remove_project_from_group(&project_id).await
+}
+
+#[tauri::command]
+pub async fn set_group_agent_defaults(
+    state: State<'_, Arc<AppState>>,
+    group_id: String,
+    defaults: GroupAgentDefaults,
+) -> Result<FactoryLayout, String> {
+    if let Some(preset) = &defaults.preset {
+        if !state.settings.get().spawn_presets.iter().any(|p| &p.name == preset) {
+            return Err(format!("Unknown spawn preset: {}", preset));
+        }
+    }
+    state.factory.set_group_agent_defaults(&group_id, defaults).await
+}
+
+/// Exploration of a group's projects taken as one, per project root
+#[tauri::command]
+pub async fn get_group_fog_statistics(
+    state: State<'_, Arc<AppState>>,
+    group_id: String,
+) -> Result<FogStatistics, String> {
+    let layout = state.factory.get_layout().await;
+    let roots: Vec<String> = layout
+        .group_projects(&group_id)?
+        .iter()
+        .map(|p| p.path.clone())
+        .collect();
+    let trees = state.project_trees(&roots).await;
+    Ok(state.fog.statistics_across(&trees))
+}
@@ -1,12 +1,16 @@
-use crate::filesystem::{FogState, ProjectTree, FileSystemWatcher};
-use crate::state::{AppState, Metrics};
+use crate::clock::{now_ms, now_secs};
+use crate::events::TrackedEmitter;
+use crate::filesystem::{
+    content_hash, diff_trees, fuzzy_find_files, resolve_editor, CachedTree, EditorLaunch,
+    ExplorationMilestone, FogDelta, FogState, FogStatistics, FogVisibility, FoundFile, HeatEntry,
+    ProjectTree, WatcherStatus,
+};
+use crate::agent::LeaderboardRange;
+use crate::state::{AppState, Metrics, TimeTracking};
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Emitter, State};
-use once_cell::sync::Lazy;
-
-// Global file watcher - we only need one at a time
-static FILE_WATCHER: Lazy<Mutex<Option<FileSystemWatcher>>> = Lazy::new(|| Mutex::new(None));
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
 
 #[tauri::command]
 pub async fn scan_project(
@@ -14,29 +18,79 @@ pub async fn scan_project(
     state: State<'_, Arc<AppState>>,
     app_handle: AppHandle,
 ) -> Result<ProjectTree, String> {
-    let path_buf = PathBuf::from(&path);
-    let tree = state.load_project(path_buf.clone()).await?;
-
-    // Start file watcher for this project
-    if let Ok(mut watcher_guard) = FILE_WATCHER.lock() {
-        // Create new watcher (drops old one if exists)
-        match FileSystemWatcher::new(app_handle.clone()) {
-            Ok(mut watcher) => {
-                if let Err(e) = watcher.watch(&path_buf) {
-                    eprintln!("Failed to watch directory: {}", e);
-                } else {
-                    println!("File watcher started for: {}", path);
+    open_project(&path, &state, &app_handle).await
+}
+
+/// Load a project, start watching it, and notify the frontend
+pub(crate) async fn open_project(
+    path: &str,
+    state: &AppState,
+    app_handle: &AppHandle,
+) -> Result<ProjectTree, String> {
+    let path_buf = PathBuf::from(path);
+    let tree = match state.load_cached_project(path_buf.clone()).await {
+        Some(cached) => {
+            let tree = cached.tree.clone();
+            spawn_rescan(app_handle.clone(), cached);
+            tree
+        }
+        None => state.load_project(path_buf.clone()).await?,
+    };
+
+    if let Err(e) = state.watcher.watch_project(app_handle, &path_buf) {
+        tracing::warn!("Failed to watch {}: {}", path, e);
+    }
+
+    let _ = app_handle.emit_tracked("project-loaded", &tree);
+    Ok(tree)
+}
+
+/// Rescan a project opened from its cached tree and send what changed
+fn spawn_rescan(app_handle: AppHandle, cached: CachedTree) {
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<Arc<AppState>>();
+        let root = PathBuf::from(&cached.tree.root);
+        // Another project was opened meanwhile
+        if state.get_project_path().await.as_ref() != Some(&root) {
+            return;
+        }
+        match state.load_project(root).await {
+            Ok(tree) if content_hash(&tree) != cached.content_hash => {
+                let delta = diff_trees(&cached.tree, &tree);
+                if !delta.is_empty() {
+                    let _ = app_handle.emit_tracked("project-tree-delta", &delta);
                 }
-                *watcher_guard = Some(watcher);
-            }
-            Err(e) => {
-                eprintln!("Failed to create file watcher: {}", e);
             }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Background rescan of {} failed: {}", cached.tree.root, e),
         }
+    });
+}
+
+#[tauri::command]
+pub fn get_watcher_status(state: State<'_, Arc<AppState>>) -> Result<WatcherStatus, String> {
+    Ok(state.watcher.status())
+}
+
+/// Watch a directory besides the open project, e.g. a sibling repo agents
+/// work in; its changes reach `fs-change` like the project's
+#[tauri::command]
+pub fn watch_path(
+    path: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    if !path.is_dir() {
+        return Err(format!("Not a directory: {}", path.display()));
     }
+    state.watcher.watch(&app_handle, &path)
+}
 
-    let _ = app_handle.emit("project-loaded", &tree);
-    Ok(tree)
+/// Stop watching a path, the open project's included
+#[tauri::command]
+pub fn unwatch_path(path: String, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    state.watcher.unwatch(&PathBuf::from(path))
 }
 
 #[tauri::command]
@@ -57,21 +111,133 @@ pub fn reveal_file(path: String, state: State<'_, Arc<AppState>>) -> Result<(),
     Ok(())
 }
 
+/// Reveal a directory of the open project, and everything below it when
+/// `recursive`. Returns the newly explored paths.
+#[tauri::command]
+pub fn reveal_directory(
+    path: String,
+    recursive: bool,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, String> {
+    let revealed = state
+        .fog
+        .reveal_subtree(&path, recursive)
+        .ok_or_else(|| format!("Not a directory of the project: {}", path))?;
+    // Sent with the next batch to every other listener
+    for path in &revealed {
+        state.fog_reveals.push(path);
+    }
+    Ok(revealed)
+}
+
+/// Every explored path; for the initial load, use `get_fog_delta` after it
 #[tauri::command]
 pub fn get_fog_state(state: State<'_, Arc<AppState>>) -> Result<FogState, String> {
     Ok(FogState::from(state.fog.as_ref()))
 }
 
+/// Paths revealed and removed after `since_seq`
+#[tauri::command]
+pub fn get_fog_delta(since_seq: u64, state: State<'_, Arc<AppState>>) -> Result<FogDelta, String> {
+    Ok(state.fog.delta(since_seq))
+}
+
+/// How much of the open project is explored, overall and per top-level
+/// directory
+#[tauri::command]
+pub fn get_fog_statistics(state: State<'_, Arc<AppState>>) -> Result<FogStatistics, String> {
+    Ok(state.fog.statistics())
+}
+
+/// Forget explored paths that are no longer in the project, e.g. files
+/// deleted while the watcher was not running. Returns how many went.
+#[tauri::command]
+pub fn prune_fog(state: State<'_, Arc<AppState>>) -> Result<usize, String> {
+    Ok(state.fog.prune_stale())
+}
+
+/// Most touched files, for rendering churn hotspots
+#[tauri::command]
+pub fn get_heatmap(
+    top_n: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<HeatEntry>, String> {
+    Ok(state.fog.heatmap(top_n.unwrap_or(50)))
+}
+
+/// Fuzzy-match file names across the open project and the factory's
+/// projects, or only those of a project group, for quick-open; files agents
+/// touched lately rank higher
+#[tauri::command]
+pub async fn find_files(
+    query: String,
+    limit: Option<usize>,
+    group_id: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<FoundFile>, String> {
+    let layout = state.factory.get_layout().await;
+    let roots: Vec<String> = match group_id {
+        Some(group_id) => layout
+            .group_projects(&group_id)?
+            .iter()
+            .map(|p| p.path.clone())
+            .collect(),
+        None => {
+            let open = state.get_project_tree().await.map(|t| t.root);
+            open.into_iter()
+                .chain(layout.projects.iter().map(|p| p.path.clone()))
+                .collect()
+        }
+    };
+    let trees = state.project_trees(&roots).await;
+    let heat = state.fog.heatmap(usize::MAX);
+    let explored: HashSet<String> = state.fog.explored_paths().into_iter().collect();
+    let now_ms = now_ms();
+
+    tokio::task::spawn_blocking(move || {
+        fuzzy_find_files(&trees, &query, limit.unwrap_or(50), &heat, &explored, now_ms)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn is_file_explored(path: String, state: State<'_, Arc<AppState>>) -> Result<bool, String> {
     Ok(state.fog.is_explored(&path))
 }
 
+/// Exploration milestones of the current project, reached or not
+#[tauri::command]
+pub fn get_exploration_milestones(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ExplorationMilestone>, String> {
+    Ok(state.fog.milestones())
+}
+
+#[tauri::command]
+pub fn get_file_visibility(
+    path: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FogVisibility, String> {
+    Ok(state.fog.visibility(&path))
+}
+
 #[tauri::command]
 pub fn get_metrics(state: State<'_, Arc<AppState>>) -> Result<Metrics, String> {
     Ok(state.metrics.get_metrics())
 }
 
+/// Wall-clock time agents spent working within `range`, per project and
+/// agent; all time by default
+#[tauri::command]
+pub fn get_time_tracking(
+    range: Option<LeaderboardRange>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<TimeTracking, String> {
+    let now = now_secs();
+    Ok(state.metrics.time_tracking(range.unwrap_or_default().since(now)))
+}
+
 #[tauri::command]
 pub fn reset_metrics(state: State<'_, Arc<AppState>>) -> Result<(), String> {
     state.metrics.reset();
@@ -85,6 +251,42 @@ pub async fn read_file(path: String) -> Result<String, String> {
         .map_err(|e| e.to_string())
 }
 
+/// Open a file in the configured editor, optionally at a line
+#[tauri::command]
+pub fn open_in_editor(
+    path: String,
+    line: Option<u32>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let editor = state.settings.get().editor;
+
+    match resolve_editor(editor.as_deref(), &path, line) {
+        EditorLaunch::Url(url) => {
+            tauri_plugin_opener::open_url(url, None::<&str>).map_err(|e| e.to_string())
+        }
+        EditorLaunch::Command { program, args } => {
+            let mut child = std::process::Command::new(&program)
+                .args(&args)
+                .spawn()
+                .map_err(|e| format!("Failed to launch {}: {}", program, e))?;
+            // Reaped once the editor exits so it doesn't linger as a zombie
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+            Ok(())
+        }
+        EditorLaunch::Default => {
+            tauri_plugin_opener::open_path(&path, None::<&str>).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Show a file or directory in the OS file manager
+#[tauri::command]
+pub fn reveal_in_file_manager(path: String) -> Result<(), String> {
+    tauri_plugin_opener::reveal_item_in_dir(&path).map_err(|e| e.to_string())
+}
+
 /// Count files in a directory recursively (ignores hidden files and common ignore patterns)
 #[tauri::command]
 pub async fn count_files(path: String) -> Result<u32, String> {
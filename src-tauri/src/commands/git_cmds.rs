@@ -0,0 +1,141 @@
+use crate::acp::Transport;
+use crate::agent::AgentInfo;
+use crate::commands::agent_cmds::spawn_agent_process;
+use crate::events::TrackedEmitter;
+use crate::git::{remove_worktree, Checkpoint, Worktree};
+use crate::state::AppState;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use uuid::Uuid;
+
+/// Get the checkpoint taken before a task ran, if any
+#[tauri::command]
+pub fn get_checkpoint(
+    task_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<Checkpoint>, String> {
+    Ok(state.agent_pool.checkpoints().get(&task_id))
+}
+
+/// Revert everything an agent did during a task
+#[tauri::command]
+pub async fn rollback_to_checkpoint(
+    task_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<Checkpoint, String> {
+    let checkpoint = state
+        .agent_pool
+        .checkpoints()
+        .rollback(&task_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let _ = app_handle.emit_tracked("checkpoint-restored", &checkpoint);
+    Ok(checkpoint)
+}
+
+/// Spawn an agent in a dedicated worktree of a factory project on `branch`
+#[tauri::command]
+pub async fn spawn_agent_in_worktree(
+    project_id: String,
+    branch: String,
+    name: String,
+    provider_id: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<AgentInfo, String> {
+    let layout = state.factory.get_layout().await;
+    let project = layout
+        .projects
+        .iter()
+        .find(|p| p.id == project_id)
+        .ok_or_else(|| format!("Unknown project: {}", project_id))?;
+
+    let worktree = state
+        .worktrees
+        .create(&project_id, Path::new(&project.path), &branch)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let info = match spawn_agent_process(
+        &state,
+        name,
+        worktree.path.clone(),
+        provider_id,
+        Transport::Stdio,
+    )
+    .await
+    {
+        Ok(info) => info,
+        Err(e) => {
+            // Don't leave an orphaned worktree, or a branch made for it, behind
+            if let Err(cleanup) = remove_worktree(&worktree, worktree.created_branch).await {
+                tracing::warn!("Failed to remove worktree {}: {}", worktree.path, cleanup);
+            }
+            return Err(e);
+        }
+    };
+
+    let worktree = state.worktrees.assign(info.id, worktree);
+    let _ = app_handle.emit_tracked("agent-spawned", &info);
+    let _ = app_handle.emit_tracked("worktree-created", &worktree);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn get_agent_worktree(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<Worktree>, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    Ok(state.worktrees.get(&id))
+}
+
+#[tauri::command]
+pub fn list_worktrees(state: State<'_, Arc<AppState>>) -> Result<Vec<Worktree>, String> {
+    Ok(state.worktrees.list())
+}
+
+/// Commit the agent's work and merge its branch back into the project checkout
+#[tauri::command]
+pub async fn merge_worktree(
+    agent_id: String,
+    remove: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<Worktree, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+
+    let worktree = state.worktrees.merge(&id).await.map_err(|e| e.to_string())?;
+    if remove.unwrap_or(false) {
+        state
+            .worktrees
+            .remove(&id)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let _ = app_handle.emit_tracked("worktree-merged", &worktree);
+    Ok(worktree)
+}
+
+/// Throw away the agent's worktree, and its branch if it was created for it
+#[tauri::command]
+pub async fn discard_worktree(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<Worktree, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+
+    let worktree = state
+        .worktrees
+        .remove(&id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let _ = app_handle.emit_tracked("worktree-removed", &worktree);
+    Ok(worktree)
+}
@@ -0,0 +1,39 @@
+use crate::agent::TaskInfo;
+use crate::state::{ActivityEntry, AppState, AuditEntry, HistoryFilter, MetricsHistory};
+use std::sync::Arc;
+use tauri::State;
+
+/// Finished and running tasks, newest first; `kind` filters on the status
+#[tauri::command]
+pub async fn get_task_history(
+    filter: Option<HistoryFilter>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<TaskInfo>, String> {
+    state.database.task_history(&filter.unwrap_or_default())
+}
+
+/// Tool calls, permission requests and plans agents reported
+#[tauri::command]
+pub async fn get_activity_feed(
+    filter: Option<HistoryFilter>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ActivityEntry>, String> {
+    state.database.activity(&filter.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn get_audit_log(
+    filter: Option<HistoryFilter>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<AuditEntry>, String> {
+    state.database.audit_log(&filter.unwrap_or_default())
+}
+
+/// Usage samples and work spans recorded across sessions
+#[tauri::command]
+pub async fn get_metrics_history(
+    filter: Option<HistoryFilter>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<MetricsHistory, String> {
+    state.database.metrics_history(&filter.unwrap_or_default())
+}
@@ -0,0 +1,116 @@
+use super::agent_cmds::spawn_update_forwarder;
+use crate::agent::{
+    claude_projects_dir, find_claude_sessions, import_seed_prompt, parse_claude_session,
+    CliSessionFile, ContinuationMethod, ConversationContinuation, ImportedConversation,
+    PromptPriority,
+};
+use crate::events::TrackedEmitter;
+use crate::state::{AppState, ConversationSummary};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use uuid::Uuid;
+
+/// List `claude` CLI session files, optionally only those of one project
+#[tauri::command]
+pub async fn list_cli_sessions(project_path: Option<String>) -> Result<Vec<CliSessionFile>, String> {
+    let projects_dir = claude_projects_dir().ok_or("Could not determine home directory")?;
+    tokio::task::spawn_blocking(move || {
+        find_claude_sessions(&projects_dir, project_path.as_deref().map(Path::new))
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Import a `claude` CLI session file into the conversation store
+#[tauri::command]
+pub async fn import_cli_session(
+    path: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<ImportedConversation, String> {
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let conversation = parse_claude_session(&content, &path)?;
+    state.conversations.save(&conversation)?;
+
+    let _ = app_handle.emit_tracked("conversation-imported", ConversationSummary::from(&conversation));
+    Ok(conversation)
+}
+
+#[tauri::command]
+pub fn list_imported_conversations(state: State<'_, Arc<AppState>>) -> Result<Vec<ConversationSummary>, String> {
+    Ok(state.conversations.list())
+}
+
+#[tauri::command]
+pub fn get_imported_conversation(
+    conversation_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ImportedConversation, String> {
+    state.conversations.get(&conversation_id)
+}
+
+#[tauri::command]
+pub fn delete_imported_conversation(
+    conversation_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state.conversations.remove(&conversation_id)
+}
+
+/// Continue an imported conversation on an agent: resume the original session
+/// when the agent supports `session/load`, otherwise seed its session with the
+/// transcript
+#[tauri::command]
+pub async fn continue_imported_conversation(
+    agent_id: String,
+    conversation_id: String,
+    prefer_session_load: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<ConversationContinuation, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let conversation = state.conversations.get(&conversation_id)?;
+    let info = state
+        .agent_pool
+        .get_agent_info(&id)
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+
+    let mut continuation = ConversationContinuation {
+        conversation_id,
+        agent_id: id,
+        method: ContinuationMethod::SessionLoad,
+        response: None,
+    };
+
+    let loaded = match &conversation.session_id {
+        Some(session_id) if prefer_session_load.unwrap_or(true) && info.supports_load_session => {
+            match state.agent_pool.load_session(&id, session_id).await {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("session/load failed, seeding with transcript instead: {}", e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+
+    if !loaded {
+        let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+        let response = state
+            .agent_pool
+            .send_prompt_as_task(id, &import_seed_prompt(&conversation), PromptPriority::Normal, tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        continuation.method = ContinuationMethod::SeedPrompt;
+        continuation.response = Some(response);
+    }
+
+    if let Some(info) = state.agent_pool.get_agent_info(&id) {
+        let _ = app_handle.emit_tracked("agent-status-changed", &info);
+    }
+    Ok(continuation)
+}
@@ -1,9 +1,37 @@
 pub mod agent_cmds;
+pub mod alert_cmds;
+pub mod deeplink_cmds;
+pub mod diagnostics_cmds;
+pub mod draft_cmds;
+pub mod event_cmds;
 pub mod factory_cmds;
 pub mod fs_cmds;
+pub mod git_cmds;
+pub mod history_cmds;
+pub mod import_cmds;
+pub mod onboarding_cmds;
+pub mod plugin_cmds;
+pub mod project_cmds;
 pub mod registry_cmds;
+pub mod scratchpad_cmds;
+pub mod settings_cmds;
+pub mod simulation_cmds;
 
 pub use agent_cmds::*;
+pub use alert_cmds::*;
+pub use deeplink_cmds::*;
+pub use diagnostics_cmds::*;
+pub use draft_cmds::*;
+pub use event_cmds::*;
 pub use factory_cmds::*;
 pub use fs_cmds::*;
+pub use git_cmds::*;
+pub use history_cmds::*;
+pub use import_cmds::*;
+pub use onboarding_cmds::*;
+pub use plugin_cmds::*;
+pub use project_cmds::*;
 pub use registry_cmds::*;
+pub use scratchpad_cmds::*;
+pub use settings_cmds::*;
+pub use simulation_cmds::*;
@@ -0,0 +1,128 @@
+use super::fs_cmds::open_project;
+use crate::agent::{api_key_variable, set_keychain_variables, store_api_key};
+use crate::diagnostics::{self, check_tool};
+use crate::registry::DEMO_AGENT_ID;
+use crate::state::{
+    create_sample_project, AppState, OnboardingStatus, OnboardingStep, OnboardingStore,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// What a step needs from the user; each step reads its own fields
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OnboardingInput {
+    /// For `pick_provider`
+    pub provider_id: Option<String>,
+    /// For `store_api_key`
+    pub api_key: Option<String>,
+    /// Variable the key is for; known for the usual providers
+    pub variable: Option<String>,
+    /// For `open_sample_project`; the bundled sample project if omitted
+    pub project_path: Option<String>,
+}
+
+#[tauri::command]
+pub fn get_onboarding_status(state: State<'_, Arc<AppState>>) -> Result<OnboardingStatus, String> {
+    Ok(state.onboarding.status())
+}
+
+/// Run a step of the first-run setup, or skip it if it is optional. A step
+/// that fails stays pending, so the wizard can offer to try again.
+#[tauri::command]
+pub async fn complete_onboarding_step(
+    step: OnboardingStep,
+    input: Option<OnboardingInput>,
+    skip: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<OnboardingStatus, String> {
+    let onboarding = &state.onboarding;
+    if skip.unwrap_or(false) {
+        onboarding.finish_step(step, true, |_| {})?;
+        return Ok(onboarding.status());
+    }
+    let input = input.unwrap_or_default();
+
+    match step {
+        OnboardingStep::CheckEnvironment => {
+            let mut report = diagnostics::check_environment().await;
+            let package_runner = state.settings.get().package_runner(true);
+            if !report.tools.iter().any(|t| t.name == package_runner) {
+                report.tools.push(check_tool(&package_runner).await);
+            }
+            let missing: Vec<String> = report
+                .tools
+                .iter()
+                .filter(|t| (t.name == "node" || t.name == package_runner) && t.version.is_none())
+                .map(|t| t.name.clone())
+                .collect();
+            onboarding.set_environment(report);
+            if !missing.is_empty() {
+                return Err(format!(
+                    "Not found: {}. Install Node.js and try again.",
+                    missing.join(", ")
+                ));
+            }
+            onboarding.finish_step(step, false, |_| {})?;
+        }
+        OnboardingStep::FetchRegistry => {
+            state.registry.refresh().await?;
+            if state.registry.get_agents().await.is_empty() {
+                return Err("The registry lists no agents".to_string());
+            }
+            onboarding.finish_step(step, false, |_| {})?;
+        }
+        OnboardingStep::PickProvider => {
+            let provider_id = input.provider_id.ok_or("Pick a provider")?;
+            let known = provider_id == DEMO_AGENT_ID
+                || state.registry.get_agent(&provider_id).await.is_some();
+            if !known {
+                return Err(format!("Unknown provider: {}", provider_id));
+            }
+            let mut settings = state.settings.get();
+            settings.default_provider_id = Some(provider_id.clone());
+            state.settings.update(settings)?;
+            onboarding.finish_step(step, false, |p| p.provider_id = Some(provider_id))?;
+        }
+        OnboardingStep::StoreApiKey => {
+            let api_key = input.api_key.filter(|k| !k.trim().is_empty()).ok_or("Enter an API key")?;
+            let variable = input
+                .variable
+                .or_else(|| {
+                    let provider_id = onboarding.progress().provider_id?;
+                    api_key_variable(&provider_id).map(str::to_string)
+                })
+                .ok_or("Name the environment variable the provider reads its key from")?;
+            store_api_key(&variable, api_key.trim())?;
+
+            let mut settings = state.settings.get();
+            if !settings.keychain_variables.contains(&variable) {
+                settings.keychain_variables.push(variable.clone());
+                let settings = state.settings.update(settings)?;
+                set_keychain_variables(&settings.keychain_variables);
+            }
+            onboarding.finish_step(step, false, |p| p.api_key_variable = Some(variable))?;
+        }
+        OnboardingStep::OpenSampleProject => {
+            let path = match input.project_path {
+                Some(path) => path,
+                None => {
+                    let path = OnboardingStore::sample_project_path();
+                    create_sample_project(&path)?;
+                    path.display().to_string()
+                }
+            };
+            open_project(&path, &state, &app_handle).await?;
+            onboarding.finish_step(step, false, |p| p.project_path = Some(path))?;
+        }
+    }
+    Ok(onboarding.status())
+}
+
+/// Show the setup wizard again from the first step
+#[tauri::command]
+pub fn reset_onboarding(state: State<'_, Arc<AppState>>) -> Result<OnboardingStatus, String> {
+    state.onboarding.reset()?;
+    Ok(state.onboarding.status())
+}
@@ -0,0 +1,20 @@
+use crate::plugins::{PluginInfo, PLUGINS};
+use serde_json::Value;
+
+/// Plugins registered at startup and the commands they answer
+#[tauri::command]
+pub fn list_plugins() -> Vec<PluginInfo> {
+    PLUGINS.list()
+}
+
+/// Run a command a plugin added; `args` and the result are up to the plugin
+#[tauri::command]
+pub async fn call_plugin_command(
+    plugin: String,
+    command: String,
+    args: Option<Value>,
+) -> Result<Value, String> {
+    PLUGINS
+        .call(&plugin, &command, args.unwrap_or(Value::Null))
+        .await
+}
@@ -0,0 +1,384 @@
+use super::agent_cmds::{spawn_agent_process, spawn_update_forwarder, stop_agent_process};
+use crate::acp::Transport;
+use crate::agent::{
+    build_follow_up_prompt, pack_files, PromptPriority, TaskInfo, TaskSpec, TaskStatus, TaskVerification,
+    VerificationStatus, MAX_FOLLOW_UPS,
+};
+use crate::events::TrackedEmitter;
+use crate::filesystem::{search_project, FileMatches};
+use crate::messages::{LocalizedMessage, MessageKey};
+use crate::runner::{
+    self, find_project_commands, CommandOutputLine, ProjectCommandKind, ProjectCommandRun,
+};
+use crate::state::{AgentPlacement, AlertKind, AppState};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Outcome for one agent of a bulk operation on a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectAgentResult {
+    pub project_id: String,
+    pub agent_id: String,
+    /// Agent that replaced it, for a restart
+    pub new_agent_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Placements of agents connected to a project
+async fn project_placements(state: &AppState, project_id: &str) -> Result<Vec<AgentPlacement>, String> {
+    let layout = state.factory.get_layout().await;
+    if !layout.projects.iter().any(|p| p.id == project_id) {
+        return Err(format!("Unknown project: {}", project_id));
+    }
+    Ok(layout
+        .agent_placements
+        .into_iter()
+        .filter(|p| p.connected_project_id.as_deref() == Some(project_id))
+        .collect())
+}
+
+async fn stop_placed_agent(state: &AppState, app_handle: &AppHandle, agent_id: &str) -> Result<(), String> {
+    let id = Uuid::parse_str(agent_id).map_err(|e| e.to_string())?;
+    // A placement may outlive its agent
+    if state.agent_pool.get_agent_info(&id).is_none() {
+        return Ok(());
+    }
+    stop_agent_process(state, &id).await?;
+    let _ = app_handle.emit_tracked("agent-stopped", serde_json::json!({ "agent_id": agent_id }));
+    Ok(())
+}
+
+/// Stop and respawn a placed agent, moving its placement to the new agent
+async fn restart_placed_agent(
+    state: &AppState,
+    app_handle: &AppHandle,
+    placement: &AgentPlacement,
+) -> Result<String, String> {
+    let (Some(name), Some(working_directory)) = (&placement.name, &placement.working_directory) else {
+        return Err("Placement has no agent to restart".to_string());
+    };
+    stop_placed_agent(state, app_handle, &placement.agent_id).await?;
+    let info = spawn_agent_process(
+        state,
+        name.clone(),
+        working_directory.clone(),
+        placement.provider_id.clone(),
+        Transport::default(),
+    )
+    .await?;
+    let _ = app_handle.emit_tracked("agent-spawned", &info);
+
+    let new_agent_id = info.id.to_string();
+    state.drafts.move_draft(&placement.agent_id, &new_agent_id).await?;
+    state.factory.remove_agent_placement(&placement.agent_id).await?;
+    state
+        .factory
+        .set_agent_placement(AgentPlacement {
+            agent_id: new_agent_id.clone(),
+            ..placement.clone()
+        })
+        .await?;
+    Ok(new_agent_id)
+}
+
+/// Stop every agent connected to a project. Placements stay so the agents
+/// can be restarted.
+#[tauri::command]
+pub async fn stop_project_agents(
+    project_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<Vec<ProjectAgentResult>, String> {
+    let mut results = Vec::new();
+    for placement in project_placements(&state, &project_id).await? {
+        let result = ProjectAgentResult {
+            project_id: project_id.clone(),
+            error: stop_placed_agent(&state, &app_handle, &placement.agent_id).await.err(),
+            agent_id: placement.agent_id,
+            new_agent_id: None,
+        };
+        let _ = app_handle.emit_tracked("project-agent-result", &result);
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Restart every agent connected to a project with its placement's name,
+/// working directory and provider
+#[tauri::command]
+pub async fn restart_project_agents(
+    project_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<Vec<ProjectAgentResult>, String> {
+    let mut results = Vec::new();
+    for placement in project_placements(&state, &project_id).await? {
+        let restarted = restart_placed_agent(&state, &app_handle, &placement).await;
+        let result = ProjectAgentResult {
+            project_id: project_id.clone(),
+            agent_id: placement.agent_id,
+            new_agent_id: restarted.as_ref().ok().cloned(),
+            error: restarted.err(),
+        };
+        let _ = app_handle.emit_tracked("project-agent-result", &result);
+        results.push(result);
+    }
+    let _ = app_handle.emit_tracked("factory-layout-changed", ());
+    Ok(results)
+}
+
+/// Most matched files dispatched by one search
+const MAX_DISPATCH_FILES: usize = 100;
+/// Matched files attached to one prompt
+const DISPATCH_BATCH_FILES: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchDispatch {
+    pub matches: Vec<FileMatches>,
+    /// One task per batch of files, each waiting for the previous one
+    pub tasks: Vec<TaskInfo>,
+}
+
+/// Prompt for one batch of matched files: `{query}` is the search query,
+/// `{files}` the batch's paths one per line and `{count}` their number
+fn render_dispatch_prompt(template: &str, query: &str, batch: &[FileMatches]) -> String {
+    let files: Vec<&str> = batch.iter().map(|f| f.path.as_str()).collect();
+    template
+        .replace("{query}", query)
+        .replace("{files}", &files.join("\n"))
+        .replace("{count}", &files.len().to_string())
+}
+
+/// Search a project for `query` and send the matching files to an agent in
+/// batches, with `prompt_template` rendered for each batch
+#[tauri::command]
+pub async fn dispatch_for_matches(
+    project_id: String,
+    query: String,
+    prompt_template: String,
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<MatchDispatch, String> {
+    let id = Uuid::parse_str(&agent_id).map_err(|e| e.to_string())?;
+    let layout = state.factory.get_layout().await;
+    let project = layout
+        .projects
+        .iter()
+        .find(|p| p.id == project_id)
+        .ok_or_else(|| format!("Unknown project: {}", project_id))?;
+    let root = Path::new(&project.path).to_path_buf();
+
+    let search_root = root.clone();
+    let search_query = query.clone();
+    let matches = tokio::task::spawn_blocking(move || {
+        search_project(&search_root, &search_query, MAX_DISPATCH_FILES)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if matches.is_empty() {
+        return Err(format!("No files in {} match '{}'", project.name, query));
+    }
+
+    let tx = spawn_update_forwarder(app_handle.clone(), state.inner().clone());
+    let mut tasks: Vec<TaskInfo> = Vec::new();
+    for batch in matches.chunks(DISPATCH_BATCH_FILES) {
+        let paths: Vec<String> = batch.iter().map(|f| f.path.clone()).collect();
+        let context = pack_files(&root, &paths).await;
+        for path in &context.included {
+            let full_path = root.join(path).to_string_lossy().to_string();
+            state.fog.touch(&full_path);
+            state.fog_reveals.push(&full_path);
+        }
+        let spec = TaskSpec {
+            agent_id: id,
+            prompt: render_dispatch_prompt(&prompt_template, &query, batch),
+            depends_on: tasks.last().map(|t| vec![t.id.clone()]).unwrap_or_default(),
+            inject_results: false,
+            context: Some(context),
+            priority: PromptPriority::Normal,
+            preamble: None,
+        };
+        let task = state
+            .agent_pool
+            .submit_task(spec, tx.clone())
+            .map_err(|e| e.to_string())?;
+        tasks.push(task);
+    }
+
+    Ok(MatchDispatch { matches, tasks })
+}
+
+/// Wait until a task has finished
+async fn wait_for_task(state: &AppState, task_id: &str) -> Result<TaskInfo, String> {
+    let graph = state.agent_pool.task_graph();
+    // Subscribe before looking so the transition cannot be missed
+    let mut events = graph.subscribe();
+    loop {
+        let task = graph
+            .get(task_id)
+            .ok_or_else(|| format!("Unknown task: {}", task_id))?;
+        if task.status.is_finished() {
+            return Ok(task);
+        }
+        match events.recv().await {
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return Err("Task graph closed".to_string()),
+        }
+    }
+}
+
+/// Channel whose lines are emitted as `project-command-output` events
+fn spawn_output_forwarder(app_handle: AppHandle) -> mpsc::Sender<CommandOutputLine> {
+    let (tx, mut rx) = mpsc::channel(256);
+    tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            let _ = app_handle.emit_tracked("project-command-output", &line);
+        }
+    });
+    tx
+}
+
+/// Run a project's configured build, test or lint command in its directory,
+/// streaming output as `project-command-output` events. With `after_task`
+/// it waits for that task and runs only if the task completed, verifying
+/// the agent's work.
+#[tauri::command]
+pub async fn run_project_command(
+    project_id: String,
+    kind: ProjectCommandKind,
+    after_task: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<ProjectCommandRun, String> {
+    let layout = state.factory.get_layout().await;
+    let project = layout
+        .projects
+        .iter()
+        .find(|p| p.id == project_id)
+        .ok_or_else(|| format!("Unknown project: {}", project_id))?;
+    let settings = state.settings.get();
+    let command = find_project_commands(&settings.project_commands, &project.path)
+        .and_then(|c| c.get(kind))
+        .ok_or_else(|| format!("No {} command configured for {}", kind, project.name))?
+        .to_string();
+
+    let mut agent_id = None;
+    if let Some(task_id) = &after_task {
+        let task = wait_for_task(&state, task_id).await?;
+        if task.status != TaskStatus::Completed {
+            return Err(format!("Task {} did not complete; skipping {}", task_id, kind));
+        }
+        agent_id = Some(task.agent_id);
+    }
+
+    let tx = spawn_output_forwarder(app_handle.clone());
+    let mut run = runner::run_project_command(&project_id, kind, &command, &project.path, tx).await;
+    run.after_task = after_task;
+    run.agent_id = agent_id;
+    let _ = app_handle.emit_tracked("project-command-finished", &run);
+    let _ = runner::FINISHED_RUNS.send(run.clone());
+    Ok(run)
+}
+
+/// Run the project's verification command after a completed task that
+/// changed files, and send the agent the output if it fails and the
+/// project asks for follow-ups
+pub(crate) async fn start_configured_verification(
+    app_handle: AppHandle,
+    state: Arc<AppState>,
+    task: TaskInfo,
+) {
+    if task.review_of.is_some() || task.verification.is_some() {
+        return;
+    }
+    if task.change_summary.as_ref().is_none_or(|s| s.files.is_empty()) {
+        return;
+    }
+    let Some(working_directory) = state
+        .agent_pool
+        .agent_statuses()
+        .into_iter()
+        .find(|a| a.id == task.agent_id)
+        .map(|a| a.working_directory)
+    else {
+        return;
+    };
+
+    let settings = state.settings.get();
+    let Some(commands) = find_project_commands(&settings.project_commands, &working_directory) else {
+        return;
+    };
+    let Some((kind, command)) = commands
+        .verify
+        .and_then(|kind| commands.get(kind).map(|c| (kind, c.to_string())))
+    else {
+        return;
+    };
+    let graph = state.agent_pool.task_graph();
+    let mut verification = TaskVerification {
+        status: VerificationStatus::Running,
+        command: command.clone(),
+        run: None,
+        follow_up_task_id: None,
+    };
+    if let Some(task) = graph.set_verification(&task.id, verification.clone()) {
+        let _ = app_handle.emit_tracked("task-verification", &task);
+    }
+
+    let project_id = state
+        .factory
+        .get_layout()
+        .await
+        .projects
+        .iter()
+        .find(|p| p.path == commands.project_path)
+        .map(|p| p.id.clone())
+        .unwrap_or_else(|| commands.project_path.clone());
+    let tx = spawn_output_forwarder(app_handle.clone());
+    let mut run = runner::run_project_command(&project_id, kind, &command, &working_directory, tx).await;
+    run.after_task = Some(task.id.clone());
+    run.agent_id = Some(task.agent_id);
+    let _ = app_handle.emit_tracked("project-command-finished", &run);
+    let _ = runner::FINISHED_RUNS.send(run.clone());
+
+    verification.status = if run.success {
+        VerificationStatus::Passed
+    } else {
+        state.alerts.raise(
+            AlertKind::VerificationFailed,
+            Some(task.agent_id),
+            Some(task.id.clone()),
+            LocalizedMessage::new(MessageKey::VerificationFailed).with("command", &command),
+        );
+        VerificationStatus::Failed
+    };
+    if !run.success && commands.follow_up_on_failure && graph.follow_up_depth(&task.id) < MAX_FOLLOW_UPS {
+        let spec = TaskSpec {
+            agent_id: task.agent_id,
+            prompt: build_follow_up_prompt(&run),
+            depends_on: vec![],
+            inject_results: false,
+            context: None,
+            priority: PromptPriority::Normal,
+            preamble: None,
+        };
+        let tx = spawn_update_forwarder(app_handle.clone(), state.clone());
+        match state.agent_pool.submit_task(spec, tx) {
+            Ok(follow_up) => {
+                graph.set_follow_up_of(&follow_up.id, &task.id);
+                verification.follow_up_task_id = Some(follow_up.id);
+            }
+            Err(e) => tracing::warn!("Failed to send verification follow-up: {}", e),
+        }
+    }
+    verification.run = Some(run);
+    if let Some(task) = graph.set_verification(&task.id, verification) {
+        let _ = app_handle.emit_tracked("task-verification", &task);
+    }
+}
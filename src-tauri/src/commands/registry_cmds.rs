@@ -1,7 +1,13 @@
-use crate::registry::RegistryAgent;
+use crate::events::TrackedEmitter;
+use crate::registry::{
+    get_platform, AgentVersionStatus, BinaryManager, ProviderOverrides, RegistryAgent,
+    RegistrySyncStatus, UPDATE_CHECK_INTERVAL_SECS,
+};
 use crate::state::AppState;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tauri::State;
+use std::time::Duration;
+use tauri::{AppHandle, State};
 
 /// Get all available agents from the registry
 #[tauri::command]
@@ -17,6 +23,15 @@ pub async fn refresh_registry(state: State<'_, Arc<AppState>>) -> Result<(), Str
     state.registry.refresh().await
 }
 
+/// When the registry was last fetched and which icons are missing, without
+/// fetching anything
+#[tauri::command]
+pub async fn get_registry_sync_status(
+    state: State<'_, Arc<AppState>>,
+) -> Result<RegistrySyncStatus, String> {
+    Ok(state.registry.sync_status().await)
+}
+
 /// Get a specific agent by ID
 #[tauri::command]
 pub async fn get_registry_agent(
@@ -49,3 +64,125 @@ pub fn get_all_agent_icons(
 ) -> Result<std::collections::HashMap<String, String>, String> {
     Ok(state.registry.get_all_icons())
 }
+
+/// Compare registry agents with their pinned or installed versions. Returns the
+/// last periodic check unless `refresh` is set or no check has run yet.
+#[tauri::command]
+pub async fn get_agent_updates(
+    refresh: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<AgentVersionStatus>, String> {
+    let statuses = state.registry.version_statuses().await;
+    if refresh.unwrap_or(false) || statuses.is_empty() {
+        return Ok(state.registry.check_updates().await);
+    }
+    Ok(statuses)
+}
+
+/// Pin an agent to `version` (default: the registry's latest), downloading
+/// the binary first for binary distributions
+#[tauri::command]
+pub async fn update_agent_version(
+    agent_id: String,
+    version: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AgentVersionStatus, String> {
+    let agent = state
+        .registry
+        .get_agent(&agent_id)
+        .await
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+    let version = version.unwrap_or_else(|| agent.version.clone());
+
+    let binary = agent
+        .distribution
+        .binary
+        .as_ref()
+        .and_then(|binaries| get_platform().and_then(|p| binaries.get(p)));
+    if let Some(binary) = binary {
+        let manager = BinaryManager::new();
+        if version == agent.version {
+            manager
+                .get_binary(&agent.id, &version, &binary.archive, &binary.cmd)
+                .await
+                .map_err(|e| format!("Failed to download {} {}: {}", agent.id, version, e))?;
+        } else if !manager.installed_versions(&agent.id).contains(&version) {
+            return Err(format!("Version {} of {} is not installed", version, agent.id));
+        }
+    }
+
+    state.registry.set_pinned_version(&agent.id, Some(version))?;
+    Ok(state.registry.refresh_version_status(&agent).await)
+}
+
+/// Remove an agent's version pin so it follows the registry again
+#[tauri::command]
+pub async fn unpin_agent_version(
+    agent_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AgentVersionStatus, String> {
+    let agent = state
+        .registry
+        .get_agent(&agent_id)
+        .await
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))?;
+    state.registry.set_pinned_version(&agent.id, None)?;
+    Ok(state.registry.refresh_version_status(&agent).await)
+}
+
+/// Local overrides of every provider that has some
+#[tauri::command]
+pub fn get_provider_overrides(
+    state: State<'_, Arc<AppState>>,
+) -> Result<HashMap<String, ProviderOverrides>, String> {
+    Ok(state.registry.provider_overrides())
+}
+
+/// Set the extra args, environment, default model and preferred auth method
+/// of a provider's agents spawned from now on, or drop them with None.
+/// Returns the provider with the overrides merged in.
+#[tauri::command]
+pub async fn set_provider_overrides(
+    agent_id: String,
+    overrides: Option<ProviderOverrides>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RegistryAgent, String> {
+    if state.registry.get_agent(&agent_id).await.is_none() {
+        return Err(format!("Unknown agent: {}", agent_id));
+    }
+    state.registry.set_provider_overrides(&agent_id, overrides)?;
+    state
+        .registry
+        .get_agent(&agent_id)
+        .await
+        .ok_or_else(|| format!("Unknown agent: {}", agent_id))
+}
+
+/// Periodically check for agent updates and announce newly available ones
+pub(crate) fn spawn_update_checker(app_handle: AppHandle, state: Arc<AppState>) {
+    tauri::async_runtime::spawn(async move {
+        // (agent id, version) pairs already announced
+        let mut announced: HashSet<(String, String)> = HashSet::new();
+        loop {
+            let updates: Vec<AgentVersionStatus> = state
+                .registry
+                .check_updates()
+                .await
+                .into_iter()
+                .filter(|s| s.update_available)
+                .collect();
+
+            let new_updates = updates
+                .iter()
+                .any(|s| announced.insert((s.agent_id.clone(), s.latest_version.clone())));
+            if new_updates {
+                let _ = app_handle.emit_tracked(
+                    "agent-updates-available",
+                    serde_json::json!({ "updates": updates }),
+                );
+            }
+
+            tokio::time::sleep(Duration::from_secs(UPDATE_CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
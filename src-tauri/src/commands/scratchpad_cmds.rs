@@ -0,0 +1,48 @@
+use crate::events::TrackedEmitter;
+use crate::state::{AppState, Scratchpad};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub async fn get_scratchpad(
+    project_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Scratchpad, String> {
+    Ok(state.scratchpad.get(&project_id).await)
+}
+
+/// Set a scratchpad entry for a project; a missing value deletes the key
+#[tauri::command]
+pub async fn set_scratchpad_entry(
+    project_id: String,
+    key: String,
+    value: Option<String>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<Scratchpad, String> {
+    let pad = state
+        .scratchpad
+        .set_entry(&project_id, &key, value, None)
+        .await?;
+
+    let _ = app_handle.emit_tracked("scratchpad-updated", serde_json::json!({
+        "project_id": project_id,
+        "entries": pad,
+    }));
+    Ok(pad)
+}
+
+#[tauri::command]
+pub async fn clear_scratchpad(
+    project_id: String,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    state.scratchpad.clear(&project_id).await?;
+
+    let _ = app_handle.emit_tracked("scratchpad-updated", serde_json::json!({
+        "project_id": project_id,
+        "entries": Scratchpad::new(),
+    }));
+    Ok(())
+}
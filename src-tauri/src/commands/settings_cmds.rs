@@ -0,0 +1,76 @@
+use crate::acp::RequestPolicies;
+use crate::agent::{set_keychain_variables, set_policy_scripts};
+use crate::automation::TriggerExecution;
+use crate::filesystem::set_extra_ignore_patterns;
+use crate::hooks::WebhookDelivery;
+use crate::shortcuts;
+use crate::state::{AppState, Settings};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, Arc<AppState>>) -> Result<Settings, String> {
+    Ok(state.settings.get())
+}
+
+#[tauri::command]
+pub fn update_settings(
+    settings: Settings,
+    app: AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Settings, String> {
+    settings.policy_scripts.validate()?;
+    // Register first so a shortcut another app holds doesn't get saved
+    if let Err(e) = shortcuts::apply(&app, &settings.permission_shortcuts) {
+        let _ = shortcuts::apply(&app, &state.settings.get().permission_shortcuts);
+        return Err(e);
+    }
+    let settings = state.settings.update(settings)?;
+    state.agent_pool.set_git_checkpoints(settings.git_checkpoints);
+    state
+        .agent_pool
+        .set_request_policies(RequestPolicies::new(settings.request_policies.clone()));
+    state.agent_pool.set_sandbox(settings.sandbox.clone());
+    state.agent_pool.set_resource_limits(settings.resource_limits);
+    state.agent_pool.set_package_runner(settings.package_runner.clone());
+    state.agent_pool.set_hold_conflicting_edits(settings.hold_conflicting_edits);
+    state.agent_pool.set_scheduling_policy(settings.scheduling.clone());
+    state.agent_pool.set_escalation_policy(settings.permission_escalation);
+    state.fog.set_scan_radius(settings.fog_scan_radius);
+    set_extra_ignore_patterns(&settings.ignore_patterns);
+    set_keychain_variables(&settings.keychain_variables);
+    set_policy_scripts(&settings.policy_scripts);
+    Ok(settings)
+}
+
+/// Recent webhook hook deliveries with their retry outcome
+#[tauri::command]
+pub fn get_webhook_deliveries(state: State<'_, Arc<AppState>>) -> Result<Vec<WebhookDelivery>, String> {
+    Ok(state.hooks.deliveries())
+}
+
+/// Enable or disable a file-change trigger rule
+#[tauri::command]
+pub fn set_trigger_rule_enabled(
+    rule_id: String,
+    enabled: bool,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Settings, String> {
+    let mut settings = state.settings.get();
+    let rule = settings
+        .trigger_rules
+        .iter_mut()
+        .find(|r| r.id == rule_id)
+        .ok_or_else(|| format!("Unknown trigger rule: {}", rule_id))?;
+    rule.enabled = enabled;
+    state.settings.update(settings)
+}
+
+/// Recent executions of one trigger rule, or of all of them, oldest first
+#[tauri::command]
+pub fn get_trigger_history(
+    rule_id: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<TriggerExecution>, String> {
+    Ok(state.trigger_history.list(rule_id.as_deref()))
+}
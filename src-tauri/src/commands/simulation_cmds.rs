@@ -0,0 +1,68 @@
+use crate::events::TrackedEmitter;
+use crate::simulation::{Simulation, SimulationConfig};
+use crate::state::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+/// Fastest tick accepted, to keep the frontend responsive
+const MIN_TICK_MS: u64 = 50;
+const MAX_AGENTS: usize = 32;
+
+/// Run fake agents that emit plausible events on a timer, replacing any
+/// simulation already running. Defaults to the open project's directory.
+#[tauri::command]
+pub async fn start_simulation(
+    seed: Option<u64>,
+    agents: Option<usize>,
+    tick_ms: Option<u64>,
+    state: State<'_, Arc<AppState>>,
+    app_handle: AppHandle,
+) -> Result<SimulationConfig, String> {
+    let defaults = SimulationConfig::default();
+    let root = match state.project_path.read().await.as_ref() {
+        Some(path) => path.display().to_string(),
+        None => defaults.root,
+    };
+    let config = SimulationConfig {
+        seed: seed.unwrap_or(defaults.seed),
+        agents: agents.unwrap_or(defaults.agents).clamp(1, MAX_AGENTS),
+        tick_ms: tick_ms.unwrap_or(defaults.tick_ms).max(MIN_TICK_MS),
+        root,
+    };
+
+    stop_running(&state, &app_handle);
+    let mut simulation = Simulation::new(config.clone());
+    let generation = state.simulation.begin(simulation.agent_ids());
+    let state = state.inner().clone();
+    let tick = Duration::from_millis(config.tick_ms);
+    tauri::async_runtime::spawn(async move {
+        let mut step = simulation.start();
+        while state.simulation.is_current(generation) {
+            state.metrics.add_tokens(step.input_tokens, step.output_tokens);
+            for (event, agent_id, payload) in step.events {
+                let _ = match agent_id {
+                    Some(agent_id) => app_handle.emit_agent_event(&event, agent_id, payload),
+                    None => app_handle.emit_tracked(&event, payload),
+                };
+            }
+            tokio::time::sleep(tick).await;
+            step = simulation.tick();
+        }
+    });
+    Ok(config)
+}
+
+fn stop_running(state: &AppState, app_handle: &AppHandle) {
+    for agent_id in state.simulation.end() {
+        let _ = app_handle
+            .emit_tracked("agent-stopped", serde_json::json!({ "agent_id": agent_id }));
+    }
+}
+
+/// Stop the simulation and remove its agents
+#[tauri::command]
+pub fn stop_simulation(state: State<'_, Arc<AppState>>, app_handle: AppHandle) -> Result<(), String> {
+    stop_running(&state, &app_handle);
+    Ok(())
+}
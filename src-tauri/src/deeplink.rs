@@ -0,0 +1,292 @@
+//! Router for `acptorio://` URLs, mapping them onto existing commands so
+//! editors and browsers can drive the app.
+//!
+//! - `acptorio://open?path=/path/to/project`
+//! - `acptorio://spawn?cwd=/path&name=Agent&provider=claude-code`
+//! - `acptorio://prompt?agent=<agent id>&text=Fix+the+build`
+//!
+//! Any web page or document can open a link, so links that spawn agents or
+//! send prompts only run once the user accepts them.
+
+use crate::acp::Transport;
+use crate::agent::{PromptPriority, TaskSpec};
+use crate::commands::agent_cmds::{spawn_agent_process, spawn_update_forwarder};
+use crate::commands::fs_cmds::open_project;
+use crate::events::TrackedEmitter;
+use crate::state::AppState;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
+use url::Url;
+use uuid::Uuid;
+
+pub const SCHEME: &str = "acptorio";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DeepLinkAction {
+    Open {
+        path: String,
+    },
+    Spawn {
+        working_directory: String,
+        name: Option<String>,
+        provider_id: Option<String>,
+    },
+    Prompt {
+        agent_id: Uuid,
+        prompt: String,
+    },
+}
+
+impl DeepLinkAction {
+    /// Whether the link starts an agent or has one run tools
+    pub fn needs_confirmation(&self) -> bool {
+        !matches!(self, DeepLinkAction::Open { .. })
+    }
+}
+
+/// A link waiting for the user to accept or reject it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingDeepLink {
+    pub id: String,
+    pub url: String,
+    pub action: DeepLinkAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeepLinkOutcome {
+    Handled { action: DeepLinkAction },
+    /// Announced with `deep-link-confirm`; runs on `confirm_deep_link`
+    Pending { link: PendingDeepLink },
+}
+
+/// Links waiting for confirmation, by id
+static PENDING_LINKS: Lazy<Mutex<HashMap<String, PendingDeepLink>>> = Lazy::new(Default::default);
+
+fn hold(url: &str, action: DeepLinkAction) -> PendingDeepLink {
+    let link = PendingDeepLink {
+        id: Uuid::new_v4().to_string(),
+        url: url.to_string(),
+        action,
+    };
+    PENDING_LINKS.lock().unwrap().insert(link.id.clone(), link.clone());
+    link
+}
+
+fn take(id: &str) -> Result<PendingDeepLink, String> {
+    PENDING_LINKS
+        .lock()
+        .unwrap()
+        .remove(id)
+        .ok_or_else(|| format!("No deep link waiting: {}", id))
+}
+
+/// Links waiting for confirmation, e.g. one the app was launched with before
+/// the frontend listened
+pub fn pending() -> Vec<PendingDeepLink> {
+    PENDING_LINKS.lock().unwrap().values().cloned().collect()
+}
+
+/// Parse an `acptorio://` URL into the action it requests
+pub fn parse(url: &str) -> Result<DeepLinkAction, String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("Unsupported scheme: {}", url.scheme()));
+    }
+
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    let required = |key: &str| {
+        params
+            .get(key)
+            .filter(|v| !v.is_empty())
+            .cloned()
+            .ok_or_else(|| format!("Missing parameter: {}", key))
+    };
+
+    // `acptorio://open?...` puts the action in the host, `acptorio:open?...` in the path
+    let action = url
+        .host_str()
+        .unwrap_or_else(|| url.path().trim_matches('/'))
+        .to_string();
+
+    match action.as_str() {
+        "open" => Ok(DeepLinkAction::Open {
+            path: required("path")?,
+        }),
+        "spawn" => Ok(DeepLinkAction::Spawn {
+            working_directory: required("cwd")?,
+            name: params.get("name").cloned(),
+            provider_id: params.get("provider").cloned(),
+        }),
+        "prompt" => Ok(DeepLinkAction::Prompt {
+            agent_id: Uuid::parse_str(&required("agent")?).map_err(|e| e.to_string())?,
+            prompt: required("text")?,
+        }),
+        other => Err(format!("Unknown deep link action: {}", other)),
+    }
+}
+
+/// Parse a deep link and execute it, or hold it for the user to confirm
+pub async fn handle(app: &AppHandle, url: &str) -> Result<DeepLinkOutcome, String> {
+    let action = parse(url)?;
+    if action.needs_confirmation() {
+        let link = hold(url, action);
+        let _ = app.emit_tracked("deep-link-confirm", &link);
+        return Ok(DeepLinkOutcome::Pending { link });
+    }
+    execute(app, action).await
+}
+
+/// Run a held link the user accepted
+pub async fn confirm(app: &AppHandle, id: &str) -> Result<DeepLinkOutcome, String> {
+    execute(app, take(id)?.action).await
+}
+
+pub fn reject(id: &str) -> Result<(), String> {
+    take(id).map(|_| ())
+}
+
+async fn execute(app: &AppHandle, action: DeepLinkAction) -> Result<DeepLinkOutcome, String> {
+    let state = app.state::<Arc<AppState>>().inner().clone();
+
+    match &action {
+        DeepLinkAction::Open { path } => {
+            open_project(path, &state, app).await?;
+        }
+        DeepLinkAction::Spawn {
+            working_directory,
+            name,
+            provider_id,
+        } => {
+            let name = name.clone().unwrap_or_else(|| "Agent".to_string());
+            let info = spawn_agent_process(
+                &state,
+                name,
+                working_directory.clone(),
+                provider_id.clone(),
+                Transport::Stdio,
+            )
+            .await?;
+            let _ = app.emit_tracked("agent-spawned", &info);
+        }
+        DeepLinkAction::Prompt { agent_id, prompt } => {
+            let spec = TaskSpec {
+                agent_id: *agent_id,
+                prompt: prompt.clone(),
+                depends_on: Vec::new(),
+                inject_results: false,
+                context: None,
+                priority: PromptPriority::Normal,
+                preamble: None,
+            };
+            let tx = spawn_update_forwarder(app.clone(), state.clone());
+            state
+                .agent_pool
+                .submit_task(spec, tx)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let _ = app.emit_tracked("deep-link-handled", &action);
+    Ok(DeepLinkOutcome::Handled { action })
+}
+
+fn spawn_handle(app: &AppHandle, url: String) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = handle(&app, &url).await {
+            tracing::warn!("Failed to handle deep link {}: {}", url, e);
+        }
+    });
+}
+
+/// Handle the links the OS opens the app with: the one it was launched with
+/// and, through the single instance plugin, any opened while it runs
+pub fn listen(app: &AppHandle) {
+    // Installers register the scheme; AppImages and dev builds aren't installed
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(e) = app.deep_link().register_all() {
+        tracing::warn!("Failed to register the {} scheme: {}", SCHEME, e);
+    }
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            spawn_handle(&handle, url.to_string());
+        }
+    });
+    // Reported before the listener above existed
+    if let Ok(Some(urls)) = app.deep_link().get_current() {
+        for url in urls {
+            spawn_handle(app, url.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_open() {
+        let action = parse("acptorio://open?path=%2Fhome%2Fme%2Fproject").unwrap();
+        assert_eq!(
+            action,
+            DeepLinkAction::Open {
+                path: "/home/me/project".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_prompt_and_spawn() {
+        let agent_id = Uuid::new_v4();
+        let url = format!("acptorio://prompt?agent={}&text=Fix+the+build", agent_id);
+        assert_eq!(
+            parse(&url).unwrap(),
+            DeepLinkAction::Prompt {
+                agent_id,
+                prompt: "Fix the build".to_string()
+            }
+        );
+
+        match parse("acptorio:spawn?cwd=/tmp&provider=gemini").unwrap() {
+            DeepLinkAction::Spawn {
+                working_directory,
+                name,
+                provider_id,
+            } => {
+                assert_eq!(working_directory, "/tmp");
+                assert_eq!(name, None);
+                assert_eq!(provider_id.as_deref(), Some("gemini"));
+            }
+            other => panic!("Unexpected action: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_only_opening_a_project_runs_unconfirmed() {
+        let open = parse("acptorio://open?path=/tmp").unwrap();
+        assert!(!open.needs_confirmation());
+        let spawn = parse("acptorio://spawn?cwd=/tmp").unwrap();
+        assert!(spawn.needs_confirmation());
+
+        let link = hold("acptorio://spawn?cwd=/tmp", spawn.clone());
+        assert!(pending().contains(&link));
+        assert_eq!(take(&link.id).unwrap().action, spawn);
+        // Accepting or rejecting it twice fails
+        assert!(take(&link.id).is_err());
+        assert!(!pending().contains(&link));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_links() {
+        assert!(parse("https://example.com/open?path=/tmp").is_err());
+        assert!(parse("acptorio://open").is_err());
+        assert!(parse("acptorio://delete?path=/").is_err());
+    }
+}
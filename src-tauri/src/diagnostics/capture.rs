@@ -0,0 +1,170 @@
+//! In-memory capture of recent log output and ACP protocol traffic, kept so
+//! a diagnostics bundle can include them.
+
+use crate::clock::now_ms;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use tracing_subscriber::fmt::MakeWriter;
+
+const LOG_CAPACITY: usize = 2000;
+const TRACE_CAPACITY: usize = 500;
+
+/// Recent log lines written by the tracing subscriber
+pub static LOGS: Lazy<LogBuffer> = Lazy::new(|| LogBuffer::new(LOG_CAPACITY));
+
+/// Recent JSON-RPC messages exchanged with agents
+pub static PROTOCOL_TRACE: Lazy<ProtocolTrace> = Lazy::new(|| ProtocolTrace::new(TRACE_CAPACITY));
+
+pub struct LogBuffer {
+    capacity: usize,
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn push(&self, text: &str) {
+        let text = strip_ansi(text);
+        let mut lines = self.lines.lock().unwrap();
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            if lines.len() >= self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Remove terminal color sequences (`ESC [ ... letter`)
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Log writer that prints to stdout like the default subscriber and keeps a
+/// copy in [`LOGS`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogCapture;
+
+impl Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = io::stdout().write(buf)?;
+        LOGS.push(&String::from_utf8_lossy(&buf[..written]));
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogCapture {
+    type Writer = LogCapture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceDirection {
+    /// Sent to the agent
+    Outgoing,
+    /// Received from the agent
+    Incoming,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub timestamp: u64,
+    /// Agent the message was exchanged with
+    pub agent: String,
+    pub direction: TraceDirection,
+    pub message: String,
+}
+
+pub struct ProtocolTrace {
+    capacity: usize,
+    entries: Mutex<VecDeque<TraceEntry>>,
+}
+
+impl ProtocolTrace {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn record(&self, agent: &str, direction: TraceDirection, message: &str) {
+        let entry = TraceEntry {
+            timestamp: now_ms(),
+            agent: agent.to_string(),
+            direction,
+            message: message.to_string(),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Recorded messages, oldest first
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_buffer_strips_colors_and_is_bounded() {
+        let logs = LogBuffer::new(2);
+        logs.push("\x1b[32m INFO\x1b[0m first\n");
+        logs.push("second\nthird\n");
+        assert_eq!(logs.lines(), vec!["second", "third"]);
+
+        let logs = LogBuffer::new(10);
+        logs.push("\x1b[2m2025-01-01\x1b[0m \x1b[32m INFO\x1b[0m started\n");
+        assert_eq!(logs.lines(), vec!["2025-01-01  INFO started"]);
+    }
+
+    #[test]
+    fn test_protocol_trace_is_bounded() {
+        let trace = ProtocolTrace::new(3);
+        for i in 0..5 {
+            trace.record("agent", TraceDirection::Incoming, &i.to_string());
+        }
+        let entries = trace.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].message, "2");
+    }
+}
@@ -0,0 +1,142 @@
+//! Log levels that can be changed while the app runs, globally or per module.
+
+use super::LogCapture;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::INFO;
+/// Top-level modules of this crate; `acp::codec` is short for
+/// `acptorio_lib::acp::codec`
+const CRATE_MODULES: &[&str] = &[
+    "acp",
+    "agent",
+    "commands",
+    "deeplink",
+    "diagnostics",
+    "events",
+    "filesystem",
+    "git",
+    "hooks",
+    "registry",
+    "state",
+    "tray",
+];
+
+static LEVELS: OnceCell<LogLevels> = OnceCell::new();
+
+/// Current log levels
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogLevelState {
+    pub default: String,
+    /// Module path -> level overriding the default
+    pub modules: BTreeMap<String, String>,
+}
+
+struct LogLevels {
+    handle: reload::Handle<Targets, Registry>,
+    state: Mutex<(LevelFilter, BTreeMap<String, LevelFilter>)>,
+}
+
+/// Install the tracing subscriber with a reloadable filter
+pub fn init_logging() {
+    let (filter, handle) = reload::Layer::new(Targets::new().with_default(DEFAULT_LEVEL));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(LogCapture))
+        .init();
+
+    let _ = LEVELS.set(LogLevels {
+        handle,
+        state: Mutex::new((DEFAULT_LEVEL, BTreeMap::new())),
+    });
+}
+
+/// Full target name for a module given relative to this crate
+fn normalize_module(module: &str) -> String {
+    let module = module.trim().trim_start_matches("crate::");
+    let first = module.split("::").next().unwrap_or_default();
+    if CRATE_MODULES.contains(&first) {
+        format!("{}::{}", env!("CARGO_CRATE_NAME"), module)
+    } else {
+        module.to_string()
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level.trim())
+        .map_err(|_| format!("Unknown log level: {} (use off, error, warn, info, debug or trace)", level))
+}
+
+fn describe(default: LevelFilter, modules: &BTreeMap<String, LevelFilter>) -> LogLevelState {
+    LogLevelState {
+        default: default.to_string().to_lowercase(),
+        modules: modules
+            .iter()
+            .map(|(module, level)| (module.clone(), level.to_string().to_lowercase()))
+            .collect(),
+    }
+}
+
+/// Set the default level, or a module's level when `module` is given.
+/// The level `default` removes a module's override.
+pub fn set_log_level(level: &str, module: Option<&str>) -> Result<LogLevelState, String> {
+    let levels = LEVELS.get().ok_or("Logging is not initialized")?;
+    let mut state = levels.state.lock().unwrap();
+    let (default, modules) = &mut *state;
+
+    match module.filter(|m| !m.trim().is_empty()) {
+        Some(module) if level.trim().eq_ignore_ascii_case("default") => {
+            modules.remove(&normalize_module(module));
+        }
+        Some(module) => {
+            modules.insert(normalize_module(module), parse_level(level)?);
+        }
+        None => *default = parse_level(level)?,
+    }
+
+    let targets = Targets::new()
+        .with_default(*default)
+        .with_targets(modules.iter().map(|(module, level)| (module.clone(), *level)));
+    levels
+        .handle
+        .reload(targets)
+        .map_err(|e| format!("Failed to apply log level: {}", e))?;
+    Ok(describe(*default, modules))
+}
+
+pub fn log_levels() -> LogLevelState {
+    match LEVELS.get() {
+        Some(levels) => {
+            let state = levels.state.lock().unwrap();
+            describe(state.0, &state.1)
+        }
+        None => describe(DEFAULT_LEVEL, &BTreeMap::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_module() {
+        assert_eq!(normalize_module("acp::codec"), "acptorio_lib::acp::codec");
+        assert_eq!(normalize_module("crate::agent"), "acptorio_lib::agent");
+        assert_eq!(normalize_module("acptorio_lib::git"), "acptorio_lib::git");
+        assert_eq!(normalize_module("reqwest"), "reqwest");
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("TRACE").unwrap(), LevelFilter::TRACE);
+        assert_eq!(parse_level(" off ").unwrap(), LevelFilter::OFF);
+        assert!(parse_level("loud").is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Self-diagnostics: a zip bundle with recent logs, protocol traces,
+//! environment checks, settings and registry state for bug reports.
+//! Anything that looks like a secret is redacted before it is written.
+
+mod capture;
+mod levels;
+
+pub use capture::*;
+pub use levels::*;
+
+use crate::clock::now_secs;
+use crate::hooks::HookAction;
+use crate::registry::RegistryCacheState;
+use crate::state::{app_data_dir, Settings};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const REDACTED: &str = "[redacted]";
+/// Longer strings in traces (file contents, transcripts) are cut off
+const MAX_TRACE_STRING: usize = 2000;
+/// Object keys whose string values are treated as secrets (compared
+/// lowercased, without `_` and `-`)
+const SECRET_KEY_PARTS: &[&str] = &[
+    "token",
+    "secret",
+    "password",
+    "apikey",
+    "authorization",
+    "cookie",
+    "credential",
+];
+/// Tools agents commonly depend on
+const CHECKED_TOOLS: &[&str] = &["node", "npx", "git", "claude"];
+const TOOL_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCheck {
+    pub name: String,
+    /// First line of `<tool> --version`
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentReport {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub data_dir: Option<String>,
+    pub tools: Vec<ToolCheck>,
+}
+
+/// Check the platform and the tools agents are launched with
+pub async fn check_environment() -> EnvironmentReport {
+    let mut tools = Vec::new();
+    for name in CHECKED_TOOLS {
+        tools.push(check_tool(name).await);
+    }
+
+    EnvironmentReport {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        data_dir: Some(app_data_dir().to_string_lossy().to_string()),
+        tools,
+    }
+}
+
+/// Run `<name> --version`
+pub async fn check_tool(name: &str) -> ToolCheck {
+    let output = tokio::time::timeout(
+        TOOL_CHECK_TIMEOUT,
+        tokio::process::Command::new(name).arg("--version").output(),
+    )
+    .await;
+
+    let (version, error) = match output {
+        Ok(Ok(output)) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            (stdout.lines().next().map(|l| l.trim().to_string()), None)
+        }
+        Ok(Ok(output)) => (None, Some(format!("`{} --version` exited with {}", name, output.status))),
+        Ok(Err(e)) => (None, Some(e.to_string())),
+        Err(_) => (None, Some("Timed out".to_string())),
+    };
+
+    ToolCheck {
+        name: name.to_string(),
+        version,
+        error,
+    }
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key: String = key
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    key == "env" || SECRET_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// Redact secret-looking values in place and shorten very long strings.
+/// Numbers and booleans are kept, so fields like `tokens_used` survive.
+pub fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let scalar = value.is_number() || value.is_boolean() || value.is_null();
+                if is_secret_key(key) && !scalar {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        Value::String(text) if text.len() > MAX_TRACE_STRING => {
+            let mut end = MAX_TRACE_STRING;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            *text = format!("{}... ({} bytes)", &text[..end], text.len());
+        }
+        _ => {}
+    }
+}
+
+/// Redacted copy of a raw JSON-RPC message
+pub fn redact_message(message: &str) -> Value {
+    match serde_json::from_str::<Value>(message) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value
+        }
+        Err(_) => {
+            let mut value = Value::String(message.to_string());
+            redact_value(&mut value);
+            value
+        }
+    }
+}
+
+/// Keep only the scheme and host of a URL; webhook paths usually embed a token
+pub fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => format!(
+            "{}://{}/{}",
+            parsed.scheme(),
+            parsed.host_str().unwrap_or_default(),
+            REDACTED
+        ),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/// Settings with webhook URLs, hook commands and secret-looking fields stripped
+pub fn strip_settings_secrets(settings: &Settings) -> Value {
+    let mut settings = settings.clone();
+    for hook in &mut settings.hooks {
+        match &mut hook.action {
+            HookAction::Webhook { url, .. } => *url = redact_url(url),
+            HookAction::Command { command } => *command = REDACTED.to_string(),
+            HookAction::Sound { .. } => {}
+        }
+    }
+
+    let mut value = serde_json::to_value(&settings).unwrap_or_default();
+    redact_value(&mut value);
+    value
+}
+
+/// Where bundles go when no path is given
+pub fn default_bundle_path() -> PathBuf {
+    let timestamp = now_secs();
+
+    app_data_dir()
+        .join("diagnostics")
+        .join(format!("acptorio-diagnostics-{}.zip", timestamp))
+}
+
+/// Write the diagnostics zip to `path`
+pub fn write_bundle(
+    path: &Path,
+    environment: &EnvironmentReport,
+    settings: &Settings,
+    registry: &RegistryCacheState,
+) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    let logs = LOGS.lines().join("\n");
+    let trace = PROTOCOL_TRACE
+        .entries()
+        .into_iter()
+        .map(|entry| {
+            json!({
+                "timestamp": entry.timestamp,
+                "agent": entry.agent,
+                "direction": entry.direction,
+                "message": redact_message(&entry.message),
+            })
+            .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut registry = serde_json::to_value(registry).unwrap_or_default();
+    redact_value(&mut registry);
+    let manifest = json!({
+        "app_version": environment.app_version,
+        "generated_at": now_secs(),
+    });
+
+    let pretty = |value: &Value| serde_json::to_string_pretty(value).unwrap_or_default();
+    let files = [
+        ("manifest.json", pretty(&manifest)),
+        ("logs.txt", logs),
+        ("protocol-trace.jsonl", trace),
+        ("environment.json", pretty(&serde_json::to_value(environment).unwrap_or_default())),
+        ("settings.json", pretty(&strip_settings_secrets(settings))),
+        ("registry.json", pretty(&registry)),
+    ];
+
+    let mut zip = ZipWriter::new(file);
+    for (name, content) in files {
+        zip.start_file(name, SimpleFileOptions::default())
+            .and_then(|_| zip.write_all(content.as_bytes()).map_err(Into::into))
+            .map_err(|e| format!("Failed to write {} to bundle: {}", name, e))?;
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to finish diagnostics bundle: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::{Hook, HookEvent};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_redact_message() {
+        let message = r#"{"jsonrpc":"2.0","method":"authenticate","params":{"apiKey":"sk-123","env":[{"name":"A","value":"b"}],"tokens_used":5,"text":"hello"}}"#;
+        let redacted = redact_message(message);
+        assert_eq!(redacted["params"]["apiKey"], REDACTED);
+        assert_eq!(redacted["params"]["env"], REDACTED);
+        assert_eq!(redacted["params"]["tokens_used"], 5);
+        assert_eq!(redacted["params"]["text"], "hello");
+
+        let long = redact_message(&json!({ "text": "x".repeat(MAX_TRACE_STRING + 10) }).to_string());
+        assert!(long["text"].as_str().unwrap().ends_with(&format!("({} bytes)", MAX_TRACE_STRING + 10)));
+    }
+
+    #[test]
+    fn test_strip_settings_secrets() {
+        let settings = Settings {
+            hooks: vec![
+                Hook {
+                    event: HookEvent::TaskFinished,
+                    action: HookAction::Webhook {
+                        url: "https://hooks.slack.com/services/T0/B0/secret".to_string(),
+                        format: Default::default(),
+                    },
+                    enabled: true,
+                },
+                Hook {
+                    event: HookEvent::AgentError,
+                    action: HookAction::Command {
+                        command: "notify --token abc".to_string(),
+                    },
+                    enabled: true,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let stripped = strip_settings_secrets(&settings).to_string();
+        assert!(stripped.contains("https://hooks.slack.com/[redacted]"));
+        assert!(!stripped.contains("secret"));
+        assert!(!stripped.contains("abc"));
+    }
+
+    #[test]
+    fn test_write_bundle() {
+        let dir = std::env::temp_dir().join(format!("acptorio-diagnostics-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("bundle.zip");
+        let environment = EnvironmentReport {
+            app_version: "0.1.0".to_string(),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            data_dir: None,
+            tools: Vec::new(),
+        };
+        let registry = RegistryCacheState {
+            cache_path: "registry.json".to_string(),
+            last_fetch: None,
+            stale: true,
+            agents: Vec::new(),
+            pins: HashMap::new(),
+            installed_binaries: HashMap::new(),
+        };
+
+        write_bundle(&path, &environment, &Settings::default(), &registry).unwrap();
+
+        let archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "environment.json",
+                "logs.txt",
+                "manifest.json",
+                "protocol-trace.jsonl",
+                "registry.json",
+                "settings.json"
+            ]
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+}
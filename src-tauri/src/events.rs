@@ -0,0 +1,483 @@
+//! Sequence-numbered event emission with a replay buffer, so windows that
+//! reload can catch up on events they missed.
+
+use crate::clock::now_ms;
+use crate::state::AppState;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+use uuid::Uuid;
+
+const REPLAY_CAPACITY: usize = 1000;
+
+/// Field added to payloads carrying the event's sequence number
+pub const SEQ_FIELD: &str = "_seq";
+/// Field holding a payload that is not an object, next to [`SEQ_FIELD`]
+pub const VALUE_FIELD: &str = "value";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub seq: u64,
+    pub event: String,
+    pub payload: Value,
+    pub timestamp: u64,
+    /// Set for events only some windows get
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<EventScope>,
+}
+
+/// Agent an event is about, so it is replayed only to windows that get it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventScope {
+    pub agent_id: Uuid,
+    /// Streamed update, which windows using agent channels get on those
+    pub update: bool,
+}
+
+/// Bounded buffer of the most recently emitted events
+pub struct EventLog {
+    next_seq: AtomicU64,
+    buffer: Mutex<VecDeque<RecordedEvent>>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::with_capacity(REPLAY_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            next_seq: AtomicU64::new(1),
+            buffer: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// Assign the next sequence number to an event, tag its payload, and buffer
+    /// it. Payloads other than objects are wrapped so every event carries its
+    /// number; a null payload becomes an object with only the number.
+    pub fn record(&self, event: &str, payload: Value) -> RecordedEvent {
+        self.record_scoped(event, None, payload)
+    }
+
+    /// Like [`record`](Self::record), for an event only windows in `scope` get
+    pub fn record_scoped(
+        &self,
+        event: &str,
+        scope: Option<EventScope>,
+        payload: Value,
+    ) -> RecordedEvent {
+        // Numbered under the lock so the buffer stays in sequence order
+        let mut buffer = self.buffer.lock().unwrap();
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let mut map = match payload {
+            Value::Object(map) => map,
+            Value::Null => serde_json::Map::new(),
+            value => serde_json::Map::from_iter([(VALUE_FIELD.to_string(), value)]),
+        };
+        map.insert(SEQ_FIELD.to_string(), Value::from(seq));
+        let payload = Value::Object(map);
+
+        let recorded = RecordedEvent {
+            seq,
+            event: event.to_string(),
+            payload,
+            timestamp: now_ms(),
+            scope,
+        };
+
+        if buffer.len() == self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(recorded.clone());
+        recorded
+    }
+
+    /// Buffered events with a sequence number greater than `since_seq`
+    pub fn since(&self, since_seq: u64) -> Vec<RecordedEvent> {
+        self.buffer
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.seq > since_seq)
+            .cloned()
+            .collect()
+    }
+
+    /// Sequence number of the most recent event, 0 if none were emitted
+    pub fn last_seq(&self) -> u64 {
+        self.next_seq.load(Ordering::Relaxed) - 1
+    }
+
+    /// Buffered events after `since_seq` that pass `include`, and whether
+    /// they are all such events after it or older ones were already dropped
+    /// from the buffer
+    pub fn replay(
+        &self,
+        since_seq: u64,
+        include: impl Fn(&RecordedEvent) -> bool,
+    ) -> EventReplay {
+        let buffer = self.buffer.lock().unwrap();
+        let events: Vec<RecordedEvent> = buffer
+            .iter()
+            .filter(|e| e.seq > since_seq && include(e))
+            .cloned()
+            .collect();
+        let last_seq = self.last_seq();
+        let complete =
+            last_seq <= since_seq || buffer.front().is_some_and(|e| e.seq <= since_seq + 1);
+        EventReplay {
+            events,
+            last_seq,
+            complete,
+        }
+    }
+}
+
+/// Events a window missed, for catching up after a gap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventReplay {
+    pub events: Vec<RecordedEvent>,
+    pub last_seq: u64,
+    /// False if some missed events are no longer buffered; the window has to
+    /// reload its state with `get_full_state` instead
+    pub complete: bool,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Name of the channel carrying `event` for one agent, e.g.
+/// `agent-update:{id}`
+pub fn agent_channel(event: &str, agent_id: &Uuid) -> String {
+    format!("{}:{}", event, agent_id)
+}
+
+/// Which agents each window wants agent-scoped events for.
+///
+/// Windows that never registered interest receive events for every agent.
+/// Windows subscribed to agent channels get agent updates only on the
+/// channels of those agents, so updates of agents nobody watches are not
+/// sent at all.
+pub struct WindowScopes {
+    interests: DashMap<String, HashSet<Uuid>>,
+    /// Agents whose update channel each window subscribed to
+    channels: DashMap<String, HashSet<Uuid>>,
+}
+
+impl WindowScopes {
+    pub fn new() -> Self {
+        Self {
+            interests: DashMap::new(),
+            channels: DashMap::new(),
+        }
+    }
+
+    pub fn set_interest(&self, label: &str, agent_ids: HashSet<Uuid>) {
+        self.interests.insert(label.to_string(), agent_ids);
+    }
+
+    pub fn clear(&self, label: &str) {
+        self.interests.remove(label);
+        self.channels.remove(label);
+    }
+
+    /// Agents the window gets updates of on their channels
+    pub fn subscribe(&self, label: &str, agent_ids: &[Uuid]) -> Vec<Uuid> {
+        let mut channels = self.channels.entry(label.to_string()).or_default();
+        channels.extend(agent_ids.iter().copied());
+        channels.iter().copied().collect()
+    }
+
+    /// Without subscriptions left the window gets every update again
+    pub fn unsubscribe(&self, label: &str, agent_ids: &[Uuid]) -> Vec<Uuid> {
+        let Some(mut channels) = self.channels.get_mut(label) else {
+            return Vec::new();
+        };
+        channels.retain(|id| !agent_ids.contains(id));
+        let left: Vec<Uuid> = channels.iter().copied().collect();
+        drop(channels);
+        self.channels.remove_if(label, |_, channels| channels.is_empty());
+        left
+    }
+
+    /// Whether any window subscribed to the agent's channel
+    pub fn observed(&self, agent_id: &Uuid) -> bool {
+        self.channels.iter().any(|entry| entry.contains(agent_id))
+    }
+
+    fn target_label(target: &EventTarget) -> Option<&str> {
+        match target {
+            EventTarget::AnyLabel { label }
+            | EventTarget::Window { label }
+            | EventTarget::Webview { label }
+            | EventTarget::WebviewWindow { label } => Some(label.as_str()),
+            _ => None,
+        }
+    }
+
+    fn subscribed_target(&self, target: &EventTarget, agent_id: &Uuid) -> bool {
+        Self::target_label(target)
+            .and_then(|label| self.channels.get(label))
+            .is_some_and(|ids| ids.contains(agent_id))
+    }
+
+    /// Whether the window gets an agent update on the shared event, i.e.
+    /// it is interested in the agent and uses no agent channels
+    fn accepts_update(&self, target: &EventTarget, agent_id: &Uuid) -> bool {
+        match Self::target_label(target) {
+            Some(label) => !self.channels.contains_key(label) && self.accepts(label, agent_id),
+            None => true,
+        }
+    }
+
+    pub fn interest(&self, label: &str) -> Option<HashSet<Uuid>> {
+        self.interests.get(label).map(|i| i.clone())
+    }
+
+    /// Whether the window with `label` should receive events about `agent_id`
+    pub fn accepts(&self, label: &str, agent_id: &Uuid) -> bool {
+        self.interests
+            .get(label)
+            .map(|ids| ids.contains(agent_id))
+            .unwrap_or(true)
+    }
+
+    fn accepts_target(&self, target: &EventTarget, agent_id: &Uuid) -> bool {
+        Self::target_label(target).is_none_or(|label| self.accepts(label, agent_id))
+    }
+
+    /// Whether the window with `label` was sent `event`, on the shared event
+    /// or its agent's channel
+    pub fn received(&self, label: &str, event: &RecordedEvent) -> bool {
+        let Some(scope) = event.scope else {
+            return true;
+        };
+        match self.channels.get(label) {
+            Some(ids) if scope.update => ids.contains(&scope.agent_id),
+            _ => self.accepts(label, &scope.agent_id),
+        }
+    }
+}
+
+impl Default for WindowScopes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Emit events through the app's [`EventLog`]
+pub trait TrackedEmitter {
+    /// Emit an event to the frontend, recording it for replay. The payload
+    /// gets a `_seq` field, see [`EventLog::record`].
+    fn emit_tracked<S: Serialize>(&self, event: &str, payload: S) -> tauri::Result<()>;
+
+    /// Like [`emit_tracked`](Self::emit_tracked), but only delivered to windows
+    /// interested in `agent_id`
+    fn emit_agent_event<S: Serialize>(
+        &self,
+        event: &str,
+        agent_id: Uuid,
+        payload: S,
+    ) -> tauri::Result<()>;
+
+    /// Emit a streamed agent update: on the agent's channel to windows
+    /// subscribed to it, and as `event` to the interested windows using no
+    /// channels. Recorded for replay even if no window gets it.
+    fn emit_agent_update<S: Serialize>(
+        &self,
+        event: &str,
+        agent_id: Uuid,
+        payload: S,
+    ) -> tauri::Result<()>;
+}
+
+impl TrackedEmitter for AppHandle {
+    fn emit_tracked<S: Serialize>(&self, event: &str, payload: S) -> tauri::Result<()> {
+        let payload = serde_json::to_value(payload)?;
+        match self.try_state::<Arc<AppState>>() {
+            Some(state) => {
+                let recorded = state.events.record(event, payload);
+                state.recorder.capture(&recorded);
+                self.emit(event, recorded.payload)
+            }
+            None => self.emit(event, payload),
+        }
+    }
+
+    fn emit_agent_event<S: Serialize>(
+        &self,
+        event: &str,
+        agent_id: Uuid,
+        payload: S,
+    ) -> tauri::Result<()> {
+        let payload = serde_json::to_value(payload)?;
+        match self.try_state::<Arc<AppState>>() {
+            Some(state) => {
+                let scope = EventScope {
+                    agent_id,
+                    update: false,
+                };
+                let recorded = state.events.record_scoped(event, Some(scope), payload);
+                state.recorder.capture(&recorded);
+                self.emit_filter(event, recorded.payload, |target| {
+                    state.window_scopes.accepts_target(target, &agent_id)
+                })
+            }
+            None => self.emit(event, payload),
+        }
+    }
+
+    fn emit_agent_update<S: Serialize>(
+        &self,
+        event: &str,
+        agent_id: Uuid,
+        payload: S,
+    ) -> tauri::Result<()> {
+        let payload = serde_json::to_value(payload)?;
+        let Some(state) = self.try_state::<Arc<AppState>>() else {
+            return self.emit(event, payload);
+        };
+        let scope = EventScope {
+            agent_id,
+            update: true,
+        };
+        let recorded = state.events.record_scoped(event, Some(scope), payload);
+        state.recorder.capture(&recorded);
+        let scopes = &state.window_scopes;
+        if scopes.observed(&agent_id) {
+            self.emit_filter(
+                &agent_channel(event, &agent_id),
+                recorded.payload.clone(),
+                |target| scopes.subscribed_target(target, &agent_id),
+            )?;
+        }
+        self.emit_filter(event, recorded.payload, |target| {
+            scopes.accepts_update(target, &agent_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_tags_objects_and_evicts_oldest() {
+        let log = EventLog::with_capacity(2);
+        let first = log.record("agent-update", serde_json::json!({ "agent_id": "a" }));
+        assert_eq!(first.payload[SEQ_FIELD], 1);
+
+        let second = log.record("fog-revealed", Value::from("/src/main.rs"));
+        assert_eq!(second.payload[VALUE_FIELD], "/src/main.rs");
+        assert_eq!(second.payload[SEQ_FIELD], 2);
+        let third = log.record("factory-layout-changed", Value::Null);
+        assert_eq!(third.payload, serde_json::json!({ "_seq": 3 }));
+
+        let events = log.since(0);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].seq, 2);
+        assert_eq!(log.since(2).len(), 1);
+        assert_eq!(log.last_seq(), 3);
+
+        let all = |_: &RecordedEvent| true;
+        assert!(log.replay(1, all).complete);
+        assert!(!log.replay(0, all).complete);
+        assert!(log.replay(3, all).complete);
+        assert!(log.replay(3, all).events.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_records_are_buffered_in_order() {
+        let log = Arc::new(EventLog::new());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let log = log.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        log.record("agent-update", Value::Null);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let seqs: Vec<u64> = log.since(0).iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (1..=400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_replay_only_has_events_the_window_got() {
+        let log = EventLog::new();
+        let scopes = WindowScopes::new();
+        let (agent, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let scope = |agent_id, update| Some(EventScope { agent_id, update });
+        log.record("factory-layout-changed", Value::Null);
+        log.record_scoped("agent-status-changed", scope(other, false), Value::Null);
+        log.record_scoped("agent-update", scope(agent, true), Value::Null);
+        log.record_scoped("agent-update", scope(other, true), Value::Null);
+
+        let seqs = |label: &str| {
+            let replay = log.replay(0, |e| scopes.received(label, e));
+            assert!(replay.complete);
+            replay.events.iter().map(|e| e.seq).collect::<Vec<_>>()
+        };
+        assert_eq!(seqs("main"), vec![1, 2, 3, 4]);
+        scopes.set_interest("chat", HashSet::from([agent]));
+        assert_eq!(seqs("chat"), vec![1, 3]);
+        // Updates of unsubscribed agents don't reach channel windows
+        scopes.subscribe("main", &[agent]);
+        assert_eq!(seqs("main"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_window_scopes() {
+        let scopes = WindowScopes::new();
+        let agent = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        assert!(scopes.accepts("main", &agent));
+
+        scopes.set_interest("chat", HashSet::from([agent]));
+        assert!(scopes.accepts("chat", &agent));
+        assert!(!scopes.accepts("chat", &other));
+        assert!(scopes.accepts_target(&EventTarget::App, &other));
+
+        scopes.clear("chat");
+        assert!(scopes.accepts("chat", &other));
+    }
+
+    #[test]
+    fn test_agent_channel_subscriptions() {
+        let scopes = WindowScopes::new();
+        let (agent, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let main = EventTarget::WebviewWindow {
+            label: "main".to_string(),
+        };
+        assert!(scopes.accepts_update(&main, &agent));
+        assert!(!scopes.observed(&agent));
+
+        assert_eq!(scopes.subscribe("main", &[agent]), vec![agent]);
+        assert!(scopes.observed(&agent));
+        assert!(!scopes.observed(&other));
+        assert!(scopes.subscribed_target(&main, &agent));
+        assert!(!scopes.subscribed_target(&main, &other));
+        // Subscribed windows no longer get the shared event
+        assert!(!scopes.accepts_update(&main, &other));
+        assert_eq!(agent_channel("agent-update", &agent), format!("agent-update:{}", agent));
+
+        assert!(scopes.unsubscribe("main", &[agent]).is_empty());
+        assert!(scopes.accepts_update(&main, &other));
+        scopes.subscribe("chat", &[agent]);
+        scopes.clear("chat");
+        assert!(!scopes.observed(&agent));
+    }
+}
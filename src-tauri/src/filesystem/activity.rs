@@ -0,0 +1,43 @@
+use crate::clock::now_secs;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Tracks which files each agent has touched and when
+pub struct AgentFileActivity {
+    touched: DashMap<Uuid, HashMap<String, u64>>,
+}
+
+impl AgentFileActivity {
+    pub fn new() -> Self {
+        Self {
+            touched: DashMap::new(),
+        }
+    }
+
+    pub fn record(&self, agent_id: Uuid, path: &str) {
+        let now = now_secs();
+        self.touched
+            .entry(agent_id)
+            .or_default()
+            .insert(path.to_string(), now);
+    }
+
+    /// Files touched by an agent, with the last time each was touched
+    pub fn touched_by(&self, agent_id: &Uuid) -> HashMap<String, u64> {
+        self.touched
+            .get(agent_id)
+            .map(|t| t.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn forget(&self, agent_id: &Uuid) {
+        self.touched.remove(agent_id);
+    }
+}
+
+impl Default for AgentFileActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
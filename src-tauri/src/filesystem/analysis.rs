@@ -0,0 +1,240 @@
+//! Per-project statistics: files and lines per language, the largest
+//! directories and how much of the code is tests. Walks the tree produced
+//! by [`ProjectScanner`], so the same directories are skipped.
+//!
+//! [`ProjectScanner`]: super::scanner::ProjectScanner
+
+use super::milestones::is_test_file;
+use super::scanner::{FileNode, ProjectTree};
+use crate::clock::now_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Files larger than this are counted but their lines are not
+const MAX_COUNTED_BYTES: u64 = 2 * 1024 * 1024;
+const LARGEST_DIRECTORIES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub lines: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryStats {
+    pub path: String,
+    pub files: usize,
+    pub lines: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSummary {
+    /// Most lines first
+    pub languages: Vec<LanguageStats>,
+    pub total_files: usize,
+    /// Lines of files in a known language
+    pub total_lines: u64,
+    /// Directories with the most lines, below the root
+    pub largest_directories: Vec<DirectoryStats>,
+    pub test_files: usize,
+    pub source_files: usize,
+    /// Test files per non-test source file
+    pub test_ratio: f64,
+    pub analyzed_at: u64,
+}
+
+/// Language of a source file by its extension or name
+pub fn language_of(path: &str) -> Option<&'static str> {
+    let path = Path::new(path);
+    if path.file_name().and_then(|n| n.to_str()) == Some("Dockerfile") {
+        return Some("Dockerfile");
+    }
+    let language = match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "rs" => "Rust",
+        "ts" | "tsx" | "mts" | "cts" => "TypeScript",
+        "js" | "jsx" | "mjs" | "cjs" => "JavaScript",
+        "py" => "Python",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "swift" => "Swift",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "C++",
+        "cs" => "C#",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "scala" => "Scala",
+        "ex" | "exs" => "Elixir",
+        "hs" => "Haskell",
+        "lua" => "Lua",
+        "dart" => "Dart",
+        "zig" => "Zig",
+        "sh" | "bash" | "zsh" => "Shell",
+        "sql" => "SQL",
+        "html" | "htm" => "HTML",
+        "css" | "scss" | "sass" | "less" => "CSS",
+        "vue" => "Vue",
+        "svelte" => "Svelte",
+        "md" | "mdx" => "Markdown",
+        "json" => "JSON",
+        "yaml" | "yml" => "YAML",
+        "toml" => "TOML",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Languages that are data or prose rather than code
+fn is_source_language(language: &str) -> bool {
+    !matches!(language, "Markdown" | "JSON" | "YAML" | "TOML")
+}
+
+fn count_lines(path: &str) -> u64 {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() <= MAX_COUNTED_BYTES => {}
+        _ => return 0,
+    }
+    fs::read(path)
+        .map(|bytes| {
+            let newlines = bytes.iter().filter(|&&b| b == b'\n').count() as u64;
+            // A last line without a trailing newline still counts
+            newlines + u64::from(bytes.last().is_some_and(|&b| b != b'\n'))
+        })
+        .unwrap_or(0)
+}
+
+/// Summarize a scanned project, reading each known-language file once
+pub fn analyze_tree(tree: &ProjectTree) -> ProjectSummary {
+    analyze_with(tree, count_lines)
+}
+
+fn analyze_with(tree: &ProjectTree, lines_of: impl Fn(&str) -> u64) -> ProjectSummary {
+    let mut languages: HashMap<&'static str, LanguageStats> = HashMap::new();
+    let mut directories: HashMap<String, DirectoryStats> = HashMap::new();
+    let (mut total_files, mut total_lines, mut test_files, mut source_files) = (0, 0, 0, 0);
+
+    let mut stack: Vec<(&FileNode, Vec<&str>)> = vec![(&tree.tree, Vec::new())];
+    while let Some((node, ancestors)) = stack.pop() {
+        if node.is_dir {
+            for child in node.children.iter().flatten() {
+                let mut path = ancestors.clone();
+                // The root is not a directory of interest
+                if !std::ptr::eq(node, &tree.tree) {
+                    path.push(node.path.as_str());
+                }
+                stack.push((child, path));
+            }
+            continue;
+        }
+
+        total_files += 1;
+        let Some(language) = language_of(&node.path) else {
+            continue;
+        };
+        let lines = lines_of(&node.path);
+        total_lines += lines;
+        let stats = languages.entry(language).or_insert_with(|| LanguageStats {
+            language: language.to_string(),
+            files: 0,
+            lines: 0,
+        });
+        stats.files += 1;
+        stats.lines += lines;
+
+        if is_source_language(language) {
+            if is_test_file(&node.path) {
+                test_files += 1;
+            } else {
+                source_files += 1;
+            }
+        }
+        for dir in ancestors {
+            let stats = directories.entry(dir.to_string()).or_insert_with(|| DirectoryStats {
+                path: dir.to_string(),
+                files: 0,
+                lines: 0,
+            });
+            stats.files += 1;
+            stats.lines += lines;
+        }
+    }
+
+    let mut languages: Vec<LanguageStats> = languages.into_values().collect();
+    languages.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.language.cmp(&b.language)));
+    let mut largest_directories: Vec<DirectoryStats> = directories.into_values().collect();
+    largest_directories.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.path.cmp(&b.path)));
+    largest_directories.truncate(LARGEST_DIRECTORIES);
+
+    ProjectSummary {
+        languages,
+        total_files,
+        total_lines,
+        largest_directories,
+        test_files,
+        source_files,
+        test_ratio: if source_files == 0 {
+            0.0
+        } else {
+            test_files as f64 / source_files as f64
+        },
+        analyzed_at: now_secs(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(path: &str, children: Option<Vec<FileNode>>) -> FileNode {
+        FileNode {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            path: path.to_string(),
+            is_dir: children.is_some(),
+            children,
+            explored: false,
+        }
+    }
+
+    #[test]
+    fn test_summary_counts_languages_directories_and_tests() {
+        let tree = ProjectTree {
+            root: "/p".to_string(),
+            tree: node(
+                "/p",
+                Some(vec![
+                    node(
+                        "/p/src",
+                        Some(vec![
+                            node("/p/src/main.rs", None),
+                            node("/p/src/ui", Some(vec![node("/p/src/ui/app.tsx", None)])),
+                        ]),
+                    ),
+                    node("/p/tests", Some(vec![node("/p/tests/api_test.rs", None)])),
+                    node("/p/README.md", None),
+                    node("/p/logo.png", None),
+                ]),
+            ),
+            total_files: 5,
+            total_dirs: 3,
+        };
+        let summary = analyze_with(&tree, |path| match path {
+            "/p/src/main.rs" => 100,
+            "/p/src/ui/app.tsx" => 40,
+            "/p/tests/api_test.rs" => 30,
+            _ => 10,
+        });
+
+        assert_eq!(summary.total_files, 5);
+        assert_eq!(summary.total_lines, 180);
+        assert_eq!(summary.languages[0], LanguageStats { language: "Rust".into(), files: 2, lines: 130 });
+        assert_eq!(summary.languages.len(), 3);
+        let dirs: Vec<(&str, u64)> =
+            summary.largest_directories.iter().map(|d| (d.path.as_str(), d.lines)).collect();
+        assert_eq!(dirs, vec![("/p/src", 140), ("/p/src/ui", 40), ("/p/tests", 30)]);
+        assert_eq!((summary.test_files, summary.source_files), (1, 2));
+        assert_eq!(summary.test_ratio, 0.5);
+    }
+}
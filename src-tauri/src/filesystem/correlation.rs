@@ -0,0 +1,150 @@
+//! Attribution of file system changes to agents. The watcher only reports
+//! paths, so a change is matched against the files agents' tool calls
+//! pointed at shortly before.
+
+use crate::clock::now_ms;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Touches older than this are not used to attribute changes
+const ATTRIBUTION_WINDOW_MS: u64 = 30_000;
+/// Touches kept across all agents
+const MAX_TOUCHES: usize = 1000;
+
+/// How a change was matched to an agent's touch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributionMatch {
+    /// The agent touched the changed file
+    File,
+    /// The agent touched another file in the same directory
+    Directory,
+}
+
+/// Probable agent behind a changed path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileAttribution {
+    pub path: String,
+    pub agent_id: Uuid,
+    #[serde(rename = "match")]
+    pub matched: AttributionMatch,
+    /// Time between the agent's touch and the change
+    pub age_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Touch {
+    agent_id: Uuid,
+    path: String,
+    at_ms: u64,
+}
+
+pub struct FileChangeCorrelator {
+    touches: Mutex<VecDeque<Touch>>,
+}
+
+/// Whether `changed` (absolute, from the watcher) is `touched`, which agents
+/// may report relative to their working directory
+fn same_file(changed: &Path, touched: &Path) -> bool {
+    if touched.is_absolute() {
+        changed == touched
+    } else {
+        changed.ends_with(touched)
+    }
+}
+
+impl FileChangeCorrelator {
+    pub fn new() -> Self {
+        Self {
+            touches: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Remember that an agent's tool call pointed at `path`
+    pub fn record(&self, agent_id: Uuid, path: &str) {
+        self.record_at(agent_id, path, now_ms());
+    }
+
+    fn record_at(&self, agent_id: Uuid, path: &str, at_ms: u64) {
+        let mut touches = self.touches.lock().unwrap();
+        if touches.len() >= MAX_TOUCHES {
+            touches.pop_front();
+        }
+        touches.push_back(Touch {
+            agent_id,
+            path: path.to_string(),
+            at_ms,
+        });
+    }
+
+    /// The agent that most probably changed `path`, preferring the latest
+    /// touch of the file itself over touches of its siblings
+    pub fn attribute(&self, path: &str) -> Option<FileAttribution> {
+        self.attribute_at(path, now_ms())
+    }
+
+    fn attribute_at(&self, path: &str, now: u64) -> Option<FileAttribution> {
+        let changed = Path::new(path);
+        let touches = self.touches.lock().unwrap();
+        let recent = || {
+            touches
+                .iter()
+                .rev()
+                .filter(|t| now.saturating_sub(t.at_ms) <= ATTRIBUTION_WINDOW_MS)
+        };
+        let attribution = |touch: &Touch, matched| FileAttribution {
+            path: path.to_string(),
+            agent_id: touch.agent_id,
+            matched,
+            age_ms: now.saturating_sub(touch.at_ms),
+        };
+
+        if let Some(touch) = recent().find(|t| same_file(changed, Path::new(&t.path))) {
+            return Some(attribution(touch, AttributionMatch::File));
+        }
+        let dir = changed.parent()?;
+        recent()
+            .find(|t| Path::new(&t.path).parent().is_some_and(|d| same_file(dir, d)))
+            .map(|touch| attribution(touch, AttributionMatch::Directory))
+    }
+
+    pub fn forget(&self, agent_id: &Uuid) {
+        self.touches.lock().unwrap().retain(|t| t.agent_id != *agent_id);
+    }
+}
+
+impl Default for FileChangeCorrelator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribution_prefers_file_match_within_window() {
+        let correlator = FileChangeCorrelator::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        correlator.record_at(a, "/work/app/src/main.rs", 1_000);
+        correlator.record_at(b, "src/lib.rs", 2_000);
+
+        // Relative paths match on whole components
+        let hit = correlator.attribute_at("/work/app/src/lib.rs", 5_000).unwrap();
+        assert_eq!((hit.agent_id, hit.matched, hit.age_ms), (b, AttributionMatch::File, 3_000));
+
+        // Only a sibling was touched; the latest toucher of the directory wins
+        let sibling = correlator.attribute_at("/work/app/src/util.rs", 5_000).unwrap();
+        assert_eq!((sibling.agent_id, sibling.matched), (b, AttributionMatch::Directory));
+
+        assert_eq!(correlator.attribute_at("/work/app/src/main.rs", 40_000), None);
+        assert_eq!(correlator.attribute_at("/elsewhere/main.rs", 5_000), None);
+
+        correlator.forget(&b);
+        assert_eq!(correlator.attribute_at("/work/app/src/lib.rs", 5_000).unwrap().agent_id, a);
+    }
+}
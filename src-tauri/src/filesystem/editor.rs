@@ -0,0 +1,101 @@
+//! Resolving how to open a file in the user's editor.
+
+/// How a file should be opened
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorLaunch {
+    /// Open a URI handled by an installed editor
+    Url(String),
+    /// Run an editor command
+    Command { program: String, args: Vec<String> },
+    /// Let the OS pick the default application
+    Default,
+}
+
+/// Resolve the configured editor into a launch method.
+///
+/// `editor` is either a known editor (`vscode`, `cursor`, `idea`, ...) opened
+/// through its URI scheme, or a command template where `{path}` and `{line}`
+/// are substituted, e.g. `subl {path}:{line}`.
+pub fn resolve_editor(editor: Option<&str>, path: &str, line: Option<u32>) -> EditorLaunch {
+    let editor = match editor.map(str::trim).filter(|e| !e.is_empty()) {
+        Some(editor) => editor,
+        None => return EditorLaunch::Default,
+    };
+    let line = line.unwrap_or(1);
+
+    // Windows paths (C:\...) need a leading slash after `file`
+    let separator = if path.starts_with('/') { "" } else { "/" };
+    let file_scheme =
+        |scheme: &str| EditorLaunch::Url(format!("{}://file{}{}:{}", scheme, separator, path, line));
+    let jetbrains = |scheme: &str| {
+        EditorLaunch::Url(format!(
+            "{}://open?file={}&line={}",
+            scheme,
+            encode_query(path),
+            line
+        ))
+    };
+
+    match editor {
+        "vscode" | "code" => file_scheme("vscode"),
+        "vscode-insiders" => file_scheme("vscode-insiders"),
+        "cursor" => file_scheme("cursor"),
+        "windsurf" => file_scheme("windsurf"),
+        "idea" | "intellij" => jetbrains("idea"),
+        "webstorm" => jetbrains("webstorm"),
+        "pycharm" => jetbrains("pycharm"),
+        "rustrover" => jetbrains("rustrover"),
+        template => {
+            let mut parts = template.split_whitespace().map(|part| {
+                part.replace("{path}", path)
+                    .replace("{line}", &line.to_string())
+            });
+            let program = parts.next().unwrap_or_default();
+            let mut args: Vec<String> = parts.collect();
+            if !template.contains("{path}") {
+                args.push(path.to_string());
+            }
+            EditorLaunch::Command { program, args }
+        }
+    }
+}
+
+fn encode_query(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_uri_schemes() {
+        assert_eq!(
+            resolve_editor(Some("vscode"), "/src/main.rs", Some(42)),
+            EditorLaunch::Url("vscode://file/src/main.rs:42".to_string())
+        );
+        assert_eq!(
+            resolve_editor(Some("idea"), "/src/my file.rs", None),
+            EditorLaunch::Url("idea://open?file=%2Fsrc%2Fmy+file.rs&line=1".to_string())
+        );
+        assert_eq!(resolve_editor(None, "/src/main.rs", None), EditorLaunch::Default);
+    }
+
+    #[test]
+    fn test_resolve_command_template() {
+        assert_eq!(
+            resolve_editor(Some("subl {path}:{line}"), "/src/main.rs", Some(7)),
+            EditorLaunch::Command {
+                program: "subl".to_string(),
+                args: vec!["/src/main.rs:7".to_string()],
+            }
+        );
+        assert_eq!(
+            resolve_editor(Some("nvim-qt"), "/src/main.rs", None),
+            EditorLaunch::Command {
+                program: "nvim-qt".to_string(),
+                args: vec!["/src/main.rs".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Paths the scanner, the watcher and the fog skip: version control,
+//! dependency and build output directories, patterns from settings, and the
+//! rules of a project's `.acptorioignore`.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Per-project ignore file, in gitignore syntax
+pub const PROJECT_IGNORE_FILE: &str = ".acptorioignore";
+
+/// Names skipped everywhere
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    ".git",
+    "node_modules",
+    "target",
+    ".DS_Store",
+    "dist",
+    "build",
+    "__pycache__",
+    ".venv",
+    "venv",
+    ".idea",
+    ".vscode",
+];
+
+/// Filter in effect, the defaults plus the patterns from settings
+static CURRENT: Lazy<RwLock<PathFilter>> = Lazy::new(|| RwLock::new(PathFilter::default()));
+
+/// Project root -> rules of its ignore file, None if it has none
+static PROJECT_RULES: Lazy<RwLock<HashMap<PathBuf, Option<Arc<Gitignore>>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// File and directory names to skip: exact names, or `*.ext` for extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathFilter {
+    patterns: Vec<String>,
+}
+
+impl PathFilter {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self { patterns }
+    }
+
+    /// The defaults plus `extra`
+    pub fn with_extra(extra: &[String]) -> Self {
+        let mut patterns: Vec<String> = DEFAULT_IGNORE_PATTERNS.iter().map(|p| p.to_string()).collect();
+        patterns.extend(extra.iter().filter(|p| !p.is_empty()).cloned());
+        Self::new(patterns)
+    }
+
+    pub fn ignores_name(&self, name: &str) -> bool {
+        self.patterns.iter().any(|p| match p.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') => name.ends_with(suffix),
+            _ => name == p,
+        })
+    }
+
+    /// Whether any component of a path relative to a project root is ignored
+    pub fn ignores_path(&self, relative: &Path) -> bool {
+        relative.components().any(|c| match c {
+            Component::Normal(name) => self.ignores_name(&name.to_string_lossy()),
+            _ => false,
+        })
+    }
+
+    /// Whether `path` is below `root` in an ignored directory, or is an
+    /// ignored file, by name or by the project's ignore file. Paths outside
+    /// `root` are not filtered.
+    pub fn ignores_within(&self, root: &Path, path: &Path) -> bool {
+        path.strip_prefix(root).is_ok_and(|relative| {
+            self.ignores_path(relative)
+                || ignored_by_project(root, relative, path.is_dir())
+        })
+    }
+}
+
+/// Whether the ignore file of the project at `root` matches `relative` or
+/// one of its parent directories
+pub fn ignored_by_project(root: &Path, relative: &Path, is_dir: bool) -> bool {
+    project_rules(root).is_some_and(|rules| {
+        rules
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+    })
+}
+
+/// Rules of the project's ignore file, read on first use
+fn project_rules(root: &Path) -> Option<Arc<Gitignore>> {
+    if let Some(rules) = PROJECT_RULES.read().unwrap().get(root) {
+        return rules.clone();
+    }
+    reload_project_ignore(root)
+}
+
+/// Read the project's ignore file again, e.g. after it changed
+pub fn reload_project_ignore(root: &Path) -> Option<Arc<Gitignore>> {
+    let rules = read_project_ignore(root).map(Arc::new);
+    PROJECT_RULES
+        .write()
+        .unwrap()
+        .insert(root.to_path_buf(), rules.clone());
+    rules
+}
+
+fn read_project_ignore(root: &Path) -> Option<Gitignore> {
+    let path = root.join(PROJECT_IGNORE_FILE);
+    if !path.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(e) = builder.add(&path) {
+        tracing::warn!("Problem in {}: {}", path.display(), e);
+    }
+    match builder.build() {
+        Ok(rules) => Some(rules),
+        Err(e) => {
+            tracing::warn!("Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+impl Default for PathFilter {
+    fn default() -> Self {
+        Self::with_extra(&[])
+    }
+}
+
+/// The filter in effect
+pub fn current_filter() -> PathFilter {
+    CURRENT.read().unwrap().clone()
+}
+
+/// Skip `extra` patterns on top of the defaults from now on
+pub fn set_extra_ignore_patterns(extra: &[String]) {
+    *CURRENT.write().unwrap() = PathFilter::with_extra(extra);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_matches_names_extensions_and_components() {
+        let filter = PathFilter::with_extra(&["*.log".to_string(), "generated".to_string()]);
+        assert!(filter.ignores_name("node_modules"));
+        assert!(filter.ignores_name("debug.log"));
+        assert!(!filter.ignores_name("log"));
+        assert!(filter.ignores_path(Path::new("src/generated/api.rs")));
+        assert!(!filter.ignores_path(Path::new("src/targets.rs")));
+
+        let root = Path::new("/home/me/target/app");
+        assert!(!filter.ignores_within(root, Path::new("/home/me/target/app/src/main.rs")));
+        assert!(filter.ignores_within(root, Path::new("/home/me/target/app/target/debug/app")));
+        assert!(!filter.ignores_within(root, Path::new("/elsewhere/target/x")));
+    }
+
+    #[test]
+    fn test_project_ignore_file_is_merged_with_patterns() {
+        let root = std::env::temp_dir().join(format!("acptorio-ignore-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("fixtures")).unwrap();
+        std::fs::write(
+            root.join(PROJECT_IGNORE_FILE),
+            "fixtures/\n*.gen.ts\n!keep.gen.ts\n",
+        )
+        .unwrap();
+        reload_project_ignore(&root);
+
+        let filter = PathFilter::default();
+        assert!(filter.ignores_within(&root, &root.join("fixtures")));
+        assert!(filter.ignores_within(&root, &root.join("fixtures/users.json")));
+        assert!(filter.ignores_within(&root, &root.join("src/api.gen.ts")));
+        assert!(!filter.ignores_within(&root, &root.join("src/keep.gen.ts")));
+        assert!(filter.ignores_within(&root, &root.join("node_modules/x.js")));
+        assert!(!filter.ignores_within(&root, &root.join("src/main.ts")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+//! Fuzzy file name matching for quick-open over the cached trees of all
+//! projects, so the frontend gets a short ranked list instead of whole trees.
+
+use super::fog::HeatEntry;
+use super::scanner::ProjectTree;
+use super::suggest::collect_files;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Touches older than this no longer lift a file
+const RECENT_WINDOW_MS: u64 = 24 * 60 * 60 * 1000;
+const RECENT_BONUS: i64 = 30;
+const EXPLORED_BONUS: i64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoundFile {
+    pub path: String,
+    /// Relative to its project's root
+    pub relative_path: String,
+    pub project_root: String,
+    pub score: i64,
+    /// Char indices of `relative_path` matching the query, for highlighting
+    pub positions: Vec<usize>,
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '/' | '\\' | '_' | '-' | '.' | ' ')
+}
+
+/// Score of `candidate` if every char of `query` appears in it in order,
+/// ignoring case, with the positions matched. Matches at the start of a
+/// word, in a row, or in the file name count more.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let name_start = chars
+        .iter()
+        .rposition(|c| matches!(c, '/' | '\\'))
+        .map_or(0, |i| i + 1);
+
+    // Greedy from the end, so the match lands in the file name when it can
+    let mut positions = Vec::with_capacity(query.len());
+    let mut next = query.len();
+    for (i, c) in chars.iter().enumerate().rev() {
+        if next == 0 {
+            break;
+        }
+        if c.to_lowercase().eq(std::iter::once(query[next - 1])) {
+            positions.push(i);
+            next -= 1;
+        }
+    }
+    if next > 0 {
+        return None;
+    }
+    positions.reverse();
+
+    let mut score = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += 1;
+        let word_start = i == 0
+            || is_separator(chars[i - 1])
+            || (chars[i].is_uppercase() && chars[i - 1].is_lowercase());
+        if word_start {
+            score += 8;
+        }
+        if n > 0 && positions[n - 1] + 1 == i {
+            score += 5;
+        }
+        if i >= name_start {
+            score += 3;
+        }
+    }
+    let spread = (positions[positions.len() - 1] - positions[0] + 1 - positions.len()) as i64;
+    score -= spread.min(20);
+    score -= (chars.len() as i64 / 10).min(10);
+    Some((score, positions))
+}
+
+/// Files of `trees` matching `query`, best first: fuzzy score lifted by how
+/// recently agents touched the file and whether it is explored
+pub fn fuzzy_find_files(
+    trees: &[ProjectTree],
+    query: &str,
+    limit: usize,
+    heat: &[HeatEntry],
+    explored: &HashSet<String>,
+    now_ms: u64,
+) -> Vec<FoundFile> {
+    let touched: HashMap<&str, u64> = heat
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry.last_touched))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for tree in trees {
+        let mut files = Vec::new();
+        collect_files(&tree.tree, &mut files);
+        for path in files {
+            // Nested projects list the same files
+            if !seen.insert(path) {
+                continue;
+            }
+            let relative = Path::new(path)
+                .strip_prefix(&tree.root)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| path.to_string());
+            let Some((mut score, positions)) = fuzzy_score(query, &relative) else {
+                continue;
+            };
+            if let Some(&at) = touched.get(path) {
+                let age = now_ms.saturating_sub(at).min(RECENT_WINDOW_MS);
+                score += RECENT_BONUS - (RECENT_BONUS * age as i64 / RECENT_WINDOW_MS as i64);
+            }
+            if explored.contains(path) {
+                score += EXPLORED_BONUS;
+            }
+            found.push(FoundFile {
+                path: path.to_string(),
+                relative_path: relative,
+                project_root: tree.root.clone(),
+                score,
+                positions,
+            });
+        }
+    }
+    found.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(a.relative_path.len().cmp(&b.relative_path.len()))
+            .then(a.path.cmp(&b.path))
+    });
+    found.truncate(limit);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::FileNode;
+
+    fn file(path: &str) -> FileNode {
+        FileNode {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: path.to_string(),
+            is_dir: false,
+            children: None,
+            explored: false,
+        }
+    }
+
+    fn tree(root: &str, files: &[&str]) -> ProjectTree {
+        ProjectTree {
+            root: root.to_string(),
+            tree: FileNode {
+                name: root.to_string(),
+                path: root.to_string(),
+                is_dir: true,
+                children: Some(files.iter().map(|f| file(f)).collect()),
+                explored: true,
+            },
+            total_files: files.len(),
+            total_dirs: 1,
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_find_files_ranks_names_and_recent_files_first() {
+        assert!(fuzzy_score("xyz", "src/main.rs").is_none());
+        let (_, positions) = fuzzy_score("mr", "src/main.rs").unwrap();
+        assert_eq!(positions, vec![4, 9]);
+        let (name, _) = fuzzy_score("pool", "agent/pool.rs").unwrap();
+        let (scattered, _) = fuzzy_score("pool", "src/plugins/tool.rs").unwrap();
+        assert!(name > scattered);
+
+        let trees = [
+            tree("/work/app", &["/work/app/src/agent/pool.rs", "/work/app/src/plugins/tool.rs"]),
+            tree("/work/lib", &["/work/lib/pool.rs"]),
+        ];
+        let found = fuzzy_find_files(&trees, "pool", 10, &[], &HashSet::new(), 0);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].path, "/work/lib/pool.rs");
+        assert_eq!(found[1].relative_path, "src/agent/pool.rs");
+
+        // A file agents just touched moves up
+        let heat = [HeatEntry {
+            path: "/work/app/src/agent/pool.rs".to_string(),
+            count: 1,
+            first_touched: 1_000,
+            last_touched: 1_000,
+        }];
+        let found = fuzzy_find_files(&trees, "pool", 1, &heat, &HashSet::new(), 1_000);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].project_root, "/work/app");
+    }
+}
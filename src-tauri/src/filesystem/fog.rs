@@ -1,37 +1,529 @@
-use dashmap::DashSet;
+use super::milestones::{
+    is_test_file, ExplorationMilestone, ExplorationProgress, MilestoneTracker, EXPLORATION_MILESTONES,
+};
+use super::filters::current_filter;
+use super::scanner::{FileNode, ProjectTree};
+use crate::clock::now_ms;
+use dashmap::{DashMap, DashSet};
+use std::collections::{BTreeMap, HashSet};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FogVisibility {
+    Hidden,
+    /// Next to an explored file, with the scan radius on
+    Dim,
+    Explored,
+}
+
+/// How often agents touched a file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeatEntry {
+    pub path: String,
+    pub count: u64,
+    pub first_touched: u64,
+    pub last_touched: u64,
+}
 
 pub struct FogOfWar {
     explored_paths: DashSet<String>,
+    dim_paths: DashSet<String>,
+    heat: DashMap<String, HeatEntry>,
+    /// Entries of each directory of the project tree
+    directories: DashMap<String, Vec<String>>,
+    /// Files and directories of the project tree
+    tree_paths: DashSet<String>,
+    /// Files of the project tree
+    tree_files: DashSet<String>,
+    explored_tree_files: AtomicUsize,
+    test_file_touched: AtomicBool,
+    milestones: MilestoneTracker,
+    scan_radius: AtomicBool,
+    /// Last sequence number handed out to a change of the explored paths
+    seq: AtomicU64,
+    /// Sequence number at which each explored path was revealed
+    revealed_seq: DashMap<String, u64>,
+    /// Sequence number at which a path stopped being explored
+    removed_seq: DashMap<String, u64>,
+    /// Sequence number of the last reset
+    reset_seq: AtomicU64,
+    /// Root of the project tree; ignored paths below it are never revealed
+    root: RwLock<Option<PathBuf>>,
+}
+
+/// `path` with its `from` prefix replaced by `to`
+fn moved_path(path: &str, from: &str, to: &str) -> String {
+    match Path::new(path).strip_prefix(from) {
+        Ok(rest) if !rest.as_os_str().is_empty() => Path::new(to).join(rest).to_string_lossy().to_string(),
+        _ => to.to_string(),
+    }
+}
+
+fn is_within(path: &str, root: &str) -> bool {
+    Path::new(path).starts_with(root)
+}
+
+/// Move (or with no `to`, drop) every path of `set` at or below `from`
+fn relocate_set(set: &DashSet<String>, from: &str, to: Option<&str>) {
+    let affected: Vec<String> = set.iter().filter(|p| is_within(p, from)).map(|p| p.clone()).collect();
+    for path in affected {
+        set.remove(&path);
+        if let Some(to) = to {
+            set.insert(moved_path(&path, from, to));
+        }
+    }
 }
 
 impl FogOfWar {
     pub fn new() -> Self {
         Self {
             explored_paths: DashSet::new(),
+            dim_paths: DashSet::new(),
+            heat: DashMap::new(),
+            directories: DashMap::new(),
+            tree_paths: DashSet::new(),
+            tree_files: DashSet::new(),
+            explored_tree_files: AtomicUsize::new(0),
+            test_file_touched: AtomicBool::new(false),
+            milestones: MilestoneTracker::new(),
+            scan_radius: AtomicBool::new(false),
+            seq: AtomicU64::new(0),
+            revealed_seq: DashMap::new(),
+            removed_seq: DashMap::new(),
+            reset_seq: AtomicU64::new(0),
+            root: RwLock::new(None),
+        }
+    }
+
+    /// Whether `path` is in an ignored directory of the project
+    fn is_filtered(&self, path: &str) -> bool {
+        self.root
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|root| current_filter().ignores_within(root, Path::new(path)))
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn mark_revealed(&self, path: &str) {
+        self.revealed_seq.insert(path.to_string(), self.next_seq());
+        self.removed_seq.remove(path);
+    }
+
+    /// Number paths that entered or left the explored set by a move or drop
+    fn sync_seqs(&self) {
+        let gone: Vec<String> = self
+            .revealed_seq
+            .iter()
+            .filter(|e| !self.explored_paths.contains(e.key()))
+            .map(|e| e.key().clone())
+            .collect();
+        for path in gone {
+            self.revealed_seq.remove(&path);
+            self.removed_seq.insert(path, self.next_seq());
+        }
+        let added: Vec<String> = self
+            .explored_paths
+            .iter()
+            .filter(|p| !self.revealed_seq.contains_key(p.key()))
+            .map(|p| p.clone())
+            .collect();
+        for path in added {
+            self.mark_revealed(&path);
+        }
+    }
+
+    /// Sequence number of the latest change of the explored paths
+    pub fn seq(&self) -> u64 {
+        self.seq.load(Ordering::Relaxed)
+    }
+
+    /// Changes of the explored paths after `since_seq`
+    pub fn delta(&self, since_seq: u64) -> FogDelta {
+        let seq = self.seq();
+        let after = |entries: &DashMap<String, u64>| {
+            let mut changed: Vec<(u64, String)> = entries
+                .iter()
+                .filter(|e| *e.value() > since_seq)
+                .map(|e| (*e.value(), e.key().clone()))
+                .collect();
+            changed.sort_unstable();
+            changed.into_iter().map(|(_, path)| path).collect()
+        };
+        FogDelta {
+            seq,
+            reset: since_seq < self.reset_seq.load(Ordering::Relaxed),
+            revealed: after(&self.revealed_seq),
+            removed: after(&self.removed_seq),
+        }
+    }
+
+    /// Whether revealing a file dimly reveals the rest of its directory
+    pub fn set_scan_radius(&self, enabled: bool) {
+        self.scan_radius.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Index the project tree so the scan radius knows each file's siblings
+    pub fn set_tree(&self, tree: &ProjectTree) {
+        fn index(node: &FileNode, fog: &FogOfWar) {
+            fog.tree_paths.insert(node.path.clone());
+            if !node.is_dir {
+                fog.tree_files.insert(node.path.clone());
+            }
+            if let Some(children) = &node.children {
+                fog.directories.insert(
+                    node.path.clone(),
+                    children.iter().map(|c| c.path.clone()).collect(),
+                );
+                for child in children {
+                    index(child, fog);
+                }
+            }
+        }
+        *self.root.write().unwrap() = Some(PathBuf::from(&tree.root));
+        self.directories.clear();
+        self.tree_paths.clear();
+        self.tree_files.clear();
+        index(&tree.tree, self);
+        self.recount_tree_files();
+    }
+
+    fn recount_tree_files(&self) {
+        let explored = self.tree_files.iter().filter(|f| self.explored_paths.contains(f.key())).count();
+        self.explored_tree_files.store(explored, Ordering::Relaxed);
+    }
+
+    /// Drop a deleted path, and everything below it if it was a directory
+    pub fn forget(&self, path: &str) {
+        self.relocate(path, None);
+    }
+
+    /// Carry what was known about `from` over to `to` after a rename
+    pub fn rename(&self, from: &str, to: &str) {
+        self.relocate(from, Some(to));
+    }
+
+    fn relocate(&self, from: &str, to: Option<&str>) {
+        for set in [&self.explored_paths, &self.dim_paths, &self.tree_paths, &self.tree_files] {
+            relocate_set(set, from, to);
+        }
+
+        let moved_heat: Vec<String> = self.heat.iter().map(|e| e.key().clone()).filter(|p| is_within(p, from)).collect();
+        for path in moved_heat {
+            if let Some((_, mut entry)) = self.heat.remove(&path) {
+                if let Some(to) = to {
+                    entry.path = moved_path(&path, from, to);
+                    self.heat.insert(entry.path.clone(), entry);
+                }
+            }
+        }
+
+        let moved_dirs: Vec<String> =
+            self.directories.iter().map(|e| e.key().clone()).filter(|p| is_within(p, from)).collect();
+        for dir in moved_dirs {
+            if let Some((_, entries)) = self.directories.remove(&dir) {
+                if let Some(to) = to {
+                    self.directories.insert(moved_path(&dir, from, to), entries);
+                }
+            }
+        }
+        for mut entries in self.directories.iter_mut() {
+            let mut kept: Vec<String> = Vec::with_capacity(entries.len());
+            for entry in entries.iter() {
+                match to {
+                    _ if !is_within(entry, from) => kept.push(entry.clone()),
+                    Some(to) => kept.push(moved_path(entry, from, to)),
+                    None => {}
+                }
+            }
+            *entries = kept;
+        }
+        // A rename into a known directory shows up in its listing
+        if let Some(to) = to {
+            if let Some(mut entries) = Path::new(to)
+                .parent()
+                .and_then(|parent| self.directories.get_mut(parent.to_string_lossy().as_ref()))
+            {
+                if !entries.iter().any(|e| e == to) {
+                    entries.push(to.to_string());
+                }
+            }
+        }
+
+        self.sync_seqs();
+        self.recount_tree_files();
+    }
+
+    /// After a rescan of the project at `root`, drop fog of paths below it
+    /// that are no longer in the tree
+    pub fn prune_to_tree(&self, root: &str) {
+        let stale = |p: &String| is_within(p, root) && !self.tree_paths.contains(p);
+        self.explored_paths.retain(|p| !stale(p));
+        self.dim_paths.retain(|p| !stale(p));
+        self.heat.retain(|p, _| !stale(p));
+        self.sync_seqs();
+        self.recount_tree_files();
+    }
+
+    /// Explored paths below the project root that are no longer in the tree
+    fn stale_paths(&self) -> Vec<String> {
+        let Some(root) = self.root.read().unwrap().clone() else {
+            return Vec::new();
+        };
+        self.explored_paths
+            .iter()
+            .filter(|p| Path::new(p.as_str()).starts_with(&root) && !self.tree_paths.contains(p.key()))
+            .map(|p| p.clone())
+            .collect()
+    }
+
+    /// Drop fog of paths no longer in the project tree, returning how many
+    /// explored paths went
+    pub fn prune_stale(&self) -> usize {
+        let Some(root) = self.root.read().unwrap().clone() else {
+            return 0;
+        };
+        let stale = self.stale_paths().len();
+        self.prune_to_tree(&root.to_string_lossy());
+        stale
+    }
+
+    /// Exploration of the project tree overall and per top-level directory
+    pub fn statistics(&self) -> FogStatistics {
+        let root = self.root.read().unwrap().clone().unwrap_or_default();
+        let mut directories: BTreeMap<String, DirectoryFogStats> = BTreeMap::new();
+        for file in self.tree_files.iter() {
+            let relative = Path::new(file.as_str()).strip_prefix(&root).unwrap_or(Path::new(file.as_str()));
+            let mut components = relative.components();
+            let first = components.next().map(|c| c.as_os_str().to_string_lossy().to_string());
+            // Files directly in the root are counted under "."
+            let directory = match (first, components.next()) {
+                (Some(first), Some(_)) => first,
+                _ => ".".to_string(),
+            };
+            let stats = directories.entry(directory.clone()).or_insert_with(|| DirectoryFogStats {
+                directory,
+                total_files: 0,
+                explored_files: 0,
+            });
+            stats.total_files += 1;
+            if self.explored_paths.contains(file.key()) {
+                stats.explored_files += 1;
+            }
+        }
+
+        let total_files = self.tree_files.len();
+        let explored_files: usize = directories.values().map(|d| d.explored_files).sum();
+        FogStatistics {
+            total_files,
+            explored_files,
+            percent_explored: if total_files == 0 {
+                0.0
+            } else {
+                explored_files as f64 * 100.0 / total_files as f64
+            },
+            stale_paths: self.stale_paths().len(),
+            directories: directories.into_values().collect(),
+        }
+    }
+
+    /// Exploration of several project trees taken as one, e.g. a project
+    /// group; `directories` then holds one entry per project root
+    pub fn statistics_across(&self, trees: &[ProjectTree]) -> FogStatistics {
+        fn index(node: &FileNode, paths: &mut HashSet<String>, files: &mut Vec<String>) {
+            paths.insert(node.path.clone());
+            if !node.is_dir {
+                files.push(node.path.clone());
+            }
+            for child in node.children.iter().flatten() {
+                index(child, paths, files);
+            }
+        }
+        let mut tree_paths = HashSet::new();
+        let mut directories = Vec::new();
+        for tree in trees {
+            let mut files = Vec::new();
+            index(&tree.tree, &mut tree_paths, &mut files);
+            directories.push(DirectoryFogStats {
+                directory: tree.root.clone(),
+                total_files: files.len(),
+                explored_files: files.iter().filter(|f| self.explored_paths.contains(*f)).count(),
+            });
+        }
+
+        let total_files: usize = directories.iter().map(|d| d.total_files).sum();
+        let explored_files: usize = directories.iter().map(|d| d.explored_files).sum();
+        let stale_paths = self
+            .explored_paths
+            .iter()
+            .filter(|p| trees.iter().any(|t| is_within(p, &t.root)) && !tree_paths.contains(p.key()))
+            .count();
+        FogStatistics {
+            total_files,
+            explored_files,
+            percent_explored: if total_files == 0 {
+                0.0
+            } else {
+                explored_files as f64 * 100.0 / total_files as f64
+            },
+            stale_paths,
+            directories,
         }
     }
 
     pub fn reveal(&self, path: &str) {
-        self.explored_paths.insert(path.to_string());
+        if self.is_filtered(path) {
+            return;
+        }
+        if self.explored_paths.insert(path.to_string()) {
+            self.mark_revealed(path);
+            self.on_newly_explored(path);
+        }
+        self.dim_paths.remove(path);
+        if !self.scan_radius.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(parent) = Path::new(path).parent() else {
+            return;
+        };
+        if let Some(siblings) = self.directories.get(parent.to_string_lossy().as_ref()) {
+            for sibling in siblings.iter() {
+                if !self.explored_paths.contains(sibling) {
+                    self.dim_paths.insert(sibling.clone());
+                }
+            }
+        }
+    }
+
+    fn on_newly_explored(&self, path: &str) {
+        if self.tree_files.contains(path) {
+            self.explored_tree_files.fetch_add(1, Ordering::Relaxed);
+        }
+        if is_test_file(path) {
+            self.test_file_touched.store(true, Ordering::Relaxed);
+        }
+        let progress = ExplorationProgress {
+            revealed: self.explored_paths.len(),
+            tree_files: self.tree_files.len(),
+            explored_tree_files: self.explored_tree_files.load(Ordering::Relaxed),
+            test_file_touched: self.test_file_touched.load(Ordering::Relaxed),
+        };
+        for milestone in self.milestones.update(&progress, now_ms()) {
+            // No subscribers is fine
+            let _ = EXPLORATION_MILESTONES.send(milestone);
+        }
+    }
+
+    /// Every exploration milestone, with when it was reached
+    pub fn milestones(&self) -> Vec<ExplorationMilestone> {
+        self.milestones.all()
+    }
+
+    /// Reveal a file and count it towards the heat map
+    pub fn touch(&self, path: &str) {
+        self.touch_at(path, now_ms());
+    }
+
+    fn touch_at(&self, path: &str, at_ms: u64) {
+        if self.is_filtered(path) {
+            return;
+        }
+        self.reveal(path);
+        let mut entry = self.heat.entry(path.to_string()).or_insert_with(|| HeatEntry {
+            path: path.to_string(),
+            count: 0,
+            first_touched: at_ms,
+            last_touched: at_ms,
+        });
+        entry.count += 1;
+        entry.last_touched = at_ms;
+    }
+
+    /// The `top_n` most touched files, most recently touched first on ties
+    pub fn heatmap(&self, top_n: usize) -> Vec<HeatEntry> {
+        let mut entries: Vec<HeatEntry> = self.heat.iter().map(|e| e.value().clone()).collect();
+        entries.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(b.last_touched.cmp(&a.last_touched))
+        });
+        entries.truncate(top_n);
+        entries
     }
 
     pub fn reveal_many(&self, paths: &[String]) {
         for path in paths {
-            self.explored_paths.insert(path.clone());
+            self.reveal(path);
+        }
+    }
+
+    /// Reveal a directory of the project tree and its entries, or its whole
+    /// subtree when `recursive`. Returns the newly explored paths in path
+    /// order, or None when `dir` is not a directory of the tree.
+    pub fn reveal_subtree(&self, dir: &str, recursive: bool) -> Option<Vec<String>> {
+        if !self.directories.contains_key(dir) {
+            return None;
         }
+        let mut revealed = Vec::new();
+        let mut pending = vec![dir.to_string()];
+        while let Some(path) = pending.pop() {
+            if self.is_filtered(&path) {
+                continue;
+            }
+            if !self.explored_paths.contains(&path) {
+                revealed.push(path.clone());
+            }
+            self.reveal(&path);
+            if path == dir || recursive {
+                if let Some(children) = self.directories.get(&path) {
+                    pending.extend(children.iter().cloned());
+                }
+            }
+        }
+        revealed.sort_unstable();
+        Some(revealed)
     }
 
     pub fn is_explored(&self, path: &str) -> bool {
         self.explored_paths.contains(path)
     }
 
+    pub fn visibility(&self, path: &str) -> FogVisibility {
+        if self.explored_paths.contains(path) {
+            FogVisibility::Explored
+        } else if self.dim_paths.contains(path) {
+            FogVisibility::Dim
+        } else {
+            FogVisibility::Hidden
+        }
+    }
+
     pub fn explored_paths(&self) -> Vec<String> {
         self.explored_paths.iter().map(|p| p.clone()).collect()
     }
 
+    pub fn dim_paths(&self) -> Vec<String> {
+        self.dim_paths.iter().map(|p| p.clone()).collect()
+    }
+
+    /// Forget what was revealed; the tree index and scan radius stay
     pub fn reset(&self) {
         self.explored_paths.clear();
+        self.dim_paths.clear();
+        self.heat.clear();
+        self.explored_tree_files.store(0, Ordering::Relaxed);
+        self.test_file_touched.store(false, Ordering::Relaxed);
+        self.milestones.reset();
+        self.revealed_seq.clear();
+        self.removed_seq.clear();
+        self.reset_seq.store(self.next_seq(), Ordering::Relaxed);
     }
 
     pub fn explored_count(&self) -> usize {
@@ -47,15 +539,278 @@ impl Default for FogOfWar {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FogState {
+    /// Pass to `get_fog_delta` for the changes after this state
+    #[serde(default)]
+    pub seq: u64,
     pub explored_paths: Vec<String>,
     pub total_explored: usize,
+    /// Paths seen only through the scan radius
+    #[serde(default)]
+    pub dim_paths: Vec<String>,
 }
 
 impl From<&FogOfWar> for FogState {
     fn from(fog: &FogOfWar) -> Self {
         Self {
+            // Read first, so changes made while copying show up in the next delta
+            seq: fog.seq(),
             explored_paths: fog.explored_paths(),
             total_explored: fog.explored_count(),
+            dim_paths: fog.dim_paths(),
+        }
+    }
+}
+
+/// Explored files of one top-level directory of the project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryFogStats {
+    /// Relative to the project root; "." for files directly in it. The
+    /// project root itself in the statistics of several projects.
+    pub directory: String,
+    pub total_files: usize,
+    pub explored_files: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FogStatistics {
+    /// Files of the project tree
+    pub total_files: usize,
+    pub explored_files: usize,
+    pub percent_explored: f64,
+    /// Explored paths that are no longer in the project tree
+    pub stale_paths: usize,
+    pub directories: Vec<DirectoryFogStats>,
+}
+
+/// Changes of the explored paths since a sequence number
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FogDelta {
+    pub seq: u64,
+    /// The fog was reset since; `revealed` then holds every explored path
+    pub reset: bool,
+    /// In the order they were revealed
+    pub revealed: Vec<String>,
+    /// No longer explored, because they were deleted or renamed
+    pub removed: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heatmap_ranks_by_count_then_recency() {
+        let fog = FogOfWar::new();
+        fog.touch_at("/p/a.rs", 1);
+        fog.touch_at("/p/b.rs", 2);
+        fog.touch_at("/p/a.rs", 3);
+        fog.touch_at("/p/c.rs", 4);
+        fog.reveal("/p/d.rs");
+
+        let heat = fog.heatmap(2);
+        assert_eq!(heat.len(), 2);
+        assert_eq!((heat[0].path.as_str(), heat[0].count), ("/p/a.rs", 2));
+        assert_eq!((heat[0].first_touched, heat[0].last_touched), (1, 3));
+        assert_eq!(heat[1].path, "/p/c.rs");
+        assert!(fog.is_explored("/p/b.rs"));
+        assert_eq!(fog.heatmap(10).len(), 3);
+    }
+
+    fn node(path: &str, children: Option<Vec<FileNode>>) -> FileNode {
+        FileNode {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            path: path.to_string(),
+            is_dir: children.is_some(),
+            children,
+            explored: false,
         }
     }
+
+    #[test]
+    fn test_scan_radius_dims_siblings() {
+        let tree = ProjectTree {
+            root: "/p".to_string(),
+            tree: node(
+                "/p",
+                Some(vec![
+                    node("/p/src", Some(vec![node("/p/src/a.rs", None), node("/p/src/b.rs", None)])),
+                    node("/p/README.md", None),
+                ]),
+            ),
+            total_files: 3,
+            total_dirs: 1,
+        };
+        let fog = FogOfWar::new();
+        fog.set_tree(&tree);
+        fog.reveal("/p/src/a.rs");
+        assert_eq!(fog.visibility("/p/src/b.rs"), FogVisibility::Hidden);
+
+        fog.set_scan_radius(true);
+        fog.reveal("/p/src/a.rs");
+        assert_eq!(fog.visibility("/p/src/a.rs"), FogVisibility::Explored);
+        assert_eq!(fog.visibility("/p/src/b.rs"), FogVisibility::Dim);
+        assert_eq!(fog.visibility("/p/README.md"), FogVisibility::Hidden);
+
+        fog.reveal("/p/src/b.rs");
+        assert_eq!(fog.visibility("/p/src/b.rs"), FogVisibility::Explored);
+        assert!(FogState::from(&fog).dim_paths.is_empty());
+
+        // Two of three tree files explored
+        let reached: Vec<String> = fog
+            .milestones()
+            .into_iter()
+            .filter(|m| m.reached_at.is_some())
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(reached, vec!["explored_25_percent", "explored_50_percent"]);
+    }
+
+    #[test]
+    fn test_reveal_subtree() {
+        let tree = ProjectTree {
+            root: "/p".to_string(),
+            tree: node(
+                "/p",
+                Some(vec![
+                    node(
+                        "/p/src",
+                        Some(vec![
+                            node("/p/src/util", Some(vec![node("/p/src/util/x.rs", None)])),
+                            node("/p/src/a.rs", None),
+                        ]),
+                    ),
+                    node("/p/README.md", None),
+                ]),
+            ),
+            total_files: 3,
+            total_dirs: 2,
+        };
+        let fog = FogOfWar::new();
+        fog.set_tree(&tree);
+        fog.reveal("/p/src/a.rs");
+
+        assert_eq!(fog.reveal_subtree("/p/README.md", true), None);
+        assert_eq!(
+            fog.reveal_subtree("/p/src", false),
+            Some(vec!["/p/src".to_string(), "/p/src/util".to_string()])
+        );
+        assert!(!fog.is_explored("/p/src/util/x.rs"));
+        assert_eq!(fog.reveal_subtree("/p/src", true), Some(vec!["/p/src/util/x.rs".to_string()]));
+        assert!(!fog.is_explored("/p/README.md"));
+    }
+
+    #[test]
+    fn test_statistics_and_pruning_of_stale_paths() {
+        let tree = ProjectTree {
+            root: "/p".to_string(),
+            tree: node(
+                "/p",
+                Some(vec![
+                    node("/p/src", Some(vec![node("/p/src/a.rs", None), node("/p/src/b.rs", None)])),
+                    node("/p/README.md", None),
+                ]),
+            ),
+            total_files: 3,
+            total_dirs: 1,
+        };
+        let fog = FogOfWar::new();
+        fog.set_tree(&tree);
+        fog.reveal("/p/src/a.rs");
+        fog.reveal("/p/README.md");
+        fog.reveal("/p/src/deleted.rs");
+        fog.reveal("/elsewhere/x.rs");
+
+        let stats = fog.statistics();
+        assert_eq!((stats.total_files, stats.explored_files, stats.stale_paths), (3, 2, 1));
+        assert!((stats.percent_explored - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            stats.directories,
+            vec![
+                DirectoryFogStats { directory: ".".to_string(), total_files: 1, explored_files: 1 },
+                DirectoryFogStats { directory: "src".to_string(), total_files: 2, explored_files: 1 },
+            ]
+        );
+
+        assert_eq!(fog.prune_stale(), 1);
+        assert!(!fog.is_explored("/p/src/deleted.rs"));
+        assert!(fog.is_explored("/elsewhere/x.rs"));
+        assert_eq!(fog.statistics().stale_paths, 0);
+
+        // Several projects taken as one count /elsewhere too
+        let elsewhere = ProjectTree {
+            root: "/elsewhere".to_string(),
+            tree: node("/elsewhere", Some(vec![node("/elsewhere/x.rs", None), node("/elsewhere/y.rs", None)])),
+            total_files: 2,
+            total_dirs: 0,
+        };
+        let stats = fog.statistics_across(&[tree, elsewhere]);
+        assert_eq!((stats.total_files, stats.explored_files, stats.stale_paths), (5, 3, 0));
+        assert_eq!(stats.directories[1].directory, "/elsewhere");
+        assert_eq!(stats.directories[1].explored_files, 1);
+    }
+
+    #[test]
+    fn test_forget_and_rename_follow_the_filesystem() {
+        let tree = ProjectTree {
+            root: "/p".to_string(),
+            tree: node(
+                "/p",
+                Some(vec![
+                    node("/p/src", Some(vec![node("/p/src/a.rs", None), node("/p/src/b.rs", None)])),
+                    node("/p/README.md", None),
+                ]),
+            ),
+            total_files: 3,
+            total_dirs: 1,
+        };
+        let fog = FogOfWar::new();
+        fog.set_tree(&tree);
+        fog.touch("/p/src/a.rs");
+        fog.reveal("/p/src/b.rs");
+        fog.reveal("/p/README.md");
+
+        fog.rename("/p/src", "/p/lib");
+        assert!(fog.is_explored("/p/lib/a.rs"));
+        assert!(!fog.is_explored("/p/src/a.rs"));
+        assert_eq!(fog.heatmap(1)[0].path, "/p/lib/a.rs");
+
+        fog.forget("/p/lib/b.rs");
+        assert!(!fog.is_explored("/p/lib/b.rs"));
+        assert_eq!(fog.explored_count(), 2);
+
+        // Rescan where README.md is gone; paths outside the root are kept
+        fog.reveal("/elsewhere/notes.md");
+        fog.set_tree(&ProjectTree {
+            root: "/p".to_string(),
+            tree: node("/p", Some(vec![node("/p/lib", Some(vec![node("/p/lib/a.rs", None)]))])),
+            total_files: 1,
+            total_dirs: 1,
+        });
+        fog.prune_to_tree("/p");
+        let mut explored = fog.explored_paths();
+        explored.sort();
+        assert_eq!(explored, vec!["/elsewhere/notes.md", "/p/lib/a.rs"]);
+    }
+
+    #[test]
+    fn test_delta_holds_changes_since_seq() {
+        let fog = FogOfWar::new();
+        fog.reveal("/p/a.rs");
+        let since = FogState::from(&fog).seq;
+        fog.reveal("/p/b.rs");
+        fog.reveal("/p/a.rs");
+        fog.rename("/p/b.rs", "/p/c.rs");
+
+        let delta = fog.delta(since);
+        assert!(!delta.reset);
+        assert_eq!(delta.revealed, vec!["/p/c.rs".to_string()]);
+        assert_eq!(delta.removed, vec!["/p/b.rs".to_string()]);
+        assert!(fog.delta(delta.seq).revealed.is_empty());
+
+        fog.reset();
+        fog.reveal("/p/d.rs");
+        let delta = fog.delta(since);
+        assert!(delta.reset);
+        assert_eq!(delta.revealed, vec!["/p/d.rs".to_string()]);
+    }
 }
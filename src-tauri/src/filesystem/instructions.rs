@@ -0,0 +1,121 @@
+//! Instruction files agent CLIs read from a project (AGENTS.md, CLAUDE.md,
+//! .cursorrules), found when a project is scanned.
+
+use super::scanner::{FileNode, ProjectTree};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const INSTRUCTION_FILE_NAMES: &[&str] = &["AGENTS.md", "CLAUDE.md", ".cursorrules"];
+
+/// Longer instruction files are cut off
+const MAX_INSTRUCTION_BYTES: usize = 32 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstructionFile {
+    /// Relative to the project root
+    pub path: String,
+    pub content: String,
+    pub truncated: bool,
+}
+
+fn is_instruction_file(name: &str) -> bool {
+    INSTRUCTION_FILE_NAMES.contains(&name)
+}
+
+/// Relative paths of the instruction files in a scanned tree, shallowest first
+pub fn instruction_paths(tree: &ProjectTree) -> Vec<String> {
+    fn walk(node: &FileNode, root: &Path, found: &mut Vec<String>) {
+        for child in node.children.iter().flatten() {
+            if child.is_dir {
+                walk(child, root, found);
+            } else if is_instruction_file(&child.name) {
+                let path = Path::new(&child.path);
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                found.push(relative.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    walk(&tree.tree, Path::new(&tree.root), &mut found);
+    found.sort_by_key(|p| (Path::new(p).components().count(), p.clone()));
+    found
+}
+
+/// Read instruction files below `root`; unreadable files are skipped
+pub fn read_instructions(root: &Path, paths: &[String]) -> Vec<InstructionFile> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let bytes = fs::read(root.join(path)).ok()?;
+            let truncated = bytes.len() > MAX_INSTRUCTION_BYTES;
+            let bytes = &bytes[..bytes.len().min(MAX_INSTRUCTION_BYTES)];
+            Some(InstructionFile {
+                path: path.clone(),
+                content: String::from_utf8_lossy(bytes).to_string(),
+                truncated,
+            })
+        })
+        .collect()
+}
+
+/// Instruction files that apply to an agent working in `directory`: those
+/// in the directory and in its parents up to `root`, outermost first
+pub fn instructions_for_directory(directory: &Path, root: &Path) -> Vec<InstructionFile> {
+    let mut dirs: Vec<&Path> = directory
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .collect();
+    dirs.reverse();
+    let paths: Vec<String> = dirs
+        .into_iter()
+        .flat_map(|dir| INSTRUCTION_FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let relative = path.strip_prefix(root).ok()?;
+            Some(relative.to_string_lossy().to_string())
+        })
+        .collect();
+    read_instructions(root, &paths)
+}
+
+/// Preamble quoting instruction files, for agents whose CLI doesn't read them
+pub fn instructions_preamble(files: &[InstructionFile]) -> Option<String> {
+    let sections: Vec<String> = files
+        .iter()
+        .filter(|f| !f.content.trim().is_empty())
+        .map(|f| format!("Project instructions from {}:\n\n{}", f.path, f.content.trim()))
+        .collect();
+    (!sections.is_empty()).then(|| sections.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::ProjectScanner;
+
+    #[test]
+    fn test_detects_and_reads_instruction_files() {
+        let root =
+            std::env::temp_dir().join(format!("acptorio-instructions-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("web/src")).unwrap();
+        fs::write(root.join("AGENTS.md"), "Run the tests.").unwrap();
+        fs::write(root.join("web/CLAUDE.md"), "Use pnpm.").unwrap();
+        fs::write(root.join("web/README.md"), "Not instructions").unwrap();
+
+        let tree = ProjectScanner::new().scan(&root).unwrap();
+        let paths = instruction_paths(&tree);
+        let nested = Path::new("web").join("CLAUDE.md").to_string_lossy().to_string();
+        assert_eq!(paths, vec!["AGENTS.md".to_string(), nested.clone()]);
+
+        let files = instructions_for_directory(&root.join("web/src"), &root);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].path, nested);
+        let preamble = instructions_preamble(&files).unwrap();
+        assert!(preamble.starts_with("Project instructions from AGENTS.md:\n\nRun the tests."));
+        assert!(preamble.ends_with("Use pnpm."));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
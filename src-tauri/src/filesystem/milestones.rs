@@ -0,0 +1,195 @@
+//! Exploration milestones reached as the fog lifts, measured against the
+//! loaded project tree.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Newly reached milestones, forwarded to the frontend
+pub static EXPLORATION_MILESTONES: Lazy<broadcast::Sender<ExplorationMilestone>> =
+    Lazy::new(|| broadcast::channel(32).0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    /// Share of the tree's files explored
+    ExploredPercent(usize),
+    /// Paths revealed, inside the tree or not
+    FilesRevealed(usize),
+    FirstTestFile,
+}
+
+const GOALS: &[Goal] = &[
+    Goal::FirstTestFile,
+    Goal::FilesRevealed(10),
+    Goal::FilesRevealed(100),
+    Goal::FilesRevealed(1000),
+    Goal::ExploredPercent(25),
+    Goal::ExploredPercent(50),
+    Goal::ExploredPercent(75),
+    Goal::ExploredPercent(100),
+];
+
+impl Goal {
+    fn id(&self) -> String {
+        match self {
+            Goal::ExploredPercent(percent) => format!("explored_{}_percent", percent),
+            Goal::FilesRevealed(count) => format!("revealed_{}_files", count),
+            Goal::FirstTestFile => "first_test_file".to_string(),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Goal::ExploredPercent(100) => "Entire project explored".to_string(),
+            Goal::ExploredPercent(percent) => format!("{}% of project explored", percent),
+            Goal::FilesRevealed(count) => format!("{} files revealed", count),
+            Goal::FirstTestFile => "First test file touched".to_string(),
+        }
+    }
+
+    fn is_reached(&self, progress: &ExplorationProgress) -> bool {
+        match *self {
+            Goal::ExploredPercent(percent) => {
+                progress.tree_files > 0 && progress.explored_tree_files * 100 >= percent * progress.tree_files
+            }
+            Goal::FilesRevealed(count) => progress.revealed >= count,
+            Goal::FirstTestFile => progress.test_file_touched,
+        }
+    }
+}
+
+/// Counters the milestones are computed from
+#[derive(Debug, Clone, Default)]
+pub struct ExplorationProgress {
+    pub revealed: usize,
+    pub tree_files: usize,
+    pub explored_tree_files: usize,
+    pub test_file_touched: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplorationMilestone {
+    pub id: String,
+    pub title: String,
+    /// Unix millis; None while not reached
+    pub reached_at: Option<u64>,
+}
+
+/// Test files by the usual naming conventions across languages
+pub fn is_test_file(path: &str) -> bool {
+    let path = Path::new(path);
+    let in_test_dir = path.parent().is_some_and(|dir| {
+        dir.components().any(|c| {
+            matches!(
+                c.as_os_str().to_str(),
+                Some("test" | "tests" | "__tests__" | "spec" | "specs")
+            )
+        })
+    });
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let stem = name.split('.').next().unwrap_or(name);
+    in_test_dir
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_spec")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+pub struct MilestoneTracker {
+    reached: Mutex<HashMap<String, u64>>,
+}
+
+impl MilestoneTracker {
+    pub fn new() -> Self {
+        Self {
+            reached: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record milestones `progress` reaches for the first time and return them
+    pub fn update(&self, progress: &ExplorationProgress, now_ms: u64) -> Vec<ExplorationMilestone> {
+        let mut reached = self.reached.lock().unwrap();
+        let mut newly = Vec::new();
+        for goal in GOALS {
+            if reached.contains_key(&goal.id()) || !goal.is_reached(progress) {
+                continue;
+            }
+            reached.insert(goal.id(), now_ms);
+            newly.push(ExplorationMilestone {
+                id: goal.id(),
+                title: goal.title(),
+                reached_at: Some(now_ms),
+            });
+        }
+        newly
+    }
+
+    /// Every milestone, reached or not
+    pub fn all(&self) -> Vec<ExplorationMilestone> {
+        let reached = self.reached.lock().unwrap();
+        GOALS
+            .iter()
+            .map(|goal| ExplorationMilestone {
+                id: goal.id(),
+                title: goal.title(),
+                reached_at: reached.get(&goal.id()).copied(),
+            })
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        self.reached.lock().unwrap().clear();
+    }
+}
+
+impl Default for MilestoneTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_test_file() {
+        assert!(is_test_file("/p/tests/mock_agent_test.rs"));
+        assert!(is_test_file("/p/src/app.test.tsx"));
+        assert!(is_test_file("/p/pkg/test_parser.py"));
+        assert!(is_test_file("/p/pkg/server_test.go"));
+        assert!(!is_test_file("/p/src/attestation.rs"));
+        assert!(!is_test_file("/p/tests"));
+    }
+
+    #[test]
+    fn test_milestones_are_reached_once() {
+        let tracker = MilestoneTracker::new();
+        let mut progress = ExplorationProgress {
+            revealed: 10,
+            tree_files: 20,
+            explored_tree_files: 5,
+            test_file_touched: false,
+        };
+        let ids = |milestones: Vec<ExplorationMilestone>| -> Vec<String> {
+            milestones.into_iter().map(|m| m.id).collect()
+        };
+        assert_eq!(ids(tracker.update(&progress, 1)), vec!["revealed_10_files", "explored_25_percent"]);
+        assert!(tracker.update(&progress, 2).is_empty());
+
+        progress.test_file_touched = true;
+        progress.explored_tree_files = 10;
+        assert_eq!(ids(tracker.update(&progress, 3)), vec!["first_test_file", "explored_50_percent"]);
+
+        let all = tracker.all();
+        assert_eq!(all.len(), GOALS.len());
+        assert_eq!(all.iter().filter(|m| m.reached_at.is_some()).count(), 4);
+        assert_eq!(all[0].reached_at, Some(3));
+    }
+}
@@ -1,7 +1,33 @@
+pub mod activity;
+pub mod analysis;
+pub mod correlation;
+pub mod editor;
+pub mod filters;
+pub mod finder;
 pub mod fog;
+pub mod instructions;
+pub mod milestones;
+pub mod reveal_batch;
 pub mod scanner;
+pub mod search;
+pub mod suggest;
+pub mod tree_cache;
 pub mod watcher;
+pub mod workspaces;
 
+pub use activity::*;
+pub use analysis::*;
+pub use correlation::*;
+pub use editor::*;
+pub use filters::*;
+pub use finder::*;
 pub use fog::*;
+pub use instructions::*;
+pub use milestones::*;
+pub use reveal_batch::*;
 pub use scanner::*;
+pub use search::*;
+pub use suggest::*;
+pub use tree_cache::*;
 pub use watcher::*;
+pub use workspaces::*;
@@ -0,0 +1,80 @@
+//! Batching of fog reveals. An agent reading hundreds of files would
+//! otherwise send one event per file; reveals are queued instead and sent
+//! as one numbered batch per interval.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Time between two `fog-revealed-batch` events
+pub const REVEAL_BATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FogRevealBatch {
+    /// Numbers batches from 1 without gaps, so a missed batch can be noticed
+    pub seq: u64,
+    /// In the order they were first revealed
+    pub paths: Vec<String>,
+}
+
+#[derive(Default)]
+struct Pending {
+    paths: Vec<String>,
+    seen: HashSet<String>,
+}
+
+/// Revealed paths waiting for the next batch
+#[derive(Default)]
+pub struct RevealQueue {
+    pending: Mutex<Pending>,
+    next_seq: AtomicU64,
+}
+
+impl RevealQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, path: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.seen.insert(path.to_string()) {
+            pending.paths.push(path.to_string());
+        }
+    }
+
+    /// The queued paths as the next batch, None when nothing is queued
+    pub fn take_batch(&self) -> Option<FogRevealBatch> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.paths.is_empty() {
+            return None;
+        }
+        Some(FogRevealBatch {
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed) + 1,
+            paths: pending.paths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_are_deduplicated_and_numbered() {
+        let queue = RevealQueue::new();
+        assert_eq!(queue.take_batch(), None);
+
+        queue.push("/p/a.rs");
+        queue.push("/p/b.rs");
+        queue.push("/p/a.rs");
+        let first = queue.take_batch().unwrap();
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.paths, vec!["/p/a.rs".to_string(), "/p/b.rs".to_string()]);
+        assert_eq!(queue.take_batch(), None);
+
+        queue.push("/p/a.rs");
+        assert_eq!(queue.take_batch().unwrap().seq, 2);
+    }
+}
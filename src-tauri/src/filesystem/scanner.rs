@@ -1,3 +1,4 @@
+use super::filters::{current_filter, ignored_by_project, reload_project_ignore, PathFilter};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
@@ -21,32 +22,21 @@ pub struct ProjectTree {
 }
 
 pub struct ProjectScanner {
-    ignore_patterns: Vec<String>,
+    /// None to use the filter in effect when scanning
+    filter: Option<PathFilter>,
     max_depth: usize,
 }
 
 impl ProjectScanner {
     pub fn new() -> Self {
         Self {
-            ignore_patterns: vec![
-                ".git".to_string(),
-                "node_modules".to_string(),
-                "target".to_string(),
-                ".DS_Store".to_string(),
-                "dist".to_string(),
-                "build".to_string(),
-                "__pycache__".to_string(),
-                ".venv".to_string(),
-                "venv".to_string(),
-                ".idea".to_string(),
-                ".vscode".to_string(),
-            ],
+            filter: None,
             max_depth: 10,
         }
     }
 
     pub fn with_ignore_patterns(mut self, patterns: Vec<String>) -> Self {
-        self.ignore_patterns = patterns;
+        self.filter = Some(PathFilter::new(patterns));
         self
     }
 
@@ -67,7 +57,10 @@ impl ProjectScanner {
         let mut total_files = 0;
         let mut total_dirs = 0;
 
-        let tree = self.scan_dir(root, 0, &mut total_files, &mut total_dirs)?;
+        let filter = self.filter.clone().unwrap_or_else(current_filter);
+        // Pick up edits of the ignore file the watcher may not have seen
+        reload_project_ignore(root);
+        let tree = self.scan_dir(root, root, &filter, 0, &mut total_files, &mut total_dirs)?;
 
         Ok(ProjectTree {
             root: root.to_string_lossy().to_string(),
@@ -79,7 +72,9 @@ impl ProjectScanner {
 
     fn scan_dir(
         &self,
+        root: &Path,
         path: &Path,
+        filter: &PathFilter,
         depth: usize,
         total_files: &mut usize,
         total_dirs: &mut usize,
@@ -112,13 +107,16 @@ impl ProjectScanner {
                 .to_string();
 
             // Skip ignored patterns
-            if self.should_ignore(&entry_name) {
+            let is_dir = entry_path.is_dir();
+            let relative = entry_path.strip_prefix(root).unwrap_or(&entry_path);
+            if filter.ignores_name(&entry_name) || ignored_by_project(root, relative, is_dir) {
                 continue;
             }
 
-            if entry_path.is_dir() {
+            if is_dir {
                 *total_dirs += 1;
-                let child = self.scan_dir(&entry_path, depth + 1, total_files, total_dirs)?;
+                let child =
+                    self.scan_dir(root, &entry_path, filter, depth + 1, total_files, total_dirs)?;
                 children.push(child);
             } else {
                 *total_files += 1;
@@ -149,16 +147,6 @@ impl ProjectScanner {
             explored: true,
         })
     }
-
-    fn should_ignore(&self, name: &str) -> bool {
-        self.ignore_patterns.iter().any(|p| {
-            if p.starts_with("*.") {
-                name.ends_with(&p[1..])
-            } else {
-                name == p
-            }
-        })
-    }
 }
 
 impl Default for ProjectScanner {
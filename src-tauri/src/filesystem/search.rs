@@ -0,0 +1,94 @@
+//! Case-insensitive text search over the files of a project. Walks the tree
+//! produced by [`ProjectScanner`], so the same directories are skipped.
+
+use super::scanner::ProjectScanner;
+use super::suggest::collect_files;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Larger files are not searched
+const MAX_SEARCH_BYTES: u64 = 1024 * 1024;
+/// Matching lines kept per file
+const MAX_LINES_PER_FILE: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchMatch {
+    /// 1-based
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileMatches {
+    /// Relative to the project root
+    pub path: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Files under `root` with lines containing `query`, in path order, at most
+/// `max_files` of them. Blocking; run off the async runtime.
+pub fn search_project(root: &Path, query: &str, max_files: usize) -> Result<Vec<FileMatches>, String> {
+    if query.trim().is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let tree = ProjectScanner::new().scan(root).map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+    collect_files(&tree.tree, &mut files);
+    files.sort_unstable();
+
+    let query = query.to_lowercase();
+    let mut results = Vec::new();
+    for path in files {
+        if results.len() == max_files {
+            break;
+        }
+        let searchable = std::fs::metadata(path).is_ok_and(|m| m.len() <= MAX_SEARCH_BYTES);
+        // Unreadable and binary files fail to read as text
+        let Some(content) = searchable.then(|| std::fs::read_to_string(path).ok()).flatten() else {
+            continue;
+        };
+        let matches: Vec<SearchMatch> = content
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| text.to_lowercase().contains(&query))
+            .take(MAX_LINES_PER_FILE)
+            .map(|(i, text)| SearchMatch {
+                line: i + 1,
+                text: text.trim().to_string(),
+            })
+            .collect();
+        if !matches.is_empty() {
+            let relative = Path::new(path).strip_prefix(root).unwrap_or(Path::new(path));
+            results.push(FileMatches {
+                path: relative.to_string_lossy().to_string(),
+                matches,
+            });
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_project_finds_lines_case_insensitively() {
+        let dir = std::env::temp_dir().join(format!("acptorio-search-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("node_modules")).unwrap();
+        std::fs::write(dir.join("src/a.rs"), "use old_api;\nfn main() { OLD_API::call(); }\n").unwrap();
+        std::fs::write(dir.join("src/b.rs"), "fn b() {}\n").unwrap();
+        std::fs::write(dir.join("src/c.rs"), "old_api\n").unwrap();
+        std::fs::write(dir.join("node_modules/d.js"), "old_api\n").unwrap();
+
+        let results = search_project(&dir, "old_api", 10).unwrap();
+        let limited = search_project(&dir, "old_api", 1).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let paths: Vec<&str> = results.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/a.rs", "src/c.rs"]);
+        assert_eq!(results[0].matches[1], SearchMatch { line: 2, text: "fn main() { OLD_API::call(); }".to_string() });
+        assert_eq!(limited.len(), 1);
+    }
+}
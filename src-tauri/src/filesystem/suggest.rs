@@ -0,0 +1,185 @@
+//! Ranking project files as context candidates for a prompt.
+
+use super::scanner::{FileNode, ProjectScanner};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Files larger than this are ranked by name only
+const MAX_SEARCH_BYTES: u64 = 200 * 1024;
+/// Edits older than this no longer count as recent
+const RECENT_WINDOW_SECS: u64 = 60 * 60;
+
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "with", "that", "this", "from", "into", "make", "add", "fix", "use",
+    "should", "please", "can", "you", "all", "when", "what", "how", "not", "are", "have",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSuggestion {
+    pub path: String,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+/// Signals available for a single file
+#[derive(Debug, Default)]
+struct FileSignals<'a> {
+    path: &'a str,
+    /// Seconds since the file was last modified
+    modified_ago: Option<u64>,
+    touched_by_agent: bool,
+    explored: bool,
+    /// Prompt keywords found in the file's contents
+    content_matches: usize,
+}
+
+/// Lowercase words from the prompt that are worth searching for
+pub fn extract_keywords(prompt: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    prompt
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .map(|w| w.to_lowercase())
+        .filter(|w| w.len() >= 3 && !STOP_WORDS.contains(&w.as_str()))
+        .filter(|w| seen.insert(w.clone()))
+        .collect()
+}
+
+fn score(signals: &FileSignals, keywords: &[String]) -> (f64, Vec<String>) {
+    let mut score = 0.0;
+    let mut reasons = Vec::new();
+
+    let path = signals.path.to_lowercase();
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(&path);
+    let name_matches = keywords.iter().filter(|k| name.contains(k.as_str())).count();
+    let path_matches = keywords.iter().filter(|k| path.contains(k.as_str())).count() - name_matches;
+    if name_matches + path_matches > 0 {
+        score += 3.0 * name_matches as f64 + path_matches as f64;
+        reasons.push("name matches prompt".to_string());
+    }
+    if signals.content_matches > 0 {
+        score += signals.content_matches as f64;
+        reasons.push(format!("contains {} prompt keyword(s)", signals.content_matches));
+    }
+    if let Some(ago) = signals.modified_ago.filter(|a| *a < RECENT_WINDOW_SECS) {
+        score += 2.0 * (1.0 - ago as f64 / RECENT_WINDOW_SECS as f64);
+        reasons.push("recently modified".to_string());
+    }
+    if signals.touched_by_agent {
+        score += 2.0;
+        reasons.push("previously touched by this agent".to_string());
+    }
+    if signals.explored {
+        score += 0.5;
+        reasons.push("explored".to_string());
+    }
+
+    (score, reasons)
+}
+
+pub(super) fn collect_files<'a>(node: &'a FileNode, files: &mut Vec<&'a str>) {
+    if !node.is_dir {
+        files.push(&node.path);
+    }
+    for child in node.children.iter().flatten() {
+        collect_files(child, files);
+    }
+}
+
+/// Rank the files under `root` for `prompt`. Blocking; run off the async runtime.
+pub fn suggest_files(
+    root: &Path,
+    prompt: &str,
+    touched: &HashMap<String, u64>,
+    explored: &HashSet<String>,
+    limit: usize,
+) -> Result<Vec<ContextSuggestion>, String> {
+    let tree = ProjectScanner::new().scan(root).map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+    collect_files(&tree.tree, &mut files);
+
+    let keywords = extract_keywords(prompt);
+    let now = SystemTime::now();
+
+    let mut suggestions: Vec<ContextSuggestion> = files
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = std::fs::metadata(path).ok();
+            let modified_ago = metadata
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|m| now.duration_since(m).ok())
+                .map(|d| d.as_secs());
+
+            let content_matches = match metadata {
+                Some(m) if m.len() <= MAX_SEARCH_BYTES && !keywords.is_empty() => {
+                    std::fs::read_to_string(path)
+                        .map(|c| {
+                            let c = c.to_lowercase();
+                            keywords.iter().filter(|k| c.contains(k.as_str())).count()
+                        })
+                        .unwrap_or(0)
+                }
+                _ => 0,
+            };
+
+            let signals = FileSignals {
+                path,
+                modified_ago,
+                touched_by_agent: touched.contains_key(path),
+                explored: explored.contains(path),
+                content_matches,
+            };
+            let (score, reasons) = score(&signals, &keywords);
+            (score > 0.0).then(|| ContextSuggestion {
+                path: path.to_string(),
+                score,
+                reasons,
+            })
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.path.cmp(&b.path)));
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_keywords_skips_stop_words_and_duplicates() {
+        let keywords = extract_keywords("Fix the login handler and the Login form");
+        assert_eq!(keywords, vec!["login", "handler", "form"]);
+    }
+
+    #[test]
+    fn test_name_match_outranks_content_match() {
+        let keywords = extract_keywords("update the login handler");
+        let by_name = FileSignals {
+            path: "src/auth/login.rs",
+            ..Default::default()
+        };
+        let by_content = FileSignals {
+            path: "src/main.rs",
+            content_matches: 1,
+            ..Default::default()
+        };
+        assert!(score(&by_name, &keywords).0 > score(&by_content, &keywords).0);
+    }
+
+    #[test]
+    fn test_agent_activity_and_recency_add_score() {
+        let signals = FileSignals {
+            path: "README.md",
+            modified_ago: Some(60),
+            touched_by_agent: true,
+            ..Default::default()
+        };
+        let (score, reasons) = score(&signals, &[]);
+        assert!(score > 3.5);
+        assert_eq!(reasons.len(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+//! Last scanned [`ProjectTree`] of each project, kept under the app data dir
+//! so a project opens instantly while it is rescanned in the background.
+
+use super::scanner::{FileNode, ProjectTree};
+use crate::clock::now_secs;
+use crate::state::app_data_dir;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TREE_CACHE_DIR: &str = "tree-cache";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedTree {
+    pub tree: ProjectTree,
+    /// Hash of every path in the tree, to tell whether a rescan changed it
+    pub content_hash: String,
+    pub cached_at: u64,
+}
+
+/// Difference between a cached tree and a fresh scan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TreeDelta {
+    pub root: String,
+    /// New files
+    pub added: Vec<String>,
+    /// Files and directories that are gone, without their descendants
+    pub removed: Vec<String>,
+}
+
+impl TreeDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn collect_paths<'a>(node: &'a FileNode, out: &mut Vec<&'a FileNode>) {
+    out.push(node);
+    for child in node.children.iter().flatten() {
+        collect_paths(child, out);
+    }
+}
+
+pub fn content_hash(tree: &ProjectTree) -> String {
+    let mut nodes = Vec::new();
+    collect_paths(&tree.tree, &mut nodes);
+    let mut sha = Sha1::new();
+    for node in nodes {
+        sha.update(node.path.as_bytes());
+        sha.update(if node.is_dir { "/\n" } else { "\n" });
+    }
+    hex(&sha.finalize())
+}
+
+/// What changed from `old` to `new`
+pub fn diff_trees(old: &ProjectTree, new: &ProjectTree) -> TreeDelta {
+    let (mut old_nodes, mut new_nodes) = (Vec::new(), Vec::new());
+    collect_paths(&old.tree, &mut old_nodes);
+    collect_paths(&new.tree, &mut new_nodes);
+    let old_paths: HashSet<&str> = old_nodes.iter().map(|n| n.path.as_str()).collect();
+    let new_paths: HashSet<&str> = new_nodes.iter().map(|n| n.path.as_str()).collect();
+
+    let added = new_nodes
+        .iter()
+        .filter(|n| !n.is_dir && !old_paths.contains(n.path.as_str()))
+        .map(|n| n.path.clone())
+        .collect();
+    // Nodes come parents first, so a removed directory precedes its contents
+    let mut removed: Vec<String> = Vec::new();
+    for node in old_nodes.iter().filter(|n| !new_paths.contains(n.path.as_str())) {
+        if !removed.iter().any(|dir| Path::new(&node.path).starts_with(dir)) {
+            removed.push(node.path.clone());
+        }
+    }
+    TreeDelta {
+        root: new.root.clone(),
+        added,
+        removed,
+    }
+}
+
+pub struct TreeCache {
+    dir: PathBuf,
+}
+
+impl TreeCache {
+    pub fn new() -> Self {
+        Self::in_dir(app_data_dir().join(TREE_CACHE_DIR))
+    }
+
+    fn in_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn file_for(&self, root: &Path) -> PathBuf {
+        let digest = Sha1::digest(root.to_string_lossy().as_bytes());
+        self.dir.join(format!("{}.json", hex(&digest)))
+    }
+
+    pub fn load(&self, root: &Path) -> Option<CachedTree> {
+        let content = fs::read_to_string(self.file_for(root)).ok()?;
+        let cached: CachedTree = serde_json::from_str(&content).ok()?;
+        // Guard against hash collisions and moved projects
+        (Path::new(&cached.tree.root) == root).then_some(cached)
+    }
+
+    pub fn save(&self, tree: &ProjectTree) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create tree cache dir: {}", e))?;
+        let cached = CachedTree {
+            tree: tree.clone(),
+            content_hash: content_hash(tree),
+            cached_at: now_secs(),
+        };
+        let content = serde_json::to_string(&cached)
+            .map_err(|e| format!("Failed to serialize project tree: {}", e))?;
+        fs::write(self.file_for(Path::new(&tree.root)), content)
+            .map_err(|e| format!("Failed to write tree cache: {}", e))
+    }
+}
+
+impl Default for TreeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(path: &str, children: Option<Vec<FileNode>>) -> FileNode {
+        FileNode {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            path: path.to_string(),
+            is_dir: children.is_some(),
+            children,
+            explored: false,
+        }
+    }
+
+    fn tree(children: Vec<FileNode>) -> ProjectTree {
+        ProjectTree {
+            root: "/p".to_string(),
+            tree: node("/p", Some(children)),
+            total_files: 0,
+            total_dirs: 0,
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_new_files_and_topmost_removals() {
+        let old = tree(vec![
+            node("/p/old", Some(vec![node("/p/old/a.rs", None)])),
+            node("/p/keep.rs", None),
+        ]);
+        let new = tree(vec![
+            node("/p/new", Some(vec![node("/p/new/b.rs", None)])),
+            node("/p/keep.rs", None),
+        ]);
+        let delta = diff_trees(&old, &new);
+        assert_eq!(delta.added, vec!["/p/new/b.rs"]);
+        assert_eq!(delta.removed, vec!["/p/old"]);
+        assert!(diff_trees(&new, &new).is_empty());
+        assert_ne!(content_hash(&old), content_hash(&new));
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("acptorio-tree-cache-{}", uuid::Uuid::new_v4()));
+        let cache = TreeCache::in_dir(dir.clone());
+        let project = tree(vec![node("/p/a.rs", None)]);
+        assert!(cache.load(Path::new("/p")).is_none());
+
+        cache.save(&project).unwrap();
+        let cached = cache.load(Path::new("/p")).unwrap();
+        assert_eq!(cached.content_hash, content_hash(&project));
+        assert!(cache.load(Path::new("/q")).is_none());
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -1,12 +1,31 @@
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use super::correlation::{FileAttribution, FileChangeCorrelator};
+use super::filters::{current_filter, reload_project_ignore, PROJECT_IGNORE_FILE};
+use super::fog::FogOfWar;
+use crate::clock::now_ms;
+use crate::events::TrackedEmitter;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use tauri::{AppHandle, Emitter};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::AppHandle;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Changes in the watched project, for automation in the backend
+pub static FILE_EVENTS: Lazy<broadcast::Sender<FileEvent>> = Lazy::new(|| broadcast::channel(1024).0);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEvent {
     pub kind: FileEventKind,
     pub paths: Vec<String>,
+    /// Agent that most probably caused the change, if any
+    pub agent_id: Option<Uuid>,
+    /// Per-path guesses behind `agent_id`
+    pub attributions: Vec<FileAttribution>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +43,7 @@ impl From<notify::EventKind> for FileEventKind {
         use notify::EventKind::*;
         match kind {
             Create(_) => FileEventKind::Create,
+            Modify(ModifyKind::Name(_)) => FileEventKind::Rename,
             Modify(_) => FileEventKind::Modify,
             Remove(_) => FileEventKind::Remove,
             _ => FileEventKind::Other,
@@ -34,24 +54,75 @@ impl From<notify::EventKind> for FileEventKind {
 pub struct FileSystemWatcher {
     watcher: RecommendedWatcher,
     app_handle: AppHandle,
+    /// Watched directories, to filter changes relative to them
+    roots: Arc<RwLock<Vec<PathBuf>>>,
+    /// Changes reported so far
+    events: Arc<AtomicU64>,
+    /// When the last change was reported, in ms; 0 before the first
+    last_event_at: Arc<AtomicU64>,
 }
 
 impl FileSystemWatcher {
-    pub fn new(app_handle: AppHandle) -> Result<Self, WatcherError> {
+    pub fn new(
+        app_handle: AppHandle,
+        correlator: Arc<FileChangeCorrelator>,
+        fog: Arc<FogOfWar>,
+    ) -> Result<Self, WatcherError> {
         let app_handle_clone = app_handle.clone();
+        let roots: Arc<RwLock<Vec<PathBuf>>> = Arc::default();
+        let watched = roots.clone();
+        let events: Arc<AtomicU64> = Arc::default();
+        let last_event_at: Arc<AtomicU64> = Arc::default();
+        let (counted, stamped) = (events.clone(), last_event_at.clone());
+        let mut renames = RenamePairer::default();
 
         let watcher = RecommendedWatcher::new(
             move |res: Result<Event, notify::Error>| {
-                if let Ok(event) = res {
+                let Ok(event) = res else {
+                    return;
+                };
+                for (kind, paths) in renames.pair(&event) {
+                    invalidate_fog(&fog, &kind, &paths);
+                    let roots = watched.read().unwrap();
+                    for root in roots.iter() {
+                        if paths.iter().any(|p| *p == root.join(PROJECT_IGNORE_FILE)) {
+                            reload_project_ignore(root);
+                        }
+                    }
+                    // Build output, dependencies and the like are noise
+                    let filter = current_filter();
+                    let kept: Vec<bool> = paths
+                        .iter()
+                        .map(|p| !roots.iter().any(|root| filter.ignores_within(root, p)))
+                        .collect();
+                    let kind = match (kind, kept.as_slice()) {
+                        // Moved out of or into an ignored directory
+                        (FileEventKind::Rename, [false, true]) => FileEventKind::Create,
+                        (FileEventKind::Rename, [true, false]) => FileEventKind::Remove,
+                        (kind, _) => kind,
+                    };
+                    let paths: Vec<String> = paths
+                        .iter()
+                        .zip(&kept)
+                        .filter(|(_, kept)| **kept)
+                        .map(|(p, _)| p.to_string_lossy().to_string())
+                        .collect();
+                    if paths.is_empty() {
+                        continue;
+                    }
+                    let attributions: Vec<FileAttribution> =
+                        paths.iter().filter_map(|p| correlator.attribute(p)).collect();
                     let file_event = FileEvent {
-                        kind: event.kind.into(),
-                        paths: event
-                            .paths
-                            .iter()
-                            .map(|p| p.to_string_lossy().to_string())
-                            .collect(),
+                        kind,
+                        paths,
+                        agent_id: attributions.first().map(|a| a.agent_id),
+                        attributions,
                     };
-                    let _ = app_handle_clone.emit("fs-change", &file_event);
+                    counted.fetch_add(1, Ordering::Relaxed);
+                    stamped.store(now_ms(), Ordering::Relaxed);
+                    let _ = app_handle_clone.emit_tracked("fs-change", &file_event);
+                    // No subscribers is fine
+                    let _ = FILE_EVENTS.send(file_event);
                 }
             },
             Config::default(),
@@ -61,22 +132,324 @@ impl FileSystemWatcher {
         Ok(Self {
             watcher,
             app_handle,
+            roots,
+            events,
+            last_event_at,
         })
     }
 
     pub fn watch(&mut self, path: &Path) -> Result<(), WatcherError> {
         self.watcher
             .watch(path, RecursiveMode::Recursive)
-            .map_err(|e| WatcherError::WatchFailed(e.to_string()))
+            .map_err(|e| WatcherError::WatchFailed(e.to_string()))?;
+        self.roots.write().unwrap().push(path.to_path_buf());
+        Ok(())
     }
 
     pub fn unwatch(&mut self, path: &Path) -> Result<(), WatcherError> {
+        self.roots.write().unwrap().retain(|root| root != path);
         self.watcher
             .unwatch(path)
             .map_err(|e| WatcherError::UnwatchFailed(e.to_string()))
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedPath {
+    pub path: String,
+    /// Watched as the open project
+    pub project: bool,
+    /// Added through `watch_path`; stays watched when the project changes
+    #[serde(default)]
+    pub explicit: bool,
+    pub since: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatcherStatus {
+    pub running: bool,
+    pub watched: Vec<WatchedPath>,
+    /// Changes reported since the watcher started
+    pub events: u64,
+    pub last_event_at: Option<u64>,
+    /// Last failure to start the watcher or to watch a path
+    pub last_error: Option<String>,
+}
+
+/// What the slot needs of a watcher, so its bookkeeping can be tested
+/// without a running app
+trait PathWatcher {
+    fn watch(&mut self, path: &Path) -> Result<(), String>;
+    fn unwatch(&mut self, path: &Path) -> Result<(), String>;
+}
+
+impl PathWatcher for FileSystemWatcher {
+    fn watch(&mut self, path: &Path) -> Result<(), String> {
+        FileSystemWatcher::watch(self, path).map_err(|e| e.to_string())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<(), String> {
+        FileSystemWatcher::unwatch(self, path).map_err(|e| e.to_string())
+    }
+}
+
+struct WatcherSlot<W = FileSystemWatcher> {
+    watcher: Option<W>,
+    watched: Vec<WatchedPath>,
+    last_error: Option<String>,
+}
+
+impl<W> Default for WatcherSlot<W> {
+    fn default() -> Self {
+        Self {
+            watcher: None,
+            watched: Vec::new(),
+            last_error: None,
+        }
+    }
+}
+
+impl<W: PathWatcher> WatcherSlot<W> {
+    fn find(&mut self, path: &Path) -> Option<&mut WatchedPath> {
+        self.watched.iter_mut().find(|w| Path::new(&w.path) == path)
+    }
+
+    /// Watch a path not watched yet, starting the watcher if needed
+    fn add(
+        &mut self,
+        path: &Path,
+        project: bool,
+        start: impl FnOnce() -> Result<W, String>,
+    ) -> Result<(), String> {
+        let watcher = match self.watcher.take() {
+            Some(watcher) => watcher,
+            None => start()?,
+        };
+        let watcher = self.watcher.insert(watcher);
+        let watched = watcher.watch(path);
+        if self.watched.is_empty() && watched.is_err() {
+            self.watcher = None;
+        }
+        watched?;
+        self.watched.push(WatchedPath {
+            path: path.to_string_lossy().to_string(),
+            project,
+            explicit: !project,
+            since: now_ms(),
+        });
+        Ok(())
+    }
+
+    /// Stop watching a path, stopping the watcher with the last one. The
+    /// entry goes even if the watcher fails to let go of the path, e.g.
+    /// because the directory was removed.
+    fn remove(&mut self, path: &str) -> Result<(), String> {
+        let unwatched = match self.watcher.as_mut() {
+            Some(watcher) => watcher.unwatch(Path::new(path)),
+            None => Ok(()),
+        };
+        self.watched.retain(|w| w.path != path);
+        if self.watched.is_empty() {
+            self.watcher = None;
+        }
+        unwatched
+    }
+
+    fn watch(&mut self, path: &Path, start: impl FnOnce() -> Result<W, String>) -> Result<(), String> {
+        if let Some(watched) = self.find(path) {
+            watched.explicit = true;
+            return Ok(());
+        }
+        self.add(path, false, start)
+    }
+
+    /// Stop watching a path; one that is also the open project only stops
+    /// being kept across project changes
+    fn unwatch(&mut self, path: &Path) -> Result<(), String> {
+        let watched = self
+            .find(path)
+            .ok_or_else(|| format!("{} is not watched", path.display()))?;
+        if watched.project && watched.explicit {
+            watched.explicit = false;
+            return Ok(());
+        }
+        let path = watched.path.clone();
+        self.remove(&path)
+    }
+
+    /// Watch `path` as the open project instead of the previous one
+    fn replace_project(
+        &mut self,
+        path: &Path,
+        start: impl FnOnce() -> Result<W, String>,
+    ) -> Result<(), String> {
+        let previous: Vec<String> = self
+            .watched
+            .iter_mut()
+            .filter(|w| w.project && Path::new(&w.path) != path)
+            .filter_map(|w| {
+                w.project = false;
+                (!w.explicit).then(|| w.path.clone())
+            })
+            .collect();
+        for previous in previous {
+            // The old project may have been moved or deleted meanwhile
+            if let Err(e) = self.remove(&previous) {
+                tracing::warn!("Failed to stop watching {}: {}", previous, e);
+            }
+        }
+        if let Some(watched) = self.find(path) {
+            watched.project = true;
+            return Ok(());
+        }
+        self.add(path, true, start)
+    }
+}
+
+/// Owns the file watcher, started on the first watched path and stopped
+/// when none is left: the open project and any path added on top of it
+pub struct WatcherManager {
+    slot: Mutex<WatcherSlot>,
+    correlator: Arc<FileChangeCorrelator>,
+    fog: Arc<FogOfWar>,
+}
+
+impl WatcherManager {
+    pub fn new(correlator: Arc<FileChangeCorrelator>, fog: Arc<FogOfWar>) -> Self {
+        Self {
+            slot: Mutex::new(WatcherSlot::default()),
+            correlator,
+            fog,
+        }
+    }
+
+    fn start(&self, app_handle: &AppHandle) -> Result<FileSystemWatcher, String> {
+        FileSystemWatcher::new(app_handle.clone(), self.correlator.clone(), self.fog.clone())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Watch a newly opened project instead of the one before; paths added
+    /// with [`WatcherManager::watch`] stay watched
+    pub fn watch_project(&self, app_handle: &AppHandle, path: &Path) -> Result<(), String> {
+        let mut slot = self.slot.lock().unwrap();
+        let result = slot.replace_project(path, || self.start(app_handle));
+        if let Err(e) = &result {
+            slot.last_error = Some(e.clone());
+        }
+        result
+    }
+
+    pub fn watch(&self, app_handle: &AppHandle, path: &Path) -> Result<(), String> {
+        let mut slot = self.slot.lock().unwrap();
+        let result = slot.watch(path, || self.start(app_handle));
+        if let Err(e) = &result {
+            slot.last_error = Some(e.clone());
+        }
+        result
+    }
+
+    pub fn unwatch(&self, path: &Path) -> Result<(), String> {
+        self.slot.lock().unwrap().unwatch(path)
+    }
+
+    pub fn status(&self) -> WatcherStatus {
+        let slot = self.slot.lock().unwrap();
+        let watcher = slot.watcher.as_ref();
+        let last_event_at = watcher.map_or(0, |w| w.last_event_at.load(Ordering::Relaxed));
+        WatcherStatus {
+            running: watcher.is_some(),
+            watched: slot.watched.clone(),
+            events: watcher.map_or(0, |w| w.events.load(Ordering::Relaxed)),
+            last_event_at: (last_event_at > 0).then_some(last_event_at),
+            last_error: slot.last_error.clone(),
+        }
+    }
+}
+
+/// Turns notify's rename events into one `Rename` event with the old and
+/// the new path. Inotify reports a move as `From` and `To` halves sharing a
+/// tracker, followed by a `Both` event for the pair; backends that cannot
+/// pair renames report `Any` for each side.
+#[derive(Default)]
+struct RenamePairer {
+    /// `From` half waiting for its `To`
+    pending_from: Option<(Option<usize>, PathBuf)>,
+    /// Trackers of pairs already reported, so their `Both` is not repeated
+    paired: HashSet<usize>,
+}
+
+impl RenamePairer {
+    /// The changes `event` stands for. A `From` without a `To` means the
+    /// path was moved out of the project; it is reported as removed with
+    /// the next event.
+    fn pair(&mut self, event: &Event) -> Vec<(FileEventKind, Vec<PathBuf>)> {
+        let tracker = event.attrs.tracker();
+        let mut changes = Vec::new();
+        let EventKind::Modify(ModifyKind::Name(mode)) = event.kind else {
+            changes.extend(self.flush());
+            changes.push((event.kind.into(), event.paths.clone()));
+            return changes;
+        };
+
+        match mode {
+            RenameMode::From => {
+                changes.extend(self.flush());
+                if let Some(from) = event.paths.first() {
+                    self.pending_from = Some((tracker, from.clone()));
+                }
+            }
+            RenameMode::To => match self.pending_from.take() {
+                Some((from_tracker, from)) if from_tracker == tracker => {
+                    if let Some(tracker) = tracker {
+                        self.paired.insert(tracker);
+                    }
+                    changes.push((FileEventKind::Rename, [vec![from], event.paths.clone()].concat()));
+                }
+                pending => {
+                    self.pending_from = pending;
+                    changes.extend(self.flush());
+                    changes.push((FileEventKind::Create, event.paths.clone()));
+                }
+            },
+            RenameMode::Both => {
+                changes.extend(self.flush());
+                let reported = tracker.is_some_and(|t| self.paired.remove(&t));
+                if !reported && event.paths.len() == 2 {
+                    changes.push((FileEventKind::Rename, event.paths.clone()));
+                }
+            }
+            // Only the filesystem knows which side of the rename a path was
+            _ => {
+                changes.extend(self.flush());
+                for path in &event.paths {
+                    let kind = if path.exists() { FileEventKind::Create } else { FileEventKind::Remove };
+                    changes.push((kind, vec![path.clone()]));
+                }
+            }
+        }
+        changes
+    }
+
+    /// A `From` that is no longer going to be paired, as a removal
+    fn flush(&mut self) -> Option<(FileEventKind, Vec<PathBuf>)> {
+        self.pending_from.take().map(|(_, from)| (FileEventKind::Remove, vec![from]))
+    }
+}
+
+/// Keep fog in step with deletions and renames. A file moved out of the
+/// project only reports its old path; the next rescan prunes it.
+fn invalidate_fog(fog: &FogOfWar, kind: &FileEventKind, paths: &[PathBuf]) {
+    match (kind, paths) {
+        (FileEventKind::Remove, removed) => {
+            for path in removed {
+                fog.forget(&path.to_string_lossy());
+            }
+        }
+        (FileEventKind::Rename, [from, to]) => fog.rename(&from.to_string_lossy(), &to.to_string_lossy()),
+        _ => {}
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum WatcherError {
     #[error("Watcher init failed: {0}")]
@@ -86,3 +459,127 @@ pub enum WatcherError {
     #[error("Unwatch failed: {0}")]
     UnwatchFailed(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::CreateKind;
+
+    fn rename(mode: RenameMode, paths: &[&str], tracker: Option<usize>) -> Event {
+        let event = paths
+            .iter()
+            .fold(Event::new(EventKind::Modify(ModifyKind::Name(mode))), |e, p| e.add_path(PathBuf::from(p)));
+        match tracker {
+            Some(tracker) => event.set_tracker(tracker),
+            None => event,
+        }
+    }
+
+    fn kinds(changes: &[(FileEventKind, Vec<PathBuf>)]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|(kind, paths)| format!("{:?} {:?}", kind, paths.iter().map(|p| p.to_str().unwrap()).collect::<Vec<_>>()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rename_halves_are_paired_once() {
+        let mut pairer = RenamePairer::default();
+        assert!(pairer.pair(&rename(RenameMode::From, &["/p/a.rs"], Some(7))).is_empty());
+        let paired = pairer.pair(&rename(RenameMode::To, &["/p/b.rs"], Some(7)));
+        assert_eq!(kinds(&paired), vec![r#"Rename ["/p/a.rs", "/p/b.rs"]"#]);
+        assert!(pairer.pair(&rename(RenameMode::Both, &["/p/a.rs", "/p/b.rs"], Some(7))).is_empty());
+
+        // Backends that only report the pair
+        let both = pairer.pair(&rename(RenameMode::Both, &["/p/b.rs", "/p/c.rs"], None));
+        assert_eq!(kinds(&both), vec![r#"Rename ["/p/b.rs", "/p/c.rs"]"#]);
+    }
+
+    #[test]
+    fn test_unpaired_halves_are_removes_and_creates() {
+        let mut pairer = RenamePairer::default();
+        pairer.pair(&rename(RenameMode::From, &["/p/moved_out.rs"], Some(1)));
+        let next = pairer.pair(&Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/p/new.rs")));
+        assert_eq!(kinds(&next), vec![r#"Remove ["/p/moved_out.rs"]"#, r#"Create ["/p/new.rs"]"#]);
+
+        let moved_in = pairer.pair(&rename(RenameMode::To, &["/p/moved_in.rs"], Some(2)));
+        assert_eq!(kinds(&moved_in), vec![r#"Create ["/p/moved_in.rs"]"#]);
+    }
+    /// Records the paths it watches; letting go of `failing` fails
+    #[derive(Default)]
+    struct FakeWatcher {
+        paths: Vec<PathBuf>,
+        failing: Option<PathBuf>,
+    }
+
+    impl PathWatcher for FakeWatcher {
+        fn watch(&mut self, path: &Path) -> Result<(), String> {
+            self.paths.push(path.to_path_buf());
+            Ok(())
+        }
+
+        fn unwatch(&mut self, path: &Path) -> Result<(), String> {
+            self.paths.retain(|p| p != path);
+            match &self.failing {
+                Some(failing) if failing == path => Err("No such file or directory".to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn start() -> Result<FakeWatcher, String> {
+        Ok(FakeWatcher::default())
+    }
+
+    fn watched(slot: &WatcherSlot<FakeWatcher>) -> Vec<(&str, bool, bool)> {
+        slot.watched.iter().map(|w| (w.path.as_str(), w.project, w.explicit)).collect()
+    }
+
+    #[test]
+    fn test_watcher_runs_while_paths_are_watched() {
+        let mut slot = WatcherSlot::<FakeWatcher>::default();
+        slot.watch(Path::new("/a"), start).unwrap();
+        slot.watch(Path::new("/b"), || Err("started twice".to_string())).unwrap();
+        slot.watch(Path::new("/b"), start).unwrap();
+        assert_eq!(slot.watcher.as_ref().unwrap().paths, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+
+        slot.unwatch(Path::new("/a")).unwrap();
+        assert!(slot.unwatch(Path::new("/a")).is_err());
+        assert!(slot.watcher.is_some());
+        slot.unwatch(Path::new("/b")).unwrap();
+        assert!(slot.watcher.is_none());
+        assert!(slot.watched.is_empty());
+
+        assert!(slot.watch(Path::new("/c"), || Err("no inotify".to_string())).is_err());
+        assert!(slot.watcher.is_none());
+    }
+
+    #[test]
+    fn test_replacing_the_project_keeps_explicit_paths() {
+        let mut slot = WatcherSlot::<FakeWatcher>::default();
+        slot.replace_project(Path::new("/one"), start).unwrap();
+        slot.watch(Path::new("/one"), start).unwrap();
+        slot.watch(Path::new("/docs"), start).unwrap();
+        slot.replace_project(Path::new("/two"), start).unwrap();
+        assert_eq!(
+            watched(&slot),
+            vec![("/one", false, true), ("/docs", false, true), ("/two", true, false)]
+        );
+
+        // The old project was deleted, so the watcher can't let go of it
+        slot.watcher.as_mut().unwrap().failing = Some(PathBuf::from("/two"));
+        slot.replace_project(Path::new("/three"), start).unwrap();
+        assert_eq!(
+            watched(&slot),
+            vec![("/one", false, true), ("/docs", false, true), ("/three", true, false)]
+        );
+
+        // Unwatching the project itself keeps it until the next project
+        slot.replace_project(Path::new("/docs"), start).unwrap();
+        slot.unwatch(Path::new("/docs")).unwrap();
+        slot.unwatch(Path::new("/one")).unwrap();
+        assert_eq!(watched(&slot), vec![("/docs", true, false)]);
+        slot.unwatch(Path::new("/docs")).unwrap();
+        assert!(slot.watcher.is_none());
+    }
+}
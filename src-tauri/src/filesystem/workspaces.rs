@@ -0,0 +1,366 @@
+//! Members of monorepo workspaces, offered as sub-project nodes: Cargo
+//! workspace members, npm and pnpm workspaces and `go.work` modules. The
+//! manifests are read with just enough parsing to find the member paths.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `**` in a member pattern looks this deep at most
+const MAX_GLOB_DEPTH: usize = 6;
+/// Never workspace members, and slow to walk
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", ".git"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceKind {
+    Cargo,
+    Npm,
+    Pnpm,
+    Go,
+}
+
+impl WorkspaceKind {
+    /// Manifest every member has
+    fn manifest(self) -> &'static str {
+        match self {
+            WorkspaceKind::Cargo => "Cargo.toml",
+            WorkspaceKind::Npm | WorkspaceKind::Pnpm => "package.json",
+            WorkspaceKind::Go => "go.mod",
+        }
+    }
+}
+
+/// A workspace member that could become a project node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubprojectCandidate {
+    /// Package or module name, else the directory name
+    pub name: String,
+    pub path: String,
+    /// Relative to the workspace root
+    pub relative_path: String,
+    pub kind: WorkspaceKind,
+}
+
+/// Members of the workspaces declared at `root`, by relative path. Blocking.
+pub fn detect_workspace_members(root: &Path) -> Vec<SubprojectCandidate> {
+    let read = |name: &str| fs::read_to_string(root.join(name)).ok();
+    let mut declared: Vec<(WorkspaceKind, Vec<String>, Vec<String>)> = Vec::new();
+    if let Some(cargo) = read("Cargo.toml") {
+        declared.push((
+            WorkspaceKind::Cargo,
+            toml_string_array(&cargo, "workspace", "members"),
+            toml_string_array(&cargo, "workspace", "exclude"),
+        ));
+    }
+    if let Some(package) = read("package.json") {
+        let (include, exclude) = split_negated(npm_workspaces(&package));
+        declared.push((WorkspaceKind::Npm, include, exclude));
+    }
+    if let Some(pnpm) = read("pnpm-workspace.yaml") {
+        let (include, exclude) = split_negated(pnpm_packages(&pnpm));
+        declared.push((WorkspaceKind::Pnpm, include, exclude));
+    }
+    if let Some(work) = read("go.work") {
+        declared.push((WorkspaceKind::Go, go_work_uses(&work), Vec::new()));
+    }
+
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+    for (kind, include, exclude) in declared {
+        let excluded: HashSet<PathBuf> =
+            exclude.iter().flat_map(|p| expand_pattern(root, p)).collect();
+        for dir in include.iter().flat_map(|p| expand_pattern(root, p)) {
+            if dir == root || excluded.contains(&dir) || !dir.join(kind.manifest()).is_file() {
+                continue;
+            }
+            if !seen.insert(dir.clone()) {
+                continue;
+            }
+            let name = member_name(&dir, kind).unwrap_or_else(|| {
+                let name = dir.file_name().unwrap_or_default();
+                name.to_string_lossy().to_string()
+            });
+            candidates.push(SubprojectCandidate {
+                name,
+                path: dir.to_string_lossy().to_string(),
+                relative_path: dir.strip_prefix(root).unwrap_or(&dir).to_string_lossy().to_string(),
+                kind,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    candidates
+}
+
+/// Name the member's own manifest gives it
+fn member_name(dir: &Path, kind: WorkspaceKind) -> Option<String> {
+    let manifest = fs::read_to_string(dir.join(kind.manifest())).ok()?;
+    match kind {
+        WorkspaceKind::Cargo => toml_string(&manifest, "package", "name"),
+        WorkspaceKind::Npm | WorkspaceKind::Pnpm => {
+            let package: Value = serde_json::from_str(&manifest).ok()?;
+            package.get("name")?.as_str().map(String::from)
+        }
+        WorkspaceKind::Go => manifest.lines().find_map(|line| {
+            let module = line.trim().strip_prefix("module")?.trim().trim_matches('"');
+            module.rsplit('/').next().filter(|n| !n.is_empty()).map(String::from)
+        }),
+    }
+}
+
+/// Patterns starting with `!` exclude, the others include
+fn split_negated(patterns: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let (exclude, include): (Vec<String>, Vec<String>) =
+        patterns.into_iter().partition(|p| p.starts_with('!'));
+    let exclude = exclude.into_iter().map(|p| p[1..].to_string()).collect();
+    (include, exclude)
+}
+
+/// `line` without a trailing `#` comment outside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Raw value of `key` in the `[section]` table of a TOML file, with
+/// arrays spanning lines joined
+fn toml_value(content: &str, section: &str, key: &str) -> Option<String> {
+    let mut current = String::new();
+    let mut lines = content.lines().map(strip_comment);
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            current = header.trim_end_matches(']').trim().to_string();
+            continue;
+        }
+        if current != section {
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        if name.trim().trim_matches('"') != key {
+            continue;
+        }
+        let mut value = value.trim().to_string();
+        if value.starts_with('[') {
+            while !value.contains(']') {
+                let Some(next) = lines.next() else {
+                    break;
+                };
+                value.push_str(next.trim());
+            }
+        }
+        return Some(value);
+    }
+    None
+}
+
+fn toml_string(content: &str, section: &str, key: &str) -> Option<String> {
+    let value = toml_value(content, section, key)?;
+    let value = value.trim_matches(|c| c == '"' || c == '\'');
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn toml_string_array(content: &str, section: &str, key: &str) -> Vec<String> {
+    toml_value(content, section, key)
+        .map(|value| quoted_strings(&value))
+        .unwrap_or_default()
+}
+
+/// Strings in double or single quotes, in order
+fn quoted_strings(text: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['"', '\'']) {
+        let quote = rest[start..].chars().next().unwrap_or('"');
+        let after = &rest[start + 1..];
+        let Some(end) = after.find(quote) else {
+            break;
+        };
+        strings.push(after[..end].to_string());
+        rest = &after[end + 1..];
+    }
+    strings
+}
+
+/// `workspaces` of a package.json: a list, or an object with `packages`
+fn npm_workspaces(content: &str) -> Vec<String> {
+    let Ok(package) = serde_json::from_str::<Value>(content) else {
+        return Vec::new();
+    };
+    let workspaces = match package.get("workspaces") {
+        Some(Value::Object(object)) => object.get("packages"),
+        other => other,
+    };
+    workspaces
+        .and_then(Value::as_array)
+        .map(|list| list.iter().filter_map(|p| p.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+/// Items of the `packages` list of a pnpm-workspace.yaml
+fn pnpm_packages(content: &str) -> Vec<String> {
+    let mut packages = Vec::new();
+    let mut in_packages = false;
+    for line in content.lines().map(strip_comment) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+            packages.push(item.trim().trim_matches(|c| c == '"' || c == '\'').to_string());
+        }
+    }
+    packages
+}
+
+/// Module directories of a go.work, from `use dir` and `use ( ... )`
+fn go_work_uses(content: &str) -> Vec<String> {
+    let mut uses = Vec::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if in_block {
+            if line == ")" {
+                in_block = false;
+            } else if !line.is_empty() {
+                uses.push(line.trim_matches('"').to_string());
+            }
+        } else if let Some(rest) = line.strip_prefix("use") {
+            match rest.trim() {
+                "(" => in_block = true,
+                dir if !dir.is_empty() => uses.push(dir.trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+    }
+    uses
+}
+
+/// Whether `name` matches `pattern` with `*` and `?` wildcards
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|e| !SKIPPED_DIRS.iter().any(|skipped| e.file_name() == *skipped))
+        .map(|e| e.path())
+        .collect()
+}
+
+/// Directories below `root` matching a member pattern like `crates/*`
+fn expand_pattern(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    for segment in pattern.trim_end_matches('/').split('/') {
+        dirs = match segment {
+            "" | "." => dirs,
+            "**" => {
+                let mut all = dirs.clone();
+                let mut level = dirs;
+                for _ in 0..MAX_GLOB_DEPTH {
+                    level = level.iter().flat_map(|d| subdirectories(d)).collect();
+                    all.extend(level.iter().cloned());
+                }
+                all
+            }
+            _ if segment.contains(['*', '?']) => dirs
+                .iter()
+                .flat_map(|d| subdirectories(d))
+                .filter(|d| {
+                    d.file_name()
+                        .is_some_and(|n| wildcard_match(segment, &n.to_string_lossy()))
+                })
+                .collect(),
+            _ => dirs.iter().map(|d| d.join(segment)).filter(|d| d.is_dir()).collect(),
+        };
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_detects_cargo_npm_pnpm_and_go_members() {
+        let root =
+            std::env::temp_dir().join(format!("acptorio-workspaces-{}", uuid::Uuid::new_v4()));
+        write(
+            &root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\n  \"crates/*\", # all crates\n  \"tools/cli\",\n]\n\
+             exclude = [\"crates/old\"]\n",
+        );
+        write(&root, "crates/core/Cargo.toml", "[package]\nname = \"app-core\"\n");
+        write(&root, "crates/old/Cargo.toml", "[package]\nname = \"old\"\n");
+        write(&root, "crates/notes/README.md", "no manifest");
+        write(&root, "tools/cli/Cargo.toml", "[package]\nname = 'app-cli'\n");
+        write(
+            &root,
+            "package.json",
+            r#"{ "workspaces": { "packages": ["web/**", "!web/legacy"] } }"#,
+        );
+        write(&root, "web/apps/site/package.json", r#"{ "name": "@app/site" }"#);
+        write(&root, "web/legacy/package.json", r#"{ "name": "legacy" }"#);
+        write(&root, "pnpm-workspace.yaml", "packages:\n  - 'docs'\n  - \"web/apps/*\"\n");
+        write(&root, "docs/package.json", "{}");
+        write(&root, "go.work", "go 1.22\n\nuse (\n\t./services/api\n)\nuse ./missing\n");
+        write(&root, "services/api/go.mod", "module github.com/acme/api\n");
+
+        let found: Vec<(String, String, WorkspaceKind)> = detect_workspace_members(&root)
+            .into_iter()
+            .map(|c| (c.relative_path, c.name, c.kind))
+            .collect();
+        let expected = [
+            ("crates/core", "app-core", WorkspaceKind::Cargo),
+            ("docs", "docs", WorkspaceKind::Pnpm),
+            ("services/api", "api", WorkspaceKind::Go),
+            ("tools/cli", "app-cli", WorkspaceKind::Cargo),
+            ("web/apps/site", "@app/site", WorkspaceKind::Npm),
+        ];
+        let expected: Vec<(String, String, WorkspaceKind)> = expected
+            .iter()
+            .map(|(path, name, kind)| (path.to_string(), name.to_string(), *kind))
+            .collect();
+        assert_eq!(found, expected);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Pre-prompt checkpoints of a project's working tree.
+//!
+//! A checkpoint is a commit object built from a temporary index (so the
+//! user's index and branches are untouched), pinned by a ref under
+//! `refs/acptorio/checkpoints/`. Rolling back restores the working tree to
+//! that commit, resets HEAD to where it was, and deletes files added since.
+
+use super::{repo_root, run_git, run_git_with_env, GitError};
+use crate::clock::now_secs;
+use crate::state::app_data_dir;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const CHECKPOINTS_FILE: &str = "checkpoints.json";
+const CHECKPOINT_REF_PREFIX: &str = "refs/acptorio/checkpoints";
+
+const COMMIT_IDENTITY: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "ACPtorio"),
+    ("GIT_AUTHOR_EMAIL", "acptorio@localhost"),
+    ("GIT_COMMITTER_NAME", "ACPtorio"),
+    ("GIT_COMMITTER_EMAIL", "acptorio@localhost"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub task_id: String,
+    pub repo_root: String,
+    /// Commit holding the full working tree at checkpoint time
+    pub commit: String,
+    /// HEAD at checkpoint time (None for repos without commits)
+    pub head: Option<String>,
+    pub created_at: u64,
+}
+
+/// Snapshot the working tree (tracked + untracked, honoring .gitignore) into a tree object
+pub async fn snapshot_tree(root: &Path, head: Option<&str>) -> Result<String, GitError> {
+    let index_path = std::env::temp_dir().join(format!("acptorio-index-{}", Uuid::new_v4()));
+    let index = index_path.to_string_lossy().to_string();
+    let env = [("GIT_INDEX_FILE", index.as_str())];
+
+    let result = async {
+        if let Some(head) = head {
+            run_git_with_env(root, &["read-tree", head], &env).await?;
+        }
+        run_git_with_env(root, &["add", "-A"], &env).await?;
+        run_git_with_env(root, &["write-tree"], &env).await
+    }
+    .await;
+
+    let _ = tokio::fs::remove_file(&index_path).await;
+    result
+}
+
+/// Checkpoints by task id, kept on disk so tasks can be rolled back after a
+/// restart
+pub struct CheckpointStore {
+    checkpoints: DashMap<String, Checkpoint>,
+    storage_path: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new() -> Self {
+        Self::at(app_data_dir().join(CHECKPOINTS_FILE))
+    }
+
+    fn at(storage_path: PathBuf) -> Self {
+        let checkpoints = Self::load_from_file(&storage_path).unwrap_or_default();
+        Self {
+            checkpoints: checkpoints.into_iter().collect(),
+            storage_path,
+        }
+    }
+
+    fn load_from_file(path: &Path) -> Option<HashMap<String, Checkpoint>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save_to_file(&self) -> Result<(), String> {
+        let checkpoints: HashMap<String, Checkpoint> = self
+            .checkpoints
+            .iter()
+            .map(|c| (c.key().clone(), c.value().clone()))
+            .collect();
+        let content = serde_json::to_string_pretty(&checkpoints)
+            .map_err(|e| format!("Failed to serialize checkpoints: {}", e))?;
+        fs::write(&self.storage_path, content)
+            .map_err(|e| format!("Failed to write checkpoints file: {}", e))
+    }
+
+    /// Create a checkpoint of the repo containing `working_directory`
+    pub async fn create(
+        &self,
+        task_id: &str,
+        working_directory: &Path,
+    ) -> Result<Checkpoint, GitError> {
+        let root = repo_root(working_directory)
+            .await
+            .ok_or_else(|| GitError::NotARepository(working_directory.to_string_lossy().to_string()))?;
+
+        let head = run_git(&root, &["rev-parse", "--verify", "-q", "HEAD"]).await.ok();
+        let tree = snapshot_tree(&root, head.as_deref()).await?;
+
+        let message = format!("ACPtorio checkpoint before task {}", task_id);
+        let mut args = vec!["commit-tree", tree.as_str(), "-m", message.as_str()];
+        if let Some(ref head) = head {
+            args.push("-p");
+            args.push(head);
+        }
+        let commit = run_git_with_env(&root, &args, &COMMIT_IDENTITY).await?;
+
+        let ref_name = format!("{}/{}", CHECKPOINT_REF_PREFIX, task_id);
+        run_git(&root, &["update-ref", &ref_name, &commit]).await?;
+
+        let checkpoint = Checkpoint {
+            task_id: task_id.to_string(),
+            repo_root: root.to_string_lossy().to_string(),
+            commit,
+            head,
+            created_at: now_secs(),
+        };
+
+        info!("Created checkpoint {} for task {}", checkpoint.commit, task_id);
+        self.checkpoints.insert(task_id.to_string(), checkpoint.clone());
+        if let Err(e) = self.save_to_file() {
+            warn!("{}", e);
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<Checkpoint> {
+        self.checkpoints.get(task_id).map(|c| c.clone())
+    }
+
+    /// Revert the repo to the state captured before the task ran.
+    ///
+    /// Changes the user had staged at checkpoint time come back as unstaged.
+    pub async fn rollback(&self, task_id: &str) -> Result<Checkpoint, GitError> {
+        let checkpoint = self
+            .get(task_id)
+            .ok_or_else(|| GitError::NoCheckpoint(task_id.to_string()))?;
+        let root = PathBuf::from(&checkpoint.repo_root);
+
+        // Files that exist now but not in the checkpoint were added by the agent
+        let current_head = run_git(&root, &["rev-parse", "--verify", "-q", "HEAD"]).await.ok();
+        let current_tree = snapshot_tree(&root, current_head.as_deref()).await?;
+        let added = run_git(
+            &root,
+            &["diff", "--name-only", "--diff-filter=A", &checkpoint.commit, &current_tree],
+        )
+        .await?;
+
+        if let Some(ref head) = checkpoint.head {
+            run_git(&root, &["reset", "-q", "--mixed", head]).await?;
+        }
+        run_git(
+            &root,
+            &["restore", "--source", &checkpoint.commit, "--worktree", "--", "."],
+        )
+        .await?;
+
+        for file in added.lines().filter(|l| !l.is_empty()) {
+            let _ = tokio::fs::remove_file(root.join(file)).await;
+        }
+
+        info!("Rolled back task {} to checkpoint {}", task_id, checkpoint.commit);
+        Ok(checkpoint)
+    }
+}
+
+impl Default for CheckpointStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rollback_after_restart_restores_edits_and_removes_added_files() {
+        let dir = std::env::temp_dir().join(format!("acptorio-checkpoint-{}", Uuid::new_v4()));
+        let repo = dir.join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "-q"]).await.unwrap();
+        fs::write(repo.join("main.rs"), "fn main() {}\n").unwrap();
+        run_git(&repo, &["add", "-A"]).await.unwrap();
+        run_git_with_env(&repo, &["commit", "-q", "-m", "init"], &COMMIT_IDENTITY)
+            .await
+            .unwrap();
+        fs::write(repo.join("notes.txt"), "uncommitted\n").unwrap();
+
+        let storage_path = dir.join(CHECKPOINTS_FILE);
+        CheckpointStore::at(storage_path.clone())
+            .create("task-1", &repo)
+            .await
+            .unwrap();
+
+        fs::write(repo.join("main.rs"), "fn main() { panic!() }\n").unwrap();
+        fs::write(repo.join("added.rs"), "// new\n").unwrap();
+
+        // A fresh store finds the checkpoint the first one saved
+        CheckpointStore::at(storage_path).rollback("task-1").await.unwrap();
+        assert_eq!(fs::read_to_string(repo.join("main.rs")).unwrap(), "fn main() {}\n");
+        assert_eq!(fs::read_to_string(repo.join("notes.txt")).unwrap(), "uncommitted\n");
+        assert!(!repo.join("added.rs").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
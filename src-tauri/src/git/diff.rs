@@ -0,0 +1,154 @@
+//! Summaries of what changed in a working tree between two points in time.
+
+use super::{repo_root, run_git, snapshot_tree, GitError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: FileChangeKind,
+    pub additions: u32,
+    pub deletions: u32,
+    /// Binary files have no line counts
+    pub binary: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangeSummary {
+    pub files: Vec<FileChange>,
+    pub additions: u32,
+    pub deletions: u32,
+    /// Repo and trees the summary was computed from, to fetch the full patch
+    #[serde(default)]
+    pub repo_root: Option<String>,
+    #[serde(default)]
+    pub from_tree: Option<String>,
+    #[serde(default)]
+    pub to_tree: Option<String>,
+}
+
+impl ChangeSummary {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// The state of a working tree at some point, used as a diff base
+#[derive(Debug, Clone)]
+pub struct TreeSnapshot {
+    pub repo_root: PathBuf,
+    pub tree: String,
+}
+
+impl TreeSnapshot {
+    /// Capture the repo containing `working_directory`
+    pub async fn capture(working_directory: &Path) -> Result<Self, GitError> {
+        let root = repo_root(working_directory)
+            .await
+            .ok_or_else(|| GitError::NotARepository(working_directory.to_string_lossy().to_string()))?;
+        let head = run_git(&root, &["rev-parse", "--verify", "-q", "HEAD"]).await.ok();
+        let tree = snapshot_tree(&root, head.as_deref()).await?;
+        Ok(Self { repo_root: root, tree })
+    }
+
+    /// Summarize everything that changed in the working tree since this snapshot
+    pub async fn changes_since(&self) -> Result<ChangeSummary, GitError> {
+        let current = Self::capture(&self.repo_root).await?;
+        let mut summary = diff_trees(&self.repo_root, &self.tree, &current.tree).await?;
+        summary.repo_root = Some(self.repo_root.to_string_lossy().to_string());
+        summary.from_tree = Some(self.tree.clone());
+        summary.to_tree = Some(current.tree);
+        Ok(summary)
+    }
+}
+
+/// Diff two tree-ish objects in `root`
+pub async fn diff_trees(root: &Path, from: &str, to: &str) -> Result<ChangeSummary, GitError> {
+    if from == to {
+        return Ok(ChangeSummary::default());
+    }
+    let name_status = run_git(root, &["diff", "--no-renames", "--name-status", from, to]).await?;
+    let numstat = run_git(root, &["diff", "--no-renames", "--numstat", from, to]).await?;
+    Ok(parse_diff(&name_status, &numstat))
+}
+
+/// Unified diff between two tree-ish objects in `root`
+pub async fn diff_patch(root: &Path, from: &str, to: &str) -> Result<String, GitError> {
+    run_git(root, &["diff", "--no-renames", "--no-color", from, to]).await
+}
+
+fn parse_diff(name_status: &str, numstat: &str) -> ChangeSummary {
+    // path -> (additions, deletions), None for binary files
+    let counts: HashMap<&str, Option<(u32, u32)>> = numstat
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let added = parts.next()?;
+            let deleted = parts.next()?;
+            let path = parts.next()?;
+            let counts = added.parse().ok().zip(deleted.parse().ok());
+            Some((path, counts))
+        })
+        .collect();
+
+    let mut summary = ChangeSummary::default();
+    for line in name_status.lines() {
+        let Some((status, path)) = line.split_once('\t') else {
+            continue;
+        };
+        let kind = match status.chars().next() {
+            Some('A') => FileChangeKind::Added,
+            Some('D') => FileChangeKind::Deleted,
+            _ => FileChangeKind::Modified,
+        };
+        let (additions, deletions, binary) = match counts.get(path) {
+            Some(Some((a, d))) => (*a, *d, false),
+            Some(None) => (0, 0, true),
+            None => (0, 0, false),
+        };
+        summary.additions += additions;
+        summary.deletions += deletions;
+        summary.files.push(FileChange {
+            path: path.to_string(),
+            kind,
+            additions,
+            deletions,
+            binary,
+        });
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff() {
+        let name_status = "A\tsrc/new.rs\nM\tsrc/lib.rs\nD\told.txt\nM\tlogo.png";
+        let numstat = "120\t0\tsrc/new.rs\n5\t12\tsrc/lib.rs\n0\t28\told.txt\n-\t-\tlogo.png";
+        let summary = parse_diff(name_status, numstat);
+
+        assert_eq!(summary.files.len(), 4);
+        assert_eq!(summary.additions, 125);
+        assert_eq!(summary.deletions, 40);
+        assert_eq!(summary.files[0].kind, FileChangeKind::Added);
+        assert_eq!(summary.files[2].kind, FileChangeKind::Deleted);
+        assert!(summary.files[3].binary);
+    }
+
+    #[test]
+    fn test_parse_empty_diff() {
+        assert!(parse_diff("", "").is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+//! Thin wrappers around the `git` CLI for project repositories
+pub mod checkpoint;
+pub mod diff;
+pub mod worktree;
+
+pub use checkpoint::*;
+pub use diff::*;
+pub use worktree::*;
+
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Run a git command in `repo` and return its trimmed stdout
+pub async fn run_git(repo: &Path, args: &[&str]) -> Result<String, GitError> {
+    run_git_with_env(repo, args, &[]).await
+}
+
+pub async fn run_git_with_env(
+    repo: &Path,
+    args: &[&str],
+    envs: &[(&str, &str)],
+) -> Result<String, GitError> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(repo).args(args);
+    for (key, value) in envs {
+        cmd.env(key, value);
+    }
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| GitError::Spawn(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(GitError::CommandFailed(format!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Find the root of the repository containing `path`, if any
+pub async fn repo_root(path: &Path) -> Option<PathBuf> {
+    run_git(path, &["rev-parse", "--show-toplevel"])
+        .await
+        .ok()
+        .map(PathBuf::from)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    #[error("Failed to run git: {0}")]
+    Spawn(String),
+    #[error("{0}")]
+    CommandFailed(String),
+    #[error("Not a git repository: {0}")]
+    NotARepository(String),
+    #[error("No checkpoint for task: {0}")]
+    NoCheckpoint(String),
+    #[error("No worktree for agent: {0}")]
+    NoWorktree(String),
+    #[error("Invalid branch name: {0}")]
+    InvalidBranch(String),
+}
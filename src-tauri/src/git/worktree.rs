@@ -0,0 +1,297 @@
+//! Dedicated git worktrees so agents can work on a branch without touching
+//! the user's live checkout.
+
+use super::{repo_root, run_git, run_git_with_env, GitError};
+use crate::clock::now_secs;
+use crate::state::app_data_dir;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const WORKTREES_FILE: &str = "worktrees.json";
+
+const MERGE_IDENTITY: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "ACPtorio"),
+    ("GIT_AUTHOR_EMAIL", "acptorio@localhost"),
+    ("GIT_COMMITTER_NAME", "ACPtorio"),
+    ("GIT_COMMITTER_EMAIL", "acptorio@localhost"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Worktree {
+    pub agent_id: Option<Uuid>,
+    pub project_id: String,
+    /// Root of the repository the worktree was created from
+    pub repo_root: String,
+    pub path: String,
+    pub branch: String,
+    /// Commit the branch was created from
+    pub base_commit: String,
+    /// The branch did not exist before the worktree was created
+    #[serde(default)]
+    pub created_branch: bool,
+    pub created_at: u64,
+}
+
+fn worktrees_dir() -> PathBuf {
+    app_data_dir().join("worktrees")
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+/// Worktrees created for agents, keyed by agent id once the agent is spawned
+pub struct WorktreeStore {
+    worktrees: DashMap<Uuid, Worktree>,
+    /// Kept on disk so worktrees of stopped agents can still be merged or
+    /// discarded after a restart
+    storage_path: PathBuf,
+}
+
+impl WorktreeStore {
+    pub fn new() -> Self {
+        Self::at(app_data_dir().join(WORKTREES_FILE))
+    }
+
+    /// Store kept in `storage_path`, with the worktrees still on disk
+    fn at(storage_path: PathBuf) -> Self {
+        let saved: Vec<Worktree> = fs::read_to_string(&storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let worktrees = saved
+            .into_iter()
+            .filter(|w| Path::new(&w.path).exists())
+            .filter_map(|w| Some((w.agent_id?, w)))
+            .collect();
+        Self {
+            worktrees,
+            storage_path,
+        }
+    }
+
+    fn save_to_file(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(&self.list())
+            .map_err(|e| format!("Failed to serialize worktrees: {}", e))?;
+        fs::write(&self.storage_path, content)
+            .map_err(|e| format!("Failed to write worktrees file: {}", e))
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.save_to_file() {
+            warn!("{}", e);
+        }
+    }
+
+    /// Create a worktree of the repo containing `project_path` on `branch`.
+    ///
+    /// The branch is created from the current HEAD if it does not exist yet.
+    pub async fn create(
+        &self,
+        project_id: &str,
+        project_path: &Path,
+        branch: &str,
+    ) -> Result<Worktree, GitError> {
+        let root = repo_root(project_path)
+            .await
+            .ok_or_else(|| GitError::NotARepository(project_path.to_string_lossy().to_string()))?;
+        check_branch_name(&root, branch).await?;
+        let base_commit = run_git(&root, &["rev-parse", "HEAD"]).await?;
+
+        let repo_name = root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "project".to_string());
+        let short_id = Uuid::new_v4().simple().to_string()[..8].to_string();
+        let path = worktrees_dir().join(format!("{}-{}-{}", sanitize(&repo_name), sanitize(branch), short_id));
+        fs::create_dir_all(worktrees_dir()).map_err(|e| GitError::Spawn(e.to_string()))?;
+        let path_str = path.to_string_lossy().to_string();
+
+        let branch_ref = format!("refs/heads/{}", branch);
+        let branch_exists = run_git(&root, &["rev-parse", "--verify", "-q", &branch_ref])
+            .await
+            .is_ok();
+        if branch_exists {
+            run_git(&root, &["worktree", "add", &path_str, branch]).await?;
+        } else {
+            run_git(&root, &["worktree", "add", "-b", branch, &path_str]).await?;
+        }
+
+        info!("Created worktree {} on branch {}", path_str, branch);
+        Ok(Worktree {
+            agent_id: None,
+            project_id: project_id.to_string(),
+            repo_root: root.to_string_lossy().to_string(),
+            path: path_str,
+            branch: branch.to_string(),
+            base_commit,
+            created_branch: !branch_exists,
+            created_at: now_secs(),
+        })
+    }
+
+    /// Associate a worktree with the agent working in it
+    pub fn assign(&self, agent_id: Uuid, mut worktree: Worktree) -> Worktree {
+        worktree.agent_id = Some(agent_id);
+        self.worktrees.insert(agent_id, worktree.clone());
+        self.save();
+        worktree
+    }
+
+    pub fn get(&self, agent_id: &Uuid) -> Option<Worktree> {
+        self.worktrees.get(agent_id).map(|w| w.clone())
+    }
+
+    pub fn list(&self) -> Vec<Worktree> {
+        self.worktrees.iter().map(|w| w.value().clone()).collect()
+    }
+
+    /// Commit pending work in the agent's worktree and merge its branch into
+    /// the branch checked out in the main repository.
+    ///
+    /// A conflicting merge is aborted so the main checkout is left untouched.
+    pub async fn merge(&self, agent_id: &Uuid) -> Result<Worktree, GitError> {
+        let worktree = self
+            .get(agent_id)
+            .ok_or_else(|| GitError::NoWorktree(agent_id.to_string()))?;
+        let path = PathBuf::from(&worktree.path);
+        let root = PathBuf::from(&worktree.repo_root);
+
+        run_git(&path, &["add", "-A"]).await?;
+        let has_staged = run_git(&path, &["diff", "--cached", "--quiet"]).await.is_err();
+        if has_staged {
+            let message = format!("ACPtorio: work from branch {}", worktree.branch);
+            run_git_with_env(&path, &["commit", "-q", "-m", &message], &MERGE_IDENTITY).await?;
+        }
+
+        let message = format!("Merge branch '{}'", worktree.branch);
+        let merged = run_git_with_env(
+            &root,
+            &["merge", "--no-ff", "-m", &message, &worktree.branch],
+            &MERGE_IDENTITY,
+        )
+        .await;
+        if let Err(e) = merged {
+            let _ = run_git(&root, &["merge", "--abort"]).await;
+            return Err(e);
+        }
+
+        info!("Merged worktree branch {}", worktree.branch);
+        Ok(worktree)
+    }
+
+    /// Remove the agent's worktree, and its branch if it was created for it;
+    /// a branch that existed before is the user's and stays
+    pub async fn remove(&self, agent_id: &Uuid) -> Result<Worktree, GitError> {
+        let worktree = self
+            .get(agent_id)
+            .ok_or_else(|| GitError::NoWorktree(agent_id.to_string()))?;
+
+        remove_worktree(&worktree, worktree.created_branch).await?;
+        self.worktrees.remove(agent_id);
+        self.save();
+        Ok(worktree)
+    }
+}
+
+/// Refuse a branch name git would take for an option or not accept as a
+/// branch
+async fn check_branch_name(root: &Path, branch: &str) -> Result<(), GitError> {
+    if branch.starts_with('-') {
+        return Err(GitError::InvalidBranch(branch.to_string()));
+    }
+    run_git(root, &["check-ref-format", "--branch", branch])
+        .await
+        .map(|_| ())
+        .map_err(|_| GitError::InvalidBranch(branch.to_string()))
+}
+
+/// Delete a worktree from disk and from the repository's worktree list
+pub async fn remove_worktree(worktree: &Worktree, delete_branch: bool) -> Result<(), GitError> {
+    let root = PathBuf::from(&worktree.repo_root);
+
+    run_git(&root, &["worktree", "remove", "--force", &worktree.path]).await?;
+    if delete_branch {
+        run_git(&root, &["branch", "-D", &worktree.branch]).await?;
+    }
+
+    info!("Removed worktree {}", worktree.path);
+    Ok(())
+}
+
+impl Default for WorktreeStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_branch_names_are_checked_before_use() {
+        let dir = std::env::temp_dir();
+        assert!(check_branch_name(&dir, "agent/fix-login").await.is_ok());
+        for branch in ["-b", "--orphan=x", "bad..name", "no space", ""] {
+            assert!(
+                matches!(check_branch_name(&dir, branch).await, Err(GitError::InvalidBranch(_))),
+                "{} was accepted",
+                branch
+            );
+        }
+    }
+
+    async fn init_repo(dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        run_git(dir, &["init", "-q"]).await.unwrap();
+        fs::write(dir.join("README.md"), "hello\n").unwrap();
+        run_git(dir, &["add", "-A"]).await.unwrap();
+        run_git_with_env(dir, &["commit", "-q", "-m", "init"], &MERGE_IDENTITY)
+            .await
+            .unwrap();
+    }
+
+    async fn branch_exists(root: &Path, branch: &str) -> bool {
+        let branch_ref = format!("refs/heads/{}", branch);
+        run_git(root, &["rev-parse", "--verify", "-q", &branch_ref]).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_remove_keeps_branches_it_did_not_create() {
+        let dir = std::env::temp_dir().join(format!("acptorio-worktree-{}", Uuid::new_v4()));
+        let repo = dir.join("repo");
+        init_repo(&repo).await;
+        run_git(&repo, &["branch", "existing"]).await.unwrap();
+        let storage_path = dir.join(WORKTREES_FILE);
+        let store = WorktreeStore::at(storage_path.clone());
+
+        let reused = store.create("p", &repo, "existing").await.unwrap();
+        assert!(!reused.created_branch);
+        let reused_agent = Uuid::new_v4();
+        store.assign(reused_agent, reused);
+
+        let created = store.create("p", &repo, "agent/new").await.unwrap();
+        assert!(created.created_branch);
+        let created_agent = Uuid::new_v4();
+        store.assign(created_agent, created);
+
+        let reloaded = WorktreeStore::at(storage_path);
+        assert!(reloaded.get(&reused_agent).is_some());
+        assert!(reloaded.get(&created_agent).is_some());
+
+        reloaded.remove(&reused_agent).await.unwrap();
+        reloaded.remove(&created_agent).await.unwrap();
+        assert!(branch_exists(&repo, "existing").await);
+        assert!(!branch_exists(&repo, "agent/new").await);
+        assert!(WorktreeStore::at(dir.join(WORKTREES_FILE)).list().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,362 @@
+//! User-configured hooks: play a sound, run a shell command, or POST to a
+//! webhook when something notable happens.
+
+mod webhook;
+
+pub use webhook::*;
+
+use crate::agent::TaskInfo;
+use crate::clock::now_ms;
+use crate::git::ChangeSummary;
+use crate::messages::{LocalizedMessage, MessageKey};
+use crate::state::{AlertKind, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// Events hooks can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    PermissionRequested,
+    TaskFinished,
+    AgentError,
+    BudgetExceeded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookAction {
+    /// Play a sound file, or the platform's default alert sound
+    Sound {
+        #[serde(default)]
+        file: Option<String>,
+    },
+    /// Run a shell command; the payload is passed as JSON on stdin and
+    /// summarized in `ACPTORIO_*` environment variables
+    Command { command: String },
+    /// POST the payload as JSON, optionally shaped for Slack or Discord
+    Webhook {
+        url: String,
+        #[serde(default)]
+        format: WebhookFormat,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub event: HookEvent,
+    pub action: HookAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// What a hook receives about the event that triggered it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookPayload {
+    pub event: HookEvent,
+    pub agent_id: Option<Uuid>,
+    pub agent_name: Option<String>,
+    pub message: String,
+    #[serde(default)]
+    pub details: Value,
+    pub timestamp: u64,
+}
+
+impl HookPayload {
+    pub fn new(event: HookEvent, agent_id: Option<Uuid>, message: impl Into<String>) -> Self {
+        Self {
+            event,
+            agent_id,
+            agent_name: None,
+            message: message.into(),
+            details: Value::Null,
+            timestamp: now_ms(),
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// Details of a finished task, sent as the payload's `details`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskReport {
+    pub task_id: String,
+    pub status: String,
+    pub prompt: String,
+    /// Name of the agent's working directory
+    pub project: String,
+    pub duration_secs: Option<u64>,
+    pub stop_reason: Option<String>,
+    pub error: Option<String>,
+    pub change_summary: Option<ChangeSummary>,
+}
+
+/// Build the `task_finished` payload for a task that completed or failed
+pub fn task_finished_payload(state: &AppState, task: &TaskInfo) -> HookPayload {
+    let agent = state
+        .agent_pool
+        .agent_statuses()
+        .into_iter()
+        .find(|a| a.id == task.agent_id);
+    let project = agent
+        .as_ref()
+        .and_then(|a| Path::new(&a.working_directory).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let status = serde_json::to_value(task.status)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default();
+
+    let report = TaskReport {
+        task_id: task.id.clone(),
+        status: status.clone(),
+        prompt: task.prompt.clone(),
+        project,
+        duration_secs: match (task.started_at, task.finished_at) {
+            (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+            _ => None,
+        },
+        stop_reason: task.stop_reason.clone(),
+        error: task.error.clone(),
+        change_summary: task.change_summary.clone(),
+    };
+
+    let mut payload = HookPayload::new(
+        HookEvent::TaskFinished,
+        Some(task.agent_id),
+        format!("Task {}", status),
+    )
+    .with_details(serde_json::to_value(&report).unwrap_or_default());
+    payload.agent_name = agent.map(|a| a.name);
+    payload
+}
+
+/// Enabled hooks attached to an event
+pub fn hooks_for(hooks: &[Hook], event: HookEvent) -> Vec<Hook> {
+    hooks
+        .iter()
+        .filter(|h| h.enabled && h.event == event)
+        .cloned()
+        .collect()
+}
+
+/// Runs hook actions in the background
+pub struct HookRunner {
+    client: reqwest::Client,
+    budget_alerted: AtomicBool,
+    deliveries: Arc<DeliveryLog>,
+}
+
+impl HookRunner {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            budget_alerted: AtomicBool::new(false),
+            deliveries: Arc::new(DeliveryLog::new()),
+        }
+    }
+
+    /// Recent webhook deliveries, oldest first
+    pub fn deliveries(&self) -> Vec<WebhookDelivery> {
+        self.deliveries.list()
+    }
+
+    /// Run every hook attached to the payload's event without waiting for them
+    pub fn fire(&self, hooks: &[Hook], payload: HookPayload) {
+        for hook in hooks_for(hooks, payload.event) {
+            let client = self.client.clone();
+            let deliveries = self.deliveries.clone();
+            let payload = payload.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = run_action(&client, &deliveries, &hook.action, &payload).await {
+                    tracing::warn!("Hook for {:?} failed: {}", payload.event, e);
+                }
+            });
+        }
+    }
+
+    /// Whether `BudgetExceeded` should fire; it fires once until usage drops
+    /// back under the budget (e.g. after metrics are reset)
+    pub fn budget_crossed(&self, budget: Option<u64>, total_tokens: u64) -> bool {
+        match budget {
+            Some(budget) if total_tokens > budget => !self.budget_alerted.swap(true, Ordering::SeqCst),
+            _ => {
+                self.budget_alerted.store(false, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+}
+
+impl Default for HookRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fire the configured hooks for an event, filling in the agent's name
+pub fn dispatch(state: &AppState, mut payload: HookPayload) {
+    let hooks = state.settings.get().hooks;
+    if hooks_for(&hooks, payload.event).is_empty() {
+        return;
+    }
+
+    if let Some(agent_id) = payload.agent_id {
+        payload.agent_name = state
+            .agent_pool
+            .agent_statuses()
+            .into_iter()
+            .find(|a| a.id == agent_id)
+            .map(|a| a.name);
+    }
+    state.hooks.fire(&hooks, payload);
+}
+
+/// Fire `BudgetExceeded` if token usage just went over the configured budget
+pub fn check_budget(state: &AppState) {
+    let budget = state.settings.get().token_budget;
+    let total_tokens = state.metrics.get_metrics().total_tokens;
+
+    if state.hooks.budget_crossed(budget, total_tokens) {
+        let message = LocalizedMessage::new(MessageKey::BudgetExceeded).with("tokens", total_tokens);
+        let payload = HookPayload::new(HookEvent::BudgetExceeded, None, message.render())
+        .with_details(serde_json::json!({
+            "budget": budget,
+            "total_tokens": total_tokens,
+        }));
+        state.alerts.raise(AlertKind::BudgetExceeded, None, None, message);
+        dispatch(state, payload);
+    }
+}
+
+async fn run_action(
+    client: &reqwest::Client,
+    deliveries: &DeliveryLog,
+    action: &HookAction,
+    payload: &HookPayload,
+) -> Result<(), String> {
+    match action {
+        HookAction::Sound { file } => play_sound(file.as_deref()).await,
+        HookAction::Command { command } => run_command(command, payload).await,
+        HookAction::Webhook { url, format } => {
+            webhook::deliver(client, url, *format, payload, deliveries).await
+        }
+    }
+}
+
+async fn play_sound(file: Option<&str>) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut c = Command::new("afplay");
+        c.arg(file.unwrap_or("/System/Library/Sounds/Glass.aiff"));
+        c
+    } else if cfg!(target_os = "windows") {
+        let script = match file {
+            Some(file) => format!(
+                "(New-Object Media.SoundPlayer '{}').PlaySync()",
+                file.replace('\'', "''")
+            ),
+            None => "[System.Media.SystemSounds]::Exclamation.Play()".to_string(),
+        };
+        let mut c = Command::new("powershell");
+        c.args(["-NoProfile", "-Command", &script]);
+        c
+    } else {
+        let mut c = Command::new("paplay");
+        c.arg(file.unwrap_or("/usr/share/sounds/freedesktop/stereo/complete.oga"));
+        c
+    };
+
+    let status = command.status().await.map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Sound player exited with {}", status))
+    }
+}
+
+async fn run_command(command: &str, payload: &HookPayload) -> Result<(), String> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    };
+
+    let event = serde_json::to_value(payload.event)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default();
+    cmd.env("ACPTORIO_EVENT", event)
+        .env("ACPTORIO_MESSAGE", &payload.message)
+        .env(
+            "ACPTORIO_AGENT_ID",
+            payload.agent_id.map(|id| id.to_string()).unwrap_or_default(),
+        )
+        .env("ACPTORIO_AGENT_NAME", payload.agent_name.clone().unwrap_or_default())
+        .stdin(std::process::Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let json = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+        // The command may not read stdin at all
+        let _ = stdin.write_all(&json).await;
+    }
+
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Hook command exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_config_and_matching() {
+        let hooks: Vec<Hook> = serde_json::from_str(
+            r#"[
+                {"event": "task_finished", "action": {"type": "sound"}},
+                {"event": "task_finished", "action": {"type": "webhook", "url": "http://x"}, "enabled": false},
+                {"event": "agent_error", "action": {"type": "command", "command": "notify-send err"}}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(hooks_for(&hooks, HookEvent::TaskFinished).len(), 1);
+        assert_eq!(hooks_for(&hooks, HookEvent::AgentError).len(), 1);
+        assert!(hooks_for(&hooks, HookEvent::BudgetExceeded).is_empty());
+    }
+
+    #[test]
+    fn test_budget_fires_once_until_reset() {
+        let runner = HookRunner::new();
+        assert!(!runner.budget_crossed(None, 5_000));
+        assert!(!runner.budget_crossed(Some(1_000), 500));
+        assert!(runner.budget_crossed(Some(1_000), 1_500));
+        assert!(!runner.budget_crossed(Some(1_000), 2_000));
+
+        // Metrics reset brings usage back under the budget
+        assert!(!runner.budget_crossed(Some(1_000), 0));
+        assert!(runner.budget_crossed(Some(1_000), 1_001));
+    }
+}
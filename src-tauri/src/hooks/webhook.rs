@@ -0,0 +1,258 @@
+//! Outbound webhook delivery with Slack/Discord formatting, retries and a
+//! delivery log.
+
+use super::{HookPayload, TaskReport};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const DELIVERY_LOG_CAPACITY: usize = 100;
+
+/// Body layout a webhook endpoint expects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The hook payload as-is
+    #[default]
+    Raw,
+    /// Slack incoming webhook (`text` with mrkdwn)
+    Slack,
+    /// Discord webhook (`content` plus an embed)
+    Discord,
+}
+
+/// Outcome of one webhook delivery, including its retries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: u64,
+    pub url: String,
+    pub format: WebhookFormat,
+    pub event: super::HookEvent,
+    pub agent_id: Option<uuid::Uuid>,
+    pub attempts: u32,
+    pub delivered: bool,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+/// Most recent webhook deliveries, newest last
+pub struct DeliveryLog {
+    next_id: AtomicU64,
+    deliveries: Mutex<VecDeque<WebhookDelivery>>,
+}
+
+impl DeliveryLog {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            deliveries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn record(&self, mut delivery: WebhookDelivery) {
+        delivery.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if deliveries.len() >= DELIVERY_LOG_CAPACITY {
+            deliveries.pop_front();
+        }
+        deliveries.push_back(delivery);
+    }
+
+    pub fn list(&self) -> Vec<WebhookDelivery> {
+        self.deliveries.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Default for DeliveryLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build the request body for a payload
+pub fn format_body(format: WebhookFormat, payload: &HookPayload) -> Value {
+    let report: Option<TaskReport> = serde_json::from_value(payload.details.clone()).ok();
+    let title = match &payload.agent_name {
+        Some(name) => format!("{}: {}", name, payload.message),
+        None => payload.message.clone(),
+    };
+    let fields = report.as_ref().map(report_fields).unwrap_or_default();
+
+    match format {
+        WebhookFormat::Raw => serde_json::to_value(payload).unwrap_or_default(),
+        WebhookFormat::Slack => {
+            let mut text = format!("*{}*", title);
+            for (name, value) in &fields {
+                text.push_str(&format!("\n*{}:* {}", name, value));
+            }
+            json!({ "text": text })
+        }
+        WebhookFormat::Discord => {
+            let failed = report.as_ref().is_some_and(|r| r.error.is_some());
+            json!({
+                "content": title,
+                "embeds": [{
+                    "title": title,
+                    "color": if failed { 0xE74C3C } else { 0x2ECC71 },
+                    "fields": fields
+                        .iter()
+                        .map(|(name, value)| json!({ "name": name, "value": value, "inline": false }))
+                        .collect::<Vec<_>>(),
+                }],
+            })
+        }
+    }
+}
+
+fn report_fields(report: &TaskReport) -> Vec<(String, String)> {
+    let mut fields = vec![("Project".to_string(), report.project.clone())];
+    if let Some(duration) = report.duration_secs {
+        fields.push(("Duration".to_string(), format!("{}s", duration)));
+    }
+    if let Some(stop_reason) = &report.stop_reason {
+        fields.push(("Stop reason".to_string(), stop_reason.clone()));
+    }
+    if let Some(error) = &report.error {
+        fields.push(("Error".to_string(), error.clone()));
+    }
+    if let Some(summary) = &report.change_summary {
+        let mut changes = format!(
+            "{} files, +{} -{}",
+            summary.files.len(),
+            summary.additions,
+            summary.deletions
+        );
+        for file in summary.files.iter().take(10) {
+            changes.push_str(&format!("\n{:?} {}", file.kind, file.path));
+        }
+        fields.push(("Changes".to_string(), changes));
+    }
+    fields
+}
+
+/// POST a payload, retrying network errors and 429/5xx responses with
+/// exponential backoff, and record the outcome in the log
+pub async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    format: WebhookFormat,
+    payload: &HookPayload,
+    log: &DeliveryLog,
+) -> Result<(), String> {
+    let body = format_body(format, payload);
+    let mut delivery = WebhookDelivery {
+        id: 0,
+        url: url.to_string(),
+        format,
+        event: payload.event,
+        agent_id: payload.agent_id,
+        attempts: 0,
+        delivered: false,
+        status_code: None,
+        error: None,
+        timestamp: payload.timestamp,
+    };
+
+    while delivery.attempts < MAX_ATTEMPTS {
+        if delivery.attempts > 0 {
+            tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(delivery.attempts - 1)).await;
+        }
+        delivery.attempts += 1;
+
+        let retryable = match client.post(url).timeout(REQUEST_TIMEOUT).json(&body).send().await {
+            Ok(response) => {
+                let status = response.status();
+                delivery.status_code = Some(status.as_u16());
+                if status.is_success() {
+                    delivery.delivered = true;
+                    delivery.error = None;
+                    break;
+                }
+                delivery.error = Some(format!("Webhook returned {}", status));
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(e) => {
+                delivery.error = Some(e.to_string());
+                true
+            }
+        };
+        if !retryable {
+            break;
+        }
+    }
+
+    let result = match &delivery.error {
+        Some(error) if !delivery.delivered => Err(error.clone()),
+        _ => Ok(()),
+    };
+    log.record(delivery);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::HookEvent;
+
+    fn task_payload() -> HookPayload {
+        let mut payload = HookPayload::new(HookEvent::TaskFinished, None, "Task completed");
+        payload.agent_name = Some("Builder".to_string());
+        payload.with_details(json!({
+            "task_id": "t1",
+            "status": "completed",
+            "prompt": "Fix the build",
+            "project": "acptorio",
+            "duration_secs": 42,
+            "stop_reason": "end_turn",
+            "error": null,
+            "change_summary": null
+        }))
+    }
+
+    #[test]
+    fn test_slack_body_includes_task_fields() {
+        let body = format_body(WebhookFormat::Slack, &task_payload());
+        let text = body["text"].as_str().unwrap();
+        assert!(text.starts_with("*Builder: Task completed*"));
+        assert!(text.contains("*Project:* acptorio"));
+        assert!(text.contains("*Duration:* 42s"));
+        assert!(text.contains("*Stop reason:* end_turn"));
+    }
+
+    #[test]
+    fn test_discord_body_uses_embed() {
+        let body = format_body(WebhookFormat::Discord, &task_payload());
+        assert_eq!(body["content"], "Builder: Task completed");
+        assert_eq!(body["embeds"][0]["fields"][0]["name"], "Project");
+        assert_eq!(body["embeds"][0]["color"], 0x2ECC71);
+    }
+
+    #[test]
+    fn test_delivery_log_is_bounded() {
+        let log = DeliveryLog::new();
+        for _ in 0..DELIVERY_LOG_CAPACITY + 5 {
+            log.record(WebhookDelivery {
+                id: 0,
+                url: "http://localhost".to_string(),
+                format: WebhookFormat::Raw,
+                event: HookEvent::TaskFinished,
+                agent_id: None,
+                attempts: 1,
+                delivered: true,
+                status_code: Some(200),
+                error: None,
+                timestamp: 0,
+            });
+        }
+        let deliveries = log.list();
+        assert_eq!(deliveries.len(), DELIVERY_LOG_CAPACITY);
+        assert_eq!(deliveries[0].id, 6);
+    }
+}
@@ -1,52 +1,540 @@
-mod acp;
+pub mod acp;
 pub mod agent;
+mod automation;
+mod clock;
 mod commands;
+mod deeplink;
+mod diagnostics;
+mod events;
 mod filesystem;
+mod git;
+mod hooks;
+mod messages;
+pub mod plugins;
+mod recording;
 pub mod registry;
+mod report;
+mod runner;
+mod simulation;
+mod shortcuts;
 mod state;
+mod tray;
 
 use commands::{
-    add_factory_project, count_files, get_agent, get_agent_icon, get_all_agent_icons,
-    get_factory_layout, get_fog_state, get_metrics, get_project_path, get_project_tree,
-    get_registry_agent, get_registry_agents, is_file_explored, list_agents, move_factory_project,
-    preload_agent_icons, read_file, refresh_registry, remove_agent_placement,
-    remove_factory_project, reset_metrics, respond_to_permission, reveal_file, retry_create_session,
-    save_factory_layout, scan_project, send_prompt, set_agent_placement, set_factory_viewport,
-    spawn_agent, start_agent_auth, stop_agent, stop_all_agents, update_factory_project,
+    acknowledge_alert, add_factory_project, add_project_to_group, analyze_project, benchmark_agent,
+    call_plugin_command, cancel_prompt, cancel_tool_call, clear_scratchpad, clear_window_interest,
+    clone_agent, compact_session, complete_onboarding_step, confirm_deep_link,
+    continue_imported_conversation, count_files, create_project_group, delete_imported_conversation,
+    delete_project_group, detect_subprojects, discard_worktree, dismiss_alert,
+    dismiss_interrupted_task, dismiss_saved_session, dispatch_for_matches, dispatch_routed_task,
+    dispatch_task, export_factory_report, export_usage_stats, find_files,
+    generate_diagnostics_bundle, get_activity_feed, get_agent, get_agent_environment,
+    get_agent_icon, get_agent_leaderboard, get_agent_updates, get_agent_worktree, get_alerts,
+    get_all_agent_icons, get_audit_log, get_away_summary, get_checkpoint, get_conflicts,
+    get_exploration_milestones, get_factory_layout, get_file_locks, get_file_visibility,
+    get_fog_delta, get_fog_state, get_fog_statistics, get_full_state, get_group_fog_statistics,
+    get_heatmap, get_imported_conversation, get_interrupted_tasks, get_last_event_seq,
+    get_log_levels, get_metrics, get_metrics_history, get_onboarding_status,
+    get_pending_permissions, get_placement_suggestions, get_pool_queue, get_project_instructions,
+    get_project_path, get_project_tree, get_prompt_draft, get_prompt_history,
+    get_protocol_violations, get_provider_overrides, get_recent_events, get_recording_status,
+    get_registry_agent, get_registry_agents, get_registry_sync_status, get_resumable_sessions,
+    get_running_tool_calls, get_scratchpad, get_session_history, get_settings, get_storage_status,
+    get_task_graph, get_task_history, get_time_tracking, get_tool_call_artifact, get_tool_output,
+    get_trigger_history, get_usage_stats, get_watcher_status, get_webhook_deliveries,
+    get_window_interest, handle_deep_link, import_cli_session, is_file_explored,
+    list_agent_sessions, list_agents, list_cli_sessions, list_imported_conversations,
+    list_pending_deep_links, list_pending_permissions, list_plugins, list_worktrees, merge_worktree,
+    migrate_data_dir, move_factory_project, move_prompt_draft, open_agent_window, open_in_editor,
+    preload_agent_icons, prune_fog, read_file, read_transcript_chunk, redispatch_interrupted_task,
+    refresh_registry, register_window_interest, reject_deep_link, remove_agent_placement,
+    remove_factory_project, remove_project_from_group, replay_session, request_task_review,
+    resend_prompt, reset_metrics, reset_onboarding, reset_usage_stats, respond_to_latest_permission,
+    respond_to_permission, restart_project_agents, resume_agent_session, resume_saved_session,
+    retry_create_session, reveal_directory, reveal_file, reveal_in_file_manager,
+    rollback_to_checkpoint, run_project_command, run_self_test, save_factory_layout,
+    save_prompt_draft, scan_project, send_followup, send_prompt, send_prompt_with_context,
+    set_agent_placement, set_factory_viewport, set_group_agent_defaults, set_log_level,
+    set_placement_stats, set_provider_overrides, set_scratchpad_entry, set_standing_order,
+    set_trigger_rule_enabled, spawn_agent, spawn_agent_in_worktree, start_agent_auth,
+    start_recording, start_simulation, stop_agent, stop_all_agents, stop_project_agents,
+    stop_recording, stop_replay, stop_simulation, subscribe_agent_updates, suggest_context,
+    unpin_agent_version, unsubscribe_agent_updates, unwatch_path, update_agent_version,
+    update_factory_project, update_settings, watch_path,
 };
-use state::AppState;
+use agent::TaskStatus;
+use events::TrackedEmitter;
+use hooks::{HookEvent, HookPayload};
+use messages::{LocalizedMessage, MessageKey};
+use state::{storage_status, AlertKind, AlertSeverity, AppState};
 use std::sync::Arc;
+use tauri::Manager;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tracing_subscriber::fmt::init();
+    diagnostics::init_logging();
 
     tauri::Builder::default()
+        // First, so a second launch, e.g. for an `acptorio://` link, hands
+        // its arguments to the running app and exits
+        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            tray::show_main_window(app)
+        }))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(shortcuts::plugin())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(Arc::new(AppState::new()))
-        .invoke_handler(tauri::generate_handler![
+        .setup(|app| {
+            // Forward task status changes to the frontend and keep them in
+            // the task history
+            let app_handle = app.handle().clone();
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            let mut task_events = state.agent_pool.task_graph().subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(task) = next_broadcast(&mut task_events, "task events").await {
+                    let _ = app_handle.emit_tracked("task-updated", &task);
+                    let project = state
+                        .agent_pool
+                        .get_agent_info(&task.agent_id)
+                        .map(|info| info.working_directory);
+                    if let Err(e) = state.database.save_task(&task, project.as_deref()) {
+                        tracing::warn!("{}", e);
+                    }
+
+                    // Offer to merge work done in an isolated worktree
+                    if task.status == TaskStatus::Completed {
+                        if let Some(worktree) = state.worktrees.get(&task.agent_id) {
+                            let _ = app_handle.emit_tracked("worktree-ready-to-merge", &worktree);
+                        }
+                    }
+
+                    // Keep the notes the agent left for the other agents
+                    if let (TaskStatus::Completed, Some(reply)) = (&task.status, &task.result) {
+                        if let Some((project_id, entries)) =
+                            state.apply_scratchpad_writes(&task.agent_id, reply).await
+                        {
+                            let update = serde_json::json!({
+                                "project_id": project_id,
+                                "entries": entries,
+                            });
+                            let _ = app_handle.emit_tracked("scratchpad-updated", &update);
+                        }
+                    }
+
+                    if matches!(task.status, TaskStatus::Completed | TaskStatus::Failed) {
+                        let finished = hooks::task_finished_payload(&state, &task);
+                        if task.status == TaskStatus::Failed {
+                            let message = match &task.error {
+                                Some(error) => {
+                                    LocalizedMessage::new(MessageKey::TaskFailedWithError).with("error", error)
+                                }
+                                None => LocalizedMessage::new(MessageKey::TaskFailed),
+                            };
+                            hooks::dispatch(
+                                &state,
+                                HookPayload::new(HookEvent::AgentError, Some(task.agent_id), message.render())
+                                    .with_details(finished.details.clone()),
+                            );
+                            state.alerts.raise(AlertKind::AgentError, Some(task.agent_id), Some(task.id.clone()), message);
+                        }
+                        hooks::dispatch(&state, finished);
+                        hooks::check_budget(&state);
+
+                        let tokens_used = state
+                            .agent_pool
+                            .get_agent_info(&task.agent_id)
+                            .map(|info| info.tokens_used)
+                            .unwrap_or(0);
+                        if let Err(e) = state
+                            .factory
+                            .record_prompt(&task.agent_id.to_string(), tokens_used, task.touched_files.len())
+                            .await
+                        {
+                            tracing::warn!("Failed to record placement stats: {}", e);
+                        }
+
+                        // Warn about files another agent touched meanwhile, once
+                        // both tasks are done
+                        let tasks = state.agent_pool.task_graph().snapshot().tasks;
+                        for conflict in agent::find_conflicts(&tasks) {
+                            let Some(other) = conflict.other(&task.id) else {
+                                continue;
+                            };
+                            if tasks.iter().any(|t| t.id == other && t.status.is_finished()) {
+                                let _ = app_handle.emit_tracked("task-conflict", &conflict);
+                            }
+                        }
+                    }
+
+                    // Verify the changes and send to the project's reviewer
+                    // agent, if either is configured
+                    if task.status == TaskStatus::Completed {
+                        tauri::async_runtime::spawn(commands::start_configured_verification(
+                            app_handle.clone(),
+                            state.clone(),
+                            task.clone(),
+                        ));
+                        tauri::async_runtime::spawn(commands::start_configured_review(
+                            app_handle.clone(),
+                            state.clone(),
+                            task,
+                        ));
+                    }
+                }
+            });
+
+            let app_handle = app.handle().clone();
+            let mut change_summaries = app
+                .state::<Arc<AppState>>()
+                .agent_pool
+                .task_graph()
+                .subscribe_change_summaries();
+            tauri::async_runtime::spawn(async move {
+                while let Some(summary) =
+                    next_broadcast(&mut change_summaries, "change summaries").await
+                {
+                    let _ = app_handle.emit_tracked("task-change-summary", &summary);
+                }
+            });
+
+            // Report malformed agent messages
+            let app_handle = app.handle().clone();
+            let mut violations = acp::PROTOCOL_VIOLATIONS.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(violation) =
+                    next_broadcast(&mut violations, "protocol violations").await
+                {
+                    app_handle
+                        .state::<Arc<AppState>>()
+                        .usage
+                        .record_error("protocol_violation");
+                    let _ = match violation.agent_id {
+                        Some(agent_id) => {
+                            app_handle.emit_agent_event("protocol-violation", agent_id, &violation)
+                        }
+                        None => app_handle.emit_tracked("protocol-violation", &violation),
+                    };
+                }
+            });
+
+            // Announce exploration milestones as the fog lifts
+            let app_handle = app.handle().clone();
+            let mut milestones = filesystem::EXPLORATION_MILESTONES.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(milestone) =
+                    next_broadcast(&mut milestones, "exploration milestones").await
+                {
+                    let _ = app_handle.emit_tracked("exploration-milestone", &milestone);
+                }
+            });
+
+            // Warn when agents edit the same file at the same time
+            let app_handle = app.handle().clone();
+            let mut conflicts = agent::FILE_CONFLICTS.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(conflict) = next_broadcast(&mut conflicts, "file conflicts").await {
+                    let _ = app_handle.emit_agent_event("file-conflict", conflict.agent_id, &conflict);
+                }
+            });
+
+            // Record the tokens and cost agents report
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            let mut usage = agent::AGENT_USAGE.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(usage) = next_broadcast(&mut usage, "usage reports").await {
+                    state.metrics.add_tokens(usage.input_tokens, usage.output_tokens);
+                    if usage.cost_cents > 0 {
+                        state.metrics.add_cost(usage.cost_cents);
+                    }
+                    hooks::check_budget(&state);
+                }
+            });
+
+            // Push agent state as it changes so the frontend never polls
+            let app_handle = app.handle().clone();
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            let mut state_changes = agent::AGENT_STATE_CHANGES.subscribe();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match state_changes.recv().await {
+                        Ok(change) => {
+                            let _ = app_handle.emit_agent_event("agent-state-changed", change.agent_id, &change);
+                            // Track time spent working for billing by project
+                            match change.status {
+                                Some(agent::AgentStatus::Working) => {
+                                    if let Some(info) = state.agent_pool.get_agent_info(&change.agent_id) {
+                                        state.metrics.work_started(info.id, &info.name, &info.working_directory);
+                                    }
+                                }
+                                Some(_) => state.metrics.work_stopped(change.agent_id),
+                                None => {}
+                            }
+                            if change.status == Some(agent::AgentStatus::Error) {
+                                state.alerts.raise(
+                                    AlertKind::ProcessCrashed,
+                                    Some(change.agent_id),
+                                    None,
+                                    LocalizedMessage::new(MessageKey::AgentProcessFailed),
+                                );
+                            }
+                        }
+                        // A later change carries the latest values of its fields
+                        Err(RecvError::Lagged(skipped)) => {
+                            tracing::warn!("Dropped {} agent state changes", skipped);
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Clear permission requests of stopped agents
+            let app_handle = app.handle().clone();
+            let mut cancelled_permissions = agent::CANCELLED_PERMISSIONS.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(permission) =
+                    next_broadcast(&mut cancelled_permissions, "cancelled permissions").await
+                {
+                    let _ = app_handle.emit_agent_event("permission-cancelled", permission.agent_id, &permission);
+                }
+            });
+
+            // Remind about permission requests nobody answered and apply the
+            // default action to those waiting too long
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            tauri::async_runtime::spawn(state.agent_pool.clone().run_escalation_sweeper());
+            let app_handle = app.handle().clone();
+            let mut escalations = agent::PERMISSION_ESCALATIONS.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(escalation) =
+                    next_broadcast(&mut escalations, "permission escalations").await
+                {
+                    let _ = app_handle.emit_agent_event("permission-escalated", escalation.agent_id, &escalation);
+                    if escalation.kind == agent::EscalationKind::Reminder {
+                        let message =
+                            LocalizedMessage::new(MessageKey::StillWaiting).with("request", &escalation.message);
+                        hooks::dispatch(
+                            &state,
+                            HookPayload::new(HookEvent::PermissionRequested, Some(escalation.agent_id), message.render())
+                                .with_details(serde_json::to_value(&escalation).unwrap_or_default()),
+                        );
+                        state.alerts.raise(AlertKind::StalePermission, Some(escalation.agent_id), None, message);
+                    } else {
+                        state.resolve_stale_permissions(escalation.agent_id);
+                    }
+                }
+            });
+
+            // Report agents that went over their CPU or memory limit
+            let app_handle = app.handle().clone();
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            let mut breaches = agent::LIMIT_BREACHES.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(breach) = next_broadcast(&mut breaches, "limit breaches").await {
+                    let _ = app_handle.emit_agent_event("agent-limit-exceeded", breach.agent_id, &breach);
+                    if breach.killed {
+                        let message = LocalizedMessage::new(MessageKey::KilledForMemory)
+                            .with("megabytes", format!("{:.0}", breach.observed));
+                        state.alerts.raise(AlertKind::ProcessCrashed, Some(breach.agent_id), None, message);
+                    }
+                }
+            });
+
+            // Tell the map which alarm badges to show
+            let app_handle = app.handle().clone();
+            let mut alerts = app.state::<Arc<AppState>>().alerts.subscribe();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match alerts.recv().await {
+                        Ok(alerts) => {
+                            let _ = app_handle.emit_tracked(
+                                "alerts-changed",
+                                serde_json::json!({ "alerts": alerts }),
+                            );
+                        }
+                        // The next list replaces the dropped ones
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Count errors in the local usage stats
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            let mut raised = state.alerts.subscribe_raised();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match raised.recv().await {
+                        Ok(alert) if alert.severity != AlertSeverity::Info => {
+                            let kind = serde_json::to_value(alert.kind).unwrap_or_default();
+                            state.usage.record_error(kind.as_str().unwrap_or("alert"));
+                        }
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Forward agent notifications the session handling does not consume
+            let app_handle = app.handle().clone();
+            let mut notifications = acp::AGENT_NOTIFICATIONS.subscribe();
+            tauri::async_runtime::spawn(async move {
+                while let Some(notification) =
+                    next_broadcast(&mut notifications, "agent notifications").await
+                {
+                    let _ = match notification.agent_id {
+                        Some(agent_id) => {
+                            app_handle.emit_agent_event("agent-notification", agent_id, &notification)
+                        }
+                        None => app_handle.emit_tracked("agent-notification", &notification),
+                    };
+                }
+            });
+
+            let app_handle = app.handle().clone();
+            let mut reviews = app
+                .state::<Arc<AppState>>()
+                .agent_pool
+                .task_graph()
+                .subscribe_reviews();
+            tauri::async_runtime::spawn(async move {
+                while let Some(task) = next_broadcast(&mut reviews, "task reviews").await {
+                    let _ = app_handle.emit_tracked("task-review", &task);
+                }
+            });
+            // Projects from the saved factory layout
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            tauri::async_runtime::spawn(async move { state.sync_project_roots().await });
+            // Reveal queued files and send them to the frontend in batches
+            let app_handle = app.handle().clone();
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(filesystem::REVEAL_BATCH_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Some(batch) = state.fog_reveals.take_batch() {
+                        state.fog.reveal_many(&batch.paths);
+                        let _ = app_handle.emit_tracked("fog-revealed-batch", &batch);
+                    }
+                }
+            });
+
+            automation::start_standing_orders(app.handle().clone());
+            automation::start_trigger_rules(app.handle().clone());
+            automation::start_placement_suggestions(app.handle().clone());
+            commands::spawn_update_checker(
+                app.handle().clone(),
+                app.state::<Arc<AppState>>().inner().clone(),
+            );
+            deeplink::listen(app.handle());
+
+            // Stores keep their data in memory for this session
+            let storage = storage_status();
+            if !storage.writable {
+                let _ = app.handle().emit_tracked("storage-degraded", &storage);
+            }
+
+            if let Err(e) = tray::init(app.handle()) {
+                tracing::warn!("Failed to create tray icon: {}", e);
+            }
+            let settings = app.state::<Arc<AppState>>().settings.get();
+            if let Err(e) = shortcuts::apply(app.handle(), &settings.permission_shortcuts) {
+                tracing::warn!("{}", e);
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                window
+                    .state::<Arc<AppState>>()
+                    .window_scopes
+                    .clear(window.label());
+            }
+        })
+        .invoke_handler(count_invocations(tauri::generate_handler![
             // Agent commands
             spawn_agent,
+            benchmark_agent,
+            clone_agent,
             stop_agent,
             list_agents,
             get_agent,
+            get_agent_environment,
             send_prompt,
+            send_followup,
+            send_prompt_with_context,
+            suggest_context,
+            get_prompt_history,
+            resend_prompt,
             stop_all_agents,
             respond_to_permission,
+            respond_to_latest_permission,
+            get_pending_permissions,
+            list_pending_permissions,
+            get_file_locks,
             start_agent_auth,
             retry_create_session,
+            list_agent_sessions,
+            resume_agent_session,
+            compact_session,
+            get_session_history,
+            get_tool_call_artifact,
+            get_tool_output,
+            get_running_tool_calls,
+            cancel_tool_call,
+            cancel_prompt,
+            get_task_history,
+            get_activity_feed,
+            get_audit_log,
+            get_metrics_history,
+            read_transcript_chunk,
+            get_protocol_violations,
+            dispatch_task,
+            dispatch_routed_task,
+            get_interrupted_tasks,
+            redispatch_interrupted_task,
+            dismiss_interrupted_task,
+            get_resumable_sessions,
+            resume_saved_session,
+            dismiss_saved_session,
+            get_pool_queue,
+            request_task_review,
+            get_task_graph,
+            get_agent_leaderboard,
+            get_conflicts,
             // Filesystem commands
             scan_project,
             get_project_tree,
+            get_watcher_status,
+            watch_path,
+            unwatch_path,
             get_project_path,
             reveal_file,
+            reveal_directory,
             get_fog_state,
+            get_fog_delta,
+            get_fog_statistics,
+            prune_fog,
+            get_heatmap,
+            find_files,
+            get_exploration_milestones,
             is_file_explored,
+            get_file_visibility,
             read_file,
             count_files,
+            open_in_editor,
+            reveal_in_file_manager,
+            // Import commands
+            list_cli_sessions,
+            import_cli_session,
+            list_imported_conversations,
+            get_imported_conversation,
+            delete_imported_conversation,
+            continue_imported_conversation,
             // Metrics commands
             get_metrics,
+            get_time_tracking,
             reset_metrics,
             // Factory commands
             get_factory_layout,
@@ -55,17 +543,147 @@ pub fn run() {
             remove_factory_project,
             move_factory_project,
             update_factory_project,
+            analyze_project,
+            detect_subprojects,
+            get_project_instructions,
+            run_project_command,
+            stop_project_agents,
+            restart_project_agents,
+            dispatch_for_matches,
             set_agent_placement,
+            set_standing_order,
+            set_placement_stats,
             remove_agent_placement,
             set_factory_viewport,
+            create_project_group,
+            delete_project_group,
+            add_project_to_group,
+            remove_project_from_group,
+            set_group_agent_defaults,
+            get_group_fog_statistics,
+            get_placement_suggestions,
+            export_factory_report,
             // Registry commands
             get_registry_agents,
             refresh_registry,
+            get_registry_sync_status,
             get_registry_agent,
             get_agent_icon,
             get_all_agent_icons,
             preload_agent_icons,
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+            get_agent_updates,
+            update_agent_version,
+            unpin_agent_version,
+            get_provider_overrides,
+            set_provider_overrides,
+            // Scratchpad commands
+            get_scratchpad,
+            set_scratchpad_entry,
+            clear_scratchpad,
+            // Prompt draft commands
+            get_prompt_draft,
+            save_prompt_draft,
+            move_prompt_draft,
+            // Deep link commands
+            handle_deep_link,
+            list_pending_deep_links,
+            confirm_deep_link,
+            reject_deep_link,
+            // Diagnostics commands
+            generate_diagnostics_bundle,
+            run_self_test,
+            set_log_level,
+            get_log_levels,
+            get_storage_status,
+            migrate_data_dir,
+            get_usage_stats,
+            export_usage_stats,
+            reset_usage_stats,
+            // Event commands
+            get_recent_events,
+            get_last_event_seq,
+            get_full_state,
+            get_away_summary,
+            register_window_interest,
+            get_window_interest,
+            clear_window_interest,
+            subscribe_agent_updates,
+            unsubscribe_agent_updates,
+            open_agent_window,
+            start_recording,
+            stop_recording,
+            get_recording_status,
+            replay_session,
+            stop_replay,
+            // Simulation commands
+            start_simulation,
+            stop_simulation,
+            // Alert commands
+            get_alerts,
+            acknowledge_alert,
+            dismiss_alert,
+            // Settings commands
+            get_settings,
+            update_settings,
+            get_webhook_deliveries,
+            set_trigger_rule_enabled,
+            get_trigger_history,
+            // Onboarding commands
+            get_onboarding_status,
+            complete_onboarding_step,
+            reset_onboarding,
+            // Plugin commands
+            list_plugins,
+            call_plugin_command,
+            // Git commands
+            get_checkpoint,
+            rollback_to_checkpoint,
+            spawn_agent_in_worktree,
+            get_agent_worktree,
+            list_worktrees,
+            merge_worktree,
+            discard_worktree,
+        ]))
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Sandboxed agents' containers outlive the app unless removed
+            if let tauri::RunEvent::Exit = event {
+                let state = app.state::<Arc<AppState>>();
+                // Saved before stopping, which ends the sessions
+                state.save_sessions();
+                if let Err(e) = tauri::async_runtime::block_on(state.agent_pool.stop_all()) {
+                    tracing::warn!("Failed to stop agents on exit: {}", e);
+                }
+                state.usage.save();
+            }
+        });
+}
+
+/// Next message of a broadcast channel, `None` once it closes; messages a
+/// lagging receiver missed are logged and skipped
+async fn next_broadcast<T: Clone>(rx: &mut broadcast::Receiver<T>, what: &str) -> Option<T> {
+    loop {
+        match rx.recv().await {
+            Ok(message) => return Some(message),
+            Err(RecvError::Lagged(skipped)) => tracing::warn!("Dropped {} {}", skipped, what),
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Count the commands the frontend invokes in the local usage stats
+fn count_invocations<R: tauri::Runtime>(
+    handler: impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        let webview = invoke.message.webview();
+        let state = webview.try_state::<Arc<AppState>>().map(|state| state.inner().clone());
+        let handled = handler(invoke);
+        if let Some(state) = state.filter(|_| handled) {
+            state.usage.record_feature(&command);
+        }
+        handled
+    }
 }
@@ -0,0 +1,125 @@
+//! User-facing messages composed in the backend, as a key with named
+//! parameters the frontend can translate. The English rendering is kept
+//! next to the key for logs, exports and frontends without a translation.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKey {
+    /// A tool call waits for approval; `tool`
+    AgentWantsTo,
+    /// An agent asked for permission; `tool`
+    PermissionRequested,
+    /// Legacy input request without text; `tool`
+    AgentNeedsPermission,
+    TaskFailed,
+    /// `error`
+    TaskFailedWithError,
+    AgentProcessFailed,
+    /// A permission request is still unanswered; `request`
+    StillWaiting,
+    /// `megabytes`
+    KilledForMemory,
+    /// `tokens`
+    BudgetExceeded,
+    /// `command`
+    VerificationFailed,
+    /// `project`, `edits`
+    PlacementSuggested,
+    /// `project`, `edits`, `percent`
+    PlacementSuggestedExplored,
+}
+
+impl MessageKey {
+    /// English template; `{name}` stands for the parameter `name`
+    fn english(self) -> &'static str {
+        match self {
+            MessageKey::AgentWantsTo => "Agent wants to: {tool}",
+            MessageKey::PermissionRequested => "Permission requested: {tool}",
+            MessageKey::AgentNeedsPermission => "Agent needs permission to use: {tool}",
+            MessageKey::TaskFailed => "Task failed",
+            MessageKey::TaskFailedWithError => "{error}",
+            MessageKey::AgentProcessFailed => "Agent process failed",
+            MessageKey::StillWaiting => "Still waiting: {request}",
+            MessageKey::KilledForMemory => "Killed for using {megabytes} MB of memory",
+            MessageKey::BudgetExceeded => "Token budget exceeded: {tokens} used",
+            MessageKey::VerificationFailed => "`{command}` failed after the task",
+            MessageKey::PlacementSuggested => {
+                "{project} had {edits} manual edits but no agent activity lately. Place an agent?"
+            }
+            MessageKey::PlacementSuggestedExplored => {
+                "{project} had {edits} manual edits but no agent activity lately. Place an agent? \
+                 Agents explored {percent}% of it."
+            }
+        }
+    }
+}
+
+/// A message key with its parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalizedMessage {
+    pub key: MessageKey,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+impl LocalizedMessage {
+    pub fn new(key: MessageKey) -> Self {
+        Self {
+            key,
+            params: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// The message in English. Parameter values are inserted as they are,
+    /// even if they contain braces.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let mut rest = self.key.english();
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let param = after
+                .find('}')
+                .and_then(|end| self.params.get(&after[..end]).map(|value| (end, value)));
+            match param {
+                Some((end, value)) => {
+                    text.push_str(value);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    text.push('{');
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_params() {
+        let message =
+            LocalizedMessage::new(MessageKey::PermissionRequested).with("tool", "Edit {tool}");
+        assert_eq!(message.render(), "Permission requested: Edit {tool}");
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["key"], "permission_requested");
+        assert_eq!(json["params"]["tool"], "Edit {tool}");
+
+        let missing = LocalizedMessage::new(MessageKey::KilledForMemory);
+        assert_eq!(missing.render(), "Killed for using {megabytes} MB of memory");
+    }
+}
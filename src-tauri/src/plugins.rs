@@ -0,0 +1,250 @@
+//! Extension points for org-specific policies and integrations, registered
+//! at startup instead of forking the message processing: plugins can change
+//! or drop agent updates before they reach the frontend, veto permissions
+//! the app would approve on its own, and answer commands of their own
+//! through `call_plugin_command`.
+//!
+//! A build embedding the app registers its plugins before starting it:
+//!
+//! ```ignore
+//! acptorio_lib::plugins::register_plugin(MyPolicy::default()).unwrap();
+//! acptorio_lib::run();
+//! ```
+
+use crate::acp::RequestPermissionRequest;
+use crate::agent::AgentUpdate;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// Plugins registered at startup
+pub static PLUGINS: Lazy<PluginRegistry> = Lazy::new(PluginRegistry::new);
+
+/// Why the app would approve a permission request without asking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoApprovalReason {
+    /// The user allowed similar calls for the prompt
+    AllowedSimilar,
+    /// The escalation policy approves requests left waiting
+    DefaultAction,
+    /// The caller asked to approve without the user
+    AutoApprove,
+    /// The permission policy script approved it
+    Script,
+}
+
+/// A permission request about to be approved without asking the user
+#[derive(Debug, Clone, Copy)]
+pub struct AutoApproval<'a> {
+    pub agent_id: Uuid,
+    pub request: &'a RequestPermissionRequest,
+    pub reason: AutoApprovalReason,
+}
+
+#[async_trait]
+pub trait Plugin: Send + Sync {
+    /// Unique name, used to address its commands
+    fn name(&self) -> &str;
+
+    /// Observe or change an update on its way to the frontend; `None` drops it
+    fn on_update(&self, update: AgentUpdate) -> Option<AgentUpdate> {
+        Some(update)
+    }
+
+    /// Why the request must go to the user instead, if it must
+    fn veto_auto_approval(&self, _approval: &AutoApproval<'_>) -> Option<String> {
+        None
+    }
+
+    /// Commands answered by [`Plugin::call`]
+    fn commands(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    async fn call(&self, command: &str, _args: Value) -> Result<Value, String> {
+        Err(format!("{} has no command {}", self.name(), command))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub name: String,
+    pub commands: Vec<String>,
+}
+
+pub struct PluginRegistry {
+    plugins: RwLock<Vec<Arc<dyn Plugin>>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self {
+            plugins: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn register(&self, plugin: Arc<dyn Plugin>) -> Result<(), String> {
+        let mut plugins = self.plugins.write().unwrap();
+        if plugins.iter().any(|p| p.name() == plugin.name()) {
+            return Err(format!(
+                "A plugin named {} is already registered",
+                plugin.name()
+            ));
+        }
+        tracing::info!("Registered plugin {}", plugin.name());
+        plugins.push(plugin);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Vec<Arc<dyn Plugin>> {
+        self.plugins.read().unwrap().clone()
+    }
+
+    pub fn list(&self) -> Vec<PluginInfo> {
+        self.snapshot()
+            .iter()
+            .map(|p| PluginInfo {
+                name: p.name().to_string(),
+                commands: p.commands(),
+            })
+            .collect()
+    }
+
+    /// Pass an update through every plugin in registration order
+    pub fn transform_update(&self, update: AgentUpdate) -> Option<AgentUpdate> {
+        self.snapshot()
+            .iter()
+            .try_fold(update, |update, plugin| plugin.on_update(update))
+    }
+
+    /// False if a plugin vetoes approving the request without the user
+    pub fn allows_auto_approval(
+        &self,
+        agent_id: Uuid,
+        request: &RequestPermissionRequest,
+        reason: AutoApprovalReason,
+    ) -> bool {
+        let approval = AutoApproval {
+            agent_id,
+            request,
+            reason,
+        };
+        for plugin in self.snapshot() {
+            if let Some(why) = plugin.veto_auto_approval(&approval) {
+                tracing::info!(
+                    "Plugin {} sent a permission request of agent {} to the user: {}",
+                    plugin.name(),
+                    agent_id,
+                    why
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    pub async fn call(&self, plugin: &str, command: &str, args: Value) -> Result<Value, String> {
+        let found = self.snapshot().into_iter().find(|p| p.name() == plugin);
+        let plugin = found.ok_or_else(|| format!("Unknown plugin: {}", plugin))?;
+        if !plugin.commands().iter().any(|c| c == command) {
+            return Err(format!("{} has no command {}", plugin.name(), command));
+        }
+        plugin.call(command, args).await
+    }
+}
+
+impl Default for PluginRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Register a plugin; call before [`crate::run`]
+pub fn register_plugin(plugin: impl Plugin + 'static) -> Result<(), String> {
+    PLUGINS.register(Arc::new(plugin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Policy;
+
+    #[async_trait]
+    impl Plugin for Policy {
+        fn name(&self) -> &str {
+            "policy"
+        }
+
+        fn on_update(&self, mut update: AgentUpdate) -> Option<AgentUpdate> {
+            if update.update_type == "thought" {
+                return None;
+            }
+            update.message = update.message.map(|m| m.replace("secret", "[hidden]"));
+            Some(update)
+        }
+
+        fn veto_auto_approval(&self, approval: &AutoApproval<'_>) -> Option<String> {
+            (approval.reason == AutoApprovalReason::DefaultAction)
+                .then(|| "nothing is approved unattended".to_string())
+        }
+
+        fn commands(&self) -> Vec<String> {
+            vec!["echo".to_string()]
+        }
+
+        async fn call(&self, _command: &str, args: Value) -> Result<Value, String> {
+            Ok(args)
+        }
+    }
+
+    fn update(update_type: &str, message: &str) -> AgentUpdate {
+        AgentUpdate {
+            agent_id: Uuid::new_v4(),
+            update_type: update_type.to_string(),
+            message: Some(message.to_string()),
+            tool: None,
+            progress: None,
+            current_file: None,
+            status: None,
+            pending_inputs: None,
+            touched_files: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugins_transform_veto_and_answer() {
+        let registry = PluginRegistry::new();
+        registry.register(Arc::new(Policy)).unwrap();
+        assert!(registry.register(Arc::new(Policy)).is_err());
+
+        let shown = registry.transform_update(update("agent_message_chunk", "a secret"));
+        assert_eq!(shown.unwrap().message.as_deref(), Some("a [hidden]"));
+        assert!(registry.transform_update(update("thought", "hm")).is_none());
+
+        let request: RequestPermissionRequest = serde_json::from_value(json!({
+            "sessionId": "s",
+            "toolCall": { "toolCallId": "t1", "title": "Edit file" },
+            "options": []
+        }))
+        .unwrap();
+        let agent = Uuid::new_v4();
+        assert!(registry.allows_auto_approval(agent, &request, AutoApprovalReason::AllowedSimilar));
+        assert!(!registry.allows_auto_approval(agent, &request, AutoApprovalReason::DefaultAction));
+
+        assert_eq!(
+            registry.call("policy", "echo", json!(1)).await,
+            Ok(json!(1))
+        );
+        assert!(registry
+            .call("policy", "drop_tables", json!(1))
+            .await
+            .is_err());
+        assert!(registry.call("other", "echo", json!(1)).await.is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Session recordings: every tracked event written with its time offset to
+//! a JSON Lines file, which can be played back into the frontend later for
+//! demos or to reproduce a bug.
+
+use crate::clock::now_ms;
+use crate::events::RecordedEvent;
+use crate::state::app_data_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Format version written in the header line
+const RECORDING_VERSION: u32 = 1;
+
+/// First line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordingHeader {
+    version: u32,
+    started_at: u64,
+}
+
+/// One recorded event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// Milliseconds since the recording started
+    pub offset_ms: u64,
+    pub event: String,
+    pub payload: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingStatus {
+    pub path: String,
+    pub started_at: u64,
+    pub events: usize,
+}
+
+struct ActiveRecording {
+    status: RecordingStatus,
+    writer: LineWriter<File>,
+}
+
+pub struct SessionRecorder {
+    active: Mutex<Option<ActiveRecording>>,
+    /// Bumped to cancel the replay in progress
+    replay_generation: AtomicU64,
+}
+
+impl SessionRecorder {
+    pub fn new() -> Self {
+        Self {
+            active: Mutex::new(None),
+            replay_generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Start writing events to `path`, replacing any recording in progress
+    pub fn start(&self, path: &Path) -> Result<RecordingStatus, String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let mut writer = LineWriter::new(file);
+        let started_at = now_ms();
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            started_at,
+        };
+        serde_json::to_writer(&mut writer, &header).map_err(|e| e.to_string())?;
+        writer.write_all(b"\n").map_err(|e| e.to_string())?;
+
+        let status = RecordingStatus {
+            path: path.display().to_string(),
+            started_at,
+            events: 0,
+        };
+        *self.active.lock().unwrap() = Some(ActiveRecording {
+            status: status.clone(),
+            writer,
+        });
+        Ok(status)
+    }
+
+    /// Finish the recording in progress
+    pub fn stop(&self) -> Result<RecordingStatus, String> {
+        let mut recording = self
+            .active
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| "Not recording".to_string())?;
+        recording
+            .writer
+            .flush()
+            .map_err(|e| format!("Failed to write recording: {}", e))?;
+        Ok(recording.status)
+    }
+
+    pub fn status(&self) -> Option<RecordingStatus> {
+        self.active.lock().unwrap().as_ref().map(|r| r.status.clone())
+    }
+
+    /// Append an emitted event to the recording in progress, if any
+    pub fn capture(&self, event: &RecordedEvent) {
+        let mut active = self.active.lock().unwrap();
+        let Some(recording) = active.as_mut() else {
+            return;
+        };
+        let frame = ReplayFrame {
+            offset_ms: event.timestamp.saturating_sub(recording.status.started_at),
+            event: event.event.clone(),
+            payload: event.payload.clone(),
+        };
+        let written = serde_json::to_writer(&mut recording.writer, &frame)
+            .map_err(|e| e.to_string())
+            .and_then(|_| recording.writer.write_all(b"\n").map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => recording.status.events += 1,
+            Err(e) => {
+                // A full disk should not break event delivery; stop recording instead
+                tracing::warn!("Stopping session recording {}: {}", recording.status.path, e);
+                *active = None;
+            }
+        }
+    }
+
+    /// Start a new replay, cancelling the one in progress; returns its
+    /// generation for [`is_current_replay`](Self::is_current_replay)
+    pub fn begin_replay(&self) -> u64 {
+        self.replay_generation.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn cancel_replay(&self) {
+        self.replay_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn is_current_replay(&self, generation: u64) -> bool {
+        self.replay_generation.load(Ordering::Relaxed) == generation
+    }
+}
+
+impl Default for SessionRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read the frames of a recording, oldest first
+pub fn load_recording(path: &Path) -> Result<Vec<ReplayFrame>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut lines = BufReader::new(file).lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| format!("{} is empty", path.display()))?
+        .map_err(|e| e.to_string())?;
+    let header: RecordingHeader =
+        serde_json::from_str(&header).map_err(|e| format!("Not a session recording: {}", e))?;
+    if header.version > RECORDING_VERSION {
+        return Err(format!("Unsupported recording version {}", header.version));
+    }
+
+    let mut frames = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ReplayFrame>(&line) {
+            Ok(frame) => frames.push(frame),
+            // The last line of a recording cut short by a crash may be partial
+            Err(e) => tracing::warn!("Skipping line {} of {}: {}", index + 2, path.display(), e),
+        }
+    }
+    frames.sort_by_key(|f| f.offset_ms);
+    Ok(frames)
+}
+
+/// Default location for a new recording
+pub fn default_recording_path() -> PathBuf {
+    app_data_dir()
+        .join("recordings")
+        .join(format!("session-{}.jsonl", now_ms()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_recording_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("acptorio-recording-{}", uuid::Uuid::new_v4()))
+            .join("session.jsonl");
+        let recorder = SessionRecorder::new();
+        let status = recorder.start(&path).unwrap();
+        for (offset, event) in [(5, "agent-update"), (1, "fog-revealed")] {
+            recorder.capture(&RecordedEvent {
+                seq: offset,
+                event: event.to_string(),
+                payload: json!({ "n": offset }),
+                timestamp: status.started_at + offset,
+                scope: None,
+            });
+        }
+        assert_eq!(recorder.stop().unwrap().events, 2);
+        assert!(recorder.stop().is_err());
+
+        let frames = load_recording(&path).unwrap();
+        assert_eq!(frames[0].event, "fog-revealed");
+        assert_eq!(frames[1], ReplayFrame {
+            offset_ms: 5,
+            event: "agent-update".to_string(),
+            payload: json!({ "n": 5 }),
+        });
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
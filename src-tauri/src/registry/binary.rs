@@ -1,4 +1,5 @@
 //! Binary distribution download and caching
+use crate::state::cache_dir;
 use std::path::PathBuf;
 use tokio::fs;
 use tracing::{info, warn};
@@ -27,14 +28,23 @@ pub struct BinaryManager {
 
 impl BinaryManager {
     pub fn new() -> Self {
-        let cache_dir = dirs::data_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("acptorio")
-            .join("binaries");
+        let cache_dir = cache_dir().join("binaries");
 
         Self { cache_dir }
     }
 
+    /// Versions of an agent that have been downloaded
+    pub fn installed_versions(&self, agent_id: &str) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(self.cache_dir.join(agent_id)) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect()
+    }
+
     /// Get the path to a cached binary, downloading if needed
     pub async fn get_binary(
         &self,
@@ -1,7 +1,9 @@
 pub mod binary;
 mod service;
 mod types;
+mod updates;
 
 pub use binary::{BinaryManager, BinaryError, get_platform};
 pub use service::RegistryService;
 pub use types::*;
+pub use updates::*;
@@ -1,27 +1,48 @@
-use super::types::{get_claude_agent, Registry, RegistryAgent};
+use super::binary::BinaryManager;
+use super::types::{
+    get_claude_agent, get_demo_agent, ProviderOverrides, Registry, RegistryAgent,
+    RegistryCacheState, RegistrySyncStatus, DEMO_AGENT_ID,
+};
+use super::updates::{version_status, AgentVersionStatus};
+use crate::clock::now_secs;
+use crate::state::{app_data_dir, cache_dir, storage_writable};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
 const REGISTRY_URL: &str =
     "https://github.com/agentclientprotocol/registry/releases/latest/download/registry.json";
 const CACHE_TTL_HOURS: u64 = 1;
+const PINS_FILE: &str = "agent-pins.json";
+const OVERRIDES_FILE: &str = "provider-overrides.json";
 
 pub struct RegistryService {
     registry: RwLock<Registry>,
     cache_path: PathBuf,
     icons_dir: PathBuf,
     last_fetch: RwLock<Option<u64>>,
+    fetch_error: RwLock<Option<String>>,
+    /// agent id -> error of the last attempt to download its icon
+    icon_errors: std::sync::RwLock<HashMap<String, String>>,
+    /// agent id -> version to use instead of the registry's latest
+    pins: std::sync::RwLock<HashMap<String, String>>,
+    pins_path: PathBuf,
+    /// agent id -> local changes merged into its registry entry
+    overrides: std::sync::RwLock<HashMap<String, ProviderOverrides>>,
+    overrides_path: PathBuf,
+    version_statuses: RwLock<Vec<AgentVersionStatus>>,
 }
 
 impl RegistryService {
     pub fn new() -> Self {
         let base_path = Self::get_cache_dir();
         let cache_path = base_path.join("registry.json");
-        let icons_dir = base_path.join("icons");
+        let icons_dir = cache_dir().join("icons");
+        let pins_path = base_path.join(PINS_FILE);
+        let overrides_path = base_path.join(OVERRIDES_FILE);
 
         // Create icons directory
         fs::create_dir_all(&icons_dir).ok();
@@ -34,15 +55,20 @@ impl RegistryService {
             cache_path,
             icons_dir,
             last_fetch: RwLock::new(None),
+            fetch_error: RwLock::new(None),
+            icon_errors: std::sync::RwLock::new(HashMap::new()),
+            pins: std::sync::RwLock::new(Self::load_pins(&pins_path).unwrap_or_default()),
+            pins_path,
+            overrides: std::sync::RwLock::new(
+                Self::load_overrides(&overrides_path).unwrap_or_default(),
+            ),
+            overrides_path,
+            version_statuses: RwLock::new(Vec::new()),
         }
     }
 
     fn get_cache_dir() -> PathBuf {
-        let base = dirs::data_dir()
-            .or_else(dirs::home_dir)
-            .unwrap_or_else(|| PathBuf::from("."));
-
-        let app_dir = base.join("acptorio");
+        let app_dir = app_data_dir();
         fs::create_dir_all(&app_dir).ok();
         app_dir
     }
@@ -52,7 +78,20 @@ impl RegistryService {
         serde_json::from_str(&content).ok()
     }
 
+    fn load_pins(path: &Path) -> Option<HashMap<String, String>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn load_overrides(path: &Path) -> Option<HashMap<String, ProviderOverrides>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
     fn save_registry(&self, registry: &Registry) {
+        if !storage_writable() {
+            return;
+        }
         if let Ok(content) = serde_json::to_string_pretty(registry) {
             if let Err(e) = fs::write(&self.cache_path, content) {
                 warn!("Failed to save registry cache: {}", e);
@@ -65,10 +104,7 @@ impl RegistryService {
     }
 
     fn current_timestamp() -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+        now_secs()
     }
 
     fn is_cache_stale(&self, last_fetch: Option<u64>) -> bool {
@@ -83,6 +119,12 @@ impl RegistryService {
 
     /// Fetch registry from remote (called at startup and on refresh)
     pub async fn fetch_registry(&self) -> Result<(), String> {
+        let result = self.fetch_remote_registry().await;
+        *self.fetch_error.write().await = result.as_ref().err().cloned();
+        result
+    }
+
+    async fn fetch_remote_registry(&self) -> Result<(), String> {
         info!("Fetching registry from {}", REGISTRY_URL);
 
         let client = reqwest::Client::builder()
@@ -128,7 +170,7 @@ impl RegistryService {
         // Download all icons
         for agent in &registry.agents {
             if let Some(icon_url) = &agent.icon {
-                if let Err(e) = self.download_icon(&agent.id, icon_url).await {
+                if let Err(e) = self.sync_icon(&agent.id, icon_url).await {
                     warn!("Failed to download icon for {}: {}", agent.id, e);
                 }
             }
@@ -150,17 +192,24 @@ impl RegistryService {
             let _ = self.fetch_registry().await;
         }
 
-        // Always include Claude first, then registry agents
+        // Always include Claude first, then registry agents, then the demo
         let mut agents = vec![get_claude_agent()];
         let registry_agents = self.registry.read().await.agents.clone();
 
-        // Add registry agents, but skip if there's already a "claude" entry
+        // Add registry agents, but skip ids of built-in agents
         for agent in registry_agents {
-            if agent.id != "claude" {
+            if agent.id != "claude" && agent.id != DEMO_AGENT_ID {
                 agents.push(agent);
             }
         }
+        agents.push(get_demo_agent());
 
+        let overrides = self.overrides.read().unwrap();
+        for agent in agents.iter_mut() {
+            if let Some(o) = overrides.get(&agent.id) {
+                o.apply(agent);
+            }
+        }
         agents
     }
 
@@ -169,20 +218,176 @@ impl RegistryService {
         self.fetch_registry().await
     }
 
-    /// Get a specific agent by ID
+    /// Get a specific agent by ID, with its local overrides merged in
     pub async fn get_agent(&self, id: &str) -> Option<RegistryAgent> {
         // Check for built-in Claude first
-        if id == "claude" {
-            return Some(get_claude_agent());
+        let mut agent = if id == "claude" {
+            get_claude_agent()
+        } else if id == DEMO_AGENT_ID {
+            get_demo_agent()
+        } else {
+            self.registry
+                .read()
+                .await
+                .agents
+                .iter()
+                .find(|a| a.id == id)
+                .cloned()?
+        };
+
+        if let Some(overrides) = self.overrides.read().unwrap().get(id) {
+            overrides.apply(&mut agent);
+        }
+        Some(agent)
+    }
+
+    /// Local overrides of every provider that has some
+    pub fn provider_overrides(&self) -> HashMap<String, ProviderOverrides> {
+        self.overrides.read().unwrap().clone()
+    }
+
+    /// Replace a provider's overrides, or drop them with None
+    pub fn set_provider_overrides(
+        &self,
+        agent_id: &str,
+        overrides: Option<ProviderOverrides>,
+    ) -> Result<(), String> {
+        let mut all = self.overrides.write().unwrap();
+        match overrides.filter(|o| !o.is_empty()) {
+            Some(overrides) => all.insert(agent_id.to_string(), overrides),
+            None => all.remove(agent_id),
+        };
+
+        if !storage_writable() {
+            return Ok(());
+        }
+        let content = serde_json::to_string_pretty(&*all)
+            .map_err(|e| format!("Failed to serialize provider overrides: {}", e))?;
+        fs::write(&self.overrides_path, content)
+            .map_err(|e| format!("Failed to write provider overrides file: {}", e))
+    }
+
+    /// Version an agent is pinned to, if any
+    pub fn pinned_version(&self, agent_id: &str) -> Option<String> {
+        self.pins.read().unwrap().get(agent_id).cloned()
+    }
+
+    /// Pin an agent to a version, or unpin it with None
+    pub fn set_pinned_version(&self, agent_id: &str, version: Option<String>) -> Result<(), String> {
+        let mut pins = self.pins.write().unwrap();
+        match version {
+            Some(version) => pins.insert(agent_id.to_string(), version),
+            None => pins.remove(agent_id),
+        };
+
+        if !storage_writable() {
+            return Ok(());
+        }
+        let content = serde_json::to_string_pretty(&*pins)
+            .map_err(|e| format!("Failed to serialize pins: {}", e))?;
+        fs::write(&self.pins_path, content).map_err(|e| format!("Failed to write pins file: {}", e))
+    }
+
+    /// Refresh the registry and compare every agent with its pinned or
+    /// installed version
+    pub async fn check_updates(&self) -> Vec<AgentVersionStatus> {
+        if let Err(e) = self.fetch_registry().await {
+            warn!("Update check could not refresh the registry: {}", e);
         }
 
-        self.registry
+        let binaries = BinaryManager::new();
+        let statuses: Vec<AgentVersionStatus> = self
+            .get_agents()
+            .await
+            .iter()
+            .map(|agent| {
+                version_status(
+                    agent,
+                    self.pinned_version(&agent.id),
+                    binaries.installed_versions(&agent.id),
+                )
+            })
+            .collect();
+
+        *self.version_statuses.write().await = statuses.clone();
+        statuses
+    }
+
+    /// Recompute one agent's version status, e.g. after pinning it
+    pub async fn refresh_version_status(&self, agent: &RegistryAgent) -> AgentVersionStatus {
+        let status = version_status(
+            agent,
+            self.pinned_version(&agent.id),
+            BinaryManager::new().installed_versions(&agent.id),
+        );
+
+        let mut statuses = self.version_statuses.write().await;
+        match statuses.iter_mut().find(|s| s.agent_id == agent.id) {
+            Some(existing) => *existing = status.clone(),
+            None => statuses.push(status.clone()),
+        }
+        status
+    }
+
+    /// Result of the most recent update check
+    pub async fn version_statuses(&self) -> Vec<AgentVersionStatus> {
+        self.version_statuses.read().await.clone()
+    }
+
+    /// Current state of the cache without fetching anything
+    pub async fn cache_state(&self) -> RegistryCacheState {
+        let last_fetch = *self.last_fetch.read().await;
+        let binaries = BinaryManager::new();
+        let agents: Vec<(String, String)> = self
+            .registry
             .read()
             .await
             .agents
             .iter()
-            .find(|a| a.id == id)
-            .cloned()
+            .map(|a| (a.id.clone(), a.version.clone()))
+            .collect();
+        let installed_binaries = agents
+            .iter()
+            .map(|(id, _)| (id.clone(), binaries.installed_versions(id)))
+            .filter(|(_, versions)| !versions.is_empty())
+            .collect();
+
+        RegistryCacheState {
+            cache_path: self.cache_path.to_string_lossy().to_string(),
+            last_fetch,
+            stale: self.is_cache_stale(last_fetch),
+            agents,
+            pins: self.pins.read().unwrap().clone(),
+            installed_binaries,
+        }
+    }
+
+    /// Age of the cache, icons cached and missing, and errors of the last
+    /// fetches, without fetching anything
+    pub async fn sync_status(&self) -> RegistrySyncStatus {
+        let last_fetch = *self.last_fetch.read().await;
+        let cache_age_secs = fs::metadata(&self.cache_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .map(|age| age.as_secs());
+        let registry = self.registry.read().await;
+        let (cached, missing): (Vec<&RegistryAgent>, Vec<&RegistryAgent>) = registry
+            .agents
+            .iter()
+            .filter(|a| a.icon.is_some())
+            .partition(|a| self.get_icon_path(&a.id).exists());
+
+        RegistrySyncStatus {
+            last_fetch,
+            cache_age_secs,
+            stale: self.is_cache_stale(last_fetch),
+            agent_count: registry.agents.len(),
+            icons_cached: cached.len(),
+            icons_missing: missing.into_iter().map(|a| a.id.clone()).collect(),
+            fetch_error: self.fetch_error.read().await.clone(),
+            icon_errors: self.icon_errors.read().unwrap().clone(),
+        }
     }
 
     /// Get all cached icons as base64 data URLs
@@ -221,6 +426,17 @@ impl RegistryService {
         None
     }
 
+    /// Download an icon and remember whether that worked
+    async fn sync_icon(&self, agent_id: &str, icon_url: &str) -> Result<(), String> {
+        let result = self.download_icon(agent_id, icon_url).await;
+        let mut errors = self.icon_errors.write().unwrap();
+        match &result {
+            Ok(()) => errors.remove(agent_id),
+            Err(e) => errors.insert(agent_id.to_string(), e.clone()),
+        };
+        result
+    }
+
     /// Download icon SVG file to cache
     async fn download_icon(&self, agent_id: &str, icon_url: &str) -> Result<(), String> {
         let icon_path = self.get_icon_path(agent_id);
@@ -269,7 +485,7 @@ impl RegistryService {
             .ok_or_else(|| "Agent not found".to_string())?;
 
         if let Some(icon_url) = &agent.icon {
-            self.download_icon(agent_id, icon_url).await?;
+            self.sync_icon(agent_id, icon_url).await?;
         }
 
         self.get_icon(agent_id).ok_or_else(|| "Icon not found".to_string())
@@ -280,7 +496,7 @@ impl RegistryService {
         let agents = self.get_agents().await;
         for agent in agents {
             if let Some(icon_url) = &agent.icon {
-                if let Err(e) = self.download_icon(&agent.id, icon_url).await {
+                if let Err(e) = self.sync_icon(&agent.id, icon_url).await {
                     warn!("Failed to download icon for {}: {}", agent.id, e);
                 }
             }
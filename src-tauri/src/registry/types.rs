@@ -10,7 +10,48 @@ pub struct RegistryAgent {
     pub description: String,
     #[serde(default)]
     pub icon: Option<String>,
+    /// Release notes for `version`, when the registry provides them
+    #[serde(default)]
+    pub changelog: Option<String>,
     pub distribution: Distribution,
+    /// Model to select once a session is open, from a local override
+    #[serde(default)]
+    pub default_model: Option<String>,
+    /// Auth method to offer first, from a local override
+    #[serde(default)]
+    pub auth_method: Option<String>,
+}
+
+/// Snapshot of the registry cache, for diagnostics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryCacheState {
+    pub cache_path: String,
+    pub last_fetch: Option<u64>,
+    pub stale: bool,
+    /// Agent id -> version known from the cached registry
+    pub agents: Vec<(String, String)>,
+    pub pins: HashMap<String, String>,
+    /// Agent id -> versions downloaded by the binary manager
+    pub installed_binaries: HashMap<String, Vec<String>>,
+}
+
+/// How fresh the registry and its icons are, for the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySyncStatus {
+    /// Unix seconds of the last successful fetch since the app started
+    pub last_fetch: Option<u64>,
+    /// Seconds since the cached registry was written, by this run or an earlier one
+    pub cache_age_secs: Option<u64>,
+    pub stale: bool,
+    /// Agents in the cached registry, without the built-in ones
+    pub agent_count: usize,
+    pub icons_cached: usize,
+    /// Agents with an icon URL whose icon is not cached
+    pub icons_missing: Vec<String>,
+    /// Why the last fetch failed; cleared by a successful one
+    pub fetch_error: Option<String>,
+    /// Agent id -> why its icon could not be downloaded
+    pub icon_errors: HashMap<String, String>,
 }
 
 /// How to spawn/run the agent - matches the actual registry format
@@ -37,6 +78,48 @@ pub struct BinaryPlatform {
     pub cmd: String,
     #[serde(default)]
     pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+/// Local changes to how a provider is run, for quirks the registry entry
+/// doesn't cover
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderOverrides {
+    /// Appended to the distribution's arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Set on top of the distribution's environment
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub auth_method: Option<String>,
+}
+
+impl ProviderOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge into a registry entry, for every distribution it has
+    pub fn apply(&self, agent: &mut RegistryAgent) {
+        if let Some(npx) = agent.distribution.npx.as_mut() {
+            npx.args.extend(self.args.iter().cloned());
+            npx.env.extend(self.env.clone());
+        }
+        for platform in agent.distribution.binary.iter_mut().flat_map(|b| b.values_mut()) {
+            platform.args.extend(self.args.iter().cloned());
+            platform.env.extend(self.env.clone());
+        }
+        if self.default_model.is_some() {
+            agent.default_model = self.default_model.clone();
+        }
+        if self.auth_method.is_some() {
+            agent.auth_method = self.auth_method.clone();
+        }
+    }
 }
 
 /// The full registry structure from the remote
@@ -55,6 +138,26 @@ impl Default for Registry {
     }
 }
 
+/// Provider id of the built-in in-process demo agent
+pub const DEMO_AGENT_ID: &str = "demo";
+pub const DEMO_AGENT_NAME: &str = "Demo Agent";
+
+/// Get the built-in demo agent. It has no distribution: it runs in-process
+/// and needs neither Node nor an API key.
+pub fn get_demo_agent() -> RegistryAgent {
+    RegistryAgent {
+        id: DEMO_AGENT_ID.to_string(),
+        name: DEMO_AGENT_NAME.to_string(),
+        version: "latest".to_string(),
+        description: "Simulated agent that replays canned updates, tool calls and permission requests, for trying the factory offline".to_string(),
+        icon: None,
+        changelog: None,
+        distribution: Distribution::default(),
+        default_model: None,
+        auth_method: None,
+    }
+}
+
 /// Get the built-in Claude agent
 pub fn get_claude_agent() -> RegistryAgent {
     RegistryAgent {
@@ -63,6 +166,7 @@ pub fn get_claude_agent() -> RegistryAgent {
         version: "latest".to_string(),
         description: "Anthropic's Claude AI coding assistant".to_string(),
         icon: None,
+        changelog: None,
         distribution: Distribution {
             npx: Some(NpxDistribution {
                 package: "@zed-industries/claude-code-acp@latest".to_string(),
@@ -71,6 +175,43 @@ pub fn get_claude_agent() -> RegistryAgent {
             }),
             binary: None,
         },
+        default_model: None,
+        auth_method: None,
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_to_every_distribution() {
+        let mut agent = get_claude_agent();
+        agent.distribution.binary = Some(HashMap::from([(
+            "linux-x86_64".to_string(),
+            BinaryPlatform {
+                archive: "https://example.com/agent.tar.gz".to_string(),
+                cmd: "./agent".to_string(),
+                args: vec!["acp".to_string()],
+                env: HashMap::new(),
+            },
+        )]));
+        let overrides = ProviderOverrides {
+            args: vec!["--verbose".to_string()],
+            env: HashMap::from([("AGENT_MODE".to_string(), "acp".to_string())]),
+            default_model: Some("opus".to_string()),
+            auth_method: None,
+        };
+        overrides.apply(&mut agent);
+
+        let npx = agent.distribution.npx.unwrap();
+        assert_eq!(npx.args, vec!["--verbose"]);
+        assert_eq!(npx.env["AGENT_MODE"], "acp");
+        let binary = &agent.distribution.binary.unwrap()["linux-x86_64"];
+        assert_eq!(binary.args, vec!["acp", "--verbose"]);
+        assert_eq!(agent.default_model.as_deref(), Some("opus"));
+        assert!(agent.auth_method.is_none());
+        assert!(ProviderOverrides::default().is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! Comparing registry agent versions with the installed and pinned ones.
+
+use super::types::RegistryAgent;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// How often the registry is checked for agent updates
+pub const UPDATE_CHECK_INTERVAL_SECS: u64 = 6 * 3600;
+
+/// An agent's local version compared to the registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentVersionStatus {
+    pub agent_id: String,
+    pub name: String,
+    /// Pinned version, or else the newest installed one
+    pub current_version: Option<String>,
+    pub pinned_version: Option<String>,
+    pub installed_versions: Vec<String>,
+    pub latest_version: String,
+    pub update_available: bool,
+    pub description: String,
+    pub changelog: Option<String>,
+}
+
+/// Compare dotted versions numerically where possible (`1.10.0 > 1.9.2`),
+/// ignoring a leading `v` and any pre-release suffix on equal cores
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    };
+    let (pa, pb) = (parts(a), parts(b));
+    for i in 0..pa.len().max(pb.len()) {
+        let ord = pa.get(i).unwrap_or(&0).cmp(pb.get(i).unwrap_or(&0));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Newest of a list of versions
+pub fn newest_version(versions: &[String]) -> Option<&String> {
+    versions.iter().max_by(|a, b| compare_versions(a, b))
+}
+
+/// Build the version status of a registry agent
+pub fn version_status(
+    agent: &RegistryAgent,
+    pinned_version: Option<String>,
+    installed_versions: Vec<String>,
+) -> AgentVersionStatus {
+    let current_version = pinned_version
+        .clone()
+        .or_else(|| newest_version(&installed_versions).cloned());
+    // "latest" is a moving tag and can never be outdated
+    let update_available = agent.version != "latest"
+        && current_version
+            .as_deref()
+            .is_some_and(|current| compare_versions(&agent.version, current) == Ordering::Greater);
+
+    AgentVersionStatus {
+        agent_id: agent.id.clone(),
+        name: agent.name.clone(),
+        current_version,
+        pinned_version,
+        installed_versions,
+        latest_version: agent.version.clone(),
+        update_available,
+        description: agent.description.clone(),
+        changelog: agent.changelog.clone(),
+    }
+}
+
+/// Replace the version of an npx package spec, e.g.
+/// `@scope/pkg@latest` -> `@scope/pkg@1.2.3`
+pub fn pin_npx_package(package: &str, version: &str) -> String {
+    // A leading `@` belongs to the scope, not the version
+    let name = match package.get(1..).and_then(|rest| rest.rfind('@')) {
+        Some(i) => &package[..i + 1],
+        None => package,
+    };
+    format!("{}@{}", name, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::Distribution;
+
+    fn agent(version: &str) -> RegistryAgent {
+        RegistryAgent {
+            id: "gemini".to_string(),
+            name: "Gemini".to_string(),
+            version: version.to_string(),
+            description: String::new(),
+            icon: None,
+            changelog: Some("Faster startup".to_string()),
+            distribution: Distribution::default(),
+            default_model: None,
+            auth_method: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("v2.0", "2.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.3.1-beta", "0.3.2"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_version_status() {
+        let installed = vec!["0.9.0".to_string(), "0.10.1".to_string()];
+        let status = version_status(&agent("0.11.0"), None, installed.clone());
+        assert_eq!(status.current_version.as_deref(), Some("0.10.1"));
+        assert!(status.update_available);
+
+        let pinned = version_status(&agent("0.11.0"), Some("0.11.0".to_string()), installed);
+        assert!(!pinned.update_available);
+
+        // Nothing installed or pinned: nothing to update
+        assert!(!version_status(&agent("0.11.0"), None, Vec::new()).update_available);
+        assert!(!version_status(&agent("latest"), Some("0.1.0".to_string()), Vec::new()).update_available);
+    }
+
+    #[test]
+    fn test_pin_npx_package() {
+        assert_eq!(
+            pin_npx_package("@zed-industries/claude-code-acp@latest", "0.5.0"),
+            "@zed-industries/claude-code-acp@0.5.0"
+        );
+        assert_eq!(pin_npx_package("@scope/pkg", "1.0.0"), "@scope/pkg@1.0.0");
+        assert_eq!(pin_npx_package("pkg@2", "3.0.0"), "pkg@3.0.0");
+    }
+}
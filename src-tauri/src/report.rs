@@ -0,0 +1,367 @@
+//! Factory overview report: projects, agents, recent tasks, metrics, time
+//! spent working and exploration rendered as Markdown or HTML, for sharing the factory's
+//! status with people who don't run it.
+
+use crate::agent::{AgentInfo, TaskInfo};
+use crate::state::{FactoryLayout, Metrics, TimeTracking};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Tasks listed, newest first
+const RECENT_TASKS: usize = 20;
+/// Prompts are cut to this many characters
+const PROMPT_PREVIEW_CHARS: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// HTML for `.html` and `.htm` files, Markdown otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                ReportFormat::Html
+            }
+            _ => ReportFormat::Markdown,
+        }
+    }
+}
+
+/// Everything the report shows
+pub struct ReportInput<'a> {
+    pub layout: &'a FactoryLayout,
+    pub agents: &'a [AgentInfo],
+    pub tasks: &'a [TaskInfo],
+    pub metrics: &'a Metrics,
+    pub time: &'a TimeTracking,
+    /// Explored files per project id
+    pub explored: &'a HashMap<String, usize>,
+    pub generated_at: u64,
+}
+
+/// A titled table
+struct Section {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+/// Serialized name of an enum value, e.g. `running`
+fn label<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn ago(now: u64, at: u64) -> String {
+    match now.saturating_sub(at) {
+        secs if secs < 60 => format!("{}s ago", secs),
+        secs if secs < 3600 => format!("{} min ago", secs / 60),
+        secs if secs < 86400 => format!("{} h ago", secs / 3600),
+        secs => format!("{} d ago", secs / 86400),
+    }
+}
+
+fn duration(secs: u64) -> String {
+    match secs {
+        secs if secs < 3600 => format!("{} min", secs / 60),
+        secs => format!("{} h {:02} min", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn preview(prompt: &str) -> String {
+    let line = prompt.lines().next().unwrap_or_default();
+    if line.chars().count() > PROMPT_PREVIEW_CHARS || prompt.lines().nth(1).is_some() {
+        let cut: String = line.chars().take(PROMPT_PREVIEW_CHARS).collect();
+        format!("{}…", cut)
+    } else {
+        line.to_string()
+    }
+}
+
+fn sections(input: &ReportInput) -> Vec<Section> {
+    let layout = input.layout;
+    let placements: HashMap<&str, _> = layout
+        .agent_placements
+        .iter()
+        .map(|p| (p.agent_id.as_str(), p))
+        .collect();
+    let project_name = |id: &str| {
+        layout
+            .projects
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| p.name.clone())
+            .unwrap_or_default()
+    };
+
+    let projects = layout
+        .projects
+        .iter()
+        .map(|project| {
+            let agents = layout
+                .agent_placements
+                .iter()
+                .filter(|p| p.connected_project_id.as_deref() == Some(project.id.as_str()))
+                .count();
+            let explored = input.explored.get(&project.id).copied().unwrap_or(0);
+            let exploration = match project.file_count {
+                Some(count) if count > 0 => {
+                    format!("{:.0}%", (explored as f64 / count as f64 * 100.0).min(100.0))
+                }
+                _ => format!("{} files", explored),
+            };
+            vec![
+                project.name.clone(),
+                project.path.clone(),
+                project.file_count.map(|c| c.to_string()).unwrap_or_else(|| "?".to_string()),
+                agents.to_string(),
+                exploration,
+            ]
+        })
+        .collect();
+
+    let agents = input
+        .agents
+        .iter()
+        .map(|agent| {
+            let placement = placements.get(agent.id.to_string().as_str());
+            let project = placement
+                .and_then(|p| p.connected_project_id.as_deref())
+                .map(project_name)
+                .unwrap_or_default();
+            vec![
+                agent.name.clone(),
+                label(&agent.status),
+                agent.provider_id.clone().unwrap_or_else(|| "default".to_string()),
+                project,
+                agent.tokens_used.to_string(),
+                agent.pending_inputs.len().to_string(),
+            ]
+        })
+        .collect();
+
+    let names: HashMap<_, _> = input.agents.iter().map(|a| (a.id, a.name.as_str())).collect();
+    let mut tasks: Vec<&TaskInfo> = input.tasks.iter().collect();
+    tasks.sort_by_key(|task| Reverse(task.seq));
+    let tasks = tasks
+        .into_iter()
+        .take(RECENT_TASKS)
+        .map(|task| {
+            let duration = match (task.started_at, task.finished_at) {
+                (Some(start), Some(end)) => format!("{}s", end.saturating_sub(start)),
+                _ => String::new(),
+            };
+            let agent = names
+                .get(&task.agent_id)
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| task.agent_id.to_string());
+            vec![
+                agent,
+                preview(&task.prompt),
+                label(&task.status),
+                duration,
+                ago(input.generated_at, task.finished_at.unwrap_or(task.created_at)),
+            ]
+        })
+        .collect();
+
+    let metrics = input.metrics;
+    let metrics = vec![
+        vec!["Input tokens".to_string(), metrics.total_input_tokens.to_string()],
+        vec!["Output tokens".to_string(), metrics.total_output_tokens.to_string()],
+        vec!["Cost".to_string(), format!("${:.2}", metrics.total_cost_dollars)],
+        vec!["Session".to_string(), format!("{} min", metrics.session_duration_secs / 60)],
+    ];
+
+    let project_label = |path: &str| {
+        layout
+            .project_containing(path)
+            .map(|p| p.name.clone())
+            .unwrap_or_else(|| path.to_string())
+    };
+    let project_time = input
+        .time
+        .projects
+        .iter()
+        .map(|p| vec![project_label(&p.project), duration(p.active_secs)])
+        .collect();
+    let agent_time = input
+        .time
+        .agents
+        .iter()
+        .map(|a| {
+            vec![
+                a.agent_name.clone(),
+                project_label(&a.project),
+                duration(a.active_secs),
+            ]
+        })
+        .collect();
+
+    vec![
+        Section {
+            title: "Projects",
+            headers: &["Project", "Path", "Files", "Agents", "Explored"],
+            rows: projects,
+        },
+        Section {
+            title: "Agents",
+            headers: &["Agent", "Status", "Provider", "Project", "Tokens", "Pending"],
+            rows: agents,
+        },
+        Section {
+            title: "Recent tasks",
+            headers: &["Agent", "Prompt", "Status", "Duration", "Updated"],
+            rows: tasks,
+        },
+        Section {
+            title: "Metrics",
+            headers: &["Metric", "Value"],
+            rows: metrics,
+        },
+        Section {
+            title: "Time by project",
+            headers: &["Project", "Active"],
+            rows: project_time,
+        },
+        Section {
+            title: "Time by agent",
+            headers: &["Agent", "Project", "Active"],
+            rows: agent_time,
+        },
+    ]
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+pub fn render_markdown(input: &ReportInput) -> String {
+    let mut out = String::from("# Factory report\n");
+    for section in sections(input) {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("_None_\n");
+            continue;
+        }
+        out.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+        out.push_str(&format!("|{}\n", " --- |".repeat(section.headers.len())));
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().map(|c| markdown_cell(c)).collect();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_html(input: &ReportInput) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Factory report</title>\n\
+         <style>body{font-family:sans-serif}table{border-collapse:collapse}\
+         td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}</style>\n</head>\n<body>\n\
+         <h1>Factory report</h1>\n",
+    );
+    for section in sections(input) {
+        out.push_str(&format!("<h2>{}</h2>\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("<p><em>None</em></p>\n");
+            continue;
+        }
+        out.push_str("<table>\n<tr>");
+        for header in section.headers {
+            out.push_str(&format!("<th>{}</th>", header));
+        }
+        out.push_str("</tr>\n");
+        for row in &section.rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub fn render(input: &ReportInput, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(input),
+        ReportFormat::Html => render_html(input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ProjectNode;
+
+    #[test]
+    fn test_render_escapes_cells() {
+        let layout = FactoryLayout {
+            projects: vec![ProjectNode {
+                id: "p".to_string(),
+                path: "/work/a|b".to_string(),
+                name: "<app>".to_string(),
+                grid_x: 0,
+                grid_y: 0,
+                file_count: Some(10),
+                color_index: None,
+                summary: None,
+            }],
+            ..Default::default()
+        };
+        let metrics = Metrics {
+            total_input_tokens: 1,
+            total_output_tokens: 2,
+            total_tokens: 3,
+            total_cost_dollars: 0.5,
+            session_duration_secs: 120,
+        };
+        let explored = HashMap::from([("p".to_string(), 4)]);
+        let time = TimeTracking {
+            total_secs: 3900,
+            projects: vec![crate::state::ProjectTime {
+                project: "/work/a|b/src".to_string(),
+                active_secs: 3900,
+            }],
+            ..Default::default()
+        };
+        let input = ReportInput {
+            layout: &layout,
+            agents: &[],
+            tasks: &[],
+            metrics: &metrics,
+            time: &time,
+            explored: &explored,
+            generated_at: 0,
+        };
+
+        let markdown = render_markdown(&input);
+        assert!(markdown.contains("| <app> | /work/a\\|b | 10 | 0 | 40% |"));
+        assert!(markdown.contains("## Agents\n\n_None_"));
+        assert!(markdown.contains("| <app> | 1 h 05 min |"));
+
+        let html = render_html(&input);
+        assert!(html.contains("<td>&lt;app&gt;</td>"));
+        assert!(html.contains("<td>$0.50</td>"));
+        assert_eq!(ReportFormat::from_path(Path::new("out.HTML")), ReportFormat::Html);
+        assert_eq!(ReportFormat::from_path(Path::new("out.md")), ReportFormat::Markdown);
+    }
+}
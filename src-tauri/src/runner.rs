@@ -0,0 +1,265 @@
+//! Build, test and lint commands configured per project, run in the
+//! project directory with their output streamed line by line.
+
+use crate::clock::now_ms;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+/// Output lines kept on the run record
+const OUTPUT_TAIL_LINES: usize = 200;
+
+/// Finished runs, once the task and agent they verified are filled in
+pub static FINISHED_RUNS: Lazy<broadcast::Sender<ProjectCommandRun>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectCommandKind {
+    Build,
+    Test,
+    Lint,
+}
+
+impl fmt::Display for ProjectCommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProjectCommandKind::Build => "build",
+            ProjectCommandKind::Test => "test",
+            ProjectCommandKind::Lint => "lint",
+        })
+    }
+}
+
+/// Shell commands of one project
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectCommands {
+    /// Applies to this directory and below it
+    pub project_path: String,
+    #[serde(default)]
+    pub build: Option<String>,
+    #[serde(default)]
+    pub test: Option<String>,
+    #[serde(default)]
+    pub lint: Option<String>,
+    /// Command run after each task that changed files in the project
+    #[serde(default)]
+    pub verify: Option<ProjectCommandKind>,
+    /// Send the agent the output when verification fails
+    #[serde(default)]
+    pub follow_up_on_failure: bool,
+}
+
+impl ProjectCommands {
+    pub fn get(&self, kind: ProjectCommandKind) -> Option<&str> {
+        match kind {
+            ProjectCommandKind::Build => self.build.as_deref(),
+            ProjectCommandKind::Test => self.test.as_deref(),
+            ProjectCommandKind::Lint => self.lint.as_deref(),
+        }
+        .filter(|c| !c.trim().is_empty())
+    }
+}
+
+/// The commands covering `directory`, preferring the most specific project
+pub fn find_project_commands<'a>(
+    all: &'a [ProjectCommands],
+    directory: &str,
+) -> Option<&'a ProjectCommands> {
+    all.iter()
+        .filter(|c| Path::new(directory).starts_with(&c.project_path))
+        .max_by_key(|c| c.project_path.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutputLine {
+    pub run_id: String,
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectCommandRun {
+    pub run_id: String,
+    /// Factory project the command ran for
+    pub project_id: String,
+    pub kind: ProjectCommandKind,
+    pub command: String,
+    pub success: bool,
+    /// None when the command could not start or was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Last lines of stdout and stderr, interleaved
+    pub output_tail: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// Task whose result the run verified
+    pub after_task: Option<String>,
+    /// Agent of that task
+    pub agent_id: Option<Uuid>,
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    }
+}
+
+fn pipe_lines(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+    stream: OutputStream,
+    lines: mpsc::UnboundedSender<(OutputStream, String)>,
+) {
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            if lines.send((stream, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Run `command` in `directory`, sending each output line to `output`
+pub async fn run_project_command(
+    project_id: &str,
+    kind: ProjectCommandKind,
+    command: &str,
+    directory: &str,
+    output: mpsc::Sender<CommandOutputLine>,
+) -> ProjectCommandRun {
+    let mut run = ProjectCommandRun {
+        run_id: Uuid::new_v4().to_string(),
+        project_id: project_id.to_string(),
+        kind,
+        command: command.to_string(),
+        success: false,
+        exit_code: None,
+        output_tail: String::new(),
+        started_at: now_ms(),
+        finished_at: 0,
+        after_task: None,
+        agent_id: None,
+    };
+
+    let mut child = match shell(command)
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            run.output_tail = format!("Failed to start {}: {}", command, e);
+            run.finished_at = now_ms();
+            return run;
+        }
+    };
+
+    let (lines_tx, mut lines_rx) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        pipe_lines(stdout, OutputStream::Stdout, lines_tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pipe_lines(stderr, OutputStream::Stderr, lines_tx);
+    }
+
+    let mut tail: VecDeque<String> = VecDeque::with_capacity(OUTPUT_TAIL_LINES);
+    while let Some((stream, line)) = lines_rx.recv().await {
+        if tail.len() == OUTPUT_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line.clone());
+        // Nobody listening is fine
+        let _ = output
+            .send(CommandOutputLine {
+                run_id: run.run_id.clone(),
+                stream,
+                line,
+            })
+            .await;
+    }
+
+    match child.wait().await {
+        Ok(status) => {
+            run.success = status.success();
+            run.exit_code = status.code();
+        }
+        Err(e) => tail.push_back(format!("Failed to wait for {}: {}", command, e)),
+    }
+    run.output_tail = Vec::from(tail).join("\n");
+    run.finished_at = now_ms();
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_project_commands_prefers_most_specific() {
+        let all = vec![
+            ProjectCommands {
+                project_path: "/work".into(),
+                test: Some("make test".into()),
+                ..Default::default()
+            },
+            ProjectCommands {
+                project_path: "/work/app".into(),
+                test: Some("cargo test".into()),
+                lint: Some("  ".into()),
+                ..Default::default()
+            },
+        ];
+        let commands = find_project_commands(&all, "/work/app/src").unwrap();
+        assert_eq!(commands.get(ProjectCommandKind::Test), Some("cargo test"));
+        assert_eq!(commands.get(ProjectCommandKind::Lint), None);
+        assert!(find_project_commands(&all, "/elsewhere").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_streams_output_and_reports_failure() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let run = run_project_command(
+            "p1",
+            ProjectCommandKind::Test,
+            "pwd; echo oops >&2; exit 3",
+            "/tmp",
+            tx,
+        )
+        .await;
+        assert!(!run.success);
+        assert_eq!(run.exit_code, Some(3));
+        assert!(run.output_tail.contains("oops"));
+
+        let mut lines = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            assert_eq!(line.run_id, run.run_id);
+            lines.push((line.stream, line.line));
+        }
+        assert!(lines.contains(&(OutputStream::Stderr, "oops".to_string())));
+        assert_eq!(lines.len(), 2);
+    }
+}
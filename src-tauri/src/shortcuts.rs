@@ -0,0 +1,99 @@
+//! System-wide shortcuts that answer the newest permission request while
+//! another app has the focus.
+
+use crate::state::AppState;
+use crate::tray;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+/// Key combinations like `CmdOrCtrl+Shift+Y`; none, the default, to leave the
+/// keys free
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionShortcuts {
+    #[serde(default)]
+    pub approve: Option<String>,
+    #[serde(default)]
+    pub reject: Option<String>,
+}
+
+impl PermissionShortcuts {
+    /// Parsed shortcuts with whether they approve
+    fn bindings(&self) -> Result<Vec<(Shortcut, bool)>, String> {
+        let mut bindings = Vec::new();
+        for (keys, approved) in [(&self.approve, true), (&self.reject, false)] {
+            let Some(keys) = keys else {
+                continue;
+            };
+            let shortcut = keys
+                .parse::<Shortcut>()
+                .map_err(|e| format!("Invalid shortcut {}: {}", keys, e))?;
+            if bindings.iter().any(|(s, _)| *s == shortcut) {
+                return Err(format!("Shortcut {} is bound twice", keys));
+            }
+            bindings.push((shortcut, approved));
+        }
+        Ok(bindings)
+    }
+}
+
+pub fn plugin() -> tauri::plugin::TauriPlugin<tauri::Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(handle_shortcut)
+        .build()
+}
+
+fn handle_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state != ShortcutState::Pressed {
+        return;
+    }
+    let settings = app.state::<Arc<AppState>>().settings.get();
+    let Ok(bindings) = settings.permission_shortcuts.bindings() else {
+        return;
+    };
+    if let Some((_, approved)) = bindings.iter().find(|(s, _)| s == shortcut) {
+        tray::respond_to_latest(app, *approved);
+    }
+}
+
+/// Replace the registered shortcuts with `shortcuts`
+pub fn apply(app: &AppHandle, shortcuts: &PermissionShortcuts) -> Result<(), String> {
+    let bindings = shortcuts.bindings()?;
+    let global = app.global_shortcut();
+    global.unregister_all().map_err(|e| e.to_string())?;
+    for (shortcut, _) in bindings {
+        global
+            .register(shortcut)
+            .map_err(|e| format!("Failed to register shortcut {}: {}", shortcut, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings_reject_invalid_and_duplicate_keys() {
+        assert!(PermissionShortcuts::default().bindings().unwrap().is_empty());
+
+        let both = PermissionShortcuts {
+            approve: Some("CmdOrCtrl+Shift+Y".to_string()),
+            reject: Some("CmdOrCtrl+Shift+N".to_string()),
+        };
+        assert_eq!(both.bindings().unwrap().len(), 2);
+
+        let invalid = PermissionShortcuts {
+            approve: Some("Ctrl+Nope".to_string()),
+            reject: None,
+        };
+        assert!(invalid.bindings().is_err());
+
+        let duplicate = PermissionShortcuts {
+            approve: Some("Ctrl+Shift+Y".to_string()),
+            reject: Some("Shift+Ctrl+Y".to_string()),
+        };
+        assert!(duplicate.bindings().is_err());
+    }
+}
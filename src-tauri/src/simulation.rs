@@ -0,0 +1,349 @@
+//! Simulation mode for frontend development: fake agents that spawn, run
+//! tool calls over a made-up project, reveal fog and use tokens on a timer,
+//! with no agent processes, API keys or network. The same seed always
+//! produces the same sequence of events.
+
+use crate::agent::{AgentInfo, AgentStatus, AgentUpdate, ToolUpdate, TouchedRange};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const AGENT_NAMES: &[&str] = &["Miner", "Smelter", "Assembler", "Inserter", "Belt", "Radar"];
+
+const FILES: &[&str] = &[
+    "README.md",
+    "Cargo.toml",
+    "package.json",
+    "src/main.rs",
+    "src/lib.rs",
+    "src/config.rs",
+    "src/api/mod.rs",
+    "src/api/routes.rs",
+    "src/api/handlers.rs",
+    "src/db/mod.rs",
+    "src/db/schema.rs",
+    "src/ui/App.tsx",
+    "src/ui/components/Header.tsx",
+    "src/ui/components/Sidebar.tsx",
+    "src/ui/hooks/useSession.ts",
+    "tests/api_test.rs",
+    "tests/db_test.rs",
+    "docs/architecture.md",
+];
+
+/// (kind, title verb)
+const TOOLS: &[(&str, &str)] = &[
+    ("read", "Read"),
+    ("read", "Read"),
+    ("search", "Search"),
+    ("edit", "Edit"),
+    ("execute", "Run tests for"),
+];
+
+const TOKEN_LIMIT: u64 = 200_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub agents: usize,
+    /// Milliseconds between steps
+    pub tick_ms: u64,
+    /// Directory the fake files appear under
+    pub root: String,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            agents: 3,
+            tick_ms: 800,
+            root: "/simulated/project".to_string(),
+        }
+    }
+}
+
+/// Events of one step, plus tokens to add to the session metrics
+#[derive(Debug, Default)]
+pub struct SimulationStep {
+    /// Event name, agent the event is about, payload
+    pub events: Vec<(String, Option<Uuid>, Value)>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl SimulationStep {
+    fn push(&mut self, event: &str, agent_id: Option<Uuid>, payload: impl Serialize) {
+        let payload = serde_json::to_value(payload).unwrap_or(Value::Null);
+        self.events.push((event.to_string(), agent_id, payload));
+    }
+}
+
+/// SplitMix64, so runs do not depend on a platform random source
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+struct SimAgent {
+    info: AgentInfo,
+    /// File of the tool call waiting for its completion update
+    open_call: Option<String>,
+    /// Tool calls left in the current prompt
+    calls_left: usize,
+}
+
+pub struct Simulation {
+    config: SimulationConfig,
+    rng: Rng,
+    agents: Vec<SimAgent>,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        let agents = (0..config.agents)
+            .map(|i| SimAgent {
+                info: AgentInfo {
+                    id: Uuid::from_u64_pair(config.seed, i as u64),
+                    name: format!("{} {}", AGENT_NAMES[i % AGENT_NAMES.len()], i + 1),
+                    status: AgentStatus::Idle,
+                    session_id: Some(format!("sim-session-{}", i + 1)),
+                    working_directory: config.root.clone(),
+                    current_file: None,
+                    progress: 0.0,
+                    tokens_used: 0,
+                    token_limit: TOKEN_LIMIT,
+                    pending_inputs: Vec::new(),
+                    provider_id: Some("simulation".to_string()),
+                    provider_name: Some("Simulation".to_string()),
+                    auth_methods: Vec::new(),
+                    needs_auth: false,
+                    available_commands: Vec::new(),
+                    supports_load_session: false,
+                    supports_list_sessions: false,
+                    modes: None,
+                    models: None,
+                    // Fixed so a seed always gives the same events
+                    created_at: 0,
+                    last_prompt_at: None,
+                    last_update_at: None,
+                    last_transcript: None,
+                },
+                open_call: None,
+                calls_left: 0,
+            })
+            .collect();
+        Self {
+            rng: Rng(config.seed),
+            config,
+            agents,
+        }
+    }
+
+    pub fn agent_ids(&self) -> Vec<Uuid> {
+        self.agents.iter().map(|a| a.info.id).collect()
+    }
+
+    /// Spawn events of every agent
+    pub fn start(&self) -> SimulationStep {
+        let mut step = SimulationStep::default();
+        for agent in &self.agents {
+            step.push("agent-spawned", None, &agent.info);
+        }
+        step
+    }
+
+    /// Advance one agent by one action
+    pub fn tick(&mut self) -> SimulationStep {
+        let mut step = SimulationStep::default();
+        if self.agents.is_empty() {
+            return step;
+        }
+        let index = self.rng.below(self.agents.len());
+        let root = self.config.root.trim_end_matches('/').to_string();
+
+        let agent = &mut self.agents[index];
+        let id = agent.info.id;
+        if agent.info.status == AgentStatus::Idle {
+            // Idle agents only sometimes get a new prompt
+            if !self.rng.chance(40) {
+                return step;
+            }
+            agent.info.status = AgentStatus::Working;
+            agent.info.progress = 0.0;
+            agent.calls_left = 2 + self.rng.below(5);
+            step.push("agent-status-changed", None, &agent.info);
+            return step;
+        }
+
+        if let Some(path) = agent.open_call.take() {
+            let input = 500 + self.rng.below(3000) as u64;
+            let output = 100 + self.rng.below(800) as u64;
+            agent.info.tokens_used = (agent.info.tokens_used + input + output).min(TOKEN_LIMIT);
+            step.input_tokens += input;
+            step.output_tokens += output;
+            step.push(
+                "agent-update",
+                Some(id),
+                update(id, "tool_call_update", None, Some(path.clone()), Vec::new()),
+            );
+            step.push("fog-revealed", None, json!({ "path": path }));
+            return step;
+        }
+
+        if agent.calls_left == 0 {
+            agent.info.status = AgentStatus::Idle;
+            agent.info.progress = 100.0;
+            agent.info.current_file = None;
+            step.push(
+                "agent-update",
+                Some(id),
+                update(id, "agent_message_chunk", Some("Done. The changes are in place.".to_string()), None, Vec::new()),
+            );
+            step.push("agent-status-changed", None, &agent.info);
+            return step;
+        }
+
+        let path = format!("{}/{}", root, FILES[self.rng.below(FILES.len())]);
+        let (kind, verb) = TOOLS[self.rng.below(TOOLS.len())];
+        let start_line = 1 + self.rng.below(200) as u32;
+        let range = TouchedRange {
+            path: path.clone(),
+            start_line: Some(start_line),
+            end_line: Some(start_line + self.rng.below(40) as u32),
+        };
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        let title = format!("{} {}", verb, name);
+
+        agent.calls_left -= 1;
+        agent.info.current_file = Some(path.clone());
+        agent.info.progress = (agent.info.progress + 100.0 / (agent.calls_left + 2) as f64).min(95.0);
+        let mut tool_call = update(id, "tool_call", Some(title.clone()), Some(path.clone()), vec![range]);
+        tool_call.tool = Some(ToolUpdate {
+            name: title,
+            input: Some(json!({ "kind": kind, "file_path": path })),
+        });
+        tool_call.progress = Some(agent.info.progress);
+        step.push("agent-update", Some(id), tool_call);
+        agent.open_call = Some(path);
+        step
+    }
+}
+
+fn update(
+    agent_id: Uuid,
+    update_type: &str,
+    message: Option<String>,
+    current_file: Option<String>,
+    touched_files: Vec<TouchedRange>,
+) -> AgentUpdate {
+    AgentUpdate {
+        agent_id,
+        update_type: update_type.to_string(),
+        message,
+        tool: None,
+        progress: None,
+        current_file,
+        status: None,
+        pending_inputs: None,
+        touched_files,
+    }
+}
+
+/// The running simulation, if any
+pub struct SimulationControl {
+    /// Bumped to stop the running simulation
+    generation: AtomicU64,
+    agents: Mutex<Vec<Uuid>>,
+}
+
+impl SimulationControl {
+    pub fn new() -> Self {
+        Self {
+            generation: AtomicU64::new(0),
+            agents: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Replace the running simulation; returns its generation
+    pub fn begin(&self, agents: Vec<Uuid>) -> u64 {
+        *self.agents.lock().unwrap() = agents;
+        self.generation.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Stop the running simulation, returning its agents
+    pub fn end(&self) -> Vec<Uuid> {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        std::mem::take(&mut *self.agents.lock().unwrap())
+    }
+
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::Relaxed) == generation
+    }
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64, ticks: usize) -> Vec<(String, Value)> {
+        let mut sim = Simulation::new(SimulationConfig {
+            seed,
+            ..Default::default()
+        });
+        let mut events: Vec<(String, Value)> = Vec::new();
+        for _ in 0..ticks {
+            events.extend(sim.tick().events.into_iter().map(|(e, _, p)| (e, p)));
+        }
+        events
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_events() {
+        let events = run(7, 200);
+        assert_eq!(events, run(7, 200));
+        assert_ne!(events, run(8, 200));
+        assert!(events.iter().any(|(e, _)| e == "fog-revealed"));
+        assert!(events.iter().any(|(e, p)| e == "agent-update" && p["update_type"] == "tool_call"));
+    }
+
+    #[test]
+    fn test_tool_calls_stay_under_the_root() {
+        let mut sim = Simulation::new(SimulationConfig {
+            root: "/demo/".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(sim.start().events.len(), 3);
+        for _ in 0..100 {
+            for (event, _, payload) in sim.tick().events {
+                if event == "fog-revealed" {
+                    let path = payload["path"].as_str().unwrap();
+                    assert!(path.starts_with("/demo/") && !path.contains("//"));
+                }
+            }
+        }
+    }
+}
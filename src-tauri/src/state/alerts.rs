@@ -0,0 +1,232 @@
+use crate::clock::now_secs;
+use crate::messages::LocalizedMessage;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    AgentError,
+    BudgetExceeded,
+    VerificationFailed,
+    StalePermission,
+    ProcessCrashed,
+    /// A project could use an agent
+    PlacementSuggestion,
+}
+
+impl AlertKind {
+    pub fn severity(self) -> AlertSeverity {
+        match self {
+            AlertKind::StalePermission | AlertKind::VerificationFailed | AlertKind::BudgetExceeded => {
+                AlertSeverity::Warning
+            }
+            AlertKind::AgentError | AlertKind::ProcessCrashed => AlertSeverity::Critical,
+            AlertKind::PlacementSuggestion => AlertSeverity::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Something on the factory floor that needs the user's attention, shown
+/// as an alarm badge on the entity it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: u64,
+    pub kind: AlertKind,
+    pub severity: AlertSeverity,
+    /// Agent the alert is about
+    pub agent_id: Option<Uuid>,
+    /// Other entity, e.g. a task or project id; none for factory-wide alerts
+    pub entity_id: Option<String>,
+    /// English rendering of `localized`
+    pub message: String,
+    pub localized: LocalizedMessage,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Times it was raised while still open
+    pub count: u32,
+    pub acknowledged: bool,
+}
+
+/// Open alerts of the factory. Raising an alert of the same kind for the
+/// same entity again updates the open one instead of adding another.
+pub struct AlertStore {
+    alerts: RwLock<Vec<Alert>>,
+    next_id: AtomicU64,
+    changes: broadcast::Sender<Vec<Alert>>,
+    raised: broadcast::Sender<Alert>,
+}
+
+impl AlertStore {
+    pub fn new() -> Self {
+        Self {
+            alerts: RwLock::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            changes: broadcast::channel(16).0,
+            raised: broadcast::channel(16).0,
+        }
+    }
+
+    /// Subscribe to the alert list after each change
+    pub fn subscribe(&self) -> broadcast::Receiver<Vec<Alert>> {
+        self.changes.subscribe()
+    }
+
+    /// Subscribe to each alert raised, including repeats of open ones
+    pub fn subscribe_raised(&self) -> broadcast::Receiver<Alert> {
+        self.raised.subscribe()
+    }
+
+    /// Open alerts, oldest first
+    pub fn list(&self) -> Vec<Alert> {
+        self.alerts.read().unwrap().clone()
+    }
+
+    pub fn raise(
+        &self,
+        kind: AlertKind,
+        agent_id: Option<Uuid>,
+        entity_id: Option<String>,
+        localized: LocalizedMessage,
+    ) -> Alert {
+        let now = now_secs();
+        let message = localized.render();
+        let mut alerts = self.alerts.write().unwrap();
+        let alert = match alerts
+            .iter_mut()
+            .find(|a| a.kind == kind && a.agent_id == agent_id && a.entity_id == entity_id)
+        {
+            Some(open) => {
+                open.message = message;
+                open.localized = localized;
+                open.updated_at = now;
+                open.count += 1;
+                // Happening again needs attention again
+                open.acknowledged = false;
+                open.clone()
+            }
+            None => {
+                let alert = Alert {
+                    id: self.next_id.fetch_add(1, Ordering::Relaxed),
+                    kind,
+                    severity: kind.severity(),
+                    agent_id,
+                    entity_id,
+                    message,
+                    localized,
+                    created_at: now,
+                    updated_at: now,
+                    count: 1,
+                    acknowledged: false,
+                };
+                alerts.push(alert.clone());
+                alert
+            }
+        };
+        self.publish(&alerts);
+        let _ = self.raised.send(alert.clone());
+        alert
+    }
+
+    /// Mark an alert as seen; it stays until dismissed or resolved
+    pub fn acknowledge(&self, id: u64) -> Result<Alert, String> {
+        let mut alerts = self.alerts.write().unwrap();
+        let alert = alerts
+            .iter_mut()
+            .find(|a| a.id == id)
+            .ok_or_else(|| format!("Alert not found: {}", id))?;
+        alert.acknowledged = true;
+        let alert = alert.clone();
+        self.publish(&alerts);
+        Ok(alert)
+    }
+
+    pub fn dismiss(&self, id: u64) -> Result<(), String> {
+        let mut alerts = self.alerts.write().unwrap();
+        let before = alerts.len();
+        alerts.retain(|a| a.id != id);
+        if alerts.len() == before {
+            return Err(format!("Alert not found: {}", id));
+        }
+        self.publish(&alerts);
+        Ok(())
+    }
+
+    /// Remove the alerts of a kind about an agent, e.g. once its stale
+    /// permission requests were answered
+    pub fn resolve(&self, kind: AlertKind, agent_id: Uuid) {
+        let mut alerts = self.alerts.write().unwrap();
+        let before = alerts.len();
+        alerts.retain(|a| !(a.kind == kind && a.agent_id == Some(agent_id)));
+        if alerts.len() != before {
+            self.publish(&alerts);
+        }
+    }
+
+    /// Remove the alerts of a kind about an entity, e.g. a project that no
+    /// longer needs a suggestion
+    pub fn resolve_entity(&self, kind: AlertKind, entity_id: &str) {
+        let mut alerts = self.alerts.write().unwrap();
+        let before = alerts.len();
+        alerts.retain(|a| !(a.kind == kind && a.entity_id.as_deref() == Some(entity_id)));
+        if alerts.len() != before {
+            self.publish(&alerts);
+        }
+    }
+
+    fn publish(&self, alerts: &[Alert]) {
+        // No subscribers is fine
+        let _ = self.changes.send(alerts.to_vec());
+    }
+}
+
+impl Default for AlertStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::MessageKey;
+
+    #[test]
+    fn test_raise_merges_open_alerts() {
+        let store = AlertStore::new();
+        let agent = Uuid::new_v4();
+
+        let waiting = || LocalizedMessage::new(MessageKey::StillWaiting).with("request", "Edit");
+        let first = store.raise(AlertKind::StalePermission, Some(agent), None, waiting());
+        store.acknowledge(first.id).unwrap();
+        let again = store.raise(AlertKind::StalePermission, Some(agent), None, waiting());
+        assert_eq!(again.id, first.id);
+        assert_eq!(again.count, 2);
+        assert!(!again.acknowledged);
+        assert_eq!(again.severity, AlertSeverity::Warning);
+
+        let crashed = LocalizedMessage::new(MessageKey::AgentProcessFailed);
+        store.raise(AlertKind::ProcessCrashed, Some(agent), None, crashed);
+        assert_eq!(store.list().len(), 2);
+
+        store.resolve(AlertKind::StalePermission, agent);
+        let open = store.list();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].kind, AlertKind::ProcessCrashed);
+
+        store.dismiss(open[0].id).unwrap();
+        assert!(store.list().is_empty());
+        assert!(store.dismiss(open[0].id).is_err());
+    }
+}
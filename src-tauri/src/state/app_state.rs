@@ -1,34 +1,142 @@
-use crate::agent::AgentPool;
-use crate::filesystem::{FogOfWar, ProjectScanner, ProjectTree};
+use crate::acp::RequestPolicies;
+use crate::agent::{
+    clear_tool_outputs, clear_transcripts, resolve_preamble, set_keychain_variables,
+    set_policy_scripts, set_project_roots, AgentPool, PromptJournal, SavedSession, SavedSessions,
+    TaskInfo,
+};
+use crate::automation::{ProjectActivity, TriggerHistory};
+use crate::events::{EventLog, WindowScopes};
+use crate::filesystem::{
+    instruction_paths, instructions_for_directory, instructions_preamble, set_extra_ignore_patterns,
+    AgentFileActivity, CachedTree, FileChangeCorrelator, FogOfWar, ProjectScanner, ProjectTree,
+    RevealQueue, TreeCache, WatcherManager,
+};
+use crate::git::WorktreeStore;
+use crate::hooks::HookRunner;
+use crate::recording::SessionRecorder;
+use crate::simulation::SimulationControl;
 use crate::registry::RegistryService;
+use crate::state::alerts::{AlertKind, AlertStore};
+use crate::state::conversations::ConversationStore;
+use crate::state::database::{Database, HistoryFilter};
+use crate::state::drafts::DraftStore;
 use crate::state::factory::FactoryStore;
+use crate::state::history::AuditKind;
 use crate::state::metrics::MetricsTracker;
-use std::path::PathBuf;
+use crate::state::onboarding::OnboardingStore;
+use crate::state::scratchpad::{scratchpad_preamble, scratchpad_writes, Scratchpad, ScratchpadStore};
+use crate::state::settings::SettingsStore;
+use crate::state::usage_stats::UsageStatsStore;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Prompts of an agent the history goes back
+const PROMPT_HISTORY_LIMIT: usize = 1000;
 
 pub struct AppState {
     pub agent_pool: Arc<AgentPool>,
     pub project_tree: RwLock<Option<ProjectTree>>,
     pub project_path: RwLock<Option<PathBuf>>,
     pub fog: Arc<FogOfWar>,
+    /// Reveals waiting to be sent to the frontend
+    pub fog_reveals: Arc<RevealQueue>,
+    pub file_activity: Arc<AgentFileActivity>,
+    pub file_correlation: Arc<FileChangeCorrelator>,
+    /// Watches the open project and paths added with `watch_path`
+    pub watcher: WatcherManager,
     pub metrics: Arc<MetricsTracker>,
     pub scanner: ProjectScanner,
+    pub tree_cache: TreeCache,
     pub factory: Arc<FactoryStore>,
     pub registry: Arc<RegistryService>,
+    pub settings: Arc<SettingsStore>,
+    pub worktrees: Arc<WorktreeStore>,
+    pub scratchpad: Arc<ScratchpadStore>,
+    pub drafts: Arc<DraftStore>,
+    pub events: Arc<EventLog>,
+    pub window_scopes: Arc<WindowScopes>,
+    pub recorder: Arc<SessionRecorder>,
+    pub simulation: Arc<SimulationControl>,
+    pub hooks: HookRunner,
+    pub conversations: Arc<ConversationStore>,
+    /// Shared by the conversations, task history, metrics history, audit
+    /// log and activity feed
+    pub database: Arc<Database>,
+    pub trigger_history: Arc<TriggerHistory>,
+    pub alerts: Arc<AlertStore>,
+    pub project_activity: Arc<ProjectActivity>,
+    /// Instruction files found by the last scan, per project root
+    pub instruction_files: RwLock<HashMap<String, Vec<String>>>,
+    pub onboarding: Arc<OnboardingStore>,
+    /// Prompts in flight, and those a crash of the last run cut off
+    pub journal: Arc<PromptJournal>,
+    /// Local-only counts of features used, spawns and errors
+    pub usage: Arc<UsageStatsStore>,
+    /// Agents saved when the app quit, to resume on the next launch
+    pub saved_sessions: Arc<SavedSessions>,
 }
 
 impl AppState {
     pub fn new() -> Self {
+        let settings = Arc::new(SettingsStore::new());
+        let agent_pool = Arc::new(AgentPool::new());
+        agent_pool.set_git_checkpoints(settings.get().git_checkpoints);
+        agent_pool.set_request_policies(RequestPolicies::new(settings.get().request_policies));
+        agent_pool.set_sandbox(settings.get().sandbox);
+        agent_pool.set_resource_limits(settings.get().resource_limits);
+        agent_pool.set_package_runner(settings.get().package_runner);
+        agent_pool.set_hold_conflicting_edits(settings.get().hold_conflicting_edits);
+        agent_pool.set_scheduling_policy(settings.get().scheduling);
+        agent_pool.set_escalation_policy(settings.get().permission_escalation);
+        clear_tool_outputs();
+        clear_transcripts();
+        let journal = Arc::new(PromptJournal::new());
+        agent_pool.set_journal(journal.clone());
+        set_extra_ignore_patterns(&settings.get().ignore_patterns);
+        set_keychain_variables(&settings.get().keychain_variables);
+        set_policy_scripts(&settings.get().policy_scripts);
+        let fog = Arc::new(FogOfWar::new());
+        fog.set_scan_radius(settings.get().fog_scan_radius);
+        let file_correlation = Arc::new(FileChangeCorrelator::new());
+        let watcher = WatcherManager::new(file_correlation.clone(), fog.clone());
+        let database = Arc::new(Database::new());
+
         Self {
-            agent_pool: Arc::new(AgentPool::new()),
+            agent_pool,
             project_tree: RwLock::new(None),
             project_path: RwLock::new(None),
-            fog: Arc::new(FogOfWar::new()),
-            metrics: Arc::new(MetricsTracker::new()),
+            fog,
+            fog_reveals: Arc::new(RevealQueue::new()),
+            file_activity: Arc::new(AgentFileActivity::new()),
+            file_correlation,
+            watcher,
+            metrics: Arc::new(MetricsTracker::with_history(database.clone())),
             scanner: ProjectScanner::new(),
+            tree_cache: TreeCache::new(),
             factory: Arc::new(FactoryStore::new()),
             registry: Arc::new(RegistryService::new()),
+            settings,
+            worktrees: Arc::new(WorktreeStore::new()),
+            scratchpad: Arc::new(ScratchpadStore::new()),
+            drafts: Arc::new(DraftStore::new()),
+            events: Arc::new(EventLog::new()),
+            window_scopes: Arc::new(WindowScopes::new()),
+            recorder: Arc::new(SessionRecorder::new()),
+            simulation: Arc::new(SimulationControl::new()),
+            hooks: HookRunner::new(),
+            conversations: Arc::new(ConversationStore::new(database.clone())),
+            database,
+            trigger_history: Arc::new(TriggerHistory::new()),
+            alerts: Arc::new(AlertStore::new()),
+            project_activity: Arc::new(ProjectActivity::new()),
+            instruction_files: RwLock::new(HashMap::new()),
+            onboarding: Arc::new(OnboardingStore::new()),
+            journal,
+            usage: Arc::new(UsageStatsStore::new()),
+            saved_sessions: Arc::new(SavedSessions::new()),
         }
     }
 
@@ -38,23 +146,201 @@ impl AppState {
             .scan(&path)
             .map_err(|e| e.to_string())?;
 
+        self.set_project(path, tree.clone()).await;
+        if let Err(e) = self.tree_cache.save(&tree) {
+            tracing::warn!("{}", e);
+        }
+        Ok(tree)
+    }
+
+    /// Open a project from the tree cached by its last scan, if any
+    pub async fn load_cached_project(&self, path: PathBuf) -> Option<CachedTree> {
+        let cached = self.tree_cache.load(&path)?;
+        self.set_project(path, cached.tree.clone()).await;
+        Some(cached)
+    }
+
+    async fn set_project(&self, path: PathBuf, tree: ProjectTree) {
+        let rescan = self.project_path.read().await.as_deref() == Some(path.as_path());
+        if rescan {
+            // Keep the fog, minus paths that no longer exist
+            self.fog.set_tree(&tree);
+            self.fog.prune_to_tree(&tree.root);
+        } else {
+            // Reset fog when loading new project
+            self.fog.reset();
+            self.fog.set_tree(&tree);
+        }
+        self.instruction_files
+            .write()
+            .await
+            .insert(tree.root.clone(), instruction_paths(&tree));
         *self.project_path.write().await = Some(path);
-        *self.project_tree.write().await = Some(tree.clone());
+        *self.project_tree.write().await = Some(tree);
+        self.sync_project_roots().await;
+    }
 
-        // Reset fog when loading new project
-        self.fog.reset();
+    /// Add to the audit log under the agent's project; failures are only logged
+    pub fn audit(&self, kind: AuditKind, agent_id: Option<Uuid>, message: &str) {
+        let project = agent_id
+            .and_then(|id| self.agent_pool.get_agent_info(&id))
+            .map(|info| info.working_directory);
+        if let Err(e) = self.database.audit(kind, agent_id, project.as_deref(), message) {
+            tracing::warn!("{}", e);
+        }
+    }
 
-        Ok(tree)
+    /// Clear an agent's stale permission alert once none of its permission
+    /// requests waits anymore
+    pub fn resolve_stale_permissions(&self, agent_id: Uuid) {
+        let pending = self.agent_pool.get_pending_permissions().list();
+        if !pending.iter().any(|p| p.agent_id == agent_id) {
+            self.alerts.resolve(AlertKind::StalePermission, agent_id);
+        }
+    }
+
+    /// Standing instructions configured for an agent and its project, after
+    /// the project's instruction files if the agent's CLI doesn't read them
+    pub async fn prompt_preamble(&self, agent_id: &Uuid) -> Option<String> {
+        let info = self.agent_pool.get_agent_info(agent_id)?;
+        let settings = self.settings.get();
+        let mut parts = Vec::new();
+
+        let project = self
+            .factory
+            .get_layout()
+            .await
+            .project_containing(&info.working_directory)
+            .cloned();
+
+        let provider = info.provider_id.as_deref().unwrap_or("default");
+        if settings.instruction_file_providers.iter().any(|p| p == provider) {
+            let directory = PathBuf::from(&info.working_directory);
+            let root = project
+                .as_ref()
+                .map(|p| PathBuf::from(&p.path))
+                .unwrap_or_else(|| directory.clone());
+            parts.extend(instructions_preamble(&instructions_for_directory(&directory, &root)));
+        }
+        if let Some(project) = &project {
+            let pad = self.scratchpad.get(&project.id).await;
+            parts.extend(scratchpad_preamble(&pad, settings.scratchpad_writes));
+        }
+        parts.extend(resolve_preamble(
+            &settings.prompt_preambles,
+            &info.name,
+            &info.working_directory,
+        ));
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+
+    /// Apply the scratchpad writes in an agent's reply to the scratchpad of
+    /// the agent's project if the user allows it; the project id and its
+    /// notes if any were made
+    pub async fn apply_scratchpad_writes(
+        &self,
+        agent_id: &Uuid,
+        reply: &str,
+    ) -> Option<(String, Scratchpad)> {
+        if !self.settings.get().scratchpad_writes {
+            return None;
+        }
+        let writes = scratchpad_writes(reply);
+        if writes.is_empty() {
+            return None;
+        }
+        let info = self.agent_pool.get_agent_info(agent_id)?;
+        let project = self
+            .factory
+            .get_layout()
+            .await
+            .project_containing(&info.working_directory)?
+            .id
+            .clone();
+        let mut pad = None;
+        for (key, value) in writes {
+            match self
+                .scratchpad
+                .set_entry(&project, &key, value, Some(agent_id.to_string()))
+                .await
+            {
+                Ok(updated) => pad = Some(updated),
+                Err(e) => tracing::warn!("{}", e),
+            }
+        }
+        pad.map(|pad| (project, pad))
+    }
+
+    /// Register the open project and the factory's projects as the places
+    /// agents may work in without a high risk warning
+    pub async fn sync_project_roots(&self) {
+        let mut roots: Vec<PathBuf> = self.project_path.read().await.iter().cloned().collect();
+        roots.extend(self.factory.get_layout().await.projects.iter().map(|p| PathBuf::from(&p.path)));
+        set_project_roots(&roots);
     }
 
     pub async fn get_project_tree(&self) -> Option<ProjectTree> {
         self.project_tree.read().await.clone()
     }
 
+    /// Trees of these project roots, each once: the open project's as
+    /// scanned, the others as last cached. Roots without either are left out.
+    pub async fn project_trees(&self, roots: &[String]) -> Vec<ProjectTree> {
+        let open = self.get_project_tree().await;
+        let mut trees: Vec<ProjectTree> = Vec::new();
+        for root in roots {
+            let root = Path::new(root);
+            if trees.iter().any(|t| Path::new(&t.root) == root) {
+                continue;
+            }
+            let tree = match &open {
+                Some(tree) if Path::new(&tree.root) == root => Some(tree.clone()),
+                _ => self.tree_cache.load(root).map(|cached| cached.tree),
+            };
+            trees.extend(tree);
+        }
+        trees
+    }
+
     pub async fn get_project_path(&self) -> Option<PathBuf> {
         self.project_path.read().await.clone()
     }
 
+    /// Prompts sent to an agent, oldest first: the saved tasks, updated by
+    /// those still in the task graph
+    pub fn prompt_history(&self, agent_id: &Uuid) -> Vec<TaskInfo> {
+        let filter = HistoryFilter {
+            agent_id: Some(*agent_id),
+            limit: Some(PROMPT_HISTORY_LIMIT),
+            ..Default::default()
+        };
+        let mut tasks: HashMap<String, TaskInfo> = match self.database.task_history(&filter) {
+            Ok(saved) => saved.into_iter().map(|t| (t.id.clone(), t)).collect(),
+            Err(e) => {
+                tracing::warn!("{}", e);
+                HashMap::new()
+            }
+        };
+        for task in self.agent_pool.task_graph().tasks_for_agent(agent_id) {
+            tasks.insert(task.id.clone(), task);
+        }
+        let mut history: Vec<TaskInfo> = tasks.into_values().collect();
+        history.sort_by_key(|t| (t.created_at, t.seq));
+        history
+    }
+
+    /// Save the agents with a session for resuming them on the next launch
+    pub fn save_sessions(&self) {
+        let sessions = self
+            .agent_pool
+            .list_agents()
+            .iter()
+            .filter(|info| info.session_id.is_some())
+            .map(|info| SavedSession::new(info, &self.prompt_history(&info.id)))
+            .collect();
+        self.saved_sessions.save(sessions);
+    }
+
     pub fn reveal_file(&self, path: &str) {
         self.fog.reveal(path);
     }
@@ -0,0 +1,177 @@
+//! What happened while the user was away: finished tasks, permissions still
+//! waiting, usage, and the files that changed the most.
+
+use super::Usage;
+use crate::agent::{PendingPermissionInfo, TaskInfo, TaskStatus};
+use crate::events::RecordedEvent;
+use crate::filesystem::FileEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Files listed in a summary, most changed first
+const MAX_FILE_CHANGES: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AwayTask {
+    pub id: String,
+    pub agent_id: Uuid,
+    pub prompt: String,
+    pub finished_at: u64,
+    pub error: Option<String>,
+    /// Files the task changed in the project repo
+    pub files_changed: usize,
+}
+
+impl AwayTask {
+    fn from_task(task: &TaskInfo) -> Self {
+        Self {
+            id: task.id.clone(),
+            agent_id: task.agent_id,
+            prompt: task.prompt.clone(),
+            finished_at: task.finished_at.unwrap_or_default(),
+            error: task.error.clone(),
+            files_changed: task.change_summary.as_ref().map_or(0, |s| s.files.len()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    /// Used since the start of the summary
+    pub used: Usage,
+    /// Used since the app started
+    pub total_tokens: u64,
+    pub budget: Option<u64>,
+    pub remaining: Option<u64>,
+}
+
+impl BudgetUsage {
+    pub fn new(used: Usage, total_tokens: u64, budget: Option<u64>) -> Self {
+        Self {
+            used,
+            total_tokens,
+            budget,
+            remaining: budget.map(|b| b.saturating_sub(total_tokens)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChangeTally {
+    pub path: String,
+    pub changes: u32,
+    /// Agents the changes were attributed to
+    pub agent_ids: Vec<Uuid>,
+    /// In seconds
+    pub last_changed_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AwaySummary {
+    /// Start of the summary, in seconds
+    pub since: u64,
+    pub completed_tasks: Vec<AwayTask>,
+    pub failed_tasks: Vec<AwayTask>,
+    /// Waiting now, including requests from before `since`
+    pub waiting_permissions: Vec<PendingPermissionInfo>,
+    pub budget: BudgetUsage,
+    pub file_changes: Vec<FileChangeTally>,
+    /// False if the event log already dropped changes made after `since`
+    pub file_changes_complete: bool,
+}
+
+/// Tasks that completed and that failed at or after `since`, oldest first
+pub fn finished_tasks(tasks: &[TaskInfo], since: u64) -> (Vec<AwayTask>, Vec<AwayTask>) {
+    let mut finished: Vec<&TaskInfo> = tasks
+        .iter()
+        .filter(|t| t.finished_at.is_some_and(|at| at >= since))
+        .collect();
+    finished.sort_by_key(|t| t.finished_at);
+
+    let pick = |status: TaskStatus| {
+        finished
+            .iter()
+            .filter(|t| t.status == status)
+            .map(|t| AwayTask::from_task(t))
+            .collect()
+    };
+    (pick(TaskStatus::Completed), pick(TaskStatus::Failed))
+}
+
+/// Files changed at or after `since` by the `fs-change` events in `events`,
+/// and whether those are all the changes since then
+pub fn tally_file_changes(events: &[RecordedEvent], since: u64) -> (Vec<FileChangeTally>, bool) {
+    let since_ms = since.saturating_mul(1000);
+    let complete = events
+        .first()
+        .is_none_or(|first| first.seq == 1 || first.timestamp <= since_ms);
+
+    let mut tallies: HashMap<String, FileChangeTally> = HashMap::new();
+    for event in events
+        .iter()
+        .filter(|e| e.event == "fs-change" && e.timestamp >= since_ms)
+    {
+        let Ok(change) = serde_json::from_value::<FileEvent>(event.payload.clone()) else {
+            continue;
+        };
+        for path in &change.paths {
+            let agent_id = change
+                .attributions
+                .iter()
+                .find(|a| a.path == *path)
+                .map(|a| a.agent_id)
+                .or(change.agent_id);
+            let tally = tallies
+                .entry(path.clone())
+                .or_insert_with(|| FileChangeTally {
+                    path: path.clone(),
+                    changes: 0,
+                    agent_ids: Vec::new(),
+                    last_changed_at: 0,
+                });
+            tally.changes += 1;
+            tally.last_changed_at = event.timestamp / 1000;
+            if let Some(agent_id) = agent_id.filter(|id| !tally.agent_ids.contains(id)) {
+                tally.agent_ids.push(agent_id);
+            }
+        }
+    }
+
+    let mut tallies: Vec<FileChangeTally> = tallies.into_values().collect();
+    tallies.sort_by(|a, b| b.changes.cmp(&a.changes).then_with(|| a.path.cmp(&b.path)));
+    tallies.truncate(MAX_FILE_CHANGES);
+    (tallies, complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventLog;
+    use serde_json::json;
+
+    #[test]
+    fn test_tally_counts_changes_per_file() {
+        let agent = Uuid::new_v4();
+        let log = EventLog::new();
+        log.record("agent-status", json!({ "status": "working" }));
+        for paths in [vec!["src/a.rs", "src/b.rs"], vec!["src/a.rs"]] {
+            log.record(
+                "fs-change",
+                json!({ "kind": "modify", "paths": paths, "agent_id": agent, "attributions": [] }),
+            );
+        }
+
+        let (tallies, complete) = tally_file_changes(&log.since(0), 0);
+        assert!(complete);
+        assert_eq!(tallies.len(), 2);
+        assert_eq!(
+            (tallies[0].path.as_str(), tallies[0].changes),
+            ("src/a.rs", 2)
+        );
+        assert_eq!(tallies[0].agent_ids, vec![agent]);
+
+        let (tallies, _) = tally_file_changes(&log.since(0), u64::MAX / 1000);
+        assert!(tallies.is_empty());
+    }
+}
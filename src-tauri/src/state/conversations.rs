@@ -0,0 +1,308 @@
+use super::database::Database;
+use super::storage::app_data_dir;
+use crate::agent::{CompactionRecord, ImportSource, ImportedConversation};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where earlier versions kept one JSON file per conversation
+const CONVERSATIONS_DIR: &str = "conversations";
+
+/// An imported conversation without its messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub source: ImportSource,
+    pub title: String,
+    pub session_id: Option<String>,
+    pub cwd: Option<String>,
+    pub message_count: usize,
+    pub imported_at: u64,
+}
+
+impl From<&ImportedConversation> for ConversationSummary {
+    fn from(conversation: &ImportedConversation) -> Self {
+        Self {
+            id: conversation.id.clone(),
+            source: conversation.source,
+            title: conversation.title.clone(),
+            session_id: conversation.session_id.clone(),
+            cwd: conversation.cwd.clone(),
+            message_count: conversation.messages.len(),
+            imported_at: conversation.imported_at,
+        }
+    }
+}
+
+/// Imported conversations, kept in the database
+pub struct ConversationStore {
+    db: Arc<Database>,
+}
+
+impl ConversationStore {
+    pub fn new(db: Arc<Database>) -> Self {
+        let store = Self { db };
+        // A database kept in memory would lose the imported files at exit
+        if store.db.is_persistent() {
+            store.import_files(&app_data_dir().join(CONVERSATIONS_DIR));
+        }
+        store
+    }
+
+    /// Move conversations saved as JSON files by earlier versions into the
+    /// database; the files are removed only once all of them are committed
+    fn import_files(&self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let files: Vec<(PathBuf, ImportedConversation)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter_map(|path| {
+                let content = fs::read_to_string(&path).ok()?;
+                let conversation = serde_json::from_str(&content).ok()?;
+                Some((path, conversation))
+            })
+            .collect();
+        if let Err(e) = self.save_all(files.iter().map(|(_, conversation)| conversation)) {
+            tracing::warn!("Failed to import conversations from {}: {}", dir.display(), e);
+            return;
+        }
+        for (path, _) in &files {
+            fs::remove_file(path).ok();
+        }
+        // Only removed once empty, so nothing unreadable is lost
+        fs::remove_dir(dir).ok();
+    }
+
+    /// Save in one transaction and checkpoint it into the database file, so
+    /// the commit survives a power loss
+    fn save_all<'a>(
+        &self,
+        conversations: impl Iterator<Item = &'a ImportedConversation>,
+    ) -> Result<(), String> {
+        let mut conn = self.db.conn();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for conversation in conversations {
+            insert(&tx, conversation)?;
+        }
+        tx.commit().map_err(|e| format!("Failed to save conversations: {}", e))?;
+        conn.query_row("PRAGMA wal_checkpoint(FULL)", [], |_| Ok(()))
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, conversation: &ImportedConversation) -> Result<(), String> {
+        insert(&self.db.conn(), conversation)
+    }
+
+    pub fn get(&self, id: &str) -> Result<ImportedConversation, String> {
+        let data: String = self
+            .db
+            .conn()
+            .query_row("SELECT data FROM conversations WHERE id = ?1", [id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Conversation not found: {}", id))?;
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse conversation: {}", e))
+    }
+
+    /// All imported conversations, newest first
+    pub fn list(&self) -> Vec<ConversationSummary> {
+        let conn = self.db.conn();
+        let listed = conn
+            .prepare(
+                "SELECT id, source, title, session_id, cwd, message_count, imported_at
+                 FROM conversations ORDER BY imported_at DESC",
+            )
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| {
+                        // Sources a later version added are skipped
+                        let source: String = row.get(1)?;
+                        let Ok(source) = serde_json::from_value(Value::String(source)) else {
+                            return Ok(None);
+                        };
+                        Ok(Some(ConversationSummary {
+                            id: row.get(0)?,
+                            source,
+                            title: row.get(2)?,
+                            session_id: row.get(3)?,
+                            cwd: row.get(4)?,
+                            message_count: row.get::<_, i64>(5)? as usize,
+                            imported_at: row.get::<_, i64>(6)? as u64,
+                        }))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            });
+        match listed {
+            Ok(summaries) => summaries.into_iter().flatten().collect(),
+            Err(e) => {
+                tracing::warn!("Failed to list conversations: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let removed = self
+            .db
+            .conn()
+            .execute("DELETE FROM conversations WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to remove conversation: {}", e))?;
+        if removed == 0 {
+            return Err(format!("Conversation not found: {}", id));
+        }
+        Ok(())
+    }
+
+    /// Remember which session a compaction replaced, across restarts
+    pub fn save_compaction(&self, record: &CompactionRecord) -> Result<(), String> {
+        let data = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        let method = serde_json::to_value(record.method).map_err(|e| e.to_string())?;
+        self.db
+            .conn()
+            .execute(
+                "INSERT INTO compactions
+                    (agent_id, method, previous_session_id, session_id, created_at, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    record.agent_id.to_string(),
+                    method.as_str(),
+                    record.previous_session_id,
+                    record.session_id,
+                    record.created_at as i64,
+                    data,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to save compaction: {}", e))
+    }
+
+    /// Compactions that led to `session_id`, following each one back to the
+    /// session it replaced; oldest first
+    pub fn compactions_leading_to(&self, session_id: &str) -> Vec<CompactionRecord> {
+        let conn = self.db.conn();
+        let mut records: Vec<CompactionRecord> = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next = vec![session_id.to_string()];
+        while let Some(session_id) = next.pop() {
+            if !visited.insert(session_id.clone()) {
+                continue;
+            }
+            let found = conn
+                .prepare("SELECT data FROM compactions WHERE session_id = ?1")
+                .and_then(|mut stmt| {
+                    stmt.query_map([&session_id], |row| row.get::<_, String>(0))?
+                        .collect::<Result<Vec<_>, _>>()
+                });
+            let found = match found {
+                Ok(found) => found,
+                Err(e) => {
+                    tracing::warn!("Failed to read compactions: {}", e);
+                    break;
+                }
+            };
+            for data in found {
+                let Ok(record) = serde_json::from_str::<CompactionRecord>(&data) else {
+                    continue;
+                };
+                next.extend(record.previous_session_id.clone());
+                records.push(record);
+            }
+        }
+        records.sort_by_key(|r| r.created_at);
+        records
+    }
+}
+
+fn insert(conn: &Connection, conversation: &ImportedConversation) -> Result<(), String> {
+    let data = serde_json::to_string(conversation)
+        .map_err(|e| format!("Failed to serialize conversation: {}", e))?;
+    let source = serde_json::to_value(conversation.source).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO conversations
+            (id, source, title, session_id, cwd, message_count, imported_at, data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            conversation.id,
+            source.as_str(),
+            conversation.title,
+            conversation.session_id,
+            conversation.cwd,
+            conversation.messages.len() as i64,
+            conversation.imported_at as i64,
+            data,
+        ],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Failed to save conversation: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::CompactionMethod;
+
+    #[test]
+    fn test_conversation_files_are_moved_into_the_database() {
+        let dir = std::env::temp_dir().join(format!("acptorio-conv-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let conversation = |id: &str, imported_at: u64| ImportedConversation {
+            id: id.to_string(),
+            source: ImportSource::ClaudeCode,
+            source_path: "/home/me/.claude/projects/app/s1.jsonl".to_string(),
+            session_id: Some("s1".to_string()),
+            cwd: Some("/work/app".to_string()),
+            title: "Fix the build".to_string(),
+            messages: Vec::new(),
+            imported_at,
+        };
+        let old = conversation("old", 10);
+        fs::write(dir.join("old.json"), serde_json::to_string(&old).unwrap()).unwrap();
+
+        let store = ConversationStore {
+            db: Arc::new(Database::in_memory()),
+        };
+        store.import_files(&dir);
+        assert!(!dir.exists());
+        store.save(&conversation("new", 20)).unwrap();
+
+        let listed: Vec<String> = store.list().into_iter().map(|c| c.id).collect();
+        assert_eq!(listed, vec!["new".to_string(), "old".to_string()]);
+        assert_eq!(store.get("old").unwrap().session_id.as_deref(), Some("s1"));
+        store.remove("old").unwrap();
+        assert!(store.get("old").is_err());
+        assert!(store.remove("old").is_err());
+    }
+
+    #[test]
+    fn test_compactions_are_followed_back_to_the_first_session() {
+        let store = ConversationStore {
+            db: Arc::new(Database::in_memory()),
+        };
+        let compaction = |method, previous: &str, session: &str, created_at| CompactionRecord {
+            agent_id: uuid::Uuid::new_v4(),
+            method,
+            previous_session_id: Some(previous.to_string()),
+            session_id: Some(session.to_string()),
+            summary: None,
+            created_at,
+        };
+        store.save_compaction(&compaction(CompactionMethod::NewSession, "s1", "s2", 10)).unwrap();
+        store.save_compaction(&compaction(CompactionMethod::AgentCommand, "s2", "s2", 20)).unwrap();
+        store.save_compaction(&compaction(CompactionMethod::NewSession, "s2", "s3", 30)).unwrap();
+        store.save_compaction(&compaction(CompactionMethod::NewSession, "x1", "x2", 15)).unwrap();
+
+        let history: Vec<u64> = store
+            .compactions_leading_to("s3")
+            .iter()
+            .map(|r| r.created_at)
+            .collect();
+        assert_eq!(history, vec![10, 20, 30]);
+        assert!(store.compactions_leading_to("s1").is_empty());
+    }
+}
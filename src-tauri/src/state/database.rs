@@ -0,0 +1,372 @@
+//! Embedded SQLite database shared by the stores whose history outgrew one
+//! JSON file each: imported conversations, finished tasks, usage and work
+//! time, the audit log and the activity feed. It runs in WAL mode so a crash
+//! mid-write loses at most that write, and its schema is versioned by
+//! `PRAGMA user_version` with the migrations below.
+
+use super::storage::{app_data_dir, storage_writable};
+use crate::clock::now_secs;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use uuid::Uuid;
+
+const DATABASE_FILE: &str = "acptorio.db";
+/// How long a write waits for another connection before it fails
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Activity and usage samples older than this are dropped on open
+const RETENTION_SECS: u64 = 90 * 24 * 60 * 60;
+/// Rows a history query returns without a limit
+const DEFAULT_LIMIT: usize = 200;
+
+/// Schema changes in order; the database's user_version counts those applied
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE conversations (
+        id TEXT PRIMARY KEY,
+        source TEXT NOT NULL,
+        title TEXT NOT NULL,
+        session_id TEXT,
+        cwd TEXT,
+        message_count INTEGER NOT NULL,
+        imported_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE tasks (
+        id TEXT PRIMARY KEY,
+        agent_id TEXT NOT NULL,
+        project TEXT,
+        status TEXT NOT NULL,
+        prompt TEXT NOT NULL,
+        result TEXT,
+        created_at INTEGER NOT NULL,
+        finished_at INTEGER,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX tasks_created_at ON tasks (created_at);
+    CREATE TABLE usage_samples (
+        at INTEGER NOT NULL,
+        tokens INTEGER NOT NULL,
+        cost_cents INTEGER NOT NULL
+    );
+    CREATE INDEX usage_samples_at ON usage_samples (at);
+    CREATE TABLE work_spans (
+        agent_id TEXT NOT NULL,
+        agent_name TEXT NOT NULL,
+        project TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER NOT NULL
+    );
+    CREATE INDEX work_spans_started_at ON work_spans (started_at);
+    CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        agent_id TEXT,
+        project TEXT,
+        message TEXT NOT NULL
+    );
+    CREATE INDEX audit_log_at ON audit_log (at);
+    CREATE TABLE activity (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        agent_id TEXT NOT NULL,
+        project TEXT,
+        kind TEXT NOT NULL,
+        message TEXT,
+        path TEXT
+    );
+    CREATE INDEX activity_at ON activity (at);",
+    "CREATE TABLE compactions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        agent_id TEXT NOT NULL,
+        method TEXT NOT NULL,
+        previous_session_id TEXT,
+        session_id TEXT,
+        created_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX compactions_session_id ON compactions (session_id);",
+];
+
+/// Which rows a history query returns; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+    #[serde(default)]
+    pub agent_id: Option<Uuid>,
+    /// Working directory of the agent
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Task status, audit kind or activity type
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// Seconds, inclusive
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Seconds, exclusive
+    #[serde(default)]
+    pub until: Option<u64>,
+    /// Substring of the prompt, message or path, ignoring ASCII case
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: Option<usize>,
+}
+
+/// Columns of a table the filter's fields apply to
+pub(crate) struct FilterColumns<'a> {
+    pub at: &'a str,
+    pub agent_id: Option<&'a str>,
+    pub project: Option<&'a str>,
+    pub kind: Option<&'a str>,
+    pub text: &'a [&'a str],
+}
+
+impl HistoryFilter {
+    /// `WHERE ... ORDER BY ... LIMIT ...` for a query on a table with
+    /// `columns`, newest first, with its parameters
+    pub(crate) fn to_sql(&self, columns: &FilterColumns<'_>) -> (String, Vec<SqlValue>) {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+        let mut push = |clause: String, value: SqlValue| {
+            params.push(value);
+            clauses.push(clause.replace('?', &format!("?{}", params.len())));
+        };
+        if let (Some(column), Some(agent_id)) = (columns.agent_id, self.agent_id) {
+            push(format!("{} = ?", column), SqlValue::Text(agent_id.to_string()));
+        }
+        if let (Some(column), Some(project)) = (columns.project, &self.project) {
+            push(format!("{} = ?", column), SqlValue::Text(project.clone()));
+        }
+        if let (Some(column), Some(kind)) = (columns.kind, &self.kind) {
+            push(format!("{} = ?", column), SqlValue::Text(kind.clone()));
+        }
+        if let Some(since) = self.since {
+            push(format!("{} >= ?", columns.at), SqlValue::Integer(since as i64));
+        }
+        if let Some(until) = self.until {
+            push(format!("{} < ?", columns.at), SqlValue::Integer(until as i64));
+        }
+        if let Some(text) = self.text.as_ref().filter(|t| !t.is_empty()) {
+            if !columns.text.is_empty() {
+                let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+                let matches: Vec<String> = columns
+                    .text
+                    .iter()
+                    .map(|c| format!("{} LIKE ? ESCAPE '\\'", c))
+                    .collect();
+                let pattern = SqlValue::Text(format!("%{}%", escaped));
+                push(format!("({})", matches.join(" OR ")), pattern);
+            }
+        }
+
+        let mut sql = String::new();
+        if !clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&clauses.join(" AND "));
+        }
+        sql.push_str(&format!(
+            " ORDER BY {} DESC LIMIT {} OFFSET {}",
+            columns.at,
+            self.limit.unwrap_or(DEFAULT_LIMIT),
+            self.offset.unwrap_or(0)
+        ));
+        (sql, params)
+    }
+}
+
+pub struct Database {
+    conn: Mutex<Connection>,
+    /// False when the database only lives in memory for the session
+    persistent: bool,
+}
+
+impl Database {
+    /// The app's database, in memory if the data directory can't be written
+    pub fn new() -> Self {
+        if storage_writable() {
+            match Self::open(&app_data_dir().join(DATABASE_FILE)) {
+                Ok(db) => return db,
+                Err(e) => {
+                    tracing::warn!("Failed to open the database, keeping history in memory: {}", e)
+                }
+            }
+        }
+        Self::in_memory()
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mode: String = conn
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            tracing::warn!("{} runs in {} mode instead of WAL", path.display(), mode);
+        }
+        // Safe with WAL: a crash loses at most the last commits, never the file
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|e| e.to_string())?;
+        Self::setup(conn, true)
+    }
+
+    pub fn in_memory() -> Self {
+        let conn = Connection::open_in_memory().expect("SQLite opens in-memory databases");
+        Self::setup(conn, false).expect("Migrations apply to an empty database")
+    }
+
+    fn setup(mut conn: Connection, persistent: bool) -> Result<Self, String> {
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+        migrate(&mut conn)?;
+        let db = Self {
+            conn: Mutex::new(conn),
+            persistent,
+        };
+        db.prune(now_secs().saturating_sub(RETENTION_SECS));
+        Ok(db)
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    pub(crate) fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    /// Checkpoint the WAL into the database file and close it so the file can
+    /// be copied; writes go to memory until [`Database::reopen`]
+    pub fn close(&self) -> Result<(), String> {
+        let mut conn = self.conn();
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| format!("Failed to checkpoint the database: {}", e))?;
+        let memory = Self::in_memory().conn.into_inner().unwrap();
+        std::mem::replace(&mut *conn, memory)
+            .close()
+            .map_err(|(_, e)| format!("Failed to close the database: {}", e))
+    }
+
+    /// Open the app's database file again after [`Database::close`]
+    pub fn reopen(&self) -> Result<(), String> {
+        let db = Self::open(&app_data_dir().join(DATABASE_FILE))?;
+        *self.conn() = db.conn.into_inner().unwrap();
+        Ok(())
+    }
+
+    /// Drop activity and usage samples from before `before`, in seconds
+    fn prune(&self, before: u64) {
+        let pruned = self.conn().execute_batch(&format!(
+            "DELETE FROM activity WHERE at < {0}; DELETE FROM usage_samples WHERE at < {0};",
+            before
+        ));
+        if let Err(e) = pruned {
+            tracing::warn!("Failed to prune old history: {}", e);
+        }
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply the migrations the database hasn't seen yet, each in a transaction
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let applied: usize = conn
+        .pragma_query_value(None, "user_version", |row| row.get::<_, i64>(0))
+        .map_err(|e| e.to_string())? as usize;
+    if applied > MIGRATIONS.len() {
+        return Err(format!(
+            "The database is at schema version {}, newer than this app knows ({})",
+            applied,
+            MIGRATIONS.len()
+        ));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute_batch(migration)
+            .and_then(|_| tx.pragma_update(None, "user_version", (index + 1) as i64))
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Migration {} failed: {}", index + 1, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_migrates_once_and_runs_in_wal_mode() {
+        let dir = std::env::temp_dir().join(format!("acptorio-db-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DATABASE_FILE);
+
+        let db = Database::open(&path).unwrap();
+        assert!(db.is_persistent());
+        let version: i64 = db
+            .conn()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version as usize, MIGRATIONS.len());
+        let mode: String = db
+            .conn()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        let insert = "INSERT INTO usage_samples (at, tokens, cost_cents) VALUES (?1, 10, 1)";
+        db.conn().execute(insert, [now_secs() as i64]).unwrap();
+        drop(db);
+
+        // Reopening keeps the rows and applies nothing twice
+        let db = Database::open(&path).unwrap();
+        let count: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM usage_samples", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        drop(db);
+
+        let conn = Connection::open(&path).unwrap();
+        let newer = (MIGRATIONS.len() + 1) as i64;
+        conn.pragma_update(None, "user_version", newer).unwrap();
+        drop(conn);
+        assert!(Database::open(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_history_filter_builds_numbered_clauses() {
+        let columns = FilterColumns {
+            at: "at",
+            agent_id: Some("agent_id"),
+            project: None,
+            kind: Some("kind"),
+            text: &["message", "path"],
+        };
+        let filter = HistoryFilter {
+            project: Some("/work/app".to_string()),
+            kind: Some("tool_call".to_string()),
+            since: Some(10),
+            text: Some("50%".to_string()),
+            limit: Some(5),
+            ..Default::default()
+        };
+        let (sql, params) = filter.to_sql(&columns);
+        assert_eq!(
+            sql,
+            concat!(
+                " WHERE kind = ?1 AND at >= ?2",
+                " AND (message LIKE ?3 ESCAPE '\\' OR path LIKE ?3 ESCAPE '\\')",
+                " ORDER BY at DESC LIMIT 5 OFFSET 0"
+            )
+        );
+        assert_eq!(params.len(), 3);
+        assert_eq!(params[2], SqlValue::Text("%50\\%%".to_string()));
+    }
+}
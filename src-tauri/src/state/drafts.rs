@@ -0,0 +1,93 @@
+use super::storage::app_data_dir;
+use crate::clock::now_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+
+const DRAFTS_FILE: &str = "prompt_drafts.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptDraft {
+    pub text: String,
+    pub updated_at: u64,
+}
+
+/// Unsent prompts per agent, kept across app restarts
+pub struct DraftStore {
+    /// agent id -> draft
+    drafts: RwLock<HashMap<String, PromptDraft>>,
+    storage_path: PathBuf,
+}
+
+impl DraftStore {
+    pub fn new() -> Self {
+        let storage_path = Self::get_storage_path();
+        let drafts = Self::load_from_file(&storage_path).unwrap_or_default();
+
+        Self {
+            drafts: RwLock::new(drafts),
+            storage_path,
+        }
+    }
+
+    fn get_storage_path() -> PathBuf {
+        let app_dir = app_data_dir();
+        fs::create_dir_all(&app_dir).ok();
+
+        app_dir.join(DRAFTS_FILE)
+    }
+
+    fn load_from_file(path: &Path) -> Option<HashMap<String, PromptDraft>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save_to_file(&self, drafts: &HashMap<String, PromptDraft>) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(drafts)
+            .map_err(|e| format!("Failed to serialize prompt drafts: {}", e))?;
+
+        fs::write(&self.storage_path, content)
+            .map_err(|e| format!("Failed to write prompt drafts file: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn get(&self, agent_id: &str) -> Option<PromptDraft> {
+        self.drafts.read().await.get(agent_id).cloned()
+    }
+
+    /// Save the draft of an agent; blank text deletes it
+    pub async fn save(&self, agent_id: &str, text: String) -> Result<(), String> {
+        let mut drafts = self.drafts.write().await;
+        if text.trim().is_empty() {
+            if drafts.remove(agent_id).is_none() {
+                return Ok(());
+            }
+        } else {
+            let draft = PromptDraft {
+                text,
+                updated_at: now_secs(),
+            };
+            drafts.insert(agent_id.to_string(), draft);
+        }
+        self.save_to_file(&drafts)
+    }
+
+    /// Hand the draft of an agent to the agent that replaced it
+    pub async fn move_draft(&self, from_agent_id: &str, to_agent_id: &str) -> Result<(), String> {
+        let mut drafts = self.drafts.write().await;
+        let Some(draft) = drafts.remove(from_agent_id) else {
+            return Ok(());
+        };
+        drafts.insert(to_agent_id.to_string(), draft);
+        self.save_to_file(&drafts)
+    }
+}
+
+impl Default for DraftStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
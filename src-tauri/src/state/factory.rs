@@ -1,6 +1,11 @@
+use crate::filesystem::ProjectSummary;
+use super::storage::{app_data_dir, storage_writable};
+use crate::runner::ProjectCommandKind;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tokio::sync::RwLock;
 
 const FACTORY_LAYOUT_FILE: &str = "factory-layout.json";
@@ -17,6 +22,9 @@ pub struct ProjectNode {
     pub file_count: Option<u32>,
     #[serde(default)]
     pub color_index: Option<u32>,
+    /// Result of the last `analyze_project`
+    #[serde(default)]
+    pub summary: Option<ProjectSummary>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +40,69 @@ pub struct AgentPlacement {
     pub working_directory: Option<String>,
     #[serde(default)]
     pub provider_id: Option<String>,
+    #[serde(default)]
+    pub standing_order: Option<StandingOrder>,
+    #[serde(default)]
+    pub stats: PlacementStats,
+}
+
+/// Work done at a placement by all the agents that ran there, kept across
+/// restarts although every restart spawns an agent with a new id
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlacementStats {
+    pub prompts: u64,
+    pub tokens: u64,
+    pub files_touched: u64,
+}
+
+/// What makes a standing order fire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StandingOrderTrigger {
+    /// A command of the agent's project failed; any command when `command`
+    /// is None
+    CommandFailed {
+        #[serde(default)]
+        command: Option<ProjectCommandKind>,
+    },
+    /// Someone other than the agent changed files in its working directory
+    FilesChanged,
+}
+
+/// Prompt sent to an agent whenever its trigger fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StandingOrder {
+    pub prompt: String,
+    pub trigger: StandingOrderTrigger,
+    /// Least time between two dispatches of the order
+    #[serde(default = "default_min_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+fn default_min_interval_secs() -> u64 {
+    300
+}
+
+/// Projects that form one product, e.g. services living in separate repos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectGroup {
+    pub id: String,
+    pub name: String,
+    /// A project belongs to one group at most
+    pub project_ids: Vec<String>,
+    #[serde(default)]
+    pub agent_defaults: GroupAgentDefaults,
+}
+
+/// Used for agents spawned in a project of the group unless the spawn
+/// names its own
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupAgentDefaults {
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    /// Name of a spawn preset
+    #[serde(default)]
+    pub preset: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +128,8 @@ pub struct FactoryLayout {
     pub projects: Vec<ProjectNode>,
     pub agent_placements: Vec<AgentPlacement>,
     pub viewport: FactoryViewport,
+    #[serde(default)]
+    pub groups: Vec<ProjectGroup>,
 }
 
 impl Default for FactoryLayout {
@@ -66,13 +139,103 @@ impl Default for FactoryLayout {
             projects: Vec::new(),
             agent_placements: Vec::new(),
             viewport: FactoryViewport::default(),
+            groups: Vec::new(),
         }
     }
 }
 
+/// Side length in cells of a project node, growing with its file count
+fn project_size(project: &ProjectNode) -> i32 {
+    (2 + project.file_count.unwrap_or(0) as i32 / 400).clamp(2, 8)
+}
+
+/// Side length in cells of an agent
+const AGENT_SIZE: i32 = 2;
+
+impl FactoryLayout {
+    fn is_free(&self, x: i32, y: i32) -> bool {
+        let overlaps = |left: i32, top: i32, size: i32| {
+            x < left + size && left < x + AGENT_SIZE && y < top + size && top < y + AGENT_SIZE
+        };
+        !self.projects.iter().any(|p| overlaps(p.grid_x, p.grid_y, project_size(p)))
+            && !self
+                .agent_placements
+                .iter()
+                .any(|p| overlaps(p.grid_x, p.grid_y, AGENT_SIZE))
+    }
+
+    /// Project containing `path`, the innermost if projects are nested
+    pub fn project_containing(&self, path: &str) -> Option<&ProjectNode> {
+        self.projects
+            .iter()
+            .filter(|p| Path::new(path).starts_with(&p.path))
+            .max_by_key(|p| p.path.len())
+    }
+
+    /// Group the project belongs to
+    pub fn group_of(&self, project_id: &str) -> Option<&ProjectGroup> {
+        self.groups
+            .iter()
+            .find(|g| g.project_ids.iter().any(|id| id == project_id))
+    }
+
+    /// Projects of a group, in the group's order
+    pub fn group_projects(&self, group_id: &str) -> Result<Vec<&ProjectNode>, String> {
+        let group = self
+            .groups
+            .iter()
+            .find(|g| g.id == group_id)
+            .ok_or_else(|| format!("Unknown project group: {}", group_id))?;
+        Ok(group
+            .project_ids
+            .iter()
+            .filter_map(|id| self.projects.iter().find(|p| &p.id == id))
+            .collect())
+    }
+
+    fn group_mut(&mut self, group_id: &str) -> Result<&mut ProjectGroup, String> {
+        self.groups
+            .iter_mut()
+            .find(|g| g.id == group_id)
+            .ok_or_else(|| format!("Unknown project group: {}", group_id))
+    }
+
+    /// Move a project into a group, out of any other it was in
+    pub fn add_to_group(&mut self, group_id: &str, project_id: &str) -> Result<(), String> {
+        if !self.projects.iter().any(|p| p.id == project_id) {
+            return Err(format!("Unknown project: {}", project_id));
+        }
+        self.group_mut(group_id)?;
+        self.remove_from_groups(project_id);
+        self.group_mut(group_id)?.project_ids.push(project_id.to_string());
+        Ok(())
+    }
+
+    pub fn remove_from_groups(&mut self, project_id: &str) {
+        for group in &mut self.groups {
+            group.project_ids.retain(|id| id != project_id);
+        }
+    }
+
+    /// Free cell for an agent next to the one at (x, y): right, below,
+    /// left or above, further out when those are taken
+    pub fn free_cell_near(&self, x: i32, y: i32) -> (i32, i32) {
+        for ring in 1.. {
+            let offset = ring * AGENT_SIZE;
+            let candidates = [(x + offset, y), (x, y + offset), (x - offset, y), (x, y - offset)];
+            if let Some(&cell) = candidates.iter().find(|(cx, cy)| self.is_free(*cx, *cy)) {
+                return cell;
+            }
+        }
+        unreachable!("the grid is unbounded")
+    }
+}
+
 pub struct FactoryStore {
     layout: RwLock<FactoryLayout>,
     storage_path: PathBuf,
+    /// Tokens each running agent had used when its stats were last recorded
+    recorded_tokens: Mutex<HashMap<String, u64>>,
 }
 
 impl FactoryStore {
@@ -83,16 +246,13 @@ impl FactoryStore {
         Self {
             layout: RwLock::new(layout),
             storage_path,
+            recorded_tokens: Mutex::new(HashMap::new()),
         }
     }
 
     fn get_storage_path() -> PathBuf {
         // Use app data directory
-        let base = dirs::data_dir()
-            .or_else(dirs::home_dir)
-            .unwrap_or_else(|| PathBuf::from("."));
-
-        let app_dir = base.join("acptorio");
+        let app_dir = app_data_dir();
         fs::create_dir_all(&app_dir).ok();
 
         app_dir.join(FACTORY_LAYOUT_FILE)
@@ -112,6 +272,9 @@ impl FactoryStore {
     }
 
     fn save_to_file(&self, layout: &FactoryLayout) -> Result<(), String> {
+        if !storage_writable() {
+            return Ok(());
+        }
         let content = serde_json::to_string_pretty(layout)
             .map_err(|e| format!("Failed to serialize layout: {}", e))?;
 
@@ -149,6 +312,7 @@ impl FactoryStore {
         let mut layout = self.layout.write().await;
 
         layout.projects.retain(|p| p.id != project_id);
+        layout.remove_from_groups(project_id);
 
         // Disconnect agents from removed project
         for placement in &mut layout.agent_placements {
@@ -199,6 +363,24 @@ impl FactoryStore {
         Ok(layout.clone())
     }
 
+    pub async fn set_project_summary(
+        &self,
+        project_id: &str,
+        summary: ProjectSummary,
+    ) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+
+        let project = layout
+            .projects
+            .iter_mut()
+            .find(|p| p.id == project_id)
+            .ok_or_else(|| format!("Unknown project: {}", project_id))?;
+        project.summary = Some(summary);
+
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
     // Agent placement operations
     pub async fn set_agent_placement(
         &self,
@@ -234,6 +416,68 @@ impl FactoryStore {
         Ok(layout.clone())
     }
 
+    pub async fn set_standing_order(
+        &self,
+        agent_id: &str,
+        order: Option<StandingOrder>,
+    ) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        let placement = layout
+            .agent_placements
+            .iter_mut()
+            .find(|p| p.agent_id == agent_id)
+            .ok_or_else(|| format!("Agent {} is not placed", agent_id))?;
+        placement.standing_order = order;
+
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
+    /// Carry the stats of an agent's earlier placement over, e.g. after it
+    /// was restored under a new id
+    pub async fn set_placement_stats(
+        &self,
+        agent_id: &str,
+        stats: PlacementStats,
+    ) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        let placement = layout
+            .agent_placements
+            .iter_mut()
+            .find(|p| p.agent_id == agent_id)
+            .ok_or_else(|| format!("Agent {} is not placed", agent_id))?;
+        placement.stats = stats;
+
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
+    /// Add a finished prompt to the stats of the agent's placement.
+    /// `tokens_used` is the agent's running total; only what it used since
+    /// the last record is added. Unplaced agents are ignored.
+    pub async fn record_prompt(
+        &self,
+        agent_id: &str,
+        tokens_used: u64,
+        files_touched: usize,
+    ) -> Result<(), String> {
+        let mut layout = self.layout.write().await;
+        let Some(placement) = layout.agent_placements.iter_mut().find(|p| p.agent_id == agent_id) else {
+            return Ok(());
+        };
+        let recorded = self
+            .recorded_tokens
+            .lock()
+            .unwrap()
+            .insert(agent_id.to_string(), tokens_used)
+            .unwrap_or(0);
+        placement.stats.prompts += 1;
+        placement.stats.tokens += tokens_used.saturating_sub(recorded);
+        placement.stats.files_touched += files_touched as u64;
+
+        self.save_to_file(&layout)
+    }
+
     pub async fn remove_agent_placement(&self, agent_id: &str) -> Result<FactoryLayout, String> {
         let mut layout = self.layout.write().await;
         layout.agent_placements.retain(|p| p.agent_id != agent_id);
@@ -241,6 +485,67 @@ impl FactoryStore {
         Ok(layout.clone())
     }
 
+    /// Add a group, moving its projects out of the groups they were in
+    pub async fn create_group(&self, group: ProjectGroup) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        if layout.groups.iter().any(|g| g.id == group.id) {
+            return Err(format!("Project group {} already exists", group.id));
+        }
+        if let Some(unknown) = group
+            .project_ids
+            .iter()
+            .find(|id| !layout.projects.iter().any(|p| &p.id == *id))
+        {
+            return Err(format!("Unknown project: {}", unknown));
+        }
+        for project_id in &group.project_ids {
+            layout.remove_from_groups(project_id);
+        }
+        layout.groups.push(group);
+
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
+    pub async fn delete_group(&self, group_id: &str) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        layout.groups.retain(|g| g.id != group_id);
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
+    pub async fn add_project_to_group(
+        &self,
+        group_id: &str,
+        project_id: &str,
+    ) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        layout.add_to_group(group_id, project_id)?;
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
+    pub async fn remove_project_from_group(
+        &self,
+        project_id: &str,
+    ) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        layout.remove_from_groups(project_id);
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
+    pub async fn set_group_agent_defaults(
+        &self,
+        group_id: &str,
+        defaults: GroupAgentDefaults,
+    ) -> Result<FactoryLayout, String> {
+        let mut layout = self.layout.write().await;
+        layout.group_mut(group_id)?.agent_defaults = defaults;
+        self.save_to_file(&layout)?;
+        Ok(layout.clone())
+    }
+
     pub async fn set_viewport(&self, viewport: FactoryViewport) -> Result<FactoryLayout, String> {
         let mut layout = self.layout.write().await;
         layout.viewport = viewport;
@@ -254,3 +559,89 @@ impl Default for FactoryStore {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(agent_id: &str, grid_x: i32, grid_y: i32) -> AgentPlacement {
+        AgentPlacement {
+            agent_id: agent_id.to_string(),
+            grid_x,
+            grid_y,
+            connected_project_id: None,
+            name: None,
+            working_directory: None,
+            provider_id: None,
+            standing_order: None,
+            stats: PlacementStats::default(),
+        }
+    }
+
+    #[test]
+    fn test_free_cell_near_skips_taken_cells() {
+        let mut layout = FactoryLayout {
+            agent_placements: vec![placement("a", 0, 0), placement("b", 2, 0)],
+            ..Default::default()
+        };
+        assert_eq!(layout.free_cell_near(0, 0), (0, 2));
+
+        layout.projects.push(ProjectNode {
+            id: "p".to_string(),
+            path: "/p".to_string(),
+            name: "p".to_string(),
+            grid_x: -1,
+            grid_y: 2,
+            file_count: None,
+            color_index: None,
+            summary: None,
+        });
+        layout.agent_placements.push(placement("c", -2, -1));
+        assert_eq!(layout.free_cell_near(0, 0), (0, -2));
+
+        layout.agent_placements.push(placement("d", 0, -2));
+        assert_eq!(layout.free_cell_near(0, 0), (4, 0));
+    }
+    #[test]
+    fn test_projects_belong_to_one_group_at_most() {
+        let project = |id: &str| ProjectNode {
+            id: id.to_string(),
+            path: format!("/work/{}", id),
+            name: id.to_string(),
+            grid_x: 0,
+            grid_y: 0,
+            file_count: None,
+            color_index: None,
+            summary: None,
+        };
+        let group = |id: &str, project_ids: &[&str]| ProjectGroup {
+            id: id.to_string(),
+            name: id.to_string(),
+            project_ids: project_ids.iter().map(|p| p.to_string()).collect(),
+            agent_defaults: GroupAgentDefaults::default(),
+        };
+        let mut layout = FactoryLayout {
+            projects: vec![project("api"), project("web"), project("docs")],
+            groups: vec![group("shop", &["api", "web"]), group("site", &["docs"])],
+            ..Default::default()
+        };
+
+        let names = |layout: &FactoryLayout, group_id: &str| -> Vec<String> {
+            let projects = layout.group_projects(group_id).unwrap();
+            projects.iter().map(|p| p.id.clone()).collect()
+        };
+        assert_eq!(names(&layout, "shop"), ["api", "web"]);
+        assert_eq!(layout.group_of("docs").unwrap().id, "site");
+
+        layout.add_to_group("shop", "docs").unwrap();
+        assert_eq!(names(&layout, "shop"), ["api", "web", "docs"]);
+        assert!(names(&layout, "site").is_empty());
+        assert!(layout.add_to_group("shop", "cli").is_err());
+        assert!(layout.add_to_group("blog", "api").is_err());
+        assert_eq!(layout.group_of("api").unwrap().id, "shop");
+
+        layout.remove_from_groups("api");
+        assert!(layout.group_of("api").is_none());
+        assert!(layout.group_projects("blog").is_err());
+    }
+}
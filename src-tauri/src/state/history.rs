@@ -0,0 +1,451 @@
+//! History kept in the database beyond the session: finished tasks, usage
+//! and working time, an audit log of what the user did to agents, and a
+//! feed of what agents did, each queried with a [`HistoryFilter`].
+
+use super::database::{Database, FilterColumns, HistoryFilter};
+use super::metrics::Usage;
+use crate::agent::{AgentUpdate, TaskInfo};
+use crate::clock::now_secs;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Row};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Agent updates worth keeping in the activity feed; message chunks are not
+const ACTIVITY_UPDATES: &[&str] = &["tool_call", "permission_request", "pending_input", "plan"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    AgentSpawned,
+    AgentStopped,
+    PermissionAnswered,
+    PromptCancelled,
+    ToolCallCancelled,
+}
+
+impl AuditKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditKind::AgentSpawned => "agent_spawned",
+            AuditKind::AgentStopped => "agent_stopped",
+            AuditKind::PermissionAnswered => "permission_answered",
+            AuditKind::PromptCancelled => "prompt_cancelled",
+            AuditKind::ToolCallCancelled => "tool_call_cancelled",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        [
+            AuditKind::AgentSpawned,
+            AuditKind::AgentStopped,
+            AuditKind::PermissionAnswered,
+            AuditKind::PromptCancelled,
+            AuditKind::ToolCallCancelled,
+        ]
+        .into_iter()
+        .find(|k| k.as_str() == kind)
+    }
+}
+
+/// Something the user did to an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: u64,
+    pub kind: AuditKind,
+    pub agent_id: Option<Uuid>,
+    pub project: Option<String>,
+    pub message: String,
+}
+
+/// Something an agent did, from its updates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub id: i64,
+    pub at: u64,
+    pub agent_id: Uuid,
+    pub project: Option<String>,
+    /// Update type, e.g. tool_call
+    pub kind: String,
+    pub message: Option<String>,
+    pub path: Option<String>,
+}
+
+/// A stretch of time an agent was working, in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkSpanRecord {
+    pub agent_id: Uuid,
+    pub agent_name: String,
+    pub project: String,
+    pub started_at: u64,
+    pub ended_at: u64,
+}
+
+/// Usage and working time over a filtered stretch of history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsHistory {
+    pub usage: Usage,
+    pub work: Vec<WorkSpanRecord>,
+}
+
+fn uuid_column(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<Uuid>> {
+    let text: Option<String> = row.get(index)?;
+    Ok(text.and_then(|t| Uuid::parse_str(&t).ok()))
+}
+
+impl Database {
+    /// Insert or update a task as it changes
+    pub fn save_task(&self, task: &TaskInfo, project: Option<&str>) -> Result<(), String> {
+        let data = serde_json::to_string(task).map_err(|e| e.to_string())?;
+        let status = serde_json::to_value(task.status).map_err(|e| e.to_string())?;
+        self.conn()
+            .execute(
+                "INSERT INTO tasks
+                    (id, agent_id, project, status, prompt, result, created_at, finished_at, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT (id) DO UPDATE SET
+                    status = excluded.status, result = excluded.result,
+                    finished_at = excluded.finished_at, data = excluded.data",
+                params![
+                    task.id,
+                    task.agent_id.to_string(),
+                    project,
+                    status.as_str(),
+                    task.prompt,
+                    task.result.as_deref().or(task.error.as_deref()),
+                    task.created_at as i64,
+                    task.finished_at.map(|at| at as i64),
+                    data,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to save task {}: {}", task.id, e))
+    }
+
+    /// Tasks of this and earlier runs, newest first; `kind` is the status
+    pub fn task_history(&self, filter: &HistoryFilter) -> Result<Vec<TaskInfo>, String> {
+        let (clause, values) = filter.to_sql(&FilterColumns {
+            at: "created_at",
+            agent_id: Some("agent_id"),
+            project: Some("project"),
+            kind: Some("status"),
+            text: &["prompt", "result"],
+        });
+        let sql = format!("SELECT data FROM tasks{}", clause);
+        let rows: Vec<String> = self.query(&sql, values, |row| row.get(0))?;
+        Ok(rows
+            .iter()
+            .filter_map(|data| serde_json::from_str(data).ok())
+            .collect())
+    }
+
+    pub fn record_usage(&self, at: u64, tokens: u64, cost_cents: u64) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO usage_samples (at, tokens, cost_cents) VALUES (?1, ?2, ?3)",
+                params![at as i64, tokens as i64, cost_cents as i64],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to record usage: {}", e))
+    }
+
+    pub fn record_work_span(&self, span: &WorkSpanRecord) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO work_spans (agent_id, agent_name, project, started_at, ended_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    span.agent_id.to_string(),
+                    span.agent_name,
+                    span.project,
+                    span.started_at as i64,
+                    span.ended_at as i64,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to record working time: {}", e))
+    }
+
+    /// Usage within the filter's time range, and the work spans it matches;
+    /// `text` matches agent names
+    pub fn metrics_history(&self, filter: &HistoryFilter) -> Result<MetricsHistory, String> {
+        let (clause, values) = filter.to_sql(&FilterColumns {
+            at: "started_at",
+            agent_id: Some("agent_id"),
+            project: Some("project"),
+            kind: None,
+            text: &["agent_name"],
+        });
+        let work = self.query(
+            &format!(
+                "SELECT agent_id, agent_name, project, started_at, ended_at FROM work_spans{}",
+                clause
+            ),
+            values,
+            |row| {
+                Ok(WorkSpanRecord {
+                    agent_id: uuid_column(row, 0)?.unwrap_or_default(),
+                    agent_name: row.get(1)?,
+                    project: row.get(2)?,
+                    started_at: row.get::<_, i64>(3)? as u64,
+                    ended_at: row.get::<_, i64>(4)? as u64,
+                })
+            },
+        )?;
+
+        let (tokens, cost_cents): (i64, i64) = self
+            .conn()
+            .query_row(
+                "SELECT COALESCE(SUM(tokens), 0), COALESCE(SUM(cost_cents), 0) FROM usage_samples
+                 WHERE at >= ?1 AND at < ?2",
+                params![
+                    filter.since.unwrap_or(0) as i64,
+                    filter.until.map_or(i64::MAX, |until| until as i64)
+                ],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(MetricsHistory {
+            usage: Usage {
+                tokens: tokens as u64,
+                cost_dollars: cost_cents as f64 / 100.0,
+            },
+            work,
+        })
+    }
+
+    pub fn audit(
+        &self,
+        kind: AuditKind,
+        agent_id: Option<Uuid>,
+        project: Option<&str>,
+        message: &str,
+    ) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO audit_log (at, kind, agent_id, project, message)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    now_secs() as i64,
+                    kind.as_str(),
+                    agent_id.map(|id| id.to_string()),
+                    project,
+                    message,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to write the audit log: {}", e))
+    }
+
+    pub fn audit_log(&self, filter: &HistoryFilter) -> Result<Vec<AuditEntry>, String> {
+        let (clause, values) = filter.to_sql(&FilterColumns {
+            at: "at",
+            agent_id: Some("agent_id"),
+            project: Some("project"),
+            kind: Some("kind"),
+            text: &["message"],
+        });
+        let rows = self.query(
+            &format!("SELECT id, at, kind, agent_id, project, message FROM audit_log{}", clause),
+            values,
+            |row| {
+                let kind: String = row.get(2)?;
+                // Kinds a later version added are skipped
+                let Some(kind) = AuditKind::parse(&kind) else {
+                    return Ok(None);
+                };
+                Ok(Some(AuditEntry {
+                    id: row.get(0)?,
+                    at: row.get::<_, i64>(1)? as u64,
+                    kind,
+                    agent_id: uuid_column(row, 3)?,
+                    project: row.get(4)?,
+                    message: row.get(5)?,
+                }))
+            },
+        )?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// Add an update to the activity feed if it is worth keeping; returns
+    /// whether it was
+    pub fn record_activity(
+        &self,
+        update: &AgentUpdate,
+        project: Option<&str>,
+    ) -> Result<bool, String> {
+        if !ACTIVITY_UPDATES.contains(&update.update_type.as_str()) {
+            return Ok(false);
+        }
+        let message = update
+            .message
+            .clone()
+            .or_else(|| update.tool.as_ref().map(|tool| tool.name.clone()));
+        let path = update
+            .touched_files
+            .first()
+            .map(|range| range.path.clone())
+            .or_else(|| update.current_file.clone());
+        self.conn()
+            .execute(
+                "INSERT INTO activity (at, agent_id, project, kind, message, path)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    now_secs() as i64,
+                    update.agent_id.to_string(),
+                    project,
+                    update.update_type,
+                    message,
+                    path,
+                ],
+            )
+            .map(|_| true)
+            .map_err(|e| format!("Failed to record activity: {}", e))
+    }
+
+    pub fn activity(&self, filter: &HistoryFilter) -> Result<Vec<ActivityEntry>, String> {
+        let (clause, values) = filter.to_sql(&FilterColumns {
+            at: "at",
+            agent_id: Some("agent_id"),
+            project: Some("project"),
+            kind: Some("kind"),
+            text: &["message", "path"],
+        });
+        self.query(
+            &format!(
+                "SELECT id, at, agent_id, project, kind, message, path FROM activity{}",
+                clause
+            ),
+            values,
+            |row| {
+                Ok(ActivityEntry {
+                    id: row.get(0)?,
+                    at: row.get::<_, i64>(1)? as u64,
+                    agent_id: uuid_column(row, 2)?.unwrap_or_default(),
+                    project: row.get(3)?,
+                    kind: row.get(4)?,
+                    message: row.get(5)?,
+                    path: row.get(6)?,
+                })
+            },
+        )
+    }
+
+    fn query<T>(
+        &self,
+        sql: &str,
+        values: Vec<SqlValue>,
+        map: impl FnMut(&Row<'_>) -> rusqlite::Result<T>,
+    ) -> Result<Vec<T>, String> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(sql).map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params_from_iter(values), map)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<Vec<T>>>()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{TaskGraph, TaskSpec, TaskStatus};
+
+    fn update(agent_id: Uuid, update_type: &str, message: &str) -> AgentUpdate {
+        AgentUpdate {
+            agent_id,
+            update_type: update_type.to_string(),
+            message: Some(message.to_string()),
+            tool: None,
+            progress: None,
+            current_file: Some("/work/app/src/main.rs".to_string()),
+            status: None,
+            pending_inputs: None,
+            touched_files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_history_is_recorded_and_filtered() {
+        let db = Database::in_memory();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let read = update(a, "tool_call", "Read main.rs");
+        assert!(db.record_activity(&read, Some("/work/app")).unwrap());
+        let plan = update(b, "plan", "Fix the build");
+        assert!(db.record_activity(&plan, Some("/work/lib")).unwrap());
+        let chunk = update(a, "agent_message_chunk", "Hi");
+        assert!(!db.record_activity(&chunk, None).unwrap());
+        let feed = db.activity(&HistoryFilter::default()).unwrap();
+        assert_eq!(feed.len(), 2);
+        let only_a = HistoryFilter {
+            agent_id: Some(a),
+            ..Default::default()
+        };
+        let feed = db.activity(&only_a).unwrap();
+        assert_eq!(feed[0].path.as_deref(), Some("/work/app/src/main.rs"));
+        let by_text = HistoryFilter {
+            text: Some("BUILD".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.activity(&by_text).unwrap()[0].agent_id, b);
+
+        db.audit(AuditKind::AgentSpawned, Some(a), Some("/work/app"), "Spawned Builder").unwrap();
+        db.audit(AuditKind::AgentStopped, Some(a), Some("/work/app"), "Stopped Builder").unwrap();
+        let stopped = HistoryFilter {
+            kind: Some("agent_stopped".to_string()),
+            ..Default::default()
+        };
+        let log = db.audit_log(&stopped).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].kind, AuditKind::AgentStopped);
+
+        let graph = TaskGraph::new();
+        let (mut task, _) = graph
+            .add(TaskSpec {
+                agent_id: a,
+                prompt: "fix the build".to_string(),
+                depends_on: Vec::new(),
+                inject_results: false,
+                context: None,
+                priority: Default::default(),
+                preamble: None,
+            })
+            .unwrap();
+        db.save_task(&task, Some("/work/app")).unwrap();
+        task.status = TaskStatus::Completed;
+        task.result = Some("Fixed".to_string());
+        db.save_task(&task, Some("/work/app")).unwrap();
+        let completed = HistoryFilter {
+            kind: Some("completed".to_string()),
+            text: Some("fixed".to_string()),
+            ..Default::default()
+        };
+        let tasks = db.task_history(&completed).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, task.id);
+
+        let span = |agent_id: Uuid, project: &str, started_at: u64| WorkSpanRecord {
+            agent_id,
+            agent_name: "worker".to_string(),
+            project: project.to_string(),
+            started_at,
+            ended_at: started_at + 60,
+        };
+        db.record_work_span(&span(a, "/work/app", 100)).unwrap();
+        db.record_work_span(&span(b, "/work/lib", 200)).unwrap();
+        db.record_usage(150, 1_000, 25).unwrap();
+        db.record_usage(250, 500, 10).unwrap();
+        let history = db
+            .metrics_history(&HistoryFilter {
+                since: Some(200),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(history.work, vec![span(b, "/work/lib", 200)]);
+        assert_eq!(history.usage.tokens, 500);
+        assert_eq!(history.usage.cost_dollars, 0.1);
+    }
+}
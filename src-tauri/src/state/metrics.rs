@@ -1,12 +1,51 @@
+use super::database::Database;
+use super::history::WorkSpanRecord;
+use crate::clock::now_secs;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
+use uuid::Uuid;
+
+/// Usage samples kept for [`MetricsTracker::usage_since`]
+const MAX_SAMPLES: usize = 10_000;
+/// Finished work spans kept for [`MetricsTracker::time_tracking`]
+const MAX_WORK_SPANS: usize = 10_000;
+
+/// Usage recorded at one moment, in seconds
+#[derive(Debug, Clone, Copy)]
+struct UsageSample {
+    at: u64,
+    tokens: u64,
+    cost_cents: u64,
+}
+
+/// A stretch of time an agent was working, in seconds
+#[derive(Debug, Clone)]
+struct WorkSpan {
+    agent_id: Uuid,
+    agent_name: String,
+    project: String,
+    start: u64,
+    end: u64,
+}
+
+#[derive(Default)]
+struct WorkLog {
+    /// Spans still open, by agent
+    active: HashMap<Uuid, WorkSpan>,
+    finished: VecDeque<WorkSpan>,
+}
 
 pub struct MetricsTracker {
     total_input_tokens: AtomicU64,
     total_output_tokens: AtomicU64,
     total_cost_cents: AtomicU64,
     session_start: RwLock<Option<std::time::Instant>>,
+    samples: Mutex<VecDeque<UsageSample>>,
+    work: Mutex<WorkLog>,
+    /// Keeps usage and finished work spans beyond the session
+    history: Option<Arc<Database>>,
 }
 
 impl MetricsTracker {
@@ -16,16 +55,157 @@ impl MetricsTracker {
             total_output_tokens: AtomicU64::new(0),
             total_cost_cents: AtomicU64::new(0),
             session_start: RwLock::new(Some(std::time::Instant::now())),
+            samples: Mutex::new(VecDeque::new()),
+            work: Mutex::new(WorkLog::default()),
+            history: None,
+        }
+    }
+
+    /// Also record usage and working time in `db`
+    pub fn with_history(db: Arc<Database>) -> Self {
+        Self {
+            history: Some(db),
+            ..Self::new()
         }
     }
 
     pub fn add_tokens(&self, input: u64, output: u64) {
         self.total_input_tokens.fetch_add(input, Ordering::Relaxed);
         self.total_output_tokens.fetch_add(output, Ordering::Relaxed);
+        self.sample(input + output, 0);
     }
 
     pub fn add_cost(&self, cost_cents: u64) {
         self.total_cost_cents.fetch_add(cost_cents, Ordering::Relaxed);
+        self.sample(0, cost_cents);
+    }
+
+    fn sample(&self, tokens: u64, cost_cents: u64) {
+        let at = now_secs();
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() >= MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(UsageSample {
+            at,
+            tokens,
+            cost_cents,
+        });
+        drop(samples);
+        if let Some(db) = &self.history {
+            if let Err(e) = db.record_usage(at, tokens, cost_cents) {
+                tracing::warn!("{}", e);
+            }
+        }
+    }
+
+    /// Tokens and cost recorded at or after `since`, in seconds. Usage older
+    /// than the last [`MAX_SAMPLES`] recordings is not counted.
+    pub fn usage_since(&self, since: u64) -> Usage {
+        let (tokens, cost_cents) = self
+            .samples
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.at >= since)
+            .fold((0, 0), |(tokens, cost), s| (tokens + s.tokens, cost + s.cost_cents));
+        Usage {
+            tokens,
+            cost_dollars: cost_cents as f64 / 100.0,
+        }
+    }
+
+    /// The agent started working; a no-op while it already is
+    pub fn work_started(&self, agent_id: Uuid, agent_name: &str, project: &str) {
+        let now = now_secs();
+        self.work
+            .lock()
+            .unwrap()
+            .active
+            .entry(agent_id)
+            .or_insert_with(|| WorkSpan {
+                agent_id,
+                agent_name: agent_name.to_string(),
+                project: project.to_string(),
+                start: now,
+                end: now,
+            });
+    }
+
+    /// The agent stopped working, e.g. went idle or paused on a permission
+    pub fn work_stopped(&self, agent_id: Uuid) {
+        let mut work = self.work.lock().unwrap();
+        if let Some(mut span) = work.active.remove(&agent_id) {
+            span.end = now_secs();
+            if let Some(db) = &self.history {
+                let record = WorkSpanRecord {
+                    agent_id: span.agent_id,
+                    agent_name: span.agent_name.clone(),
+                    project: span.project.clone(),
+                    started_at: span.start,
+                    ended_at: span.end,
+                };
+                if let Err(e) = db.record_work_span(&record) {
+                    tracing::warn!("{}", e);
+                }
+            }
+            if work.finished.len() >= MAX_WORK_SPANS {
+                work.finished.pop_front();
+            }
+            work.finished.push_back(span);
+        }
+    }
+
+    /// Time agents spent working at or after `since`, in seconds, per
+    /// project and per agent; agents working now count up to now
+    pub fn time_tracking(&self, since: u64) -> TimeTracking {
+        self.time_tracking_at(since, now_secs())
+    }
+
+    fn time_tracking_at(&self, since: u64, now: u64) -> TimeTracking {
+        let work = self.work.lock().unwrap();
+        let open = work.active.values().map(|span| WorkSpan {
+            end: now,
+            ..span.clone()
+        });
+        let mut projects: BTreeMap<String, u64> = BTreeMap::new();
+        let mut agents: HashMap<Uuid, AgentTime> = HashMap::new();
+        for span in work.finished.iter().cloned().chain(open) {
+            let secs = span.end.saturating_sub(span.start.max(since));
+            if secs == 0 {
+                continue;
+            }
+            *projects.entry(span.project.clone()).or_default() += secs;
+            let agent = agents.entry(span.agent_id).or_insert_with(|| AgentTime {
+                agent_id: span.agent_id,
+                agent_name: span.agent_name.clone(),
+                project: span.project.clone(),
+                active_secs: 0,
+            });
+            agent.active_secs += secs;
+        }
+
+        let mut agents: Vec<AgentTime> = agents.into_values().collect();
+        agents.sort_by(|a, b| {
+            b.active_secs
+                .cmp(&a.active_secs)
+                .then(a.agent_name.cmp(&b.agent_name))
+        });
+        let mut projects: Vec<ProjectTime> = projects
+            .into_iter()
+            .map(|(project, active_secs)| ProjectTime {
+                project,
+                active_secs,
+            })
+            .collect();
+        projects.sort_by_key(|p| std::cmp::Reverse(p.active_secs));
+        TimeTracking {
+            since,
+            until: now,
+            total_secs: projects.iter().map(|p| p.active_secs).sum(),
+            projects,
+            agents,
+        }
     }
 
     pub fn get_metrics(&self) -> Metrics {
@@ -50,6 +230,8 @@ impl MetricsTracker {
         self.total_input_tokens.store(0, Ordering::Relaxed);
         self.total_output_tokens.store(0, Ordering::Relaxed);
         self.total_cost_cents.store(0, Ordering::Relaxed);
+        self.samples.lock().unwrap().clear();
+        self.work.lock().unwrap().finished.clear();
         *self.session_start.write().unwrap() = Some(std::time::Instant::now());
     }
 }
@@ -68,3 +250,94 @@ pub struct Metrics {
     pub total_cost_dollars: f64,
     pub session_duration_secs: u64,
 }
+
+/// Usage over a stretch of time
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub tokens: u64,
+    pub cost_dollars: f64,
+}
+
+/// Working time of the agents of one project, by working directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTime {
+    pub project: String,
+    pub active_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTime {
+    pub agent_id: Uuid,
+    pub agent_name: String,
+    pub project: String,
+    pub active_secs: u64,
+}
+
+/// Wall-clock time agents spent working, most first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimeTracking {
+    pub since: u64,
+    pub until: u64,
+    pub total_secs: u64,
+    pub projects: Vec<ProjectTime>,
+    pub agents: Vec<AgentTime>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_since_counts_recent_samples() {
+        let metrics = MetricsTracker::new();
+        metrics.add_tokens(100, 50);
+        metrics.add_cost(25);
+        metrics.samples.lock().unwrap().push_front(UsageSample {
+            at: 1,
+            tokens: 1000,
+            cost_cents: 100,
+        });
+
+        let usage = metrics.usage_since(2);
+        assert_eq!(usage.tokens, 150);
+        assert_eq!(usage.cost_dollars, 0.25);
+        assert_eq!(metrics.usage_since(0).tokens, 1150);
+
+        metrics.reset();
+        assert_eq!(metrics.usage_since(0).tokens, 0);
+    }
+
+    #[test]
+    fn test_time_tracking_sums_spans_within_range() {
+        let metrics = MetricsTracker::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let span = |agent_id: Uuid, project: &str, start: u64, end: u64| WorkSpan {
+            agent_id,
+            agent_name: "worker".to_string(),
+            project: project.to_string(),
+            start,
+            end,
+        };
+        {
+            let mut work = metrics.work.lock().unwrap();
+            work.finished.push_back(span(a, "/work/app", 100, 160));
+            work.finished.push_back(span(a, "/work/app", 200, 230));
+            work.finished.push_back(span(b, "/work/lib", 10, 50));
+            work.active.insert(b, span(b, "/work/lib", 290, 290));
+        }
+
+        let time = metrics.time_tracking_at(0, 300);
+        assert_eq!(time.total_secs, 140);
+        assert_eq!(time.agents[0].agent_id, a);
+        assert_eq!(time.agents[0].active_secs, 90);
+        assert_eq!(time.projects[1].project, "/work/lib");
+        assert_eq!(time.projects[1].active_secs, 50);
+
+        // Spans are cut at the start of the range
+        let time = metrics.time_tracking_at(150, 300);
+        assert_eq!(time.total_secs, 10 + 30 + 10);
+
+        metrics.work_stopped(b);
+        assert!(metrics.work.lock().unwrap().active.is_empty());
+    }
+}
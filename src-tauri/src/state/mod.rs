@@ -1,7 +1,31 @@
+pub mod alerts;
 pub mod app_state;
+pub mod away;
+pub mod conversations;
+pub mod database;
+pub mod drafts;
 pub mod factory;
+pub mod history;
 pub mod metrics;
+pub mod onboarding;
+pub mod presets;
+pub mod scratchpad;
+pub mod settings;
+pub mod storage;
+pub mod usage_stats;
 
+pub use alerts::*;
 pub use app_state::*;
+pub use away::*;
+pub use conversations::*;
+pub use database::*;
+pub use drafts::*;
 pub use factory::*;
+pub use history::*;
 pub use metrics::*;
+pub use onboarding::*;
+pub use presets::*;
+pub use scratchpad::*;
+pub use settings::*;
+pub use storage::*;
+pub use usage_stats::*;
@@ -0,0 +1,278 @@
+//! Progress of the first-run setup wizard, kept across restarts.
+
+use super::storage::app_data_dir;
+use crate::diagnostics::EnvironmentReport;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const ONBOARDING_FILE: &str = "onboarding.json";
+const SAMPLE_PROJECT_DIR: &str = "sample-project";
+
+/// Files of the sample project, relative to its root
+const SAMPLE_FILES: &[(&str, &str)] = &[
+    (
+        "README.md",
+        "# Sample project\n\nA small project to try agents on. Ask one to add a \
+         `subtract` function with tests.\n",
+    ),
+    (
+        "package.json",
+        "{\n  \"name\": \"acptorio-sample\",\n  \"version\": \"0.1.0\",\n  \
+         \"type\": \"module\",\n  \"scripts\": { \"test\": \"node --test\" }\n}\n",
+    ),
+    ("src/math.js", "export function add(a, b) {\n  return a + b;\n}\n"),
+    (
+        "test/math.test.js",
+        "import { test } from \"node:test\";\nimport assert from \"node:assert\";\n\
+         import { add } from \"../src/math.js\";\n\ntest(\"add\", () => {\n  \
+         assert.strictEqual(add(2, 3), 5);\n});\n",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    CheckEnvironment,
+    FetchRegistry,
+    PickProvider,
+    StoreApiKey,
+    OpenSampleProject,
+}
+
+impl OnboardingStep {
+    /// In the order the wizard shows them
+    pub const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::CheckEnvironment,
+        OnboardingStep::FetchRegistry,
+        OnboardingStep::PickProvider,
+        OnboardingStep::StoreApiKey,
+        OnboardingStep::OpenSampleProject,
+    ];
+
+    /// Agents can log in by themselves and any project will do
+    pub fn can_skip(self) -> bool {
+        matches!(self, OnboardingStep::StoreApiKey | OnboardingStep::OpenSampleProject)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepState {
+    Pending,
+    Done,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OnboardingProgress {
+    #[serde(default)]
+    pub done: Vec<OnboardingStep>,
+    #[serde(default)]
+    pub skipped: Vec<OnboardingStep>,
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    /// Variable whose API key went to the keychain
+    #[serde(default)]
+    pub api_key_variable: Option<String>,
+    #[serde(default)]
+    pub project_path: Option<String>,
+}
+
+impl OnboardingProgress {
+    pub fn state(&self, step: OnboardingStep) -> StepState {
+        if self.done.contains(&step) {
+            StepState::Done
+        } else if self.skipped.contains(&step) {
+            StepState::Skipped
+        } else {
+            StepState::Pending
+        }
+    }
+
+    /// First step neither done nor skipped
+    pub fn next_step(&self) -> Option<OnboardingStep> {
+        OnboardingStep::ALL
+            .into_iter()
+            .find(|step| self.state(*step) == StepState::Pending)
+    }
+
+    fn finish(&mut self, step: OnboardingStep, skipped: bool) {
+        self.done.retain(|s| *s != step);
+        self.skipped.retain(|s| *s != step);
+        if skipped {
+            self.skipped.push(step);
+        } else {
+            self.done.push(step);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingStepStatus {
+    pub step: OnboardingStep,
+    pub state: StepState,
+    pub can_skip: bool,
+}
+
+/// Everything the setup wizard shows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingStatus {
+    pub steps: Vec<OnboardingStepStatus>,
+    pub next_step: Option<OnboardingStep>,
+    pub finished: bool,
+    /// Result of the last environment check since the app started
+    pub environment: Option<EnvironmentReport>,
+    pub provider_id: Option<String>,
+    pub api_key_variable: Option<String>,
+    pub project_path: Option<String>,
+}
+
+pub struct OnboardingStore {
+    progress: RwLock<OnboardingProgress>,
+    environment: RwLock<Option<EnvironmentReport>>,
+    storage_path: PathBuf,
+}
+
+impl OnboardingStore {
+    pub fn new() -> Self {
+        let storage_path = Self::get_storage_path();
+        let progress = Self::load_from_file(&storage_path).unwrap_or_default();
+
+        Self {
+            progress: RwLock::new(progress),
+            environment: RwLock::new(None),
+            storage_path,
+        }
+    }
+
+    fn app_dir() -> PathBuf {
+        let app_dir = app_data_dir();
+        fs::create_dir_all(&app_dir).ok();
+        app_dir
+    }
+
+    fn get_storage_path() -> PathBuf {
+        Self::app_dir().join(ONBOARDING_FILE)
+    }
+
+    fn load_from_file(path: &Path) -> Option<OnboardingProgress> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save_to_file(&self, progress: &OnboardingProgress) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(progress)
+            .map_err(|e| format!("Failed to serialize onboarding progress: {}", e))?;
+
+        fs::write(&self.storage_path, content)
+            .map_err(|e| format!("Failed to write onboarding file: {}", e))?;
+
+        Ok(())
+    }
+
+    pub fn progress(&self) -> OnboardingProgress {
+        self.progress.read().unwrap().clone()
+    }
+
+    pub fn set_environment(&self, report: EnvironmentReport) {
+        *self.environment.write().unwrap() = Some(report);
+    }
+
+    /// Mark a step done or skipped after applying `update` to the progress
+    pub fn finish_step(
+        &self,
+        step: OnboardingStep,
+        skipped: bool,
+        update: impl FnOnce(&mut OnboardingProgress),
+    ) -> Result<(), String> {
+        if skipped && !step.can_skip() {
+            return Err(format!("{:?} can't be skipped", step));
+        }
+        let mut progress = self.progress.write().unwrap();
+        update(&mut progress);
+        progress.finish(step, skipped);
+        self.save_to_file(&progress)
+    }
+
+    /// Start the wizard over
+    pub fn reset(&self) -> Result<(), String> {
+        let mut progress = self.progress.write().unwrap();
+        *progress = OnboardingProgress::default();
+        self.save_to_file(&progress)
+    }
+
+    pub fn status(&self) -> OnboardingStatus {
+        let progress = self.progress();
+        let steps = OnboardingStep::ALL
+            .into_iter()
+            .map(|step| OnboardingStepStatus {
+                step,
+                state: progress.state(step),
+                can_skip: step.can_skip(),
+            })
+            .collect();
+        let next_step = progress.next_step();
+        OnboardingStatus {
+            steps,
+            next_step,
+            finished: next_step.is_none(),
+            environment: self.environment.read().unwrap().clone(),
+            provider_id: progress.provider_id,
+            api_key_variable: progress.api_key_variable,
+            project_path: progress.project_path,
+        }
+    }
+
+    /// Where the sample project is created
+    pub fn sample_project_path() -> PathBuf {
+        Self::app_dir().join(SAMPLE_PROJECT_DIR)
+    }
+}
+
+impl Default for OnboardingStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write the sample project's files below `root`, keeping files that exist
+pub fn create_sample_project(root: &Path) -> Result<(), String> {
+    for (relative, content) in SAMPLE_FILES {
+        let path = root.join(relative);
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_step_skips_finished_steps() {
+        let mut progress = OnboardingProgress::default();
+        assert_eq!(progress.next_step(), Some(OnboardingStep::CheckEnvironment));
+
+        progress.finish(OnboardingStep::CheckEnvironment, false);
+        progress.finish(OnboardingStep::FetchRegistry, false);
+        progress.finish(OnboardingStep::PickProvider, false);
+        progress.finish(OnboardingStep::StoreApiKey, true);
+        assert_eq!(progress.state(OnboardingStep::StoreApiKey), StepState::Skipped);
+        assert_eq!(progress.next_step(), Some(OnboardingStep::OpenSampleProject));
+
+        // Completing a skipped step later replaces the skip
+        progress.finish(OnboardingStep::StoreApiKey, false);
+        assert_eq!(progress.state(OnboardingStep::StoreApiKey), StepState::Done);
+        assert!(progress.skipped.is_empty());
+    }
+}
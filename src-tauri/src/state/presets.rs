@@ -0,0 +1,95 @@
+//! Spawn presets: a provider and a working directory template, so agents of
+//! a project can start in a subfolder like `{project}/backend`.
+
+use super::factory::ProjectNode;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnPreset {
+    pub name: String,
+    /// Provider to spawn when the spawn request names none
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    /// Working directory template: `{project}` is the project's path,
+    /// `{project_name}` and `{project_id}` its name and id
+    pub working_directory: String,
+}
+
+impl SpawnPreset {
+    /// Working directory of an agent spawned for `project`; fails unless it
+    /// is an existing directory
+    pub fn resolve_working_directory(&self, project: Option<&ProjectNode>) -> Result<String, String> {
+        let mut directory = self.working_directory.clone();
+        if let Some(project) = project {
+            directory = directory
+                .replace("{project}", project.path.trim_end_matches(['/', '\\']))
+                .replace("{project_name}", &project.name)
+                .replace("{project_id}", &project.id);
+        }
+        if let Some(start) = directory.find('{') {
+            let placeholder = directory[start..]
+                .find('}')
+                .map_or(&directory[start..], |end| &directory[start..=start + end]);
+            return Err(match project {
+                Some(_) => format!("Unknown placeholder {} in preset '{}'", placeholder, self.name),
+                None => format!("Preset '{}' needs a project for {}", self.name, placeholder),
+            });
+        }
+
+        let path = Path::new(&directory);
+        if !path.is_absolute() {
+            return Err(format!("Working directory of preset '{}' is not absolute: {}", self.name, directory));
+        }
+        if !path.is_dir() {
+            return Err(format!("Working directory of preset '{}' does not exist: {}", self.name, directory));
+        }
+        Ok(directory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(path: &str) -> ProjectNode {
+        ProjectNode {
+            id: "p1".to_string(),
+            path: path.to_string(),
+            name: "shop".to_string(),
+            grid_x: 0,
+            grid_y: 0,
+            file_count: None,
+            color_index: None,
+            summary: None,
+        }
+    }
+
+    fn preset(working_directory: &str) -> SpawnPreset {
+        SpawnPreset {
+            name: "backend".to_string(),
+            provider_id: None,
+            working_directory: working_directory.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_working_directory() {
+        let dir = std::env::temp_dir().join(format!("acptorio-preset-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("backend")).unwrap();
+        let root = format!("{}/", dir.display());
+        let backend = dir.join("backend").display().to_string();
+
+        assert_eq!(
+            preset("{project}/backend").resolve_working_directory(Some(&project(&root))),
+            Ok(backend)
+        );
+        let missing = preset("{project}/frontend").resolve_working_directory(Some(&project(&root)));
+        assert!(missing.unwrap_err().contains("does not exist"));
+        let unknown = preset("{project}/{team}").resolve_working_directory(Some(&project(&root)));
+        assert!(unknown.unwrap_err().contains("{team}"));
+        let no_project = preset("{project}/backend").resolve_working_directory(None);
+        assert!(no_project.unwrap_err().contains("needs a project"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use super::storage::app_data_dir;
+use crate::clock::now_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+
+const SCRATCHPAD_FILE: &str = "scratchpad.json";
+/// Lines of an agent's reply starting with this write the scratchpad
+const WRITE_MARKER: &str = "SCRATCHPAD ";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScratchpadEntry {
+    pub value: String,
+    pub updated_at: u64,
+    /// Agent id that wrote the entry, None when set by the user
+    #[serde(default)]
+    pub updated_by: Option<String>,
+}
+
+pub type Scratchpad = BTreeMap<String, ScratchpadEntry>;
+
+/// Per-project key-value notes shared between the user and agents
+pub struct ScratchpadStore {
+    /// project id -> entries
+    pads: RwLock<HashMap<String, Scratchpad>>,
+    storage_path: PathBuf,
+}
+
+impl ScratchpadStore {
+    pub fn new() -> Self {
+        let storage_path = Self::get_storage_path();
+        let pads = Self::load_from_file(&storage_path).unwrap_or_default();
+
+        Self {
+            pads: RwLock::new(pads),
+            storage_path,
+        }
+    }
+
+    fn get_storage_path() -> PathBuf {
+        let app_dir = app_data_dir();
+        fs::create_dir_all(&app_dir).ok();
+
+        app_dir.join(SCRATCHPAD_FILE)
+    }
+
+    fn load_from_file(path: &Path) -> Option<HashMap<String, Scratchpad>> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save_to_file(&self, pads: &HashMap<String, Scratchpad>) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(pads)
+            .map_err(|e| format!("Failed to serialize scratchpad: {}", e))?;
+
+        fs::write(&self.storage_path, content)
+            .map_err(|e| format!("Failed to write scratchpad file: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn get(&self, project_id: &str) -> Scratchpad {
+        self.pads
+            .read()
+            .await
+            .get(project_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Set an entry, or remove it when `value` is None
+    pub async fn set_entry(
+        &self,
+        project_id: &str,
+        key: &str,
+        value: Option<String>,
+        updated_by: Option<String>,
+    ) -> Result<Scratchpad, String> {
+        let mut pads = self.pads.write().await;
+        let pad = pads.entry(project_id.to_string()).or_default();
+
+        match value {
+            Some(value) => {
+                let entry = ScratchpadEntry {
+                    value,
+                    updated_at: now_secs(),
+                    updated_by,
+                };
+                pad.insert(key.to_string(), entry);
+            }
+            None => {
+                pad.remove(key);
+            }
+        }
+
+        let pad = pad.clone();
+        if pad.is_empty() {
+            pads.remove(project_id);
+        }
+        self.save_to_file(&pads)?;
+        Ok(pad)
+    }
+
+    pub async fn clear(&self, project_id: &str) -> Result<(), String> {
+        let mut pads = self.pads.write().await;
+        pads.remove(project_id);
+        self.save_to_file(&pads)
+    }
+}
+
+/// Preamble that shows agents the project's notes, and how to write them when
+/// `writable`; None when there is nothing to tell
+pub fn scratchpad_preamble(pad: &Scratchpad, writable: bool) -> Option<String> {
+    if pad.is_empty() && !writable {
+        return None;
+    }
+    let mut text = "Notes shared by the agents and the user of this project.".to_string();
+    if writable {
+        text.push_str(&format!(
+            " To set a note, put a line `{marker}<key>: <value>` in your reply; \
+             `{marker}<key>:` removes it.",
+            marker = WRITE_MARKER
+        ));
+    }
+    for (key, entry) in pad {
+        text.push_str(&format!("\n- {}: {}", key, entry.value));
+    }
+    Some(text)
+}
+
+/// Writes an agent asked for in its reply, in order: key and value, or None
+/// to remove the key. Lines in code blocks are quoted content, not writes.
+pub fn scratchpad_writes(reply: &str) -> Vec<(String, Option<String>)> {
+    let mut in_code = false;
+    reply
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            if line.starts_with("```") {
+                in_code = !in_code;
+            }
+            !in_code
+        })
+        .filter_map(|line| line.strip_prefix(WRITE_MARKER))
+        .filter_map(|write| {
+            let (key, value) = write.split_once(':')?;
+            let key = key.trim();
+            let value = value.trim();
+            let value = (!value.is_empty()).then(|| value.to_string());
+            (!key.is_empty()).then(|| (key.to_string(), value))
+        })
+        .collect()
+}
+
+impl Default for ScratchpadStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratchpad_writes_from_reply() {
+        let reply = "Done.\nSCRATCHPAD api_port: 8080\n  SCRATCHPAD old_note:\n\
+                     SCRATCHPAD : x\nscratchpad a: b\n```\nSCRATCHPAD quoted: file\n```";
+        assert_eq!(
+            scratchpad_writes(reply),
+            vec![
+                ("api_port".to_string(), Some("8080".to_string())),
+                ("old_note".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preamble_only_when_there_is_something_to_tell() {
+        let mut pad = Scratchpad::new();
+        assert_eq!(scratchpad_preamble(&pad, false), None);
+        assert!(scratchpad_preamble(&pad, true).unwrap().contains(WRITE_MARKER));
+
+        pad.insert(
+            "api_port".to_string(),
+            ScratchpadEntry {
+                value: "8080".to_string(),
+                updated_at: 0,
+                updated_by: None,
+            },
+        );
+        let read_only = scratchpad_preamble(&pad, false).unwrap();
+        assert!(read_only.contains("- api_port: 8080"));
+        assert!(!read_only.contains(WRITE_MARKER));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::acp::RequestPolicy;
+use crate::agent::{
+    default_package_runner, ContainerSandbox, EscalationPolicy, PolicyScripts, PromptPreamble,
+    ResourceLimits, ReviewWorkflow, SchedulingPolicy,
+};
+use crate::automation::TriggerRule;
+use crate::hooks::Hook;
+use crate::runner::ProjectCommands;
+use crate::shortcuts::PermissionShortcuts;
+use super::storage::app_data_dir;
+use super::SpawnPreset;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    /// Snapshot the project repo before each prompt so it can be rolled back
+    #[serde(default)]
+    pub git_checkpoints: bool,
+    /// Editor for "open in editor": a known editor name (vscode, cursor, idea, ...)
+    /// or a command template using {path} and {line}
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Actions to run when notable events happen
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    /// Total tokens after which the budget_exceeded hooks fire
+    #[serde(default)]
+    pub token_budget: Option<u64>,
+    /// Per-project reviewer agents that review every completed task
+    #[serde(default)]
+    pub reviews: Vec<ReviewWorkflow>,
+    /// Per-method request timeouts and retries (`*` for methods without
+    /// their own entry), applied to agents spawned afterwards
+    #[serde(default)]
+    pub request_policies: HashMap<String, RequestPolicy>,
+    /// Run locally spawned agents inside a container, applied to agents
+    /// spawned afterwards
+    #[serde(default)]
+    pub sandbox: Option<ContainerSandbox>,
+    /// CPU and memory limits of locally spawned agents
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    /// Revealing a file dimly reveals the other entries of its directory
+    #[serde(default)]
+    pub fog_scan_radius: bool,
+    /// Hold an agent's edit of a file until another agent finished editing it
+    #[serde(default)]
+    pub hold_conflicting_edits: bool,
+    /// Build, test and lint commands per project
+    #[serde(default)]
+    pub project_commands: Vec<ProjectCommands>,
+    /// Dispatch order and concurrency limit of prompts across agents
+    #[serde(default)]
+    pub scheduling: SchedulingPolicy,
+    /// Named provider and working directory templates to spawn agents from
+    #[serde(default)]
+    pub spawn_presets: Vec<SpawnPreset>,
+    /// Prompts sent to agents when files of a project change
+    #[serde(default)]
+    pub trigger_rules: Vec<TriggerRule>,
+    /// Names and `*.ext` patterns the scanner, watcher and fog skip on top of
+    /// the defaults (.git, node_modules, target, ...)
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    /// Reminders and default answer for unanswered permission requests
+    #[serde(default)]
+    pub permission_escalation: EscalationPolicy,
+    /// Rhai scripts that answer permission requests and pick agents for
+    /// routed tasks
+    #[serde(default)]
+    pub policy_scripts: PolicyScripts,
+    /// Standing instructions per agent or project, sent ahead of prompts
+    #[serde(default)]
+    pub prompt_preambles: Vec<PromptPreamble>,
+    /// Providers (`default` for agents spawned without one) whose CLIs don't
+    /// read AGENTS.md, CLAUDE.md or .cursorrules; their agents get these files
+    /// in the preamble instead
+    #[serde(default)]
+    pub instruction_file_providers: Vec<String>,
+    /// Command that runs npm packages of npx agents, e.g. `npx.cmd` or `bunx`;
+    /// npx (npx.cmd on Windows) if unset
+    #[serde(default)]
+    pub package_runner: Option<String>,
+    /// Provider of agents spawned without one; Claude if unset
+    #[serde(default)]
+    pub default_provider_id: Option<String>,
+    /// Environment variables set from the OS keychain for locally spawned
+    /// agents, e.g. ANTHROPIC_API_KEY
+    #[serde(default)]
+    pub keychain_variables: Vec<String>,
+    /// System-wide shortcuts that answer the newest permission request
+    #[serde(default)]
+    pub permission_shortcuts: PermissionShortcuts,
+    /// Let agents set and remove scratchpad notes with lines in their replies
+    #[serde(default)]
+    pub scratchpad_writes: bool,
+}
+
+impl Settings {
+    /// Package runner for agents started locally, or over SSH where the
+    /// local platform's default doesn't apply
+    pub fn package_runner(&self, local: bool) -> String {
+        match &self.package_runner {
+            Some(runner) => runner.clone(),
+            None if local => default_package_runner().to_string(),
+            None => "npx".to_string(),
+        }
+    }
+}
+
+pub struct SettingsStore {
+    settings: RwLock<Settings>,
+    storage_path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn new() -> Self {
+        let storage_path = Self::get_storage_path();
+        let settings = Self::load_from_file(&storage_path).unwrap_or_default();
+
+        Self {
+            settings: RwLock::new(settings),
+            storage_path,
+        }
+    }
+
+    fn get_storage_path() -> PathBuf {
+        let app_dir = app_data_dir();
+        fs::create_dir_all(&app_dir).ok();
+
+        app_dir.join(SETTINGS_FILE)
+    }
+
+    fn load_from_file(path: &Path) -> Option<Settings> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save_to_file(&self, settings: &Settings) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+        fs::write(&self.storage_path, content)
+            .map_err(|e| format!("Failed to write settings file: {}", e))?;
+
+        Ok(())
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn update(&self, settings: Settings) -> Result<Settings, String> {
+        self.save_to_file(&settings)?;
+        *self.settings.write().unwrap() = settings.clone();
+        Ok(settings)
+    }
+}
+
+impl Default for SettingsStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
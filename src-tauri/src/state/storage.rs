@@ -0,0 +1,335 @@
+//! Where the app keeps its data, and whether it can write there, checked
+//! once at startup. Without a writable directory the layout, pins and
+//! provider overrides only live in memory for the session, and downloads go
+//! to a temporary directory.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Environment variable naming the data directory, for all other ways
+pub const DATA_DIR_VAR: &str = "ACPTORIO_DATA_DIR";
+/// File next to the executable that keeps the data beside it, in
+/// [`PORTABLE_DATA_DIR`]
+pub const PORTABLE_MARKER: &str = "portable";
+const PORTABLE_DATA_DIR: &str = "data";
+/// In the default data directory, holds the path data was moved to
+const MOVED_FILE: &str = "data-dir";
+const PROBE_FILE: &str = ".write-probe";
+/// Left in place by a move: git keeps the absolute paths of worktrees
+const UNMOVED_ENTRIES: &[&str] = &[MOVED_FILE, PROBE_FILE, "worktrees"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    Default,
+    /// Named by [`DATA_DIR_VAR`]
+    Environment,
+    /// Beside the executable
+    Portable,
+    /// Moved with `migrate_data_dir`
+    Moved,
+}
+
+static DATA_DIR: Lazy<(PathBuf, DataDirSource)> = Lazy::new(resolve_data_dir);
+
+/// Set once the data was moved; the old directory gets no new files until
+/// the restart
+static MOVED_AWAY: AtomicBool = AtomicBool::new(false);
+
+static STATUS: Lazy<StorageStatus> = Lazy::new(|| {
+    let status = check_storage_in(&app_data_dir());
+    if let Some(error) = &status.error {
+        tracing::warn!(
+            "App data directory {} is not writable, keeping data in memory: {}",
+            status.data_dir,
+            error
+        );
+    }
+    status
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageStatus {
+    pub data_dir: String,
+    pub source: DataDirSource,
+    pub writable: bool,
+    /// Why the directory can't be written
+    pub error: Option<String>,
+    /// Where downloaded binaries and icons go
+    pub cache_dir: String,
+}
+
+/// Result of moving the data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataDirMove {
+    pub from: String,
+    pub to: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+fn default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("acptorio")
+}
+
+fn resolve_data_dir() -> (PathBuf, DataDirSource) {
+    if let Some(dir) = std::env::var_os(DATA_DIR_VAR).filter(|v| !v.is_empty()) {
+        return (PathBuf::from(dir), DataDirSource::Environment);
+    }
+    let portable = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .filter(|dir| dir.join(PORTABLE_MARKER).exists());
+    if let Some(dir) = portable {
+        return (dir.join(PORTABLE_DATA_DIR), DataDirSource::Portable);
+    }
+    let default = default_data_dir();
+    match moved_to(&default) {
+        Some(dir) => (dir, DataDirSource::Moved),
+        None => (default, DataDirSource::Default),
+    }
+}
+
+fn moved_to(default: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(default.join(MOVED_FILE)).ok()?;
+    let path = content.trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Directory the app keeps its data in
+pub fn app_data_dir() -> PathBuf {
+    DATA_DIR.0.clone()
+}
+
+fn check_storage_in(dir: &Path) -> StorageStatus {
+    let probe = dir.join(PROBE_FILE);
+    let written = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe));
+    let cache_dir = match written {
+        Ok(()) => dir.to_path_buf(),
+        Err(_) => std::env::temp_dir().join("acptorio"),
+    };
+    StorageStatus {
+        data_dir: dir.display().to_string(),
+        source: DATA_DIR.1,
+        writable: written.is_ok(),
+        error: written.err().map(|e| e.to_string()),
+        cache_dir: cache_dir.display().to_string(),
+    }
+}
+
+pub fn storage_status() -> StorageStatus {
+    STATUS.clone()
+}
+
+/// Whether stores should write their files
+pub fn storage_writable() -> bool {
+    STATUS.writable && !MOVED_AWAY.load(Ordering::Relaxed)
+}
+
+/// Directory for data that can be downloaded again, in the data directory
+/// if it is writable
+pub fn cache_dir() -> PathBuf {
+    PathBuf::from(&STATUS.cache_dir)
+}
+
+/// Move the app's data to `to`, which takes effect on the next start. Fails
+/// without touching the current data if anything can't be copied.
+pub fn move_data_dir(to: &Path) -> Result<DataDirMove, String> {
+    match DATA_DIR.1 {
+        DataDirSource::Environment => {
+            return Err(format!(
+                "The data directory is set by {}; change it instead",
+                DATA_DIR_VAR
+            ))
+        }
+        DataDirSource::Portable => {
+            return Err("The data directory of a portable install can't be moved".to_string())
+        }
+        DataDirSource::Default | DataDirSource::Moved => {}
+    }
+    let moved = move_data_dir_in(&default_data_dir(), &app_data_dir(), to)?;
+    MOVED_AWAY.store(true, Ordering::Relaxed);
+    Ok(moved)
+}
+
+fn move_data_dir_in(default: &Path, from: &Path, to: &Path) -> Result<DataDirMove, String> {
+    if !to.is_absolute() {
+        return Err(format!("Not an absolute path: {}", to.display()));
+    }
+    if to == from {
+        return Err("The data is already there".to_string());
+    }
+    if to.starts_with(from) || from.starts_with(to) {
+        return Err(
+            "The new data directory can't contain or be inside the current one".to_string(),
+        );
+    }
+
+    let entries: Vec<fs::DirEntry> = match fs::read_dir(from) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|e| !UNMOVED_ENTRIES.iter().any(|name| e.file_name() == *name))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    if let Some(taken) = entries.iter().find(|e| to.join(e.file_name()).exists()) {
+        return Err(format!("{} already exists", to.join(taken.file_name()).display()));
+    }
+
+    let mut moved = DataDirMove {
+        from: from.display().to_string(),
+        to: to.display().to_string(),
+        files: 0,
+        bytes: 0,
+    };
+    let remove_copies = || {
+        for entry in &entries {
+            let _ = remove_entry(&to.join(entry.file_name()));
+        }
+    };
+    let copied = entries
+        .iter()
+        .try_for_each(|e| copy_entry(&e.path(), &to.join(e.file_name()), &mut moved));
+    if let Err(e) = copied {
+        remove_copies();
+        return Err(format!("Failed to copy the data to {}: {}", to.display(), e));
+    }
+
+    let pointer = default.join(MOVED_FILE);
+    let pointed = if to == default {
+        fs::remove_file(&pointer).or_else(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })
+    } else {
+        fs::create_dir_all(default).and_then(|_| fs::write(&pointer, to.display().to_string()))
+    };
+    if let Err(e) = pointed {
+        remove_copies();
+        return Err(format!("Failed to record the new data directory: {}", e));
+    }
+
+    // The copy is complete, so a leftover in the old directory loses nothing
+    for entry in &entries {
+        if let Err(e) = remove_entry(&entry.path()) {
+            tracing::warn!("Failed to remove {}: {}", entry.path().display(), e);
+        }
+    }
+    Ok(moved)
+}
+
+fn copy_entry(from: &Path, to: &Path, moved: &mut DataDirMove) -> std::io::Result<()> {
+    if fs::symlink_metadata(from)?.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_entry(&entry.path(), &to.join(entry.file_name()), moved)?;
+        }
+    } else {
+        moved.bytes += fs::copy(from, to)?;
+        moved.files += 1;
+    }
+    Ok(())
+}
+
+fn remove_entry(path: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_below_a_file_is_not_writable() {
+        let dir = std::env::temp_dir().join(format!("acptorio-storage-{}", uuid::Uuid::new_v4()));
+        let status = check_storage_in(&dir);
+        assert!(status.writable);
+        assert_eq!(status.cache_dir, status.data_dir);
+        assert!(!dir.join(PROBE_FILE).exists());
+
+        let blocker = dir.join("file");
+        fs::write(&blocker, "").unwrap();
+        let status = check_storage_in(&blocker.join("acptorio"));
+        assert!(!status.writable);
+        assert!(status.error.is_some());
+        assert_ne!(status.cache_dir, status.data_dir);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_data_dir_copies_and_records_new_location() {
+        let root = std::env::temp_dir().join(format!("acptorio-move-{}", uuid::Uuid::new_v4()));
+        let default = root.join("default");
+        let synced = root.join("synced");
+        fs::create_dir_all(default.join("transcripts")).unwrap();
+        fs::create_dir_all(default.join("worktrees").join("app")).unwrap();
+        fs::write(default.join("settings.json"), "{}").unwrap();
+        fs::write(default.join("transcripts").join("a.txt"), "answer").unwrap();
+
+        let moved = move_data_dir_in(&default, &default, &synced).unwrap();
+        assert_eq!((moved.files, moved.bytes), (2, 8));
+        assert_eq!(
+            fs::read_to_string(synced.join("transcripts").join("a.txt")).unwrap(),
+            "answer"
+        );
+        assert!(!default.join("settings.json").exists());
+        assert!(default.join("worktrees").join("app").exists());
+        assert_eq!(moved_to(&default), Some(synced.clone()));
+
+        // Moving back is refused over existing files and drops the pointer
+        fs::write(default.join("settings.json"), "{}").unwrap();
+        assert!(move_data_dir_in(&default, &synced, &default).is_err());
+        fs::remove_file(default.join("settings.json")).unwrap();
+        move_data_dir_in(&default, &synced, &default).unwrap();
+        assert_eq!(moved_to(&default), None);
+        assert!(default.join("settings.json").exists());
+
+        assert!(move_data_dir_in(&default, &default, &default.join("inner")).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_move_data_dir_with_open_database() {
+        use crate::state::database::Database;
+
+        let root = std::env::temp_dir().join(format!("acptorio-move-{}", uuid::Uuid::new_v4()));
+        let default = root.join("default");
+        let synced = root.join("synced");
+        fs::create_dir_all(&default).unwrap();
+        let db = Database::open(&default.join("acptorio.db")).unwrap();
+        let insert = "INSERT INTO usage_samples (at, tokens, cost_cents) VALUES (?1, 10, 1)";
+        let now = crate::clock::now_secs() as i64;
+        db.conn().execute(insert, [now]).unwrap();
+        assert!(default.join("acptorio.db-wal").exists());
+
+        db.close().unwrap();
+        assert!(!default.join("acptorio.db-wal").exists());
+        move_data_dir_in(&default, &default, &synced).unwrap();
+        // Writes after closing don't reach the moved file
+        db.conn().execute(insert, [now]).unwrap();
+
+        let moved = Database::open(&synced.join("acptorio.db")).unwrap();
+        let count: i64 = moved
+            .conn()
+            .query_row("SELECT COUNT(*) FROM usage_samples", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        drop(moved);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
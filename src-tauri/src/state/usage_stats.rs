@@ -0,0 +1,184 @@
+//! Usage counts kept on this machine only: which commands the frontend
+//! invokes, how many agents of each provider are spawned and which errors
+//! happen. Nothing is sent anywhere; the user can export the counts to
+//! attach them to a report.
+
+use super::storage::{app_data_dir, storage_writable};
+use crate::clock::now_secs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const USAGE_FILE: &str = "usage-stats.json";
+/// Least time between writes of the counts; they are also written on exit
+const SAVE_INTERVAL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// When counting started, in seconds
+    #[serde(default)]
+    pub since: u64,
+    /// Command -> times invoked
+    #[serde(default)]
+    pub features: BTreeMap<String, u64>,
+    /// Provider id -> agents spawned
+    #[serde(default)]
+    pub spawns: BTreeMap<String, u64>,
+    /// Error kind -> times it happened
+    #[serde(default)]
+    pub errors: BTreeMap<String, u64>,
+}
+
+/// What `export_usage_stats` writes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub app_version: String,
+    pub os: String,
+    pub exported_at: u64,
+    pub stats: UsageStats,
+}
+
+pub struct UsageStatsStore {
+    stats: Mutex<UsageStats>,
+    storage_path: PathBuf,
+    last_saved: AtomicU64,
+}
+
+impl UsageStatsStore {
+    pub fn new() -> Self {
+        Self::at(app_data_dir().join(USAGE_FILE))
+    }
+
+    fn at(storage_path: PathBuf) -> Self {
+        let stats = fs::read_to_string(&storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_else(|| UsageStats {
+                since: now_secs(),
+                ..Default::default()
+            });
+        Self {
+            stats: Mutex::new(stats),
+            storage_path,
+            last_saved: AtomicU64::new(now_secs()),
+        }
+    }
+
+    pub fn record_feature(&self, command: &str) {
+        self.bump(|stats| &mut stats.features, command);
+    }
+
+    pub fn record_spawn(&self, provider_id: &str) {
+        self.bump(|stats| &mut stats.spawns, provider_id);
+    }
+
+    pub fn record_error(&self, kind: &str) {
+        self.bump(|stats| &mut stats.errors, kind);
+    }
+
+    fn bump(&self, counts: impl FnOnce(&mut UsageStats) -> &mut BTreeMap<String, u64>, key: &str) {
+        {
+            let mut stats = self.stats.lock().unwrap();
+            *counts(&mut stats).entry(key.to_string()).or_default() += 1;
+        }
+        let last_saved = self.last_saved.load(Ordering::Relaxed);
+        if now_secs().saturating_sub(last_saved) >= SAVE_INTERVAL_SECS {
+            self.save();
+        }
+    }
+
+    pub fn get(&self) -> UsageStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Start counting from zero
+    pub fn reset(&self) -> UsageStats {
+        let stats = UsageStats {
+            since: now_secs(),
+            ..Default::default()
+        };
+        *self.stats.lock().unwrap() = stats.clone();
+        self.save();
+        stats
+    }
+
+    pub fn save(&self) {
+        self.last_saved.store(now_secs(), Ordering::Relaxed);
+        if !storage_writable() {
+            return;
+        }
+        let written = serde_json::to_string_pretty(&self.get())
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.storage_path, content).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write usage stats: {}", e);
+        }
+    }
+
+    /// Write the counts to `path` for attaching to a report
+    pub fn export(&self, path: &Path) -> Result<UsageReport, String> {
+        let report = UsageReport {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            exported_at: now_secs(),
+            stats: self.get(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("Failed to serialize usage stats: {}", e))?;
+        fs::write(path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(report)
+    }
+}
+
+impl Default for UsageStatsStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where exports go when no path is given
+pub fn default_usage_export_path() -> PathBuf {
+    app_data_dir()
+        .join("diagnostics")
+        .join(format!("acptorio-usage-{}.json", now_secs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_survive_reopening() {
+        let dir = std::env::temp_dir().join(format!("acptorio-usage-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(USAGE_FILE);
+
+        let store = UsageStatsStore::at(path.clone());
+        store.record_feature("send_prompt");
+        store.record_feature("send_prompt");
+        store.record_spawn("gemini");
+        store.record_error("agent_error");
+        fs::write(&path, serde_json::to_string(&store.get()).unwrap()).unwrap();
+
+        let reopened = UsageStatsStore::at(path.clone());
+        assert_eq!(reopened.get().features["send_prompt"], 2);
+        assert_eq!(reopened.get().spawns["gemini"], 1);
+
+        let report = reopened
+            .export(&dir.join("export").join("usage.json"))
+            .unwrap();
+        assert_eq!(report.stats.errors["agent_error"], 1);
+        assert!(dir.join("export").join("usage.json").exists());
+        assert!(reopened.reset().features.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
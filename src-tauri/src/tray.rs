@@ -0,0 +1,221 @@
+//! System tray icon with agent status and quick actions.
+
+use crate::agent::{AgentStatus, AgentStatusSummary, PendingPermissionInfo};
+use crate::events::TrackedEmitter;
+use crate::state::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::menu::{Menu, MenuBuilder, MenuEvent, SubmenuBuilder};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Listener, Manager};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+const TRAY_ID: &str = "main";
+
+/// Events after which the tray menu is rebuilt
+const REFRESH_EVENTS: &[&str] = &[
+    "agent-spawned",
+    "agent-stopped",
+    "agent-update",
+    "agent-status-changed",
+    "all-agents-stopped",
+    "permission-responded",
+    "permission-cancelled",
+    "permission-escalated",
+];
+
+fn status_glyph(status: AgentStatus) -> &'static str {
+    match status {
+        AgentStatus::Initializing => "◌",
+        AgentStatus::Idle => "○",
+        AgentStatus::Working => "●",
+        AgentStatus::Paused => "⏸",
+        AgentStatus::NeedsAuth => "⚿",
+        AgentStatus::Error => "✕",
+        AgentStatus::Stopped => "■",
+    }
+}
+
+fn build_menu(
+    app: &AppHandle,
+    agents: &[AgentStatusSummary],
+    pending: &[PendingPermissionInfo],
+) -> tauri::Result<Menu<tauri::Wry>> {
+    let mut menu = MenuBuilder::new(app)
+        .text("header", format!("{} agents · {} pending", agents.len(), pending.len()))
+        .separator();
+
+    if !pending.is_empty() {
+        menu = menu
+            .text("approve-latest", "Approve latest permission")
+            .text("reject-latest", "Reject latest permission")
+            .separator();
+    }
+
+    for agent in agents {
+        let mut submenu = SubmenuBuilder::new(
+            app,
+            format!("{} {}", status_glyph(agent.status), agent.name),
+        );
+        // Most recent request first
+        for input in pending.iter().rev().filter(|p| p.agent_id == agent.id) {
+            submenu = submenu
+                .text(format!("label:{}", input.input_id), &input.message)
+                .text(format!("approve:{}:{}", agent.id, input.input_id), "Approve")
+                .text(format!("reject:{}:{}", agent.id, input.input_id), "Reject")
+                .separator();
+        }
+        let submenu = submenu
+            .text(format!("stop:{}", agent.id), "Stop agent")
+            .build()?;
+        menu = menu.item(&submenu);
+    }
+
+    if !agents.is_empty() {
+        menu = menu.separator();
+    }
+    menu.text("open", "Open acptorio")
+        .text("stop-all", "Stop all agents")
+        .separator()
+        .text("quit", "Quit")
+        .build()
+}
+
+/// Rebuild the tray menu and badge from the current agent pool
+pub async fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    let agents = state.agent_pool.agent_statuses();
+    let pending = state.agent_pool.get_pending_permissions().list();
+
+    match build_menu(app, &agents, &pending) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => tracing::warn!("Failed to build tray menu: {}", e),
+    }
+
+    let badge = (!pending.is_empty()).then(|| pending.len().to_string());
+    let _ = tray.set_title(badge.as_deref());
+    let _ = tray.set_tooltip(Some(format!(
+        "acptorio: {} agents, {} pending permissions",
+        agents.len(),
+        pending.len()
+    )));
+}
+
+async fn respond(app: &AppHandle, agent_id: &str, input_id: &str, approved: bool) {
+    let Ok(id) = Uuid::parse_str(agent_id) else {
+        return;
+    };
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    if let Err(e) = state
+        .agent_pool
+        .respond_to_permission(&id, input_id, approved, None, false)
+    {
+        tracing::warn!("Tray permission response failed: {}", e);
+        return;
+    }
+    state.resolve_stale_permissions(id);
+
+    let _ = app.emit_tracked("permission-responded", serde_json::json!({
+        "agent_id": agent_id,
+        "input_id": input_id,
+        "approved": approved,
+    }));
+}
+
+/// Answer the newest pending permission, used where the app has no focus
+pub fn respond_to_latest(app: &AppHandle, approved: bool) {
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    match state.agent_pool.respond_to_latest_permission(approved) {
+        Ok(permission) => {
+            state.resolve_stale_permissions(permission.agent_id);
+            let _ = app.emit_tracked("permission-responded", serde_json::json!({
+                "agent_id": permission.agent_id.to_string(),
+                "input_id": permission.input_id,
+                "approved": approved,
+            }));
+        }
+        Err(e) => tracing::info!("No permission to respond to: {}", e),
+    }
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref().to_string();
+    let app = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let parts: Vec<&str> = id.splitn(3, ':').collect();
+        match parts.as_slice() {
+            ["approve", agent_id, input_id] => respond(&app, agent_id, input_id, true).await,
+            ["reject", agent_id, input_id] => respond(&app, agent_id, input_id, false).await,
+            ["approve-latest"] => respond_to_latest(&app, true),
+            ["reject-latest"] => respond_to_latest(&app, false),
+            ["stop", agent_id] => {
+                if let Ok(agent_id) = Uuid::parse_str(agent_id) {
+                    let state = app.state::<Arc<AppState>>().inner().clone();
+                    if state.agent_pool.stop_agent(&agent_id).await.is_ok() {
+                        let _ = app.emit_tracked(
+                            "agent-stopped",
+                            serde_json::json!({ "agent_id": agent_id }),
+                        );
+                    }
+                }
+            }
+            ["stop-all"] => {
+                let state = app.state::<Arc<AppState>>().inner().clone();
+                if state.agent_pool.stop_all().await.is_ok() {
+                    let _ = app.emit_tracked("all-agents-stopped", ());
+                }
+            }
+            ["open"] => show_main_window(&app),
+            ["quit"] => app.exit(0),
+            _ => {}
+        }
+    });
+}
+
+/// Bring the main window to the front, also when hidden or minimized
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Create the tray icon and keep it in sync with agent activity
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app, &[], &[])?;
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip("acptorio")
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    // Coalesce bursts of events (e.g. streaming updates) into one refresh
+    let dirty = Arc::new(Notify::new());
+    for event in REFRESH_EVENTS {
+        let dirty = dirty.clone();
+        app.listen_any(*event, move |_| dirty.notify_one());
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            dirty.notified().await;
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            refresh(&app).await;
+        }
+    });
+
+    Ok(())
+}
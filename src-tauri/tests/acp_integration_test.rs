@@ -5,6 +5,7 @@
 //!
 //! Note: Some tests require ANTHROPIC_API_KEY to be set.
 
+use acptorio_lib::acp::methods;
 use std::process::Stdio;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
@@ -54,7 +55,7 @@ async fn test_initialize_handshake() {
     let request = serde_json::json!({
         "jsonrpc": "2.0",
         "id": 1,
-        "method": "initialize",
+        "method": methods::INITIALIZE,
         "params": {
             "protocolVersion": 1,
             "capabilities": {},
@@ -169,7 +170,7 @@ async fn test_full_prompt_flow() {
     let init_req = serde_json::json!({
         "jsonrpc": "2.0",
         "id": request_id,
-        "method": "initialize",
+        "method": methods::INITIALIZE,
         "params": {
             "protocolVersion": 1,
             "capabilities": {},
@@ -186,7 +187,7 @@ async fn test_full_prompt_flow() {
     let session_req = serde_json::json!({
         "jsonrpc": "2.0",
         "id": request_id,
-        "method": "session/new",
+        "method": methods::SESSION_NEW,
         "params": {
             "cwd": "/tmp",
             "mcpServers": []
@@ -205,7 +206,7 @@ async fn test_full_prompt_flow() {
     let prompt_req = serde_json::json!({
         "jsonrpc": "2.0",
         "id": request_id,
-        "method": "session/prompt",
+        "method": methods::SESSION_PROMPT,
         "params": {
             "sessionId": session_id,
             "prompt": [{"type": "text", "text": "Say hello in one word"}]
@@ -3,14 +3,17 @@
 //! Run with: cargo test --test agent_process_test -- --nocapture
 //! Some tests require ANTHROPIC_API_KEY
 
-use acptorio_lib::agent::{AgentProcess, AgentStatus, AgentUpdate, PendingPermissions};
+use acptorio_lib::agent::{
+    default_package_runner, AgentProcess, AgentStatus, AgentUpdate, PendingPermissions,
+};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
 /// Test spawning an agent process
 #[tokio::test]
 async fn test_spawn_agent() {
-    let result = AgentProcess::spawn("test-agent".into(), "/tmp".into()).await;
+    let result =
+        AgentProcess::spawn("test-agent".into(), "/tmp".into(), default_package_runner()).await;
 
     match result {
         Ok(agent) => {
@@ -28,9 +31,10 @@ async fn test_spawn_agent() {
 /// Test initialize handshake
 #[tokio::test]
 async fn test_initialize_agent() {
-    let mut agent = AgentProcess::spawn("test-agent".into(), "/tmp".into())
-        .await
-        .expect("Failed to spawn");
+    let mut agent =
+        AgentProcess::spawn("test-agent".into(), "/tmp".into(), default_package_runner())
+            .await
+            .expect("Failed to spawn");
 
     let result = agent.initialize().await;
 
@@ -50,9 +54,10 @@ async fn test_initialize_agent() {
 /// Test session creation
 #[tokio::test]
 async fn test_create_session() {
-    let mut agent = AgentProcess::spawn("test-agent".into(), "/tmp".into())
-        .await
-        .expect("Failed to spawn");
+    let mut agent =
+        AgentProcess::spawn("test-agent".into(), "/tmp".into(), default_package_runner())
+            .await
+            .expect("Failed to spawn");
 
     agent.initialize().await.expect("Initialize failed");
 
@@ -76,9 +81,10 @@ async fn test_create_session() {
 #[tokio::test]
 #[ignore] // Requires API key and makes real API call
 async fn test_send_prompt() {
-    let mut agent = AgentProcess::spawn("test-agent".into(), "/tmp".into())
-        .await
-        .expect("Failed to spawn");
+    let mut agent =
+        AgentProcess::spawn("test-agent".into(), "/tmp".into(), default_package_runner())
+            .await
+            .expect("Failed to spawn");
 
     agent.initialize().await.expect("Initialize failed");
     let session_id = agent.create_session().await.expect("Session create failed");
@@ -123,9 +129,10 @@ async fn test_send_prompt() {
 /// Test stopping an agent
 #[tokio::test]
 async fn test_stop_agent() {
-    let mut agent = AgentProcess::spawn("test-agent".into(), "/tmp".into())
-        .await
-        .expect("Failed to spawn");
+    let mut agent =
+        AgentProcess::spawn("test-agent".into(), "/tmp".into(), default_package_runner())
+            .await
+            .expect("Failed to spawn");
 
     agent.initialize().await.expect("Initialize failed");
 
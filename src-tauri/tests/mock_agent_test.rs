@@ -0,0 +1,309 @@
+//! ACP conformance tests against a scripted in-process agent
+//!
+//! Run with: cargo test --test mock_agent_test
+//! Needs no npx, network or API keys.
+
+use acptorio_lib::acp::methods;
+use acptorio_lib::acp::scripted::{ScriptStep, ScriptedAgent, ScriptedAgentHandle};
+use acptorio_lib::acp::{RequestPolicies, RequestPolicy, Transport, PROTOCOL_VIOLATIONS};
+use acptorio_lib::agent::{
+    process_session_update, AgentPool, AgentProcess, AgentProcessError, AgentStatus, AgentUpdate,
+    PendingPermissions, PermissionUserResponse, SpawnConfig,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+fn connect(script: ScriptedAgent) -> (AgentProcess, ScriptedAgentHandle) {
+    let (stream, handle) = script.connect();
+    (AgentProcess::connect("mock".into(), "/tmp".into(), stream), handle)
+}
+
+fn tool_call(id: &str, status: &str) -> ScriptStep {
+    ScriptStep::Update(json!({
+        "sessionUpdate": "tool_call",
+        "toolCallId": id,
+        "title": "Edit src/main.rs",
+        "kind": "edit",
+        "status": status,
+        "rawInput": { "file_path": "/tmp/src/main.rs" },
+    }))
+}
+
+fn permission_request() -> ScriptStep {
+    ScriptStep::RequestPermission(json!({
+        "toolCall": { "toolCallId": "tc-1", "title": "Edit src/main.rs" },
+        "options": [
+            { "optionId": "allow", "name": "Allow", "kind": "allow_once" },
+            { "optionId": "reject", "name": "Reject", "kind": "reject_once" },
+        ],
+    }))
+}
+
+#[tokio::test]
+async fn test_initialize_reads_capabilities_and_sends_initialized() {
+    let (mut agent, handle) = connect(
+        ScriptedAgent::new()
+            .capabilities(json!({ "loadSession": true, "sessionCapabilities": { "list": {} } }))
+            .auth_methods(json!([{ "id": "oauth", "name": "Log in" }])),
+    );
+
+    agent.initialize().await.unwrap();
+    assert_eq!(agent.status, AgentStatus::Idle);
+    assert!(agent.supports_load_session);
+    assert!(agent.supports_list_sessions);
+    assert_eq!(agent.auth_methods.len(), 1);
+
+    let session_id = agent.create_session().await.unwrap();
+    assert_eq!(session_id, "scripted-session");
+
+    drop(agent);
+    assert_eq!(
+        handle.methods(),
+        vec![methods::INITIALIZE, methods::INITIALIZED, methods::SESSION_NEW]
+    );
+    handle.finished().await;
+}
+
+#[tokio::test]
+async fn test_prompt_accumulates_text_and_records_tool_calls() {
+    let (mut agent, _handle) = connect(ScriptedAgent::new().stop_reason("max_tokens").turn(vec![
+        ScriptStep::message("Editing "),
+        tool_call("tc-1", "in_progress"),
+        ScriptStep::message("done"),
+    ]));
+    agent.initialize().await.unwrap();
+    agent.create_session().await.unwrap();
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let text = agent
+        .send_prompt("fix it", tx, Arc::new(PendingPermissions::new()))
+        .await
+        .unwrap();
+
+    assert_eq!(text, "Editing done");
+    assert_eq!(agent.status, AgentStatus::Idle);
+    assert_eq!(agent.last_stop_reason.as_deref(), Some("max_tokens"));
+    assert!(agent.tool_calls.get("tc-1").is_some());
+
+    let mut update_types = Vec::new();
+    while let Ok(update) = rx.try_recv() {
+        update_types.push(update.update_type);
+    }
+    assert!(update_types.iter().any(|t| t == "tool_call"), "{:?}", update_types);
+}
+
+#[tokio::test]
+async fn test_permission_answer_is_sent_back_to_agent() {
+    let (mut agent, handle) = connect(
+        ScriptedAgent::new().turn(vec![permission_request(), ScriptStep::message("written")]),
+    );
+    agent.initialize().await.unwrap();
+    agent.create_session().await.unwrap();
+
+    let pending = Arc::new(PendingPermissions::new());
+    let (tx, mut rx) = mpsc::channel::<AgentUpdate>(32);
+    let answer = {
+        let pending = pending.clone();
+        tokio::spawn(async move {
+            while let Some(update) = rx.recv().await {
+                if update.update_type == "permission_request" {
+                    let request = pending.latest().expect("request is stored before the update");
+                    pending
+                        .respond(
+                            request.agent_id,
+                            &request.input_id,
+                            PermissionUserResponse { approved: false, option_id: None },
+                        )
+                        .unwrap();
+                }
+            }
+        })
+    };
+
+    let text = agent.send_prompt("write", tx, pending.clone()).await.unwrap();
+    assert_eq!(text, "written");
+    assert!(!agent.has_pending_inputs());
+    answer.await.unwrap();
+
+    let answer = handle
+        .received()
+        .into_iter()
+        .find(|m| m.get("result").is_some())
+        .expect("permission answer");
+    assert_eq!(answer["result"]["outcome"]["optionId"], "reject");
+}
+
+#[tokio::test]
+async fn test_malformed_line_is_recorded_and_prompt_completes() {
+    let (mut agent, _handle) = connect(ScriptedAgent::new().turn(vec![
+        ScriptStep::Raw("{not json".to_string()),
+        ScriptStep::message("still here"),
+    ]));
+    agent.initialize().await.unwrap();
+    agent.create_session().await.unwrap();
+
+    let (tx, _rx) = mpsc::channel(32);
+    let text = agent
+        .send_prompt("hi", tx, Arc::new(PendingPermissions::new()))
+        .await
+        .unwrap();
+    assert_eq!(text, "still here");
+    assert_eq!(PROTOCOL_VIOLATIONS.counts().get(&agent.id), Some(&1));
+}
+
+#[tokio::test]
+async fn test_prompt_records_activity_timestamps() {
+    let (mut agent, _handle) = connect(ScriptedAgent::new().turn(vec![ScriptStep::message("hi")]));
+    agent.initialize().await.unwrap();
+    agent.create_session().await.unwrap();
+    let info = agent.info();
+    assert!(info.created_at > 0);
+    assert_eq!(info.last_prompt_at, None);
+    assert_eq!(info.last_update_at, None);
+
+    let (tx, _rx) = mpsc::channel(32);
+    agent
+        .send_prompt("go", tx, Arc::new(PendingPermissions::new()))
+        .await
+        .unwrap();
+    let info = agent.info();
+    let prompted = info.last_prompt_at.unwrap();
+    assert!(prompted >= info.created_at);
+    assert!(info.last_update_at.unwrap() >= prompted);
+}
+
+#[tokio::test]
+async fn test_unanswered_request_times_out() {
+    let (mut agent, _handle) = connect(ScriptedAgent::new().ignore(methods::INITIALIZE));
+    agent.set_request_policies(RequestPolicies::new(HashMap::from([(
+        methods::INITIALIZE.to_string(),
+        RequestPolicy { timeout_secs: Some(1), retries: 1 },
+    )])));
+
+    match agent.initialize().await {
+        Err(AgentProcessError::Timeout { method, secs }) => {
+            assert_eq!(method, methods::INITIALIZE);
+            assert_eq!(secs, 1);
+        }
+        other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn test_updates_match_message_processor() {
+    let update = tool_call("tc-2", "pending");
+    let ScriptStep::Update(raw) = update.clone() else { unreachable!() };
+    let (mut agent, _handle) = connect(ScriptedAgent::new().turn(vec![update]));
+    agent.initialize().await.unwrap();
+    agent.create_session().await.unwrap();
+
+    let (tx, mut rx) = mpsc::channel(32);
+    agent
+        .send_prompt("go", tx, Arc::new(PendingPermissions::new()))
+        .await
+        .unwrap();
+    let mut from_process = Vec::new();
+    while let Ok(update) = rx.try_recv() {
+        from_process.push(update.update_type);
+    }
+
+    let params = json!({ "sessionId": "scripted-session", "update": raw });
+    let processed = process_session_update(agent.id, &params, None);
+    let from_processor: Vec<String> =
+        processed.updates.into_iter().map(|u| u.update_type).collect();
+    assert_eq!(from_process, from_processor);
+}
+
+#[tokio::test]
+async fn test_pool_runs_scripted_agent() {
+    let pool = AgentPool::new();
+    let (stream, handle) = ScriptedAgent::new()
+        .capabilities(json!({ "sessionCapabilities": { "list": {} } }))
+        .sessions(vec![json!({ "sessionId": "old", "cwd": "/tmp", "title": "Earlier work" })])
+        .turn(vec![ScriptStep::message("pooled")])
+        .connect();
+    let info = pool
+        .add_agent(AgentProcess::connect("pooled".into(), "/tmp".into(), stream))
+        .await
+        .unwrap();
+    assert_eq!(info.session_id.as_deref(), Some("scripted-session"));
+    assert_eq!(pool.agent_count(), 1);
+
+    let sessions = pool.list_sessions(&info.id).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].title.as_deref(), Some("Earlier work"));
+
+    let (tx, _rx) = mpsc::channel(32);
+    let text = pool.send_prompt(info.id, "hello", tx).await.unwrap();
+    assert_eq!(text, "pooled");
+
+    pool.stop_agent(&info.id).await.unwrap();
+    assert_eq!(pool.agent_count(), 0);
+    handle.finished().await;
+}
+
+#[tokio::test]
+async fn test_pool_keeps_agent_that_needs_auth() {
+    let pool = AgentPool::new();
+    let (stream, _handle) = ScriptedAgent::new()
+        .auth_methods(json!([{ "id": "oauth", "name": "Log in" }]))
+        .require_auth()
+        .connect();
+    let info = pool
+        .add_agent(AgentProcess::connect("locked".into(), "/tmp".into(), stream))
+        .await
+        .unwrap();
+    assert_eq!(info.status, AgentStatus::NeedsAuth);
+    assert!(info.needs_auth);
+    assert_eq!(info.auth_methods[0].id, "oauth");
+    assert_eq!(info.session_id, None);
+
+    assert!(pool.start_auth(&info.id, "oauth").await.unwrap().completed);
+    let session_id = pool.create_session(&info.id).await.unwrap();
+    assert_eq!(session_id, "scripted-session");
+    let info = pool.get_agent_info(&info.id).unwrap();
+    assert_eq!(info.status, AgentStatus::Idle);
+    assert!(!info.needs_auth);
+}
+
+#[tokio::test]
+async fn test_tcp_transport_connects_to_remote_agent() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let (mut stream, _handle) = ScriptedAgent::new()
+            .turn(vec![ScriptStep::message("over tcp")])
+            .connect();
+        let _ = tokio::io::copy_bidirectional(&mut socket, &mut stream).await;
+    });
+
+    let mut agent = AgentProcess::spawn_with_config(SpawnConfig {
+        name: "remote".into(),
+        working_directory: "/tmp".into(),
+        provider_id: None,
+        provider_name: None,
+        command: String::new(),
+        args: Vec::new(),
+        transport: Transport::Tcp { address },
+        sandbox: None,
+        limits: None,
+        env: HashMap::new(),
+        default_model: None,
+        auth_method: None,
+    })
+    .await
+    .unwrap();
+    agent.initialize().await.unwrap();
+    agent.create_session().await.unwrap();
+
+    let (tx, _rx) = mpsc::channel(32);
+    let text = agent
+        .send_prompt("hi", tx, Arc::new(PendingPermissions::new()))
+        .await
+        .unwrap();
+    assert_eq!(text, "over tcp");
+    agent.stop().await.unwrap();
+}
@@ -5,6 +5,7 @@
 //!
 //! Note: Requires network access and npx installed.
 
+use acptorio_lib::acp::methods;
 use std::collections::HashMap;
 use std::process::Stdio;
 use std::time::Duration;
@@ -160,7 +161,7 @@ async fn spawn_and_verify_agent(agent: &RegistryAgent) -> Result<(), String> {
     let request = serde_json::json!({
         "jsonrpc": "2.0",
         "id": 1,
-        "method": "initialize",
+        "method": methods::INITIALIZE,
         "params": {
             "protocolVersion": 1,
             "clientCapabilities": {
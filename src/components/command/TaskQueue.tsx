@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
 import { useAgentStore } from "../../stores";
+import { formatMessage } from "../../i18n/messages";
 import type { PendingInput } from "../../types";
 
 export function TaskQueue() {
@@ -24,7 +25,7 @@ export function TaskQueue() {
     }
   }
 
-  const handlePermissionResponse = async (agentId: string, inputId: string, approved: boolean) => {
+  const handlePermissionResponse = async (agentId: string, inputId: string, approved: boolean, confirmHighRisk = false) => {
     console.log(`Responding to permission:`, { agentId, inputId, approved, typeOfAgentId: typeof agentId });
     try {
       const params = {
@@ -32,6 +33,7 @@ export function TaskQueue() {
         inputId: String(inputId),
         approved,
         optionId: null as string | null,
+        confirmHighRisk,
       };
       console.log("Invoke params:", params);
       await invoke("respond_to_permission", params);
@@ -113,7 +115,7 @@ export function TaskQueue() {
                   marginBottom: 8,
                 }}
               >
-                {input.message}
+                {formatMessage(input.localized, input.message)}
               </div>
               {input.tool_name && (
                 <div
@@ -133,7 +135,10 @@ export function TaskQueue() {
                   style={{ fontSize: 10, padding: "4px 12px", cursor: "pointer" }}
                   onClick={(e) => {
                     e.stopPropagation();
-                    handlePermissionResponse(agentId, input.id, true);
+                    if (input.high_risk && !window.confirm(`Allow access outside the project?\n${input.outside_paths.join("\n")}`)) {
+                      return;
+                    }
+                    handlePermissionResponse(agentId, input.id, true, input.high_risk);
                   }}
                 >
                   Approve
@@ -1,7 +1,9 @@
 import { useState, useCallback, useRef, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { useAgentStore } from "../../stores/agentStore";
-import type { AgentInfo } from "../../types";
+import { formatMessage } from "../../i18n/messages";
+import { ALLOW_SIMILAR_OPTION_ID } from "../../types";
+import type { AgentInfo, PendingInput, PromptDraft } from "../../types";
 
 interface AgentChatPaletteProps {
   agent: AgentInfo;
@@ -17,10 +19,11 @@ export function AgentChatPalette({ agent, onClose, respondedInputIds, onInputRes
   const [authMessage, setAuthMessage] = useState<string | null>(null);
   const inputRef = useRef<HTMLTextAreaElement>(null);
   const messagesRef = useRef<HTMLDivElement>(null);
+  // Agent whose draft is in the input; nothing is saved until it loaded
+  const draftAgentId = useRef<string | null>(null);
 
   const activityLog = useAgentStore((s) => s.activityLog);
   const sendPrompt = useAgentStore((s) => s.sendPrompt);
-  const refreshAgent = useAgentStore((s) => s.refreshAgent);
 
   // Filter activity log for this agent
   const agentMessages = activityLog.filter((entry) => entry.agentId === agent.id);
@@ -32,6 +35,33 @@ export function AgentChatPalette({ agent, onClose, respondedInputIds, onInputRes
     }
   }, [agentMessages.length]);
 
+  // Restore the unsent prompt of this agent
+  useEffect(() => {
+    let cancelled = false;
+    draftAgentId.current = null;
+    invoke<PromptDraft | null>("get_prompt_draft", { agentId: agent.id })
+      .then((draft) => {
+        if (cancelled) return;
+        draftAgentId.current = agent.id;
+        setInput(draft?.text ?? "");
+      })
+      .catch((error) => console.error("Failed to load prompt draft:", error));
+    return () => {
+      cancelled = true;
+    };
+  }, [agent.id]);
+
+  // Save the draft shortly after typing stops
+  useEffect(() => {
+    if (draftAgentId.current !== agent.id) return;
+    const timeoutId = setTimeout(() => {
+      invoke("save_prompt_draft", { agentId: agent.id, text: input }).catch((error) =>
+        console.error("Failed to save prompt draft:", error)
+      );
+    }, 500);
+    return () => clearTimeout(timeoutId);
+  }, [agent.id, input]);
+
   // Focus input on mount and when agent changes
   useEffect(() => {
     // Use setTimeout to ensure focus happens after render cycle
@@ -68,16 +98,21 @@ export function AgentChatPalette({ agent, onClose, respondedInputIds, onInputRes
     }
   };
 
-  const handlePermissionResponse = useCallback(async (inputId: string, approved: boolean) => {
+  const handlePermissionResponse = useCallback(async (input: PendingInput, approved: boolean, optionId: string | null = null) => {
+    const confirmHighRisk = approved && input.high_risk;
+    if (confirmHighRisk && !window.confirm(`Allow access outside the project?\n${input.outside_paths.join("\n")}`)) {
+      return;
+    }
     // Immediately hide the input
-    onInputResponded(inputId);
+    onInputResponded(input.id);
 
     try {
       await invoke("respond_to_permission", {
         agentId: agent.id,
-        inputId,
+        inputId: input.id,
         approved,
-        optionId: null,
+        optionId,
+        confirmHighRisk,
       });
     } catch (error) {
       console.error("Failed to respond to permission:", error);
@@ -103,7 +138,6 @@ export function AgentChatPalette({ agent, onClose, respondedInputIds, onInputRes
         } catch {
           setAuthMessage("Auth completed. Click 'Retry' to create session.");
         }
-        await refreshAgent(agent.id);
       } else if (result.url) {
         setAuthMessage("Browser opened. Complete login and click 'Retry' when done.");
       } else if (result.message) {
@@ -119,21 +153,20 @@ export function AgentChatPalette({ agent, onClose, respondedInputIds, onInputRes
     } finally {
       setIsAuthenticating(false);
     }
-  }, [agent.id, refreshAgent]);
+  }, [agent.id]);
 
   const handleRetrySession = useCallback(async () => {
     setIsAuthenticating(true);
     try {
       await invoke("retry_create_session", { agentId: agent.id });
       setAuthMessage("Session created successfully!");
-      await refreshAgent(agent.id);
     } catch (error) {
       console.error("Failed to create session:", error);
       setAuthMessage(`Session creation failed: ${error}`);
     } finally {
       setIsAuthenticating(false);
     }
-  }, [agent.id, refreshAgent]);
+  }, [agent.id]);
 
   const getInputTypeLabel = (type: string): string => {
     switch (type) {
@@ -222,28 +255,46 @@ export function AgentChatPalette({ agent, onClose, respondedInputIds, onInputRes
       {agent.pending_inputs && agent.pending_inputs.filter(p => !respondedInputIds.has(p.id)).length > 0 && (
         <div className="agent-chat-palette__pending">
           {agent.pending_inputs.filter(p => !respondedInputIds.has(p.id)).map((pendingInput) => (
-            <div key={pendingInput.id} className="agent-chat-palette__pending-item">
+            <div
+              key={pendingInput.id}
+              className={`agent-chat-palette__pending-item${pendingInput.risk ? ` agent-chat-palette__pending-item--${pendingInput.risk.tier}` : ""}`}
+              title={pendingInput.risk?.reasons.join(", ")}
+            >
               <div className="agent-chat-palette__pending-label">
                 {getInputTypeLabel(pendingInput.input_type)}
               </div>
               <div className="agent-chat-palette__pending-message">
-                {pendingInput.message}
+                {formatMessage(pendingInput.localized, pendingInput.message)}
               </div>
               {pendingInput.tool_name && (
                 <div className="agent-chat-palette__pending-tool">
                   Tool: {pendingInput.tool_name}
                 </div>
               )}
+              {pendingInput.high_risk && (
+                <div className="agent-chat-palette__pending-tool">
+                  Outside the project: {pendingInput.outside_paths.join(", ")}
+                </div>
+              )}
               <div className="agent-chat-palette__pending-actions">
                 <button
                   className="agent-chat-palette__btn agent-chat-palette__btn--approve"
-                  onClick={() => handlePermissionResponse(pendingInput.id, true)}
+                  onClick={() => handlePermissionResponse(pendingInput, true)}
                 >
                   Approve
                 </button>
+                {pendingInput.similar_scope && (
+                  <button
+                    className="agent-chat-palette__btn agent-chat-palette__btn--approve"
+                    title={pendingInput.similar_scope}
+                    onClick={() => handlePermissionResponse(pendingInput, true, ALLOW_SIMILAR_OPTION_ID)}
+                  >
+                    Allow Similar
+                  </button>
+                )}
                 <button
                   className="agent-chat-palette__btn agent-chat-palette__btn--deny"
-                  onClick={() => handlePermissionResponse(pendingInput.id, false)}
+                  onClick={() => handlePermissionResponse(pendingInput, false)}
                 >
                   Deny
                 </button>
@@ -1,8 +1,9 @@
 import { useRef, useEffect, useCallback, useState, useMemo } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import { useUIStore } from "../../stores/uiStore";
 import { useAgentStore } from "../../stores/agentStore";
-import { useFactoryStore } from "../../stores/factoryStore";
+import { useFactoryStore, type ProjectSummary } from "../../stores/factoryStore";
 import { useMetricsStore } from "../../stores/metricsStore";
 import { useRegistryStore } from "../../stores/registryStore";
 import { FactorioRenderer, type Entity, type AgentEntity, type ResourceEntity } from "./FactorioRenderer";
@@ -51,6 +52,18 @@ interface SelectionBox {
 
 const DRAG_THRESHOLD = 5; // pixels before drag starts
 
+// "Rust 62% · 12.4k LOC" for the project tile
+function formatProjectStats(summary: ProjectSummary | null | undefined): string | undefined {
+  if (!summary || summary.total_lines === 0) return undefined;
+  const top = summary.languages[0];
+  const share = Math.round((top.lines / summary.total_lines) * 100);
+  const loc =
+    summary.total_lines >= 1000
+      ? `${(summary.total_lines / 1000).toFixed(1)}k`
+      : String(summary.total_lines);
+  return `${top.language} ${share}% · ${loc} LOC`;
+}
+
 export function FactorioCanvas() {
   const canvasRef = useRef<HTMLCanvasElement>(null);
   const rendererRef = useRef<FactorioRenderer | null>(null);
@@ -205,6 +218,10 @@ export function FactorioCanvas() {
             // Agent gets a new ID on spawn, update placement
             // Remove old placement and create new one with the new agent ID
             await removeAgentPlacement(placement.agent_id);
+            await invoke("move_prompt_draft", {
+              fromAgentId: placement.agent_id,
+              toAgentId: agent.id,
+            });
             await setAgentPlacement(
               agent.id,
               placement.grid_x,
@@ -214,6 +231,18 @@ export function FactorioCanvas() {
               placement.working_directory,
               placement.provider_id
             );
+            if (placement.stats) {
+              await invoke("set_placement_stats", {
+                agentId: agent.id,
+                stats: placement.stats,
+              });
+            }
+            if (placement.standing_order) {
+              await invoke("set_standing_order", {
+                agentId: agent.id,
+                order: placement.standing_order,
+              });
+            }
           } catch (error) {
             console.error(`Failed to restore agent ${placement.name}:`, error);
           }
@@ -484,6 +513,7 @@ export function FactorioCanvas() {
         name: project.name,
         fileCount: project.file_count,
         colorIndex: project.color_index,
+        stats: formatProjectStats(project.summary),
       };
       entities.push(resourceEntity);
     });
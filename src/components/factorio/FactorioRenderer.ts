@@ -54,6 +54,8 @@ export interface ResourceEntity extends EntityPosition {
   name: string;
   fileCount?: number;
   colorIndex?: number;
+  // Language and size summary shown under the name
+  stats?: string;
 }
 
 export type Entity = AgentEntity | ResourceEntity;
@@ -578,7 +580,7 @@ export class FactorioRenderer {
 
   private drawResourceNode(entity: ResourceEntity, gridX: number, gridY: number): void {
     const { ctx, viewport, animationTime, selectedIds, hoveredId } = this;
-    const { id, width, height, name, colorIndex, fileCount } = entity;
+    const { id, width, height, name, colorIndex, fileCount, stats } = entity;
 
     const screenPos = worldToScreen(gridX * TILE_SIZE, gridY * TILE_SIZE, viewport);
     const screenWidth = width * TILE_SIZE * viewport.zoom;
@@ -663,6 +665,15 @@ export class FactorioRenderer {
     // Text
     ctx.fillStyle = COLORS.text;
     ctx.fillText(displayName, labelX, labelY);
+
+    if (stats) {
+      const statsY = labelY + 13 * viewport.zoom;
+      ctx.font = `${9 * viewport.zoom}px "JetBrains Mono", monospace`;
+      ctx.fillStyle = COLORS.textShadow;
+      ctx.fillText(stats, labelX + 1, statsY + 1);
+      ctx.fillStyle = COLORS.text;
+      ctx.fillText(stats, labelX, statsY);
+    }
   }
 
   private drawSelectionBrackets(
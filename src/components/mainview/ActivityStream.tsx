@@ -25,6 +25,8 @@ export function ActivityStream() {
     switch (type) {
       case "tool":
         return "activity-stream__entry--tool";
+      case "warning":
+        return "activity-stream__entry--warning";
       case "error":
         return "activity-stream__entry--error";
       case "status":
@@ -13,6 +13,7 @@ export function FileTree({ node, depth }: FileTreeProps) {
     exploredPaths,
     expandedDirs,
     toggleDir,
+    revealDirectory,
   } = useProjectStore();
 
   const isExplored = exploredPaths.has(node.path);
@@ -22,6 +23,10 @@ export function FileTree({ node, depth }: FileTreeProps) {
   const handleClick = (e: React.MouseEvent) => {
     e.stopPropagation();
     if (node.is_dir) {
+      // Opening a folder reveals its entries; shift-click the whole subtree
+      if (!isExpanded) {
+        revealDirectory(node.path, e.shiftKey);
+      }
       toggleDir(node.path);
     } else {
       setSelectedFile(node.path);
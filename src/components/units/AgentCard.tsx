@@ -19,6 +19,7 @@ export function AgentCard({ agent, isSelected, onSelect }: AgentCardProps) {
       case "working":
         return "agent-card--working";
       case "paused":
+      case "needs_auth":
         return "agent-card--attention";
       case "error":
         return "agent-card--error";
@@ -38,6 +39,8 @@ export function AgentCard({ agent, isSelected, onSelect }: AgentCardProps) {
         return "WORK";
       case "paused":
         return "PAUSE";
+      case "needs_auth":
+        return "AUTH";
       case "error":
         return "ERR";
       case "stopped":
@@ -1,52 +1,243 @@
 import { useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
 import { useAgentStore, useProjectStore } from "../stores";
-import type { AgentInfo, AgentUpdate, FileEvent, ProjectTree } from "../types";
+import { useFactoryStore } from "../stores/factoryStore";
+import type {
+  AgentInfo,
+  AgentStateChange,
+  AgentUpdate,
+  EventReplay,
+  FileConflict,
+  FileEvent,
+  FogRevealBatch,
+  PendingPermissionInfo,
+  PermissionEscalation,
+  ProjectCommandRun,
+  ProjectTree,
+  TaskConflict,
+  TreeDelta,
+} from "../types";
 
 export function useTauriEvents() {
   const { addAgent, updateAgent, removeAgent, handleAgentUpdate, addActivityLog } =
     useAgentStore();
-  const { setProjectTree, revealPath, addFile, removeFile } = useProjectStore();
+  const { setProjectTree, revealPath, addFile, removeFile, renameFile } = useProjectStore();
 
   useEffect(() => {
     const listeners: Promise<UnlistenFn>[] = [];
+    const handlers = new Map<string, (payload: unknown) => void>();
+
+    // Every backend event carries a global `_seq`. A jump between handled
+    // events may mean some were lost, e.g. while the window was hidden, so
+    // missed ones are replayed from the backend's buffer.
+    let lastSeq = 0;
+    const seen = new Set<number>();
+    let catchUpTimer: ReturnType<typeof setTimeout> | undefined;
+
+    const resync = () => {
+      useAgentStore.getState().fetchAgents();
+      useFactoryStore.getState().loadFromBackend();
+      useProjectStore.getState().fetchFogDelta();
+    };
+
+    const catchUp = async () => {
+      catchUpTimer = undefined;
+      try {
+        const replay = await invoke<EventReplay>("get_recent_events", { sinceSeq: lastSeq });
+        if (!replay.complete) {
+          resync();
+        } else {
+          for (const recorded of replay.events) {
+            const handler = handlers.get(recorded.event);
+            if (handler && !seen.has(recorded.seq)) {
+              handler(recorded.payload);
+            }
+          }
+        }
+        lastSeq = Math.max(lastSeq, replay.last_seq);
+        for (const seq of [...seen]) {
+          if (seq <= lastSeq) {
+            seen.delete(seq);
+          }
+        }
+      } catch (e) {
+        console.error("Failed to replay missed events:", e);
+      }
+    };
+
+    const scheduleCatchUp = () => {
+      if (!catchUpTimer) {
+        catchUpTimer = setTimeout(catchUp, 500);
+      }
+    };
+
+    function on<T>(name: string, handler: (event: { payload: T }) => void) {
+      handlers.set(name, (payload) => handler({ payload: payload as T }));
+      return listen<T>(name, (event) => {
+        const seq = (event.payload as { _seq?: number } | null)?._seq;
+        // Replayed recordings carry no number
+        if (seq !== undefined) {
+          if (seq <= lastSeq || seen.has(seq)) {
+            return;
+          }
+          if (lastSeq === 0 || (seq === lastSeq + 1 && !catchUpTimer)) {
+            lastSeq = seq;
+          } else {
+            seen.add(seq);
+            scheduleCatchUp();
+          }
+        }
+        handler(event);
+      });
+    }
+
+    const onVisible = () => {
+      if (document.visibilityState === "visible" && lastSeq !== 0) {
+        scheduleCatchUp();
+      }
+    };
+    document.addEventListener("visibilitychange", onVisible);
 
     // Agent events
     listeners.push(
-      listen<AgentInfo>("agent-spawned", (event) => {
+      on<AgentInfo>("agent-spawned", (event) => {
         addAgent(event.payload);
       })
     );
 
     listeners.push(
-      listen<AgentUpdate>("agent-update", (event) => {
+      on<AgentUpdate>("agent-update", (event) => {
         handleAgentUpdate(event.payload);
       })
     );
 
     listeners.push(
-      listen<AgentInfo>("agent-status-changed", (event) => {
+      on<AgentInfo>("agent-status-changed", (event) => {
         updateAgent(event.payload.id, event.payload);
       })
     );
 
     listeners.push(
-      listen<string>("agent-stopped", (event) => {
-        removeAgent(event.payload);
+      on<AgentStateChange>("agent-state-changed", (event) => {
+        const { agent_id, ...change } = event.payload;
+        updateAgent(agent_id, change);
+      })
+    );
+
+    listeners.push(
+      on<{ agent_id: string }>("agent-stopped", (event) => {
+        removeAgent(event.payload.agent_id);
+      })
+    );
+
+    // Placements moved to new agents, e.g. after restarting a project's agents
+    listeners.push(
+      on("factory-layout-changed", () => {
+        useFactoryStore.getState().loadFromBackend();
+      })
+    );
+
+    listeners.push(
+      on<PendingPermissionInfo>("permission-cancelled", (event) => {
+        const { agent_id, input_id } = event.payload;
+        const agent = useAgentStore.getState().agents.get(agent_id);
+        if (agent) {
+          updateAgent(agent_id, {
+            pending_inputs: agent.pending_inputs.filter((p) => p.id !== input_id),
+          });
+        }
+      })
+    );
+
+    listeners.push(
+      on<PermissionEscalation>("permission-escalated", (event) => {
+        const escalation = event.payload;
+        const minutes = Math.floor(escalation.age_secs / 60);
+        if (escalation.kind === "default_applied") {
+          const agent = useAgentStore.getState().agents.get(escalation.agent_id);
+          if (agent) {
+            updateAgent(escalation.agent_id, {
+              pending_inputs: agent.pending_inputs.filter((p) => p.id !== escalation.input_id),
+            });
+          }
+        }
+        addActivityLog({
+          agentId: escalation.agent_id,
+          type: "warning",
+          content:
+            escalation.kind === "reminder"
+              ? `Waiting ${minutes} min for an answer: ${escalation.message}`
+              : `${escalation.approved ? "Approved" : "Denied"} after ${minutes} min without an answer: ${escalation.message}`,
+        });
+      })
+    );
+
+    listeners.push(
+      on<FileConflict>("file-conflict", (event) => {
+        const conflict = event.payload;
+        const holder = useAgentStore.getState().agents.get(conflict.holder.agent_id);
+        addActivityLog({
+          agentId: conflict.agent_id,
+          type: "warning",
+          content: `${conflict.held ? "Waiting for" : "Conflicts with"} ${
+            holder?.name ?? "another agent"
+          } editing ${conflict.path}`,
+        });
+      })
+    );
+
+    listeners.push(
+      on<TaskConflict>("task-conflict", (event) => {
+        const conflict = event.payload;
+        const other = useAgentStore.getState().agents.get(conflict.first_agent_id);
+        const files =
+          conflict.paths.length === 1 ? conflict.paths[0] : `${conflict.paths.length} files`;
+        addActivityLog({
+          agentId: conflict.second_agent_id,
+          type: "warning",
+          content: `Touched ${files} while ${other?.name ?? "another agent"} was working on them`,
+        });
       })
     );
 
     // Project events
     listeners.push(
-      listen<ProjectTree>("project-loaded", (event) => {
+      on<ProjectTree>("project-loaded", (event) => {
         setProjectTree(event.payload);
       })
     );
 
     listeners.push(
-      listen<FileEvent>("fs-change", (event) => {
+      on<ProjectCommandRun>("project-command-finished", (event) => {
+        const run = event.payload;
+        addActivityLog({
+          agentId: run.agent_id ?? run.project_id,
+          type: run.success ? "status" : "error",
+          content: `${run.kind} ${run.success ? "passed" : "failed"}: ${run.command}`,
+        });
+      })
+    );
+
+    // Background rescan of a project opened from its cached tree
+    listeners.push(
+      on<TreeDelta>("project-tree-delta", (event) => {
+        event.payload.removed.forEach(removeFile);
+        event.payload.added.forEach(addFile);
+      })
+    );
+
+    listeners.push(
+      on<FileEvent>("fs-change", (event) => {
         const { kind, paths } = event.payload;
 
+        // Old and new path of a rename, so explored state moves along
+        if (kind === "rename" && paths.length === 2) {
+          console.log("File system change:", kind, paths[0], "->", paths[1]);
+          renameFile(paths[0], paths[1]);
+          return;
+        }
+
         for (const path of paths) {
           // Skip temporary files and hidden system files
           const fileName = path.split("/").pop() || "";
@@ -73,13 +264,30 @@ export function useTauriEvents() {
     );
 
     listeners.push(
-      listen<string>("fog-revealed", (event) => {
-        revealPath(event.payload);
+      on<{ path: string }>("fog-revealed", (event) => {
+        revealPath(event.payload.path);
+      })
+    );
+
+    // Files agents read, sent in batches; refetch the fog after a missed batch
+    let lastRevealSeq = 0;
+    listeners.push(
+      on<FogRevealBatch>("fog-revealed-batch", (event) => {
+        const { seq, paths } = event.payload;
+        const { revealPaths, fetchFogDelta } = useProjectStore.getState();
+        if (lastRevealSeq !== 0 && seq !== lastRevealSeq + 1) {
+          fetchFogDelta();
+        } else {
+          revealPaths(paths);
+        }
+        lastRevealSeq = seq;
       })
     );
 
     // Cleanup
     return () => {
+      document.removeEventListener("visibilitychange", onVisible);
+      clearTimeout(catchUpTimer);
       listeners.forEach((promise) => {
         promise.then((unlisten) => unlisten());
       });
@@ -94,5 +302,6 @@ export function useTauriEvents() {
     revealPath,
     addFile,
     removeFile,
+    renameFile,
   ]);
 }
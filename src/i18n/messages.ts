@@ -0,0 +1,28 @@
+import type { LocalizedMessage, MessageKey } from "../types";
+
+// English templates of backend messages; `{name}` stands for a parameter.
+// A translation provides the same keys.
+const en: Record<MessageKey, string> = {
+  agent_wants_to: "Agent wants to: {tool}",
+  permission_requested: "Permission requested: {tool}",
+  agent_needs_permission: "Agent needs permission to use: {tool}",
+  task_failed: "Task failed",
+  task_failed_with_error: "{error}",
+  agent_process_failed: "Agent process failed",
+  still_waiting: "Still waiting: {request}",
+  killed_for_memory: "Killed for using {megabytes} MB of memory",
+  budget_exceeded: "Token budget exceeded: {tokens} used",
+  verification_failed: "`{command}` failed after the task",
+  placement_suggested:
+    "{project} had {edits} manual edits but no agent activity lately. Place an agent?",
+  placement_suggested_explored:
+    "{project} had {edits} manual edits but no agent activity lately. Place an agent? Agents explored {percent}% of it.",
+};
+
+// Render a backend message from its key, falling back to the text the
+// backend rendered when there is no key or template
+export function formatMessage(localized: LocalizedMessage | null | undefined, fallback: string): string {
+  const template = localized ? en[localized.key] : undefined;
+  if (!localized || !template) return fallback;
+  return template.replace(/\{(\w+)\}/g, (match, name: string) => localized.params[name] ?? match);
+}
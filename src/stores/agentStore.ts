@@ -1,12 +1,24 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
-import type { AgentInfo, AgentUpdate } from "../types";
+import {
+  isPromptRejection,
+  type AgentInfo,
+  type AgentUpdate,
+  type PromptRejection,
+  type PromptReply,
+} from "../types";
+
+const REJECTION_MESSAGES: Record<PromptRejection["kind"], string> = {
+  agent_crashed: "Agent is not running; restart it to send prompts",
+  needs_auth: "Agent needs authentication before it can take prompts",
+  busy: "Agent is busy; answer its pending requests first",
+};
 
 interface ActivityLogEntry {
   id: string;
   agentId: string;
   timestamp: Date;
-  type: "message" | "tool" | "status" | "error";
+  type: "message" | "tool" | "status" | "warning" | "error";
   content: string;
   tool?: string;
 }
@@ -32,9 +44,8 @@ interface AgentState {
   // Async actions
   spawnAgent: (name: string, workingDirectory: string, providerId?: string) => Promise<AgentInfo>;
   stopAgent: (agentId: string) => Promise<void>;
-  sendPrompt: (agentId: string, prompt: string) => Promise<string>;
+  sendPrompt: (agentId: string, prompt: string) => Promise<PromptReply>;
   fetchAgents: () => Promise<void>;
-  refreshAgent: (agentId: string) => Promise<void>;
 }
 
 export const useAgentStore = create<AgentState>((set, get) => ({
@@ -137,6 +148,9 @@ export const useAgentStore = create<AgentState>((set, get) => ({
     if (update.pending_inputs !== null) {
       agentUpdate.pending_inputs = update.pending_inputs;
     }
+    if (update.touched_files?.length) {
+      agentUpdate.touched_ranges = update.touched_files;
+    }
     updateAgent(update.agent_id, agentUpdate);
 
     // Add to activity log
@@ -191,13 +205,24 @@ export const useAgentStore = create<AgentState>((set, get) => ({
       content: `> ${prompt}`,
     });
 
-    const result = await invoke<string>("send_prompt", { agentId, prompt });
+    let result: PromptReply;
+    try {
+      result = await invoke<PromptReply>("send_prompt", { agentId, prompt });
+    } catch (error) {
+      if (isPromptRejection(error)) {
+        // The agent never started working; show its actual state
+        const info = await invoke<AgentInfo | null>("get_agent", { agentId });
+        if (info) get().updateAgent(agentId, info);
+        get().addActivityLog({ agentId, type: "warning", content: REJECTION_MESSAGES[error.kind] });
+      }
+      throw error;
+    }
 
     get().updateAgent(agentId, { status: "idle", progress: 100 });
     get().addActivityLog({
       agentId,
       type: "message",
-      content: result,
+      content: result.truncated ? `…${result.tail}` : result.tail,
     });
 
     return result;
@@ -209,15 +234,4 @@ export const useAgentStore = create<AgentState>((set, get) => ({
       agents: new Map(agents.map((a) => [a.id, a])),
     });
   },
-
-  refreshAgent: async (agentId) => {
-    const agentInfo = await invoke<AgentInfo | null>("get_agent", { agentId });
-    if (agentInfo) {
-      set((state) => {
-        const agents = new Map(state.agents);
-        agents.set(agentId, agentInfo);
-        return { agents };
-      });
-    }
-  },
 }));
@@ -9,6 +9,26 @@ export interface ProjectNode {
   grid_y: number;
   file_count?: number;
   color_index?: number;
+  summary?: ProjectSummary | null;
+}
+
+export interface ProjectSummary {
+  languages: { language: string; files: number; lines: number }[];
+  total_files: number;
+  total_lines: number;
+  largest_directories: { path: string; files: number; lines: number }[];
+  test_files: number;
+  source_files: number;
+  test_ratio: number;
+  analyzed_at: number;
+}
+
+// A monorepo workspace member that could become a project node
+export interface SubprojectCandidate {
+  name: string;
+  path: string;
+  relative_path: string;
+  kind: "cargo" | "npm" | "pnpm" | "go";
 }
 
 export interface AgentPlacement {
@@ -20,6 +40,26 @@ export interface AgentPlacement {
   name?: string | null;
   working_directory?: string | null;
   provider_id?: string | null;
+  standing_order?: StandingOrder | null;
+  stats?: PlacementStats;
+}
+
+// Work done at a placement across restarts of its agent
+export interface PlacementStats {
+  prompts: number;
+  tokens: number;
+  files_touched: number;
+}
+
+export type StandingOrderTrigger =
+  | { kind: "command_failed"; command?: "build" | "test" | "lint" | null }
+  | { kind: "files_changed" };
+
+// Prompt sent to the agent whenever its trigger fires
+export interface StandingOrder {
+  prompt: string;
+  trigger: StandingOrderTrigger;
+  min_interval_secs: number;
 }
 
 export interface FactoryViewport {
@@ -28,11 +68,26 @@ export interface FactoryViewport {
   zoom: number;
 }
 
+// Agents spawned in a project of the group use these unless given their own
+export interface GroupAgentDefaults {
+  provider_id?: string | null;
+  preset?: string | null;
+}
+
+// Projects that form one product; a project is in one group at most
+export interface ProjectGroup {
+  id: string;
+  name: string;
+  project_ids: string[];
+  agent_defaults: GroupAgentDefaults;
+}
+
 export interface FactoryLayout {
   version: number;
   projects: ProjectNode[];
   agent_placements: AgentPlacement[];
   viewport: FactoryViewport;
+  groups: ProjectGroup[];
 }
 
 interface FactoryState {
@@ -48,6 +103,7 @@ interface FactoryState {
   moveProject: (id: string, gridX: number, gridY: number) => Promise<void>;
   getProjectByPath: (path: string) => ProjectNode | undefined;
   fetchFileCount: (projectId: string) => Promise<void>;
+  analyzeProject: (projectId: string) => Promise<void>;
 
   // Agent placement actions
   setAgentPlacement: (agentId: string, gridX: number, gridY: number, connectedProjectId?: string | null, name?: string | null, workingDirectory?: string | null, providerId?: string | null) => Promise<void>;
@@ -133,8 +189,9 @@ export const useFactoryStore = create<FactoryState>((set, get) => ({
         nextColorIndex: nextColorIndex + 1,
       });
 
-      // Fetch file count asynchronously (don't block)
+      // Fetch file count and statistics asynchronously (don't block)
       get().fetchFileCount(id);
+      get().analyzeProject(id);
 
       return updated.projects.get(id)!;
     } catch (error) {
@@ -203,6 +260,17 @@ export const useFactoryStore = create<FactoryState>((set, get) => ({
     }
   },
 
+  analyzeProject: async (projectId) => {
+    try {
+      await invoke<ProjectSummary>("analyze_project", { projectId });
+      const layout = await invoke<FactoryLayout>("get_factory_layout");
+      const updated = updateFromLayout(layout);
+      set({ projects: updated.projects });
+    } catch (error) {
+      console.error("Failed to analyze project:", error);
+    }
+  },
+
   setAgentPlacement: async (agentId, gridX, gridY, connectedProjectId = null, name = null, workingDirectory = null, providerId = null) => {
     try {
       const layout = await invoke<FactoryLayout>("set_agent_placement", {
@@ -363,6 +431,9 @@ export const useFactoryStore = create<FactoryState>((set, get) => ({
         if (project.file_count === undefined || project.file_count === null) {
           get().fetchFileCount(project.id);
         }
+        if (!project.summary) {
+          get().analyzeProject(project.id);
+        }
       }
     } catch (error) {
       console.error("Failed to load factory layout:", error);
@@ -1,6 +1,6 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
-import type { ProjectTree, FogState, FileNode } from "../types";
+import type { ProjectTree, FogDelta, FogState, FileNode } from "../types";
 
 // Helper to find a node in the tree by path
 function findNode(node: FileNode, path: string): FileNode | null {
@@ -72,6 +72,32 @@ function removeFileFromTree(tree: FileNode, filePath: string): FileNode {
   return newTree;
 }
 
+// Helper to move a file or directory, with everything below it, to a new path
+function renameInTree(tree: FileNode, from: string, to: string): FileNode {
+  const node = findNode(tree, from);
+  if (!node) {
+    return addFileToTree(tree, to);
+  }
+
+  const moved = JSON.parse(JSON.stringify(node)) as FileNode;
+  const repath = (n: FileNode) => {
+    n.path = to + n.path.slice(from.length);
+    n.children?.forEach(repath);
+  };
+  repath(moved);
+  moved.name = to.split("/").pop() || moved.name;
+
+  const newTree = removeFileFromTree(tree, from);
+  const parent = findNode(newTree, to.split("/").slice(0, -1).join("/"));
+  if (parent && parent.is_dir && !findNode(newTree, to)) {
+    parent.children = [...(parent.children ?? []), moved].sort((a, b) => {
+      if (a.is_dir !== b.is_dir) return a.is_dir ? -1 : 1;
+      return a.name.localeCompare(b.name);
+    });
+  }
+  return newTree;
+}
+
 const RECENT_PROJECTS_KEY = "agent-commander-recent-projects";
 const LAST_PROJECT_KEY = "agent-commander-last-project";
 const MAX_RECENT_PROJECTS = 10;
@@ -87,6 +113,8 @@ interface ProjectState {
   projectPath: string | null;
   selectedFile: string | null;
   exploredPaths: Set<string>;
+  // Sequence number of the fog state exploredPaths is in step with
+  fogSeq: number;
   expandedDirs: Set<string>;
   isLoading: boolean;
   error: string | null;
@@ -97,6 +125,7 @@ interface ProjectState {
   setSelectedFile: (path: string | null) => void;
   revealPath: (path: string) => void;
   revealPaths: (paths: string[]) => void;
+  revealDirectory: (path: string, recursive: boolean) => Promise<void>;
   toggleDir: (path: string) => void;
   expandDir: (path: string) => void;
   collapseDir: (path: string) => void;
@@ -109,12 +138,14 @@ interface ProjectState {
   // File watcher actions
   addFile: (path: string) => void;
   removeFile: (path: string) => void;
+  renameFile: (from: string, to: string) => void;
 
   // Async actions
   loadProject: (path: string) => Promise<void>;
   loadLastProject: () => Promise<boolean>;
   refreshProject: () => Promise<void>;
   fetchFogState: () => Promise<void>;
+  fetchFogDelta: () => Promise<void>;
 }
 
 function loadRecentProjectsFromStorage(): RecentProject[] {
@@ -159,6 +190,7 @@ export const useProjectStore = create<ProjectState>((set, get) => ({
   projectPath: null,
   selectedFile: null,
   exploredPaths: new Set(),
+  fogSeq: 0,
   expandedDirs: new Set(),
   isLoading: false,
   error: null,
@@ -218,6 +250,15 @@ export const useProjectStore = create<ProjectState>((set, get) => ({
     });
   },
 
+  revealDirectory: async (path, recursive) => {
+    try {
+      const revealed = await invoke<string[]>("reveal_directory", { path, recursive });
+      get().revealPaths(revealed);
+    } catch (e) {
+      console.error("Failed to reveal directory:", e);
+    }
+  },
+
   toggleDir: (path) => {
     set((state) => {
       const expandedDirs = new Set(state.expandedDirs);
@@ -304,6 +345,20 @@ export const useProjectStore = create<ProjectState>((set, get) => ({
     });
   },
 
+  renameFile: (from, to) => {
+    set((state) => {
+      if (!state.projectTree) return state;
+      const isWithin = (path: string) => path === from || path.startsWith(from + "/");
+      const exploredPaths = new Set(
+        [...state.exploredPaths].map((path) => (isWithin(path) ? to + path.slice(from.length) : path))
+      );
+      return {
+        projectTree: { ...state.projectTree, tree: renameInTree(state.projectTree.tree, from, to) },
+        exploredPaths,
+      };
+    });
+  },
+
   loadProject: async (path) => {
     console.log("Loading project:", path);
     set({ isLoading: true, error: null });
@@ -351,9 +406,23 @@ export const useProjectStore = create<ProjectState>((set, get) => ({
   fetchFogState: async () => {
     try {
       const fog = await invoke<FogState>("get_fog_state");
-      set({ exploredPaths: new Set(fog.explored_paths) });
+      set({ exploredPaths: new Set(fog.explored_paths), fogSeq: fog.seq });
     } catch (e) {
       console.error("Failed to fetch fog state:", e);
     }
   },
+
+  fetchFogDelta: async () => {
+    try {
+      const delta = await invoke<FogDelta>("get_fog_delta", { sinceSeq: get().fogSeq });
+      set((state) => {
+        const exploredPaths = new Set(delta.reset ? [] : state.exploredPaths);
+        delta.removed.forEach((p) => exploredPaths.delete(p));
+        delta.revealed.forEach((p) => exploredPaths.add(p));
+        return { exploredPaths, fogSeq: delta.seq };
+      });
+    } catch (e) {
+      console.error("Failed to fetch fog delta:", e);
+    }
+  },
 }));
@@ -6,6 +6,28 @@ export interface Metrics {
   session_duration_secs: number;
 }
 
+/** Working time of the agents of one project, by working directory */
+export interface ProjectTime {
+  project: string;
+  active_secs: number;
+}
+
+export interface AgentTime {
+  agent_id: string;
+  agent_name: string;
+  project: string;
+  active_secs: number;
+}
+
+/** Wall-clock time agents spent working, most first */
+export interface TimeTracking {
+  since: number;
+  until: number;
+  total_secs: number;
+  projects: ProjectTime[];
+  agents: AgentTime[];
+}
+
 export interface SessionUpdate {
   session_id: string;
   type: string;
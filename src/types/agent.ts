@@ -3,6 +3,7 @@ export type AgentStatus =
   | "idle"
   | "working"
   | "paused"
+  | "needs_auth"
   | "error"
   | "stopped";
 
@@ -12,6 +13,42 @@ export interface AuthMethod {
   description?: string | null;
 }
 
+/** Why an agent refused a prompt; thrown by the prompt commands instead of a message */
+export type PromptRejection =
+  | { kind: "agent_crashed"; agent_id: string; status: AgentStatus }
+  | { kind: "needs_auth"; agent_id: string; auth_methods: AuthMethod[] }
+  | { kind: "busy"; agent_id: string; status: AgentStatus; pending_inputs: number };
+
+/** Transcript of a prompt's answer */
+export interface TranscriptInfo {
+  id: string;
+  /** Bytes of answer text */
+  length: number;
+}
+
+/** What the prompt commands return */
+export interface PromptReply {
+  /** Null if the agent answered nothing */
+  transcript_id: string | null;
+  length: number;
+  tail: string;
+  /** Whether `tail` is only the end of the answer; page with `read_transcript_chunk` */
+  truncated: boolean;
+}
+
+/** A page of a transcript, from `read_transcript_chunk` */
+export interface TranscriptChunk {
+  transcript_id: string;
+  offset: number;
+  text: string;
+  next_offset: number;
+  eof: boolean;
+}
+
+export function isPromptRejection(error: unknown): error is PromptRejection {
+  return typeof error === "object" && error !== null && "kind" in error && "agent_id" in error;
+}
+
 export interface AgentInfo {
   id: string;
   name: string;
@@ -27,6 +64,85 @@ export interface AgentInfo {
   provider_name?: string | null;
   auth_methods?: AuthMethod[];
   needs_auth?: boolean;
+  available_commands?: string[];
+  modes?: SessionModeState | null;
+  models?: SessionModelState | null;
+  /** Unix seconds */
+  created_at?: number;
+  last_prompt_at?: number | null;
+  last_update_at?: number | null;
+  /** Where the answer to the latest finished prompt was written */
+  last_transcript?: TranscriptInfo | null;
+  /** Lines of files the latest tool call points at, for highlighting */
+  touched_ranges?: TouchedRange[];
+}
+
+/** Fields of an agent that changed; absent fields did not change */
+export interface AgentStateChange {
+  agent_id: string;
+  status?: AgentStatus;
+  current_file?: string | null;
+  progress?: number;
+  tokens_used?: number;
+  pending_inputs?: PendingInput[];
+  session_id?: string | null;
+  needs_auth?: boolean;
+  available_commands?: string[];
+  modes?: SessionModeState | null;
+  models?: SessionModelState | null;
+  last_prompt_at?: number | null;
+  last_update_at?: number | null;
+}
+
+/** Modes a session can run in, as the agent reports them */
+export interface SessionModeState {
+  currentModeId: string;
+  availableModes: { id: string; name: string; description?: string }[];
+}
+
+/** Models a session can use, as the agent reports them */
+export interface SessionModelState {
+  currentModelId: string;
+  availableModels: { modelId: string; name: string; description?: string }[];
+}
+
+/** A permission request waiting for an answer */
+export interface PendingPermissionInfo {
+  agent_id: string;
+  input_id: string;
+  message: string;
+  seq: number;
+  high_risk: boolean;
+  outside_paths: string[];
+  risk: PermissionRisk | null;
+  /** Set once similar requests repeat; answer with ALLOW_SIMILAR_OPTION_ID to allow them all */
+  similar_scope: string | null;
+  created_at: number;
+  reminders: number;
+  last_reminded_at: number | null;
+}
+
+/** A permission request that waited long enough to be escalated */
+export interface PermissionEscalation {
+  agent_id: string;
+  input_id: string;
+  message: string;
+  kind: "reminder" | "default_applied";
+  age_secs: number;
+  reminders: number;
+  approved: boolean | null;
+}
+
+/** Synthesized option approving similar requests for the rest of the prompt */
+export const ALLOW_SIMILAR_OPTION_ID = "acptorio_allow_similar";
+
+export type RiskTier = "low" | "medium" | "high" | "critical";
+
+/** Heuristic risk of a permission request */
+export interface PermissionRisk {
+  score: number;
+  tier: RiskTier;
+  reasons: string[];
 }
 
 export type PendingInputType = "tool_permission" | "user_question" | "confirmation";
@@ -37,6 +153,16 @@ export interface PendingInput {
   tool_name: string | null;
   message: string;
   timestamp: number;
+  /** Reaches outside every registered project; approving needs confirmHighRisk */
+  high_risk: boolean;
+  outside_paths: string[];
+  risk: PermissionRisk | null;
+  /** Set once similar requests repeat; answer with ALLOW_SIMILAR_OPTION_ID to allow them all */
+  similar_scope: string | null;
+  /** Tool call the input is about; one entry per tool call is kept */
+  tool_call_id: string | null;
+  /** `message` as a key for translation; null for text from the agent */
+  localized: LocalizedMessage | null;
 }
 
 export interface AgentUpdate {
@@ -48,9 +174,269 @@ export interface AgentUpdate {
   current_file: string | null;
   status: AgentStatus | null;
   pending_inputs: PendingInput[] | null;
+  touched_files: TouchedRange[];
+}
+
+/** Lines as numbered by the agent; without lines the whole file is meant */
+export interface TouchedRange {
+  path: string;
+  start_line: number | null;
+  end_line: number | null;
 }
 
 export interface ToolUpdate {
   name: string;
   input: Record<string, unknown> | null;
 }
+
+/** A tool call not finished yet; running_ms drives its timer */
+export interface RunningToolCall {
+  agent_id: string;
+  tool_call_id: string;
+  title: string | null;
+  kind: string | null;
+  status: string | null;
+  started_at: number;
+  running_ms: number;
+}
+
+/** Narrows a history query; unset fields match everything */
+export interface HistoryFilter {
+  agent_id?: string;
+  /** Working directory of the agent */
+  project?: string;
+  /** Task status, audit kind or activity type */
+  kind?: string;
+  /** Seconds, inclusive */
+  since?: number;
+  /** Seconds, exclusive */
+  until?: number;
+  text?: string;
+  limit?: number;
+  offset?: number;
+}
+
+export type AuditKind =
+  | "agent_spawned"
+  | "agent_stopped"
+  | "permission_answered"
+  | "prompt_cancelled"
+  | "tool_call_cancelled";
+
+export interface AuditEntry {
+  id: number;
+  at: number;
+  kind: AuditKind;
+  agent_id: string | null;
+  project: string | null;
+  message: string;
+}
+
+export interface ActivityEntry {
+  id: number;
+  at: number;
+  agent_id: string;
+  project: string | null;
+  kind: string;
+  message: string | null;
+  path: string | null;
+}
+
+export interface WorkSpanRecord {
+  agent_id: string;
+  agent_name: string;
+  project: string;
+  started_at: number;
+  ended_at: number;
+}
+
+export interface MetricsHistory {
+  usage: { tokens: number; cost_dollars: number };
+  work: WorkSpanRecord[];
+}
+
+export interface FileLock {
+  path: string;
+  agent_id: string;
+  tool_call_id: string;
+  acquired_at: number;
+}
+
+/** An agent touching a file another agent is editing */
+export interface FileConflict {
+  path: string;
+  agent_id: string;
+  tool_call_id: string;
+  holder: FileLock;
+  /** The agent's permission request waits for the holder to finish */
+  held: boolean;
+  timestamp: number;
+}
+
+/** Tasks of two agents that ran at the same time and touched the same files */
+export interface TaskConflict {
+  first_task_id: string;
+  first_agent_id: string;
+  second_task_id: string;
+  second_agent_id: string;
+  paths: string[];
+}
+
+/** A prompt that was running when the app crashed, from `get_interrupted_tasks` */
+export interface InterruptedTask {
+  id: string;
+  /** Id of the agent before the restart */
+  agent_id: string;
+  agent_name: string;
+  provider_id: string | null;
+  working_directory: string;
+  prompt: string;
+  started_at: number;
+  /** Running agent with the same name and working directory */
+  matching_agent_id: string | null;
+}
+
+/** An agent that was running when the app quit, from `get_resumable_sessions` */
+export interface SavedSession {
+  id: string;
+  agent_name: string;
+  provider_id: string | null;
+  working_directory: string;
+  session_id: string | null;
+  /** What the agent was asked and answered lately */
+  summary: string;
+  saved_at: number;
+}
+
+export type ResumeMethod = "session_load" | "seed_prompt" | "fresh_session";
+
+export interface ResumedSession {
+  agent: AgentInfo;
+  method: ResumeMethod;
+  /** Task of the prompt seeding a fresh session with the saved summary */
+  seed_task_id: string | null;
+}
+
+export interface PromptDraft {
+  text: string;
+  updated_at: number;
+}
+
+export type AlertKind =
+  | "agent_error"
+  | "budget_exceeded"
+  | "verification_failed"
+  | "stale_permission"
+  | "process_crashed"
+  | "placement_suggestion";
+
+export type AlertSeverity = "info" | "warning" | "critical";
+
+/** Something on the factory floor that needs attention */
+export interface Alert {
+  id: number;
+  kind: AlertKind;
+  severity: AlertSeverity;
+  agent_id: string | null;
+  /** Task or project id; null for factory-wide alerts */
+  entity_id: string | null;
+  /** English rendering of `localized` */
+  message: string;
+  localized: LocalizedMessage;
+  created_at: number;
+  updated_at: number;
+  /** Times it was raised while still open */
+  count: number;
+  acknowledged: boolean;
+}
+
+export type LeaderboardRange = "hour" | "day" | "week" | "all";
+
+export interface LeaderboardEntry {
+  /** Provider id, or the agent id of the placement */
+  key: string;
+  label: string;
+  tasks: number;
+  avg_prompt_secs: number | null;
+  success_rate: number | null;
+  verification_pass_rate: number | null;
+  tokens_per_completed_task: number | null;
+}
+
+export interface Leaderboard {
+  range: LeaderboardRange;
+  providers: LeaderboardEntry[];
+  placements: LeaderboardEntry[];
+}
+
+/** A project edited a lot by hand but not by agents lately */
+export interface PlacementSuggestion {
+  project_id: string;
+  project_name: string;
+  manual_edits: number;
+  last_manual_edit: number;
+  /** Share of the project's files agents explored, if its size is known */
+  explored_percent: number | null;
+  message: string;
+  localized: LocalizedMessage;
+}
+
+export type ReportFormat = "markdown" | "html";
+
+/** AGENTS.md, CLAUDE.md or .cursorrules file of a project */
+export interface InstructionFile {
+  /** Relative to the project root */
+  path: string;
+  content: string;
+  truncated: boolean;
+}
+
+export type MessageKey =
+  | "agent_wants_to"
+  | "permission_requested"
+  | "agent_needs_permission"
+  | "task_failed"
+  | "task_failed_with_error"
+  | "agent_process_failed"
+  | "still_waiting"
+  | "killed_for_memory"
+  | "budget_exceeded"
+  | "verification_failed"
+  | "placement_suggested"
+  | "placement_suggested_explored";
+
+/** A backend message as a key with named parameters, for translation */
+export interface LocalizedMessage {
+  key: MessageKey;
+  params: Record<string, string>;
+}
+
+/** How a running agent was started, from `get_agent_environment` */
+export interface LaunchInfo {
+  command: string;
+  args: string[];
+  /** Null over SSH */
+  working_directory: string | null;
+  pid: number | null;
+  /** Variables set from the keychain */
+  keychain_variables: string[];
+}
+
+/** What the agent answered to `initialize` */
+export interface NegotiatedProtocol {
+  protocol_version: unknown;
+  agent_info: Record<string, unknown> | null;
+  agent_capabilities: Record<string, unknown> | null;
+}
+
+export interface AgentEnvironment {
+  agent_id: string;
+  name: string;
+  provider_id: string | null;
+  transport: string | null;
+  /** Null for agents the app didn't start */
+  launch: LaunchInfo | null;
+  protocol: NegotiatedProtocol | null;
+  /** Redacted; empty for agents on other machines or in a container */
+  environment: Record<string, string>;
+}
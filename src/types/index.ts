@@ -2,3 +2,5 @@ export * from "./agent";
 export * from "./project";
 export * from "./acp";
 export * from "./registry";
+export * from "./snapshot";
+export * from "./onboarding";
@@ -0,0 +1,49 @@
+export type OnboardingStep =
+  | "check_environment"
+  | "fetch_registry"
+  | "pick_provider"
+  | "store_api_key"
+  | "open_sample_project";
+
+export interface ToolCheck {
+  name: string;
+  /** First line of `<tool> --version` */
+  version: string | null;
+  error: string | null;
+}
+
+export interface EnvironmentReport {
+  app_version: string;
+  os: string;
+  arch: string;
+  data_dir: string | null;
+  tools: ToolCheck[];
+}
+
+export interface OnboardingStepStatus {
+  step: OnboardingStep;
+  state: "pending" | "done" | "skipped";
+  can_skip: boolean;
+}
+
+/** Result of `get_onboarding_status` and `complete_onboarding_step` */
+export interface OnboardingStatus {
+  steps: OnboardingStepStatus[];
+  next_step: OnboardingStep | null;
+  finished: boolean;
+  /** Last environment check since the app started */
+  environment: EnvironmentReport | null;
+  provider_id: string | null;
+  api_key_variable: string | null;
+  project_path: string | null;
+}
+
+/** What `complete_onboarding_step` needs for a step */
+export interface OnboardingInput {
+  provider_id?: string;
+  api_key?: string;
+  /** Environment variable the key is for; known for the usual providers */
+  variable?: string;
+  /** Project to open; the bundled sample project if omitted */
+  project_path?: string;
+}
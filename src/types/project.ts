@@ -13,12 +13,193 @@ export interface ProjectTree {
   total_dirs: number;
 }
 
+export type FogVisibility = "hidden" | "dim" | "explored";
+
 export interface FogState {
+  // Pass to get_fog_delta for the changes after this state
+  seq: number;
   explored_paths: string[];
   total_explored: number;
+  dim_paths: string[];
+}
+
+export interface FogDelta {
+  seq: number;
+  // The fog was reset; revealed then holds every explored path
+  reset: boolean;
+  revealed: string[];
+  removed: string[];
+}
+
+export interface DirectoryFogStats {
+  // Relative to the project root; "." for files directly in it
+  directory: string;
+  total_files: number;
+  explored_files: number;
+}
+
+export interface FogStatistics {
+  total_files: number;
+  explored_files: number;
+  percent_explored: number;
+  // Explored paths no longer in the project tree; prune_fog drops them
+  stale_paths: number;
+  directories: DirectoryFogStats[];
+}
+
+export interface ExplorationMilestone {
+  id: string;
+  title: string;
+  reached_at: number | null;
+}
+
+export interface HeatEntry {
+  path: string;
+  count: number;
+  first_touched: number;
+  last_touched: number;
+}
+
+export interface WatchedPath {
+  path: string;
+  // Watched as the open project
+  project: boolean;
+  // Added through watch_path; stays watched when the project changes
+  explicit: boolean;
+  since: number;
+}
+
+export interface WatcherStatus {
+  running: boolean;
+  watched: WatchedPath[];
+  events: number;
+  last_event_at: number | null;
+  last_error: string | null;
+}
+
+export interface FoundFile {
+  path: string;
+  relative_path: string;
+  project_root: string;
+  score: number;
+  positions: number[];
+}
+
+export interface FileAttribution {
+  path: string;
+  agent_id: string;
+  match: "file" | "directory";
+  age_ms: number;
+}
+
+export interface TreeDelta {
+  root: string;
+  added: string[];
+  removed: string[];
 }
 
 export interface FileEvent {
   kind: "create" | "modify" | "remove" | "rename" | "other";
   paths: string[];
+  agent_id: string | null;
+  attributions: FileAttribution[];
+}
+
+// Files revealed since the previous batch; seq counts up from 1 without gaps
+export interface FogRevealBatch {
+  seq: number;
+  paths: string[];
+}
+
+export type ProjectCommandKind = "build" | "test" | "lint";
+
+export interface ProjectCommandOutput {
+  run_id: string;
+  stream: "stdout" | "stderr";
+  line: string;
 }
+
+export interface ProjectCommandRun {
+  run_id: string;
+  project_id: string;
+  kind: ProjectCommandKind;
+  command: string;
+  success: boolean;
+  exit_code: number | null;
+  output_tail: string;
+  started_at: number;
+  finished_at: number;
+  after_task: string | null;
+  agent_id: string | null;
+}
+
+/** Result of `get_storage_status`, and payload of `storage-degraded` */
+export interface StorageStatus {
+  data_dir: string;
+  /** How the directory was chosen; `environment` is ACPTORIO_DATA_DIR */
+  source: "default" | "environment" | "portable" | "moved";
+  /** False if layout, pins and provider overrides only live in memory */
+  writable: boolean;
+  error: string | null;
+  /** Where downloaded binaries and icons go */
+  cache_dir: string;
+}
+
+/** Result of `migrate_data_dir`; the app restarts in the new directory */
+export interface DataDirMove {
+  from: string;
+  to: string;
+  files: number;
+  bytes: number;
+}
+
+/** Counts kept on this machine only, from `get_usage_stats` */
+export interface UsageStats {
+  /** When counting started, in seconds */
+  since: number;
+  /** Command name -> times invoked */
+  features: Record<string, number>;
+  /** Provider id -> agents spawned */
+  spawns: Record<string, number>;
+  /** Error kind -> times it happened */
+  errors: Record<string, number>;
+}
+
+/** A plugin registered at startup, from `list_plugins` */
+export interface PluginInfo {
+  name: string;
+  /** Commands it answers through `call_plugin_command` */
+  commands: string[];
+}
+
+/** One stage of `run_self_test` */
+export interface SelfTestStage {
+  /** spawn, initialize, session, prompt, tool_call, permission, cancel or stop */
+  name: string;
+  /** `skipped` after an earlier stage failed */
+  outcome: "passed" | "failed" | "skipped";
+  duration_ms: number;
+  error: string | null;
+}
+
+export interface SelfTestReport {
+  passed: boolean;
+  stages: SelfTestStage[];
+  duration_ms: number;
+}
+
+export type DeepLinkAction =
+  | { action: "open"; path: string }
+  | { action: "spawn"; working_directory: string; name: string | null; provider_id: string | null }
+  | { action: "prompt"; agent_id: string; prompt: string };
+
+/** A spawn or prompt link waiting for `confirm_deep_link` or `reject_deep_link` */
+export interface PendingDeepLink {
+  id: string;
+  url: string;
+  action: DeepLinkAction;
+}
+
+export type DeepLinkOutcome =
+  | { status: "handled"; action: DeepLinkAction }
+  | { status: "pending"; link: PendingDeepLink };
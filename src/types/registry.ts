@@ -10,6 +10,7 @@ export interface BinaryPlatform {
   archive: string;
   cmd: string;
   args?: string[];
+  env?: Record<string, string>;
 }
 
 /** Distribution method for an agent */
@@ -26,6 +27,35 @@ export interface RegistryAgent {
   description: string;
   icon?: string;
   distribution: Distribution;
+  /** Model selected once a session is open, from a local override */
+  default_model?: string | null;
+  /** Auth method offered first, from a local override */
+  auth_method?: string | null;
+}
+
+/** Local changes to how a provider is run, set with `set_provider_overrides` */
+export interface ProviderOverrides {
+  /** Appended to the distribution's arguments */
+  args?: string[];
+  /** Set on top of the distribution's environment */
+  env?: Record<string, string>;
+  default_model?: string | null;
+  auth_method?: string | null;
+}
+
+/** Freshness of the registry and its icons, from `get_registry_sync_status` */
+export interface RegistrySyncStatus {
+  /** Unix seconds of the last successful fetch since the app started */
+  last_fetch: number | null;
+  cache_age_secs: number | null;
+  stale: boolean;
+  agent_count: number;
+  icons_cached: number;
+  /** Agent ids whose icon is not cached */
+  icons_missing: string[];
+  fetch_error: string | null;
+  /** Agent id -> why its icon could not be downloaded */
+  icon_errors: Record<string, string>;
 }
 
 /** Brand colors for each provider */
@@ -41,6 +71,7 @@ export const PROVIDER_COLORS: Record<
   auggie: { main: "#8B5CF6", light: "#A78BFA", dark: "#7C3AED" },
   "qwen-code": { main: "#6366F1", light: "#818CF8", dark: "#4F46E5" },
   opencode: { main: "#06B6D4", light: "#22D3EE", dark: "#0891B2" },
+  demo: { main: "#84CC16", light: "#A3E635", dark: "#65A30D" },
 };
 
 /** Get color for a provider, with fallback */
@@ -0,0 +1,90 @@
+import type { FactoryLayout } from "../stores/factoryStore";
+import type { Metrics } from "./acp";
+import type { AgentInfo, Alert, PermissionRisk } from "./agent";
+import type { FogState } from "./project";
+import type { RegistryAgent } from "./registry";
+
+/** A permission request waiting for an answer, with the options the agent offered */
+export interface PendingPermission {
+  agent_id: string;
+  input_id: string;
+  message: string;
+  seq: number;
+  high_risk: boolean;
+  outside_paths: string[];
+  risk: PermissionRisk | null;
+  similar_scope: string | null;
+  created_at: number;
+  reminders: number;
+  last_reminded_at: number | null;
+  request: {
+    sessionId: string;
+    toolCall: { toolCallId: string; title?: string | null };
+    options: { optionId: string; name: string; kind: string }[];
+  };
+}
+
+/** Result of `get_full_state`, for restoring the UI after a reload */
+export interface FullState {
+  /** Last event reflected; replay newer ones with `get_recent_events` */
+  seq: number;
+  agents: AgentInfo[];
+  layout: FactoryLayout;
+  fog: FogState;
+  metrics: Metrics;
+  pending_permissions: PendingPermission[];
+  registry: RegistryAgent[];
+  alerts: Alert[];
+}
+
+/** An emitted event as kept in the backend's replay buffer */
+export interface RecordedEvent {
+  seq: number;
+  event: string;
+  payload: unknown;
+  timestamp: number;
+  /** Agent the event is about, for events only some windows get */
+  scope?: { agent_id: string; update: boolean };
+}
+
+/** Result of `get_recent_events` */
+export interface EventReplay {
+  events: RecordedEvent[];
+  last_seq: number;
+  /** False if missed events were already dropped; reload with `get_full_state` */
+  complete: boolean;
+}
+
+export interface AwayTask {
+  id: string;
+  agent_id: string;
+  prompt: string;
+  finished_at: number;
+  error: string | null;
+  files_changed: number;
+}
+
+export interface FileChangeTally {
+  path: string;
+  changes: number;
+  agent_ids: string[];
+  last_changed_at: number;
+}
+
+/** Result of `get_away_summary`; times in seconds */
+export interface AwaySummary {
+  since: number;
+  completed_tasks: AwayTask[];
+  failed_tasks: AwayTask[];
+  /** Waiting now, including requests from before `since` */
+  waiting_permissions: Omit<PendingPermission, "request">[];
+  budget: {
+    used: { tokens: number; cost_dollars: number };
+    total_tokens: number;
+    budget: number | null;
+    remaining: number | null;
+  };
+  file_changes: FileChangeTally[];
+  /** False if changes made after `since` were already dropped from the event log */
+  file_changes_complete: boolean;
+}